@@ -26,41 +26,397 @@
 //! // identifier                      := /[a-f0-9]{64}/
 //! // short-identifier                := /[a-f0-9]{6,64}/
 //! ```
+//!
+//! Like newer versions of the grammar, a `<domain>` can also be an IPv6 address in brackets, `[` /[a-fA-F0-9:]+/ `]`,
+//! such as `[::1]:5000/foo`.
+//!
+//! The `<digest-algorithm-separator>` is one of `-`, `_`, `+` and `.`, which the character class above means to list,
+//! and like in Go, `\w` only matches ASCII word characters.
+//!
+//! Without the default `std` feature, the crate only needs `alloc`.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
-use std::{borrow::Cow, ops::Range, str::FromStr, sync::LazyLock};
+extern crate alloc;
+
+use alloc::{
+    borrow::{Cow, ToOwned},
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{ops::Range, str::FromStr};
 
 #[cfg(feature = "serde")]
 use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
-use regex::Regex;
 
+/// Why a container image name is invalid. The offsets are in bytes from the start of the name and point at the
+/// section or path component that is invalid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InvalidContainerImageNameReason {
+    /// The `<domain>` section does not match the grammar, or is a single component without a port.
+    InvalidDomain { offset: usize },
+    /// The `<port>` section is empty or not a number, or a port given to [`ImageNameBuilder::with_domain_and_port`] is
+    /// not in `1..=65535`.
+    InvalidPort { offset: usize },
+    /// A component of the `<path>` section is empty or does not match the grammar.
+    InvalidPathComponent { offset: usize },
+    /// The `<tag>` section does not match the grammar.
+    InvalidTag { offset: usize },
+    /// The `<algorithm>` section of the digest does not match the grammar.
+    InvalidDigestAlgorithm { offset: usize },
+    /// The `<hex>` section of the digest contains a character that is not hexadecimal.
+    InvalidDigestHex { offset: usize },
+    /// The `<hex>` section of the digest has fewer than the [`MIN_DIGEST_HEX_LENGTH`] characters of the grammar.
+    DigestHexTooShort { offset: usize, found: usize },
+    /// The `<hex>` section does not have the length that the `<algorithm>` section requires.
+    DigestLength {
+        offset: usize,
+        algorithm: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    /// The `<registry>` and `<path>` sections together are longer than [`NAME_MAX_LENGTH`]. The offset is that of the
+    /// first byte over the limit.
+    NameTooLong { offset: usize, length: usize },
+    /// The image name is longer than the limit it was parsed with. The offset is that of the first byte over the
+    /// limit.
+    ReferenceTooLong { offset: usize, length: usize },
+}
+
+type Reason = InvalidContainerImageNameReason;
+
+impl InvalidContainerImageNameReason {
+    pub fn offset(&self) -> usize {
+        match *self {
+            Reason::InvalidDomain { offset }
+            | Reason::InvalidPort { offset }
+            | Reason::InvalidPathComponent { offset }
+            | Reason::InvalidTag { offset }
+            | Reason::InvalidDigestAlgorithm { offset }
+            | Reason::InvalidDigestHex { offset }
+            | Reason::DigestHexTooShort { offset, .. }
+            | Reason::DigestLength { offset, .. }
+            | Reason::NameTooLong { offset, .. }
+            | Reason::ReferenceTooLong { offset, .. } => offset,
+        }
+    }
+
+    /// Describes the reason without its offset, for the errors of [`image_name!`], which can not format numbers.
+    pub const fn description(&self) -> &'static str {
+        match *self {
+            Reason::InvalidDomain { .. } => "invalid domain",
+            Reason::InvalidPort { .. } => "invalid port",
+            Reason::InvalidPathComponent { .. } => "invalid path component",
+            Reason::InvalidTag { .. } => "invalid tag",
+            Reason::InvalidDigestAlgorithm { .. } => "invalid digest algorithm",
+            Reason::InvalidDigestHex { .. } => "invalid digest hex",
+            Reason::DigestHexTooShort { .. } => "the digest hex is too short",
+            Reason::DigestLength { .. } => {
+                "the digest hex does not have the length of its algorithm"
+            }
+            Reason::NameTooLong { .. } => "the name is too long",
+            Reason::ReferenceTooLong { .. } => "the image name is too long",
+        }
+    }
+
+    /// Moves the offset by `by`, for a section that was checked on its own.
+    fn offset_by(mut self, by: usize) -> Self {
+        match &mut self {
+            Reason::InvalidDomain { offset }
+            | Reason::InvalidPort { offset }
+            | Reason::InvalidPathComponent { offset }
+            | Reason::InvalidTag { offset }
+            | Reason::InvalidDigestAlgorithm { offset }
+            | Reason::InvalidDigestHex { offset }
+            | Reason::DigestHexTooShort { offset, .. }
+            | Reason::DigestLength { offset, .. }
+            | Reason::NameTooLong { offset, .. }
+            | Reason::ReferenceTooLong { offset, .. } => *offset += by,
+        }
+        self
+    }
+}
+
+impl core::fmt::Display for InvalidContainerImageNameReason {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            Reason::InvalidDomain { offset } => write!(f, "invalid domain at byte {offset}"),
+            Reason::InvalidPort { offset } => write!(f, "invalid port at byte {offset}"),
+            Reason::InvalidPathComponent { offset } => {
+                write!(f, "invalid path component at byte {offset}")
+            }
+            Reason::InvalidTag { offset } => write!(f, "invalid tag at byte {offset}"),
+            Reason::InvalidDigestAlgorithm { offset } => {
+                write!(f, "invalid digest algorithm at byte {offset}")
+            }
+            Reason::InvalidDigestHex { offset } => {
+                write!(f, "invalid digest hex at byte {offset}")
+            }
+            Reason::DigestHexTooShort { offset, found } => write!(
+                f,
+                "the digest hex at byte {offset} has {found} characters but at least {MIN_DIGEST_HEX_LENGTH} are required"
+            ),
+            Reason::DigestLength {
+                offset,
+                algorithm,
+                expected,
+                found,
+            } => write!(
+                f,
+                "the digest hex at byte {offset} has {found} characters but a {algorithm} digest has {expected}"
+            ),
+            Reason::NameTooLong { offset, length } => write!(
+                f,
+                "the name is too long at byte {offset}, it has {length} bytes but at most {NAME_MAX_LENGTH} are allowed"
+            ),
+            Reason::ReferenceTooLong { offset, length } => write!(
+                f,
+                "the image name is too long at byte {offset}, it has {length} bytes"
+            ),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct InvalidContainerImageNameMarker {
+    reason: Reason,
+}
+
+impl InvalidContainerImageNameMarker {
+    pub const fn reason(&self) -> InvalidContainerImageNameReason {
+        self.reason
+    }
+}
+
+impl core::error::Error for InvalidContainerImageNameMarker {}
+
+impl core::fmt::Display for InvalidContainerImageNameMarker {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "invalid container image name: {}", self.reason)
+    }
+}
+
+impl From<Reason> for InvalidContainerImageNameMarker {
+    fn from(reason: Reason) -> Self {
+        Self { reason }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct InvalidContainerImageName {
+    value: String,
+    reason: Reason,
+}
+
+impl InvalidContainerImageName {
+    /// Returns the name that failed to parse.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn reason(&self) -> InvalidContainerImageNameReason {
+        self.reason
+    }
+}
+
+impl core::error::Error for InvalidContainerImageName {}
+
+impl core::fmt::Display for InvalidContainerImageName {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "invalid container image name: {:?}: {}",
+            self.value, self.reason
+        )
+    }
+}
+
+/// A section of an image name as set on an [`ImageNameBuilder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Section {
+    /// The `<registry>` section, which includes the `<port>` section.
+    Registry,
+    Path,
+    Tag,
+    /// The `<digest>` section, which includes the `<algorithm>` and `<hex>` sections.
+    Digest,
+}
+
+impl Section {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Section::Registry => "registry",
+            Section::Path => "path",
+            Section::Tag => "tag",
+            Section::Digest => "digest",
+        }
+    }
+}
+
+impl core::fmt::Display for Section {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An [`ImageNameBuilder`] failed to build because of the value of one of its sections.
 #[derive(Debug, PartialEq, Eq)]
-pub struct InvalidContainerImageNameMarker;
+pub struct InvalidImageNameSection {
+    section: Section,
+    /// The combined name, which the section is a range of.
+    name: String,
+    range: Range<usize>,
+    /// Relative to the start of the section.
+    reason: Reason,
+}
 
-impl std::error::Error for InvalidContainerImageNameMarker {}
+impl InvalidImageNameSection {
+    /// Returns the section that is invalid.
+    pub fn section(&self) -> Section {
+        self.section
+    }
+
+    /// Returns the value of the section that is invalid, without the separator that precedes it in the name.
+    pub fn value(&self) -> &str {
+        &self.name[self.range.clone()]
+    }
+
+    /// Returns the reason, with the offset relative to the start of [`value`](Self::value).
+    pub fn reason(&self) -> InvalidContainerImageNameReason {
+        self.reason
+    }
+}
+
+impl core::error::Error for InvalidImageNameSection {}
+
+impl core::fmt::Display for InvalidImageNameSection {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "invalid {} {:?} of container image name: {}",
+            self.section,
+            self.value(),
+            self.reason
+        )
+    }
+}
 
-impl std::fmt::Display for InvalidContainerImageNameMarker {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str("invalid container image name")
+impl From<InvalidImageNameSection> for InvalidContainerImageName {
+    fn from(error: InvalidImageNameSection) -> Self {
+        InvalidContainerImageName {
+            reason: error.reason.offset_by(error.range.start),
+            value: error.name,
+        }
     }
 }
 
+/// An image name lacks the section that an operation needs, such as [`ImageName::to_canonical`] or
+/// [`ImageName::expect_tag`].
 #[derive(Debug, PartialEq, Eq)]
-pub struct InvalidContainerImageName(String);
+pub struct MissingSection {
+    value: String,
+    section: Section,
+}
+
+impl MissingSection {
+    /// Returns the name that lacks the section.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn section(&self) -> Section {
+        self.section
+    }
+}
 
-impl std::error::Error for InvalidContainerImageName {}
+impl core::error::Error for MissingSection {}
 
-impl std::fmt::Display for InvalidContainerImageName {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "invalid container image name: {:?}", self.0)
+impl core::fmt::Display for MissingSection {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "container image name {:?} has no {} section",
+            self.value, self.section
+        )
     }
 }
 
 const REGISTRY_SUFFIX: char = '/';
 const PORT_PREFIX: char = ':';
+const IPV6_START: char = '[';
+const IPV6_END: char = ']';
+const PATH_COMPONENT_SEPARATOR: char = '/';
 const TAG_PREFIX: char = ':';
 const DIGEST_ALGORITHM_PREFIX: char = '@';
 const DIGEST_HEX_PREFIX: char = ':';
 
+/// The number of `<hex>` characters of the digest algorithms registered by the
+/// [OCI image spec](https://github.com/opencontainers/image-spec/blob/v1.1.0/descriptor.md#registered-algorithms).
+/// Other algorithms only need the 32 characters that the grammar requires.
+const DIGEST_HEX_LENGTHS: [(&str, usize); 2] = [("sha256", 64), ("sha512", 128)];
+
+/// The number of `<hex>` characters that the grammar requires, 128 bits.
+const MIN_DIGEST_HEX_LENGTH: usize = 32;
+
+/// Checks the length of the `<registry>` and `<path>` sections, which end at `name_end`.
+const fn check_name_length(name_end: usize) -> Result<(), Reason> {
+    if name_end > NAME_MAX_LENGTH {
+        return Err(Reason::NameTooLong {
+            offset: NAME_MAX_LENGTH,
+            length: name_end,
+        });
+    }
+    Ok(())
+}
+
+/// Whether two `<registry>` sections refer to the same registry, comparing the `<domain>` sections case-insensitively
+/// like DNS does and the `<port>` sections exactly. See [`ImageNameRef::eq_canonical`].
+pub fn registry_eq_canonical(a: &str, b: &str) -> bool {
+    let ((a_domain, a_port), (b_domain, b_port)) = (split_registry(a), split_registry(b));
+    a_domain.eq_ignore_ascii_case(b_domain) && a_port == b_port
+}
+
+/// Splits a `<registry>` section into its `<domain>` and `<port>` sections. The `<domain>` section can only contain the
+/// port prefix between the brackets of an IPv6 address.
+fn split_registry(registry: &str) -> (&str, Option<&str>) {
+    let domain_end = match registry.starts_with(IPV6_START) {
+        true => registry
+            .find(IPV6_END)
+            .map_or(registry.len(), |index| index + IPV6_END.len_utf8()),
+        false => 0,
+    };
+    match registry[domain_end..].find(PORT_PREFIX) {
+        Some(index) => (
+            &registry[..domain_end + index],
+            Some(&registry[domain_end + index + PORT_PREFIX.len_utf8()..]),
+        ),
+        None => (registry, None),
+    }
+}
+
+/// The maximum length of the `<registry>` and `<path>` sections together, including the separator, like
+/// `NameTotalLengthMax` in [reference.go](https://github.com/distribution/distribution/blob/v2.7.1/reference/reference.go).
+pub const NAME_MAX_LENGTH: usize = 255;
+
+/// The maximum length of a whole image name that [`ImageName::new`] and [`ImageNameRef::new`] accept. A name of
+/// [`NAME_MAX_LENGTH`] with the longest tag and a sha512 digest fits. Use [`ImageNameRef::new_with_max_length`] for
+/// another limit.
+pub const DEFAULT_MAX_LENGTH: usize = 1024;
+
+/// The registry that Docker assumes when an image name has none.
+const DEFAULT_DOMAIN: &str = "docker.io";
+/// An alias of [`DEFAULT_DOMAIN`] that Docker still accepts.
+const LEGACY_DEFAULT_DOMAIN: &str = "index.docker.io";
+/// The namespace of the official images on [`DEFAULT_DOMAIN`], which Docker assumes for single-component paths.
+const OFFICIAL_REPOSITORY_NAMESPACE: &str = "library";
+/// The tag that Docker assumes when an image name has neither a tag nor a digest.
+const DEFAULT_TAG: &str = "latest";
+/// The number of `<hex>` characters that [`ImageNameRef::familiar_name`], [`ImageNameRef::short_digest`] and the
+/// alternate `{:#}` formatting keep when truncating the digest, like the short image IDs of Docker.
+const SHORT_DIGEST_HEX_LENGTH: usize = 12;
+
 #[derive(Copy, Clone)]
 struct IndicesRegistry {
     // NOTE: domain_start is implicitly 0.
@@ -138,6 +494,17 @@ impl Indices {
         &buffer[self.path_range(buffer.len())]
     }
 
+    #[inline]
+    fn repository_range(&self, buffer_len: usize) -> Range<usize> {
+        // NOTE: The registry, when present, starts at 0 and is directly followed by the path.
+        0..self.path_range(buffer_len).end
+    }
+
+    #[inline]
+    fn repository<'a>(&self, buffer: &'a str) -> &'a str {
+        &buffer[self.repository_range(buffer.len())]
+    }
+
     #[inline]
     fn tag_range(&self, buffer_len: usize) -> Option<Range<usize>> {
         self.tag_start.map(|tag_start| {
@@ -196,586 +563,3929 @@ impl Indices {
     }
 }
 
+// NOTE: The patterns of the sections are macros so that `concat!` can combine them into the pattern of the JSON schema
+// and into the regex that the tests compare the parser with.
+#[cfg(any(feature = "schemars", test))]
+macro_rules! domain_component_pattern {
+    () => {
+        r"[a-zA-Z0-9](?:[a-zA-Z0-9-]*[a-zA-Z0-9])?"
+    };
+}
+
+#[cfg(any(feature = "schemars", test))]
+macro_rules! ipv6_pattern {
+    () => {
+        r"\[[a-fA-F0-9:]+\]"
+    };
+}
+
+#[cfg(any(feature = "schemars", test))]
+macro_rules! port_pattern {
+    () => {
+        r"[0-9]+"
+    };
+}
+
+// NOTE: Like `splitDockerDomain` of docker, the first component of a name is only a registry when it contains a dot or a
+// port, is `localhost` or an IPv6 address. Otherwise `myorg/foo` would be an image on the registry `myorg`.
+#[cfg(any(feature = "schemars", test))]
+macro_rules! registry_pattern {
+    () => {
+        concat!(
+            r"(?:localhost|",
+            ipv6_pattern!(),
+            r"|",
+            domain_component_pattern!(),
+            r"(?:\.",
+            domain_component_pattern!(),
+            r")+)(?::",
+            port_pattern!(),
+            r")?|",
+            domain_component_pattern!(),
+            r":",
+            port_pattern!()
+        )
+    };
+}
+
+#[cfg(any(feature = "schemars", test))]
+macro_rules! path_component_pattern {
+    () => {
+        r"[a-z0-9]+(?:[_.]|__|[-]*[a-z0-9]+)*"
+    };
+}
+
+#[cfg(any(feature = "schemars", test))]
+macro_rules! tag_pattern {
+    () => {
+        r"[a-zA-Z0-9_][a-zA-Z0-9_.-]{0,127}"
+    };
+}
+
+#[cfg(any(feature = "schemars", test))]
+macro_rules! algorithm_pattern {
+    () => {
+        r"[A-Za-z][A-Za-z0-9]*(?:[-_+.][A-Za-z][A-Za-z0-9]*)*"
+    };
+}
+
+#[cfg(any(feature = "schemars", test))]
+macro_rules! hex_pattern {
+    () => {
+        r"[0-9a-fA-F]{32,}"
+    };
+}
+
 impl FromStr for Indices {
     type Err = InvalidContainerImageNameMarker;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        static IMAGE_NAME_REGEX: LazyLock<Regex> = LazyLock::new(|| {
-            Regex::new(concat!(
-                r"^",
-                r"(?:(?:(?P<domain>[a-zA-Z0-9](?:[a-zA-Z0-9-]*[a-zA-Z0-9])?(?:\.[a-zA-Z0-9](?:[a-zA-Z0-9-]*[a-zA-Z0-9])?)+)(?::(?P<port>[0-9]+))?)\/)?",
-                r"(?P<name>[a-z0-9]+(?:[_.]|__|[-]*[a-z0-9]+)*)(?:\/(?:[a-z0-9]+(?:[_.]|__|[-]*[a-z0-9]+)*))*",
-                r"(?::(?P<tag>[\w][\w.-]{0,127}))?",
-                r"(?:@(?P<algorithm>[A-Za-z][A-Za-z0-9]*(?:[+.-_][A-Za-z][A-Za-z0-9]*)*):(?P<hex>[0-9a-fA-F]{32,}))?",
-                r"$"
-            )).unwrap()
-        });
+        Self::parse(s, DEFAULT_MAX_LENGTH)
+    }
+}
 
-        let captures = IMAGE_NAME_REGEX.captures(s).ok_or(InvalidContainerImageNameMarker)?;
-        // NOTE: The first sub-capture match, index 0, matches the entire string.
-        // NOTE: Obtaining match data by index rather than group name to avoid string lookup.
-        Ok(Self {
-            registry_start: captures.get(1).map(|m| {
-                debug_assert_eq!(m.start(), 0);
-                IndicesRegistry {
-                    port_start: captures.get(2).map(|m| m.start()),
-                }
-            }),
-            path_start: captures.get(3).map(|m| m.start()).ok_or(InvalidContainerImageNameMarker)?,
-            tag_start: captures.get(4).map(|m| m.start()),
-            digest_start: captures.get(5).map(|m| IndicesDigest {
-                algorithm_start: m.start(),
-                hex_start: captures.get(6).unwrap().start(),
-            }),
-        })
+impl Indices {
+    /// Parses `s`, which may be at most `max_length` bytes long, see [`Indices::parse_const`].
+    fn parse(s: &str, max_length: usize) -> Result<Self, InvalidContainerImageNameMarker> {
+        Ok(Self::parse_const(s, max_length)?)
     }
 }
 
-macro_rules! impl_image_name_common {
-    ($T:ident $(<$lt:tt>)?) => {
-        impl$(<$lt>)? $T$(<$lt>)? {
-            /// Returns the `<domain>` section of the string documented at [`ImageName`].
-            pub fn domain(&self) -> Option<&$($lt)? str> {
-                self.indices.domain(&self.buffer)
-            }
+/// Checks a `<registry>` section on its own and returns the offset of its `<port>` section.
+fn check_registry(registry: &str) -> Result<Option<usize>, Reason> {
+    check_registry_const(registry.as_bytes(), registry.len())
+}
 
-            /// Returns the `<port>` section of the string documented at [`ImageName`].
-            pub fn port(&self) -> Option<&$($lt)? str> {
-                self.indices.port(&self.buffer)
-            }
+/// Checks a `<path>` section on its own.
+fn check_path(path: &str) -> Result<(), Reason> {
+    check_path_const(path.as_bytes(), 0, path.len())
+}
 
-            /// Returns the `<domain>(:<port>)?` section of the string documented at [`ImageName`].
-            pub fn registry(&self) -> Option<&$($lt)? str> {
-                self.indices.registry(&self.buffer)
-            }
+/// Checks a `<tag>` section on its own.
+fn check_tag(tag: &str) -> Result<(), Reason> {
+    if is_tag(tag.as_bytes(), 0, tag.len()) {
+        Ok(())
+    } else {
+        Err(Reason::InvalidTag { offset: 0 })
+    }
+}
 
-            /// Returns the `<path>` section of the string documented at [`ImageName`]. This is Returns the only required section.
-            pub fn path(&self) -> &$($lt)? str {
-                self.indices.path(&self.buffer)
-            }
+/// Checks a `<digest>` section on its own and returns the offset of its `<hex>` section.
+fn check_digest(digest: &str) -> Result<usize, Reason> {
+    let b = digest.as_bytes();
+    let hex_start = check_digest_const(b, 0, b.len())?;
+    check_digest_hex_length_const(b, 0, hex_start)?;
+    Ok(hex_start)
+}
 
-            /// Returns the `<tag>` section of the string documented at [`ImageName`].
-            pub fn tag(&self) -> Option<&$($lt)? str> {
-                self.indices.tag(&self.buffer)
-            }
+// NOTE: The parser is made of const functions so that `image_name!` can use it. Const functions can not use iterators,
+// so they walk the bytes of the name between absolute offsets.
 
-            /// Returns the `<algorithm>` section of the string documented at [`ImageName`].
-            pub fn digest_algorithm(&self) -> Option<&$($lt)? str> {
-                self.indices.digest_algorithm(&self.buffer)
-            }
+/// Like `?`, which const functions can not use.
+macro_rules! const_try {
+    ($result:expr) => {
+        match $result {
+            Ok(value) => value,
+            Err(error) => return Err(error),
+        }
+    };
+}
 
-            /// Returns the `<hex>` section of the string documented at [`ImageName`].
-            pub fn digest_hex(&self) -> Option<&$($lt)? str> {
-                self.indices.digest_hex(&self.buffer)
-            }
+/// The maximum length of the `<tag>` section, see `tag_pattern!`.
+pub const TAG_MAX_LENGTH: usize = 128;
 
-            /// Returns the `<digest>` section of the string documented at [`ImageName`].
-            pub fn digest(&self) -> Option<&$($lt)? str> {
-                self.indices.digest(&self.buffer)
-            }
+/// Returns the offset of the first `byte` in `b[start..end]`.
+const fn find_byte(b: &[u8], start: usize, end: usize, byte: u8) -> Option<usize> {
+    let mut index = start;
+    while index < end {
+        if b[index] == byte {
+            return Some(index);
         }
+        index += 1;
+    }
+    None
+}
 
-        impl$(<$lt>)? ::core::cmp::PartialEq for $T$(<$lt>)? {
-            fn eq(&self, other: &Self) -> bool {
-                self.buffer == other.buffer
-            }
+/// Returns the offset of the last `byte` in `b[start..end]`.
+const fn rfind_byte(b: &[u8], start: usize, end: usize, byte: u8) -> Option<usize> {
+    let mut index = end;
+    while index > start {
+        index -= 1;
+        if b[index] == byte {
+            return Some(index);
         }
+    }
+    None
+}
 
-        impl$(<$lt>)? ::core::cmp::Eq for $T$(<$lt>)? {}
-
-        impl$(<$lt>)? ::core::cmp::PartialOrd for $T$(<$lt>)? {
-            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-                Some(self.cmp(other))
-            }
+const fn bytes_eq(b: &[u8], start: usize, end: usize, other: &[u8]) -> bool {
+    if end - start != other.len() {
+        return false;
+    }
+    let mut index = 0;
+    while index < other.len() {
+        if b[start + index] != other[index] {
+            return false;
         }
+        index += 1;
+    }
+    true
+}
 
-        impl$(<$lt>)? ::core::cmp::Ord for $T$(<$lt>)? {
-            fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-                self.buffer.cmp(&other.buffer)
-            }
+/// See `domain_component_pattern!`.
+const fn is_domain_component(b: &[u8], start: usize, end: usize) -> bool {
+    if start == end || b[start] == b'-' || b[end - 1] == b'-' {
+        return false;
+    }
+    let mut index = start;
+    while index < end {
+        if !(b[index].is_ascii_alphanumeric() || b[index] == b'-') {
+            return false;
         }
+        index += 1;
+    }
+    true
+}
 
-        impl$(<$lt>)? ::core::hash::Hash for $T$(<$lt>)? {
-            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-                self.buffer.hash(state);
-            }
+/// See `ipv6_pattern!`, which includes the brackets.
+const fn is_ipv6_address(b: &[u8], start: usize, end: usize) -> bool {
+    if end - start < 3 || b[start] != IPV6_START as u8 || b[end - 1] != IPV6_END as u8 {
+        return false;
+    }
+    let mut index = start + 1;
+    while index < end - 1 {
+        if !(b[index].is_ascii_hexdigit() || b[index] == PORT_PREFIX as u8) {
+            return false;
         }
+        index += 1;
+    }
+    true
+}
 
-        // NOTE: It would make sense for ImageName to deref to ImageNameRef instead, but
-        // this can not be done because we can not return a reference to a new object
-        // created inside deref. Instead, both just deref to &str.
-        impl$(<$lt>)? ::core::ops::Deref for $T$(<$lt>)? {
-            type Target = str;
-
-            fn deref(&self) -> &Self::Target {
-                &self.buffer
-            }
+/// See `port_pattern!`.
+const fn is_port(b: &[u8], start: usize, end: usize) -> bool {
+    let mut index = start;
+    while index < end {
+        if !b[index].is_ascii_digit() {
+            return false;
         }
+        index += 1;
+    }
+    start < end
+}
 
-        impl$(<$lt>)? ::std::fmt::Debug for $T$(<$lt>)? {
-            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
-                (**self).fmt(f)
+/// See `path_component_pattern!`, which allows any sequence of `.` and `_` but requires every run of `-` to be followed
+/// by a lowercase alphanumeric character.
+const fn is_path_component(b: &[u8], start: usize, end: usize) -> bool {
+    if start == end || !matches!(b[start], b'a'..=b'z' | b'0'..=b'9') {
+        return false;
+    }
+    let mut index = start + 1;
+    while index < end {
+        match b[index] {
+            b'-' if index + 1 < end && matches!(b[index + 1], b'a'..=b'z' | b'0'..=b'9' | b'-') => {
             }
+            b'a'..=b'z' | b'0'..=b'9' | b'.' | b'_' => {}
+            _ => return false,
         }
+        index += 1;
+    }
+    true
+}
 
-        impl$(<$lt>)? ::std::fmt::Display for $T$(<$lt>)? {
-            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
-                (**self).fmt(f)
-            }
+/// See `tag_pattern!`.
+const fn is_tag(b: &[u8], start: usize, end: usize) -> bool {
+    if start == end
+        || end - start > TAG_MAX_LENGTH
+        || !(b[start].is_ascii_alphanumeric() || b[start] == b'_')
+    {
+        return false;
+    }
+    let mut index = start + 1;
+    while index < end {
+        if !(b[index].is_ascii_alphanumeric() || matches!(b[index], b'_' | b'.' | b'-')) {
+            return false;
         }
-    };
+        index += 1;
+    }
+    true
 }
 
-/// Represents a parsed container image name. The container image name is written as:
-/// ```txt
-/// <domain>:<port>/<path>:<tag>@<algorithm>:<hex>
-/// <registry----->/<path>:<tag>@<digest--------->
-/// ```
-/// Only the `<path>` section is required to be present. The `<port>` section can only be present if
-/// the `<domain>` section is present. The `<registry>` section equals the combination of
-/// `<domain>:<port>` and the `<digest>` secotion equals the combination of `<algorithm>:<hex>`.
-#[derive(Clone)]
-pub struct ImageName {
-    buffer: String,
-    indices: Indices,
+/// See `algorithm_pattern!`.
+const fn is_algorithm(b: &[u8], start: usize, end: usize) -> bool {
+    if start == end || !b[start].is_ascii_alphabetic() {
+        return false;
+    }
+    let mut index = start + 1;
+    while index < end {
+        match b[index] {
+            b'-' | b'_' | b'+' | b'.' if index + 1 < end && b[index + 1].is_ascii_alphabetic() => {}
+            byte if byte.is_ascii_alphanumeric() => {}
+            _ => return false,
+        }
+        index += 1;
+    }
+    true
 }
 
-impl ImageName {
-    pub fn new(value: String) -> Result<Self, InvalidContainerImageName> {
-        let indices = match value.parse() {
-            Ok(indices) => indices,
-            Err(InvalidContainerImageNameMarker) => {
-                return Err(InvalidContainerImageName(value))
-            }
+/// See [`check_registry`]. The `<registry>` section ends at `end`.
+const fn check_registry_const(b: &[u8], end: usize) -> Result<Option<usize>, Reason> {
+    if end > 0 && b[0] == IPV6_START as u8 {
+        return check_ipv6_registry_const(b, end);
+    }
+    let colon = find_byte(b, 0, end, PORT_PREFIX as u8);
+    let domain_end = match colon {
+        Some(colon) => colon,
+        None => end,
+    };
+    let is_localhost = bytes_eq(b, 0, domain_end, b"localhost");
+    let mut components = 0;
+    let mut invalid_component = None;
+    let mut start = 0;
+    loop {
+        let component_end = match find_byte(b, start, domain_end, b'.') {
+            Some(dot) => dot,
+            None => domain_end,
         };
-        Ok(Self {
-            indices,
-            buffer: value,
-        })
+        if !is_domain_component(b, start, component_end) {
+            invalid_component = Some(start);
+            break;
+        }
+        components += 1;
+        if component_end == domain_end {
+            break;
+        }
+        start = component_end + 1;
     }
-
-    pub fn builder<'a>(path: impl Into<Cow<'a, str>>) -> ImageNameBuilder<'a> {
-        ImageNameBuilder::new(path)
+    let port_start = match colon {
+        Some(colon) => Some(colon + PORT_PREFIX.len_utf8()),
+        None => None,
+    };
+    let is_port_valid = match port_start {
+        Some(port_start) => is_port(b, port_start, end),
+        None => true,
+    };
+    // NOTE: A single domain component is only a registry when it has a port.
+    let is_domain_valid =
+        is_localhost || (invalid_component.is_none() && (components > 1 || colon.is_some()));
+    match (invalid_component, port_start) {
+        _ if is_domain_valid && is_port_valid => Ok(port_start),
+        (Some(offset), _) if !is_localhost => Err(Reason::InvalidDomain { offset }),
+        (_, Some(offset)) if !is_port_valid => Err(Reason::InvalidPort { offset }),
+        _ => Err(Reason::InvalidDomain { offset: 0 }),
     }
+}
 
-    pub fn as_builder(&self) -> ImageNameBuilder<'_> {
-        let mut builder = ImageNameBuilder::new(self.path());
-        if let Some(registry) = self.registry() {
-            builder = builder.with_registry(registry);
+/// See [`check_registry_const`], for a `<registry>` section that starts with an IPv6 address.
+const fn check_ipv6_registry_const(b: &[u8], end: usize) -> Result<Option<usize>, Reason> {
+    // NOTE: Like `split_registry`, the port prefix is only searched for after the closing bracket.
+    let domain_end = match find_byte(b, 0, end, IPV6_END as u8) {
+        Some(bracket) => bracket + IPV6_END.len_utf8(),
+        None => end,
+    };
+    let port_start = match find_byte(b, domain_end, end, PORT_PREFIX as u8) {
+        Some(colon) => Some(colon + PORT_PREFIX.len_utf8()),
+        None => None,
+    };
+    let domain_end = match port_start {
+        Some(port_start) => port_start - PORT_PREFIX.len_utf8(),
+        None => end,
+    };
+    if !is_ipv6_address(b, 0, domain_end) {
+        return Err(Reason::InvalidDomain { offset: 0 });
+    }
+    match port_start {
+        Some(port_start) if !is_port(b, port_start, end) => {
+            Err(Reason::InvalidPort { offset: port_start })
         }
-        if let Some(tag) = self.tag() {
-            builder = builder.with_tag(tag);
+        _ => Ok(port_start),
+    }
+}
+
+/// See [`check_path`].
+const fn check_path_const(b: &[u8], start: usize, end: usize) -> Result<(), Reason> {
+    let mut start = start;
+    loop {
+        let component_end = match find_byte(b, start, end, PATH_COMPONENT_SEPARATOR as u8) {
+            Some(separator) => separator,
+            None => end,
+        };
+        if !is_path_component(b, start, component_end) {
+            return Err(Reason::InvalidPathComponent { offset: start });
         }
-        if let Some(digest) = self.digest() {
-            builder = builder.with_digest(digest);
+        if component_end == end {
+            return Ok(());
         }
-        builder
+        start = component_end + PATH_COMPONENT_SEPARATOR.len_utf8();
     }
+}
 
-    pub fn as_ref(&self) -> ImageNameRef<'_> {
-        ImageNameRef {
-            buffer: &self.buffer[..],
-            indices: self.indices,
+/// See [`check_digest`]. Returns the offset of the `<hex>` section.
+const fn check_digest_const(b: &[u8], start: usize, end: usize) -> Result<usize, Reason> {
+    let (algorithm_end, hex_start) = match find_byte(b, start, end, DIGEST_HEX_PREFIX as u8) {
+        Some(prefix) => (prefix, prefix + DIGEST_HEX_PREFIX.len_utf8()),
+        None => (end, end),
+    };
+    if !is_algorithm(b, start, algorithm_end) {
+        return Err(Reason::InvalidDigestAlgorithm { offset: start });
+    }
+    let mut index = hex_start;
+    while index < end {
+        if !b[index].is_ascii_hexdigit() {
+            return Err(Reason::InvalidDigestHex { offset: index });
         }
+        index += 1;
     }
-
-    pub fn as_str(&self) -> &str {
-        &self.buffer
+    if end - hex_start < MIN_DIGEST_HEX_LENGTH {
+        return Err(Reason::DigestHexTooShort {
+            offset: hex_start,
+            found: end - hex_start,
+        });
     }
+    Ok(hex_start)
 }
 
-impl_image_name_common!(ImageName);
-
-impl FromStr for ImageName {
-    type Err = InvalidContainerImageNameMarker;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        ImageNameRef::new(s).map(ImageNameRef::to_owned)
+/// Checks the length of the `<hex>` section of a digest, which starts at `hex_start`, against its `<algorithm>` section.
+const fn check_digest_hex_length_const(
+    b: &[u8],
+    algorithm_start: usize,
+    hex_start: usize,
+) -> Result<(), Reason> {
+    let algorithm_end = hex_start - DIGEST_HEX_PREFIX.len_utf8();
+    let found = b.len() - hex_start;
+    let mut index = 0;
+    while index < DIGEST_HEX_LENGTHS.len() {
+        let (algorithm, expected) = DIGEST_HEX_LENGTHS[index];
+        if bytes_eq(b, algorithm_start, algorithm_end, algorithm.as_bytes()) && found != expected {
+            return Err(Reason::DigestLength {
+                offset: hex_start,
+                algorithm,
+                expected,
+                found,
+            });
+        }
+        index += 1;
     }
+    Ok(())
 }
 
-impl From<ImageNameRef<'_>> for ImageName {
-    fn from(value: ImageNameRef<'_>) -> Self {
-        value.to_owned()
+/// Returns the end of the `<registry>` section when the name in `b[..name_end]` starts with one, which is when its first
+/// component looks like a registry, see `registry_pattern!`.
+const fn find_registry_end_const(b: &[u8], name_end: usize) -> Option<usize> {
+    // NOTE: A first component that contains a dot but is not a valid registry may still be a valid path component.
+    match find_byte(b, 0, name_end, REGISTRY_SUFFIX as u8) {
+        Some(first_end)
+            if (bytes_eq(b, 0, first_end, b"localhost")
+                || (first_end > 0 && b[0] == IPV6_START as u8)
+                || find_byte(b, 0, first_end, b'.').is_some()
+                || find_byte(b, 0, first_end, PORT_PREFIX as u8).is_some())
+                && (check_registry_const(b, first_end).is_ok()
+                    || check_path_const(b, 0, first_end).is_err()) =>
+        {
+            Some(first_end)
+        }
+        _ => None,
     }
 }
 
-impl TryFrom<String> for ImageName {
-    type Error = InvalidContainerImageName;
+impl Indices {
+    /// Like [`Indices::parse`], but usable in const contexts. The name is split into sections like the grammar would
+    /// split it, and the sections are checked on their own so that an invalid name fails with the section to blame.
+    const fn parse_const(s: &str, max_length: usize) -> Result<Self, Reason> {
+        let b = s.as_bytes();
+        if b.len() > max_length {
+            return Err(Reason::ReferenceTooLong {
+                offset: max_length,
+                length: b.len(),
+            });
+        }
 
-    fn try_from(value: String) -> Result<Self, Self::Error> {
-        Self::new(value)
+        let digest_prefix = find_byte(b, 0, b.len(), DIGEST_ALGORITHM_PREFIX as u8);
+        let name_and_tag_end = match digest_prefix {
+            Some(prefix) => prefix,
+            None => b.len(),
+        };
+        let (name_end, tag_start) = match rfind_byte(b, 0, name_and_tag_end, TAG_PREFIX as u8) {
+            Some(prefix)
+                if find_byte(b, prefix, name_and_tag_end, PATH_COMPONENT_SEPARATOR as u8)
+                    .is_none() =>
+            {
+                (prefix, Some(prefix + TAG_PREFIX.len_utf8()))
+            }
+            _ => (name_and_tag_end, None),
+        };
+        let (registry_start, path_start) = match find_registry_end_const(b, name_end) {
+            Some(registry_end) => (
+                Some(IndicesRegistry {
+                    port_start: const_try!(check_registry_const(b, registry_end)),
+                }),
+                registry_end + REGISTRY_SUFFIX.len_utf8(),
+            ),
+            None => (None, 0),
+        };
+        const_try!(check_path_const(b, path_start, name_end));
+        if let Some(tag_start) = tag_start {
+            if !is_tag(b, tag_start, name_and_tag_end) {
+                return Err(Reason::InvalidTag { offset: tag_start });
+            }
+        }
+        let digest_start = match digest_prefix {
+            Some(prefix) => {
+                let algorithm_start = prefix + DIGEST_ALGORITHM_PREFIX.len_utf8();
+                Some(IndicesDigest {
+                    algorithm_start,
+                    hex_start: const_try!(check_digest_const(b, algorithm_start, b.len())),
+                })
+            }
+            None => None,
+        };
+        const_try!(check_name_length(name_end));
+        if let Some(digest_start) = digest_start {
+            const_try!(check_digest_hex_length_const(
+                b,
+                digest_start.algorithm_start,
+                digest_start.hex_start
+            ));
+        }
+        Ok(Self {
+            registry_start,
+            path_start,
+            tag_start,
+            digest_start,
+        })
     }
 }
 
-impl From<ImageName> for String {
-    fn from(value: ImageName) -> Self {
-        value.buffer
-    }
+/// Parses a container image name at compile time, so that a typo fails the build rather than the submission. Expands to
+/// an [`ImageNameRef<'static>`], which can initialize a `const`. See [`ImageNameRef::new_const`] for the names that are
+/// accepted.
+///
+/// ```
+/// use container_image_name::{image_name, ImageNameRef};
+///
+/// const BUSYBOX: ImageNameRef<'static> = image_name!("busybox:1.36");
+/// assert_eq!(BUSYBOX.tag(), Some("1.36"));
+/// ```
+///
+/// Path components are lowercase:
+///
+/// ```compile_fail
+/// use container_image_name::{image_name, ImageNameRef};
+///
+/// const IMAGE: ImageNameRef<'static> = image_name!("registry.io/Org/img:v1");
+/// ```
+///
+/// The hex of a sha256 digest has 64 characters:
+///
+/// ```compile_fail
+/// let image = container_image_name::image_name!("img@sha256:0123456789abcdef0123456789abcdef");
+/// ```
+#[macro_export]
+macro_rules! image_name {
+    ($value:expr $(,)?) => {{
+        // NOTE: Unlike an inline `const` block, a const item is also evaluated by `cargo check`.
+        const IMAGE_NAME: $crate::ImageNameRef<'static> =
+            match $crate::ImageNameRef::new_const($value) {
+                Ok(image_name) => image_name,
+                Err(error) => panic!("{}", error.reason().description()),
+            };
+        IMAGE_NAME
+    }};
 }
 
-#[cfg(feature = "serde")]
-impl Serialize for ImageName {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        serializer.serialize_str(&self.buffer)
-    }
-}
+macro_rules! impl_image_name_common {
+    ($T:ident $(<$lt:tt>)?) => {
+        impl$(<$lt>)? $T$(<$lt>)? {
+            /// Returns the `<domain>` section of the string documented at [`ImageName`].
+            pub fn domain(&self) -> Option<&$($lt)? str> {
+                self.indices.domain(&self.buffer)
+            }
 
-#[cfg(feature = "serde")]
-impl<'de> Deserialize<'de> for ImageName {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        Self::new(Deserialize::deserialize(deserializer)?).map_err(::serde::de::Error::custom)
-    }
-}
+            /// Returns the `<port>` section of the string documented at [`ImageName`].
+            pub fn port(&self) -> Option<&$($lt)? str> {
+                self.indices.port(&self.buffer)
+            }
 
-/// A version of [`ImageName`] that only borrows its buffer.
-#[derive(Copy, Clone)]
-pub struct ImageNameRef<'a> {
-    buffer: &'a str,
-    indices: Indices,
-}
+            /// Returns the `<domain>(:<port>)?` section of the string documented at [`ImageName`].
+            pub fn registry(&self) -> Option<&$($lt)? str> {
+                self.indices.registry(&self.buffer)
+            }
 
-impl<'a> ImageNameRef<'a> {
-    pub fn new(value: &'a str) -> Result<Self, InvalidContainerImageNameMarker> {
-        Ok(Self {
-            buffer: value,
-            indices: value.parse()?,
-        })
-    }
+            /// Returns the `<path>` section of the string documented at [`ImageName`]. This is Returns the only required section.
+            pub fn path(&self) -> &$($lt)? str {
+                self.indices.path(&self.buffer)
+            }
 
-    pub fn as_builder(self) -> ImageNameBuilder<'a> {
-        let mut builder = ImageNameBuilder::new(self.path());
-        if let Some(registry) = self.registry() {
-            builder = builder.with_registry(registry);
-        }
-        if let Some(tag) = self.tag() {
-            builder = builder.with_tag(tag);
-        }
-        if let Some(digest) = self.digest() {
-            builder = builder.with_digest(digest);
-        }
-        builder
-    }
+            /// Returns the `(<registry>/)?<path>` sections of the string documented at [`ImageName`], which leave out the
+            /// `<tag>` and `<digest>` sections.
+            pub fn repository(&self) -> &$($lt)? str {
+                self.indices.repository(&self.buffer)
+            }
 
-    pub fn to_owned(self) -> ImageName {
-        ImageName {
-            buffer: self.buffer.to_owned(),
-            indices: self.indices,
-        }
-    }
+            /// Returns the components of the `<path>` section, which are separated by `/`.
+            pub fn path_components(&self) -> impl Iterator<Item = &$($lt)? str> {
+                self.path().split(PATH_COMPONENT_SEPARATOR)
+            }
 
-    pub fn as_str(self) -> &'a str {
-        self.buffer
-    }
-}
+            /// Returns the last component of the `<path>` section, such as `img-name` for `org-name/img-name`.
+            pub fn name(&self) -> &$($lt)? str {
+                let path = self.path();
+                path.rsplit_once(PATH_COMPONENT_SEPARATOR)
+                    .map_or(path, |(_, name)| name)
+            }
 
-impl_image_name_common!(ImageNameRef<'a>);
+            /// Returns the components of the `<path>` section before the last one, such as `org-name` for
+            /// `org-name/img-name`, or `None` when the path has a single component.
+            pub fn namespace(&self) -> Option<&$($lt)? str> {
+                self.path()
+                    .rsplit_once(PATH_COMPONENT_SEPARATOR)
+                    .map(|(namespace, _)| namespace)
+            }
 
-impl<'a> From<&'a ImageName> for ImageNameRef<'a> {
-    fn from(value: &'a ImageName) -> Self {
-        value.as_ref()
-    }
-}
+            /// Returns the `<tag>` section of the string documented at [`ImageName`].
+            pub fn tag(&self) -> Option<&$($lt)? str> {
+                self.indices.tag(&self.buffer)
+            }
 
-impl<'a> TryFrom<&'a str> for ImageNameRef<'a> {
-    type Error = InvalidContainerImageNameMarker;
+            /// Returns the `<algorithm>` section of the string documented at [`ImageName`].
+            pub fn digest_algorithm(&self) -> Option<&$($lt)? str> {
+                self.indices.digest_algorithm(&self.buffer)
+            }
 
-    fn try_from(value: &'a str) -> Result<Self, Self::Error> {
-        Self::new(value)
-    }
-}
+            /// Returns the `<hex>` section of the string documented at [`ImageName`].
+            pub fn digest_hex(&self) -> Option<&$($lt)? str> {
+                self.indices.digest_hex(&self.buffer)
+            }
 
-impl<'a> From<ImageNameRef<'a>> for &'a str {
-    fn from(value: ImageNameRef<'a>) -> Self {
-        value.buffer
-    }
-}
+            /// Returns the `<digest>` section of the string documented at [`ImageName`].
+            pub fn digest(&self) -> Option<&$($lt)? str> {
+                self.indices.digest(&self.buffer)
+            }
 
-#[cfg(feature = "serde")]
-impl Serialize for ImageNameRef<'_> {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        serializer.serialize_str(self.buffer)
-    }
-}
+            /// Whether the name has a `<tag>` section.
+            pub fn is_tagged(&self) -> bool {
+                self.indices.tag_start.is_some()
+            }
 
-#[cfg(feature = "serde")]
-impl<'de> Deserialize<'de> for ImageNameRef<'de> {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        Self::new(Deserialize::deserialize(deserializer)?).map_err(::serde::de::Error::custom)
-    }
-}
+            /// Whether the name has a `<digest>` section.
+            pub fn is_digested(&self) -> bool {
+                self.indices.digest_start.is_some()
+            }
 
-enum ImageNameBuilderRegistry<'a> {
-    Registry(Cow<'a, str>),
-    DomainPort {
-        domain: Cow<'a, str>,
-        port: Option<Cow<'a, str>>,
-    },
-}
+            /// Whether the name has a `<registry>` section.
+            pub fn has_registry(&self) -> bool {
+                self.registry().is_some()
+            }
 
-impl ImageNameBuilderRegistry<'_> {
-    fn len(&self) -> usize {
-        (match self {
-            ImageNameBuilderRegistry::Registry(registry) => registry.len(),
-            ImageNameBuilderRegistry::DomainPort { domain, port } => {
-                domain.len()
-                    + port
-                        .as_ref()
-                        .map(|port| PORT_PREFIX.len_utf8() + port.len())
-                        .unwrap_or_default()
+            /// Like [`tag`](Self::tag), but fails with an error that names the missing section.
+            pub fn expect_tag(&self) -> Result<&$($lt)? str, MissingSection> {
+                self.tag().ok_or_else(|| self.missing(Section::Tag))
             }
-        }) + REGISTRY_SUFFIX.len_utf8()
-    }
 
-    fn write(&self, buffer: &mut String) {
-        match self {
-            ImageNameBuilderRegistry::Registry(registry) => {
-                buffer.push_str(registry);
+            /// Like [`registry`](Self::registry), but fails with an error that names the missing section.
+            pub fn expect_registry(&self) -> Result<&$($lt)? str, MissingSection> {
+                self.registry().ok_or_else(|| self.missing(Section::Registry))
             }
-            ImageNameBuilderRegistry::DomainPort { domain, port } => {
-                buffer.push_str(domain);
-                buffer.push(PORT_PREFIX);
-                if let Some(port) = port {
-                    buffer.push_str(port);
+
+            fn missing(&self, section: Section) -> MissingSection {
+                MissingSection {
+                    value: (*self.buffer).to_owned(),
+                    section,
                 }
             }
-        }
-        buffer.push(REGISTRY_SUFFIX)
-    }
-}
 
-enum ImageNameBuilderDigest<'a> {
-    Digest(Cow<'a, str>),
-    AlgorithmHex {
-        algorithm: Cow<'a, str>,
-        hex: Cow<'a, str>,
-    },
-}
+            /// Returns the first 12 characters of the `<hex>` section, like the short image IDs of Docker.
+            pub fn short_digest(&self) -> Option<&$($lt)? str> {
+                self.digest_hex()
+                    .map(|hex| &hex[..hex.len().min(SHORT_DIGEST_HEX_LENGTH)])
+            }
 
-impl ImageNameBuilderDigest<'_> {
-    fn len(&self) -> usize {
-        DIGEST_ALGORITHM_PREFIX.len_utf8()
-            + (match self {
-                ImageNameBuilderDigest::Digest(digest) => digest.len(),
-                ImageNameBuilderDigest::AlgorithmHex { algorithm, hex } => {
-                    algorithm.len() + DIGEST_HEX_PREFIX.len_utf8() + hex.len()
+            /// Whether both names refer to the same image the way a registry sees them: the `<domain>` sections are
+            /// compared case-insensitively, a missing `<tag>` section is `latest` and the `<hex>` sections of digests
+            /// are compared case-insensitively. Unlike [`normalize`](ImageNameRef::normalize), a missing `<registry>`
+            /// section is not `docker.io`. `==` and `Hash` keep comparing the buffers, so names that are equal here may
+            /// hash differently.
+            pub fn eq_canonical(&self, other: ImageNameRef<'_>) -> bool {
+                fn tag_or_default(tag: Option<&str>) -> &str {
+                    tag.unwrap_or(DEFAULT_TAG)
                 }
-            })
-    }
 
-    fn write(&self, buffer: &mut String) {
-        buffer.push(DIGEST_ALGORITHM_PREFIX);
-        match self {
-            ImageNameBuilderDigest::Digest(digest) => buffer.push_str(digest),
-            ImageNameBuilderDigest::AlgorithmHex { algorithm, hex } => {
-                buffer.push_str(algorithm);
-                buffer.push(DIGEST_HEX_PREFIX);
+                let registries_eq = match (self.registry(), other.registry()) {
+                    (Some(a), Some(b)) => registry_eq_canonical(a, b),
+                    (a, b) => a == b,
+                };
+                registries_eq
+                    && self.path() == other.path()
+                    && tag_or_default(self.tag()) == tag_or_default(other.tag())
+                    && self.digest_algorithm() == other.digest_algorithm()
+                    && match (self.digest_hex(), other.digest_hex()) {
+                        (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+                        (a, b) => a == b,
+                    }
+            }
+
+            /// Whether both names are in the same repository regardless of their `<tag>` and `<digest>` sections. The
+            /// `<domain>` sections are compared case-insensitively like in [`eq_canonical`](Self::eq_canonical), and a
+            /// missing `<registry>` section only matches another missing one.
+            pub fn same_repository<'o>(&self, other: impl Into<ImageNameRef<'o>>) -> bool {
+                let other = other.into();
+                let registries_eq = match (self.registry(), other.registry()) {
+                    (Some(a), Some(b)) => registry_eq_canonical(a, b),
+                    (a, b) => a == b,
+                };
+                registries_eq && self.path() == other.path()
+            }
+
+            /// Returns the `<digest>` section as a [`Digest`]. Fails when the hex of an algorithm other than those of
+            /// [`Algorithm`] has an odd number of characters, which the grammar allows.
+            pub fn digest_parsed(&self) -> Option<Result<Digest, InvalidDigest>> {
+                Some(Digest::from_sections(self.digest_algorithm()?, self.digest_hex()?))
+            }
+        }
+
+        impl$(<$lt>)? ::core::cmp::PartialEq for $T$(<$lt>)? {
+            fn eq(&self, other: &Self) -> bool {
+                self.buffer == other.buffer
+            }
+        }
+
+        impl$(<$lt>)? ::core::cmp::Eq for $T$(<$lt>)? {}
+
+        // NOTE: Comparing with strings compares the buffer, like the Hash implementation below hashes it.
+        impl$(<$lt>)? ::core::cmp::PartialEq<str> for $T$(<$lt>)? {
+            fn eq(&self, other: &str) -> bool {
+                *self.buffer == *other
+            }
+        }
+
+        impl<'s $(, $lt)?> ::core::cmp::PartialEq<&'s str> for $T$(<$lt>)? {
+            fn eq(&self, other: &&'s str) -> bool {
+                *self.buffer == **other
+            }
+        }
+
+        impl$(<$lt>)? ::core::cmp::PartialEq<$T$(<$lt>)?> for str {
+            fn eq(&self, other: &$T$(<$lt>)?) -> bool {
+                *self == *other.buffer
+            }
+        }
+
+        impl<'s $(, $lt)?> ::core::cmp::PartialEq<$T$(<$lt>)?> for &'s str {
+            fn eq(&self, other: &$T$(<$lt>)?) -> bool {
+                **self == *other.buffer
+            }
+        }
+
+        impl$(<$lt>)? ::core::cmp::PartialOrd for $T$(<$lt>)? {
+            fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl$(<$lt>)? ::core::cmp::Ord for $T$(<$lt>)? {
+            fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+                self.buffer.cmp(&other.buffer)
+            }
+        }
+
+        impl$(<$lt>)? ::core::hash::Hash for $T$(<$lt>)? {
+            fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+                self.buffer.hash(state);
+            }
+        }
+
+        // NOTE: Equality, ordering and hashing are all defined on the buffer, so they agree with those of `str` as the
+        // contract of `Borrow` requires. This allows looking up keys of maps and sets by `&str`. The case-insensitive
+        // comparison of registries is therefore a separate method, `eq_canonical`.
+        impl$(<$lt>)? ::core::borrow::Borrow<str> for $T$(<$lt>)? {
+            fn borrow(&self) -> &str {
+                &self.buffer
+            }
+        }
+
+        // NOTE: It would make sense for ImageName to deref to ImageNameRef instead, but
+        // this can not be done because we can not return a reference to a new object
+        // created inside deref. Instead, both just deref to &str.
+        impl$(<$lt>)? ::core::ops::Deref for $T$(<$lt>)? {
+            type Target = str;
+
+            fn deref(&self) -> &Self::Target {
+                &self.buffer
+            }
+        }
+
+        impl$(<$lt>)? ::core::fmt::Debug for $T$(<$lt>)? {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                (**self).fmt(f)
+            }
+        }
+
+        // NOTE: The alternate form `{:#}` truncates the `<hex>` section to its first 12 characters followed by `…`, so
+        // that log lines do not wrap. It no longer parses and is only meant for display.
+        impl$(<$lt>)? ::core::fmt::Display for $T$(<$lt>)? {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                if f.alternate() {
+                    if let Some(hex) = self
+                        .indices
+                        .digest_hex_range(self.buffer.len())
+                        .filter(|hex| hex.len() > SHORT_DIGEST_HEX_LENGTH)
+                    {
+                        return f.pad(&format!(
+                            "{}\u{2026}",
+                            &self.buffer[..hex.start + SHORT_DIGEST_HEX_LENGTH]
+                        ));
+                    }
+                }
+                (**self).fmt(f)
+            }
+        }
+    };
+}
+
+/// Represents a parsed container image name. The container image name is written as:
+/// ```txt
+/// <domain>:<port>/<path>:<tag>@<algorithm>:<hex>
+/// <registry----->/<path>:<tag>@<digest--------->
+/// ```
+/// Only the `<path>` section is required to be present. The `<port>` section can only be present if
+/// the `<domain>` section is present. The `<registry>` section equals the combination of
+/// `<domain>:<port>` and the `<digest>` secotion equals the combination of `<algorithm>:<hex>`.
+#[derive(Clone)]
+pub struct ImageName {
+    buffer: String,
+    indices: Indices,
+}
+
+impl ImageName {
+    pub fn new(value: String) -> Result<Self, InvalidContainerImageName> {
+        Self::new_with_max_length(value, DEFAULT_MAX_LENGTH)
+    }
+
+    /// Like [`ImageName::new`], but accepts image names of up to `max_length` bytes instead of
+    /// [`DEFAULT_MAX_LENGTH`]. The [`NAME_MAX_LENGTH`] still applies.
+    pub fn new_with_max_length(
+        value: String,
+        max_length: usize,
+    ) -> Result<Self, InvalidContainerImageName> {
+        let indices = match Indices::parse(&value, max_length) {
+            Ok(indices) => indices,
+            Err(InvalidContainerImageNameMarker { reason }) => {
+                return Err(InvalidContainerImageName { value, reason })
+            }
+        };
+        Ok(Self {
+            indices,
+            buffer: value,
+        })
+    }
+
+    pub fn builder<'a>(path: impl Into<Cow<'a, str>>) -> ImageNameBuilder<'a> {
+        ImageNameBuilder::new(path)
+    }
+
+    pub fn as_builder(&self) -> ImageNameBuilder<'_> {
+        let mut builder = ImageNameBuilder::new(self.path());
+        if let Some(registry) = self.registry() {
+            builder = builder.with_registry(registry);
+        }
+        if let Some(tag) = self.tag() {
+            builder = builder.with_tag(TagRef(tag));
+        }
+        if let Some(digest) = self.digest() {
+            builder = builder.with_digest(digest);
+        }
+        builder
+    }
+
+    pub fn as_ref(&self) -> ImageNameRef<'_> {
+        ImageNameRef {
+            buffer: &self.buffer[..],
+            indices: self.indices,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.buffer
+    }
+
+    /// See [`ImageNameRef::normalize`].
+    pub fn normalize(&self) -> ImageName {
+        self.as_ref().normalize()
+    }
+
+    /// See [`ImageNameRef::familiar_name`].
+    pub fn familiar_name(&self, truncate_digest: bool) -> Cow<'_, str> {
+        self.as_ref().familiar_name(truncate_digest)
+    }
+
+    /// Replaces `range` of the buffer with `replacement` and moves the indices at or after the end of `range`. The
+    /// indices within `range` are left to the caller.
+    fn splice(&mut self, range: Range<usize>, replacement: &str) {
+        let end = range.end;
+        let shift = |index: &mut usize| {
+            if *index >= end {
+                *index = *index - end + range.start + replacement.len();
+            }
+        };
+        let indices = &mut self.indices;
+        shift(&mut indices.path_start);
+        if let Some(tag_start) = &mut indices.tag_start {
+            shift(tag_start);
+        }
+        if let Some(digest_start) = &mut indices.digest_start {
+            shift(&mut digest_start.algorithm_start);
+            shift(&mut digest_start.hex_start);
+        }
+        self.buffer.replace_range(range, replacement);
+    }
+
+    /// Returns the error for replacing `range` of the buffer with `replacement`, where `reason` is relative to the
+    /// section that starts `section_offset` bytes into `replacement`.
+    fn invalid(
+        &self,
+        range: Range<usize>,
+        replacement: &str,
+        section_offset: usize,
+        reason: Reason,
+    ) -> InvalidContainerImageName {
+        let reason = reason.offset_by(range.start + section_offset);
+        let mut value = self.buffer.clone();
+        value.replace_range(range, replacement);
+        InvalidContainerImageName { value, reason }
+    }
+
+    /// Replaces or adds the `<registry>` section in place. Only `registry` and the length of the name are validated, the
+    /// rest of the name is not parsed again. On error, `self` is left unchanged.
+    pub fn set_registry(&mut self, registry: &str) -> Result<(), InvalidContainerImageName> {
+        let range = match self.indices.registry_start {
+            Some(_) => 0..self.indices.path_start,
+            None => 0..0,
+        };
+        let replacement = format!("{registry}{REGISTRY_SUFFIX}");
+        let port_start = check_registry(registry)
+            .and_then(|port_start| {
+                check_name_length(
+                    self.indices.path_range(self.buffer.len()).end - range.end + replacement.len(),
+                )?;
+                Ok(port_start)
+            })
+            .map_err(|reason| self.invalid(range.clone(), &replacement, 0, reason))?;
+        self.splice(range, &replacement);
+        self.indices.registry_start = Some(IndicesRegistry { port_start });
+        Ok(())
+    }
+
+    /// Replaces or adds the `<tag>` section in place. Only `tag` is validated, the rest of the name is not parsed
+    /// again. On error, `self` is left unchanged.
+    pub fn set_tag(&mut self, tag: &str) -> Result<(), InvalidContainerImageName> {
+        let path_end = self.indices.path_range(self.buffer.len()).end;
+        let range = path_end
+            ..self
+                .indices
+                .tag_range(self.buffer.len())
+                .map_or(path_end, |range| range.end);
+        let replacement = format!("{TAG_PREFIX}{tag}");
+        check_tag(tag).map_err(|reason| {
+            self.invalid(range.clone(), &replacement, TAG_PREFIX.len_utf8(), reason)
+        })?;
+        self.splice(range, &replacement);
+        self.indices.tag_start = Some(path_end + TAG_PREFIX.len_utf8());
+        Ok(())
+    }
+
+    /// Removes the `<tag>` section, if any.
+    pub fn clear_tag(&mut self) {
+        if let Some(range) = self.indices.tag_range(self.buffer.len()) {
+            self.splice(range.start - TAG_PREFIX.len_utf8()..range.end, "");
+            self.indices.tag_start = None;
+        }
+    }
+
+    /// Replaces or adds the `<digest>` section in place. Only `digest` is validated, the rest of the name is not
+    /// parsed again. On error, `self` is left unchanged.
+    pub fn set_digest(&mut self, digest: &str) -> Result<(), InvalidContainerImageName> {
+        let start = self
+            .indices
+            .digest_range(self.buffer.len())
+            .map_or(self.buffer.len(), |range| {
+                range.start - DIGEST_ALGORITHM_PREFIX.len_utf8()
+            });
+        let range = start..self.buffer.len();
+        let replacement = format!("{DIGEST_ALGORITHM_PREFIX}{digest}");
+        let hex_offset = check_digest(digest).map_err(|reason| {
+            self.invalid(
+                range.clone(),
+                &replacement,
+                DIGEST_ALGORITHM_PREFIX.len_utf8(),
+                reason,
+            )
+        })?;
+        self.buffer.replace_range(range, &replacement);
+        let algorithm_start = start + DIGEST_ALGORITHM_PREFIX.len_utf8();
+        self.indices.digest_start = Some(IndicesDigest {
+            algorithm_start,
+            hex_start: algorithm_start + hex_offset,
+        });
+        Ok(())
+    }
+
+    /// Removes the `<digest>` section, if any.
+    pub fn clear_digest(&mut self) {
+        if let Some(range) = self.indices.digest_range(self.buffer.len()) {
+            self.buffer
+                .truncate(range.start - DIGEST_ALGORITHM_PREFIX.len_utf8());
+            self.indices.digest_start = None;
+        }
+    }
+
+    /// Whether the name has a `<digest>` section, which decides what is pulled even when there is a `<tag>` section as
+    /// well.
+    pub fn is_canonical(&self) -> bool {
+        self.is_digested()
+    }
+
+    /// Returns a copy without the `<tag>` section, so that the `<digest>` section alone names what is pulled. Fails
+    /// when there is no `<digest>` section.
+    pub fn to_canonical(&self) -> Result<ImageName, MissingSection> {
+        if !self.is_canonical() {
+            return Err(self.missing(Section::Digest));
+        }
+        let mut canonical = self.clone();
+        canonical.clear_tag();
+        Ok(canonical)
+    }
+
+    /// Returns a copy without the `<digest>` section. Fails when there is no `<tag>` section.
+    pub fn to_tagged(&self) -> Result<ImageName, MissingSection> {
+        if !self.is_tagged() {
+            return Err(self.missing(Section::Tag));
+        }
+        let mut tagged = self.clone();
+        tagged.clear_digest();
+        Ok(tagged)
+    }
+}
+
+impl_image_name_common!(ImageName);
+
+impl FromStr for ImageName {
+    type Err = InvalidContainerImageNameMarker;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ImageNameRef::new(s).map(ImageNameRef::to_owned)
+    }
+}
+
+impl From<ImageNameRef<'_>> for ImageName {
+    fn from(value: ImageNameRef<'_>) -> Self {
+        value.to_owned()
+    }
+}
+
+impl TryFrom<String> for ImageName {
+    type Error = InvalidContainerImageName;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl From<ImageName> for String {
+    fn from(value: ImageName) -> Self {
+        value.buffer
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for ImageName {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.buffer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for ImageName {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Self::new(Deserialize::deserialize(deserializer)?).map_err(::serde::de::Error::custom)
+    }
+}
+
+/// A version of [`ImageName`] that only borrows its buffer.
+#[derive(Copy, Clone)]
+pub struct ImageNameRef<'a> {
+    buffer: &'a str,
+    indices: Indices,
+}
+
+impl<'a> ImageNameRef<'a> {
+    pub fn new(value: &'a str) -> Result<Self, InvalidContainerImageNameMarker> {
+        Self::new_with_max_length(value, DEFAULT_MAX_LENGTH)
+    }
+
+    /// See [`ImageName::new_with_max_length`].
+    pub fn new_with_max_length(
+        value: &'a str,
+        max_length: usize,
+    ) -> Result<Self, InvalidContainerImageNameMarker> {
+        Ok(Self {
+            buffer: value,
+            indices: Indices::parse(value, max_length)?,
+        })
+    }
+
+    /// Like [`ImageNameRef::new`], but usable in const contexts, see [`image_name!`].
+    pub const fn new_const(value: &'a str) -> Result<Self, InvalidContainerImageNameMarker> {
+        match Indices::parse_const(value, DEFAULT_MAX_LENGTH) {
+            Ok(indices) => Ok(Self {
+                buffer: value,
+                indices,
+            }),
+            Err(reason) => Err(InvalidContainerImageNameMarker { reason }),
+        }
+    }
+
+    /// The sections of the builder borrow from the buffer of `self`, so building only allocates the buffer of the
+    /// result.
+    pub fn as_builder(self) -> ImageNameBuilder<'a> {
+        let mut builder = ImageNameBuilder::new(self.path());
+        if let Some(registry) = self.registry() {
+            builder = builder.with_registry(registry);
+        }
+        if let Some(tag) = self.tag() {
+            builder = builder.with_tag(TagRef(tag));
+        }
+        if let Some(digest) = self.digest() {
+            builder = builder.with_digest(digest);
+        }
+        builder
+    }
+
+    /// Returns a copy of this image name with the `<registry>` section replaced.
+    pub fn with_registry(self, registry: &str) -> Result<ImageName, InvalidContainerImageName> {
+        Ok(self.as_builder().with_registry(registry).build()?)
+    }
+
+    /// Returns a copy of this image name with the `<tag>` section replaced.
+    pub fn with_tag(self, tag: &str) -> Result<ImageName, InvalidContainerImageName> {
+        Ok(self.as_builder().with_tag(tag).build()?)
+    }
+
+    /// Returns a copy of this image name with the `<digest>` section replaced.
+    pub fn with_digest(self, digest: &str) -> Result<ImageName, InvalidContainerImageName> {
+        Ok(self.as_builder().with_digest(digest).build()?)
+    }
+
+    /// Returns a copy of this image name with the defaults of Docker applied, like `ParseNormalizedNamed` followed by
+    /// `TagNameOnly` in [normalize.go](https://github.com/distribution/distribution/blob/v2.7.1/reference/normalize.go).
+    /// A missing `<registry>` section becomes `docker.io`, a single-component `<path>` on `docker.io` gets the
+    /// `library/` namespace and a missing `<tag>` becomes `latest` unless there is a `<digest>`. Names with another
+    /// registry only get the tag.
+    pub fn normalize(self) -> ImageName {
+        let mut builder = self.as_builder();
+        let registry = match self.registry() {
+            None | Some(LEGACY_DEFAULT_DOMAIN) => {
+                builder = builder.with_registry(DEFAULT_DOMAIN);
+                DEFAULT_DOMAIN
+            }
+            Some(registry) => registry,
+        };
+        if registry == DEFAULT_DOMAIN && !self.path().contains('/') {
+            builder = builder.with_path(format!("{OFFICIAL_REPOSITORY_NAMESPACE}/{}", self.path()));
+        }
+        if self.tag().is_none() && self.digest().is_none() {
+            builder = builder.with_tag(DEFAULT_TAG);
+        }
+        builder
+            .build()
+            .expect("normalizing a valid image name keeps it valid")
+    }
+
+    /// Returns the name the way the Docker CLI shows it, with the defaults that [`normalize`](Self::normalize) applies
+    /// left out: the `docker.io` registry, the `library/` namespace and the `latest` tag. The registry is kept when
+    /// the first component of the path would otherwise be mistaken for a registry, so the result always names the same
+    /// repository. With `truncate_digest`, the `<hex>` section of the digest is cut to 12 characters, which no longer
+    /// parses and is only meant for display.
+    pub fn familiar_name(self, truncate_digest: bool) -> Cow<'a, str> {
+        let path = self.path();
+        let mut start = 0;
+        let first_component = path.split('/').next().unwrap_or(path);
+        if matches!(
+            self.registry(),
+            None | Some(DEFAULT_DOMAIN | LEGACY_DEFAULT_DOMAIN)
+        ) && !first_component.contains('.')
+            && first_component != "localhost"
+        {
+            start = self.indices.path_start;
+            if let Some(name) = path
+                .strip_prefix(OFFICIAL_REPOSITORY_NAMESPACE)
+                .and_then(|rest| rest.strip_prefix('/'))
+                .filter(|name| !name.contains('/'))
+            {
+                start += path.len() - name.len();
+            }
+        }
+
+        let elide_tag = self.tag() == Some(DEFAULT_TAG);
+        let truncate_hex = truncate_digest
+            && self
+                .digest_hex()
+                .is_some_and(|hex| hex.len() > SHORT_DIGEST_HEX_LENGTH);
+        if !elide_tag && !truncate_hex {
+            return Cow::Borrowed(&self.buffer[start..]);
+        }
+
+        let mut familiar =
+            self.buffer[start..self.indices.path_range(self.buffer.len()).end].to_owned();
+        if let Some(tag) = self.tag().filter(|_| !elide_tag) {
+            familiar.push(TAG_PREFIX);
+            familiar.push_str(tag);
+        }
+        if let (Some(algorithm), Some(hex)) = (self.digest_algorithm(), self.digest_hex()) {
+            familiar.push(DIGEST_ALGORITHM_PREFIX);
+            familiar.push_str(algorithm);
+            familiar.push(DIGEST_HEX_PREFIX);
+            familiar.push_str(if truncate_hex {
+                &hex[..SHORT_DIGEST_HEX_LENGTH]
+            } else {
+                hex
+            });
+        }
+        Cow::Owned(familiar)
+    }
+
+    pub fn to_owned(self) -> ImageName {
+        ImageName {
+            buffer: self.buffer.to_owned(),
+            indices: self.indices,
+        }
+    }
+
+    pub const fn as_str(self) -> &'a str {
+        self.buffer
+    }
+
+    /// Like [`ImageNameRef::registry`], but usable in const contexts, such as on the result of [`image_name!`].
+    pub const fn registry_const(self) -> Option<&'a str> {
+        match self.indices.registry_start {
+            Some(_) => Some(
+                self.buffer
+                    .split_at(self.indices.path_start - REGISTRY_SUFFIX.len_utf8())
+                    .0,
+            ),
+            None => None,
+        }
+    }
+}
+
+impl_image_name_common!(ImageNameRef<'a>);
+
+impl PartialEq<ImageNameRef<'_>> for ImageName {
+    fn eq(&self, other: &ImageNameRef<'_>) -> bool {
+        self.buffer == other.buffer
+    }
+}
+
+impl PartialEq<ImageName> for ImageNameRef<'_> {
+    fn eq(&self, other: &ImageName) -> bool {
+        self.buffer == other.buffer
+    }
+}
+
+impl<'a> From<&'a ImageName> for ImageNameRef<'a> {
+    fn from(value: &'a ImageName) -> Self {
+        value.as_ref()
+    }
+}
+
+impl<'a> TryFrom<&'a str> for ImageNameRef<'a> {
+    type Error = InvalidContainerImageNameMarker;
+
+    fn try_from(value: &'a str) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl<'a> From<ImageNameRef<'a>> for &'a str {
+    fn from(value: ImageNameRef<'a>) -> Self {
+        value.buffer
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for ImageNameRef<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.buffer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for ImageNameRef<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(ImageNameRefVisitor)
+    }
+}
+
+/// Borrows the image name from the input of the deserializer. Input that can not be borrowed, such as a string with
+/// escape sequences or a reader, has no buffer that outlives the deserializer.
+#[cfg(feature = "serde")]
+struct ImageNameRefVisitor;
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::de::Visitor<'de> for ImageNameRefVisitor {
+    type Value = ImageNameRef<'de>;
+
+    fn expecting(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("a container image name borrowed from the input")
+    }
+
+    fn visit_borrowed_str<E>(self, value: &'de str) -> Result<Self::Value, E>
+    where
+        E: ::serde::de::Error,
+    {
+        ImageNameRef::new(value).map_err(E::custom)
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: ::serde::de::Error,
+    {
+        Err(E::custom(format_args!(
+            "can not borrow container image name {value:?} from the input, which happens for strings with escape sequences and for deserializers that read from a stream, deserialize an `ImageName` instead"
+        )))
+    }
+}
+
+/// The grammar of a reference for the `pattern` of the JSON schema.
+#[cfg(feature = "schemars")]
+const SCHEMA_PATTERN: &str = concat!(
+    r"^(?:",
+    registry_pattern!(),
+    r"\/)?",
+    path_component_pattern!(),
+    r"(?:\/",
+    path_component_pattern!(),
+    r")*(?::",
+    tag_pattern!(),
+    r")?(?:@",
+    algorithm_pattern!(),
+    r":",
+    hex_pattern!(),
+    r")?$"
+);
+
+/// The schema of [`ImageName`] and [`ImageNameRef`], which both serialize to the same string. The pattern does not
+/// check the lengths of the sections, which the description mentions instead.
+#[cfg(feature = "schemars")]
+fn json_schema() -> schemars::schema::Schema {
+    use schemars::schema::{InstanceType, Metadata, SchemaObject, StringValidation};
+
+    SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        string: Some(Box::new(StringValidation {
+            max_length: Some(DEFAULT_MAX_LENGTH as u32),
+            min_length: Some(1),
+            pattern: Some(SCHEMA_PATTERN.to_owned()),
+        })),
+        metadata: Some(Box::new(Metadata {
+            description: Some(format!(
+                "A container image name `[registry/]path[:tag][@digest]`, such as `docker.io/library/ubuntu:24.04`. The registry and path are at most {NAME_MAX_LENGTH} characters long together."
+            )),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+#[cfg(feature = "schemars")]
+impl schemars::JsonSchema for ImageName {
+    fn schema_name() -> String {
+        "ImageName".to_owned()
+    }
+
+    fn schema_id() -> Cow<'static, str> {
+        Cow::Borrowed(concat!(module_path!(), "::ImageName"))
+    }
+
+    fn json_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        json_schema()
+    }
+}
+
+/// Shares the schema of [`ImageName`], so that both end up as the same definition.
+#[cfg(feature = "schemars")]
+impl schemars::JsonSchema for ImageNameRef<'_> {
+    fn schema_name() -> String {
+        <ImageName as schemars::JsonSchema>::schema_name()
+    }
+
+    fn schema_id() -> Cow<'static, str> {
+        <ImageName as schemars::JsonSchema>::schema_id()
+    }
+
+    fn json_schema(generator: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        <ImageName as schemars::JsonSchema>::json_schema(generator)
+    }
+}
+
+enum ImageNameBuilderRegistry<'a> {
+    Registry(Cow<'a, str>),
+    DomainPort {
+        domain: Cow<'a, str>,
+        port: Option<Cow<'a, str>>,
+    },
+}
+
+impl ImageNameBuilderRegistry<'_> {
+    fn len(&self) -> usize {
+        (match self {
+            ImageNameBuilderRegistry::Registry(registry) => registry.len(),
+            ImageNameBuilderRegistry::DomainPort { domain, port } => {
+                domain.len()
+                    + port
+                        .as_ref()
+                        .map(|port| PORT_PREFIX.len_utf8() + port.len())
+                        .unwrap_or_default()
+            }
+        }) + REGISTRY_SUFFIX.len_utf8()
+    }
+
+    fn write(&self, buffer: &mut String) {
+        match self {
+            ImageNameBuilderRegistry::Registry(registry) => {
+                buffer.push_str(registry);
+            }
+            ImageNameBuilderRegistry::DomainPort { domain, port } => {
+                buffer.push_str(domain);
+                if let Some(port) = port {
+                    buffer.push(PORT_PREFIX);
+                    buffer.push_str(port);
+                }
+            }
+        }
+        buffer.push(REGISTRY_SUFFIX)
+    }
+}
+
+enum ImageNameBuilderDigest<'a> {
+    Digest(Cow<'a, str>),
+    AlgorithmHex {
+        algorithm: Cow<'a, str>,
+        hex: Cow<'a, str>,
+    },
+}
+
+impl ImageNameBuilderDigest<'_> {
+    fn len(&self) -> usize {
+        DIGEST_ALGORITHM_PREFIX.len_utf8()
+            + (match self {
+                ImageNameBuilderDigest::Digest(digest) => digest.len(),
+                ImageNameBuilderDigest::AlgorithmHex { algorithm, hex } => {
+                    algorithm.len() + DIGEST_HEX_PREFIX.len_utf8() + hex.len()
+                }
+            })
+    }
+
+    fn write(&self, buffer: &mut String) {
+        buffer.push(DIGEST_ALGORITHM_PREFIX);
+        match self {
+            ImageNameBuilderDigest::Digest(digest) => buffer.push_str(digest),
+            ImageNameBuilderDigest::AlgorithmHex { algorithm, hex } => {
+                buffer.push_str(algorithm);
+                buffer.push(DIGEST_HEX_PREFIX);
                 buffer.push_str(hex);
             }
-        }
+        }
+    }
+}
+
+/// The `<port>` section of [`ImageNameBuilder::with_domain_and_port`], from a number, a string, or `None` for no port.
+/// Unlike the ports of parsed names, which the grammar allows any number of digits for, [`ImageNameBuilder::build`]
+/// checks that it is in `1..=65535`.
+pub struct PortInput<'a>(Option<Cow<'a, str>>);
+
+impl<'a> From<Option<Cow<'a, str>>> for PortInput<'a> {
+    fn from(value: Option<Cow<'a, str>>) -> Self {
+        Self(value)
+    }
+}
+
+impl<'a> From<Cow<'a, str>> for PortInput<'a> {
+    fn from(value: Cow<'a, str>) -> Self {
+        Self(Some(value))
+    }
+}
+
+impl<'a> From<&'a str> for PortInput<'a> {
+    fn from(value: &'a str) -> Self {
+        Self(Some(Cow::Borrowed(value)))
+    }
+}
+
+impl From<String> for PortInput<'_> {
+    fn from(value: String) -> Self {
+        Self(Some(Cow::Owned(value)))
+    }
+}
+
+impl From<u16> for PortInput<'_> {
+    fn from(value: u16) -> Self {
+        Self(Some(Cow::Owned(value.to_string())))
+    }
+}
+
+/// Whether a `<port>` section is a port number that can be connected to.
+fn is_port_number(port: &str) -> bool {
+    port.parse::<u16>().is_ok_and(|port| port != 0)
+}
+
+/// The `<tag>` section of an [`ImageNameBuilder`], from a string or a [`Tag`]. Only strings are checked by
+/// [`ImageNameBuilder::build`].
+pub struct TagInput<'a> {
+    value: Cow<'a, str>,
+    checked: bool,
+}
+
+impl<'a> From<Cow<'a, str>> for TagInput<'a> {
+    fn from(value: Cow<'a, str>) -> Self {
+        Self {
+            value,
+            checked: false,
+        }
+    }
+}
+
+impl<'a> From<&'a str> for TagInput<'a> {
+    fn from(value: &'a str) -> Self {
+        Cow::Borrowed(value).into()
+    }
+}
+
+impl<'a> From<&'a String> for TagInput<'a> {
+    fn from(value: &'a String) -> Self {
+        Cow::Borrowed(value.as_str()).into()
+    }
+}
+
+impl From<String> for TagInput<'_> {
+    fn from(value: String) -> Self {
+        Cow::<str>::Owned(value).into()
+    }
+}
+
+impl<'a> From<TagRef<'a>> for TagInput<'a> {
+    fn from(value: TagRef<'a>) -> Self {
+        Self {
+            value: Cow::Borrowed(value.0),
+            checked: true,
+        }
+    }
+}
+
+impl<'a> From<&'a Tag> for TagInput<'a> {
+    fn from(value: &'a Tag) -> Self {
+        value.as_ref().into()
+    }
+}
+
+impl From<Tag> for TagInput<'_> {
+    fn from(value: Tag) -> Self {
+        Self {
+            value: Cow::Owned(value.0),
+            checked: true,
+        }
+    }
+}
+
+pub struct ImageNameBuilder<'a> {
+    registry: Option<ImageNameBuilderRegistry<'a>>,
+    path: Cow<'a, str>,
+    tag: Option<TagInput<'a>>,
+    digest: Option<ImageNameBuilderDigest<'a>>,
+}
+
+impl<'a> ImageNameBuilder<'a> {
+    fn new(path: impl Into<Cow<'a, str>>) -> Self {
+        Self {
+            registry: None,
+            path: path.into(),
+            tag: None,
+            digest: None,
+        }
+    }
+
+    pub fn with_registry(mut self, registry: impl Into<Cow<'a, str>>) -> Self {
+        self.registry = Some(ImageNameBuilderRegistry::Registry(registry.into()));
+        self
+    }
+
+    /// Sets the `<registry>` section from its `<domain>` and `<port>` sections, such as `("reg.io", 5000)` or
+    /// `("reg.io", None)`.
+    pub fn with_domain_and_port(
+        mut self,
+        domain: impl Into<Cow<'a, str>>,
+        port: impl Into<PortInput<'a>>,
+    ) -> Self {
+        self.registry = Some(ImageNameBuilderRegistry::DomainPort {
+            domain: domain.into(),
+            port: port.into().0,
+        });
+        self
+    }
+
+    pub fn without_registry(mut self) -> Self {
+        self.registry = None;
+        self
+    }
+
+    pub fn with_path(mut self, path: impl Into<Cow<'a, str>>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// Sets the `<tag>` section, from a string or a [`Tag`], which is not checked again.
+    pub fn with_tag(mut self, tag: impl Into<TagInput<'a>>) -> Self {
+        self.tag = Some(tag.into());
+        self
+    }
+
+    pub fn without_tag(mut self) -> Self {
+        self.tag = None;
+        self
+    }
+
+    /// Sets the `<digest>` section, from a string or a [`Digest`].
+    pub fn with_digest(mut self, digest: impl Into<Cow<'a, str>>) -> Self {
+        self.digest = Some(ImageNameBuilderDigest::Digest(digest.into()));
+        self
+    }
+
+    pub fn with_algorithm_and_hex(
+        mut self,
+        algorithm: impl Into<Cow<'a, str>>,
+        hex: impl Into<Cow<'a, str>>,
+    ) -> Self {
+        self.digest = Some(ImageNameBuilderDigest::AlgorithmHex {
+            algorithm: algorithm.into(),
+            hex: hex.into(),
+        });
+        self
+    }
+
+    pub fn without_digest(mut self) -> Self {
+        self.digest = None;
+        self
+    }
+
+    /// Combines the sections into an image name. Each section is validated on its own, so the error names the section
+    /// that is invalid, and the combined name is not parsed again.
+    pub fn build(self) -> Result<ImageName, InvalidImageNameSection> {
+        let mut buffer = String::with_capacity(
+            self.registry.as_ref().map(|x| x.len()).unwrap_or_default()
+                + self.path.len()
+                + self
+                    .tag
+                    .as_ref()
+                    .map(|x| x.value.len() + TAG_PREFIX.len_utf8())
+                    .unwrap_or_default()
+                + self.digest.as_ref().map(|x| x.len()).unwrap_or_default(),
+        );
+
+        let check_port_number = matches!(
+            self.registry,
+            Some(ImageNameBuilderRegistry::DomainPort { port: Some(_), .. })
+        );
+        let registry_range = self.registry.map(|registry| {
+            registry.write(&mut buffer);
+            0..buffer.len() - REGISTRY_SUFFIX.len_utf8()
+        });
+        let path_range = buffer.len()..buffer.len() + self.path.len();
+        buffer.push_str(&self.path);
+        let tag_checked = self.tag.as_ref().is_some_and(|tag| tag.checked);
+        let tag_range = self.tag.map(|tag| {
+            buffer.push(TAG_PREFIX);
+            buffer.push_str(&tag.value);
+            buffer.len() - tag.value.len()..buffer.len()
+        });
+        let digest_range = self.digest.map(|digest| {
+            let start = buffer.len() + DIGEST_ALGORITHM_PREFIX.len_utf8();
+            digest.write(&mut buffer);
+            start..buffer.len()
+        });
+
+        let sections = [
+            registry_range
+                .clone()
+                .map(|range| (Section::Registry, range)),
+            Some((Section::Path, path_range.clone())),
+            tag_range.clone().map(|range| (Section::Tag, range)),
+            digest_range.clone().map(|range| (Section::Digest, range)),
+        ];
+        let invalid =
+            |section: Section, range: Range<usize>, reason: Reason| InvalidImageNameSection {
+                section,
+                name: buffer.clone(),
+                range,
+                reason,
+            };
+        // NOTE: The length limits apply to several sections, so the section that contains the first byte over the
+        // limit is blamed.
+        let too_long = |reason: Reason| {
+            let (section, range) = sections
+                .iter()
+                .flatten()
+                .rfind(|(_, range)| range.start <= reason.offset())
+                .cloned()
+                .unwrap_or((Section::Path, path_range.clone()));
+            let offset = reason.offset() - range.start;
+            let reason = match reason {
+                Reason::NameTooLong { length, .. } => Reason::NameTooLong { offset, length },
+                Reason::ReferenceTooLong { length, .. } => {
+                    Reason::ReferenceTooLong { offset, length }
+                }
+                reason => reason,
+            };
+            invalid(section, range, reason)
+        };
+
+        let mut registry_start = match &registry_range {
+            Some(range) => {
+                let port_start = check_registry(&buffer[range.clone()])
+                    .map_err(|reason| invalid(Section::Registry, range.clone(), reason))?;
+                if let Some(port_start) = port_start.filter(|_| check_port_number) {
+                    if !is_port_number(&buffer[port_start..range.end]) {
+                        return Err(invalid(
+                            Section::Registry,
+                            range.clone(),
+                            Reason::InvalidPort { offset: port_start },
+                        ));
+                    }
+                }
+                Some(IndicesRegistry { port_start })
+            }
+            None => None,
+        };
+        let mut path_start = path_range.start;
+        // NOTE: Without a registry, a first path component that looks like a registry is one, like when parsing. It is
+        // found before the path is checked, because a registry such as `Reg.IO` or `localhost:5000` is no valid path.
+        if registry_start.is_none() {
+            if let Some(registry_end) = find_registry_end_const(buffer.as_bytes(), path_range.end) {
+                let port_start = check_registry(&buffer[..registry_end])
+                    .map_err(|reason| invalid(Section::Path, path_range.clone(), reason))?;
+                registry_start = Some(IndicesRegistry { port_start });
+                path_start = registry_end + REGISTRY_SUFFIX.len_utf8();
+            }
+        }
+        check_path(&buffer[path_start..path_range.end])
+            .map_err(|reason| invalid(Section::Path, path_start..path_range.end, reason))?;
+        check_name_length(path_range.end).map_err(too_long)?;
+        if let Some(range) = tag_range.as_ref().filter(|_| !tag_checked) {
+            check_tag(&buffer[range.clone()])
+                .map_err(|reason| invalid(Section::Tag, range.clone(), reason))?;
+        }
+        let digest_start = match &digest_range {
+            Some(range) => Some(IndicesDigest {
+                algorithm_start: range.start,
+                hex_start: range.start
+                    + check_digest(&buffer[range.clone()])
+                        .map_err(|reason| invalid(Section::Digest, range.clone(), reason))?,
+            }),
+            None => None,
+        };
+        if buffer.len() > DEFAULT_MAX_LENGTH {
+            return Err(too_long(Reason::ReferenceTooLong {
+                offset: DEFAULT_MAX_LENGTH,
+                length: buffer.len(),
+            }));
+        }
+
+        let indices = Indices {
+            registry_start,
+            path_start,
+            tag_start: tag_range.map(|range| range.start),
+            digest_start,
+        };
+        Ok(ImageName { buffer, indices })
+    }
+}
+
+/// The `<algorithm>` section of a digest. The algorithms registered by the
+/// [OCI image spec](https://github.com/opencontainers/image-spec/blob/v1.1.0/descriptor.md#registered-algorithms) have
+/// their own variants, other algorithms keep their name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Algorithm {
+    Sha256,
+    Sha512,
+    Other(String),
+}
+
+impl Algorithm {
+    fn new(algorithm: &str) -> Self {
+        match algorithm {
+            "sha256" => Algorithm::Sha256,
+            "sha512" => Algorithm::Sha512,
+            _ => Algorithm::Other(algorithm.to_owned()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Sha512 => "sha512",
+            Algorithm::Other(algorithm) => algorithm,
+        }
+    }
+}
+
+impl core::fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Why a digest is invalid. The offsets are in bytes from the start of the digest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InvalidDigest {
+    /// The digest does not match the grammar documented at [`ImageName`].
+    Grammar(InvalidContainerImageNameReason),
+    /// The `<hex>` section has an odd number of characters, so it does not encode whole bytes. Only algorithms other
+    /// than those of [`Algorithm`] can have one.
+    OddHexLength { offset: usize, found: usize },
+}
+
+impl core::error::Error for InvalidDigest {}
+
+impl core::fmt::Display for InvalidDigest {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            InvalidDigest::Grammar(reason) => write!(f, "invalid digest: {reason}"),
+            InvalidDigest::OddHexLength { offset, found } => write!(
+                f,
+                "invalid digest: the digest hex at byte {offset} has {found} characters, which do not encode whole bytes"
+            ),
+        }
+    }
+}
+
+/// A parsed `<digest>` section, `<algorithm>:<hex>`, with the `<hex>` section decoded. Digests that only differ in the
+/// case of their hex are equal, and display with lowercase hex.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Digest {
+    algorithm: Algorithm,
+    bytes: Vec<u8>,
+}
+
+impl Digest {
+    /// Decodes the sections of a digest that matches the grammar.
+    fn from_sections(algorithm: &str, hex: &str) -> Result<Self, InvalidDigest> {
+        fn nibble(byte: u8) -> u8 {
+            match byte {
+                b'0'..=b'9' => byte - b'0',
+                b'a'..=b'f' => byte - b'a' + 10,
+                b'A'..=b'F' => byte - b'A' + 10,
+                _ => unreachable!("the grammar only allows hexadecimal digits"),
+            }
+        }
+
+        if !hex.len().is_multiple_of(2) {
+            return Err(InvalidDigest::OddHexLength {
+                offset: algorithm.len() + DIGEST_HEX_PREFIX.len_utf8(),
+                found: hex.len(),
+            });
+        }
+        Ok(Self {
+            algorithm: Algorithm::new(algorithm),
+            bytes: hex
+                .as_bytes()
+                .chunks_exact(2)
+                .map(|pair| nibble(pair[0]) << 4 | nibble(pair[1]))
+                .collect(),
+        })
+    }
+
+    pub fn algorithm(&self) -> &Algorithm {
+        &self.algorithm
+    }
+
+    /// Returns the decoded `<hex>` section.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the `<hex>` section in lowercase.
+    pub fn hex(&self) -> String {
+        self.bytes
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+}
+
+impl FromStr for Digest {
+    type Err = InvalidDigest;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex_start = check_digest(s).map_err(InvalidDigest::Grammar)?;
+        let algorithm = &s[..hex_start - DIGEST_HEX_PREFIX.len_utf8()];
+        Self::from_sections(algorithm, &s[hex_start..])
+    }
+}
+
+impl core::fmt::Display for Digest {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}{DIGEST_HEX_PREFIX}{}", self.algorithm, self.hex())
+    }
+}
+
+/// Lets [`ImageNameBuilder::with_digest`] accept a `&Digest`.
+impl From<&Digest> for Cow<'_, str> {
+    fn from(value: &Digest) -> Self {
+        Cow::Owned(value.to_string())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Digest {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Digest {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        <Cow<'de, str>>::deserialize(deserializer)?
+            .parse()
+            .map_err(::serde::de::Error::custom)
+    }
+}
+
+/// A `<tag>` that does not match the grammar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidTag {
+    value: String,
+}
+
+impl InvalidTag {
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+impl core::error::Error for InvalidTag {}
+
+impl core::fmt::Display for InvalidTag {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "invalid tag {:?}: a tag starts with a word character, continues with word characters, `.` and `-`, and has at most {TAG_MAX_LENGTH} characters",
+            self.value
+        )
+    }
+}
+
+fn is_tag_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'.' | b'-')
+}
+
+/// A `<tag>` section that matches the grammar, so that [`ImageNameBuilder::with_tag`] does not check it again. See
+/// [`TagRef`] for the borrowed version.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Tag(String);
+
+impl Tag {
+    pub fn new(value: String) -> Result<Self, InvalidTag> {
+        match check_tag(&value) {
+            Ok(()) => Ok(Self(value)),
+            Err(_) => Err(InvalidTag { value }),
+        }
+    }
+
+    /// Converts the input into a tag, like [`Tag::sanitize_lossy_with_max_length`] with [`TAG_MAX_LENGTH`].
+    pub fn sanitize_lossy(input: &str) -> Option<Self> {
+        Self::sanitize_lossy_with_max_length(input, TAG_MAX_LENGTH)
+    }
+
+    /// Converts the input into a tag of at most `max_length` characters, so that it can be followed by a suffix. Runs
+    /// of characters other than ASCII word characters, `.` and `-` are replaced by a single `-`, and leading and
+    /// trailing dots and dashes are removed. Returns `None` if nothing is left.
+    pub fn sanitize_lossy_with_max_length(input: &str, max_length: usize) -> Option<Self> {
+        let max_length = max_length.min(TAG_MAX_LENGTH);
+        let mut output = String::with_capacity(input.len().min(max_length));
+        let mut replaced = false;
+        for byte in input.bytes() {
+            if is_tag_byte(byte) {
+                output.push(char::from(byte));
+                replaced = false;
+            } else if !replaced {
+                output.push('-');
+                replaced = true;
+            }
+        }
+        let output = output.trim_start_matches(['.', '-']);
+        let output = output[..output.len().min(max_length)].trim_end_matches(['.', '-']);
+        (!output.is_empty()).then(|| Self(output.to_owned()))
+    }
+
+    pub fn as_ref(&self) -> TagRef<'_> {
+        TagRef(&self.0)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for Tag {
+    type Err = InvalidTag;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TagRef::new(s).map(TagRef::to_owned)
+    }
+}
+
+impl TryFrom<String> for Tag {
+    type Error = InvalidTag;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl From<TagRef<'_>> for Tag {
+    fn from(value: TagRef<'_>) -> Self {
+        value.to_owned()
+    }
+}
+
+impl From<Tag> for String {
+    fn from(value: Tag) -> Self {
+        value.0
+    }
+}
+
+impl core::fmt::Display for Tag {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.pad(&self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Tag {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Tag {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Self::new(Deserialize::deserialize(deserializer)?).map_err(::serde::de::Error::custom)
+    }
+}
+
+/// A version of [`Tag`] that only borrows its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TagRef<'a>(&'a str);
+
+impl<'a> TagRef<'a> {
+    pub fn new(value: &'a str) -> Result<Self, InvalidTag> {
+        match check_tag(value) {
+            Ok(()) => Ok(Self(value)),
+            Err(_) => Err(InvalidTag {
+                value: value.to_owned(),
+            }),
+        }
+    }
+
+    pub fn as_str(self) -> &'a str {
+        self.0
+    }
+
+    pub fn to_owned(self) -> Tag {
+        Tag(self.0.to_owned())
+    }
+}
+
+impl<'a> TryFrom<&'a str> for TagRef<'a> {
+    type Error = InvalidTag;
+
+    fn try_from(value: &'a str) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl<'a> From<&'a Tag> for TagRef<'a> {
+    fn from(value: &'a Tag) -> Self {
+        value.as_ref()
+    }
+}
+
+impl core::fmt::Display for TagRef<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.pad(self.0)
+    }
+}
+
+/// Generates image names that follow the grammar, section by section, for property tests and fuzzing.
+#[cfg(any(test, feature = "arbitrary"))]
+mod generate {
+    use ::arbitrary::{Result, Unstructured};
+
+    use super::*;
+
+    const LOWER_ALPHANUMERIC: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+    const ALPHANUMERIC: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+    const HEX: &[u8] = b"0123456789abcdefABCDEF";
+
+    /// The sections of an image name, which are joined with their prefixes and suffixes to form the name.
+    #[derive(Debug)]
+    pub(crate) struct Sections {
+        pub domain: Option<String>,
+        pub port: Option<String>,
+        pub path: String,
+        pub tag: Option<String>,
+        pub algorithm: Option<String>,
+        pub hex: Option<String>,
+    }
+
+    impl Sections {
+        pub fn registry(&self) -> Option<String> {
+            let domain = self.domain.as_deref()?;
+            Some(match &self.port {
+                Some(port) => format!("{domain}{PORT_PREFIX}{port}"),
+                None => domain.to_owned(),
+            })
+        }
+
+        pub fn digest(&self) -> Option<String> {
+            Some(format!(
+                "{}{DIGEST_HEX_PREFIX}{}",
+                self.algorithm.as_deref()?,
+                self.hex.as_deref()?
+            ))
+        }
+    }
+
+    impl core::fmt::Display for Sections {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            if let Some(registry) = self.registry() {
+                write!(f, "{registry}{REGISTRY_SUFFIX}")?;
+            }
+            f.write_str(&self.path)?;
+            if let Some(tag) = &self.tag {
+                write!(f, "{TAG_PREFIX}{tag}")?;
+            }
+            if let Some(digest) = self.digest() {
+                write!(f, "{DIGEST_ALGORITHM_PREFIX}{digest}")?;
+            }
+            Ok(())
+        }
+    }
+
+    fn string(u: &mut Unstructured, alphabet: &[u8], len: usize) -> Result<String> {
+        (0..len)
+            .map(|_| u.choose(alphabet).map(|&byte| char::from(byte)))
+            .collect()
+    }
+
+    fn join(
+        u: &mut Unstructured,
+        count: usize,
+        separators: &[&str],
+        mut part: impl FnMut(&mut Unstructured) -> Result<String>,
+    ) -> Result<String> {
+        let mut joined = part(u)?;
+        for _ in 1..count {
+            joined.push_str(u.choose(separators)?);
+            joined.push_str(&part(u)?);
+        }
+        Ok(joined)
+    }
+
+    fn domain_component(u: &mut Unstructured) -> Result<String> {
+        let len = u.int_in_range(1..=12)?;
+        let mut component = string(u, ALPHANUMERIC, len)?;
+        if len > 2 {
+            let hyphens = u.int_in_range(0..=2)?;
+            component.insert_str(1, &"-".repeat(hyphens));
+        }
+        Ok(component)
+    }
+
+    fn domain(u: &mut Unstructured, with_port: bool) -> Result<String> {
+        if u.ratio(1, 8)? {
+            return Ok("localhost".to_owned());
+        }
+        if u.ratio(1, 8)? {
+            let len = u.int_in_range(1..=39)?;
+            return Ok(format!("[{}]", string(u, b"0123456789abcdefABCDEF:", len)?));
+        }
+        // NOTE: A single component is only a domain when it has a port, see `registry_pattern!`.
+        let min = if with_port { 1 } else { 2 };
+        let count = u.int_in_range(min..=4)?;
+        join(u, count, &["."], domain_component)
+    }
+
+    fn path_component(u: &mut Unstructured) -> Result<String> {
+        let count = u.int_in_range(1..=3)?;
+        join(u, count, &[".", "_", "__", "-", "--"], |u| {
+            let len = u.int_in_range(1..=8)?;
+            string(u, LOWER_ALPHANUMERIC, len)
+        })
+    }
+
+    fn tag(u: &mut Unstructured) -> Result<String> {
+        let mut tag = string(
+            u,
+            b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789_",
+            1,
+        )?;
+        let len = u.int_in_range(0..=127)?;
+        tag.push_str(&string(
+            u,
+            b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789_.-",
+            len,
+        )?);
+        Ok(tag)
+    }
+
+    fn algorithm(u: &mut Unstructured) -> Result<String> {
+        if u.arbitrary()? {
+            return Ok(u.choose(&DIGEST_HEX_LENGTHS)?.0.to_owned());
+        }
+        let count = u.int_in_range(1..=3)?;
+        join(u, count, &["-", "_", "+", "."], |u| {
+            let mut part = string(u, &ALPHANUMERIC[..52], 1)?;
+            let len = u.int_in_range(0..=6)?;
+            part.push_str(&string(u, ALPHANUMERIC, len)?);
+            Ok(part)
+        })
+    }
+
+    pub(crate) fn sections(u: &mut Unstructured) -> Result<Sections> {
+        let (domain, port) = if u.arbitrary()? {
+            let with_port = u.arbitrary()?;
+            let domain = domain(u, with_port)?;
+            let port = if with_port {
+                let len = u.int_in_range(1..=5)?;
+                Some(string(u, b"0123456789", len)?)
+            } else {
+                None
+            };
+            (Some(domain), port)
+        } else {
+            (None, None)
+        };
+        let count = u.int_in_range(1..=4)?;
+        let mut path = join(u, count, &["/"], path_component)?;
+        // NOTE: Without a registry, a first component with a dot would be parsed as the registry, see
+        // `registry_pattern!`.
+        if domain.is_none() && count > 1 {
+            let first_end = path.find(PATH_COMPONENT_SEPARATOR).unwrap();
+            path.replace_range(..first_end, &path[..first_end].replace('.', "_"));
+        }
+        let tag = if u.arbitrary()? { Some(tag(u)?) } else { None };
+        let (algorithm, hex) = if u.arbitrary()? {
+            let algorithm = algorithm(u)?;
+            let len = match DIGEST_HEX_LENGTHS
+                .iter()
+                .find(|&&(name, _)| name == algorithm)
+            {
+                Some(&(_, len)) => len,
+                None => u.int_in_range(MIN_DIGEST_HEX_LENGTH..=160)?,
+            };
+            let hex = string(u, HEX, len)?;
+            (Some(algorithm), Some(hex))
+        } else {
+            (None, None)
+        };
+        Ok(Sections {
+            domain,
+            port,
+            path,
+            tag,
+            algorithm,
+            hex,
+        })
+    }
+}
+
+/// Generates names that follow the grammar and are within the length limits, built section by section.
+#[cfg(feature = "arbitrary")]
+impl<'a> ::arbitrary::Arbitrary<'a> for ImageName {
+    fn arbitrary(u: &mut ::arbitrary::Unstructured<'a>) -> ::arbitrary::Result<Self> {
+        let name = generate::sections(u)?.to_string();
+        Ok(ImageName::new(name).expect("generated image names should follow the grammar"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo-random bytes, see [SplitMix64](https://prng.di.unimi.it/splitmix64.c), so that failures of
+    /// the property tests reproduce.
+    fn random_bytes(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_add(0x9e3779b97f4a7c15);
+                let mut z = state;
+                z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+                (z ^ (z >> 31)) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn generated_names_round_trip() {
+        for seed in 0..2000 {
+            let bytes = random_bytes(seed, 1024);
+            let sections = generate::sections(&mut ::arbitrary::Unstructured::new(&bytes)).unwrap();
+            let value = sections.to_string();
+            let name = ImageNameRef::new(&value)
+                .unwrap_or_else(|error| panic!("{sections:?} should parse: {error}"));
+            assert_eq!(name.domain(), sections.domain.as_deref(), "{value}");
+            assert_eq!(name.port(), sections.port.as_deref(), "{value}");
+            assert_eq!(name.registry(), sections.registry().as_deref(), "{value}");
+            assert_eq!(name.path(), sections.path, "{value}");
+            assert_eq!(name.tag(), sections.tag.as_deref(), "{value}");
+            assert_eq!(
+                name.digest_algorithm(),
+                sections.algorithm.as_deref(),
+                "{value}"
+            );
+            assert_eq!(name.digest_hex(), sections.hex.as_deref(), "{value}");
+            assert_eq!(name.digest(), sections.digest().as_deref(), "{value}");
+
+            // Building from the sections produces the same indices as parsing.
+            let mut builder = ImageName::builder(name.path());
+            if let Some(registry) = name.registry() {
+                builder = builder.with_registry(registry);
+            }
+            if let Some(tag) = name.tag() {
+                builder = builder.with_tag(tag);
+            }
+            if let Some(digest) = name.digest() {
+                builder = builder.with_digest(digest);
+            }
+            let built = builder.build().unwrap();
+            assert_eq!(built, *value);
+            assert_eq!(
+                (built.registry(), built.port(), built.path(), built.tag()),
+                (name.registry(), name.port(), name.path(), name.tag()),
+                "{value}"
+            );
+            assert_eq!(
+                (built.digest_algorithm(), built.digest_hex()),
+                (name.digest_algorithm(), name.digest_hex()),
+                "{value}"
+            );
+
+            assert!(ImageNameRef::new_const(&value).is_ok(), "{value}");
+            assert_parses_like_the_grammar(&value);
+        }
+    }
+
+    /// The grammar as a single regex, which the parser is checked against.
+    static GRAMMAR_REGEX: std::sync::LazyLock<regex::Regex> = std::sync::LazyLock::new(|| {
+        regex::Regex::new(concat!(
+            r"^",
+            r"(?:(?P<registry>",
+            registry_pattern!(),
+            r")\/)?",
+            r"(?P<path>",
+            path_component_pattern!(),
+            r"(?:\/(?:",
+            path_component_pattern!(),
+            r"))*)",
+            r"(?::(?P<tag>",
+            tag_pattern!(),
+            r"))?",
+            r"(?:@(?P<algorithm>",
+            algorithm_pattern!(),
+            r"):(?P<hex>",
+            hex_pattern!(),
+            r"))?",
+            r"$"
+        ))
+        .unwrap()
+    });
+
+    /// Checks that the parser splits `value` like [`GRAMMAR_REGEX`] does, and that [`ImageNameRef::new_const`] agrees
+    /// with [`ImageNameRef::new`]. The regex does not check the lengths of the name and of the digest hex, so a name
+    /// that it matches may still be too long.
+    fn assert_parses_like_the_grammar(value: &str) {
+        let parsed = ImageNameRef::new(value);
+        assert_eq!(parsed, ImageNameRef::new_const(value), "{value:?}");
+        match (GRAMMAR_REGEX.captures(value), parsed) {
+            (Some(captures), Ok(name)) => {
+                let group = |group| captures.name(group).map(|m| m.as_str());
+                assert_eq!(
+                    [
+                        name.registry(),
+                        Some(name.path()),
+                        name.tag(),
+                        name.digest_algorithm(),
+                        name.digest_hex()
+                    ],
+                    [
+                        group("registry"),
+                        group("path"),
+                        group("tag"),
+                        group("algorithm"),
+                        group("hex")
+                    ],
+                    "{value:?}"
+                );
+            }
+            (Some(_), Err(error)) => assert!(
+                matches!(
+                    error.reason(),
+                    InvalidContainerImageNameReason::NameTooLong { .. }
+                        | InvalidContainerImageNameReason::ReferenceTooLong { .. }
+                        | InvalidContainerImageNameReason::DigestLength { .. }
+                ),
+                "{value:?} should parse: {error}"
+            ),
+            (None, Ok(_)) => panic!("{value:?} should not parse"),
+            (None, Err(_)) => {}
+        }
+    }
+
+    /// Checks that the sections of `name` are in bounds, in order and do not overlap, by joining them back into the
+    /// parsed string.
+    fn assert_sections_in_bounds(name: ImageNameRef, value: &str) {
+        let indices = name.indices;
+        let len = value.len();
+        let ranges = [
+            indices.registry_range(),
+            Some(indices.path_range(len)),
+            indices.tag_range(len),
+            indices.digest_range(len),
+        ];
+        let mut end = 0;
+        for range in ranges.iter().flatten() {
+            assert!(
+                end <= range.start && range.start <= range.end && range.end <= len,
+                "{value:?}: {ranges:?}"
+            );
+            end = range.end;
+        }
+        for (outer, inner) in [
+            (indices.registry_range(), indices.domain_range()),
+            (indices.registry_range(), indices.port_range()),
+            (indices.digest_range(len), indices.digest_algorithm_range()),
+            (indices.digest_range(len), indices.digest_hex_range(len)),
+        ] {
+            if let Some(inner) = inner {
+                let outer = outer.unwrap();
+                assert!(
+                    outer.start <= inner.start && inner.end <= outer.end,
+                    "{value:?}"
+                );
+            }
+        }
+
+        let mut joined = String::new();
+        if let Some(registry) = name.registry() {
+            joined.push_str(registry);
+            joined.push(REGISTRY_SUFFIX);
+        }
+        joined.push_str(name.path());
+        if let Some(tag) = name.tag() {
+            joined.push(TAG_PREFIX);
+            joined.push_str(tag);
+        }
+        if let Some(digest) = name.digest() {
+            joined.push(DIGEST_ALGORITHM_PREFIX);
+            joined.push_str(digest);
+        }
+        assert_eq!(joined, value);
+        // The other accessors slice as well.
+        let _ = (
+            name.domain(),
+            name.port(),
+            name.digest_algorithm(),
+            name.digest_hex(),
+        );
+    }
+
+    #[test]
+    fn any_string_errors_or_parses_within_bounds() {
+        const GRAMMAR: &[u8] = b"aZ09.-_:/@+";
+        let mut parsed = 0;
+        for seed in 0..20_000 {
+            let bytes = random_bytes(seed, 256);
+            let mut u = ::arbitrary::Unstructured::new(&bytes);
+            let value = match seed % 3 {
+                0 => u.arbitrary::<String>().unwrap(),
+                // Strings of the characters with a meaning in the grammar are more likely to parse.
+                1 => {
+                    let len = u.int_in_range(0..=40).unwrap();
+                    (0..len)
+                        .map(|_| char::from(*u.choose(GRAMMAR).unwrap()))
+                        .collect()
+                }
+                // Valid names with one character replaced are close to the edge cases of the grammar.
+                _ => {
+                    let mut value = generate::sections(&mut u).unwrap().to_string();
+                    let index = u.choose_index(value.len()).unwrap();
+                    let replacement = char::from(*u.choose(GRAMMAR).unwrap()).to_string();
+                    value.replace_range(index..index + 1, &replacement);
+                    value
+                }
+            };
+            if let Ok(name) = ImageNameRef::new(&value) {
+                parsed += 1;
+                assert_sections_in_bounds(name, &value);
+            }
+            if let Ok(name) = ImageName::new(value.clone()) {
+                assert_sections_in_bounds(name.as_ref(), &value);
+            }
+            assert_parses_like_the_grammar(&value);
+        }
+        // Make sure that the property is not only checked for errors.
+        assert!(parsed > 2_000, "{parsed}");
+    }
+
+    #[test]
+    fn image_name_macro_works() {
+        const NAME: ImageNameRef<'static> = image_name!(
+            "reg.io:5000/org/img:v1@sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef"
+        );
+        assert_eq!(NAME.domain(), Some("reg.io"));
+        assert_eq!(NAME.port(), Some("5000"));
+        assert_eq!(NAME.registry(), Some("reg.io:5000"));
+        assert_eq!(NAME.path(), "org/img");
+        assert_eq!(NAME.tag(), Some("v1"));
+        assert_eq!(NAME.digest_algorithm(), Some("sha256"));
+        assert_eq!(
+            NAME.digest_hex(),
+            Some("0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef")
+        );
+        assert_eq!(NAME, ImageNameRef::new(NAME.as_str()).unwrap());
+
+        // The macro is an expression as well.
+        let name = image_name!("localhost/img");
+        assert_eq!(name.registry(), Some("localhost"));
+        const REGISTRY: Option<&str> = image_name!("reg.io:5000/img").registry_const();
+        assert_eq!(REGISTRY, Some("reg.io:5000"));
+        assert_eq!(image_name!("img").registry_const(), None);
+        assert_eq!(name.normalize().as_str(), "localhost/img:latest");
+
+        // Like in Go, the word characters of tags are ASCII.
+        assert_eq!(
+            ImageNameRef::new_const("img:é").unwrap_err().reason(),
+            InvalidContainerImageNameReason::InvalidTag { offset: 4 }
+        );
+    }
+
+    #[test]
+    fn image_name_parsing_works() {
+        {
+            let name = ImageNameRef::new("org-name/img-name").unwrap();
+            assert_eq!(name.domain(), None);
+            assert_eq!(name.port(), None);
+            assert_eq!(name.registry(), None);
+            assert_eq!(name.path(), "org-name/img-name");
+            assert_eq!(name.tag(), None);
+            assert_eq!(name.digest(), None);
+            assert_eq!(name.digest_algorithm(), None);
+            assert_eq!(name.digest_hex(), None);
+        }
+
+        {
+            let name = ImageNameRef::new("reg.io/org-name/img-name:latest").unwrap();
+            assert_eq!(name.domain(), Some("reg.io"));
+            assert_eq!(name.port(), None);
+            assert_eq!(name.registry(), Some("reg.io"));
+            assert_eq!(name.path(), "org-name/img-name");
+            assert_eq!(name.tag(), Some("latest"));
+            assert_eq!(name.digest(), None);
+            assert_eq!(name.digest_algorithm(), None);
+            assert_eq!(name.digest_hex(), None);
+        }
+
+        {
+            let name = ImageNameRef::new("reg.io:12345/org-name/img-name:latest").unwrap();
+            assert_eq!(name.domain(), Some("reg.io"));
+            assert_eq!(name.port(), Some("12345"));
+            assert_eq!(name.registry(), Some("reg.io:12345"));
+            assert_eq!(name.path(), "org-name/img-name");
+            assert_eq!(name.tag(), Some("latest"));
+            assert_eq!(name.digest(), None);
+            assert_eq!(name.digest_algorithm(), None);
+            assert_eq!(name.digest_hex(), None);
+        }
+
+        {
+            let name = ImageNameRef::new("localhost:5000/img-name:dev").unwrap();
+            assert_eq!(name.domain(), Some("localhost"));
+            assert_eq!(name.port(), Some("5000"));
+            assert_eq!(name.registry(), Some("localhost:5000"));
+            assert_eq!(name.path(), "img-name");
+            assert_eq!(name.tag(), Some("dev"));
+
+            let name = ImageNameRef::new("localhost/img-name").unwrap();
+            assert_eq!(name.registry(), Some("localhost"));
+            assert_eq!(name.path(), "img-name");
+
+            let name = ImageNameRef::new("localhost").unwrap();
+            assert_eq!(name.registry(), None);
+            assert_eq!(name.path(), "localhost");
+        }
+
+        {
+            let name = ImageNameRef::new("[::1]/foo").unwrap();
+            assert_eq!(name.domain(), Some("[::1]"));
+            assert_eq!(name.port(), None);
+            assert_eq!(name.registry(), Some("[::1]"));
+            assert_eq!(name.path(), "foo");
+            assert_eq!(name.tag(), None);
+
+            let name = ImageNameRef::new("[2001:db8::1]:5000/org/img:tag").unwrap();
+            assert_eq!(name.domain(), Some("[2001:db8::1]"));
+            assert_eq!(name.port(), Some("5000"));
+            assert_eq!(name.registry(), Some("[2001:db8::1]:5000"));
+            assert_eq!(name.path(), "org/img");
+            assert_eq!(name.tag(), Some("tag"));
+
+            for value in ["[::1]/foo", "[2001:db8::1]:5000/org/img:tag"] {
+                assert_parses_like_the_grammar(value);
+            }
+            assert_eq!(
+                ImageNameRef::new("[::1:5000/foo").unwrap_err().reason(),
+                InvalidContainerImageNameReason::InvalidDomain { offset: 0 }
+            );
+        }
+
+        {
+            // A single-label domain is only a registry with a port.
+            let name = ImageNameRef::new("myregistry:5000/org-name/img-name:dev").unwrap();
+            assert_eq!(name.domain(), Some("myregistry"));
+            assert_eq!(name.port(), Some("5000"));
+            assert_eq!(name.registry(), Some("myregistry:5000"));
+            assert_eq!(name.path(), "org-name/img-name");
+            assert_eq!(name.tag(), Some("dev"));
+
+            let name = ImageNameRef::new("MyRegistry:5000/img-name").unwrap();
+            assert_eq!(name.domain(), Some("MyRegistry"));
+            assert_eq!(name.path(), "img-name");
+
+            let name = ImageNameRef::new("myorg/img-name").unwrap();
+            assert_eq!(name.registry(), None);
+            assert_eq!(name.path(), "myorg/img-name");
+
+            let name = ImageNameRef::new("img-name:5000").unwrap();
+            assert_eq!(name.registry(), None);
+            assert_eq!(name.path(), "img-name");
+            assert_eq!(name.tag(), Some("5000"));
+
+            assert!(ImageNameRef::new("MyOrg/img-name").is_err());
+            assert!(ImageNameRef::new("myregistry:dev/img-name").is_err());
+        }
+
+        {
+            let name = ImageNameRef::new(
+                "reg.io/org-name/img-name@sha256:01234567aaaaaaaa01234567aaaaaaaa01234567aaaaaaaa01234567aaaaaaaa",
+            )
+            .unwrap();
+            assert_eq!(name.domain(), Some("reg.io"));
+            assert_eq!(name.port(), None);
+            assert_eq!(name.registry(), Some("reg.io"));
+            assert_eq!(name.path(), "org-name/img-name");
+            assert_eq!(name.tag(), None);
+            assert_eq!(
+                name.digest(),
+                Some("sha256:01234567aaaaaaaa01234567aaaaaaaa01234567aaaaaaaa01234567aaaaaaaa")
+            );
+            assert_eq!(name.digest_algorithm(), Some("sha256"));
+            assert_eq!(
+                name.digest_hex(),
+                Some("01234567aaaaaaaa01234567aaaaaaaa01234567aaaaaaaa01234567aaaaaaaa")
+            );
+        }
+
+        {
+            assert_eq!(
+                ImageNameRef::new(".").err().unwrap().reason(),
+                InvalidContainerImageNameReason::InvalidPathComponent { offset: 0 }
+            );
+            assert_eq!(
+                ImageNameRef::new("a@sha256:1234").err().unwrap().reason(),
+                InvalidContainerImageNameReason::DigestHexTooShort {
+                    offset: 9,
+                    found: 4
+                },
+            );
+        }
+    }
+
+    #[test]
+    fn image_name_builder_works() {
+        {
+            assert_eq!(
+                ImageNameRef::new("org-name/img-name")
+                    .unwrap()
+                    .as_builder()
+                    .with_algorithm_and_hex("sha256", "12345678aaaaaaaa12345678aaaaaaaa12345678aaaaaaaa12345678aaaaaaaa")
+                    .build()
+                    .unwrap()
+                    .as_str(),
+                "org-name/img-name@sha256:12345678aaaaaaaa12345678aaaaaaaa12345678aaaaaaaa12345678aaaaaaaa"
+            );
+        }
+    }
+
+    #[cfg(feature = "schemars")]
+    #[test]
+    fn json_schema_validates_image_names() {
+        let schema = serde_json::to_value(schemars::schema_for!(ImageName)).unwrap();
+        assert_eq!(
+            serde_json::to_value(schemars::schema_for!(ImageNameRef)).unwrap(),
+            schema
+        );
+        let schema = jsonschema::JSONSchema::compile(&schema).unwrap();
+        let hex = "0123456789abcdef".repeat(4);
+
+        for valid in [
+            "ubuntu".to_owned(),
+            "docker.io/library/ubuntu:24.04".to_owned(),
+            format!("localhost:5000/team/app:v1.2_rc-3@sha256:{hex}"),
+        ] {
+            assert!(schema.is_valid(&valid.as_str().into()), "{valid:?}");
+            assert!(ImageNameRef::new(&valid).is_ok(), "{valid:?}");
+        }
+        for invalid in [
+            "".to_owned(),
+            "Ubuntu".to_owned(),
+            "ubuntu:".to_owned(),
+            "docker.io//ubuntu".to_owned(),
+            format!("ubuntu@sha256:{}", &hex[..16]),
+        ] {
+            assert!(!schema.is_valid(&invalid.as_str().into()), "{invalid:?}");
+            assert!(ImageNameRef::new(&invalid).is_err(), "{invalid:?}");
+        }
+        assert!(!schema.is_valid(&serde_json::json!(42)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn image_name_serde_works() {
+        {
+            let des = ImageName::new("org-name/img-name".to_string()).unwrap();
+            let ser = r#""org-name/img-name""#;
+            assert_eq!(serde_json::to_string(&des).unwrap(), ser);
+            assert_eq!(serde_json::from_str::<ImageName>(ser).unwrap(), des);
+        }
+
+        {
+            let des = ImageNameRef::new("org-name/img-name").unwrap();
+            let ser = r#""org-name/img-name""#;
+            assert_eq!(serde_json::to_string(&des).unwrap(), ser);
+            assert_eq!(serde_json::from_str::<ImageNameRef>(ser).unwrap(), des);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn image_name_ref_deserialize_borrows() {
+        let input = r#"["reg.io/org-name/img-name:latest", "busybox"]"#;
+        let images: Vec<ImageNameRef> = serde_json::from_str(input).unwrap();
+        assert_eq!(images, ["reg.io/org-name/img-name:latest", "busybox"]);
+        for image in &images {
+            assert!(input
+                .as_bytes()
+                .as_ptr_range()
+                .contains(&image.as_str().as_ptr()));
+        }
+        assert_eq!(images[0].tag(), Some("latest"));
+
+        // A reader has no buffer to borrow from.
+        let reader = &br#""org-name/img-name""#[..];
+        let error = ImageNameRef::deserialize(&mut serde_json::Deserializer::from_reader(reader))
+            .err()
+            .unwrap();
+        assert!(
+            error
+                .to_string()
+                .contains("deserialize an `ImageName` instead"),
+            "{error}"
+        );
+        assert!(serde_json::from_reader::<_, ImageName>(reader).is_ok());
+
+        // Invalid names are still rejected when borrowed.
+        assert!(serde_json::from_str::<ImageNameRef>(r#""Org/img""#).is_err());
+    }
+
+    #[test]
+    fn comparisons_agree_with_hash() {
+        use std::{
+            collections::HashMap,
+            hash::{BuildHasher, RandomState},
+        };
+
+        let state = RandomState::new();
+        let literal = "reg.io/foo:latest";
+        let owned = ImageName::new(literal.to_owned()).unwrap();
+        let borrowed = ImageNameRef::new(literal).unwrap();
+
+        assert!(owned == *literal && owned == literal);
+        assert!(*literal == owned && literal == owned);
+        assert!(borrowed == *literal && borrowed == literal);
+        assert!(*literal == borrowed && literal == borrowed);
+        assert!(owned == borrowed);
+        assert!(borrowed == owned);
+        assert_eq!(state.hash_one(&owned), state.hash_one(literal));
+        assert_eq!(state.hash_one(borrowed), state.hash_one(literal));
+
+        let other = ImageNameRef::new("reg.io/foo:v1").unwrap();
+        assert!(owned != other);
+        assert!(other != owned);
+        assert!(other != literal);
+        assert!(literal != other);
+
+        let mut images = HashMap::new();
+        images.insert(owned.clone(), 1);
+        images.insert(borrowed.to_owned(), 2);
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[&owned], 2);
+    }
+
+    #[test]
+    fn map_keys_can_be_looked_up_by_str() {
+        use std::collections::{BTreeSet, HashMap};
+
+        let mut outputs = HashMap::new();
+        outputs.insert(ImageName::new("reg.io/foo:v1".to_owned()).unwrap(), 1);
+        outputs.insert(ImageName::new("reg.io/foo:v2".to_owned()).unwrap(), 2);
+
+        assert_eq!(outputs.get("reg.io/foo:v1"), Some(&1));
+        assert_eq!(
+            outputs.get(ImageNameRef::new("reg.io/foo:v2").unwrap().as_str()),
+            Some(&2)
+        );
+        assert_eq!(outputs.get("reg.io/foo"), None);
+
+        let names = BTreeSet::from([
+            ImageNameRef::new("b").unwrap(),
+            ImageNameRef::new("a").unwrap(),
+        ]);
+        assert!(names.contains("a"));
+    }
+
+    #[test]
+    fn as_builder_borrows() {
+        let name = ImageNameRef::new(
+            "reg.io:12345/org-name/img-name:latest@sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef",
+        )
+        .unwrap();
+        let builder = name.as_builder();
+        assert!(matches!(builder.path, Cow::Borrowed(_)));
+        assert!(matches!(
+            builder.tag,
+            Some(TagInput {
+                value: Cow::Borrowed(_),
+                checked: true
+            })
+        ));
+        assert!(matches!(
+            builder.registry,
+            Some(ImageNameBuilderRegistry::Registry(Cow::Borrowed(_)))
+        ));
+        assert!(matches!(
+            builder.digest,
+            Some(ImageNameBuilderDigest::Digest(Cow::Borrowed(_)))
+        ));
+    }
+
+    #[test]
+    fn as_builder_without_works() {
+        let digest = "sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let name = ImageName::new(format!("reg.io:12345/org-name/img-name:v1@{digest}")).unwrap();
+
+        let stripped = name.as_builder().without_registry().build().unwrap();
+        assert_eq!(stripped.as_str(), format!("org-name/img-name:v1@{digest}"));
+        assert_eq!(stripped.registry(), None);
+        assert_eq!(stripped.path(), "org-name/img-name");
+        assert_eq!(stripped.tag(), Some("v1"));
+        assert_eq!(stripped.digest(), Some(digest));
+
+        let stripped = name.as_builder().without_tag().build().unwrap();
+        assert_eq!(
+            stripped.as_str(),
+            format!("reg.io:12345/org-name/img-name@{digest}")
+        );
+        assert_eq!(stripped.registry(), Some("reg.io:12345"));
+        assert_eq!(stripped.tag(), None);
+        assert_eq!(stripped.digest(), Some(digest));
+
+        let stripped = name.as_builder().without_digest().build().unwrap();
+        assert_eq!(stripped.as_str(), "reg.io:12345/org-name/img-name:v1");
+        assert_eq!(stripped.registry(), Some("reg.io:12345"));
+        assert_eq!(stripped.tag(), Some("v1"));
+        assert_eq!(stripped.digest(), None);
+
+        let stripped = name
+            .as_builder()
+            .without_registry()
+            .without_tag()
+            .without_digest()
+            .build()
+            .unwrap();
+        assert_eq!(stripped.as_str(), "org-name/img-name");
+
+        // The result is still validated.
+        assert!(name
+            .as_builder()
+            .without_registry()
+            .with_path("Org/img")
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn build_errors_name_the_invalid_section() {
+        let hex = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let builder = || {
+            ImageName::builder("org-name/img-name")
+                .with_registry("reg.io:5000")
+                .with_tag("v1")
+        };
+
+        let error = builder()
+            .with_digest(format!("sha256:{}", &hex[1..]))
+            .build()
+            .unwrap_err();
+        assert_eq!(error.section(), Section::Digest);
+        assert_eq!(error.value(), format!("sha256:{}", &hex[1..]));
+        assert_eq!(
+            error.reason(),
+            Reason::DigestLength {
+                offset: 7,
+                algorithm: "sha256",
+                expected: 64,
+                found: 63
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            format!("invalid digest \"sha256:{}\" of container image name: the digest hex at byte 7 has 63 characters but a sha256 digest has 64", &hex[1..])
+        );
+        // Converted, the offset is that of the combined name again.
+        let error = InvalidContainerImageName::from(error);
+        assert_eq!(
+            error.value(),
+            format!("reg.io:5000/org-name/img-name:v1@sha256:{}", &hex[1..])
+        );
+        assert_eq!(error.reason().offset(), 40);
+
+        let error = builder()
+            .with_algorithm_and_hex("sha256", format!("{}g", &hex[1..]))
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            (error.section(), error.reason()),
+            (Section::Digest, Reason::InvalidDigestHex { offset: 70 })
+        );
+
+        let section = |builder: ImageNameBuilder| {
+            let error = builder.build().unwrap_err();
+            (error.section(), error.value().to_owned(), error.reason())
+        };
+        assert_eq!(
+            section(builder().with_registry("reg.io:port")),
+            (
+                Section::Registry,
+                "reg.io:port".to_owned(),
+                Reason::InvalidPort { offset: 7 }
+            )
+        );
+        for port in ["0", "65536", "99999999999999999999"] {
+            assert_eq!(
+                section(builder().with_domain_and_port("reg.io", port)),
+                (
+                    Section::Registry,
+                    format!("reg.io:{port}"),
+                    Reason::InvalidPort { offset: 7 }
+                ),
+                "{port}"
+            );
+        }
+        assert_eq!(
+            section(builder().with_domain_and_port("reg.io", 0)),
+            (
+                Section::Registry,
+                "reg.io:0".to_owned(),
+                Reason::InvalidPort { offset: 7 }
+            )
+        );
+        // Only the ports of the builder are numbers, those of names can have any number of digits.
+        assert!(builder().with_registry("reg.io:65536").build().is_ok());
+        assert_eq!(
+            section(builder().with_domain_and_port("-reg.io", None)),
+            (
+                Section::Registry,
+                "-reg.io".to_owned(),
+                Reason::InvalidDomain { offset: 0 }
+            )
+        );
+        assert_eq!(
+            section(builder().with_path("org-name//img-name")),
+            (
+                Section::Path,
+                "org-name//img-name".to_owned(),
+                Reason::InvalidPathComponent { offset: 9 }
+            )
+        );
+        assert_eq!(
+            section(builder().with_tag(".v1")),
+            (
+                Section::Tag,
+                ".v1".to_owned(),
+                Reason::InvalidTag { offset: 0 }
+            )
+        );
+        assert_eq!(
+            section(builder().with_path("a".repeat(NAME_MAX_LENGTH))),
+            (
+                Section::Path,
+                "a".repeat(NAME_MAX_LENGTH),
+                Reason::NameTooLong {
+                    offset: NAME_MAX_LENGTH - "reg.io:5000/".len(),
+                    length: NAME_MAX_LENGTH + "reg.io:5000/".len()
+                }
+            )
+        );
+        assert_eq!(
+            section(builder().with_tag("a".repeat(DEFAULT_MAX_LENGTH))).0,
+            Section::Tag
+        );
+    }
+
+    #[test]
+    fn build_finds_the_registry_like_parsing() {
+        // A first path component that looks like a registry is one, unless a registry is set.
+        let built = ImageName::builder("reg.io/img-name").build().unwrap();
+        assert_eq!(
+            (built.registry(), built.path()),
+            (Some("reg.io"), "img-name")
+        );
+        let built = ImageName::builder("reg.io/img-name")
+            .with_registry("localhost:5000")
+            .build()
+            .unwrap();
+        assert_eq!(
+            (built.registry(), built.path()),
+            (Some("localhost:5000"), "reg.io/img-name")
+        );
+        let built = ImageName::builder("org-name/img-name").build().unwrap();
+        assert_eq!(
+            (built.registry(), built.path()),
+            (None, "org-name/img-name")
+        );
+        // Registries that are no valid path components are found too.
+        for (name, registry) in [
+            ("Reg.IO/img", "Reg.IO"),
+            ("localhost:5000/img", "localhost:5000"),
+        ] {
+            let built = ImageName::builder(name).build().unwrap();
+            assert_eq!((built.registry(), built.path()), (Some(registry), "img"));
+            assert_eq!(built, ImageName::new(name.to_owned()).unwrap());
+        }
+
+        // A domain without a port has no port prefix.
+        let built = ImageName::builder("img-name")
+            .with_domain_and_port("reg.io", None)
+            .build()
+            .unwrap();
+        assert_eq!(built, "reg.io/img-name");
+        assert_eq!((built.domain(), built.port()), (Some("reg.io"), None));
+
+        let built = ImageName::builder("img-name")
+            .with_domain_and_port("reg.io", 5000u16)
+            .build()
+            .unwrap();
+        assert_eq!(built, "reg.io:5000/img-name");
+        assert_eq!(
+            (built.domain(), built.port()),
+            (Some("reg.io"), Some("5000"))
+        );
+        let built = ImageName::builder("img-name")
+            .with_domain_and_port("reg.io", "65535")
+            .build()
+            .unwrap();
+        assert_eq!(built.port(), Some("65535"));
+
+        let built = ImageName::builder("img-name")
+            .with_domain_and_port("[::1]", Some("5000".into()))
+            .build()
+            .unwrap();
+        assert_eq!(built, "[::1]:5000/img-name");
+        assert_eq!(
+            (built.domain(), built.port()),
+            (Some("[::1]"), Some("5000"))
+        );
+    }
+
+    #[test]
+    fn parse_errors_name_the_invalid_section() {
+        let reason = |value: &str| {
+            assert_parses_like_the_grammar(value);
+            ImageNameRef::new(value).unwrap_err().reason()
+        };
+        use InvalidContainerImageNameReason::*;
+
+        assert_eq!(reason(""), InvalidPathComponent { offset: 0 });
+        assert_eq!(reason("reg.i_o:5000/img"), InvalidDomain { offset: 4 });
+        assert_eq!(reason("-reg.io:5000/img"), InvalidDomain { offset: 0 });
+        assert_eq!(reason("reg.io:50a0/img"), InvalidPort { offset: 7 });
+        assert_eq!(reason("[::1/img"), InvalidDomain { offset: 0 });
+        assert_eq!(reason("[::1:5000/img"), InvalidDomain { offset: 0 });
+        assert_eq!(reason("[::g]/img"), InvalidDomain { offset: 0 });
+        assert_eq!(reason("[]/img"), InvalidDomain { offset: 0 });
+        assert_eq!(reason("[::1]x/img"), InvalidDomain { offset: 0 });
+        assert_eq!(reason("[::1]:50a0/img"), InvalidPort { offset: 6 });
+        assert_eq!(reason("localhost:/img"), InvalidPort { offset: 10 });
+        assert_eq!(
+            reason("reg.io/org/Img"),
+            InvalidPathComponent { offset: 11 }
+        );
+        assert_eq!(reason("org//img"), InvalidPathComponent { offset: 4 });
+        assert_eq!(reason("org/img/"), InvalidPathComponent { offset: 8 });
+        assert_eq!(reason("reg.io:5000/img:-dev"), InvalidTag { offset: 16 });
+        assert_eq!(
+            reason(&format!("img:{}", "a".repeat(129))),
+            InvalidTag { offset: 4 }
+        );
+        assert_eq!(
+            reason(&format!("img@1sha:{}", "a".repeat(32))),
+            InvalidDigestAlgorithm { offset: 4 }
+        );
+        assert_eq!(
+            reason(&format!("img:v1@sha256:{}g", "a".repeat(63))),
+            InvalidDigestHex { offset: 77 }
+        );
+        assert_eq!(
+            reason("img@sha256"),
+            DigestHexTooShort {
+                offset: 10,
+                found: 0
+            }
+        );
+        assert_eq!(
+            reason("a.b/img@blake3:0123"),
+            DigestHexTooShort {
+                offset: 15,
+                found: 4
+            }
+        );
+
+        // The offset is part of the message.
+        assert_eq!(
+            ImageName::new("reg.io/org/Img".to_owned())
+                .unwrap_err()
+                .to_string(),
+            r#"invalid container image name: "reg.io/org/Img": invalid path component at byte 11"#
+        );
+    }
+
+    #[test]
+    fn length_limits_apply_from_one_byte_over() {
+        use InvalidContainerImageNameReason::*;
+        // "reg.io/" takes 7 bytes of the name.
+        let name = |length: usize| format!("reg.io/org/{}", "a".repeat(length - 11));
+
+        let at_limit = name(NAME_MAX_LENGTH);
+        for value in [
+            format!("{at_limit}:v1"),
+            format!("{}:v1", name(NAME_MAX_LENGTH + 1)),
+            "a".repeat(DEFAULT_MAX_LENGTH),
+            "a".repeat(DEFAULT_MAX_LENGTH + 1),
+        ] {
+            assert_parses_like_the_grammar(&value);
+        }
+        assert_eq!(
+            ImageNameRef::new(&format!("{at_limit}:v1")).map(|name| name.path().len()),
+            Ok(NAME_MAX_LENGTH - 7)
+        );
+        assert_eq!(
+            ImageNameRef::new(&format!("{}:v1", name(NAME_MAX_LENGTH + 1)))
+                .unwrap_err()
+                .reason(),
+            NameTooLong {
+                offset: NAME_MAX_LENGTH,
+                length: NAME_MAX_LENGTH + 1
+            }
+        );
+
+        // Replacing the registry may push the name over the limit.
+        let mut image = ImageName::new(at_limit.clone()).unwrap();
+        assert_eq!(
+            image.set_registry("reg.iox").unwrap_err().reason(),
+            NameTooLong {
+                offset: NAME_MAX_LENGTH,
+                length: NAME_MAX_LENGTH + 1
+            }
+        );
+        assert_eq!(image.as_str(), at_limit);
+        image.set_registry("reg.i").unwrap();
+
+        // The limit of the whole image name is configurable.
+        let reference = format!("img:{}", "a".repeat(96));
+        assert!(ImageNameRef::new_with_max_length(&reference, 100).is_ok());
+        assert_eq!(
+            ImageNameRef::new_with_max_length(&reference, 99)
+                .unwrap_err()
+                .reason(),
+            ReferenceTooLong {
+                offset: 99,
+                length: 100
+            }
+        );
+        assert_eq!(
+            ImageName::new_with_max_length(reference, 99)
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "invalid container image name: \"img:{}\": the image name is too long at byte 99, it has 100 bytes",
+                "a".repeat(96)
+            )
+        );
+        assert_eq!(
+            ImageNameRef::new(&"a".repeat(DEFAULT_MAX_LENGTH + 1))
+                .unwrap_err()
+                .reason(),
+            ReferenceTooLong {
+                offset: DEFAULT_MAX_LENGTH,
+                length: DEFAULT_MAX_LENGTH + 1
+            }
+        );
+    }
+
+    #[test]
+    fn digest_hex_length_depends_on_the_algorithm() {
+        let hex = |len: usize| "a".repeat(len);
+        let parse = |algorithm: &str, len: usize| {
+            let value = format!("a/b@{algorithm}:{}", hex(len));
+            assert_parses_like_the_grammar(&value);
+            ImageNameRef::new(&value).map(|name| name.digest_hex().unwrap().len())
+        };
+
+        assert_eq!(parse("sha256", 64), Ok(64));
+        assert_eq!(parse("sha512", 128), Ok(128));
+        assert_eq!(
+            parse("sha256", 32).unwrap_err().to_string(),
+            "invalid container image name: the digest hex at byte 11 has 32 characters but a sha256 digest has 64"
+        );
+        assert_eq!(
+            parse("sha256", 65).unwrap_err().to_string(),
+            "invalid container image name: the digest hex at byte 11 has 65 characters but a sha256 digest has 64"
+        );
+        assert_eq!(
+            parse("sha512", 64).unwrap_err().to_string(),
+            "invalid container image name: the digest hex at byte 11 has 64 characters but a sha512 digest has 128"
+        );
+
+        // Unknown algorithms only need the 32 characters of the grammar.
+        assert_eq!(parse("blake3", 32), Ok(32));
+        assert_eq!(parse("sha256+b64", 40), Ok(40));
+        assert_eq!(
+            parse("blake3", 31).unwrap_err().reason(),
+            InvalidContainerImageNameReason::DigestHexTooShort {
+                offset: 11,
+                found: 31
+            }
+        );
+
+        assert_eq!(
+            ImageName::new(format!("a/b@sha256:{}", hex(32)))
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "invalid container image name: \"a/b@sha256:{}\": the digest hex at byte 11 has 32 characters but a sha256 \
+                 digest has 64",
+                hex(32)
+            )
+        );
+        assert_eq!(
+            ImageNameRef::new("a/b")
+                .unwrap()
+                .with_digest("sha512:abcdef0123456789abcdef0123456789")
+                .unwrap_err()
+                .to_string(),
+            "invalid container image name: \"a/b@sha512:abcdef0123456789abcdef0123456789\": the digest hex at byte 11 \
+             has 32 characters but a sha512 digest has 128"
+        );
     }
-}
 
-pub struct ImageNameBuilder<'a> {
-    registry: Option<ImageNameBuilderRegistry<'a>>,
-    path: Cow<'a, str>,
-    tag: Option<Cow<'a, str>>,
-    digest: Option<ImageNameBuilderDigest<'a>>,
-}
+    #[test]
+    fn digest_normalizes_mixed_case_hex() {
+        let hex = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let lower: Digest = format!("sha256:{hex}").parse().unwrap();
+        let mixed: Digest = format!("sha256:{}", hex.replace("abc", "AbC"))
+            .parse()
+            .unwrap();
 
-impl<'a> ImageNameBuilder<'a> {
-    fn new(path: impl Into<Cow<'a, str>>) -> Self {
-        Self {
-            registry: None,
-            path: path.into(),
-            tag: None,
-            digest: None,
-        }
+        assert_eq!(lower, mixed);
+        assert_eq!(mixed.to_string(), format!("sha256:{hex}"));
+        assert_eq!(mixed.algorithm(), &Algorithm::Sha256);
+        assert_eq!(mixed.hex(), hex);
+        assert_eq!(mixed.bytes().len(), 32);
+        assert_eq!(mixed.bytes()[..4], [0x01, 0x23, 0x45, 0x67]);
+        assert_ne!(
+            lower,
+            format!("sha256:{}", hex.replace('0', "1")).parse().unwrap()
+        );
     }
 
-    pub fn with_registry(mut self, registry: impl Into<Cow<'a, str>>) -> Self {
-        self.registry = Some(ImageNameBuilderRegistry::Registry(registry.into()));
-        self
+    #[test]
+    fn digest_keeps_unknown_algorithms() {
+        let hex = "ABCDEF0123456789abcdef0123456789";
+        let digest: Digest = format!("blake3:{hex}").parse().unwrap();
+        assert_eq!(digest.algorithm(), &Algorithm::Other("blake3".to_owned()));
+        assert_eq!(digest.to_string(), format!("blake3:{}", hex.to_lowercase()));
+
+        // The registered algorithms are case-sensitive, like their hex lengths.
+        let digest: Digest = format!("SHA256:{hex}").parse().unwrap();
+        assert_eq!(digest.algorithm().as_str(), "SHA256");
+        assert_ne!(
+            digest,
+            format!("sha256:{}", hex.repeat(2))
+                .parse::<Digest>()
+                .unwrap()
+        );
+
+        assert_eq!(
+            format!("blake3:{hex}0").parse::<Digest>(),
+            Err(InvalidDigest::OddHexLength {
+                offset: 7,
+                found: 33
+            })
+        );
+        assert_eq!(
+            format!("sha256:{hex}").parse::<Digest>(),
+            Err(InvalidDigest::Grammar(Reason::DigestLength {
+                offset: 7,
+                algorithm: "sha256",
+                expected: 64,
+                found: 32
+            }))
+        );
+        assert_eq!(
+            "sha256:xyz".parse::<Digest>().unwrap_err().to_string(),
+            "invalid digest: invalid digest hex at byte 7"
+        );
     }
 
-    pub fn with_domain_and_port(
-        mut self,
-        domain: impl Into<Cow<'a, str>>,
-        port: impl Into<Option<Cow<'a, str>>>,
-    ) -> Self {
-        self.registry = Some(ImageNameBuilderRegistry::DomainPort {
-            domain: domain.into(),
-            port: port.into(),
-        });
-        self
+    #[test]
+    fn digest_parsed_works() {
+        let hex = "0123456789ABCDEF0123456789abcdef0123456789abcdef0123456789abcdef";
+        let name = ImageName::new(format!("registry.example.com/a/b:1@sha256:{hex}")).unwrap();
+        let digest = name.digest_parsed().unwrap().unwrap();
+        assert_eq!(digest, format!("sha256:{hex}").parse().unwrap());
+        assert_eq!(name.as_ref().digest_parsed(), Some(Ok(digest.clone())));
+        assert_eq!(ImageNameRef::new("a/b:1").unwrap().digest_parsed(), None);
+        assert_eq!(
+            ImageNameRef::new(&format!("a/b@blake3:{}", "a".repeat(33)))
+                .unwrap()
+                .digest_parsed(),
+            Some(Err(InvalidDigest::OddHexLength {
+                offset: 7,
+                found: 33
+            }))
+        );
+
+        // Building with the digest normalizes its hex.
+        let built = ImageName::builder("a/b")
+            .with_digest(&digest)
+            .build()
+            .unwrap();
+        assert_eq!(built, *format!("a/b@sha256:{}", hex.to_lowercase()));
+        assert_eq!(built.digest_parsed(), Some(Ok(digest)));
     }
 
-    pub fn with_path(mut self, path: impl Into<Cow<'a, str>>) -> Self {
-        self.path = path.into();
-        self
+    #[test]
+    fn tag_works() {
+        assert_eq!("v1.2-rc_3".parse::<Tag>().unwrap().as_str(), "v1.2-rc_3");
+        assert_eq!(TagRef::new("_").unwrap().to_owned(), Tag("_".to_owned()));
+        assert!(Tag::new("a".repeat(TAG_MAX_LENGTH)).is_ok());
+        for invalid in ["", ".v1", "-v1", "v1:2", "a b", "alice@example.com"] {
+            assert_eq!(
+                invalid.parse::<Tag>().unwrap_err().value(),
+                invalid,
+                "{invalid:?}"
+            );
+        }
+        let error = Tag::new("a".repeat(TAG_MAX_LENGTH + 1)).unwrap_err();
+        assert!(error.to_string().contains("at most 128"), "{error}");
     }
 
-    pub fn with_tag(mut self, tag: impl Into<Cow<'a, str>>) -> Self {
-        self.tag = Some(tag.into());
-        self
+    #[test]
+    fn tag_sanitize_lossy_works() {
+        let tag = |input| Tag::sanitize_lossy(input).map(String::from);
+        assert_eq!(tag("alice").as_deref(), Some("alice"));
+        assert_eq!(tag("Alice_Smith.2").as_deref(), Some("Alice_Smith.2"));
+        assert_eq!(tag("ALICE").as_deref(), Some("ALICE"));
+        assert_eq!(tag("first.last+bot").as_deref(), Some("first.last-bot"));
+        assert_eq!(
+            tag("alice@example.com").as_deref(),
+            Some("alice-example.com")
+        );
+        assert_eq!(tag("o'brien @ work").as_deref(), Some("o-brien-work"));
+        assert_eq!(tag("Mary Jane").as_deref(), Some("Mary-Jane"));
+        assert_eq!(tag("émile").as_deref(), Some("mile"));
+        assert_eq!(tag(".-hidden-.").as_deref(), Some("hidden"));
+        assert_eq!(tag("+++"), None);
+        assert_eq!(tag(""), None);
+        assert_eq!(
+            Tag::sanitize_lossy_with_max_length("abc-def", 4).map(String::from),
+            Some("abc".to_owned())
+        );
+        assert_eq!(tag(&"a".repeat(200)).map(|tag| tag.len()), Some(128));
+
+        for input in [
+            "first.last+bot@company.com",
+            "_",
+            "ZOË",
+            "a b\tc",
+            &"x.".repeat(100),
+        ] {
+            let tag = Tag::sanitize_lossy(input).unwrap();
+            assert_eq!(TagRef::new(tag.as_str()), Ok(tag.as_ref()), "{input:?}");
+        }
     }
 
-    pub fn with_digest(mut self, digest: impl Into<Cow<'a, str>>) -> Self {
-        self.digest = Some(ImageNameBuilderDigest::Digest(digest.into()));
-        self
+    #[test]
+    fn builder_takes_checked_tags() {
+        let tag = Tag::sanitize_lossy("Alice Smith").unwrap();
+        let name = ImageName::builder("launch").with_tag(&tag).build().unwrap();
+        assert_eq!(name.as_str(), "launch:Alice-Smith");
+        assert_eq!(name.tag(), Some(tag.as_str()));
+        let name = ImageName::builder("launch")
+            .with_tag(tag.as_ref())
+            .build()
+            .unwrap();
+        assert_eq!(name.as_str(), "launch:Alice-Smith");
+
+        // Strings are still checked.
+        let error = ImageName::builder("launch")
+            .with_tag("Alice Smith")
+            .build()
+            .unwrap_err();
+        assert_eq!(error.section(), Section::Tag);
     }
 
-    pub fn with_algorithm_and_hex(
-        mut self,
-        algorithm: impl Into<Cow<'a, str>>,
-        hex: impl Into<Cow<'a, str>>,
-    ) -> Self {
-        self.digest = Some(ImageNameBuilderDigest::AlgorithmHex {
-            algorithm: algorithm.into(),
-            hex: hex.into(),
-        });
-        self
+    #[cfg(feature = "serde")]
+    #[test]
+    fn tag_serde_works() {
+        let tag: Tag = serde_json::from_str(r#""v1""#).unwrap();
+        assert_eq!(serde_json::to_string(&tag).unwrap(), r#""v1""#);
+        assert!(serde_json::from_str::<Tag>(r#""v 1""#).is_err());
     }
 
-    pub fn build(self) -> Result<ImageName, InvalidContainerImageName> {
-        let mut buffer = String::with_capacity(
-            self.registry.as_ref().map(|x| x.len()).unwrap_or_default()
-                + self.path.len()
-                + self
-                    .tag
-                    .as_ref()
-                    .map(|x| x.len() + TAG_PREFIX.len_utf8())
-                    .unwrap_or_default()
-                + self.digest.as_ref().map(|x| x.len()).unwrap_or_default(),
+    #[test]
+    fn eq_canonical_works() {
+        let hex = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let eq = |a: &str, b: &str| {
+            let (a, b) = (ImageNameRef::new(a).unwrap(), ImageNameRef::new(b).unwrap());
+            assert_eq!(a.eq_canonical(b), b.eq_canonical(a), "{a} and {b}");
+            a.eq_canonical(b)
+        };
+
+        assert!(eq("Reg.IO/foo", "reg.io/foo:latest"));
+        assert!(eq(
+            "Reg.Example.COM:5000/a/b:1",
+            "reg.example.com:5000/a/b:1"
+        ));
+        assert!(eq("LOCALHOST:5000/tool", "localhost:5000/tool:latest"));
+        assert!(eq(
+            &format!("Reg.IO:443/a/b@sha256:{}", hex.to_uppercase()),
+            &format!("reg.io:443/a/b:latest@sha256:{hex}")
+        ));
+        assert!(!eq("reg.io:5000/foo", "reg.io:5001/foo"));
+        assert!(!eq("reg.io:5000/foo", "reg.io/foo"));
+        assert!(!eq("reg.io/foo", "foo"));
+        assert!(!eq("reg.io/foo:1", "reg.io/foo"));
+        assert!(!eq("reg.io/foo:Latest", "reg.io/foo"));
+        assert!(!eq("reg.io/foo/bar", "reg.io/foo"));
+        assert!(!eq(&format!("reg.io/foo@sha256:{hex}"), "reg.io/foo"));
+
+        // The equality and hash of the buffer are unaffected.
+        assert_ne!(
+            ImageNameRef::new("Reg.IO/foo").unwrap(),
+            ImageNameRef::new("reg.io/foo").unwrap()
         );
 
-        if let Some(registry) = self.registry {
-            registry.write(&mut buffer)
-        }
-        buffer.push_str(&self.path);
-        if let Some(tag) = self.tag {
-            buffer.push(TAG_PREFIX);
-            buffer.push_str(&tag);
-        }
-        if let Some(digest) = self.digest {
-            digest.write(&mut buffer);
-        }
-        ImageName::new(buffer)
+        assert!(registry_eq_canonical("Reg.IO:5000", "reg.io:5000"));
+        assert!(!registry_eq_canonical("reg.io:5000", "reg.io"));
+        assert!(!registry_eq_canonical("reg.io", "reg.com"));
+        assert!(registry_eq_canonical(
+            "[2001:DB8::1]:5000",
+            "[2001:db8::1]:5000"
+        ));
+        assert!(!registry_eq_canonical("[::1]:5000", "[::1]"));
+        assert!(!registry_eq_canonical("[::1]", "[::2]"));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn predicates_work() {
+        let hex = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let predicates = |value: &str| {
+            let name = ImageNameRef::new(value).unwrap();
+            let owned = name.to_owned();
+            let result = (name.is_tagged(), name.is_digested(), name.has_registry());
+            assert_eq!(
+                (owned.is_tagged(), owned.is_digested(), owned.has_registry()),
+                result
+            );
+            result
+        };
+        assert_eq!(predicates("foo"), (false, false, false));
+        assert_eq!(predicates("foo:1"), (true, false, false));
+        assert_eq!(
+            predicates(&format!("foo@sha256:{hex}")),
+            (false, true, false)
+        );
+        assert_eq!(predicates("reg.io/foo"), (false, false, true));
+        assert_eq!(predicates("localhost:5000/foo"), (false, false, true));
+        assert_eq!(
+            predicates(&format!("reg.io:5000/foo:1@sha256:{hex}")),
+            (true, true, true)
+        );
+    }
 
     #[test]
-    fn image_name_parsing_works() {
-        {
-            let name = ImageNameRef::new("org-name/img-name").unwrap();
-            assert_eq!(name.domain(), None);
-            assert_eq!(name.port(), None);
-            assert_eq!(name.registry(), None);
-            assert_eq!(name.path(), "org-name/img-name");
-            assert_eq!(name.tag(), None);
-            assert_eq!(name.digest(), None);
-            assert_eq!(name.digest_algorithm(), None);
-            assert_eq!(name.digest_hex(), None);
+    fn expect_tag_and_registry_work() {
+        let name = ImageNameRef::new("reg.io:5000/foo:1").unwrap();
+        assert_eq!(name.expect_tag(), Ok("1"));
+        assert_eq!(name.expect_registry(), Ok("reg.io:5000"));
+        assert_eq!(name.to_owned().expect_tag(), Ok("1"));
+
+        let name = ImageName::new("foo".to_owned()).unwrap();
+        let error = name.expect_tag().unwrap_err();
+        assert_eq!(error.section(), Section::Tag);
+        assert_eq!(error.value(), "foo");
+        assert_eq!(
+            error.to_string(),
+            r#"container image name "foo" has no tag section"#
+        );
+        let error = name.as_ref().expect_registry().unwrap_err();
+        assert_eq!(error.section(), Section::Registry);
+        assert_eq!(
+            error.to_string(),
+            r#"container image name "foo" has no registry section"#
+        );
+    }
+
+    #[test]
+    fn canonical_and_tagged_work() {
+        let hex = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let name = |value: &str| ImageName::new(value.to_owned()).unwrap();
+
+        let both = name(&format!("reg.io:5000/foo:latest@sha256:{hex}"));
+        assert!(both.is_canonical());
+        let canonical = both.to_canonical().unwrap();
+        assert_eq!(canonical, *format!("reg.io:5000/foo@sha256:{hex}"));
+        assert_eq!(canonical.tag(), None);
+        assert_eq!(canonical.digest_hex(), Some(hex));
+        assert_eq!(canonical.registry(), Some("reg.io:5000"));
+        let tagged = both.to_tagged().unwrap();
+        assert_eq!(tagged, "reg.io:5000/foo:latest");
+        assert_eq!((tagged.tag(), tagged.digest()), (Some("latest"), None));
+        // The original is unchanged.
+        assert_eq!(both, *format!("reg.io:5000/foo:latest@sha256:{hex}"));
+
+        // Only a digest.
+        let digest_only = name(&format!("foo@sha256:{hex}"));
+        assert!(digest_only.is_canonical());
+        assert_eq!(digest_only.to_canonical().unwrap(), digest_only);
+        assert_eq!(digest_only.to_tagged().unwrap_err().section(), Section::Tag);
+
+        // Only a tag.
+        let tag_only = name("reg.io/foo:1.0");
+        assert!(!tag_only.is_canonical());
+        let error = tag_only.to_canonical().unwrap_err();
+        assert_eq!(
+            (error.value(), error.section()),
+            ("reg.io/foo:1.0", Section::Digest)
+        );
+        assert_eq!(
+            error.to_string(),
+            r#"container image name "reg.io/foo:1.0" has no digest section"#
+        );
+        assert_eq!(tag_only.to_tagged().unwrap(), tag_only);
+
+        // Neither.
+        let bare = name("foo");
+        assert!(!bare.is_canonical());
+        assert!(bare.to_canonical().is_err());
+        assert!(bare.to_tagged().is_err());
+    }
+
+    #[test]
+    fn same_repository_works() {
+        let digest = "sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let names = |repository: &str| {
+            [
+                repository.to_owned(),
+                format!("{repository}:1.0"),
+                format!("{repository}@{digest}"),
+                format!("{repository}:1.0@{digest}"),
+            ]
+        };
+
+        for repository in ["foo/bar", "reg.io/foo/bar", "reg.io:5000/foo/bar"] {
+            for a in names(repository) {
+                let a = ImageName::new(a).unwrap();
+                assert_eq!(a.repository(), repository, "{a}");
+                assert_eq!(a.as_ref().repository(), repository, "{a}");
+                for b in names(repository) {
+                    let b = ImageName::new(b).unwrap();
+                    assert!(a.same_repository(&b), "{a} and {b}");
+                    assert!(a.as_ref().same_repository(b.as_ref()), "{a} and {b}");
+                }
+            }
         }
 
-        {
-            let name = ImageNameRef::new("reg.io/org-name/img-name:latest").unwrap();
-            assert_eq!(name.domain(), Some("reg.io"));
-            assert_eq!(name.port(), None);
-            assert_eq!(name.registry(), Some("reg.io"));
-            assert_eq!(name.path(), "org-name/img-name");
-            assert_eq!(name.tag(), Some("latest"));
-            assert_eq!(name.digest(), None);
-            assert_eq!(name.digest_algorithm(), None);
-            assert_eq!(name.digest_hex(), None);
+        let same = |a: &str, b: &str| {
+            let (a, b) = (ImageNameRef::new(a).unwrap(), ImageNameRef::new(b).unwrap());
+            assert_eq!(a.same_repository(b), b.same_repository(a), "{a} and {b}");
+            a.same_repository(b)
+        };
+        assert!(same("Reg.IO/foo:1", &format!("reg.io/foo@{digest}")));
+        assert!(same("LOCALHOST:5000/foo", "localhost:5000/foo:2"));
+        // Unlike normalize, the registry is not filled in.
+        assert!(!same("reg.io/foo", "foo"));
+        assert!(!same("docker.io/library/foo", "foo"));
+        assert!(!same("reg.io:5000/foo", "reg.io/foo"));
+        assert!(!same("reg.io/foo/bar", "reg.io/foo"));
+        assert!(!same("reg.io/foo:1", "reg.io/foobar:1"));
+    }
+
+    #[test]
+    fn normalize_works() {
+        let normalize = |value: &str| ImageNameRef::new(value).unwrap().normalize();
+        let digest = "sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+        // Examples from normalize_test.go of distribution.
+        for (input, expected) in [
+            ("busybox", "docker.io/library/busybox:latest"),
+            ("library/busybox", "docker.io/library/busybox:latest"),
+            ("docker.io/busybox", "docker.io/library/busybox:latest"),
+            (
+                "index.docker.io/busybox",
+                "docker.io/library/busybox:latest",
+            ),
+            (
+                "docker.io/library/busybox",
+                "docker.io/library/busybox:latest",
+            ),
+            ("ubuntu:22.04", "docker.io/library/ubuntu:22.04"),
+            ("dmcgowan/myapp", "docker.io/dmcgowan/myapp:latest"),
+            ("dmcgowan/myapp:v1", "docker.io/dmcgowan/myapp:v1"),
+            ("reg.io:5000/foo", "reg.io:5000/foo:latest"),
+            ("reg.io:5000/foo:dev", "reg.io:5000/foo:dev"),
+            ("localhost/foo", "localhost/foo:latest"),
+            ("localhost:5000/foo/bar", "localhost:5000/foo/bar:latest"),
+            ("docker.io:443/foo", "docker.io:443/foo:latest"),
+        ] {
+            assert_eq!(normalize(input).as_str(), expected, "{input}");
+            assert_eq!(normalize(expected).as_str(), expected, "{expected}");
         }
 
-        {
-            let name = ImageNameRef::new("reg.io:12345/org-name/img-name:latest").unwrap();
-            assert_eq!(name.domain(), Some("reg.io"));
-            assert_eq!(name.port(), Some("12345"));
-            assert_eq!(name.registry(), Some("reg.io:12345"));
-            assert_eq!(name.path(), "org-name/img-name");
-            assert_eq!(name.tag(), Some("latest"));
-            assert_eq!(name.digest(), None);
-            assert_eq!(name.digest_algorithm(), None);
-            assert_eq!(name.digest_hex(), None);
+        assert_eq!(
+            normalize(&format!("python@{digest}")).as_str(),
+            format!("docker.io/library/python@{digest}")
+        );
+        assert_eq!(
+            normalize(&format!("python:3.11@{digest}")).as_str(),
+            format!("docker.io/library/python:3.11@{digest}")
+        );
+        assert_eq!(
+            normalize(&format!("reg.io/foo@{digest}")).as_str(),
+            format!("reg.io/foo@{digest}")
+        );
+
+        let name = ImageName::new("busybox".to_owned()).unwrap().normalize();
+        assert_eq!(name.domain(), Some("docker.io"));
+        assert_eq!(name.port(), None);
+        assert_eq!(name.registry(), Some("docker.io"));
+        assert_eq!(name.path(), "library/busybox");
+        assert_eq!(name.tag(), Some("latest"));
+        assert_eq!(name.digest(), None);
+
+        let name = normalize("reg.io:5000/foo");
+        assert_eq!(name.domain(), Some("reg.io"));
+        assert_eq!(name.port(), Some("5000"));
+        assert_eq!(name.path(), "foo");
+        assert_eq!(name.tag(), Some("latest"));
+    }
+
+    #[test]
+    fn path_components_work() {
+        for (input, components, namespace) in [
+            ("img-name", &["img-name"][..], None),
+            ("reg.io:5000/img__name:latest", &["img__name"], None),
+            ("org-name/img-name", &["org-name", "img-name"], Some("org-name")),
+            (
+                "reg.io/org.name/team_a/img__name@sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef",
+                &["org.name", "team_a", "img__name"],
+                Some("org.name/team_a"),
+            ),
+        ] {
+            let name = ImageNameRef::new(input).unwrap();
+            assert_eq!(name.path_components().collect::<Vec<_>>(), components);
+            assert_eq!(name.name(), *components.last().unwrap());
+            assert_eq!(name.namespace(), namespace);
+
+            let owned = name.to_owned();
+            assert_eq!(owned.path_components().collect::<Vec<_>>(), components);
+            assert_eq!(owned.name(), name.name());
+            assert_eq!(owned.namespace(), namespace);
         }
 
-        {
-            let name = ImageNameRef::new(
-                "reg.io/org-name/img-name@sha256:01234567aaaaaaaa01234567aaaaaaaa",
-            )
+        // The results of a reference outlive the reference itself.
+        let buffer = String::from("org-name/img-name");
+        let name = {
+            let name = ImageNameRef::new(&buffer).unwrap();
+            name.name()
+        };
+        assert_eq!(name, "img-name");
+    }
+
+    #[test]
+    fn image_name_ref_with_works() {
+        let name = ImageNameRef::new("reg.io/org-name/img-name:latest").unwrap();
+        assert_eq!(
+            name.with_registry("other.io:5000").unwrap().as_str(),
+            "other.io:5000/org-name/img-name:latest"
+        );
+        assert_eq!(
+            name.with_tag("abc").unwrap().as_str(),
+            "reg.io/org-name/img-name:abc"
+        );
+        assert_eq!(
+            name.with_digest("sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef")
+                .unwrap()
+                .as_str(),
+            "reg.io/org-name/img-name:latest@sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef"
+        );
+        assert!(name.with_registry("not a registry").is_err());
+        assert!(name.with_digest("sha256:short").is_err());
+    }
+
+    /// Asserts that the spliced indices of `name` match those of parsing its buffer again.
+    fn assert_reparses(name: &ImageName, expected: &str) {
+        assert_eq!(name.as_str(), expected);
+        let parsed = ImageNameRef::new(expected).unwrap();
+        assert_eq!(name.domain(), parsed.domain(), "{expected}");
+        assert_eq!(name.port(), parsed.port(), "{expected}");
+        assert_eq!(name.path(), parsed.path(), "{expected}");
+        assert_eq!(name.tag(), parsed.tag(), "{expected}");
+        assert_eq!(
+            name.digest_algorithm(),
+            parsed.digest_algorithm(),
+            "{expected}"
+        );
+        assert_eq!(name.digest_hex(), parsed.digest_hex(), "{expected}");
+    }
+
+    #[test]
+    fn image_name_set_works() {
+        let digest = "sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let other_digest =
+            "sha512:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef\
+                            0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let mut name: ImageName = format!("reg.io/org-name/img-name:latest@{digest}")
+            .parse()
             .unwrap();
-            assert_eq!(name.domain(), Some("reg.io"));
-            assert_eq!(name.port(), None);
-            assert_eq!(name.registry(), Some("reg.io"));
-            assert_eq!(name.path(), "org-name/img-name");
-            assert_eq!(name.tag(), None);
-            assert_eq!(
-                name.digest(),
-                Some("sha256:01234567aaaaaaaa01234567aaaaaaaa")
-            );
-            assert_eq!(name.digest_algorithm(), Some("sha256"));
-            assert_eq!(name.digest_hex(), Some("01234567aaaaaaaa01234567aaaaaaaa"));
-        }
 
-        {
-            assert_eq!(ImageNameRef::new(".").err().unwrap(), InvalidContainerImageNameMarker); // invalid path.
-            assert_eq!(
-                ImageNameRef::new("a@sha256:1234").err().unwrap(),
-                InvalidContainerImageNameMarker,
-            ); // digest too short.
-        }
+        name.set_tag("v1.2").unwrap();
+        assert_reparses(&name, &format!("reg.io/org-name/img-name:v1.2@{digest}"));
+
+        name.set_registry("localhost:5000").unwrap();
+        assert_reparses(
+            &name,
+            &format!("localhost:5000/org-name/img-name:v1.2@{digest}"),
+        );
+
+        name.set_digest(other_digest).unwrap();
+        assert_reparses(
+            &name,
+            &format!("localhost:5000/org-name/img-name:v1.2@{other_digest}"),
+        );
+
+        name.clear_digest();
+        assert_reparses(&name, "localhost:5000/org-name/img-name:v1.2");
+
+        name.clear_tag();
+        assert_reparses(&name, "localhost:5000/org-name/img-name");
+        name.clear_tag();
+        name.clear_digest();
+        assert_reparses(&name, "localhost:5000/org-name/img-name");
+
+        name.set_tag("latest").unwrap();
+        assert_reparses(&name, "localhost:5000/org-name/img-name:latest");
+
+        let mut name: ImageName = format!("img-name@{digest}").parse().unwrap();
+        name.set_tag("dev").unwrap();
+        assert_reparses(&name, &format!("img-name:dev@{digest}"));
+        name.clear_tag();
+        assert_reparses(&name, &format!("img-name@{digest}"));
+        name.set_registry("reg.io").unwrap();
+        assert_reparses(&name, &format!("reg.io/img-name@{digest}"));
     }
 
     #[test]
-    fn image_name_builder_works() {
-        {
-            assert_eq!(
-                ImageNameRef::new("org-name/img-name")
-                    .unwrap()
-                    .as_builder()
-                    .with_algorithm_and_hex("sha256", "12345678aaaaaaaa12345678aaaaaaaa")
-                    .build()
-                    .unwrap()
-                    .as_str(),
-                "org-name/img-name@sha256:12345678aaaaaaaa12345678aaaaaaaa"
-            );
-        }
+    fn image_name_set_leaves_invalid_unchanged() {
+        let mut name: ImageName = "reg.io/img-name:latest".parse().unwrap();
+
+        let error = name.set_tag("-dev").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"invalid container image name: "reg.io/img-name:-dev": invalid tag at byte 16"#
+        );
+        let error = name
+            .set_digest("sha256:0123456789abcdef0123456789abcdef")
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"invalid container image name: "reg.io/img-name:latest@sha256:0123456789abcdef0123456789abcdef": the digest hex at byte 30 has 32 characters but a sha256 digest has 64"#
+        );
+        assert_eq!(
+            name.set_registry("reg_io:5000").unwrap_err().reason(),
+            InvalidContainerImageNameReason::InvalidDomain { offset: 0 }
+        );
+        assert!(name.set_digest("sha256").is_err());
+        assert!(name.set_registry("not a registry").is_err());
+        assert!(name.set_registry("reg.io/nested").is_err());
+        assert!(name.set_registry("myorg").is_err());
+        name.set_registry("myregistry:5000").unwrap();
+        assert_reparses(&name, "myregistry:5000/img-name:latest");
+        name.set_registry("reg.io").unwrap();
+        assert!(name.set_tag("a:b").is_err());
+
+        assert_reparses(&name, "reg.io/img-name:latest");
     }
 
-    #[cfg(feature = "serde")]
     #[test]
-    fn image_name_serde_works() {
-        {
-            let des = ImageName::new("org-name/img-name".to_string()).unwrap();
-            let ser = r#""org-name/img-name""#;
-            assert_eq!(serde_json::to_string(&des).unwrap(), ser);
-            assert_eq!(serde_json::from_str::<ImageName>(ser).unwrap(), des);
+    fn familiar_name_works() {
+        let digest = "sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let familiar = |value: &str, truncate_digest| {
+            ImageNameRef::new(value)
+                .unwrap()
+                .familiar_name(truncate_digest)
+                .into_owned()
+        };
+
+        for (input, expected) in [
+            ("docker.io/library/busybox:latest", "busybox"),
+            ("index.docker.io/library/busybox", "busybox"),
+            ("library/busybox", "busybox"),
+            ("docker.io/library/ubuntu:22.04", "ubuntu:22.04"),
+            ("docker.io/dmcgowan/myapp:latest", "dmcgowan/myapp"),
+            ("docker.io/library/foo/bar", "library/foo/bar"),
+            ("docker.io/foo.bar/baz", "docker.io/foo.bar/baz"),
+            ("docker.io/localhost/baz", "docker.io/localhost/baz"),
+            ("docker.io:443/library/foo", "docker.io:443/library/foo"),
+            ("reg.io:5000/foo:latest", "reg.io:5000/foo"),
+            ("reg.io/library/foo:dev", "reg.io/library/foo:dev"),
+        ] {
+            assert_eq!(familiar(input, false), expected, "{input}");
+            assert_eq!(familiar(input, true), expected, "{input}");
+            let repository = |value| {
+                let name = ImageNameRef::new(value).unwrap().normalize();
+                (name.registry().map(str::to_owned), name.path().to_owned())
+            };
+            assert_eq!(repository(expected), repository(input), "{input}");
         }
 
-        {
-            let des = ImageNameRef::new("org-name/img-name").unwrap();
-            let ser = r#""org-name/img-name""#;
-            assert_eq!(serde_json::to_string(&des).unwrap(), ser);
-            assert_eq!(serde_json::from_str::<ImageNameRef>(ser).unwrap(), des);
+        assert_eq!(
+            familiar(&format!("docker.io/library/python:latest@{digest}"), false),
+            format!("python@{digest}")
+        );
+        assert_eq!(
+            familiar(&format!("docker.io/library/python:3.11@{digest}"), true),
+            "python:3.11@sha256:0123456789ab"
+        );
+        assert_eq!(
+            familiar(&format!("reg.io/foo@{digest}"), true),
+            "reg.io/foo@sha256:0123456789ab"
+        );
+
+        let name = ImageName::new("docker.io/library/busybox".to_owned()).unwrap();
+        assert!(matches!(name.familiar_name(true), Cow::Borrowed("busybox")));
+    }
+
+    #[test]
+    fn alternate_display_truncates_the_digest() {
+        let digest = "sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let with_digest = format!("reg.io:5000/org/img:v1@{digest}");
+        let name = ImageName::new(with_digest.clone()).unwrap();
+        assert_eq!(name.to_string(), with_digest);
+        assert_eq!(
+            format!("{name:#}"),
+            "reg.io:5000/org/img:v1@sha256:0123456789ab\u{2026}"
+        );
+        assert_eq!(
+            format!("{:#}", name.as_ref()),
+            "reg.io:5000/org/img:v1@sha256:0123456789ab\u{2026}"
+        );
+        assert_eq!(
+            format!("{:#}", ImageNameRef::new(&format!("img@{digest}")).unwrap()),
+            "img@sha256:0123456789ab\u{2026}"
+        );
+        // The width applies to the truncated name.
+        let short = format!("img@{digest}");
+        let short = ImageNameRef::new(&short).unwrap();
+        assert_eq!(format!("[{short:<30}]"), format!("[img@{digest}]"));
+        assert_eq!(
+            format!("[{short:<#30}]"),
+            "[img@sha256:0123456789ab\u{2026}      ]"
+        );
+
+        for value in ["reg.io:5000/org/img:v1", "img", "img:latest"] {
+            let name = ImageNameRef::new(value).unwrap();
+            assert_eq!(format!("{name:#}"), value);
+            assert_eq!(format!("{name:#}"), format!("{name}"));
         }
     }
+
+    #[test]
+    fn short_digest_works() {
+        let digest = "sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let name = ImageName::new(format!("img:v1@{digest}")).unwrap();
+        assert_eq!(name.short_digest(), Some("0123456789ab"));
+        assert_eq!(name.as_ref().short_digest(), Some("0123456789ab"));
+        assert_eq!(ImageNameRef::new("img:v1").unwrap().short_digest(), None);
+    }
 }
@@ -0,0 +1,71 @@
+//! Counts the allocations of rewriting the sections of an image name. This test lives in its own binary because it
+//! installs a global allocator.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+use container_image_name::{ImageName, ImageNameRef};
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns the number of allocations that `f` performs on the current thread.
+fn count_allocations<T>(f: impl FnOnce() -> T) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    let value = f();
+    let after = ALLOCATIONS.with(Cell::get);
+    drop(value);
+    after - before
+}
+
+const NAME: &str =
+    "berkeley-docker.taila1eba.ts.net/launch:0123456789abcdef0123456789abcdef01234567";
+const REGISTRY: &str = "docker-registry.docker-registry.svc.cluster.local:5000";
+
+#[test]
+fn registry_rewrite_allocates_once() {
+    let name = ImageNameRef::new(NAME).unwrap();
+
+    // Warm up anything that is lazily initialized.
+    let expected = name.with_registry(REGISTRY).unwrap();
+
+    // Formatting the name and parsing it is the baseline that allocates the most.
+    let formatted = count_allocations(|| {
+        ImageName::new(format!(
+            "{REGISTRY}/{}:{}",
+            name.path(),
+            name.tag().unwrap()
+        ))
+        .unwrap()
+    });
+    let built = count_allocations(|| name.as_builder().with_registry(REGISTRY).build().unwrap());
+    let one_shot = count_allocations(|| name.with_registry(REGISTRY).unwrap());
+
+    // Only the buffer of the result is allocated.
+    assert_eq!(built, 1, "allocations of the builder");
+    assert_eq!(one_shot, 1, "allocations of with_registry");
+    assert!(
+        formatted >= one_shot,
+        "format and parse allocated {formatted} times, with_registry {one_shot} times"
+    );
+    assert_eq!(name.with_registry(REGISTRY).unwrap(), expected);
+}
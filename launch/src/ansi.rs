@@ -2,6 +2,7 @@
 
 pub const EMPTY: &str = "";
 pub const RESET: &str = "\x1b[0m";
+pub const BOLD: &str = "\x1b[1m";
 
 pub const BLACK: &str = "\x1b[30m";
 pub const RED: &str = "\x1b[31m";
@@ -1,13 +1,15 @@
 mod docker;
+pub mod dockerfile;
 mod kaniko;
 
 use container_image_name::ImageNameRef;
 pub use docker::*;
 pub use kaniko::*;
+use log::{debug, info, warn};
 
 use crate::{
     git::{self},
-    Result,
+    registry, Result,
 };
 
 pub struct BuildArgs<'a> {
@@ -22,3 +24,33 @@ pub struct BuildOutput {
 pub trait Builder {
     fn build<'a>(&'a self, args: BuildArgs<'a>) -> Result<BuildOutput>;
 }
+
+fn query_image_digest(
+    image: ImageNameRef<'_>,
+    client: &crate::http::Http,
+) -> Result<Option<String>> {
+    let tag = image.expect_tag()?;
+    let registry = image.expect_registry()?;
+    registry::Client::for_registry(client, registry).manifest_digest(image.path(), tag)
+}
+
+/// Returns the digest of `image` when the registry already has it, so that it does not have to be built again. The tag
+/// of `image` must identify the commit it is built from. Failing to query the registry is not an error because the
+/// image can still be built.
+fn existing_digest(image: ImageNameRef<'_>, client: &crate::http::Http) -> Option<String> {
+    debug!("Checking if image {image:?} is already available in registry...");
+    match query_image_digest(image, client) {
+        Ok(Some(digest)) => {
+            info!("Reusing existing image {image} built from this commit");
+            Some(digest)
+        }
+        Ok(None) => {
+            debug!("Did not find image {image:?} in registry");
+            None
+        }
+        Err(e) => {
+            warn!("Failed to check if image {image:?} is already available in registry: {e:?}");
+            None
+        }
+    }
+}
@@ -1,15 +1,41 @@
+use std::path::Path;
+
 use super::{BuildArgs, BuildOutput, Builder};
-use crate::{docker, Result};
+use crate::{docker, git::is_full_git_commit_hash, Result};
 
-pub struct DockerBuilder;
+pub struct DockerBuilder<'a> {
+    pub client: &'a crate::http::Http,
+    /// Build the image even when the registry already has it.
+    pub force_rebuild: bool,
+    /// See [`super::dockerfile`].
+    pub dockerfile: &'a Path,
+}
 
-impl Builder for DockerBuilder {
+/// Whether an image in the registry may be used instead of building one. The tag of a clean working tree is the hash of
+/// the commit, so an image with that tag was built from the same sources. Dirty working trees are always built because
+/// their changes are not part of any commit.
+fn may_reuse(is_clean: bool, force_rebuild: bool, tag: Option<&str>) -> bool {
+    is_clean && !force_rebuild && tag.is_some_and(is_full_git_commit_hash)
+}
+
+impl Builder for DockerBuilder<'_> {
     fn build<'a>(&'a self, args: BuildArgs<'a>) -> Result<BuildOutput> {
+        if may_reuse(
+            args.git_info.is_clean(),
+            self.force_rebuild,
+            args.image.tag(),
+        ) {
+            if let Some(digest) = super::existing_digest(args.image, self.client) {
+                return Ok(BuildOutput { digest });
+            }
+        }
+
         // This conversion is necessary because the build arguments for the backend may differ from the
         // build arguments accepted by the docker command line abstraction.
         let docker_build_output = docker::build_and_push(docker::BuildArgs {
             git_commit_hash: &args.git_info.commit_hash,
             image: args.image,
+            dockerfile: self.dockerfile,
             platform: docker::Platform::LinuxAmd64,
         })?;
         Ok(BuildOutput {
@@ -17,3 +43,19 @@ impl Builder for DockerBuilder {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn may_reuse_works() {
+        let commit = "0123456789abcdef0123456789abcdef01234567";
+        assert!(may_reuse(true, false, Some(commit)));
+        assert!(!may_reuse(true, true, Some(commit)));
+        assert!(!may_reuse(false, false, Some(commit)));
+        assert!(!may_reuse(false, true, Some(commit)));
+        assert!(!may_reuse(true, false, Some("alice-1f2e3d4c")));
+        assert!(!may_reuse(true, false, None));
+    }
+}
@@ -0,0 +1,183 @@
+//! Checks that the Dockerfile can be found before building. Without these checks, running `launch submit` from a
+//! subdirectory of the project fails with the message of docker, or with kaniko only after its Pod was created.
+
+use std::path::{Path, PathBuf};
+
+use crate::{git, Result};
+
+/// The Dockerfile that is built when `--dockerfile` is not passed.
+pub const DEFAULT: &str = "Dockerfile";
+
+/// The Dockerfile that kaniko prefers over [`DEFAULT`] when it exists, for instructions that only work with kaniko.
+pub const KANIKO: &str = "Dockerfile.kaniko";
+
+/// Returns the Dockerfile to build when `--dockerfile` is not passed, relative to `working_directory`.
+pub fn default_path(working_directory: &Path, kaniko: bool) -> PathBuf {
+    if kaniko && working_directory.join(KANIKO).exists() {
+        PathBuf::from(KANIKO)
+    } else {
+        PathBuf::from(DEFAULT)
+    }
+}
+
+/// Returns the closest parent directory of `working_directory` that contains `dockerfile`, up to the root of the
+/// working tree `git_dir`.
+fn find_upward(working_directory: &Path, git_dir: &Path, dockerfile: &Path) -> Option<PathBuf> {
+    working_directory
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(git_dir))
+        .find(|dir| dir.join(dockerfile).is_file())
+        .map(Path::to_owned)
+}
+
+/// Returns the path of `dockerfile` in the parent directory `dir` relative to `working_directory`, for example
+/// `../Dockerfile`.
+fn relative_path(working_directory: &Path, dir: &Path, dockerfile: &Path) -> PathBuf {
+    let depth = working_directory
+        .strip_prefix(dir)
+        .map_or(0, |path| path.components().count());
+    std::iter::repeat_n(Path::new(".."), depth)
+        .collect::<PathBuf>()
+        .join(dockerfile)
+}
+
+/// Checks that `dockerfile` exists relative to `working_directory`. Otherwise the error suggests the closest
+/// `dockerfile` in a parent directory within the working tree.
+pub fn check_exists(working_directory: &Path, git_dir: &Path, dockerfile: &Path) -> Result<()> {
+    if working_directory.join(dockerfile).is_file() {
+        return Ok(());
+    }
+    let missing = format!(
+        "There is no {} in {}, which is the build context.",
+        dockerfile.display(),
+        working_directory.display()
+    );
+    Err(match find_upward(working_directory, git_dir, dockerfile) {
+        Some(dir) => format!(
+            "{missing} Found {} in {}. Either `cd {}` and submit from there, or pass `--dockerfile {}` to build it with the current directory as the build context.",
+            dockerfile.display(),
+            dir.display(),
+            dir.display(),
+            relative_path(working_directory, &dir, dockerfile).display()
+        ),
+        None => format!(
+            "{missing} Submit from the directory of the Dockerfile, or pass its path with `--dockerfile`."
+        ),
+    }
+    .into())
+}
+
+/// Checks that `dockerfile`, relative to `working_directory`, is tracked by git. Kaniko builds from a clone of the
+/// pushed commit, so it does not see files that only exist in the working tree.
+pub fn check_tracked(working_directory: &Path, dockerfile: &Path) -> Result<()> {
+    if git::is_tracked(working_directory, dockerfile)? {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} is not tracked by git, so kaniko, which builds from a clone of the pushed commit, would not find it. Commit and push it, or build with `--builder docker`.",
+            dockerfile.display()
+        )
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{process, temp_path::TempDir};
+
+    /// Creates a git repository with the directories `a/b` in a new temporary directory, which is returned with the
+    /// root of the repository.
+    fn temp_repo() -> (TempDir, PathBuf) {
+        let dir = TempDir::new();
+        let repo = dir.path().join("repo");
+        std::fs::create_dir_all(repo.join("a/b")).unwrap();
+        process::command!("git", "init", "--quiet", &repo)
+            .output()
+            .unwrap();
+        (dir, repo)
+    }
+
+    #[test]
+    fn default_path_prefers_the_kaniko_dockerfile() {
+        let (_dir, repo) = temp_repo();
+        assert_eq!(default_path(&repo, true), Path::new(DEFAULT));
+        std::fs::write(repo.join(KANIKO), "FROM busybox\n").unwrap();
+        assert_eq!(default_path(&repo, true), Path::new(KANIKO));
+        assert_eq!(default_path(&repo, false), Path::new(DEFAULT));
+    }
+
+    #[test]
+    fn check_exists_suggests_a_dockerfile_upward() {
+        let (dir, repo) = temp_repo();
+        let b = repo.join("a/b");
+        let dockerfile = Path::new(DEFAULT);
+
+        // A Dockerfile outside of the working tree is not suggested.
+        std::fs::write(dir.path().join(DEFAULT), "FROM busybox\n").unwrap();
+        assert_eq!(find_upward(&b, &repo, dockerfile), None);
+        let error = check_exists(&b, &repo, dockerfile).unwrap_err().to_string();
+        assert_eq!(
+            error,
+            format!(
+                "There is no Dockerfile in {}, which is the build context. Submit from the directory of the Dockerfile, or pass its path with `--dockerfile`.",
+                b.display()
+            )
+        );
+
+        std::fs::write(repo.join(DEFAULT), "FROM busybox\n").unwrap();
+        assert_eq!(find_upward(&b, &repo, dockerfile), Some(repo.clone()));
+        let error = check_exists(&b, &repo, dockerfile).unwrap_err().to_string();
+        assert!(
+            error.contains(&format!(
+                "Either `cd {}` and submit from there, or pass `--dockerfile ../../Dockerfile`",
+                repo.display()
+            )),
+            "{error}"
+        );
+
+        // The closest Dockerfile is suggested.
+        std::fs::write(repo.join("a").join(DEFAULT), "FROM busybox\n").unwrap();
+        let error = check_exists(&b, &repo, dockerfile).unwrap_err().to_string();
+        assert!(error.contains("`--dockerfile ../Dockerfile`"), "{error}");
+
+        assert!(check_exists(&repo.join("a"), &repo, dockerfile).is_ok());
+        assert!(check_exists(&b, &repo, Path::new("../Dockerfile")).is_ok());
+    }
+
+    #[test]
+    fn relative_path_works() {
+        let dockerfile = Path::new("docker/train.Dockerfile");
+        assert_eq!(
+            relative_path(Path::new("/repo/a/b"), Path::new("/repo"), dockerfile),
+            Path::new("../../docker/train.Dockerfile")
+        );
+        assert_eq!(
+            relative_path(Path::new("/repo"), Path::new("/repo"), dockerfile),
+            dockerfile
+        );
+    }
+
+    #[test]
+    fn check_tracked_requires_the_dockerfile_in_the_index() {
+        let (_dir, repo) = temp_repo();
+        let b = repo.join("a/b");
+        std::fs::write(repo.join(DEFAULT), "FROM busybox\n").unwrap();
+
+        let error = check_tracked(&b, Path::new("../../Dockerfile"))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.starts_with("../../Dockerfile is not tracked by git"),
+            "{error}"
+        );
+
+        process::command!("git", "-C", &repo, "add", DEFAULT)
+            .output()
+            .unwrap();
+        assert!(check_tracked(&b, Path::new("../../Dockerfile")).is_ok());
+        assert!(check_tracked(&repo, Path::new(DEFAULT)).is_ok());
+        assert!(check_tracked(&repo, Path::new(KANIKO)).is_err());
+    }
+}
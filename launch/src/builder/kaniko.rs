@@ -1,67 +1,61 @@
-use std::{path::Path, time::Duration};
+mod build_log;
+pub mod cache;
+
+use std::{
+    io::{self, IsTerminal as _, Write},
+    path::Path,
+};
 
 use ::kubernetes::models as k8s;
-use container_image_name::ImageNameRef;
-use log::{debug, warn};
+use container_image_name::{image_name, ImageName, ImageNameRef};
+use log::debug;
 
 use super::{BuildArgs, BuildOutput, Builder, Result};
 use crate::{
     executor::{self, Deadline, KANIKO_POST_BUILD_TIMEOUT, POLLING_INTERVAL},
     git::is_full_git_commit_hash,
     kubectl::{self},
+    process,
 };
 
 // see ansible/playbooks/roles/talos_k8s_configs/templates/launch.yml
 pub const KANIKO_GITHUB_TOKEN: &str = "kaniko-github-token";
 pub const KANIKO_CACHE_PVC_NAME: &str = "kaniko-cache";
 pub const KANIKO_CACHE_PVC_MOUNT_PATH: &str = "/var/run/uv";
+/// The repository kaniko clones the build context from.
+// TODO support repo git url
+pub const KANIKO_CONTEXT_REPOSITORY: &str = "github.com/Astera-org/launch";
 
-// Account for different image types in the Registry API
-// Authoritive list: https://github.com/google/go-containerregistry/blob/6bce25ecf0297c1aa9072bc665b5cf58d53e1c54/pkg/v1/types/types.go#L22
-pub const ACCEPTABLE_MANIFEST_TYPES: &[&str] = &[
-    "application/vnd.oci.image.manifest.v1+json", // kaniko builder
-    "application/vnd.oci.image.index.v1+json",    // docker builder
-];
+const KANIKO_IMAGE: ImageNameRef = image_name!("gcr.io/kaniko-project/executor:latest");
 
 pub struct KanikoBuilder<'a> {
     pub kubectl: &'a kubectl::Kubectl<'a>,
     pub namespace: &'a str,
     pub user: Option<&'a str>,
     pub working_directory: &'a Path,
-    pub client: &'a reqwest::blocking::Client,
+    /// The Dockerfile relative to `working_directory`, see [`super::dockerfile`].
+    pub dockerfile: &'a Path,
+    pub client: &'a crate::http::Http,
+    /// Print the kaniko logs as they are instead of a summary of the Dockerfile steps.
+    pub raw_logs: bool,
+    /// Build the image even when the registry already has it.
+    pub force_rebuild: bool,
+    /// With [`executor::Progress::Json`], the finished Dockerfile steps are reported as events.
+    pub progress: executor::Progress,
 }
 
 impl Builder for KanikoBuilder<'_> {
     fn build<'a>(&'a self, args: BuildArgs<'a>) -> Result<BuildOutput> {
         let Self { kubectl, .. } = self;
 
-        debug!(
-            "Checking if image {:?} is already available in registry...",
-            args.image
-        );
-        if !is_full_git_commit_hash(args.image.tag().unwrap()) {
+        if !is_full_git_commit_hash(args.image.expect_tag()?) {
             return Err("Image tag is not valid, check debug logs for more details".into());
         }
-        match query_image_digest(args.image, self.client) {
-            Ok(Some(digest)) => {
-                let image = args
-                    .image
-                    .as_builder()
-                    .with_digest(&digest)
-                    .build()
-                    .unwrap();
-                debug!("Using already available image {image:?}");
+        // Kaniko builds the pushed commit, so the image is reusable even when the working tree is dirty.
+        if !self.force_rebuild {
+            if let Some(digest) = super::existing_digest(args.image, self.client) {
                 return Ok(BuildOutput { digest });
             }
-            Ok(None) => {
-                debug!("Did not find image {:?} in registry", args.image);
-            }
-            Err(e) => {
-                warn!(
-                    "Failed to check if image {:?} is already available in registry: {:?}",
-                    args.image, e
-                );
-            }
         }
 
         debug!("Building image: {:?}", args.image);
@@ -80,7 +74,27 @@ impl Builder for KanikoBuilder<'_> {
         };
         let pod = kubectl.create(&serde_json::to_string(&self.pod_spec(&args)?)?)?;
 
-        executor::wait_for_and_follow_pod_logs(kubectl, &pod.namespace, &pod.name)?;
+        // The summary is only useful to humans, so we show the raw logs when stderr is redirected.
+        let summarize = !self.raw_logs && io::stderr().is_terminal();
+        let mut reporter = executor::Reporter::new(self.progress);
+        // The events need the steps even when the raw logs are shown.
+        let mut progress = (summarize || self.progress == executor::Progress::Json)
+            .then(build_log::BuildProgress::new);
+        let mut on_line = progress.as_mut().map(|progress| {
+            |line: &str| {
+                if !summarize {
+                    writeln!(process::output_stream(), "{line}")?;
+                }
+                progress.push(line, &mut summary_output(summarize))?;
+                report_steps(progress, &mut reporter).map_err(io::Error::other)
+            }
+        });
+        executor::wait_for_and_follow_pod_log_lines(
+            kubectl,
+            &pod.namespace,
+            &pod.name,
+            on_line.as_mut().map(|f| f as &mut executor::OnLine),
+        )?;
 
         // Pod status has a lag to update, so we need to wait
         let deadline = Deadline::after(KANIKO_POST_BUILD_TIMEOUT);
@@ -95,10 +109,19 @@ impl Builder for KanikoBuilder<'_> {
                     })?;
                 }
                 kubectl::PodPhase::Succeeded => {
+                    if let Some(progress) = progress.as_mut() {
+                        progress.finish(true, &mut summary_output(summarize))?;
+                        report_steps(progress, &mut reporter)?;
+                    }
+                    cache::warn_if_almost_full(kubectl, self.namespace);
                     break status;
                 }
                 kubectl::PodPhase::Failed => {
-                    return Err("kaniko build failed, inspect the build output to learn why".into())
+                    if let Some(progress) = progress.as_mut() {
+                        progress.finish(false, &mut summary_output(summarize))?;
+                        report_steps(progress, &mut reporter)?;
+                    }
+                    return Err("kaniko build failed, inspect the build output to learn why".into());
                 }
                 other => return Err(format!("unespected status {}", other).into()),
             }
@@ -121,50 +144,83 @@ impl Builder for KanikoBuilder<'_> {
             other => return Err(format!("unexpected termination state: {}", other).into()),
         };
 
-        let digest = state
-            .message
-            .as_deref()
-            .ok_or("build container should have termination state message")?
-            .trim();
-
         Ok(BuildOutput {
-            digest: digest.to_string(),
+            digest: termination_digest(state.message.as_deref())?,
         })
     }
 }
 
+/// Where the summary of the Dockerfile steps is printed, which is nowhere when the raw logs are shown instead.
+fn summary_output(summarize: bool) -> Box<dyn Write> {
+    if summarize {
+        Box::new(io::stderr())
+    } else {
+        Box::new(io::sink())
+    }
+}
+
+fn report_steps(
+    progress: &mut build_log::BuildProgress,
+    reporter: &mut executor::Reporter,
+) -> Result<()> {
+    for step in progress.take_finished_steps() {
+        reporter.emit(executor::Event::BuildStepFinished {
+            step: step.number,
+            instruction: step.instruction,
+            seconds: step.took.as_secs(),
+            cached: step.cached,
+            succeeded: step.succeeded,
+        })?;
+    }
+    Ok(())
+}
+
+/// Returns the digest that kaniko wrote to the termination log, checking that it is a valid digest so that a bad one
+/// fails the build rather than the submission.
+fn termination_digest(message: Option<&str>) -> Result<String> {
+    let digest = message
+        .ok_or("build container should have termination state message")?
+        .trim();
+    ImageName::builder("kaniko")
+        .with_digest(digest)
+        .build()
+        .map_err(|error| format!("kaniko reported an invalid digest {digest:?}: {error}"))?;
+    Ok(digest.to_owned())
+}
+
+/// Returns the `generateName` of the build Pod, `kaniko-<user>-`, with the user label shortened so that the name fits
+/// in [`kubectl::MAX_NAME_LEN`] characters.
+fn pod_generate_name(user: Option<&str>) -> String {
+    const PREFIX: &str = "kaniko-";
+    let mut out = PREFIX.to_owned();
+    if let Some(user) = user {
+        let max_len = kubectl::MAX_NAME_LEN - kubectl::GENERATED_SUFFIX_LEN - PREFIX.len() - 1;
+        let user = user[..user.len().min(max_len)].trim_end_matches('-');
+        if !user.is_empty() {
+            out.push_str(user);
+            out.push('-');
+        }
+    }
+    out
+}
+
 impl KanikoBuilder<'_> {
     fn pod_spec(&self, args: &BuildArgs) -> Result<k8s::V1Pod> {
         let Self {
             working_directory,
+            dockerfile,
             namespace,
             user,
             ..
         } = *self;
 
-        let generate_name = {
-            let mut out = "kaniko-".to_owned();
-            if let Some(user) = user {
-                out.push_str(user);
-                out.push('-');
-            }
-            out
-        };
-
-        // TODO support repo git url
-        let push_remote = "github.com/Astera-org/launch";
+        let generate_name = pod_generate_name(user);
 
         // Does not take into account symlinks and what not, should be good enough.
         let sub_path = working_directory
             .strip_prefix(&args.git_info.dir)?
             .to_owned();
 
-        // Prefer Dockerfile.kaniko if it exists
-        let mut dockerfile = "Dockerfile";
-        if working_directory.join("Dockerfile.kaniko").exists() {
-            dockerfile = "Dockerfile.kaniko";
-        }
-
         Ok(k8s::V1Pod {
             api_version: Some("v1".to_owned()),
             kind: Some("Pod".to_owned()),
@@ -177,15 +233,15 @@ impl KanikoBuilder<'_> {
                 restart_policy: Some("Never".to_owned()),
                 containers: vec![k8s::V1Container {
                     name: "main".to_owned(),
-                    image: Some("gcr.io/kaniko-project/executor:latest".to_owned()),
+                    image: Some(KANIKO_IMAGE.to_string()),
                     args: Some(vec![
                         format!(
-                            "--context=git://{push_remote}#{commit}",
+                            "--context=git://{KANIKO_CONTEXT_REPOSITORY}#{commit}",
                             commit = args.git_info.commit_hash
                         ),
                         format!("--context-sub-path={}", sub_path.display()),
-                        // explicitly specify dockerfile, to support kaniko Dockerfile
-                        format!("--dockerfile={}", dockerfile),
+                        // Relative to the context sub path.
+                        format!("--dockerfile={}", dockerfile.display()),
                         format!("--destination={}", args.image),
                         format!("--build-arg=COMMIT_HASH={}", args.git_info.commit_hash),
                         // allow push to cluster registry
@@ -228,33 +284,53 @@ impl KanikoBuilder<'_> {
     }
 }
 
-fn query_image_digest(
-    image: ImageNameRef<'_>,
-    client: &reqwest::blocking::Client,
-) -> Result<Option<String>, Box<dyn std::error::Error>> {
-    let image_tag = image.tag().ok_or("Expected image tag not found")?;
-    let registry_lookup_url = format!(
-        "https://{registry}/v2/{image_path}/manifests/{image_tag}",
-        registry = image.registry().ok_or("Image registry must be set")?,
-        image_path = image.path(),
-        image_tag = image_tag,
-    );
-    // Registry API requires mediaType Header
-    // https://github.com/opencontainers/image-spec/blob/main/manifest.md#image-manifest
-    let resp = client
-        .head(&registry_lookup_url)
-        .header("Accept", ACCEPTABLE_MANIFEST_TYPES.join(","))
-        .timeout(Duration::from_secs(5))
-        .send()
-        .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
-    if resp.status().is_success() {
-        // Registry API should always return a digest
-        // https://distribution.github.io/distribution/spec/api/#digest-header
-        let digest = resp
-            .headers()
-            .get("Docker-Content-Digest")
-            .ok_or("Expected image digest not found")?;
-        return Ok(Some(digest.to_str().unwrap().to_string()));
+#[cfg(test)]
+mod tests {
+    use container_image_name::ImageNameRef;
+
+    use super::*;
+
+    #[test]
+    fn pod_generate_name_works() {
+        assert_eq!(pod_generate_name(None), "kaniko-");
+        assert_eq!(pod_generate_name(Some("alice")), "kaniko-alice-");
+        for user in crate::user_host::awkward_user_labels() {
+            let name = pod_generate_name(user.as_deref());
+            let generated = format!("{name}{}", "x".repeat(kubectl::GENERATED_SUFFIX_LEN));
+            assert!(
+                kubectl::is_rfc_1035_label(&generated),
+                "{user:?} became {name:?}"
+            );
+            assert!(
+                generated.len() <= kubectl::MAX_NAME_LEN,
+                "{user:?} became {name:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn termination_digest_round_trips() {
+        let digest = format!("sha256:{}", "0123456789abcdef".repeat(4));
+        assert_eq!(
+            termination_digest(Some(&format!("{digest}\n"))).unwrap(),
+            digest
+        );
+
+        let image = ImageNameRef::new("registry.io/launch:abc").unwrap();
+        let pinned = image.with_digest(&digest).unwrap();
+        assert_eq!(pinned.digest(), Some(digest.as_str()));
+        assert_eq!(
+            ImageNameRef::new(pinned.as_str()).unwrap().digest(),
+            Some(digest.as_str())
+        );
+
+        assert!(termination_digest(None).is_err());
+        let error = termination_digest(Some("sha256:0123456789abcdef0123456789abcdef"))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("has 32 characters but a sha256 digest has 64"),
+            "{error}"
+        );
     }
-    Ok(None)
 }
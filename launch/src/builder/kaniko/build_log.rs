@@ -0,0 +1,550 @@
+//! Turns the kaniko build log into a compact list of Dockerfile steps.
+//!
+//! Kaniko logs through [logrus](https://github.com/sirupsen/logrus) which produces lines like `INFO[0042] RUN pip
+//! install .`, where the number in brackets is the number of seconds since kaniko started. When kaniko is started with
+//! `--log-timestamp`, the brackets contain an RFC 3339 timestamp instead. The output of the programs executed by `RUN`
+//! instructions is interleaved verbatim and can look like anything.
+
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    time::Duration,
+};
+
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+/// The Dockerfile instructions that kaniko logs when it starts executing them. `FROM` is reported as a stage instead.
+const INSTRUCTIONS: &[&str] = &[
+    "ADD",
+    "ARG",
+    "CMD",
+    "COPY",
+    "ENTRYPOINT",
+    "ENV",
+    "EXPOSE",
+    "HEALTHCHECK",
+    "LABEL",
+    "MAINTAINER",
+    "ONBUILD",
+    "RUN",
+    "SHELL",
+    "STOPSIGNAL",
+    "USER",
+    "VOLUME",
+    "WORKDIR",
+];
+
+/// How many lines of program output to keep for the step that is currently executing. They are printed when the build
+/// fails so that the cause is visible without `--raw-build-logs`.
+const OUTPUT_TAIL_LEN: usize = 50;
+
+/// Instructions longer than this are truncated in the progress view.
+const MAX_INSTRUCTION_LEN: usize = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warning,
+    Error,
+    Fatal,
+    Panic,
+}
+
+impl Level {
+    /// Parses the four letter abbreviation that logrus uses in its text format.
+    fn from_abbreviation(value: &str) -> Option<Self> {
+        Some(match value {
+            "TRAC" => Level::Trace,
+            "DEBU" => Level::Debug,
+            "INFO" => Level::Info,
+            "WARN" => Level::Warning,
+            "ERRO" => Level::Error,
+            "FATA" => Level::Fatal,
+            "PANI" => Level::Panic,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Time {
+    /// The time since kaniko started.
+    Elapsed(Duration),
+    /// The wall clock time, which kaniko logs when passed `--log-timestamp`.
+    Timestamp(OffsetDateTime),
+}
+
+/// A line that kaniko itself logged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry<'a> {
+    pub level: Level,
+    pub time: Time,
+    pub message: &'a str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Line<'a> {
+    Kaniko(Entry<'a>),
+    /// Anything that is not a kaniko log entry, usually the output of a `RUN` instruction.
+    Output(&'a str),
+}
+
+pub fn classify(line: &str) -> Line<'_> {
+    parse_entry(line).map_or(Line::Output(line), Line::Kaniko)
+}
+
+fn parse_entry(line: &str) -> Option<Entry<'_>> {
+    let level = Level::from_abbreviation(line.get(..4)?)?;
+    let (time, message) = line[4..].strip_prefix('[')?.split_once(']')?;
+
+    let time = if !time.is_empty() && time.bytes().all(|b| b.is_ascii_digit()) {
+        Time::Elapsed(Duration::from_secs(time.parse().ok()?))
+    } else {
+        Time::Timestamp(OffsetDateTime::parse(time, &Rfc3339).ok()?)
+    };
+
+    // logrus separates the time from the message with a space and pads short messages with spaces.
+    let message = message.strip_prefix(' ')?.trim();
+
+    Some(Entry {
+        level,
+        time,
+        message,
+    })
+}
+
+/// The build events that can be recognized from the message of a kaniko log entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event<'a> {
+    /// Kaniko started building a stage with the provided name, which is the image or the `AS` name of the stage.
+    Stage(&'a str),
+    /// Kaniko started executing a Dockerfile instruction.
+    Step(&'a str),
+    /// The layer of the current step was restored from the cache.
+    CacheHit,
+    /// Kaniko started pushing the image to the provided destination.
+    Push(&'a str),
+}
+
+pub fn event(message: &str) -> Option<Event<'_>> {
+    if let Some(rest) = message.strip_prefix("Building stage '") {
+        return Some(Event::Stage(rest.split_once('\'')?.0));
+    }
+    if message == "Found cached layer, extracting to filesystem" {
+        return Some(Event::CacheHit);
+    }
+    if let Some(destination) = message.strip_prefix("Pushing image to ") {
+        return Some(Event::Push(destination));
+    }
+    let keyword = message
+        .split_once(' ')
+        .map_or(message, |(keyword, _)| keyword);
+    INSTRUCTIONS
+        .contains(&keyword)
+        .then_some(Event::Step(message))
+}
+
+#[derive(Debug)]
+struct Step {
+    number: usize,
+    instruction: String,
+    start: Duration,
+    cached: bool,
+}
+
+/// A Dockerfile step that kaniko finished executing, for the `--progress json` events.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FinishedStep {
+    pub number: usize,
+    pub instruction: String,
+    pub took: Duration,
+    pub cached: bool,
+    /// False for the step that was executing when the build failed.
+    pub succeeded: bool,
+}
+
+/// Consumes kaniko log lines and writes a line per finished Dockerfile step with the time it took.
+#[derive(Debug, Default)]
+pub struct BuildProgress {
+    /// The first timestamp seen, used to convert timestamps into elapsed time.
+    origin: Option<OffsetDateTime>,
+    /// The time of the most recent kaniko log entry.
+    now: Duration,
+    step_count: usize,
+    current: Option<Step>,
+    output: VecDeque<String>,
+    finished: Vec<FinishedStep>,
+}
+
+impl BuildProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, line: &str, out: &mut impl Write) -> io::Result<()> {
+        let entry = match classify(line) {
+            Line::Kaniko(entry) => entry,
+            Line::Output(line) => {
+                if self.output.len() == OUTPUT_TAIL_LEN {
+                    self.output.pop_front();
+                }
+                self.output.push_back(line.to_owned());
+                return Ok(());
+            }
+        };
+
+        self.now = self.elapsed(entry.time);
+
+        match event(entry.message) {
+            Some(Event::Stage(name)) => {
+                self.finish_step(out)?;
+                writeln!(out, "Building stage {name}")?;
+            }
+            Some(Event::Step(instruction)) => {
+                self.finish_step(out)?;
+                self.step_count += 1;
+                self.current = Some(Step {
+                    number: self.step_count,
+                    instruction: instruction.to_owned(),
+                    start: self.now,
+                    cached: false,
+                });
+            }
+            Some(Event::CacheHit) => {
+                if let Some(step) = self.current.as_mut() {
+                    step.cached = true;
+                }
+            }
+            Some(Event::Push(destination)) => {
+                self.finish_step(out)?;
+                writeln!(out, "Pushing image to {destination}")?;
+            }
+            None => {
+                if entry.level >= Level::Warning {
+                    writeln!(out, "{line}")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reports the step that was executing when the log ended. When the build failed, the output of that step is
+    /// written too.
+    pub fn finish(&mut self, succeeded: bool, out: &mut impl Write) -> io::Result<()> {
+        if succeeded {
+            return self.finish_step(out);
+        }
+
+        if let Some(step) = self.current.take() {
+            writeln!(out, "{} failed", format_step(&step))?;
+            self.finished.push(FinishedStep {
+                took: self.now.saturating_sub(step.start),
+                succeeded: false,
+                number: step.number,
+                instruction: step.instruction,
+                cached: step.cached,
+            });
+        }
+        for line in self.output.drain(..) {
+            writeln!(out, "  {line}")?;
+        }
+        Ok(())
+    }
+
+    fn finish_step(&mut self, out: &mut impl Write) -> io::Result<()> {
+        self.output.clear();
+        let Some(step) = self.current.take() else {
+            return Ok(());
+        };
+        let took = self.now.saturating_sub(step.start);
+        write!(out, "{} {}s", format_step(&step), took.as_secs())?;
+        if step.cached {
+            write!(out, " (cached)")?;
+        }
+        writeln!(out)?;
+        self.finished.push(FinishedStep {
+            took,
+            succeeded: true,
+            number: step.number,
+            instruction: step.instruction,
+            cached: step.cached,
+        });
+        Ok(())
+    }
+
+    /// Returns the steps that finished since the previous call.
+    pub fn take_finished_steps(&mut self) -> Vec<FinishedStep> {
+        std::mem::take(&mut self.finished)
+    }
+
+    fn elapsed(&mut self, time: Time) -> Duration {
+        match time {
+            Time::Elapsed(elapsed) => elapsed,
+            Time::Timestamp(timestamp) => {
+                let origin = *self.origin.get_or_insert(timestamp);
+                (timestamp - origin).try_into().unwrap_or_default()
+            }
+        }
+    }
+}
+
+fn format_step(step: &Step) -> String {
+    let instruction = match step.instruction.char_indices().nth(MAX_INSTRUCTION_LEN) {
+        Some((index, _)) => format!("{}...", &step.instruction[..index]),
+        None => step.instruction.clone(),
+    };
+    format!("[{}] {instruction}", step.number)
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    /// Captured from a kaniko build of a small python project, with the output of `pip` shortened.
+    const SAMPLE: &str = r#"Enumerating objects: 1042, done.
+Counting objects: 100% (1042/1042), done.
+INFO[0001] GET KEYCHAIN
+INFO[0001] running on kubernetes ....
+INFO[0002] Using dockerignore file: /kaniko/buildcontext/launch/.dockerignore
+INFO[0002] Retrieving image manifest python:3.11-slim
+INFO[0002] Retrieving image python:3.11-slim from registry index.docker.io
+INFO[0003] Built cross stage deps: map[]
+INFO[0003] Executing 0 build triggers
+INFO[0003] Building stage 'python:3.11-slim' [idx: '0', base-idx: '-1']
+INFO[0003] Checking for cached layer docker-registry.docker-registry.svc.cluster.local/launch/cache:0f3c...
+INFO[0003] Using caching version of cmd: RUN pip install -r requirements.txt
+INFO[0003] Unpacking rootfs as cmd COPY requirements.txt . requires it.
+INFO[0009] WORKDIR /app
+INFO[0009] Cmd: workdir
+INFO[0009] Changed working directory to /app
+INFO[0009] Creating directory /app with uid -1 and gid -1
+INFO[0009] Taking snapshot of files...
+INFO[0009] COPY requirements.txt .
+INFO[0009] Taking snapshot of files...
+INFO[0010] RUN pip install -r requirements.txt
+INFO[0010] Found cached layer, extracting to filesystem
+INFO[0031] COPY . .
+INFO[0032] Taking snapshot of files...
+INFO[0032] RUN pip install --no-deps -e .
+INFO[0032] Cmd: /bin/sh
+INFO[0032] Args: [-c pip install --no-deps -e .]
+INFO[0032] Running: [/bin/sh -c pip install --no-deps -e .]
+Obtaining file:///app
+  Installing build dependencies: started
+  Installing build dependencies: finished with status 'done'
+Successfully installed launch-example-0.1.0
+WARNING: Running pip as the 'root' user can result in broken permissions.
+INFO[0041] Taking snapshot of full filesystem...
+INFO[0047] Pushing image to docker-registry.docker-registry.svc.cluster.local/launch:abc
+INFO[0049] Pushed docker-registry.docker-registry.svc.cluster.local/launch@sha256:1234
+"#;
+
+    fn progress(input: &str) -> (BuildProgress, String) {
+        let mut progress = BuildProgress::new();
+        let mut out = Vec::new();
+        for line in input.lines() {
+            progress.push(line, &mut out).unwrap();
+        }
+        (progress, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn classify_works() {
+        assert_eq!(
+            classify("INFO[0042] RUN pip install ."),
+            Line::Kaniko(Entry {
+                level: Level::Info,
+                time: Time::Elapsed(Duration::from_secs(42)),
+                message: "RUN pip install .",
+            })
+        );
+        assert_eq!(
+            classify("ERRO[0007] error building image: exit status 1   "),
+            Line::Kaniko(Entry {
+                level: Level::Error,
+                time: Time::Elapsed(Duration::from_secs(7)),
+                message: "error building image: exit status 1",
+            })
+        );
+        assert_eq!(
+            classify("INFO[2025-01-13T10:20:30Z] COPY . ."),
+            Line::Kaniko(Entry {
+                level: Level::Info,
+                time: Time::Timestamp(datetime!(2025-01-13 10:20:30 UTC)),
+                message: "COPY . .",
+            })
+        );
+        assert_eq!(
+            classify("WARN[0001] "),
+            Line::Kaniko(Entry {
+                level: Level::Warning,
+                time: Time::Elapsed(Duration::from_secs(1)),
+                message: "",
+            })
+        );
+    }
+
+    #[test]
+    fn classify_tolerates_program_output() {
+        for line in [
+            "",
+            "INFO",
+            "INFO[",
+            "INFO[]",
+            "INFO[12",
+            "INFO[0042]RUN glued",
+            "INFO[yesterday] RUN pip install .",
+            "INFO: Started server process [1]",
+            "info[0042] RUN lower case level",
+            "WARNING: Running pip as the 'root' user",
+            "  Installing build dependencies: started",
+            "ERRO",
+            "é[0001] multi-byte",
+            "INFOé[0001] multi-byte",
+        ] {
+            assert_eq!(classify(line), Line::Output(line), "{line:?}");
+        }
+    }
+
+    #[test]
+    fn event_works() {
+        assert_eq!(
+            event("Building stage 'python:3.11-slim' [idx: '0', base-idx: '-1']"),
+            Some(Event::Stage("python:3.11-slim"))
+        );
+        assert_eq!(
+            event("RUN pip install -r requirements.txt"),
+            Some(Event::Step("RUN pip install -r requirements.txt"))
+        );
+        assert_eq!(event("WORKDIR /app"), Some(Event::Step("WORKDIR /app")));
+        assert_eq!(
+            event("Found cached layer, extracting to filesystem"),
+            Some(Event::CacheHit)
+        );
+        assert_eq!(
+            event("Pushing image to registry/launch:abc"),
+            Some(Event::Push("registry/launch:abc"))
+        );
+        for message in [
+            "Using caching version of cmd: RUN pip install -r requirements.txt",
+            "Unpacking rootfs as cmd COPY requirements.txt . requires it.",
+            "Cmd: /bin/sh",
+            "Running: [/bin/sh -c pip install]",
+            "RUNNING something",
+            "Taking snapshot of files...",
+            "",
+        ] {
+            assert_eq!(event(message), None, "{message:?}");
+        }
+    }
+
+    #[test]
+    fn progress_works() {
+        let (mut progress, out) = progress(SAMPLE);
+        assert_eq!(
+            out,
+            "\
+Building stage python:3.11-slim
+[1] WORKDIR /app 0s
+[2] COPY requirements.txt . 1s
+[3] RUN pip install -r requirements.txt 21s (cached)
+[4] COPY . . 1s
+[5] RUN pip install --no-deps -e . 15s
+Pushing image to docker-registry.docker-registry.svc.cluster.local/launch:abc
+"
+        );
+        let steps = progress.take_finished_steps();
+        assert_eq!(steps.len(), 5);
+        assert_eq!(
+            steps[2],
+            FinishedStep {
+                number: 3,
+                instruction: "RUN pip install -r requirements.txt".to_owned(),
+                took: Duration::from_secs(21),
+                cached: true,
+                succeeded: true,
+            }
+        );
+        assert_eq!(progress.take_finished_steps(), []);
+
+        let mut out = Vec::new();
+        progress.finish(true, &mut out).unwrap();
+        assert_eq!(out, b"");
+        assert_eq!(progress.take_finished_steps(), []);
+    }
+
+    #[test]
+    fn progress_shows_output_of_failed_step() {
+        let (mut progress, out) = progress(
+            "\
+INFO[0003] Building stage 'python:3.11-slim' [idx: '0', base-idx: '-1']
+INFO[0004] RUN pip install -r requirements.txt
+Collecting torch==9.9.9
+ERROR: No matching distribution found for torch==9.9.9
+ERRO[0012] error building image: error building stage: failed to execute command: waiting for process to exit: exit status 1
+",
+        );
+        assert_eq!(
+            out,
+            "\
+Building stage python:3.11-slim
+ERRO[0012] error building image: error building stage: failed to execute command: waiting for process to exit: exit status 1
+"
+        );
+
+        let mut out = Vec::new();
+        progress.finish(false, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\
+[1] RUN pip install -r requirements.txt failed
+  Collecting torch==9.9.9
+  ERROR: No matching distribution found for torch==9.9.9
+"
+        );
+        assert_eq!(
+            progress.take_finished_steps(),
+            [FinishedStep {
+                number: 1,
+                instruction: "RUN pip install -r requirements.txt".to_owned(),
+                took: Duration::from_secs(8),
+                cached: false,
+                succeeded: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn progress_handles_timestamps() {
+        let (mut progress, out) = progress(
+            "\
+INFO[2025-01-13T10:20:30Z] RUN make
+INFO[2025-01-13T10:22:00Z] COPY . .
+",
+        );
+        assert_eq!(out, "[1] RUN make 90s\n");
+
+        let mut out = Vec::new();
+        progress.finish(true, &mut out).unwrap();
+        assert_eq!(out, b"[2] COPY . . 0s\n");
+    }
+
+    #[test]
+    fn format_step_truncates_long_instructions() {
+        let step = Step {
+            number: 1,
+            instruction: format!("RUN {}", "x".repeat(100)),
+            start: Duration::ZERO,
+            cached: false,
+        };
+        assert_eq!(
+            format_step(&step),
+            format!("[1] RUN {}...", "x".repeat(MAX_INSTRUCTION_LEN - 4))
+        );
+    }
+}
@@ -0,0 +1,377 @@
+//! Keeps an eye on the [`KANIKO_CACHE_PVC_NAME`] PersistentVolumeClaim that kaniko builds share for the uv cache. Once
+//! it is full, builds fail with ENOSPC deep inside the output of uv. The usage and the pruning are done by short-lived
+//! Pods that mount the claim, because the kaniko image has no shell to run `df` in after a build.
+
+use ::kubernetes::models as k8s;
+use container_image_name::{image_name, ImageNameRef};
+use log::{debug, info, warn};
+
+use super::{KANIKO_CACHE_PVC_MOUNT_PATH, KANIKO_CACHE_PVC_NAME};
+use crate::{
+    executor::{Deadline, POLLING_INTERVAL},
+    kubectl::{self, Pod, PodPhase},
+    Result,
+};
+
+/// Warn when the cache is fuller than this.
+pub const USAGE_WARNING_PERCENT: u8 = 85;
+
+/// The prefix of the names of the kaniko build Pods.
+const BUILD_POD_PREFIX: &str = "kaniko-";
+
+const IMAGE: ImageNameRef = image_name!("busybox:1.36");
+
+/// How long the prune Pod may take to be scheduled and run.
+const POD_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// How long the usage Pod may take to be scheduled and run. The usage is measured after every build, so a cluster
+/// without room for the Pod must not hold up the submission for long.
+const USAGE_POD_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(20);
+
+/// The usage of the file system of the cache as reported by `df -P -k`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheUsage {
+    pub size_kib: u64,
+    pub used_kib: u64,
+    pub available_kib: u64,
+    /// The capacity column of `df`, which rounds up.
+    pub percent: u8,
+}
+
+fn gib(kib: u64) -> String {
+    format!("{:.1}GiB", kib as f64 / (1024.0 * 1024.0))
+}
+
+/// Parses the output of `df -P -k <mount path>`. Other output may precede it, such as that of the prune command.
+pub fn parse_df(output: &str, mount_path: &str) -> Result<CacheUsage, String> {
+    let line = output
+        .lines()
+        .rev()
+        .find(|line| line.split_whitespace().last() == Some(mount_path))
+        .ok_or_else(|| format!("`df` did not report {mount_path}: {output:?}"))?;
+    let fields = line.split_whitespace().collect::<Vec<_>>();
+    let [_, size, used, available, capacity, _] = fields[..] else {
+        return Err(format!("Unexpected `df` output {line:?}"));
+    };
+    let number = |value: &str| {
+        value
+            .parse::<u64>()
+            .map_err(|_| format!("Unexpected `df` output {line:?}"))
+    };
+    let percent = capacity
+        .strip_suffix('%')
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format!("Unexpected `df` capacity {capacity:?}"))?;
+    Ok(CacheUsage {
+        size_kib: number(size)?,
+        used_kib: number(used)?,
+        available_kib: number(available)?,
+        percent,
+    })
+}
+
+/// Returns a warning with instructions when the cache is almost full.
+pub fn usage_warning(usage: &CacheUsage) -> Option<String> {
+    (usage.percent >= USAGE_WARNING_PERCENT).then(|| {
+        format!(
+            "The kaniko build cache is {}% full ({} of {}, {} available). Builds fail with \"No space left on device\" once it is full. Run `launch clean --build-cache` with the same `--context` to delete cached files that were not modified recently.",
+            usage.percent,
+            gib(usage.used_kib),
+            gib(usage.size_kib),
+            gib(usage.available_kib),
+        )
+    })
+}
+
+fn df_command() -> String {
+    format!("df -P -k {KANIKO_CACHE_PVC_MOUNT_PATH}")
+}
+
+/// The spec of a Pod that mounts the cache and runs `script` with `sh`.
+fn pod_spec(namespace: &str, generate_name: &str, script: String) -> k8s::V1Pod {
+    k8s::V1Pod {
+        api_version: Some("v1".to_owned()),
+        kind: Some("Pod".to_owned()),
+        metadata: Some(Box::new(k8s::V1ObjectMeta {
+            namespace: Some(namespace.to_owned()),
+            generate_name: Some(generate_name.to_owned()),
+            ..Default::default()
+        })),
+        spec: Some(Box::new(k8s::V1PodSpec {
+            restart_policy: Some("Never".to_owned()),
+            containers: vec![k8s::V1Container {
+                name: "main".to_owned(),
+                image: Some(IMAGE.to_string()),
+                command: Some(vec!["sh".to_owned(), "-c".to_owned(), script]),
+                volume_mounts: Some(vec![k8s::V1VolumeMount {
+                    name: KANIKO_CACHE_PVC_NAME.to_owned(),
+                    mount_path: KANIKO_CACHE_PVC_MOUNT_PATH.to_owned(),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }],
+            volumes: Some(vec![k8s::V1Volume {
+                name: KANIKO_CACHE_PVC_NAME.to_owned(),
+                persistent_volume_claim: Some(Box::new(k8s::V1PersistentVolumeClaimVolumeSource {
+                    claim_name: KANIKO_CACHE_PVC_NAME.to_owned(),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+}
+
+/// The spec of a Pod that prints the usage of the cache.
+pub fn usage_pod_spec(namespace: &str) -> k8s::V1Pod {
+    pod_spec(namespace, "uv-cache-usage-", df_command())
+}
+
+/// The spec of a Pod that deletes the files in the cache that were not modified for `older_than`, then the directories
+/// that became empty, and prints the number of deleted files and the usage of the cache.
+pub fn prune_pod_spec(namespace: &str, older_than: time::Duration) -> k8s::V1Pod {
+    let minutes = older_than.whole_minutes().max(1);
+    pod_spec(
+        namespace,
+        "uv-cache-prune-",
+        format!(
+            "set -e; \
+             echo \"deleted $(find {KANIKO_CACHE_PVC_MOUNT_PATH} -mindepth 1 -type f -mmin +{minutes} -print -delete | wc -l) files\"; \
+             find {KANIKO_CACHE_PVC_MOUNT_PATH} -mindepth 1 -type d -empty -delete; \
+             {}",
+            df_command()
+        ),
+    )
+}
+
+/// Returns the names of the kaniko build Pods that have not terminated.
+pub fn active_build_pods(pods: &[Pod]) -> Vec<&str> {
+    pods.iter()
+        .filter(|pod| {
+            pod.metadata.name.starts_with(BUILD_POD_PREFIX)
+                && !matches!(pod.status.phase, PodPhase::Succeeded | PodPhase::Failed)
+        })
+        .map(|pod| pod.metadata.name.as_str())
+        .collect()
+}
+
+/// Runs the Pod to completion within `timeout` and returns its logs. The Pod is deleted afterwards.
+fn run(
+    kubectl: &kubectl::Kubectl,
+    spec: &k8s::V1Pod,
+    timeout: std::time::Duration,
+) -> Result<String> {
+    let pod = kubectl.create(&serde_json::to_string(spec)?)?;
+    let result = (|| {
+        let deadline = Deadline::after(timeout);
+        loop {
+            match kubectl.pod(&pod.namespace, &pod.name)?.status.phase {
+                PodPhase::Succeeded => break,
+                PodPhase::Failed => {
+                    return Err(format!(
+                        "Pod {}/{} failed: {}",
+                        pod.namespace,
+                        pod.name,
+                        kubectl.pod_logs(&pod.namespace, &pod.name)?.trim()
+                    )
+                    .into())
+                }
+                _ => deadline.sleep(POLLING_INTERVAL).map_err(|_| {
+                    format!(
+                        "Pod {}/{} did not finish within {timeout:?}",
+                        pod.namespace, pod.name
+                    )
+                })?,
+            }
+        }
+        kubectl.pod_logs(&pod.namespace, &pod.name)
+    })();
+    if let Err(error) = kubectl.delete_pod(&pod.namespace, &pod.name) {
+        warn!(
+            "Unable to delete Pod {}/{}: {error}",
+            pod.namespace, pod.name
+        );
+    }
+    result
+}
+
+/// Measures the usage of the cache, giving up after [`USAGE_POD_TIMEOUT`].
+pub fn usage(kubectl: &kubectl::Kubectl, namespace: &str) -> Result<CacheUsage> {
+    let output = run(kubectl, &usage_pod_spec(namespace), USAGE_POD_TIMEOUT)?;
+    Ok(parse_df(&output, KANIKO_CACHE_PVC_MOUNT_PATH)?)
+}
+
+/// Warns when the cache is almost full. The usage is informative, so failures are logged instead of returned.
+pub fn warn_if_almost_full(kubectl: &kubectl::Kubectl, namespace: &str) {
+    match usage(kubectl, namespace) {
+        Ok(usage) => match usage_warning(&usage) {
+            Some(warning) => warn!("{warning}"),
+            None => debug!("The kaniko build cache is {}% full", usage.percent),
+        },
+        Err(error) => debug!("Unable to measure the usage of the kaniko build cache: {error}"),
+    }
+}
+
+/// Deletes the files in the cache that were not modified for `older_than`. Refuses while a kaniko build is running,
+/// because it may be using the files.
+pub fn prune(
+    kubectl: &kubectl::Kubectl,
+    namespace: &str,
+    older_than: time::Duration,
+) -> Result<CacheUsage> {
+    let pods = kubectl.pods(namespace)?;
+    let active = active_build_pods(&pods);
+    if !active.is_empty() {
+        return Err(format!(
+            "Refusing to prune the kaniko build cache while kaniko builds are running: {}",
+            active.join(", ")
+        )
+        .into());
+    }
+    let output = run(kubectl, &prune_pod_spec(namespace, older_than), POD_TIMEOUT)?;
+    if let Some(deleted) = output.lines().find(|line| line.starts_with("deleted ")) {
+        info!("Pruned the kaniko build cache: {deleted}");
+    }
+    Ok(parse_df(&output, KANIKO_CACHE_PVC_MOUNT_PATH)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DF: &str = "\
+Filesystem           1024-blocks    Used Available Capacity Mounted on
+/dev/rbd0             51290592  45123456   6150752  89% /var/run/uv
+";
+
+    #[test]
+    fn parse_df_works() {
+        let usage = CacheUsage {
+            size_kib: 51290592,
+            used_kib: 45123456,
+            available_kib: 6150752,
+            percent: 89,
+        };
+        assert_eq!(parse_df(DF, KANIKO_CACHE_PVC_MOUNT_PATH), Ok(usage));
+        assert_eq!(
+            parse_df(
+                &format!("deleted 1234 files\n{DF}"),
+                KANIKO_CACHE_PVC_MOUNT_PATH
+            ),
+            Ok(usage)
+        );
+        assert!(parse_df(DF, "/other").is_err());
+        assert!(parse_df("", KANIKO_CACHE_PVC_MOUNT_PATH).is_err());
+        assert!(parse_df(
+            "/dev/rbd0 51290592 45123456 6150752 89 /var/run/uv",
+            KANIKO_CACHE_PVC_MOUNT_PATH
+        )
+        .is_err());
+        assert!(parse_df(
+            "/dev/rbd0 lots 45123456 6150752 89% /var/run/uv",
+            KANIKO_CACHE_PVC_MOUNT_PATH
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn usage_warning_works() {
+        let usage = |percent| CacheUsage {
+            size_kib: 50 * 1024 * 1024,
+            used_kib: 45 * 1024 * 1024,
+            available_kib: 5 * 1024 * 1024,
+            percent,
+        };
+        assert_eq!(usage_warning(&usage(84)), None);
+        let warning = usage_warning(&usage(85)).unwrap();
+        assert!(
+            warning.starts_with(
+                "The kaniko build cache is 85% full (45.0GiB of 50.0GiB, 5.0GiB available)."
+            ),
+            "{warning}"
+        );
+        assert!(
+            warning.contains("`launch clean --build-cache`"),
+            "{warning}"
+        );
+        assert!(usage_warning(&usage(100)).is_some());
+    }
+
+    #[test]
+    fn pod_specs_mount_the_cache() {
+        for spec in [
+            usage_pod_spec("launch"),
+            prune_pod_spec("launch", time::Duration::days(30)),
+        ] {
+            let spec = serde_json::to_value(spec).unwrap();
+            assert_eq!(spec["metadata"]["namespace"], "launch");
+            assert_eq!(spec["spec"]["restartPolicy"], "Never");
+            assert_eq!(
+                spec["spec"]["volumes"][0]["persistentVolumeClaim"]["claimName"],
+                KANIKO_CACHE_PVC_NAME
+            );
+            let container = &spec["spec"]["containers"][0];
+            assert_eq!(
+                container["volumeMounts"][0]["mountPath"],
+                KANIKO_CACHE_PVC_MOUNT_PATH
+            );
+            assert!(container["command"][2]
+                .as_str()
+                .unwrap()
+                .ends_with("df -P -k /var/run/uv"));
+        }
+    }
+
+    #[test]
+    fn prune_pod_spec_deletes_by_age() {
+        let script = |older_than| {
+            serde_json::to_value(prune_pod_spec("launch", older_than)).unwrap()["spec"]
+                ["containers"][0]["command"][2]
+                .as_str()
+                .unwrap()
+                .to_owned()
+        };
+        let script_30d = script(time::Duration::days(30));
+        assert!(
+            script_30d.contains("find /var/run/uv -mindepth 1 -type f -mmin +43200 -print -delete"),
+            "{script_30d}"
+        );
+        assert!(
+            script_30d.contains("find /var/run/uv -mindepth 1 -type d -empty -delete"),
+            "{script_30d}"
+        );
+        assert!(script(time::Duration::ZERO).contains("-mmin +1 "));
+    }
+
+    fn pod(name: &str, phase: &str) -> Pod {
+        serde_json::from_value(serde_json::json!({
+            "metadata": {
+                "name": name,
+                "namespace": "launch",
+                "creationTimestamp": "2025-01-13T12:00:00Z",
+            },
+            "spec": {},
+            "status": { "phase": phase },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn active_build_pods_works() {
+        let pods = [
+            pod("kaniko-alice-x7k2p", "Running"),
+            pod("kaniko-bob-a1b2c", "Pending"),
+            pod("kaniko-carol-d3e4f", "Succeeded"),
+            pod("kaniko-dave-g5h6i", "Failed"),
+            pod("launch-abc", "Running"),
+            pod("uv-cache-prune-j7k8l", "Running"),
+        ];
+        assert_eq!(
+            active_build_pods(&pods),
+            ["kaniko-alice-x7k2p", "kaniko-bob-a1b2c"]
+        );
+        assert!(active_build_pods(&pods[2..]).is_empty());
+    }
+}
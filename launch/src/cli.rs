@@ -1,12 +1,33 @@
-mod common;
+mod agent;
+mod attach;
+mod clean;
+mod comment;
+mod config;
+mod context;
+mod diff;
+mod get_kubeconfig;
+mod history;
 mod list;
+mod ray_status;
+mod repo_config;
+mod resubmit;
+mod stats;
 mod submit;
+mod summary;
+mod telemetry;
+mod watch;
+mod whoami;
 
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{CommandFactory as _, FromArgMatches as _, Parser, Subcommand, ValueEnum};
 use constcat::concat;
+use container_image_name::image_name;
 use log::{error, warn};
+pub use summary::exit_code;
 
-use crate::{kubectl::Kubectl, Result};
+use crate::{
+    kubectl::{self, Kubectl},
+    Result,
+};
 
 #[derive(Debug, Default, Clone, Copy, ValueEnum, PartialEq, Eq)]
 pub enum ClusterContext {
@@ -19,45 +40,111 @@ pub enum ClusterContext {
 
     /// Refers to https://voltage-park-tailscale-operator.taila1eba.ts.net
     VoltagePark,
+
+    /// Refers to the current context of your kubeconfig, or to the context named by LAUNCH_KUBE_CONTEXT, such as a
+    /// kind or minikube cluster. It has no headlamp, katib UI or registry of launch.
+    Local,
 }
 
+/// Names the context of the kubeconfig that the `local` context uses instead of the current one.
+pub const LOCAL_KUBE_CONTEXT_ENV: &str = "LAUNCH_KUBE_CONTEXT";
+
 impl ClusterContext {
-    pub const fn cluster_url(&self) -> &'static str {
+    /// Returns `None` for [`ClusterContext::Local`], which uses the server of the kubeconfig.
+    pub const fn cluster_url(&self) -> Option<&'static str> {
         match self {
-            ClusterContext::Berkeley => "https://berkeley-tailscale-operator.taila1eba.ts.net",
-            ClusterContext::Staging => "https://staging-tailscale-operator.taila1eba.ts.net",
+            ClusterContext::Berkeley => {
+                Some("https://berkeley-tailscale-operator.taila1eba.ts.net")
+            }
+            ClusterContext::Staging => Some("https://staging-tailscale-operator.taila1eba.ts.net"),
             ClusterContext::VoltagePark => {
-                "https://voltage-park-tailscale-operator.taila1eba.ts.net"
+                Some("https://voltage-park-tailscale-operator.taila1eba.ts.net")
             }
+            ClusterContext::Local => None,
         }
     }
 
-    pub const fn headlamp_url(&self) -> &'static str {
+    pub const fn headlamp_url(&self) -> Option<&'static str> {
         match self {
-            ClusterContext::Berkeley => "https://berkeley-headlamp.taila1eba.ts.net",
-            ClusterContext::Staging => "https://staging-headlamp.taila1eba.ts.net",
-            ClusterContext::VoltagePark => "https://voltage-park-headlamp.taila1eba.ts.net",
+            ClusterContext::Berkeley => Some("https://berkeley-headlamp.taila1eba.ts.net"),
+            ClusterContext::Staging => Some("https://staging-headlamp.taila1eba.ts.net"),
+            ClusterContext::VoltagePark => Some("https://voltage-park-headlamp.taila1eba.ts.net"),
+            ClusterContext::Local => None,
         }
     }
 
-    pub const fn katib_url(&self) -> &'static str {
+    pub const fn katib_url(&self) -> Option<&'static str> {
         match self {
-            ClusterContext::Berkeley => "http://berkeley-katib.taila1eba.ts.net",
-            ClusterContext::Staging => "http://staging-katib.taila1eba.ts.net",
-            ClusterContext::VoltagePark => "http://voltage-park-katib.taila1eba.ts.net",
+            ClusterContext::Berkeley => Some("http://berkeley-katib.taila1eba.ts.net"),
+            ClusterContext::Staging => Some("http://staging-katib.taila1eba.ts.net"),
+            ClusterContext::VoltagePark => Some("http://voltage-park-katib.taila1eba.ts.net"),
+            ClusterContext::Local => None,
         }
     }
 
-    pub const fn container_registry_host(&self) -> &'static str {
+    pub const fn container_registry_host(&self) -> Option<&'static str> {
+        // The registries are written as image names so that `image_name!` checks them at compile time.
         match self {
-            ClusterContext::Berkeley => "berkeley-docker.taila1eba.ts.net",
-            ClusterContext::Staging => "staging-docker.taila1eba.ts.net",
-            ClusterContext::VoltagePark => "voltage-park-docker.taila1eba.ts.net",
+            ClusterContext::Berkeley => {
+                image_name!("berkeley-docker.taila1eba.ts.net/launch").registry_const()
+            }
+            ClusterContext::Staging => {
+                image_name!("staging-docker.taila1eba.ts.net/launch").registry_const()
+            }
+            ClusterContext::VoltagePark => {
+                image_name!("voltage-park-docker.taila1eba.ts.net/launch").registry_const()
+            }
+            ClusterContext::Local => None,
         }
     }
 
+    /// Like [`ClusterContext::container_registry_host`], but fails for a context without a registry.
+    pub fn require_container_registry_host(&self) -> Result<&'static str> {
+        self.container_registry_host().ok_or_else(|| {
+            format!("The {self} context has no container registry, pass `--image` with an image that the cluster can pull instead").into()
+        })
+    }
+
     pub fn kubectl(&self) -> Kubectl {
-        Kubectl::new(self.cluster_url())
+        match self.cluster_url() {
+            Some(cluster_url) => Kubectl::new(cluster_url),
+            None => Kubectl::from_kubeconfig(
+                std::env::var(LOCAL_KUBE_CONTEXT_ENV)
+                    .ok()
+                    .filter(|context| !context.is_empty()),
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for ClusterContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.to_possible_value() {
+            Some(value) => f.write_str(value.get_name()),
+            None => write!(f, "{self:?}"),
+        }
+    }
+}
+
+impl serde::Serialize for ClusterContext {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for ClusterContext {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        <Self as ValueEnum>::from_str(&value, false).map_err(|_| {
+            serde::de::Error::custom(format!(
+                "unknown context {value:?}, expected one of {}",
+                Self::value_variants()
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        })
     }
 }
 
@@ -67,59 +154,197 @@ pub struct Cli {
     #[command(subcommand)]
     command: Commands,
 
-    #[arg(long = "context", global = true, value_enum, default_value_t)]
-    context: ClusterContext,
+    /// The cluster to talk to. `launch submit` accepts a comma-separated list to submit the same work to multiple
+    /// clusters. Defaults to the `context` of `.launch.toml` in the repository, then to the `context` of
+    /// `~/.config/launch/defaults.yaml`. Without either, launch asks on a terminal and uses berkeley otherwise.
+    #[arg(long = "context", global = true, value_enum, value_delimiter = ',')]
+    contexts: Vec<ClusterContext>,
+
+    /// An extra argument for every kubectl invocation, such as `--v=8` to see the API traffic or `--as=USER` to
+    /// impersonate a user. Repeat it for more arguments. `LAUNCH_KUBECTL_ARGS` adds arguments separated by whitespace.
+    /// This is unsupported territory: arguments that change what kubectl prints may break launch. The flags that select
+    /// the cluster, its credentials and the namespace are rejected.
+    #[arg(
+        long = "kubectl-arg",
+        global = true,
+        allow_hyphen_values = true,
+        value_name = "ARG"
+    )]
+    kubectl_args: Vec<String>,
 }
 
 #[derive(Debug, Subcommand)]
 enum Commands {
     /// Submit work to the cluster
     #[command(arg_required_else_help = true)]
-    Submit(submit::SubmitArgs),
+    Submit(Box<submit::SubmitArgs>),
 
     /// List works submitted to the cluster
     List(list::ListArgs),
     /// Follow the logs
     #[command(arg_required_else_help = true)]
     Logs { pod_name: String },
+
+    /// Submit a copy of a Job, RayJob or Experiment with the same image and command
+    #[command(arg_required_else_help = true)]
+    Resubmit(resubmit::ResubmitArgs),
+
+    /// Resume following a Job, RayJob or Experiment like `launch submit` does after creating it
+    #[command(arg_required_else_help = true)]
+    Attach(attach::AttachArgs),
+
+    /// Compare a manifest with a Job, RayJob or Experiment on the cluster
+    #[command(arg_required_else_help = true)]
+    Diff(diff::DiffArgs),
+
+    /// Wait for submitted work to finish and optionally send a notification
+    #[command(arg_required_else_help = true)]
+    Watch(watch::WatchArgs),
+
+    /// Print the scheduling and image pull latencies of recent jobs per GPU product
+    Stats(stats::StatsArgs),
+
+    /// Write a kubeconfig for running kubectl against the cluster and print its path
+    GetKubeconfig(get_kubeconfig::GetKubeconfigArgs),
+
+    /// Print the status, tasks and actors of a RayJob as reported by the Ray dashboard
+    #[command(arg_required_else_help = true)]
+    RayStatus(ray_status::RayStatusArgs),
+
+    /// Delete old images that launch pushed to the registry of the context
+    #[command(arg_required_else_help = true)]
+    Clean(clean::CleanArgs),
+
+    /// Print who launch thinks you are and how it determined that
+    Whoami(whoami::WhoamiArgs),
+
+    /// Summarize or delete the usage counts recorded with `telemetry: local` in `~/.config/launch/defaults.yaml`
+    #[command(arg_required_else_help = true)]
+    Telemetry(telemetry::TelemetryArgs),
+
+    /// Keep running and submit the specs that appear in a directory
+    #[command(arg_required_else_help = true)]
+    Agent(agent::AgentArgs),
+}
+
+impl Commands {
+    /// Whether the command talks to the cluster. Only those commands check for a newer version of launch because the
+    /// check spawns `pixi`, which is not worth it for commands that do not need the network.
+    fn talks_to_cluster(&self) -> bool {
+        match self {
+            Commands::Submit(_)
+            | Commands::List(_)
+            | Commands::Resubmit(_)
+            | Commands::Attach(_)
+            | Commands::Diff(_)
+            | Commands::Watch(_)
+            | Commands::Stats(_)
+            | Commands::RayStatus(_)
+            | Commands::Clean(_)
+            | Commands::Agent(_) => true,
+            Commands::Logs { .. }
+            | Commands::GetKubeconfig(_)
+            | Commands::Whoami(_)
+            | Commands::Telemetry(_) => false,
+        }
+    }
+}
+
+/// Parses the arguments and runs the command, recording its usage when telemetry is enabled.
+pub fn run() -> Result<()> {
+    let command = Cli::command();
+    let matches = command.clone().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|error| error.exit());
+    // Using the telemetry commands is not worth recording, and recording `clear` would undo it.
+    let record = !matches!(cli.command, Commands::Telemetry(_));
+    let start = std::time::Instant::now();
+    let result = cli.run();
+    if record {
+        telemetry::record(&command, &matches, start.elapsed(), &result);
+    }
+    result
 }
 
 impl Cli {
     pub fn run(self) -> Result<()> {
         let latest_version_lock = std::sync::Arc::new(std::sync::Mutex::new(None));
 
-        // Perform the latest version check on SIGINT for commands that don't end quickly, such as
-        // those tailing logs.
-        ctrlc::set_handler({
-            let latest_version_lock = std::sync::Arc::clone(&latest_version_lock);
-            move || latest_version_check(&latest_version_lock)
-        })
-        .expect("Failed to set Ctrl-C handler");
-
-        // Query the latest version on a separate thread so that it does not block execution of the
-        // user's command. This avoids a long wait when the network is not available or slow.
-        std::thread::Builder::new()
-            .name("version_check".to_string())
-            .spawn({
-                let latest_version_lock = std::sync::Arc::clone(&latest_version_lock);
-                move || {
-                    if let Some(latest_version) = query_latest_version() {
-                        latest_version_lock.lock().unwrap().replace(latest_version);
-                    }
-                }
-            })
-            .unwrap();
+        if self.command.talks_to_cluster() {
+            // The agent finishes the submissions in flight, other commands stop right away.
+            let on_signal: fn() = match self.command {
+                Commands::Agent(_) => agent::stop,
+                _ => || std::process::exit(130),
+            };
+            start_latest_version_check(&latest_version_lock, on_signal);
+        }
+
+        let mut kubectl_args: Vec<String> = std::env::var(kubectl::EXTRA_ARGS_ENV)
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(str::to_owned)
+            .collect();
+        kubectl_args.extend(self.kubectl_args);
+        kubectl::set_extra_args(kubectl_args)?;
+
+        // These commands do not depend on the context, so do not ask for one.
+        let command = match self.command {
+            Commands::Whoami(args) => return whoami::whoami(args),
+            Commands::Telemetry(args) => return telemetry::telemetry(args),
+            // Every spec can name its own contexts, and `--context` is the default for those that do not. The agent checks
+            // kubectl itself and the context of each spec.
+            Commands::Agent(args) => return agent::agent(self.contexts, args),
+            command => command,
+        };
+
+        let contexts = resolve_contexts(self.contexts)?;
+        let context = match (&command, contexts.as_slice()) {
+            (Commands::Submit(_), _) | (_, [_]) => contexts[0],
+            _ => return Err("Multiple contexts are only supported by `launch submit`".into()),
+        };
+        if command.talks_to_cluster() {
+            kubectl::client_version()?.check_supported()?;
+            for context in &contexts {
+                context.kubectl().check_kubeconfig_context()?;
+            }
+        }
 
-        match self.command {
+        match command {
             Commands::Submit(args) => {
-                submit::submit(&self.context, args)?;
+                submit::submit(&contexts, *args)?;
             }
             Commands::List(args) => {
-                list::list(&self.context, args)?;
+                list::list(&context, args)?;
             }
             Commands::Logs { .. } => {
                 todo!();
             }
+            Commands::Resubmit(args) => {
+                resubmit::resubmit(&context, args)?;
+            }
+            Commands::Attach(args) => {
+                attach::attach(&context, args)?;
+            }
+            Commands::Diff(args) => {
+                diff::diff(&context, args)?;
+            }
+            Commands::Watch(args) => {
+                watch::watch(&context, args)?;
+            }
+            Commands::Stats(args) => {
+                stats::stats(&context, args)?;
+            }
+            Commands::GetKubeconfig(args) => {
+                get_kubeconfig::get_kubeconfig(&context, args)?;
+            }
+            Commands::RayStatus(args) => {
+                ray_status::ray_status(&context, args)?;
+            }
+            Commands::Clean(args) => {
+                clean::clean(&context, args)?;
+            }
+            Commands::Whoami(_) | Commands::Telemetry(_) | Commands::Agent(_) => {
+                unreachable!("handled before resolving the context")
+            }
         }
 
         latest_version_check(&latest_version_lock);
@@ -128,6 +353,49 @@ impl Cli {
     }
 }
 
+/// Returns the contexts of `--context` without duplicates, or the default context, see [`context::contexts`].
+fn resolve_contexts(flag: Vec<ClusterContext>) -> Result<Vec<ClusterContext>> {
+    let mut contexts = Vec::with_capacity(flag.len());
+    for context in context::contexts(flag)? {
+        if !contexts.contains(&context) {
+            contexts.push(context);
+        }
+    }
+    Ok(contexts)
+}
+
+/// Queries the latest version on a separate thread and prints a warning on SIGINT, SIGTERM or SIGHUP if it is newer
+/// than the current version, before calling `on_signal`.
+fn start_latest_version_check(
+    latest_version_lock: &std::sync::Arc<std::sync::Mutex<Option<semver::Version>>>,
+    on_signal: fn(),
+) {
+    // Perform the latest version check on SIGINT for commands that don't end quickly, such as
+    // those tailing logs.
+    ctrlc::set_handler({
+        let latest_version_lock = std::sync::Arc::clone(latest_version_lock);
+        move || {
+            latest_version_check(&latest_version_lock);
+            on_signal();
+        }
+    })
+    .expect("Failed to set Ctrl-C handler");
+
+    // Query the latest version on a separate thread so that it does not block execution of the
+    // user's command. This avoids a long wait when the network is not available or slow.
+    std::thread::Builder::new()
+        .name("version_check".to_string())
+        .spawn({
+            let latest_version_lock = std::sync::Arc::clone(latest_version_lock);
+            move || {
+                if let Some(latest_version) = query_latest_version() {
+                    latest_version_lock.lock().unwrap().replace(latest_version);
+                }
+            }
+        })
+        .unwrap();
+}
+
 fn query_latest_version() -> Option<semver::Version> {
     let output = std::process::Command::new("pixi")
         .args([
@@ -199,9 +467,97 @@ fn latest_version_check(
     latest_version_lock: &std::sync::Arc<std::sync::Mutex<Option<semver::Version>>>,
 ) {
     if let Some(latest_version) = latest_version_lock.lock().unwrap().take() {
-        let current_version = semver::Version::parse(crate::version::VERSION).unwrap();
-        if latest_version > current_version {
+        if latest_version > *crate::version::SEMVER {
             warn!("A newer version of launch is available, install it with `pixi global install --channel https://repo.prefix.dev/obelisk launch=={latest_version}`");
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn help_is_printed_while_parsing() {
+        // Clap handles `--help` before any command runs, so printing the help does no work such as spawning processes.
+        let error = Cli::try_parse_from(["launch", "--help"]).unwrap_err();
+        assert_eq!(error.kind(), clap::error::ErrorKind::DisplayHelp);
+    }
+
+    #[test]
+    fn talks_to_cluster_works() {
+        let parse = |args: &[&str]| Cli::try_parse_from(args).unwrap().command;
+        assert!(parse(&["launch", "list"]).talks_to_cluster());
+        assert!(parse(&["launch", "watch", "job-abc"]).talks_to_cluster());
+        assert!(parse(&["launch", "attach", "job-abc"]).talks_to_cluster());
+        assert!(parse(&["launch", "ray-status", "ray-job-abc"]).talks_to_cluster());
+        assert!(!parse(&["launch", "logs", "pod-abc"]).talks_to_cluster());
+        assert!(!parse(&["launch", "whoami", "--json"]).talks_to_cluster());
+    }
+
+    #[test]
+    fn kubectl_args_parse() {
+        let kubectl_args = |args: &[&str]| Cli::try_parse_from(args).unwrap().kubectl_args;
+        assert!(kubectl_args(&["launch", "list"]).is_empty());
+        assert_eq!(
+            kubectl_args(&["launch", "--kubectl-arg", "--v=8", "list"]),
+            ["--v=8"]
+        );
+        assert_eq!(
+            kubectl_args(&[
+                "launch",
+                "list",
+                "--kubectl-arg",
+                "--as",
+                "--kubectl-arg=alice"
+            ]),
+            ["--as", "alice"]
+        );
+    }
+
+    #[test]
+    fn context_parses() {
+        let contexts = |args: &[&str]| Cli::try_parse_from(args).unwrap().contexts;
+        assert_eq!(contexts(&["launch", "list"]), []);
+        assert_eq!(
+            contexts(&["launch", "list", "--context", "staging"]),
+            [ClusterContext::Staging]
+        );
+        assert_eq!(
+            contexts(&[
+                "launch",
+                "--context",
+                "berkeley,voltage-park",
+                "submit",
+                "--",
+                "echo"
+            ]),
+            [ClusterContext::Berkeley, ClusterContext::VoltagePark]
+        );
+        assert!(Cli::try_parse_from(["launch", "--context", "berkeley,moon", "list"]).is_err());
+        assert_eq!(ClusterContext::VoltagePark.to_string(), "voltage-park");
+        assert_eq!(
+            contexts(&["launch", "--context", "local", "list"]),
+            [ClusterContext::Local]
+        );
+    }
+
+    #[test]
+    fn local_context_has_no_web_interfaces_or_registry() {
+        let local = ClusterContext::Local;
+        assert_eq!(local.cluster_url(), None);
+        assert_eq!(local.headlamp_url(), None);
+        assert_eq!(local.katib_url(), None);
+        assert_eq!(local.container_registry_host(), None);
+        assert_eq!(
+            local.require_container_registry_host().unwrap_err().to_string(),
+            "The local context has no container registry, pass `--image` with an image that the cluster can pull instead"
+        );
+        assert_eq!(
+            ClusterContext::Berkeley
+                .require_container_registry_host()
+                .unwrap(),
+            "berkeley-docker.taila1eba.ts.net"
+        );
+    }
+}
@@ -0,0 +1,600 @@
+//! `launch agent` submits the specs that tools write into a directory, so that generating a sweep does not pay for
+//! starting launch, querying git and resolving the identity of the user once per job. Each `<name>.launch.yaml` moves
+//! through the subdirectories of the watched directory as a [`Stage`]: it is claimed into `processing/`, and moved
+//! into `done/` or `failed/` along with a `<name>.result.json` once its submission finished.
+
+use std::{
+    fs, io,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use clap::{Args, Parser as _};
+use log::{error, info};
+use time::OffsetDateTime;
+
+use super::{
+    resolve_contexts,
+    submit::{self, environment, SubmitArgs},
+    summary, Cli, ClusterContext, Commands,
+};
+use crate::{kubectl, text_config::read_text_config, ui, Result};
+
+/// The suffix of the files that the agent picks up. Write a spec under another name and rename it when it is complete,
+/// so that the agent does not read it half-written.
+pub const SPEC_SUFFIX: &str = ".launch.yaml";
+
+const RESULT_SUFFIX: &str = ".result.json";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The version of [`SubmitResult`], bumped when a field is renamed or removed, or its meaning changes.
+pub const SCHEMA_VERSION: u32 = 1;
+
+const INTERRUPTED: &str =
+    "The agent stopped while submitting this spec, so the work may or may not have been submitted";
+
+#[derive(Debug, Args)]
+pub struct AgentArgs {
+    /// The directory to watch for `*.launch.yaml` files, which it checks every 2 seconds. Processed files move to its
+    /// `done/` or `failed/` subdirectory along with a `<name>.result.json`. Only run one agent per directory.
+    #[arg(long = "watch-dir", value_name = "DIR")]
+    pub watch_dir: PathBuf,
+
+    /// The number of specs to submit at the same time. Unlike `launch submit`, a submission ends once the work is
+    /// created, without following its logs.
+    #[arg(long = "parallel", value_name = "N", default_value = "1")]
+    pub parallel: NonZeroUsize,
+}
+
+/// The contents of a `*.launch.yaml` file.
+#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SubmitRequest {
+    /// The contexts to submit to, like `--context`. Defaults to the `--context` of the agent, and is then resolved
+    /// like `launch submit` does.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub contexts: Vec<String>,
+    /// The arguments of `launch submit`, such as `["--gpus", "1", "--", "python", "train.py"]`.
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Succeeded,
+    Failed,
+}
+
+/// The contents of a `*.result.json` file.
+#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SubmitResult {
+    pub schema_version: u32,
+    /// The file name of the spec.
+    pub spec: String,
+    pub outcome: Outcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The URLs of the created resources, one per context that the submission succeeded in.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub urls: Vec<String>,
+    /// What `launch submit` would have exited with, which tells a partial failure of multiple contexts apart.
+    pub exit_code: i32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub finished_at: OffsetDateTime,
+}
+
+impl SubmitResult {
+    fn new(
+        spec: String,
+        result: &Result<Vec<String>>,
+        started_at: OffsetDateTime,
+        finished_at: OffsetDateTime,
+    ) -> Self {
+        let (outcome, error, urls, exit_code) = match result {
+            Ok(urls) => (Outcome::Succeeded, None, urls.clone(), 0),
+            Err(error) => (
+                Outcome::Failed,
+                Some(error.to_string()),
+                Vec::new(),
+                summary::exit_code(&**error),
+            ),
+        };
+        Self {
+            schema_version: SCHEMA_VERSION,
+            spec,
+            outcome,
+            error,
+            urls,
+            exit_code,
+            started_at,
+            finished_at,
+        }
+    }
+}
+
+/// Where a spec is in its lifecycle, which the directory it is in records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    /// Written into the watched directory and not claimed yet.
+    Pending,
+    /// Claimed by a worker that is submitting it.
+    Processing,
+    Done,
+    Failed,
+}
+
+impl Stage {
+    fn dir(self, watch_dir: &Path) -> PathBuf {
+        match self {
+            Stage::Pending => watch_dir.to_owned(),
+            Stage::Processing => watch_dir.join("processing"),
+            Stage::Done => watch_dir.join("done"),
+            Stage::Failed => watch_dir.join("failed"),
+        }
+    }
+}
+
+/// Returns the name of the spec without [`SPEC_SUFFIX`], or `None` when the file is not a spec.
+fn spec_stem(path: &Path) -> Option<&str> {
+    path.file_name()?
+        .to_str()?
+        .strip_suffix(SPEC_SUFFIX)
+        .filter(|stem| !stem.is_empty())
+}
+
+/// Returns the specs in the directory of `stage`, sorted by name.
+fn specs(watch_dir: &Path, stage: Stage) -> Result<Vec<PathBuf>> {
+    let dir = stage.dir(watch_dir);
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == io::ErrorKind::NotFound && stage != Stage::Pending => {
+            return Ok(Vec::new())
+        }
+        Err(error) => return Err(format!("Failed to read {}: {error}", dir.display()).into()),
+    };
+    let mut specs = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_file() && spec_stem(&path).is_some() {
+            specs.push(path);
+        }
+    }
+    specs.sort();
+    Ok(specs)
+}
+
+/// Moves the spec into the directory of `stage` and returns its new path.
+fn move_to(watch_dir: &Path, spec: &Path, stage: Stage) -> io::Result<PathBuf> {
+    let dir = stage.dir(watch_dir);
+    fs::create_dir_all(&dir)?;
+    let target = dir.join(spec.file_name().expect("specs have a file name"));
+    fs::rename(spec, &target)?;
+    Ok(target)
+}
+
+/// Claims the first pending spec that no other worker claimed first, and returns its path in `processing/`.
+fn claim_next(watch_dir: &Path) -> Result<Option<PathBuf>> {
+    for spec in specs(watch_dir, Stage::Pending)? {
+        match move_to(watch_dir, &spec, Stage::Processing) {
+            Ok(claimed) => return Ok(Some(claimed)),
+            // Another worker renamed it first.
+            Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+            Err(error) => return Err(format!("Failed to claim {}: {error}", spec.display()).into()),
+        }
+    }
+    Ok(None)
+}
+
+/// Writes the result next to where the claimed spec goes, and then moves the spec there, so that a spec in `done/` or
+/// `failed/` always has its result.
+fn finish(watch_dir: &Path, claimed: &Path, result: &SubmitResult) -> Result<PathBuf> {
+    let stage = match result.outcome {
+        Outcome::Succeeded => Stage::Done,
+        Outcome::Failed => Stage::Failed,
+    };
+    let dir = stage.dir(watch_dir);
+    fs::create_dir_all(&dir)?;
+    let stem = spec_stem(claimed).expect("claimed files are specs");
+    fs::write(
+        dir.join(format!("{stem}{RESULT_SUFFIX}")),
+        serde_json::to_string_pretty(result)? + "\n",
+    )?;
+    Ok(move_to(watch_dir, claimed, stage)?)
+}
+
+/// Fails the specs that a previous agent left in `processing/` when it was killed.
+fn recover(watch_dir: &Path) -> Result<()> {
+    for claimed in specs(watch_dir, Stage::Processing)? {
+        let spec = spec_file_name(&claimed);
+        error!("{spec}: {INTERRUPTED}");
+        let now = OffsetDateTime::now_utc();
+        let result = SubmitResult::new(spec, &Err(INTERRUPTED.into()), now, now);
+        finish(watch_dir, &claimed, &result)?;
+    }
+    Ok(())
+}
+
+fn spec_file_name(spec: &Path) -> String {
+    spec.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn read_request(spec: &Path) -> Result<SubmitRequest> {
+    let contents = read_text_config(spec)
+        .map_err(|error| format!("Failed to read spec {}: {error}", spec.display()))?;
+    serde_yaml::from_str(&contents)
+        .map_err(|error| format!("Invalid spec {}: {error}", spec.display()).into())
+}
+
+/// Submits a claimed spec and finishes it with the result, which is all that is logged about it.
+fn process(
+    watch_dir: &Path,
+    claimed: &Path,
+    submit: &impl Fn(SubmitRequest) -> Result<Vec<String>>,
+) -> Result<()> {
+    let spec = spec_file_name(claimed);
+    let started_at = OffsetDateTime::now_utc();
+    let result = read_request(claimed).and_then(submit);
+    match &result {
+        Ok(urls) => info!("Submitted {spec}: {}", urls.join(", ")),
+        Err(error) => error!("Failed to submit {spec}: {error}"),
+    }
+    let result = SubmitResult::new(spec, &result, started_at, OffsetDateTime::now_utc());
+    finish(watch_dir, claimed, &result)?;
+    Ok(())
+}
+
+/// Claims and submits specs until `stop` is set, finishing the submission in flight.
+fn work(
+    watch_dir: &Path,
+    poll_interval: Duration,
+    stop: &AtomicBool,
+    submit: &impl Fn(SubmitRequest) -> Result<Vec<String>>,
+) -> Result<()> {
+    while !stop.load(Ordering::Relaxed) {
+        match claim_next(watch_dir)? {
+            Some(claimed) => process(watch_dir, &claimed, submit)?,
+            None => std::thread::sleep(poll_interval),
+        }
+    }
+    Ok(())
+}
+
+/// Runs `parallel` workers until `stop` is set. A failure to move the files stops all workers, while a failed
+/// submission only fails its spec.
+fn run(
+    watch_dir: &Path,
+    parallel: NonZeroUsize,
+    poll_interval: Duration,
+    stop: &AtomicBool,
+    submit: &(impl Fn(SubmitRequest) -> Result<Vec<String>> + Sync),
+) -> Result<()> {
+    recover(watch_dir)?;
+    std::thread::scope(|scope| {
+        let workers = (0..parallel.get())
+            .map(|_| {
+                scope.spawn(|| {
+                    let result = work(watch_dir, poll_interval, stop, submit);
+                    if result.is_err() {
+                        stop.store(true, Ordering::Relaxed);
+                    }
+                    result
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .try_for_each(|worker| worker.join().expect("agent worker panicked"))
+    })
+}
+
+/// Parses the request like `launch --context <contexts> submit <args>`.
+fn parse_request(
+    request: SubmitRequest,
+    default_contexts: &[ClusterContext],
+) -> Result<(Vec<ClusterContext>, SubmitArgs)> {
+    let SubmitRequest { contexts, args } = request;
+    let mut argv = vec!["launch".to_owned()];
+    if !contexts.is_empty() {
+        argv.extend(["--context".to_owned(), contexts.join(",")]);
+    }
+    argv.push("submit".to_owned());
+    argv.extend(args);
+    let cli = Cli::try_parse_from(argv)?;
+    if !cli.kubectl_args.is_empty() {
+        return Err("`--kubectl-arg` applies to all submissions of the agent, pass it to `launch agent` instead".into());
+    }
+    let Commands::Submit(args) = cli.command else {
+        unreachable!("parsed as `launch submit`")
+    };
+    let contexts = match cli.contexts.is_empty() {
+        true => default_contexts.to_vec(),
+        false => cli.contexts,
+    };
+    Ok((contexts, *args))
+}
+
+static STOP: AtomicBool = AtomicBool::new(false);
+
+/// Stops the agent once the submissions in flight finish, which launch does on SIGINT, SIGTERM and SIGHUP instead of
+/// terminating. An agent that is killed anyway leaves its specs in `processing/`, which the next agent fails.
+pub(super) fn stop() {
+    STOP.store(true, Ordering::Relaxed);
+}
+
+pub fn agent(default_contexts: Vec<ClusterContext>, args: AgentArgs) -> Result<()> {
+    let AgentArgs {
+        watch_dir,
+        parallel,
+    } = args;
+    if !watch_dir.is_dir() {
+        return Err(format!("{} is not a directory", watch_dir.display()).into());
+    }
+    kubectl::client_version()?.check_supported()?;
+    ui::disable_prompts();
+
+    let sources = environment::Cached::new(environment::System);
+    info!(
+        "Watching {} for *{SPEC_SUFFIX} files and submitting up to {parallel} at a time. Send SIGINT or SIGTERM to stop after the submissions in flight.",
+        watch_dir.display()
+    );
+    run(&watch_dir, parallel, POLL_INTERVAL, &STOP, &|request| {
+        let (contexts, args) = parse_request(request, &default_contexts)?;
+        let contexts = resolve_contexts(contexts)?;
+        for context in &contexts {
+            context.kubectl().check_kubeconfig_context()?;
+        }
+        submit::submit_with(&contexts, args, &sources, false)
+    })?;
+    info!("Stopped watching {}", watch_dir.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use time::macros::datetime;
+
+    use super::*;
+    use crate::temp_path::TempDir;
+
+    fn write_spec(watch_dir: &Path, name: &str, contents: &str) {
+        fs::write(watch_dir.join(format!("{name}{SPEC_SUFFIX}")), contents).unwrap();
+    }
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names = fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+
+    fn read_result(path: PathBuf) -> SubmitResult {
+        serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn spec_stem_works() {
+        assert_eq!(
+            spec_stem(Path::new("/dir/sweep-1.launch.yaml")),
+            Some("sweep-1")
+        );
+        assert_eq!(spec_stem(Path::new("/dir/.launch.yaml")), None);
+        assert_eq!(spec_stem(Path::new("/dir/sweep-1.launch.yaml.tmp")), None);
+        assert_eq!(spec_stem(Path::new("/dir/sweep-1.yaml")), None);
+    }
+
+    #[test]
+    fn spec_moves_through_the_stages() {
+        let dir = TempDir::new();
+        let watch_dir = dir.path();
+        assert_eq!(claim_next(watch_dir).unwrap(), None);
+
+        write_spec(watch_dir, "b", "args: []");
+        write_spec(watch_dir, "a", "args: []");
+        fs::write(watch_dir.join("c.launch.yaml.partial"), "").unwrap();
+
+        // Specs are claimed in order of their names, and only once.
+        let claimed = claim_next(watch_dir).unwrap().unwrap();
+        assert_eq!(claimed, watch_dir.join("processing/a.launch.yaml"));
+        assert_eq!(
+            claim_next(watch_dir).unwrap().unwrap(),
+            watch_dir.join("processing/b.launch.yaml")
+        );
+        assert_eq!(claim_next(watch_dir).unwrap(), None);
+        assert_eq!(
+            file_names(watch_dir),
+            ["c.launch.yaml.partial", "processing"]
+        );
+
+        let at = datetime!(2025-03-01 12:00 UTC);
+        let done = finish(
+            watch_dir,
+            &claimed,
+            &SubmitResult::new("a.launch.yaml".to_owned(), &Ok(Vec::new()), at, at),
+        )
+        .unwrap();
+        assert_eq!(done, watch_dir.join("done/a.launch.yaml"));
+        assert_eq!(
+            file_names(&watch_dir.join("done")),
+            ["a.launch.yaml", "a.result.json"]
+        );
+        assert_eq!(file_names(&watch_dir.join("processing")), ["b.launch.yaml"]);
+    }
+
+    #[test]
+    fn submit_result_serializes() {
+        let result = SubmitResult::new(
+            "sweep-1.launch.yaml".to_owned(),
+            &Err("cluster is down".into()),
+            datetime!(2025-03-01 12:00 UTC),
+            datetime!(2025-03-01 12:05 UTC),
+        );
+        let json = serde_json::to_string(&result).unwrap();
+        assert_eq!(
+            json,
+            r#"{"schema_version":1,"spec":"sweep-1.launch.yaml","outcome":"failed","error":"cluster is down","exit_code":1,"started_at":"2025-03-01T12:00:00Z","finished_at":"2025-03-01T12:05:00Z"}"#
+        );
+        assert_eq!(serde_json::from_str::<SubmitResult>(&json).unwrap(), result);
+
+        let result = SubmitResult::new(
+            "sweep-2.launch.yaml".to_owned(),
+            &Ok(vec!["https://headlamp/job-x7k2p".to_owned()]),
+            datetime!(2025-03-01 12:00 UTC),
+            datetime!(2025-03-01 12:05 UTC),
+        );
+        assert_eq!(
+            serde_json::to_string(&result).unwrap(),
+            r#"{"schema_version":1,"spec":"sweep-2.launch.yaml","outcome":"succeeded","urls":["https://headlamp/job-x7k2p"],"exit_code":0,"started_at":"2025-03-01T12:00:00Z","finished_at":"2025-03-01T12:05:00Z"}"#
+        );
+    }
+
+    #[test]
+    fn run_submits_every_spec_and_finishes_the_one_in_flight() {
+        let dir = TempDir::new();
+        let watch_dir = dir.path();
+        write_spec(watch_dir, "a", "args: [--gpus: 1]: oops");
+        write_spec(watch_dir, "b", "args: [--, echo, ok]");
+        write_spec(
+            watch_dir,
+            "c",
+            "contexts: [staging]\nargs: [--, echo, fail]",
+        );
+
+        let stop = AtomicBool::new(false);
+        let submitted = AtomicUsize::new(0);
+        run(
+            watch_dir,
+            NonZeroUsize::new(2).unwrap(),
+            Duration::from_millis(10),
+            &stop,
+            &|request: SubmitRequest| {
+                // Stopping does not abort the submission in flight.
+                if submitted.fetch_add(1, Ordering::Relaxed) == 1 {
+                    stop.store(true, Ordering::Relaxed);
+                }
+                match request.args.last().map(String::as_str) {
+                    Some("fail") => Err("cluster is down".into()),
+                    _ => Ok(vec!["https://headlamp/job-x7k2p".to_owned()]),
+                }
+            },
+        )
+        .unwrap();
+
+        assert_eq!(submitted.load(Ordering::Relaxed), 2);
+        assert_eq!(file_names(&watch_dir.join("processing")), [] as [&str; 0]);
+        assert_eq!(
+            file_names(&watch_dir.join("done")),
+            ["b.launch.yaml", "b.result.json"]
+        );
+        assert_eq!(
+            file_names(&watch_dir.join("failed")),
+            [
+                "a.launch.yaml",
+                "a.result.json",
+                "c.launch.yaml",
+                "c.result.json"
+            ]
+        );
+        let result = read_result(watch_dir.join("failed/a.result.json"));
+        assert!(
+            result.error.as_deref().unwrap().starts_with("Invalid spec"),
+            "{result:?}"
+        );
+        let result = read_result(watch_dir.join("failed/c.result.json"));
+        assert_eq!(result.error.as_deref(), Some("cluster is down"));
+        let result = read_result(watch_dir.join("done/b.result.json"));
+        assert_eq!(result.exit_code, 0);
+        assert_eq!(result.urls, ["https://headlamp/job-x7k2p"]);
+    }
+
+    #[test]
+    fn run_fails_specs_left_in_processing() {
+        let dir = TempDir::new();
+        let watch_dir = dir.path();
+        fs::create_dir_all(watch_dir.join("processing")).unwrap();
+        write_spec(&watch_dir.join("processing"), "a", "args: []");
+
+        let stop = AtomicBool::new(true);
+        run(
+            watch_dir,
+            NonZeroUsize::MIN,
+            Duration::ZERO,
+            &stop,
+            &|_| unreachable!(),
+        )
+        .unwrap();
+
+        let result = read_result(watch_dir.join("failed/a.result.json"));
+        assert_eq!(result.outcome, Outcome::Failed);
+        assert_eq!(result.error.as_deref(), Some(INTERRUPTED));
+        assert!(watch_dir.join("failed/a.launch.yaml").is_file());
+    }
+
+    #[test]
+    fn parse_request_works() {
+        let request = |contexts: &[&str], args: &[&str]| SubmitRequest {
+            contexts: contexts.iter().map(|&context| context.to_owned()).collect(),
+            args: args.iter().map(|&arg| arg.to_owned()).collect(),
+        };
+
+        let (contexts, args) = parse_request(
+            request(
+                &["staging", "berkeley"],
+                &["--gpus", "2", "--", "python", "train.py"],
+            ),
+            &[ClusterContext::VoltagePark],
+        )
+        .unwrap();
+        assert_eq!(
+            contexts,
+            [ClusterContext::Staging, ClusterContext::Berkeley]
+        );
+        assert_eq!(args.command, ["python", "train.py"]);
+
+        let (contexts, _) = parse_request(
+            request(&[], &["--", "echo"]),
+            &[ClusterContext::VoltagePark],
+        )
+        .unwrap();
+        assert_eq!(contexts, [ClusterContext::VoltagePark]);
+
+        let (contexts, _) = parse_request(request(&[], &["--", "echo"]), &[]).unwrap();
+        assert_eq!(contexts, []);
+
+        assert!(parse_request(request(&["moon"], &["--", "echo"]), &[]).is_err());
+        assert!(parse_request(request(&[], &["--no-such-flag"]), &[]).is_err());
+        assert!(parse_request(request(&[], &["--kubectl-arg=--v=8", "--", "echo"]), &[]).is_err());
+    }
+
+    #[test]
+    fn submit_request_deserializes() {
+        let request: SubmitRequest = serde_yaml::from_str(
+            "contexts: [staging]\nargs: [--gpus, '1', --, python, train.py]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            request,
+            SubmitRequest {
+                contexts: vec!["staging".to_owned()],
+                args: ["--gpus", "1", "--", "python", "train.py"]
+                    .map(str::to_owned)
+                    .to_vec(),
+            }
+        );
+        assert!(serde_yaml::from_str::<SubmitRequest>("args: []\nimage: x\n").is_err());
+    }
+}
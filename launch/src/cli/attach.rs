@@ -0,0 +1,98 @@
+//! Resumes the monitoring that `launch submit` does after it created a Job, RayJob or Experiment, for example after
+//! launch was interrupted.
+
+use clap::Args;
+use log::info;
+
+use super::{
+    resubmit::{self, Kind},
+    watch, ClusterContext,
+};
+use crate::{
+    executor,
+    kubectl::{self, ResourceHandle},
+    Result,
+};
+
+#[derive(Debug, Args)]
+pub struct AttachArgs {
+    /// The name of the Job, RayJob or Experiment to attach to.
+    pub name: String,
+
+    /// Post a JSON notification to this webhook URL, such as a Slack incoming webhook, once the resource has finished.
+    #[arg(long = "notify")]
+    pub notify: Option<String>,
+
+    /// With `json`, the monitoring of a katib experiment prints its events to stdout as one JSON object per line, next
+    /// to the logs on stderr.
+    #[arg(long = "progress", value_enum, default_value_t)]
+    pub progress: executor::Progress,
+
+    /// Collapse the identical lines that the Ray workers print within half a second of each other into one line that
+    /// says how many workers printed it, like `launch submit --dedupe-logs`. Only applies to RayJobs.
+    #[arg(long = "dedupe-logs")]
+    pub dedupe_logs: bool,
+}
+
+pub fn attach(context: &ClusterContext, args: AttachArgs) -> Result<()> {
+    let AttachArgs {
+        name,
+        notify,
+        progress,
+        dedupe_logs,
+    } = args;
+
+    let kubectl = context.kubectl();
+    let namespace = kubectl::NAMESPACE;
+
+    let (kind, _) = resubmit::find(context, &kubectl, namespace, &name)?;
+    if progress == executor::Progress::Json && kind != Kind::Experiment {
+        return Err(format!(
+            "`--progress json` only reports Experiments, and {name:?} is a {}",
+            kind.as_str()
+        )
+        .into());
+    }
+    let resource_kind = kind.resource_kind();
+    info!(
+        "Attaching to {} {:?}",
+        kind.as_str(),
+        resource_kind.url(context, namespace, &name)
+    );
+
+    let handle = ResourceHandle {
+        namespace: namespace.to_owned(),
+        name: name.clone(),
+    };
+    match kind {
+        // The latencies were recorded when the Job was submitted, and are not those of attaching.
+        Kind::Job => executor::monitor_job(context, &kubectl, &handle, false)?,
+        Kind::RayJob => executor::monitor_ray_job(context, &kubectl, &handle, dedupe_logs)?,
+        Kind::Experiment => executor::monitor_experiment(
+            context,
+            &kubectl,
+            &handle,
+            &mut executor::Reporter::new(progress),
+        )?,
+    }
+
+    // The logs end when the Pod exits, so wait for the Job to report its terminal status like `launch watch` does.
+    // Monitoring an Experiment already reports it, and a RayJob whose submitter Job is gone reported what it could
+    // recover.
+    let report = match kind {
+        Kind::Job => true,
+        Kind::RayJob => kubectl.try_get_job(namespace, &name)?.is_some(),
+        Kind::Experiment => notify.is_some(),
+    };
+    if report {
+        watch::watch_resource(
+            context,
+            &kubectl,
+            resource_kind,
+            namespace,
+            &name,
+            notify.as_deref(),
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,586 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::{self, IsTerminal as _},
+};
+
+use clap::{ArgGroup, Args};
+use container_image_name::ImageNameRef;
+use log::{info, warn};
+use time::OffsetDateTime;
+
+use super::{
+    config,
+    list::MaxAge,
+    summary::{OutputArg, Summary},
+    ClusterContext,
+};
+use crate::{
+    builder::cache,
+    git::is_full_git_commit_hash,
+    kubectl::{is_rfc_1035_label, NAMESPACE},
+    registry, ui, Result,
+};
+
+#[derive(Debug, Args)]
+#[command(group(ArgGroup::new("what").required(true).multiple(true).args(["images", "build_cache"])))]
+pub struct CleanArgs {
+    /// Delete the images that launch pushed to the registry of the context and that no Job, RayJob or CronJob uses
+    /// anymore.
+    #[arg(long = "images")]
+    pub images: bool,
+
+    /// Delete the files in the uv cache of kaniko builds that were not modified for `--older-than`. Refuses while a
+    /// kaniko build is running.
+    #[arg(long = "build-cache")]
+    pub build_cache: bool,
+
+    /// Only delete images that were created, or cached files that were modified, longer ago than this, such as `30d`
+    /// or `12h`.
+    #[arg(long = "older-than", default_value = "30d")]
+    pub older_than: MaxAge,
+
+    /// Delete without asking for confirmation.
+    #[arg(long = "yes", short = 'y', default_value_t)]
+    pub yes: bool,
+
+    /// How to print the summary of what was deleted. Exits with 3 when only some deletions failed.
+    #[arg(long = "output", value_enum, default_value_t)]
+    pub output: OutputArg,
+}
+
+pub fn clean(context: &ClusterContext, args: CleanArgs) -> Result<()> {
+    let CleanArgs {
+        images,
+        build_cache,
+        older_than,
+        yes,
+        output,
+    } = args;
+    // Only the summary goes to stdout, so that it is all that scripts read with `--output json`. Everything else goes to
+    // stderr along with the confirmation prompts.
+    let mut messages = io::stderr();
+    let mut summary = Summary::new("clean", "targets");
+    if images {
+        clean_images(context, older_than, yes, &mut summary, &mut messages)?;
+    }
+    if build_cache {
+        clean_build_cache(context, older_than, yes, &mut summary, &mut messages)?;
+    }
+    summary.print(output)?;
+    summary.into_result()
+}
+
+fn clean_build_cache(
+    context: &ClusterContext,
+    older_than: MaxAge,
+    yes: bool,
+    summary: &mut Summary,
+    messages: &mut dyn io::Write,
+) -> Result<()> {
+    if !yes
+        && !confirm(&format!(
+            "Delete the files in the kaniko build cache of context {context} that were not modified in the last {older_than}?"
+        ))?
+    {
+        writeln!(messages, "No cached files were deleted.")?;
+        return Ok(());
+    }
+    let result = cache::prune(&context.kubectl(), NAMESPACE, older_than.duration());
+    summary.record("kaniko build cache", "prune", &result, |usage| {
+        Some(format!("{}% full", usage.percent))
+    });
+    if let Ok(usage) = result {
+        if let Some(warning) = cache::usage_warning(&usage) {
+            warn!("Still almost full after pruning, consider a shorter `--older-than`. {warning}");
+        }
+    }
+    Ok(())
+}
+
+fn clean_images(
+    context: &ClusterContext,
+    older_than: MaxAge,
+    yes: bool,
+    summary: &mut Summary,
+    messages: &mut dyn io::Write,
+) -> Result<()> {
+    let registry_host = context
+        .container_registry_host()
+        .ok_or_else(|| format!("The {context} context has no registry of launch to clean"))?;
+    let kubectl = context.kubectl();
+    let references = References::collect(&kubectl.list_all_namespaces("jobs,rayjobs,cronjobs")?);
+
+    let http = config::http()?;
+    let client = registry::Client::for_registry(&http, registry_host);
+    let cutoff = cutoff(OffsetDateTime::now_utc(), older_than)?;
+    info!("Looking for images in {registry_host} that were created more than {older_than} ago...");
+    let deletions = find_deletions(&client, cutoff, &references)?;
+    delete_images(
+        registry_host,
+        &deletions,
+        |count| {
+            Ok(yes
+                || confirm(&format!(
+                    "Delete these {count} images from the registry of context {context}?"
+                ))?)
+        },
+        |deletion| client.delete_manifest(&deletion.repository, &deletion.digest),
+        summary,
+        messages,
+    )
+}
+
+/// Lists the deletions on `messages`, and deletes them with `delete` once `confirm` agrees.
+fn delete_images(
+    registry_host: &str,
+    deletions: &[Deletion],
+    confirm: impl FnOnce(usize) -> Result<bool>,
+    mut delete: impl FnMut(&Deletion) -> Result<()>,
+    summary: &mut Summary,
+    messages: &mut dyn io::Write,
+) -> Result<()> {
+    if deletions.is_empty() {
+        writeln!(messages, "No images to delete.")?;
+        return Ok(());
+    }
+    for deletion in deletions {
+        writeln!(
+            messages,
+            "{registry_host}/{}@{} ({})",
+            deletion.repository,
+            deletion.digest,
+            deletion.tags.join(", ")
+        )?;
+    }
+    if !confirm(deletions.len())? {
+        writeln!(messages, "No images were deleted.")?;
+        return Ok(());
+    }
+
+    for deletion in deletions {
+        let result = delete(deletion);
+        if let Err(error) = &result {
+            warn!("{error}");
+        }
+        summary.record(
+            format!(
+                "{registry_host}/{}@{}",
+                deletion.repository, deletion.digest
+            ),
+            "delete manifest",
+            &result,
+            |()| None,
+        );
+    }
+    info!("The registry reclaims the storage of deleted manifests on its next garbage collection.");
+    Ok(())
+}
+
+fn confirm(question: &str) -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        return Err(
+            "Refusing to delete without confirmation because stdin is not a terminal. Pass `--yes` to delete anyway.".into(),
+        );
+    }
+    ui::confirm(question)
+}
+
+/// Whether launch generated the tag. The tag is the commit hash when the working tree is clean or kaniko builds the
+/// image, and `<user>-<random hex>` otherwise.
+fn is_launch_tag(tag: &str) -> bool {
+    if is_full_git_commit_hash(tag) {
+        return true;
+    }
+    let Some((user, random)) = tag.rsplit_once('-') else {
+        return false;
+    };
+    is_rfc_1035_label(user)
+        && (5..=8).contains(&random.len())
+        && random
+            .bytes()
+            .all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// The images that are in use, by digest and by repository and tag.
+#[derive(Debug, Default)]
+struct References {
+    digests: HashSet<String>,
+    tags: HashSet<(String, String)>,
+}
+
+impl References {
+    /// Collects the values of all `image` fields, which covers the containers of Jobs and the Job templates of
+    /// CronJobs as well as those of the head, workers and submitter of RayJobs.
+    fn collect(resources: &serde_json::Value) -> Self {
+        let mut references = Self::default();
+        references.collect_from(resources);
+        references
+    }
+
+    fn collect_from(&mut self, value: &serde_json::Value) {
+        match value {
+            serde_json::Value::Object(object) => {
+                for (key, value) in object {
+                    if let ("image", Some(Ok(image))) =
+                        (key.as_str(), value.as_str().map(ImageNameRef::new))
+                    {
+                        match (image.digest(), image.tag()) {
+                            (Some(digest), _) => {
+                                self.digests.insert(digest.to_owned());
+                            }
+                            (None, Some(tag)) => {
+                                self.tags.insert((image.path().to_owned(), tag.to_owned()));
+                            }
+                            (None, None) => {}
+                        }
+                    }
+                    self.collect_from(value);
+                }
+            }
+            serde_json::Value::Array(values) => {
+                for value in values {
+                    self.collect_from(value);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Tag {
+    name: String,
+    digest: String,
+    /// Only looked up for the tags that may be deleted.
+    created: Option<OffsetDateTime>,
+}
+
+/// A manifest to delete along with the tags that refer to it.
+#[derive(Debug, PartialEq, Eq)]
+struct Deletion {
+    repository: String,
+    digest: String,
+    tags: Vec<String>,
+}
+
+/// The time before which images count as older than `older_than`.
+fn cutoff(now: OffsetDateTime, older_than: MaxAge) -> Result<OffsetDateTime> {
+    now.checked_sub(older_than.duration()).ok_or_else(|| {
+        format!("`--older-than {older_than}` reaches back further than dates go").into()
+    })
+}
+
+/// Deleting a manifest deletes all of its tags, so a manifest is only deleted when all of its tags were generated by
+/// launch, were created before the cutoff and are not in use.
+fn plan(
+    repository: &str,
+    tags: Vec<Tag>,
+    cutoff: OffsetDateTime,
+    references: &References,
+) -> Vec<Deletion> {
+    let mut by_digest = BTreeMap::<String, Vec<Tag>>::new();
+    for tag in tags {
+        by_digest.entry(tag.digest.clone()).or_default().push(tag);
+    }
+    by_digest
+        .into_iter()
+        .filter(|(digest, tags)| {
+            !references.digests.contains(digest)
+                && tags.iter().all(|tag| {
+                    is_launch_tag(&tag.name)
+                        && tag.created.is_some_and(|created| created < cutoff)
+                        && !references
+                            .tags
+                            .contains(&(repository.to_owned(), tag.name.clone()))
+                })
+        })
+        .map(|(digest, tags)| Deletion {
+            repository: repository.to_owned(),
+            digest,
+            tags: tags.into_iter().map(|tag| tag.name).collect(),
+        })
+        .collect()
+}
+
+fn find_deletions(
+    client: &registry::Client,
+    cutoff: OffsetDateTime,
+    references: &References,
+) -> Result<Vec<Deletion>> {
+    let mut deletions = Vec::new();
+    for repository in client.repositories()? {
+        let mut tags = Vec::new();
+        for name in client.list_tags(&repository)? {
+            // The tag may have been deleted since it was listed.
+            let Some(digest) = client.manifest_digest(&repository, &name)? else {
+                continue;
+            };
+            let created = if is_launch_tag(&name) && !references.digests.contains(&digest) {
+                client.image_created(&repository, &digest)?
+            } else {
+                None
+            };
+            tags.push(Tag {
+                name,
+                digest,
+                created,
+            });
+        }
+        deletions.extend(plan(&repository, tags, cutoff, references));
+    }
+    Ok(deletions)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser as _;
+    use time::macros::datetime;
+
+    use super::*;
+    use crate::{
+        cli::{Cli, Commands},
+        registry::mock::{self, Response},
+    };
+
+    #[test]
+    fn clean_args_require_something_to_clean() {
+        let parse = |args: &[&str]| -> Result<CleanArgs, clap::Error> {
+            let cli = Cli::try_parse_from(["launch", "clean"].iter().chain(args))?;
+            match cli.command {
+                Commands::Clean(args) => Ok(args),
+                command => panic!("{command:?}"),
+            }
+        };
+        assert!(parse(&[]).is_err());
+        let args = parse(&["--images"]).unwrap();
+        assert!(args.images && !args.build_cache);
+        let args = parse(&["--build-cache", "--older-than", "7d"]).unwrap();
+        assert!(!args.images && args.build_cache);
+        assert_eq!(args.older_than, "7d".parse().unwrap());
+        assert!(parse(&["--images", "--build-cache"]).is_ok());
+    }
+
+    #[test]
+    fn is_launch_tag_works() {
+        assert!(is_launch_tag("c0ffee0000000000000000000000000000000000"));
+        assert!(is_launch_tag("alice-8a3f9b22"));
+        assert!(is_launch_tag("unknown-user-a3f9b"));
+        assert!(!is_launch_tag("latest"));
+        assert!(!is_launch_tag("v1.2.3"));
+        assert!(!is_launch_tag("release-1"));
+        assert!(!is_launch_tag("alice-8A3F9B22"));
+        assert!(!is_launch_tag("alice-8a3f9b22c"));
+        assert!(!is_launch_tag("-8a3f9b22"));
+    }
+
+    #[test]
+    fn references_collect_images() {
+        let references = References::collect(&serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "List",
+            "items": [
+                {
+                    "kind": "Job",
+                    "spec": { "template": { "spec": { "containers": [{
+                        "name": "main",
+                        "image": "berkeley-docker.taila1eba.ts.net/launch@sha256:1111111111111111111111111111111111111111111111111111111111111111",
+                    }] } } }
+                },
+                {
+                    "kind": "RayJob",
+                    "spec": {
+                        "rayClusterSpec": {
+                            "headGroupSpec": { "template": { "spec": { "containers": [{
+                                "name": "ray-head",
+                                "image": "berkeley-docker.taila1eba.ts.net/launch:alice-8a3f9b22@sha256:2222222222222222222222222222222222222222222222222222222222222222",
+                            }] } } },
+                            "workerGroupSpecs": [{ "template": { "spec": { "containers": [{
+                                "name": "ray-worker",
+                                "image": "berkeley-docker.taila1eba.ts.net/obelisk:bob-1c2d3e4f",
+                            }] } } }]
+                        }
+                    }
+                },
+                {
+                    "kind": "CronJob",
+                    "spec": {
+                        "schedule": "0 3 * * *",
+                        "jobTemplate": { "spec": { "template": { "spec": { "containers": [{
+                            "name": "main",
+                            "image": "berkeley-docker.taila1eba.ts.net/launch@sha256:3333333333333333333333333333333333333333333333333333333333333333",
+                        }] } } } }
+                    }
+                }
+            ]
+        }));
+        assert_eq!(
+            references.digests,
+            HashSet::from([
+                "sha256:1111111111111111111111111111111111111111111111111111111111111111"
+                    .to_owned(),
+                "sha256:2222222222222222222222222222222222222222222222222222222222222222"
+                    .to_owned(),
+                "sha256:3333333333333333333333333333333333333333333333333333333333333333"
+                    .to_owned()
+            ])
+        );
+        assert_eq!(
+            references.tags,
+            HashSet::from([("obelisk".to_owned(), "bob-1c2d3e4f".to_owned())])
+        );
+    }
+
+    fn tag(name: &str, digest: &str, created: Option<OffsetDateTime>) -> Tag {
+        Tag {
+            name: name.to_owned(),
+            digest: digest.to_owned(),
+            created,
+        }
+    }
+
+    #[test]
+    fn cutoff_rejects_ages_before_the_earliest_date() {
+        let now = datetime!(2025-02-08 0:00 UTC);
+        assert_eq!(
+            cutoff(now, "7d".parse().unwrap()).unwrap(),
+            datetime!(2025-02-01 0:00 UTC)
+        );
+        assert!(cutoff(now, "999999999999d".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn plan_keeps_manifests_with_other_tags() {
+        let old = Some(datetime!(2025-01-01 0:00 UTC));
+        let cutoff = datetime!(2025-02-01 0:00 UTC);
+        let references = References {
+            digests: HashSet::new(),
+            tags: HashSet::from([("launch".to_owned(), "bob-1c2d3e4f".to_owned())]),
+        };
+        let deletions = plan(
+            "launch",
+            vec![
+                tag("alice-8a3f9b22", "sha256:a", old),
+                tag("alice-9b22c3d4", "sha256:a", old),
+                tag("carol-5e6f7a8b", "sha256:shared", old),
+                tag("latest", "sha256:shared", None),
+                tag("bob-1c2d3e4f", "sha256:tagged", old),
+                tag("dave-0d1e2f3a", "sha256:unknown", None),
+            ],
+            cutoff,
+            &references,
+        );
+        assert_eq!(
+            deletions,
+            [Deletion {
+                repository: "launch".to_owned(),
+                digest: "sha256:a".to_owned(),
+                tags: vec!["alice-8a3f9b22".to_owned(), "alice-9b22c3d4".to_owned()],
+            }]
+        );
+    }
+
+    #[test]
+    fn find_deletions_skips_referenced_digests() {
+        let (url, requests) = mock::serve(|method, path| {
+            let created = |digest: &str| match digest {
+                "sha256:recent" => "2025-03-01T00:00:00Z",
+                _ => "2025-01-01T00:00:00Z",
+            };
+            match (method, path) {
+                ("GET", "/v2/_catalog?n=100") => {
+                    Response::json(serde_json::json!({ "repositories": ["launch"] }))
+                }
+                ("GET", "/v2/launch/tags/list?n=100") => Response::json(serde_json::json!({
+                    "name": "launch",
+                    "tags": ["alice-8a3f9b22", "bob-1c2d3e4f"],
+                }))
+                .header(
+                    "Link",
+                    r#"</v2/launch/tags/list?last=bob-1c2d3e4f&n=100>; rel="next""#,
+                ),
+                ("GET", "/v2/launch/tags/list?last=bob-1c2d3e4f&n=100") => {
+                    Response::json(serde_json::json!({
+                        "name": "launch",
+                        "tags": ["c0ffee0000000000000000000000000000000000"],
+                    }))
+                }
+                ("HEAD", path) => {
+                    let digest = match path.rsplit('/').next().unwrap() {
+                        "alice-8a3f9b22" => "sha256:old",
+                        "bob-1c2d3e4f" => "sha256:used",
+                        _ => "sha256:recent",
+                    };
+                    Response::status(200).header("Docker-Content-Digest", digest)
+                }
+                ("GET", path) if path.starts_with("/v2/launch/manifests/") => {
+                    let digest = path.rsplit('/').next().unwrap();
+                    Response::json(serde_json::json!({
+                        "config": { "digest": format!("config-{digest}") },
+                    }))
+                }
+                ("GET", path) if path.starts_with("/v2/launch/blobs/config-") => {
+                    let digest = path.strip_prefix("/v2/launch/blobs/config-").unwrap();
+                    Response::json(serde_json::json!({ "created": created(digest) }))
+                }
+                _ => Response::status(404),
+            }
+        });
+        let http = crate::http::Http::new(&Default::default(), Default::default()).unwrap();
+        let client = registry::Client::new(&http, url);
+        let references = References {
+            digests: HashSet::from(["sha256:used".to_owned()]),
+            tags: HashSet::new(),
+        };
+
+        let deletions =
+            find_deletions(&client, datetime!(2025-02-01 0:00 UTC), &references).unwrap();
+        assert_eq!(
+            deletions,
+            [Deletion {
+                repository: "launch".to_owned(),
+                digest: "sha256:old".to_owned(),
+                tags: vec!["alice-8a3f9b22".to_owned()],
+            }]
+        );
+        // The creation time of an image in use is not needed.
+        assert!(!requests
+            .lock()
+            .unwrap()
+            .contains(&"GET /v2/launch/manifests/sha256:used".to_owned()));
+    }
+
+    #[test]
+    fn json_output_is_the_only_thing_on_stdout() {
+        let deletion = || Deletion {
+            repository: "launch".to_owned(),
+            digest: "sha256:old".to_owned(),
+            tags: vec!["alice-8a3f9b22".to_owned()],
+        };
+        for (deletions, confirmed, message) in [
+            (vec![], true, "No images to delete."),
+            (vec![deletion()], false, "No images were deleted."),
+            (vec![deletion()], true, "launch@sha256:old (alice-8a3f9b22)"),
+        ] {
+            let mut summary = Summary::new("clean", "targets");
+            let mut messages = Vec::new();
+            delete_images(
+                "berkeley-docker.taila1eba.ts.net",
+                &deletions,
+                |_| Ok(confirmed),
+                |_| Ok(()),
+                &mut summary,
+                &mut messages,
+            )
+            .unwrap();
+            let messages = String::from_utf8(messages).unwrap();
+            assert!(messages.contains(message), "{messages}");
+
+            let mut stdout = Vec::new();
+            summary.write(OutputArg::Json, &mut stdout).unwrap();
+            let stdout = String::from_utf8(stdout).unwrap();
+            assert_eq!(stdout.lines().count(), 1, "{stdout}");
+            let json: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+            assert_eq!(json["status"], "success", "{stdout}");
+        }
+    }
+}
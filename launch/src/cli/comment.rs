@@ -0,0 +1,113 @@
+//! The free-text comment of a submission, which records its intent in the `comment` annotation. Annotations of a
+//! resource share a limit of 256 KiB, so the comment is capped well below it.
+
+use log::warn;
+
+use crate::{ui, Result};
+
+/// The maximum length of a comment in bytes.
+pub const MAX_LEN: usize = 1024;
+
+/// Appended to a comment that has been cut short.
+const ELLIPSIS: char = '…';
+
+/// Trims the comment and cuts it at [`MAX_LEN`] bytes on a character boundary. Returns `None` for a blank comment, and
+/// whether the comment was truncated otherwise.
+fn truncate(comment: &str) -> Option<(String, bool)> {
+    let comment = comment.trim();
+    if comment.is_empty() {
+        return None;
+    }
+    if comment.len() <= MAX_LEN {
+        return Some((comment.to_owned(), false));
+    }
+    let mut end = MAX_LEN - ELLIPSIS.len_utf8();
+    while !comment.is_char_boundary(end) {
+        end -= 1;
+    }
+    let mut truncated = comment[..end].trim_end().to_owned();
+    truncated.push(ELLIPSIS);
+    Some((truncated, true))
+}
+
+/// Prepares the comment of `--comment` or of the prompt for the annotation, with a warning when it is too long.
+pub fn normalize(comment: &str) -> Option<String> {
+    let (comment, truncated) = truncate(comment)?;
+    if truncated {
+        warn!("Truncated the comment to {MAX_LEN} bytes");
+    }
+    Some(comment)
+}
+
+/// Asks for a comment on stderr. An empty answer submits without a comment.
+pub fn prompt() -> Result<Option<String>> {
+    Ok(normalize(&ui::prompt(
+        "Comment describing the intent of this submission (Enter to skip):",
+    )?))
+}
+
+/// Escapes the line breaks and other control characters of the comment, so that it fits on one line and can not
+/// move the cursor of the terminal. Backslashes are escaped as well, so that the escaped comment is unambiguous.
+pub fn display(comment: &str) -> String {
+    comment
+        .chars()
+        .map(|c| {
+            if c.is_control() || c == '\\' {
+                c.escape_default().to_string()
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_works() {
+        assert_eq!(truncate(""), None);
+        assert_eq!(truncate(" \n\t"), None);
+        assert_eq!(
+            truncate("  baseline with\nlr 3e-4 \n"),
+            Some(("baseline with\nlr 3e-4".to_owned(), false))
+        );
+
+        let exact = "a".repeat(MAX_LEN);
+        assert_eq!(truncate(&exact), Some((exact.clone(), false)));
+
+        let (long, truncated) = truncate(&"a".repeat(MAX_LEN + 1)).unwrap();
+        assert!(truncated);
+        assert_eq!(long.len(), MAX_LEN);
+        assert!(long.ends_with(ELLIPSIS));
+    }
+
+    #[test]
+    fn truncate_respects_char_boundaries() {
+        // 'é' takes two bytes and '🧪' four, so the cut falls inside a character.
+        for filler in ["é", "🧪"] {
+            let (comment, truncated) = truncate(&filler.repeat(MAX_LEN)).unwrap();
+            assert!(truncated);
+            assert!(comment.len() <= MAX_LEN, "{filler}");
+            assert!(comment.len() > MAX_LEN - 2 * filler.len(), "{filler}");
+            assert_eq!(
+                comment.trim_end_matches(ELLIPSIS).replace(filler, ""),
+                "",
+                "{filler}"
+            );
+        }
+    }
+
+    #[test]
+    fn display_escapes_control_characters() {
+        assert_eq!(
+            display("sweep lr, tiefe Netze 🧪"),
+            "sweep lr, tiefe Netze 🧪"
+        );
+        assert_eq!(display("first\nsecond\r\n"), r"first\nsecond\r\n");
+        assert_eq!(display("tab\there"), r"tab\there");
+        assert_eq!(display("\x1b[31mred"), r"\u{1b}[31mred");
+        assert_eq!(display(r"C:\data"), r"C:\\data");
+    }
+}
@@ -0,0 +1,468 @@
+//! Settings that can be configured per cluster context in `$XDG_CONFIG_HOME/launch/<context>.yaml`, which defaults to
+//! `~/.config/launch/<context>.yaml`, and overridden through environment variables and command line flags.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+
+use super::{list::MaxAge, telemetry::Telemetry, ClusterContext};
+use crate::{
+    executor::DefaultResources,
+    http::{Http, HttpConfig, ProxyEnv},
+    text_config::read_text_config,
+    Result,
+};
+
+pub const STRICT_GIT_ENV: &str = "LAUNCH_STRICT_GIT";
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct Config {
+    /// What to do when the git working tree is dirty or the commit has not been pushed.
+    pub strict_git: Option<StrictGit>,
+    /// The cpu and memory to request for jobs that do not pass `--mem`.
+    pub default_resources: Option<DefaultResources>,
+    /// Settings of `launch list`.
+    pub list: Option<ListConfig>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct ListConfig {
+    /// Finished jobs older than this are hidden unless `--all` is passed.
+    pub default_max_age: Option<MaxAge>,
+}
+
+/// Settings that apply to all contexts, in `defaults.yaml` in the [directory of launch](dir).
+#[derive(Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Defaults {
+    /// The context to use when `--context` is not passed and the repository does not set one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<ClusterContext>,
+    /// How to reach registries and webhooks on networks with a proxy or a private CA.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http: Option<HttpConfig>,
+    /// Whether to record anonymous usage counts, see [`telemetry`](super::telemetry).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub telemetry: Option<Telemetry>,
+    /// Whether `launch submit` asks for a comment when `--comment` is not passed and the terminal is interactive.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_for_comment: Option<bool>,
+}
+
+impl Defaults {
+    pub fn path() -> Option<PathBuf> {
+        Some(dir()?.join("defaults.yaml"))
+    }
+
+    /// Returns `Ok(None)` when the file does not exist.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let contents = match read_text_config(path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Ok(Some(Self::default()))
+            }
+            Err(error) => return Err(format!("Failed to read {path:?}: {error}").into()),
+        };
+        serde_yaml::from_str(&contents)
+            .map(Some)
+            .map_err(|error| format!("Failed to parse {path:?}: {error}").into())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_yaml::to_string(self)?)
+            .map_err(|error| format!("Failed to write {path:?}: {error}").into())
+    }
+}
+
+/// Builds the HTTP client from the `http` settings of `defaults.yaml` and the proxy environment variables.
+pub fn http() -> Result<Http> {
+    let config = match Defaults::path() {
+        Some(path) => Defaults::load(&path)?.and_then(|defaults| defaults.http),
+        None => None,
+    };
+    Http::new(&config.unwrap_or_default(), ProxyEnv::from_env()?)
+}
+
+/// Returns the `prompt_for_comment` setting of `defaults.yaml`, which is off by default.
+pub fn prompt_for_comment() -> Result<bool> {
+    let defaults = match Defaults::path() {
+        Some(path) => Defaults::load(&path)?,
+        None => None,
+    };
+    Ok(defaults
+        .and_then(|defaults| defaults.prompt_for_comment)
+        .unwrap_or_default())
+}
+
+/// Returns the directory that holds the files of launch, `$XDG_CONFIG_HOME/launch` or `~/.config/launch`.
+pub fn dir() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home::home_dir().map(|home| home.join(".config")))?;
+    Some(config_dir.join("launch"))
+}
+
+impl Config {
+    pub fn path(context: &ClusterContext) -> Option<PathBuf> {
+        let context = context.to_possible_value()?;
+        Some(dir()?.join(format!("{}.yaml", context.get_name())))
+    }
+
+    /// Returns `Ok(None)` when the file does not exist.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let contents = match read_text_config(path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Ok(Some(Self::default()))
+            }
+            Err(error) => return Err(format!("Failed to read {path:?}: {error}").into()),
+        };
+        serde_yaml::from_str(&contents)
+            .map(Some)
+            .map_err(|error| format!("Failed to parse {path:?}: {error}").into())
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, ValueEnum, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StrictGit {
+    /// Print a warning.
+    #[default]
+    Warn,
+    /// Refuse to submit.
+    Error,
+    /// Do not check.
+    Off,
+}
+
+impl StrictGit {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StrictGit::Warn => "warn",
+            StrictGit::Error => "error",
+            StrictGit::Off => "off",
+        }
+    }
+}
+
+impl fmt::Display for StrictGit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Where the effective value of a setting came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Flag(&'static str),
+    Env(&'static str),
+    File(PathBuf),
+    Default,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Flag(flag) => write!(f, "the `{flag}` flag"),
+            Source::Env(name) => write!(f, "the {name} environment variable"),
+            Source::File(path) => write!(f, "the configuration file {}", path.display()),
+            Source::Default => f.write_str("the default"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolved<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+/// Resolves the `strict_git` setting. The flag overrides the environment variable, which overrides the configuration
+/// file, which overrides the default.
+pub fn resolve_strict_git(
+    flag: Option<StrictGit>,
+    env: Option<&str>,
+    file: Option<(&Path, &Config)>,
+) -> Result<Resolved<StrictGit>> {
+    if let Some(value) = flag {
+        return Ok(Resolved {
+            value,
+            source: Source::Flag("--strict-git"),
+        });
+    }
+    if let Some(value) = env.filter(|value| !value.is_empty()) {
+        let value = StrictGit::from_str(value, true).map_err(|_| {
+            format!(
+                "Invalid value {value:?} for {STRICT_GIT_ENV}, expected one of warn, error or off"
+            )
+        })?;
+        return Ok(Resolved {
+            value,
+            source: Source::Env(STRICT_GIT_ENV),
+        });
+    }
+    if let Some((
+        path,
+        Config {
+            strict_git: Some(value),
+            ..
+        },
+    )) = file
+    {
+        return Ok(Resolved {
+            value: *value,
+            source: Source::File(path.to_owned()),
+        });
+    }
+    Ok(Resolved {
+        value: StrictGit::default(),
+        source: Source::Default,
+    })
+}
+
+/// Resolves a setting that can be set through a flag and a configuration file. The flag overrides the configuration
+/// file, which overrides the default.
+pub fn resolve<T>(
+    flag: Option<T>,
+    flag_name: &'static str,
+    file: Option<(&Path, Option<T>)>,
+    default: T,
+) -> Resolved<T> {
+    if let Some(value) = flag {
+        return Resolved {
+            value,
+            source: Source::Flag(flag_name),
+        };
+    }
+    if let Some((path, Some(value))) = file {
+        return Resolved {
+            value,
+            source: Source::File(path.to_owned()),
+        };
+    }
+    Resolved {
+        value: default,
+        source: Source::Default,
+    }
+}
+
+/// Returns the `default_resources` of the configuration file of the context, along with the path of the file.
+pub fn default_resources(
+    context: &ClusterContext,
+) -> Result<(Option<PathBuf>, Option<DefaultResources>)> {
+    let path = Config::path(context);
+    let config = match path.as_deref() {
+        Some(path) => Config::load(path)?,
+        None => None,
+    };
+    Ok((path, config.and_then(|config| config.default_resources)))
+}
+
+/// Returns the `list.default_max_age` of the configuration file of the context.
+pub fn list_default_max_age(context: &ClusterContext) -> Result<Option<MaxAge>> {
+    let config = match Config::path(context) {
+        Some(path) => Config::load(&path)?,
+        None => None,
+    };
+    Ok(config
+        .and_then(|config| config.list)
+        .and_then(|list| list.default_max_age))
+}
+
+/// Resolves the `strict_git` setting from the flag, the environment and the configuration file of the context.
+pub fn strict_git(
+    context: &ClusterContext,
+    flag: Option<StrictGit>,
+) -> Result<Resolved<StrictGit>> {
+    let env = std::env::var(STRICT_GIT_ENV).ok();
+    let path = Config::path(context);
+    let config = match path.as_deref() {
+        Some(path) if flag.is_none() => Config::load(path)?,
+        _ => None,
+    };
+    resolve_strict_git(flag, env.as_deref(), path.as_deref().zip(config.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_load_files_saved_on_windows() {
+        let path = crate::temp_path::tmp_json_path().with_extension("yaml");
+        std::fs::write(
+            &path,
+            "\u{FEFF}context: staging\r\nprompt_for_comment: true\r\n",
+        )
+        .unwrap();
+        let loaded = Defaults::load(&path);
+        std::fs::write(&path, "\u{FEFF}\r\n").unwrap();
+        let empty = Defaults::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.unwrap().unwrap();
+        assert_eq!(loaded.context, Some(ClusterContext::Staging));
+        assert_eq!(loaded.prompt_for_comment, Some(true));
+        assert_eq!(empty.unwrap(), Some(Defaults::default()));
+    }
+
+    #[test]
+    fn defaults_round_trip() {
+        let path = crate::temp_path::tmp_json_path().with_extension("yaml");
+        assert_eq!(Defaults::load(&path).unwrap(), None);
+
+        let defaults = Defaults {
+            context: Some(ClusterContext::VoltagePark),
+            ..Default::default()
+        };
+        defaults.save(&path).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let loaded = Defaults::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(contents, "context: voltage-park\n");
+        assert_eq!(loaded.unwrap(), Some(defaults));
+        assert_eq!(
+            serde_yaml::from_str::<Defaults>("{}").unwrap(),
+            Defaults::default()
+        );
+        assert!(serde_yaml::from_str::<Defaults>("context: moon").is_err());
+        assert_eq!(
+            serde_yaml::from_str::<Defaults>("http:\n  timeout_seconds: 30\n")
+                .unwrap()
+                .http
+                .unwrap()
+                .timeout_seconds,
+            Some(30)
+        );
+        assert_eq!(
+            serde_yaml::from_str::<Defaults>("prompt_for_comment: true\n")
+                .unwrap()
+                .prompt_for_comment,
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn resolve_strict_git_works() {
+        let path = Path::new("/home/user/.config/launch/berkeley.yaml");
+        let config = Config {
+            strict_git: Some(StrictGit::Off),
+            ..Default::default()
+        };
+        let file = Some((path, &config));
+
+        assert_eq!(
+            resolve_strict_git(Some(StrictGit::Error), Some("warn"), file).unwrap(),
+            Resolved {
+                value: StrictGit::Error,
+                source: Source::Flag("--strict-git"),
+            }
+        );
+        assert_eq!(
+            resolve_strict_git(None, Some("error"), file).unwrap(),
+            Resolved {
+                value: StrictGit::Error,
+                source: Source::Env(STRICT_GIT_ENV),
+            }
+        );
+        assert_eq!(
+            resolve_strict_git(None, Some(""), file).unwrap(),
+            Resolved {
+                value: StrictGit::Off,
+                source: Source::File(path.to_owned()),
+            }
+        );
+        assert_eq!(
+            resolve_strict_git(None, None, Some((path, &Config::default()))).unwrap(),
+            Resolved {
+                value: StrictGit::Warn,
+                source: Source::Default,
+            }
+        );
+        assert_eq!(
+            resolve_strict_git(None, None, None).unwrap(),
+            Resolved {
+                value: StrictGit::Warn,
+                source: Source::Default,
+            }
+        );
+        assert!(resolve_strict_git(None, Some("strict"), file)
+            .unwrap_err()
+            .to_string()
+            .contains(STRICT_GIT_ENV));
+    }
+
+    #[test]
+    fn resolve_works() {
+        let path = Path::new("/repo/.launch.toml");
+        let flag = |value: Option<u32>| resolve(value, "--gpus", Some((path, Some(8))), 0);
+        assert_eq!(
+            flag(Some(2)),
+            Resolved {
+                value: 2,
+                source: Source::Flag("--gpus"),
+            }
+        );
+        // An explicit flag that equals the default still overrides the configuration file.
+        assert_eq!(flag(Some(0)).value, 0);
+        assert_eq!(
+            flag(None),
+            Resolved {
+                value: 8,
+                source: Source::File(path.to_owned()),
+            }
+        );
+        assert_eq!(
+            resolve(None, "--gpus", Some((path, None)), 0),
+            Resolved {
+                value: 0,
+                source: Source::Default,
+            }
+        );
+        assert_eq!(
+            resolve::<u32>(None, "--gpus", None, 0),
+            Resolved {
+                value: 0,
+                source: Source::Default,
+            }
+        );
+    }
+
+    #[test]
+    fn config_parses() {
+        let config: Config = serde_yaml::from_str("strict_git: error\n").unwrap();
+        assert_eq!(config.strict_git, Some(StrictGit::Error));
+        let config: Config = serde_yaml::from_str("{}").unwrap();
+        assert_eq!(config.strict_git, None);
+        assert!(serde_yaml::from_str::<Config>("strict_git: maybe\n").is_err());
+        let config: Config =
+            serde_yaml::from_str("default_resources:\n  cpu: 4\n  memory: 16GiB\n").unwrap();
+        assert_eq!(config.default_resources.unwrap().cpu.as_deref(), Some("4"));
+        let config: Config = serde_yaml::from_str("list:\n  default_max_age: 7d\n").unwrap();
+        assert_eq!(
+            config.list.unwrap().default_max_age,
+            Some("7d".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn source_display_works() {
+        assert_eq!(
+            Source::File(PathBuf::from("/c/launch/staging.yaml")).to_string(),
+            "the configuration file /c/launch/staging.yaml"
+        );
+        assert_eq!(
+            Source::Env(STRICT_GIT_ENV).to_string(),
+            "the LAUNCH_STRICT_GIT environment variable"
+        );
+    }
+}
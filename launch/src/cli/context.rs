@@ -0,0 +1,123 @@
+//! Decides which contexts a command talks to. `--context` overrides the `context` of `.launch.toml` in the repository,
+//! which overrides the `context` of `defaults.yaml` in the [directory of launch](super::config::dir). When none of them
+//! is set, the user is asked once on a terminal and the default context is used otherwise.
+
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum as _;
+use log::{debug, info, warn};
+
+use super::{
+    config::{Defaults, Resolved, Source},
+    repo_config, ClusterContext,
+};
+use crate::{ui, Result};
+
+/// Resolves the context from the configuration files, or returns `None` when neither sets one.
+fn resolve(
+    repo: Option<(&Path, ClusterContext)>,
+    defaults: Option<(&Path, ClusterContext)>,
+) -> Option<Resolved<ClusterContext>> {
+    repo.or(defaults).map(|(path, value)| Resolved {
+        value,
+        source: Source::File(path.to_owned()),
+    })
+}
+
+fn describe(context: ClusterContext) -> String {
+    match context.cluster_url() {
+        Some(cluster_url) => format!("{context} ({cluster_url})"),
+        None => format!("{context} (your kubeconfig)"),
+    }
+}
+
+/// Asks the user to choose a context and offers to remember the choice in `defaults.yaml`.
+fn ask(defaults_path: Option<PathBuf>) -> Result<ClusterContext> {
+    let contexts = ClusterContext::value_variants();
+    let items = contexts
+        .iter()
+        .map(|&context| describe(context))
+        .collect::<Vec<_>>();
+    let Some(index) = ui::select(
+        "No context is configured. Which cluster do you want to use?",
+        &items,
+    )?
+    else {
+        return Err("No context was selected, pass `--context` to choose one".into());
+    };
+    let context = contexts[index];
+
+    if let Some(path) = defaults_path {
+        if ui::confirm(&format!(
+            "Remember {context} as the default context in {}?",
+            path.display()
+        ))? {
+            let mut defaults = Defaults::load(&path)?.unwrap_or_default();
+            defaults.context = Some(context);
+            match defaults.save(&path) {
+                Ok(()) => info!(
+                    "Saved {context} as the default context in {}",
+                    path.display()
+                ),
+                Err(error) => warn!("Unable to remember the default context: {error}"),
+            }
+        }
+    }
+    Ok(context)
+}
+
+/// Returns the contexts passed through `--context`, or the single context that the configuration files set or the user
+/// chooses.
+pub fn contexts(flag: Vec<ClusterContext>) -> Result<Vec<ClusterContext>> {
+    if !flag.is_empty() {
+        return Ok(flag);
+    }
+    let repo = repo_config::context()?;
+    let defaults_path = Defaults::path();
+    let defaults = match defaults_path.as_deref() {
+        Some(path) => Defaults::load(path)?.and_then(|defaults| defaults.context),
+        None => None,
+    };
+    if let Some(Resolved { value, source }) = resolve(
+        repo.as_ref()
+            .map(|(path, context)| (path.as_path(), *context)),
+        defaults_path.as_deref().zip(defaults),
+    ) {
+        debug!("Using context {value} from {source}");
+        return Ok(vec![value]);
+    }
+    if !ui::is_interactive() {
+        return Ok(vec![ClusterContext::default()]);
+    }
+    Ok(vec![ask(defaults_path)?])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_works() {
+        let repo = Path::new("/repo/.launch.toml");
+        let defaults = Path::new("/home/user/.config/launch/defaults.yaml");
+
+        assert_eq!(resolve(None, None), None);
+        assert_eq!(
+            resolve(None, Some((defaults, ClusterContext::Staging))),
+            Some(Resolved {
+                value: ClusterContext::Staging,
+                source: Source::File(defaults.to_owned()),
+            })
+        );
+        assert_eq!(
+            resolve(
+                Some((repo, ClusterContext::VoltagePark)),
+                Some((defaults, ClusterContext::Staging))
+            ),
+            Some(Resolved {
+                value: ClusterContext::VoltagePark,
+                source: Source::File(repo.to_owned()),
+            })
+        );
+    }
+}
@@ -0,0 +1,226 @@
+//! Compares a manifest with the resource of the same kind that runs on the cluster, for example to review the changes
+//! to the output of `launch submit --dry-run` before submitting it.
+
+use std::{io::IsTerminal as _, path::PathBuf};
+
+use clap::Args;
+use log::info;
+use serde_json::Value;
+
+use super::{
+    resubmit::{self, Kind},
+    ClusterContext,
+};
+use crate::{
+    diff,
+    executor::RUN_ID_ENV,
+    kubectl::{self, annotation},
+    text_config::read_text_config,
+    Result,
+};
+
+/// The number of unchanged lines around each change.
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, Args)]
+pub struct DiffArgs {
+    /// The name of the Job, RayJob or Experiment on the cluster.
+    pub name: String,
+
+    /// The YAML or JSON manifest to compare with, such as the output of `launch submit --dry-run`.
+    #[arg(long = "from-spec")]
+    pub from_spec: PathBuf,
+}
+
+/// Removes the fields that the server populates and what launch records about each submission, so that only the
+/// differences that were asked for remain. The keys come out sorted, because `serde_json` keeps objects in a `BTreeMap`.
+fn normalize(kind: Kind, mut resource: Value) -> Result<String> {
+    resubmit::sanitize(kind, &mut resource);
+    forget_submission(&mut resource);
+    Ok(serde_yaml::to_string(&resource)?)
+}
+
+/// Removes the annotations, labels and environment variables that launch sets on the resource and its templates, such as
+/// the run ID and the version of launch, because they differ between any two submissions. The comment is kept.
+fn forget_submission(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for key in ["annotations", "labels"] {
+                if let Some(entries) = map.get_mut(key).and_then(Value::as_object_mut) {
+                    entries.retain(|key, _| {
+                        key == annotation::COMMENT
+                            || !(key.starts_with(annotation::PREFIX)
+                                || key == annotation::LEGACY_LAUNCHED_BY_USER)
+                    });
+                    if entries.is_empty() {
+                        map.remove(key);
+                    }
+                }
+            }
+            if let Some(env) = map.get_mut("env").and_then(Value::as_array_mut) {
+                env.retain(|var| var["name"] != RUN_ID_ENV);
+            }
+            map.values_mut().for_each(forget_submission);
+        }
+        Value::Array(values) => values.iter_mut().for_each(forget_submission),
+        _ => {}
+    }
+}
+
+/// Returns the unified diff from the live resource to the manifest, which is empty when they are the same.
+fn render(
+    kind: Kind,
+    name: &str,
+    live: Value,
+    spec_label: &str,
+    spec: Value,
+    color: bool,
+) -> Result<String> {
+    let live = normalize(kind, live)?;
+    let spec = normalize(kind, spec)?;
+    Ok(diff::unified(
+        &diff::hunks(&live, &spec, CONTEXT_LINES),
+        &format!("{} {name}", kind.as_str()),
+        spec_label,
+        color,
+    ))
+}
+
+pub fn diff(context: &ClusterContext, args: DiffArgs) -> Result<()> {
+    let DiffArgs { name, from_spec } = args;
+
+    let contents = read_text_config(&from_spec)
+        .map_err(|error| format!("Failed to read {}: {error}", from_spec.display()))?;
+    let spec: Value = serde_yaml::from_str(&contents)
+        .map_err(|error| format!("Failed to parse {}: {error}", from_spec.display()))?;
+
+    let kubectl = context.kubectl();
+    let (kind, live) = resubmit::find(context, &kubectl, kubectl::NAMESPACE, &name)?;
+    // The live resource carries the defaults of the server, so the manifest is compared with them filled in too.
+    let spec = kubectl
+        .with_server_defaults(&spec.to_string())
+        .map_err(|error| format!("The server rejected {}: {error}", from_spec.display()))?;
+
+    let output = render(
+        kind,
+        &name,
+        live,
+        &from_spec.display().to_string(),
+        spec,
+        std::io::stdout().is_terminal(),
+    )?;
+    if output.is_empty() {
+        info!("{} {name:?} matches {}", kind.as_str(), from_spec.display());
+    } else {
+        print!("{output}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const OLD_IMAGE: &str =
+        "registry/launch@sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+    const NEW_IMAGE: &str =
+        "registry/launch@sha256:fedcba9876543210fedcba9876543210fedcba9876543210fedcba9876543210";
+
+    fn job(image: &str, learning_rate: &str, run_id: &str) -> Value {
+        json!({
+            "apiVersion": "batch/v1",
+            "kind": "Job",
+            "metadata": {
+                "generateName": "alice-job-",
+                "namespace": "launch",
+                "annotations": {
+                    annotation::RUN_ID: run_id,
+                    annotation::VERSION: run_id,
+                    annotation::COMMENT: "sweep",
+                },
+                "labels": { "team": "neuro", kubectl::label::RUN_ID: run_id },
+            },
+            "spec": {
+                "backoffLimit": 0,
+                "ttlSecondsAfterFinished": 604800,
+                "template": {
+                    "spec": {
+                        "restartPolicy": "Never",
+                        "containers": [{
+                            "name": "main",
+                            "args": ["python", "train.py"],
+                            "image": image,
+                            "imagePullPolicy": "IfNotPresent",
+                            "resources": { "limits": { "nvidia.com/gpu": "1" } },
+                            "workingDir": "/app",
+                            "env": [
+                                { "name": "LEARNING_RATE", "value": learning_rate },
+                                { "name": "WANDB_PROJECT", "value": "sweep" },
+                                { "name": RUN_ID_ENV, "value": run_id },
+                            ],
+                        }],
+                    },
+                },
+            },
+        })
+    }
+
+    #[test]
+    fn render_shows_only_the_changes() {
+        let mut live = job(OLD_IMAGE, "0.01", "run-1");
+        // The fields that the server populates and what launch records about each submission are not differences.
+        live["metadata"]["name"] = "alice-job-x7k2p".into();
+        live["metadata"]["uid"] = "5d0c3c1e".into();
+        live["metadata"]["resourceVersion"] = "123456".into();
+        live["spec"]["selector"] =
+            json!({ "matchLabels": { "batch.kubernetes.io/controller-uid": "5d0c3c1e" } });
+        live["status"] = json!({ "failed": 1 });
+
+        let output = render(
+            Kind::Job,
+            "alice-job-x7k2p",
+            live.clone(),
+            "new.yaml",
+            job(NEW_IMAGE, "0.001", "run-2"),
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            output,
+            format!(
+                "\
+--- Job alice-job-x7k2p
++++ new.yaml
+@@ -17,10 +17,10 @@
+         - train.py
+         env:
+         - name: LEARNING_RATE
+-          value: '0.01'
++          value: '0.001'
+         - name: WANDB_PROJECT
+           value: sweep
+-        image: {OLD_IMAGE}
++        image: {NEW_IMAGE}
+         imagePullPolicy: IfNotPresent
+         name: main
+         resources:
+"
+            )
+        );
+
+        assert_eq!(
+            render(
+                Kind::Job,
+                "alice-job-x7k2p",
+                live,
+                "same.yaml",
+                job(OLD_IMAGE, "0.01", "run-2"),
+                true
+            )
+            .unwrap(),
+            ""
+        );
+    }
+}
@@ -0,0 +1,52 @@
+use std::path::PathBuf;
+
+use clap::{Args, ValueEnum as _};
+use log::debug;
+
+use super::{config, ClusterContext};
+use crate::{kubectl, Result};
+
+#[derive(Debug, Args)]
+pub struct GetKubeconfigArgs {
+    /// Where to write the kubeconfig, or `-` to print it instead of its path. Defaults to
+    /// `~/.config/launch/<context>.kubeconfig`, so that `KUBECONFIG=$(launch get-kubeconfig) kubectl get pods` works.
+    #[arg(long = "output", short = 'o')]
+    pub output: Option<PathBuf>,
+}
+
+pub fn get_kubeconfig(context: &ClusterContext, args: GetKubeconfigArgs) -> Result<()> {
+    let GetKubeconfigArgs { output } = args;
+
+    let context_name = context
+        .to_possible_value()
+        .ok_or("The context has no name")?
+        .get_name()
+        .to_owned();
+    let kubeconfig = context
+        .kubectl()
+        .kubeconfig(&format!("launch-{context_name}"), kubectl::NAMESPACE)
+        .map_err(|error| format!("{error}, run kubectl without `launch get-kubeconfig`"))?;
+    let yaml = serde_yaml::to_string(&kubeconfig)?;
+
+    let path = match output {
+        Some(path) if path.as_os_str() == "-" => {
+            print!("{yaml}");
+            return Ok(());
+        }
+        Some(path) => path,
+        None => config::dir()
+            .ok_or("Failed to determine the configuration directory, pass `--output` instead")?
+            .join(format!("{context_name}.kubeconfig")),
+    };
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent)
+            .map_err(|error| format!("Failed to create {parent:?}: {error}"))?;
+    }
+    std::fs::write(&path, yaml).map_err(|error| format!("Failed to write {path:?}: {error}"))?;
+    debug!("Wrote the kubeconfig for {context_name} to {path:?}");
+    println!("{}", path.display());
+    Ok(())
+}
@@ -0,0 +1,296 @@
+//! The local history of submissions in `history.jsonl` in the [directory of launch](super::config::dir), with one JSON
+//! object per line. The history remembers which context a submission went to, so that commands that can not find a
+//! resource can point at the context that has it.
+
+use std::{
+    io::Write as _,
+    path::{Path, PathBuf},
+};
+
+use log::{debug, warn};
+
+use super::{config, ClusterContext};
+use crate::{executor::ResourceKind, Result};
+
+const FILE_NAME: &str = "history.jsonl";
+
+/// The version of the format of [`Entry`]. Entries of newer versions are skipped, entries of older versions are
+/// [migrated](migrate) when they are read.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// The schema version of entries written before the entries recorded it.
+fn unversioned() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Entry {
+    #[serde(default = "unversioned")]
+    pub schema_version: u32,
+    /// The name of the context, such as `voltage-park`.
+    pub context: String,
+    pub namespace: String,
+    pub name: String,
+    /// The kind of the resource that can be awaited, `Job` or `Experiment`.
+    pub kind: String,
+    pub run_id: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub submitted_at: time::OffsetDateTime,
+    /// The `--comment` of the submission. Older entries have none, so adding it did not change the schema version.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+impl Entry {
+    /// Describes a submission that was just created, in the current [`SCHEMA_VERSION`].
+    pub fn new(
+        context: &ClusterContext,
+        kind: ResourceKind,
+        namespace: &str,
+        name: &str,
+        run_id: &str,
+        comment: Option<String>,
+    ) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            context: context.to_string(),
+            namespace: namespace.to_owned(),
+            name: name.to_owned(),
+            kind: kind.as_str().to_owned(),
+            run_id: run_id.to_owned(),
+            submitted_at: time::OffsetDateTime::now_utc(),
+            comment,
+        }
+    }
+}
+
+pub fn path() -> Option<PathBuf> {
+    Some(config::dir()?.join(FILE_NAME))
+}
+
+fn append(path: &Path, entry: &Entry) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// Appends the submission to the history. The history only improves error messages, so failing to write it is not an
+/// error.
+pub fn record(entry: &Entry) {
+    let Some(path) = path() else {
+        debug!("Not recording the submission because the home directory is unknown");
+        return;
+    };
+    if let Err(error) = append(&path, entry) {
+        warn!(
+            "Unable to record the submission in {}: {error}",
+            path.display()
+        );
+    }
+}
+
+/// Upgrades an entry of schema version `from` to [`SCHEMA_VERSION`].
+fn migrate(mut value: serde_json::Value, from: u32) -> serde_json::Value {
+    for version in from..SCHEMA_VERSION {
+        match version {
+            // Version 2 only added `schema_version` itself.
+            1 => {}
+            _ => unreachable!("no migration from schema version {version}"),
+        }
+    }
+    value["schema_version"] = SCHEMA_VERSION.into();
+    value
+}
+
+enum Line {
+    Entry(Entry),
+    /// An entry written by a newer version of launch, with the given schema version.
+    Newer(u32),
+    Invalid,
+}
+
+fn parse(line: &str) -> Line {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(line) else {
+        return Line::Invalid;
+    };
+    let schema_version = match value.get("schema_version") {
+        None => unversioned(),
+        Some(version) => match version.as_u64().and_then(|v| u32::try_from(v).ok()) {
+            Some(version) => version,
+            None => return Line::Invalid,
+        },
+    };
+    if schema_version > SCHEMA_VERSION {
+        return Line::Newer(schema_version);
+    }
+    match serde_json::from_value(migrate(value, schema_version)) {
+        Ok(entry) => Line::Entry(entry),
+        Err(_) => Line::Invalid,
+    }
+}
+
+/// Returns the most recent submission named `name`. Lines that can not be parsed are skipped, and so are entries
+/// written by a newer version of launch, with a warning.
+fn find(path: &Path, name: &str) -> Result<Option<Entry>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(format!("Failed to read {}: {error}", path.display()).into()),
+    };
+    let mut newer = None;
+    let found = contents
+        .lines()
+        .rev()
+        .filter_map(|line| match parse(line) {
+            Line::Entry(entry) => Some(entry),
+            Line::Newer(version) => {
+                newer = newer.max(Some(version));
+                None
+            }
+            Line::Invalid => None,
+        })
+        .find(|entry| entry.name == name);
+    if let Some(version) = newer {
+        warn!(
+            "Skipping entries of {} with schema version {version}, which were written by a newer version of launch than {}",
+            path.display(),
+            *crate::version::SEMVER
+        );
+    }
+    Ok(found)
+}
+
+/// Explains that the resource was submitted to another context, if the history says so.
+fn other_context_hint(entry: Option<&Entry>, context: &ClusterContext) -> Option<String> {
+    let entry = entry.filter(|entry| entry.context != context.to_string())?;
+    Some(format!(
+        "{} {} was submitted to context {context_name}; rerun with --context {context_name}",
+        entry.kind.to_lowercase(),
+        entry.name,
+        context_name = entry.context,
+    ))
+}
+
+/// Builds the error for a resource named `name` that does not exist in `context`, mentioning the context that it was
+/// submitted to when that is a different one.
+pub fn not_found(context: &ClusterContext, name: &str, message: String) -> String {
+    let entry = path().and_then(|path| {
+        find(&path, name)
+            .inspect_err(|error| debug!("Unable to consult the submission history: {error}"))
+            .ok()
+            .flatten()
+    });
+    match other_context_hint(entry.as_ref(), context) {
+        Some(hint) => format!("{message}, {hint}"),
+        None => message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(context: &str, name: &str, run_id: &str) -> Entry {
+        Entry {
+            schema_version: SCHEMA_VERSION,
+            context: context.to_owned(),
+            namespace: "launch".to_owned(),
+            name: name.to_owned(),
+            kind: "Job".to_owned(),
+            run_id: run_id.to_owned(),
+            submitted_at: time::macros::datetime!(2025-01-13 12:00 UTC),
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn find_returns_the_latest_submission() {
+        let path = crate::temp_path::tmp_json_path();
+        assert_eq!(find(&path, "foo-abc").unwrap(), None);
+
+        append(&path, &entry("berkeley", "foo-abc", "1")).unwrap();
+        append(&path, &entry("berkeley", "bar-xyz", "2")).unwrap();
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"not json\n")
+            .unwrap();
+        append(&path, &entry("voltage-park", "foo-abc", "3")).unwrap();
+
+        let found = find(&path, "foo-abc");
+        let missing = find(&path, "baz-def");
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(found.unwrap(), Some(entry("voltage-park", "foo-abc", "3")));
+        assert_eq!(missing.unwrap(), None);
+    }
+
+    #[test]
+    fn find_handles_mixed_schema_versions() {
+        let path = crate::temp_path::tmp_json_path();
+        std::fs::write(
+            &path,
+            [
+                r#"{"context":"berkeley","namespace":"launch","name":"foo-abc","kind":"Job","run_id":"1","submitted_at":"2025-01-13T12:00:00Z"}"#,
+                r#"{"schema_version":2,"context":"berkeley","namespace":"launch","name":"bar-xyz","kind":"Job","run_id":"2","submitted_at":"2025-01-13T12:00:00Z"}"#,
+                r#"{"schema_version":3,"context":"voltage-park","namespace":"launch","name":"foo-abc","kind":"Job","run_id":"3","submitted_at":"2025-01-13T12:00:00Z","extra":true}"#,
+                r#"{"schema_version":"two","context":"voltage-park","name":"bar-xyz"}"#,
+            ]
+            .join("\n"),
+        )
+        .unwrap();
+
+        // The version 1 entry is migrated and the version 3 entry is skipped even though it is more recent.
+        let foo = find(&path, "foo-abc");
+        let bar = find(&path, "bar-xyz");
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(foo.unwrap(), Some(entry("berkeley", "foo-abc", "1")));
+        assert_eq!(bar.unwrap(), Some(entry("berkeley", "bar-xyz", "2")));
+    }
+
+    #[test]
+    fn find_returns_the_comment() {
+        let path = crate::temp_path::tmp_json_path();
+        let commented = Entry {
+            comment: Some("lr sweep, \"warmup\" 500\nπ ≈ 3.14 🧪".to_owned()),
+            ..entry("berkeley", "foo-abc", "1")
+        };
+        append(&path, &commented).unwrap();
+        append(&path, &entry("berkeley", "bar-xyz", "2")).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+
+        let foo = find(&path, "foo-abc");
+        let bar = find(&path, "bar-xyz");
+        std::fs::remove_file(&path).unwrap();
+
+        // The newline is escaped, so that the entry stays on one line, and an absent comment is omitted.
+        assert_eq!(contents.lines().count(), 2);
+        assert!(!contents.lines().nth(1).unwrap().contains("comment"));
+        assert_eq!(foo.unwrap(), Some(commented));
+        assert_eq!(bar.unwrap(), Some(entry("berkeley", "bar-xyz", "2")));
+    }
+
+    #[test]
+    fn other_context_hint_works() {
+        let entry = entry("voltage-park", "foo-abc", "1");
+        assert_eq!(
+            other_context_hint(Some(&entry), &ClusterContext::Berkeley).as_deref(),
+            Some("job foo-abc was submitted to context voltage-park; rerun with --context voltage-park")
+        );
+        assert_eq!(
+            other_context_hint(Some(&entry), &ClusterContext::VoltagePark),
+            None
+        );
+        assert_eq!(other_context_hint(None, &ClusterContext::Berkeley), None);
+    }
+}
@@ -1,10 +1,14 @@
-use std::{collections::HashMap, fmt::Write as _};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write as _,
+};
 
 use clap::{Args, ValueEnum};
+use log::warn;
 use time::UtcOffset;
 use time_local::UtcOffsetExt;
 
-use super::ClusterContext;
+use super::{comment, config, ClusterContext};
 use crate::{
     ansi,
     kubectl::{self},
@@ -16,11 +20,103 @@ pub struct ListArgs {
     /// How to build the image.
     #[arg(value_enum, default_value_t)]
     pub resource: ResourceArg,
+
+    /// How to print the resources.
+    #[arg(long = "output", short = 'o', value_enum, default_value_t)]
+    pub output: OutputArg,
+
+    /// Also print jobs that finished more than `list.default_max_age` ago, which defaults to 2d. Only applies to the
+    /// table output.
+    #[arg(long = "all", default_value_t)]
+    pub all: bool,
+
+    /// Hide jobs that finished longer ago than this, for example `12h` or `7d`. Applies to all outputs.
+    #[arg(long = "max-age", conflicts_with = "all")]
+    pub max_age: Option<MaxAge>,
+
+    /// Only print the jobs of the submission with this run ID, which `launch submit` prints. Finished jobs are not
+    /// hidden unless `--max-age` is given.
+    #[arg(long = "run-id")]
+    pub run_id: Option<String>,
+
+    /// Also print the comment of each job. Only applies to the table output.
+    #[arg(long = "wide", default_value_t)]
+    pub wide: bool,
+}
+
+/// The age after which finished jobs are hidden, such as `48h` or `2d`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaxAge(time::Duration);
+
+impl MaxAge {
+    pub const DEFAULT: Self = Self(time::Duration::days(2));
+
+    pub fn duration(self) -> time::Duration {
+        self.0
+    }
+}
+
+impl std::str::FromStr for MaxAge {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let error = || {
+            format!(
+                "invalid age {value:?}, expected a number followed by d, h, m or s, such as `2d`"
+            )
+        };
+        let split_at = value
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(error)?;
+        let (number, unit) = value.split_at(split_at);
+        let number: i64 = number.parse().map_err(|_| error())?;
+        let seconds_per_unit = match unit {
+            "d" => 86_400,
+            "h" => 3_600,
+            "m" => 60,
+            "s" => 1,
+            _ => return Err(error()),
+        };
+        let seconds = number
+            .checked_mul(seconds_per_unit)
+            .ok_or_else(|| format!("the age {value:?} is too large"))?;
+        Ok(Self(time::Duration::seconds(seconds)))
+    }
+}
+
+impl std::fmt::Display for MaxAge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let seconds = self.0.whole_seconds();
+        match seconds {
+            _ if seconds % 86400 == 0 => write!(f, "{}d", seconds / 86400),
+            _ if seconds % 3600 == 0 => write!(f, "{}h", seconds / 3600),
+            _ if seconds % 60 == 0 => write!(f, "{}m", seconds / 60),
+            _ => write!(f, "{seconds}s"),
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for MaxAge {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum OutputArg {
+    /// Print a human readable table.
+    #[default]
+    Table,
+    /// Print a JSON array with the structured status of the Job, RayJob and Pods of each job, along with the launch
+    /// annotations. Only supported for jobs, and leaves out CronJobs.
+    WideJson,
 }
 
 #[derive(Debug, Default, Clone, Copy, ValueEnum, PartialEq, Eq)]
 pub enum ResourceArg {
-    /// Print information about the launched jobs.
+    /// Print information about the launched jobs, followed by the CronJobs that `launch submit --schedule` created.
     #[default]
     Jobs,
     /// Print information about the cluster's nodes.
@@ -28,14 +124,80 @@ pub enum ResourceArg {
 }
 
 pub fn list(context: &ClusterContext, args: ListArgs) -> Result<()> {
-    match args.resource {
-        ResourceArg::Jobs => list_jobs(context)?,
-        ResourceArg::Nodes => list_nodes(context)?,
+    match (args.resource, args.output) {
+        (ResourceArg::Jobs, output) => {
+            // The JSON output is meant for scripts, which should not be surprised by rows disappearing over time.
+            let max_age = match (args.all, args.max_age, output) {
+                (true, _, _) => None,
+                (false, Some(max_age), _) => Some(max_age),
+                (false, None, _) if args.run_id.is_some() => None,
+                (false, None, OutputArg::Table) => {
+                    Some(config::list_default_max_age(context)?.unwrap_or(MaxAge::DEFAULT))
+                }
+                (false, None, OutputArg::WideJson) => None,
+            };
+            list_jobs(
+                context,
+                output,
+                max_age,
+                args.max_age.is_some(),
+                args.run_id.as_deref(),
+                args.wide,
+            )?
+        }
+        (ResourceArg::Nodes, _) if args.run_id.is_some() => {
+            return Err("`--run-id` is only supported for jobs".into())
+        }
+        (ResourceArg::Nodes, _) if args.wide => {
+            return Err("`--wide` is only supported for jobs".into())
+        }
+        (ResourceArg::Nodes, OutputArg::Table) => list_nodes(context)?,
+        (ResourceArg::Nodes, OutputArg::WideJson) => {
+            return Err("`--output wide-json` is only supported for jobs".into())
+        }
     }
     Ok(())
 }
 
-pub fn list_jobs(context: &ClusterContext) -> Result<()> {
+/// Removes the rows that finished longer than `max_age` before `now` and returns how many were removed.
+fn retain_recent<T>(
+    rows: &mut Vec<T>,
+    now: time::OffsetDateTime,
+    max_age: MaxAge,
+    finished: impl Fn(&T) -> Option<time::OffsetDateTime>,
+) -> usize {
+    let len = rows.len();
+    rows.retain(|row| finished(row).is_none_or(|finished| now - finished <= max_age.0));
+    len - rows.len()
+}
+
+/// Describes the `hidden` rows. `--all` conflicts with `--max-age`, so the hint depends on whether `max_age` was given
+/// on the command line or came from the configuration.
+fn hidden_footer(hidden: usize, max_age: MaxAge, explicit: bool) -> Option<String> {
+    let hint = if explicit {
+        "use a larger --max-age"
+    } else {
+        "use --all"
+    };
+    match hidden {
+        0 => None,
+        1 => Some(format!(
+            "+ 1 finished job older than {max_age} hidden ({hint})"
+        )),
+        _ => Some(format!(
+            "+ {hidden} finished jobs older than {max_age} hidden ({hint})"
+        )),
+    }
+}
+
+pub fn list_jobs(
+    context: &ClusterContext,
+    output: OutputArg,
+    max_age: Option<MaxAge>,
+    explicit_max_age: bool,
+    run_id: Option<&str>,
+    wide: bool,
+) -> Result<()> {
     let kubectl = context.kubectl();
 
     fn cmp_date_then_name(
@@ -60,12 +222,6 @@ pub fn list_jobs(context: &ClusterContext) -> Result<()> {
         ray_jobs
     };
 
-    let pods = {
-        let mut pods = kubectl.pods(kubectl::NAMESPACE)?;
-        pods.sort_by(|a, b| cmp_date_then_name(&a.metadata, &b.metadata));
-        pods
-    };
-
     #[derive(Default)]
     struct Entry {
         job: Option<kubectl::Job>,
@@ -98,31 +254,96 @@ pub fn list_jobs(context: &ClusterContext) -> Result<()> {
             .is_none());
     }
 
-    for pod in pods {
-        if let Some(owner_reference) = pod.metadata.owner_references.first() {
-            match owner_reference.kind.as_str() {
-                "Job" => {
-                    assert_eq!(
-                        Some(&owner_reference.name),
-                        pod.metadata.labels.get("job-name"),
-                        "owner reference and label `job-name` should be the same"
-                    );
-                    if let Some(entry) = map.get_mut(&owner_reference.name) {
-                        entry.pods.push(pod);
-                    }
-                }
-                "RayCluster" => {
-                    ray_cluster_name_to_pods
-                        .entry(owner_reference.name.to_owned())
-                        .or_default()
-                        .push(pod);
+    let ray_cluster_names = map
+        .values()
+        .filter_map(|entry| entry.ray_job.as_ref()?.status.ray_cluster_name.clone())
+        .collect::<std::collections::HashSet<_>>();
+
+    // The namespace can hold many Pods, so only the Pods of the listed resources are kept.
+    kubectl.for_each_pod(kubectl::NAMESPACE, |pod| {
+        let Some(owner_reference) = pod.metadata.owner_references.first() else {
+            return;
+        };
+        match owner_reference.kind.as_str() {
+            "Job" => {
+                assert_eq!(
+                    Some(&owner_reference.name),
+                    pod.metadata.labels.get("job-name"),
+                    "owner reference and label `job-name` should be the same"
+                );
+                if let Some(entry) = map.get_mut(&owner_reference.name) {
+                    entry.pods.push(pod);
                 }
-                _ => {}
             }
+            "RayCluster" if ray_cluster_names.contains(&owner_reference.name) => {
+                ray_cluster_name_to_pods
+                    .entry(owner_reference.name.to_owned())
+                    .or_default()
+                    .push(pod);
+            }
+            _ => {}
+        }
+    })?;
+
+    for pods in map
+        .values_mut()
+        .map(|entry| &mut entry.pods)
+        .chain(ray_cluster_name_to_pods.values_mut())
+    {
+        pods.sort_by(|a, b| cmp_date_then_name(&a.metadata, &b.metadata));
+    }
+
+    if let Some(run_id) = run_id {
+        // The submitter Job of a RayJob does not carry the label, but shares the name of the RayJob.
+        map.retain(|_, entry| {
+            [
+                entry.job.as_ref().map(|job| &job.metadata),
+                entry.ray_job.as_ref().map(|ray_job| &ray_job.metadata),
+            ]
+            .into_iter()
+            .flatten()
+            .any(|metadata| metadata.run_id() == Some(run_id))
+        });
+    }
+
+    let now = time::OffsetDateTime::now_utc();
+
+    if output == OutputArg::WideJson {
+        let mut rows = map
+            .iter()
+            .map(|(name, Entry { job, ray_job, pods })| {
+                WideRow::new(
+                    name,
+                    job.as_ref(),
+                    ray_job.as_ref(),
+                    pods,
+                    &ray_cluster_name_to_pods,
+                )
+            })
+            .collect::<Vec<_>>();
+        rows.sort_by(|a, b| a.created.cmp(&b.created).reverse());
+        if let Some(max_age) = max_age {
+            retain_recent(&mut rows, now, max_age, |row| row.finished);
         }
+        serde_json::to_writer_pretty(std::io::stdout().lock(), &rows)?;
+        println!();
+        return Ok(());
     }
 
-    let rows = {
+    // The Jobs that CronJobs create are listed in the table of Jobs, the CronJobs themselves are listed separately. They
+    // are fetched before anything is printed. Listing them is best-effort, so that the Jobs are still listed when the
+    // CronJobs cannot be, for example without permission to list them.
+    let mut cron_jobs = kubectl
+        .cron_jobs(kubectl::NAMESPACE)
+        .unwrap_or_else(|error| {
+            warn!("Unable to list the CronJobs: {error}");
+            Vec::new()
+        });
+    cron_jobs
+        .retain(|cron_job| run_id.is_none_or(|run_id| cron_job.metadata.run_id() == Some(run_id)));
+    cron_jobs.sort_by(|a, b| cmp_date_then_name(&a.metadata, &b.metadata));
+
+    let mut rows = {
         let mut rows: Vec<Row> = map
             .into_iter()
             .map(|(name, Entry { job, ray_job, pods })| -> Row {
@@ -133,6 +354,14 @@ pub fn list_jobs(context: &ClusterContext) -> Result<()> {
         rows
     };
 
+    let footer = max_age.and_then(|max_age| {
+        hidden_footer(
+            retain_recent(&mut rows, now, max_age, |row| row.finished),
+            max_age,
+            explicit_max_age,
+        )
+    });
+
     // The `Accessor` type and `accessor` function aid type inference. The type of an array is inferred from the first
     // element. Without the type annotation, the compiler treats the first element's accessor as a closure and not a
     // function pointer. Every closure compiles down to it's own unique type. The elements of an array must all be of
@@ -145,19 +374,6 @@ pub fn list_jobs(context: &ClusterContext) -> Result<()> {
         f
     }
 
-    fn format_date(value: time::OffsetDateTime) -> Result<String> {
-        let fd = time::macros::format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
-
-        Ok(value
-            .to_offset(UtcOffset::cached_local_offset())
-            .format(fd)?)
-    }
-
-    fn format_offset(value: time::UtcOffset) -> Result<String> {
-        let fd = time::macros::format_description!("[offset_hour sign:mandatory]:[offset_minute]");
-        Ok(value.format(fd)?)
-    }
-
     // The code below keeps column names together with a function that produces the value from the row data for that
     // column. Unfortunately, it does cause additional work. Perhaps some procedural macro machinery for defining table
     // row types with field annotations for headers and formatting implementations would be better.
@@ -191,6 +407,14 @@ pub fn list_jobs(context: &ClusterContext) -> Result<()> {
             }),
         ),
     ];
+    let mut columns = Vec::from(columns);
+    // Comments can be long, so they are only shown when asked for.
+    if wide {
+        columns.push((
+            "comment".to_string(),
+            accessor(|row| Ok(row.comment.as_deref().map(comment::display))),
+        ));
+    }
 
     let (column_names, accessors): (Vec<_>, Vec<_>) = columns.into_iter().unzip();
 
@@ -218,16 +442,82 @@ pub fn list_jobs(context: &ClusterContext) -> Result<()> {
     }
 
     println!("{table}");
+    if let Some(footer) = footer {
+        println!("{footer}");
+    }
+
+    if !cron_jobs.is_empty() {
+        println!("{}", cron_job_table(&cron_jobs)?);
+    }
 
     Ok(())
 }
 
+fn format_date(value: time::OffsetDateTime) -> Result<String> {
+    let fd = time::macros::format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+
+    Ok(value
+        .to_offset(UtcOffset::cached_local_offset())
+        .format(fd)?)
+}
+
+fn format_offset(value: time::UtcOffset) -> Result<String> {
+    let fd = time::macros::format_description!("[offset_hour sign:mandatory]:[offset_minute]");
+    Ok(value.format(fd)?)
+}
+
+/// Renders the CronJobs with their schedule and when they last created a Job.
+fn cron_job_table(cron_jobs: &[kubectl::CronJob]) -> Result<String> {
+    let mut table = comfy_table::Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .set_header(
+            [
+                "CronJob".to_owned(),
+                "schedule".to_owned(),
+                format!(
+                    "last run ({})",
+                    format_offset(UtcOffset::cached_local_offset())?
+                ),
+                "status".to_owned(),
+                "launched by".to_owned(),
+            ]
+            .map(|name| comfy_table::Cell::new(name).add_attribute(comfy_table::Attribute::Bold)),
+        );
+    for cron_job in cron_jobs {
+        let last_run = match cron_job.status.last_schedule_time {
+            Some(time) => format_date(time)?,
+            None => "never".to_owned(),
+        };
+        let status = match (cron_job.spec.suspend, cron_job.status.active.len()) {
+            (true, _) => "suspended",
+            (false, 0) => "scheduled",
+            (false, _) => "running",
+        };
+        let user = launched_by(std::iter::once(&cron_job.metadata))
+            .and_then(|user| user.split('@').next())
+            .unwrap_or_default();
+        table.add_row([
+            cron_job.metadata.name.as_str(),
+            cron_job.spec.schedule.as_str(),
+            &last_run,
+            status,
+            user,
+        ]);
+    }
+    Ok(table.to_string())
+}
+
 struct Row {
     name: String,
     created: time::OffsetDateTime,
+    /// When the Job or RayJob finished, if it did.
+    finished: Option<time::OffsetDateTime>,
     job_status: Option<String>,
     ray_job_status: Option<String>,
     user: Option<String>,
+    comment: Option<String>,
 }
 
 impl Row {
@@ -238,24 +528,18 @@ impl Row {
         pods: Vec<kubectl::Pod>,
         ray_cluster_name_to_pods: &HashMap<String, Vec<kubectl::Pod>>,
     ) -> Self {
+        let created = created(job.as_ref(), ray_job.as_ref(), &pods);
         Self {
-            created: match (&job, &ray_job) {
-                (Some(job), Some(ray_job)) => job
-                    .metadata
-                    .creation_timestamp
-                    .min(ray_job.metadata.creation_timestamp),
-                (Some(job), None) => job.metadata.creation_timestamp,
-                (None, Some(ray_job)) => ray_job.metadata.creation_timestamp,
-                (None, None) => pods
-                    .first()
-                    .map(|pod| pod.metadata.creation_timestamp)
-                    .unwrap_or_else(|| {
-                        unreachable!(
-                        "each entry in the hashmap should contain at least one Job, RayJob or Pod."
-                    )
-                    }),
-            },
+            created,
+            finished: finished(job.as_ref(), ray_job.as_ref(), created),
             user: determine_user(job.as_ref(), ray_job.as_ref()).map(str::to_string),
+            comment: Option::or(
+                job.as_ref().and_then(|job| job.metadata.comment()),
+                ray_job
+                    .as_ref()
+                    .and_then(|ray_job| ray_job.metadata.comment()),
+            )
+            .map(str::to_string),
             job_status: job.map(|job| {
                 let mut out = String::new();
                 for condition in &job.status.conditions {
@@ -321,6 +605,124 @@ impl Row {
     }
 }
 
+/// Returns when the Job or RayJob finished, or `None` if it has not. A RayJob is finished once its deployment is, so the
+/// RayJob takes precedence over its submitter Job. Falls back to `created` when the time of finishing is not known.
+fn finished(
+    job: Option<&kubectl::Job>,
+    ray_job: Option<&kubectl::RayJob>,
+    created: time::OffsetDateTime,
+) -> Option<time::OffsetDateTime> {
+    if let Some(ray_job) = ray_job {
+        return matches!(
+            ray_job.status.job_deployment_status.as_str(),
+            "Complete" | "Failed"
+        )
+        .then(|| ray_job.status.end_time.unwrap_or(created));
+    }
+    let job = job?;
+    let condition = job.status.conditions.iter().find(|condition| {
+        condition.status
+            && matches!(
+                condition.r#type,
+                kubectl::JobConditionType::Complete | kubectl::JobConditionType::Failed
+            )
+    })?;
+    Some(
+        condition
+            .last_transition_time
+            .or(job.status.completion_time)
+            .unwrap_or(created),
+    )
+}
+
+fn created(
+    job: Option<&kubectl::Job>,
+    ray_job: Option<&kubectl::RayJob>,
+    pods: &[kubectl::Pod],
+) -> time::OffsetDateTime {
+    match (job, ray_job) {
+        (Some(job), Some(ray_job)) => job
+            .metadata
+            .creation_timestamp
+            .min(ray_job.metadata.creation_timestamp),
+        (Some(job), None) => job.metadata.creation_timestamp,
+        (None, Some(ray_job)) => ray_job.metadata.creation_timestamp,
+        (None, None) => pods
+            .first()
+            .map(|pod| pod.metadata.creation_timestamp)
+            .unwrap_or_else(|| {
+                unreachable!(
+                    "each entry in the hashmap should contain at least one Job, RayJob or Pod."
+                )
+            }),
+    }
+}
+
+/// A row of the `wide-json` output. Field names are camelCase to match the kubernetes resources they come from.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WideRow<'a> {
+    name: &'a str,
+    #[serde(with = "time::serde::rfc3339")]
+    created: time::OffsetDateTime,
+    #[serde(skip)]
+    finished: Option<time::OffsetDateTime>,
+    job_status: Option<&'a kubectl::JobStatus>,
+    ray_job_status: Option<&'a kubectl::RayJobStatus>,
+    /// The Pods of the Job or of the RayCluster of the RayJob.
+    pods: Vec<WidePod<'a>>,
+    /// The annotations of the Job and RayJob that launch sets, such as who launched it.
+    annotations: BTreeMap<&'a str, &'a str>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WidePod<'a> {
+    name: &'a str,
+    status: &'a kubectl::PodStatus,
+}
+
+impl<'a> WideRow<'a> {
+    fn new(
+        name: &'a str,
+        job: Option<&'a kubectl::Job>,
+        ray_job: Option<&'a kubectl::RayJob>,
+        pods: &'a [kubectl::Pod],
+        ray_cluster_name_to_pods: &'a HashMap<String, Vec<kubectl::Pod>>,
+    ) -> Self {
+        let ray_cluster_pods = ray_job
+            .and_then(|ray_job| ray_job.status.ray_cluster_name.as_deref())
+            .and_then(|name| ray_cluster_name_to_pods.get(name))
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        let created = created(job, ray_job, pods);
+        Self {
+            name,
+            created,
+            finished: finished(job, ray_job, created),
+            job_status: job.map(|job| &job.status),
+            ray_job_status: ray_job.map(|ray_job| &ray_job.status),
+            pods: pods
+                .iter()
+                .chain(ray_cluster_pods)
+                .map(|pod| WidePod {
+                    name: &pod.metadata.name,
+                    status: &pod.status,
+                })
+                .collect(),
+            annotations: job
+                .map(|job| &job.metadata)
+                .into_iter()
+                .chain(ray_job.map(|ray_job| &ray_job.metadata))
+                .flat_map(|metadata| &metadata.annotations)
+                .filter(|(key, _)| key.starts_with(kubectl::annotation::PREFIX))
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect(),
+        }
+    }
+}
+
 fn append_job_condition(out: &mut String, condition: &kubectl::JobCondition) {
     if !out.is_empty() {
         out.push('\n');
@@ -402,16 +804,18 @@ fn determine_user<'a>(
 ) -> Option<&'a str> {
     let job_meta = job.as_ref().map(|job| &job.metadata);
     let ray_job_meta = ray_job.as_ref().map(|ray_job| &ray_job.metadata);
+    launched_by([job_meta, ray_job_meta].into_iter().flatten())
+}
 
-    let machine_user_host = Option::or(
-        job_meta.and_then(super::common::launched_by_machine_user),
-        ray_job_meta.and_then(super::common::launched_by_machine_user),
-    );
-
-    let tailscale_user_host = Option::or(
-        job_meta.and_then(super::common::launched_by_tailscale_user),
-        ray_job_meta.and_then(super::common::launched_by_tailscale_user),
-    );
+/// Returns the user that launched the resource from the annotations of the first of `metadata` that has them.
+fn launched_by<'a>(
+    mut metadata: impl Iterator<Item = &'a kubectl::ResourceMetadata> + Clone,
+) -> Option<&'a str> {
+    let machine_user_host = metadata
+        .clone()
+        .find_map(kubectl::ResourceMetadata::launched_by_machine_user);
+    let tailscale_user_host =
+        metadata.find_map(kubectl::ResourceMetadata::launched_by_tailscale_user);
 
     tailscale_user_host
         .and_then(|value| value.host().is_some().then_some(value.user()))
@@ -479,3 +883,179 @@ pub fn list_nodes(context: &ClusterContext) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn row(name: &str, finished: Option<time::OffsetDateTime>) -> Row {
+        Row {
+            name: name.to_owned(),
+            created: datetime!(2025-01-01 00:00 UTC),
+            finished,
+            job_status: None,
+            ray_job_status: None,
+            user: None,
+            comment: None,
+        }
+    }
+
+    fn names(rows: &[Row]) -> Vec<&str> {
+        rows.iter().map(|row| row.name.as_str()).collect()
+    }
+
+    #[test]
+    fn max_age_works() {
+        assert_eq!("2d".parse(), Ok(MaxAge::DEFAULT));
+        assert_eq!("48h".parse(), Ok(MaxAge::DEFAULT));
+        assert_eq!(MaxAge::DEFAULT.to_string(), "2d");
+        assert_eq!("36h".parse::<MaxAge>().unwrap().to_string(), "36h");
+        assert_eq!("90s".parse::<MaxAge>().unwrap().to_string(), "90s");
+        assert!("2".parse::<MaxAge>().is_err());
+        assert!("d".parse::<MaxAge>().is_err());
+        assert!("2w".parse::<MaxAge>().is_err());
+        assert!("-2d".parse::<MaxAge>().is_err());
+        assert_eq!(
+            "999999999999999d".parse::<MaxAge>(),
+            Err("the age \"999999999999999d\" is too large".to_owned())
+        );
+        assert!("99999999999999999999s".parse::<MaxAge>().is_err());
+    }
+
+    #[test]
+    fn retain_recent_works() {
+        let now = datetime!(2025-01-10 12:00 UTC);
+        let mut rows = vec![
+            row("running", None),
+            row(
+                "finished-an-hour-ago",
+                Some(datetime!(2025-01-10 11:00 UTC)),
+            ),
+            row(
+                "finished-exactly-2d-ago",
+                Some(datetime!(2025-01-08 12:00 UTC)),
+            ),
+            row("finished-3d-ago", Some(datetime!(2025-01-07 12:00 UTC))),
+            row("finished-a-week-ago", Some(datetime!(2025-01-03 12:00 UTC))),
+        ];
+
+        let hidden = retain_recent(&mut rows, now, MaxAge::DEFAULT, |row| row.finished);
+        assert_eq!(hidden, 2);
+        assert_eq!(
+            names(&rows),
+            ["running", "finished-an-hour-ago", "finished-exactly-2d-ago"]
+        );
+
+        let hidden = retain_recent(&mut rows, now, "30m".parse().unwrap(), |row| row.finished);
+        assert_eq!(hidden, 2);
+        assert_eq!(names(&rows), ["running"]);
+    }
+
+    #[test]
+    fn hidden_footer_works() {
+        assert_eq!(hidden_footer(0, MaxAge::DEFAULT, false), None);
+        assert_eq!(
+            hidden_footer(1, MaxAge::DEFAULT, false).as_deref(),
+            Some("+ 1 finished job older than 2d hidden (use --all)")
+        );
+        assert_eq!(
+            hidden_footer(312, MaxAge::DEFAULT, false).as_deref(),
+            Some("+ 312 finished jobs older than 2d hidden (use --all)")
+        );
+        assert_eq!(
+            hidden_footer(3, "12h".parse().unwrap(), true).as_deref(),
+            Some("+ 3 finished jobs older than 12h hidden (use a larger --max-age)")
+        );
+    }
+
+    #[test]
+    fn finished_works() {
+        let created = datetime!(2025-01-01 00:00 UTC);
+        let job = |status: serde_json::Value| -> kubectl::Job {
+            serde_json::from_value(serde_json::json!({
+                "metadata": {
+                    "name": "job-abc",
+                    "namespace": "launch",
+                    "creationTimestamp": "2025-01-01T00:00:00Z",
+                },
+                "status": status,
+            }))
+            .unwrap()
+        };
+
+        let running = job(serde_json::json!({ "active": 1 }));
+        assert_eq!(finished(Some(&running), None, created), None);
+
+        let complete = job(serde_json::json!({
+            "completionTime": "2025-01-02T00:00:00Z",
+            "conditions": [
+                {
+                    "lastTransitionTime": "2025-01-02T00:00:01Z",
+                    "status": "True",
+                    "type": "Complete"
+                }
+            ],
+            "succeeded": 1
+        }));
+        assert_eq!(
+            finished(Some(&complete), None, created),
+            Some(datetime!(2025-01-02 00:00:01 UTC))
+        );
+
+        let failed = job(serde_json::json!({
+            "conditions": [
+                {
+                    "status": "True",
+                    "type": "Failed"
+                }
+            ],
+            "failed": 1
+        }));
+        assert_eq!(finished(Some(&failed), None, created), Some(created));
+    }
+
+    #[test]
+    fn cron_job_table_works() {
+        let cron_job = |name: &str, spec: serde_json::Value, status: serde_json::Value| {
+            serde_json::from_value::<kubectl::CronJob>(serde_json::json!({
+                "metadata": {
+                    "name": name,
+                    "namespace": "launch",
+                    "creationTimestamp": "2025-01-01T00:00:00Z",
+                    "annotations": {
+                        kubectl::annotation::LAUNCHED_BY_MACHINE_USER: "alice@workstation",
+                    },
+                },
+                "spec": spec,
+                "status": status,
+            }))
+            .unwrap()
+        };
+        let table = cron_job_table(&[
+            cron_job(
+                "cron-job-alice-x7k2p",
+                serde_json::json!({ "schedule": "0 3 * * *" }),
+                serde_json::json!({}),
+            ),
+            cron_job(
+                "cron-job-alice-q9w4z",
+                serde_json::json!({ "schedule": "@hourly", "suspend": true }),
+                serde_json::json!({ "lastScheduleTime": "2025-01-02T03:00:00Z" }),
+            ),
+        ])
+        .unwrap();
+        let lines = table.lines().collect::<Vec<_>>();
+        let line = |name: &str| *lines.iter().find(|line| line.contains(name)).unwrap();
+
+        let first = line("cron-job-alice-x7k2p");
+        for value in ["0 3 * * *", "never", "scheduled", "alice"] {
+            assert!(first.contains(value), "{table}");
+        }
+        let second = line("cron-job-alice-q9w4z");
+        for value in ["@hourly", "2025-01-0", "suspended"] {
+            assert!(second.contains(value), "{table}");
+        }
+    }
+}
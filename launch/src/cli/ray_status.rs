@@ -0,0 +1,308 @@
+use std::{collections::BTreeMap, fmt::Write as _};
+
+use clap::Args;
+use log::debug;
+
+use super::{comment, config, history, ClusterContext};
+use crate::{
+    kubectl::{self, NAMESPACE},
+    ray_dashboard::{self, JobDetails, StateCounts},
+    Result,
+};
+
+#[derive(Debug, Args)]
+pub struct RayStatusArgs {
+    /// The name of the RayJob, which is the name of its submitter Job.
+    pub name: String,
+}
+
+pub fn ray_status(context: &ClusterContext, args: RayStatusArgs) -> Result<()> {
+    let RayStatusArgs { name } = args;
+    let kubectl = context.kubectl();
+
+    let ray_job = kubectl.try_get_ray_job(NAMESPACE, &name)?.ok_or_else(|| {
+        history::not_found(
+            context,
+            &name,
+            format!("RayJob {NAMESPACE}/{name} does not exist"),
+        )
+    })?;
+    let submission_id = kubectl::ray_job_submission_id(&ray_job)
+        .ok_or_else(|| format!("RayJob {NAMESPACE}/{name} has no submission ID yet"))?;
+    let ray_cluster_name = kubectl::ray_cluster_name(&ray_job)
+        .ok_or_else(|| format!("RayJob {NAMESPACE}/{name} has no RayCluster yet"))?;
+    let head_pod = kubectl
+        .pods_with_selector(NAMESPACE, &kubectl::head_pod_selector(ray_cluster_name))?
+        .pop()
+        .ok_or_else(|| format!("RayCluster {NAMESPACE}/{ray_cluster_name} has no head Pod, the Ray dashboard is gone once the RayCluster has been deleted"))?;
+
+    let worker_groups = worker_groups(
+        &kubectl.pods_with_selector(NAMESPACE, &kubectl::worker_pod_selector(ray_cluster_name))?,
+    );
+
+    let port_forward =
+        kubectl.port_forward(NAMESPACE, &head_pod.metadata.name, ray_dashboard::PORT)?;
+    debug!(
+        "Forwarding 127.0.0.1:{} to the Ray dashboard of Pod {NAMESPACE}/{}",
+        port_forward.local_port, head_pod.metadata.name
+    );
+
+    let http = config::http()?;
+    let client = ray_dashboard::Client::new(
+        &http,
+        format!("http://127.0.0.1:{}", port_forward.local_port),
+    );
+    let details = client.job(submission_id)?;
+    let counts = match details.job_id.as_deref() {
+        Some(job_id) => Some((client.tasks(job_id)?, client.actors(job_id)?)),
+        None => None,
+    };
+
+    print!(
+        "{}",
+        render(
+            &name,
+            submission_id,
+            &details,
+            counts.as_ref(),
+            &worker_groups,
+            ray_job["metadata"]["annotations"][kubectl::annotation::COMMENT].as_str(),
+        )
+    );
+
+    Ok(())
+}
+
+/// The worker Pods of a worker group and how often their containers have restarted, for example after running out of
+/// memory.
+#[derive(Debug, PartialEq, Eq)]
+struct WorkerGroup {
+    name: String,
+    pods: usize,
+    restarts: u32,
+}
+
+/// Aggregates the worker Pods by their worker group, in the order of the group names.
+fn worker_groups(pods: &[kubectl::Pod]) -> Vec<WorkerGroup> {
+    let mut groups = BTreeMap::<&str, (usize, u32)>::new();
+    for pod in pods {
+        let name = pod
+            .metadata
+            .labels
+            .get(kubectl::WORKER_GROUP_LABEL)
+            .map_or("unknown", String::as_str);
+        let (pods, restarts) = groups.entry(name).or_default();
+        *pods += 1;
+        *restarts += pod
+            .status
+            .container_statuses
+            .iter()
+            .map(|status| status.restart_count)
+            .sum::<u32>();
+    }
+    groups
+        .into_iter()
+        .map(|(name, (pods, restarts))| WorkerGroup {
+            name: name.to_owned(),
+            pods,
+            restarts,
+        })
+        .collect()
+}
+
+/// Renders the status with one `key: value` line per property. The counts are missing when the driver has not started.
+fn render(
+    name: &str,
+    submission_id: &str,
+    details: &JobDetails,
+    counts: Option<&(StateCounts, StateCounts)>,
+    worker_groups: &[WorkerGroup],
+    comment: Option<&str>,
+) -> String {
+    let mut lines = vec![
+        ("RayJob", format!("{NAMESPACE}/{name}")),
+        ("submission ID", submission_id.to_owned()),
+    ];
+    if let Some(job_id) = &details.job_id {
+        lines.push(("job ID", job_id.clone()));
+    }
+    let status = match &details.error_type {
+        Some(error_type) => format!("{} ({error_type})", details.status),
+        None => details.status.clone(),
+    };
+    lines.push(("status", status));
+    if let Some(message) = details.message.as_deref().filter(|value| !value.is_empty()) {
+        lines.push(("message", message.to_owned()));
+    }
+    if let (Some(start_time), Some(end_time)) = (details.start_time, details.end_time) {
+        lines.push((
+            "duration",
+            format!("{}s", end_time.saturating_sub(start_time) / 1000),
+        ));
+    }
+    if let Some((tasks, actors)) = counts {
+        lines.push(("tasks", tasks.to_string()));
+        lines.push(("actors", actors.to_string()));
+    }
+    if !worker_groups.is_empty() {
+        let groups = worker_groups
+            .iter()
+            .map(|group| {
+                format!(
+                    "{} ({} Pods, {} restarts)",
+                    group.name, group.pods, group.restarts
+                )
+            })
+            .collect::<Vec<_>>();
+        lines.push(("workers", groups.join(", ")));
+    }
+    if let Some(comment) = comment.filter(|value| !value.is_empty()) {
+        lines.push(("comment", comment::display(comment)));
+    }
+
+    let width = lines.iter().map(|(key, _)| key.len()).max().unwrap_or(0) + 1;
+    let mut output = String::new();
+    for (key, value) in lines {
+        writeln!(output, "{:<width$} {value}", format!("{key}:")).unwrap();
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn render_works() {
+        let details = JobDetails {
+            job_id: Some("02000000".to_owned()),
+            submission_id: Some("ray-job-abc-x7k2p".to_owned()),
+            status: "FAILED".to_owned(),
+            message: Some("Job entrypoint command failed with exit code 1".to_owned()),
+            error_type: Some("JOB_ENTRYPOINT_COMMAND_ERROR".to_owned()),
+            start_time: Some(1736763631000),
+            end_time: Some(1736763751500),
+        };
+        let counts = |states: &[(&str, usize)]| StateCounts {
+            by_state: states
+                .iter()
+                .map(|&(state, count)| (state.to_owned(), count))
+                .collect::<BTreeMap<_, _>>(),
+            truncated: false,
+        };
+        assert_eq!(
+            render(
+                "ray-job-abc",
+                "ray-job-abc-x7k2p",
+                &details,
+                Some(&(
+                    counts(&[("FAILED", 1), ("FINISHED", 40)]),
+                    counts(&[("DEAD", 2)])
+                )),
+                &[WorkerGroup {
+                    name: "small-group".to_owned(),
+                    pods: 2,
+                    restarts: 3,
+                }],
+                Some("sweep lr\nwith warmup 🧪"),
+            ),
+            "\
+RayJob:        launch/ray-job-abc
+submission ID: ray-job-abc-x7k2p
+job ID:        02000000
+status:        FAILED (JOB_ENTRYPOINT_COMMAND_ERROR)
+message:       Job entrypoint command failed with exit code 1
+duration:      120s
+tasks:         41 (FAILED 1, FINISHED 40)
+actors:        2 (DEAD 2)
+workers:       small-group (2 Pods, 3 restarts)
+comment:       sweep lr\\nwith warmup 🧪
+"
+        );
+
+        let details = JobDetails {
+            job_id: None,
+            status: "PENDING".to_owned(),
+            message: None,
+            error_type: None,
+            start_time: None,
+            end_time: None,
+            ..details
+        };
+        assert_eq!(
+            render(
+                "ray-job-abc",
+                "ray-job-abc-x7k2p",
+                &details,
+                None,
+                &[],
+                None
+            ),
+            "\
+RayJob:        launch/ray-job-abc
+submission ID: ray-job-abc-x7k2p
+status:        PENDING
+"
+        );
+    }
+
+    #[test]
+    fn worker_groups_sum_restarts() {
+        let pod = |name: &str, group: Option<&str>, restart_counts: &[u32]| -> kubectl::Pod {
+            let mut labels = serde_json::json!({ "ray.io/node-type": "worker" });
+            if let Some(group) = group {
+                labels[kubectl::WORKER_GROUP_LABEL] = group.into();
+            }
+            serde_json::from_value(serde_json::json!({
+                "metadata": {
+                    "name": name,
+                    "namespace": "launch",
+                    "creationTimestamp": "2025-01-13T10:20:31Z",
+                    "labels": labels,
+                },
+                "status": {
+                    "phase": "Running",
+                    "containerStatuses": restart_counts.iter().map(|&restart_count| serde_json::json!({
+                        "name": "ray-worker",
+                        "image": "registry/launch:latest",
+                        "imageID": "registry/launch@sha256:0123",
+                        "state": { "running": { "startedAt": "2025-01-13T10:25:31Z" } },
+                        "restartCount": restart_count,
+                    })).collect::<Vec<_>>(),
+                },
+            }))
+            .unwrap()
+        };
+
+        assert_eq!(
+            worker_groups(&[
+                pod("small-group-worker-a", Some("small-group"), &[2]),
+                pod("gpu-group-worker-a", Some("gpu-group"), &[0]),
+                pod("small-group-worker-b", Some("small-group"), &[1, 4]),
+                // Pending Pods have no container statuses yet.
+                pod("small-group-worker-c", Some("small-group"), &[]),
+                pod("worker-d", None, &[1]),
+            ]),
+            [
+                WorkerGroup {
+                    name: "gpu-group".to_owned(),
+                    pods: 1,
+                    restarts: 0,
+                },
+                WorkerGroup {
+                    name: "small-group".to_owned(),
+                    pods: 3,
+                    restarts: 7,
+                },
+                WorkerGroup {
+                    name: "unknown".to_owned(),
+                    pods: 1,
+                    restarts: 1,
+                },
+            ]
+        );
+        assert_eq!(worker_groups(&[]), []);
+    }
+}
@@ -0,0 +1,165 @@
+//! Settings that are shared by everyone working in a repository through `.launch.toml` at the root of the git
+//! repository. Unlike the per-user configuration in `~/.config/launch/<context>.yaml`, this file is meant to be
+//! committed.
+
+use std::path::{Path, PathBuf};
+
+use super::{
+    submit::{expect_name_prefix, BuilderArg},
+    ClusterContext,
+};
+use crate::{
+    databricks, git,
+    unit::bytes::{self, Bytes},
+    Result,
+};
+
+pub const FILE_NAME: &str = ".launch.toml";
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RepoConfig {
+    /// The context that the repository works with when `--context` is not passed.
+    pub context: Option<ClusterContext>,
+    /// Defaults for the flags of `launch submit`.
+    pub submit: Option<SubmitDefaults>,
+}
+
+/// The keys mirror the flags of `launch submit` and are overridden by them.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SubmitDefaults {
+    pub builder: Option<BuilderArg>,
+    pub gpus: Option<u32>,
+    /// In gibibyte, like `--gpu-mem`.
+    pub gpu_mem: Option<u64>,
+    pub workers: Option<u32>,
+    pub name_prefix: Option<String>,
+    pub databrickscfg_mode: Option<databricks::Mode>,
+}
+
+impl RepoConfig {
+    /// Returns the path of the configuration file of the git repository of the working directory.
+    pub fn path() -> Result<PathBuf> {
+        Ok(git::git_dir()?.join(FILE_NAME))
+    }
+
+    /// Returns `Ok(None)` when the file does not exist.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(format!("Failed to read {path:?}: {error}").into()),
+        };
+        Self::parse(&contents)
+            .map(Some)
+            .map_err(|error| format!("Failed to parse {path:?}: {error}").into())
+    }
+
+    fn parse(contents: &str) -> Result<Self, String> {
+        let config: Self = toml::from_str(contents).map_err(|error| error.to_string())?;
+        if let Some(submit) = &config.submit {
+            submit.validate()?;
+        }
+        Ok(config)
+    }
+}
+
+impl SubmitDefaults {
+    pub fn gpu_mem(&self) -> Option<Bytes> {
+        self.gpu_mem.and_then(Bytes::new::<bytes::gibibyte>)
+    }
+
+    /// Applies the validation of the corresponding flags.
+    fn validate(&self) -> Result<(), String> {
+        if self.workers == Some(0) {
+            return Err("`submit.workers` must be at least 1".to_owned());
+        }
+        if let Some(gpu_mem) = self.gpu_mem {
+            if Bytes::new::<bytes::gibibyte>(gpu_mem).is_none() {
+                return Err(format!("`submit.gpu_mem` {gpu_mem} is too large"));
+            }
+        }
+        if let Some(name_prefix) = &self.name_prefix {
+            expect_name_prefix(name_prefix).map_err(|error| {
+                format!("invalid `submit.name_prefix` {name_prefix:?}: {error}")
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Loads the `[submit]` table of the configuration file of the git repository of the working directory, along with the
+/// path of the file.
+pub fn submit_defaults() -> Result<Option<(PathBuf, SubmitDefaults)>> {
+    let path = RepoConfig::path()?;
+    let submit = RepoConfig::load(&path)?.and_then(|config| config.submit);
+    Ok(submit.map(|submit| (path, submit)))
+}
+
+/// Loads the `context` of the configuration file of the git repository of the working directory, along with the path of
+/// the file. Outside of a git repository there is no configuration file.
+pub fn context() -> Result<Option<(PathBuf, ClusterContext)>> {
+    let Ok(path) = RepoConfig::path() else {
+        return Ok(None);
+    };
+    let context = RepoConfig::load(&path)?.and_then(|config| config.context);
+    Ok(context.map(|context| (path, context)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_works() {
+        let config = RepoConfig::parse(
+            r#"
+context = "voltage-park"
+
+[submit]
+builder = "kaniko"
+gpus = 8
+gpu_mem = 80
+workers = 4
+name_prefix = "llm"
+databrickscfg_mode = "omit"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.context, Some(ClusterContext::VoltagePark));
+        assert_eq!(
+            config.submit,
+            Some(SubmitDefaults {
+                builder: Some(BuilderArg::Kaniko),
+                gpus: Some(8),
+                gpu_mem: Some(80),
+                workers: Some(4),
+                name_prefix: Some("llm".to_owned()),
+                databrickscfg_mode: Some(databricks::Mode::Omit),
+            })
+        );
+
+        assert_eq!(RepoConfig::parse("").unwrap().submit, None);
+        assert_eq!(
+            RepoConfig::parse("[submit]\n").unwrap().submit,
+            Some(SubmitDefaults::default())
+        );
+    }
+
+    #[test]
+    fn parse_rejects_invalid_values() {
+        for contents in [
+            "[submit]\ngpu = 8\n",
+            "[submit]\nbuilder = \"podman\"\n",
+            "[submit]\nworkers = 0\n",
+            "[submit]\ngpu_mem = 17179869184\n",
+            "[submit]\nname_prefix = \"LLM\"\n",
+            "[submit]\nname_prefix = \"a-name-prefix-that-is-too-long\"\n",
+            "[other]\n",
+            "context = \"moon\"\n",
+        ] {
+            assert!(RepoConfig::parse(contents).is_err(), "{contents:?}");
+        }
+    }
+}
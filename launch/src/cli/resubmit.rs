@@ -0,0 +1,581 @@
+//! Submits a copy of a Job, RayJob or Experiment without building an image or resolving the settings again. The copy
+//! is the resource as the cluster has it, without the fields that the server populates, and with the launch
+//! annotations of the current user and a new run ID.
+
+use clap::Args;
+use log::info;
+use serde_json::{Map, Value};
+
+use super::{history, ClusterContext};
+use crate::{
+    executor::{self, ResourceKind, RUN_ID_ENV},
+    kubectl::{self, annotation, label, Kubectl},
+    user_host::{self, UserHost},
+    Result,
+};
+
+#[derive(Debug, Args)]
+pub struct ResubmitArgs {
+    /// The name of the Job, RayJob or Experiment to submit again.
+    pub name: String,
+
+    /// Resubmit an Experiment, which runs all of its trials again.
+    #[arg(long = "confirm-experiment")]
+    pub confirm_experiment: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Kind {
+    Job,
+    RayJob,
+    Experiment,
+}
+
+impl Kind {
+    pub(super) fn as_str(self) -> &'static str {
+        match self {
+            Kind::Job => "Job",
+            Kind::RayJob => "RayJob",
+            Kind::Experiment => "Experiment",
+        }
+    }
+
+    /// The resource type as passed to kubectl.
+    fn resource(self) -> &'static str {
+        match self {
+            Kind::Job => "job",
+            Kind::RayJob => "rayjob",
+            Kind::Experiment => "experiment",
+        }
+    }
+
+    /// The kind that can be awaited, see [`ResourceKind`].
+    pub(super) fn resource_kind(self) -> ResourceKind {
+        match self {
+            Kind::Job | Kind::RayJob => ResourceKind::Job,
+            Kind::Experiment => ResourceKind::Experiment,
+        }
+    }
+}
+
+/// The fields of `metadata` that the server populates and that a new resource must not have.
+const SERVER_METADATA_FIELDS: [&str; 9] = [
+    "name",
+    "uid",
+    "resourceVersion",
+    "creationTimestamp",
+    "deletionTimestamp",
+    "deletionGracePeriodSeconds",
+    "generation",
+    "managedFields",
+    "finalizers",
+];
+
+/// The labels that the Job controller adds to the Pod template, which it rejects on a new Job because they select the
+/// Pods of the original.
+const JOB_CONTROLLER_LABELS: [&str; 4] = [
+    "controller-uid",
+    "batch.kubernetes.io/controller-uid",
+    "job-name",
+    "batch.kubernetes.io/job-name",
+];
+
+/// The annotations that describe the original submission and no longer apply to the copy.
+const STALE_ANNOTATIONS: [&str; 6] = [
+    annotation::K8S_SERVER_VERSION,
+    annotation::SCHEDULING_LATENCY_SECONDS,
+    annotation::PULL_LATENCY_SECONDS,
+    annotation::LOG_LATENCY_SECONDS,
+    annotation::GPU_PRODUCT,
+    annotation::OBSERVED_IMAGE_DIGEST,
+];
+
+/// Who resubmits and the run ID of the copy.
+struct Resubmission<'a> {
+    original: &'a str,
+    run_id: &'a str,
+    machine_user_host: &'a UserHost,
+    tailscale_user_host: Option<&'a UserHost>,
+}
+
+fn object<'a>(value: &'a mut Value, key: &str) -> Option<&'a mut Map<String, Value>> {
+    value.get_mut(key)?.as_object_mut()
+}
+
+/// Removes the fields that the server populates, so that the resource can be created again under a new name. Names are
+/// generated from the `generateName` of the original, or from its name when it had none.
+pub(super) fn sanitize(kind: Kind, resource: &mut Value) {
+    if let Some(resource) = resource.as_object_mut() {
+        resource.remove("status");
+    }
+    if let Some(metadata) = object(resource, "metadata") {
+        let generate_name = match metadata.get("generateName").and_then(Value::as_str) {
+            Some(generate_name) => generate_name.to_owned(),
+            None => format!(
+                "{}-",
+                metadata
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+            ),
+        };
+        for field in SERVER_METADATA_FIELDS {
+            metadata.remove(field);
+        }
+        metadata.insert("generateName".to_owned(), generate_name.into());
+    }
+    match kind {
+        Kind::Job => {
+            let Some(spec) = object(resource, "spec") else {
+                return;
+            };
+            // The selector is generated from the uid of the Job unless `manualSelector` is set.
+            if spec.get("manualSelector") != Some(&Value::Bool(true)) {
+                spec.remove("selector");
+            }
+            if let Some(labels) = spec
+                .get_mut("template")
+                .and_then(|template| object(template, "metadata"))
+                .and_then(|metadata| metadata.get_mut("labels"))
+                .and_then(Value::as_object_mut)
+            {
+                for label in JOB_CONTROLLER_LABELS {
+                    labels.remove(label);
+                }
+            }
+        }
+        // kuberay and katib fill in defaults, which are valid for a new resource too.
+        Kind::RayJob | Kind::Experiment => {}
+    }
+}
+
+/// Replaces the run ID wherever launch put it: the annotations and labels of the resource and its templates, and the
+/// environment of every container.
+fn refresh_run_id(value: &mut Value, run_id: &str) {
+    match value {
+        Value::Object(map) => {
+            for key in ["annotations", "labels"] {
+                if let Some(entries) = map.get_mut(key).and_then(Value::as_object_mut) {
+                    for key in [annotation::RUN_ID, label::RUN_ID] {
+                        if let Some(entry) = entries.get_mut(key) {
+                            *entry = run_id.into();
+                        }
+                    }
+                }
+            }
+            if map.get("name").and_then(Value::as_str) == Some(RUN_ID_ENV)
+                && map.contains_key("value")
+            {
+                map.insert("value".to_owned(), run_id.into());
+            }
+            map.values_mut()
+                .for_each(|value| refresh_run_id(value, run_id));
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| refresh_run_id(value, run_id)),
+        _ => {}
+    }
+}
+
+/// Replaces the launch annotations of the resource and of its Pod templates with those of the resubmission.
+fn refresh(resource: &mut Value, resubmission: &Resubmission) {
+    refresh_run_id(resource, resubmission.run_id);
+    let Some(metadata) = object(resource, "metadata") else {
+        return;
+    };
+    let annotations = metadata
+        .entry("annotations")
+        .or_insert_with(|| Value::Object(Map::new()));
+    if let Some(annotations) = annotations.as_object_mut() {
+        refresh_annotations(annotations, resubmission);
+    }
+    if let Some(spec) = resource.get_mut("spec") {
+        refresh_templates(spec, resubmission);
+    }
+}
+
+/// Refreshes the annotations of every metadata below `value` that carries launch annotations, which are those of the
+/// Pod templates of a Job, the trial spec of an Experiment and the head, worker and submitter Pods of a RayJob.
+fn refresh_templates(value: &mut Value, resubmission: &Resubmission) {
+    match value {
+        Value::Object(map) => {
+            if let Some(annotations) = map
+                .get_mut("metadata")
+                .and_then(|metadata| object(metadata, "annotations"))
+                .filter(|annotations| {
+                    annotations
+                        .keys()
+                        .any(|key| key.starts_with(annotation::PREFIX))
+                })
+            {
+                refresh_annotations(annotations, resubmission);
+            }
+            map.values_mut()
+                .for_each(|value| refresh_templates(value, resubmission));
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| refresh_templates(value, resubmission)),
+        _ => {}
+    }
+}
+
+fn refresh_annotations(annotations: &mut Map<String, Value>, resubmission: &Resubmission) {
+    for key in STALE_ANNOTATIONS {
+        annotations.remove(key);
+    }
+    annotations.remove(annotation::LAUNCHED_BY_TAILSCALE_USER);
+    annotations.extend(
+        [
+            (annotation::VERSION, crate::version::VERSION.to_owned()),
+            (
+                annotation::LAUNCHED_BY_MACHINE_USER,
+                resubmission.machine_user_host.to_string(),
+            ),
+            (annotation::RUN_ID, resubmission.run_id.to_owned()),
+            (
+                annotation::RESUBMITTED_FROM,
+                resubmission.original.to_owned(),
+            ),
+        ]
+        .into_iter()
+        .chain(
+            resubmission
+                .tailscale_user_host
+                .map(|value| (annotation::LAUNCHED_BY_TAILSCALE_USER, value.to_string())),
+        )
+        .map(|(key, value)| (key.to_owned(), value.into())),
+    );
+}
+
+/// Finds the resource named `name`. RayJobs are looked up first because their submitter Job has the same name.
+pub(super) fn find(
+    context: &ClusterContext,
+    kubectl: &Kubectl,
+    namespace: &str,
+    name: &str,
+) -> Result<(Kind, Value)> {
+    if let Some(ray_job) = kubectl.try_get_ray_job(namespace, name)? {
+        return Ok((Kind::RayJob, ray_job));
+    }
+    for kind in [Kind::Experiment, Kind::Job] {
+        if let Some(resource) = kubectl.try_get_json(kind.resource(), namespace, name)? {
+            return Ok((kind, resource));
+        }
+    }
+    Err(history::not_found(
+        context,
+        name,
+        format!("No Job, RayJob or Experiment named {name:?} exists in namespace {namespace:?}"),
+    )
+    .into())
+}
+
+pub fn resubmit(context: &ClusterContext, args: ResubmitArgs) -> Result<()> {
+    let ResubmitArgs {
+        name,
+        confirm_experiment,
+    } = args;
+
+    let kubectl = context.kubectl();
+    let namespace = kubectl::NAMESPACE;
+
+    let (kind, mut resource) = find(context, &kubectl, namespace, &name)?;
+    if kind == Kind::Experiment && !confirm_experiment {
+        return Err(format!(
+            "Resubmitting Experiment {name:?} runs all of its trials again. Pass `--confirm-experiment` to resubmit it anyway."
+        )
+        .into());
+    }
+
+    let run_id = executor::generate_run_id();
+    info!("Run ID: {run_id}");
+    let machine_user_host = user_host::machine_user_host();
+    let tailscale_user_host = user_host::tailscale_user_host();
+    sanitize(kind, &mut resource);
+    refresh(
+        &mut resource,
+        &Resubmission {
+            original: &name,
+            run_id: &run_id,
+            machine_user_host: &machine_user_host,
+            tailscale_user_host: tailscale_user_host.as_ref(),
+        },
+    );
+
+    let handle = kubectl.create_with_run_id(&serde_json::to_string(&resource)?, &run_id)?;
+    let resource_kind = kind.resource_kind();
+    history::record(&history::Entry::new(
+        context,
+        resource_kind,
+        &handle.namespace,
+        &handle.name,
+        &run_id,
+        // The copy keeps the comment annotation of the original.
+        resource["metadata"]["annotations"][annotation::COMMENT]
+            .as_str()
+            .map(str::to_owned),
+    ));
+    info!(
+        "Resubmitted {} {name:?} as {:?}: {}",
+        kind.as_str(),
+        handle.name,
+        resource_kind.url(context, &handle.namespace, &handle.name)
+    );
+    info!(
+        "Run `launch watch {}` to wait for it to finish",
+        handle.name
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use container_image_name::ImageName;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        executor::{
+            tests::{execution_args, RUN_ID},
+            ExecutionArgs, Executor, KatibExecutor, KubernetesExecutor, RayExecutor,
+        },
+        user_host::UserHostRef,
+    };
+
+    const NEW_RUN_ID: &str = "0191d6a4-1111-7000-8000-000000000000";
+
+    /// The resource as the cluster returns it after `executor` created it for bob: the spec of the submission, with the
+    /// fields and annotations that the server and the monitoring of launch add.
+    fn created(executor: &impl Executor, generate_name: &str, status: Value) -> Value {
+        let image = ImageName::builder("launch")
+            .with_registry("registry:5000")
+            .with_tag("latest")
+            .build()
+            .unwrap();
+        let tailscale_user_host = UserHost::parse("bob@astera.org");
+        let container_args = ["python".to_owned(), "train.py".to_owned()];
+        let args = ExecutionArgs {
+            generate_name,
+            machine_user_host: UserHostRef::parse("bob@desktop"),
+            tailscale_user_host: Some(tailscale_user_host.to_ref()),
+            container_args: &container_args,
+            server_version: Some("v1.31.0"),
+            ..execution_args(image.as_ref())
+        };
+        let mut resource = executor.spec(&args).unwrap();
+
+        let metadata = resource["metadata"].as_object_mut().unwrap();
+        metadata.extend(
+            json!({
+                "name": format!("{generate_name}x7k2p"),
+                "uid": "5d0c3c1e-8f64-4d5e-9a53-2f7f0c7d6a10",
+                "resourceVersion": "123456",
+                "creationTimestamp": "2025-01-13T12:00:00Z",
+                "generation": 1,
+                "managedFields": [{ "manager": "kubectl-create" }],
+                "finalizers": ["ray.io/rayjob-finalizer"],
+            })
+            .as_object()
+            .unwrap()
+            .clone(),
+        );
+        metadata["labels"]["team"] = "neuro".into();
+        let annotations = &mut metadata["annotations"];
+        annotations[annotation::SCHEDULING_LATENCY_SECONDS] = "5".into();
+        annotations["example.com/note"] = "keep me".into();
+        resource["status"] = status;
+        resource
+    }
+
+    fn job() -> Value {
+        let mut job = created(&KubernetesExecutor, "job-", json!({ "failed": 1 }));
+        job["spec"]["selector"] =
+            json!({ "matchLabels": { "batch.kubernetes.io/controller-uid": "5d0c3c1e" } });
+        let labels = &mut job["spec"]["template"]["metadata"]["labels"];
+        labels["controller-uid"] = "5d0c3c1e".into();
+        labels["batch.kubernetes.io/job-name"] = "job-x7k2p".into();
+        job
+    }
+
+    fn ray_job() -> Value {
+        created(
+            &RayExecutor,
+            "ray-job-",
+            json!({ "jobStatus": "FAILED", "rayClusterName": "ray-job-x7k2p-raycluster" }),
+        )
+    }
+
+    fn experiment() -> Value {
+        let experiment_spec_path = crate::temp_path::tmp_json_path();
+        std::fs::write(
+            &experiment_spec_path,
+            json!({
+                "objective": { "type": "maximize", "objectiveMetricName": "metric" },
+                "algorithm": { "algorithmName": "random" },
+                "parallelTrialCount": 2,
+                "maxTrialCount": 4,
+                "parameters": [{ "name": "lr", "parameterType": "double", "feasibleSpace": { "min": 0.01, "max": 0.1 } }],
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let executor = KatibExecutor {
+            experiment_spec_path,
+            progress: Default::default(),
+        };
+        let experiment = created(
+            &executor,
+            "katib-",
+            json!({ "conditions": [{ "type": "Succeeded" }] }),
+        );
+        std::fs::remove_file(&executor.experiment_spec_path).unwrap();
+        experiment
+    }
+
+    fn resubmit(kind: Kind, mut resource: Value) -> Value {
+        let machine_user_host = UserHost::parse("alice@laptop");
+        let original = resource["metadata"]["name"].as_str().unwrap().to_owned();
+        sanitize(kind, &mut resource);
+        refresh(
+            &mut resource,
+            &Resubmission {
+                original: &original,
+                run_id: NEW_RUN_ID,
+                machine_user_host: &machine_user_host,
+                tailscale_user_host: None,
+            },
+        );
+        // The resource is sent to kubectl as JSON.
+        serde_json::from_str(&serde_json::to_string(&resource).unwrap()).unwrap()
+    }
+
+    /// The launch annotations of the resubmission of `original`.
+    fn launch_annotations(original: &str) -> Value {
+        json!({
+            annotation::RUN_ID: NEW_RUN_ID,
+            annotation::VERSION: crate::version::VERSION,
+            annotation::LAUNCHED_BY_MACHINE_USER: "alice@laptop",
+            annotation::RESUBMITTED_FROM: original,
+            annotation::STRICT_GIT: "warn",
+            annotation::GIT_DIRTY: "false",
+            annotation::GIT_UNPUSHED: "false",
+        })
+    }
+
+    /// Asserts that the resource and each of the Pod templates at `templates`, given as JSON pointers, carry the
+    /// annotations of the resubmission.
+    fn assert_resubmitted(resource: &Value, generate_name: &str, templates: &[&str]) {
+        let original = format!("{generate_name}x7k2p");
+        assert_eq!(resource.get("status"), None);
+        let metadata = resource["metadata"].as_object().unwrap();
+        for field in SERVER_METADATA_FIELDS {
+            assert!(!metadata.contains_key(field), "{field} in {metadata:?}");
+        }
+        assert_eq!(metadata["generateName"], generate_name);
+        assert_eq!(metadata["namespace"], "launch");
+        assert_eq!(
+            metadata["labels"],
+            json!({ label::RUN_ID: NEW_RUN_ID, "team": "neuro" })
+        );
+        let mut annotations = launch_annotations(&original);
+        annotations["example.com/note"] = "keep me".into();
+        assert_eq!(metadata["annotations"], annotations);
+
+        for template in templates {
+            let template = resource
+                .pointer(template)
+                .unwrap_or_else(|| panic!("{template} is missing"));
+            assert_eq!(
+                template["metadata"]["annotations"],
+                launch_annotations(&original)
+            );
+            assert_eq!(template["metadata"]["labels"][label::RUN_ID], NEW_RUN_ID);
+        }
+
+        let rendered = resource.to_string();
+        for stale in [RUN_ID, "bob", "v1.31.0"] {
+            assert!(!rendered.contains(stale), "{stale} in {rendered}");
+        }
+        assert!(rendered.contains("train.py"), "{rendered}");
+    }
+
+    #[test]
+    fn resubmit_job() {
+        let original = job();
+        let resource = resubmit(Kind::Job, original.clone());
+        assert_resubmitted(&resource, "job-", &["/spec/template"]);
+        assert_eq!(resource["spec"].get("selector"), None);
+        assert_eq!(
+            resource["spec"]["backoffLimit"],
+            original["spec"]["backoffLimit"]
+        );
+        assert_eq!(
+            resource["spec"]["template"]["metadata"]["labels"],
+            json!({ label::RUN_ID: NEW_RUN_ID })
+        );
+        let env = &resource["spec"]["template"]["spec"]["containers"][0]["env"];
+        assert!(
+            env.as_array()
+                .unwrap()
+                .contains(&json!({ "name": RUN_ID_ENV, "value": NEW_RUN_ID })),
+            "{env}"
+        );
+    }
+
+    #[test]
+    fn resubmit_job_keeps_manual_selector() {
+        let mut job = job();
+        job["spec"]["manualSelector"] = true.into();
+        let resource = resubmit(Kind::Job, job);
+        assert!(resource["spec"].get("selector").is_some());
+    }
+
+    #[test]
+    fn resubmit_ray_job() {
+        let original = ray_job();
+        let resource = resubmit(Kind::RayJob, original.clone());
+        assert_resubmitted(
+            &resource,
+            "ray-job-",
+            &[
+                "/spec/rayClusterSpec/headGroupSpec/template",
+                "/spec/rayClusterSpec/workerGroupSpecs/0/template",
+                "/spec/submitterPodTemplate",
+            ],
+        );
+        assert_eq!(
+            resource["spec"]["entrypoint"],
+            original["spec"]["entrypoint"]
+        );
+        assert_eq!(
+            resource["spec"]["rayClusterSpec"]["workerGroupSpecs"][0]["replicas"],
+            original["spec"]["rayClusterSpec"]["workerGroupSpecs"][0]["replicas"]
+        );
+    }
+
+    #[test]
+    fn resubmit_experiment() {
+        let resource = resubmit(Kind::Experiment, experiment());
+        assert_resubmitted(
+            &resource,
+            "katib-",
+            &["/spec/trialTemplate/trialSpec/spec/template"],
+        );
+        assert_eq!(resource["spec"]["parallelTrialCount"], 2);
+        assert_eq!(resource["spec"]["parameters"][0]["name"], "lr");
+    }
+
+    #[test]
+    fn sanitize_without_generate_name() {
+        let mut resource = job();
+        resource["metadata"]
+            .as_object_mut()
+            .unwrap()
+            .remove("generateName");
+        sanitize(Kind::Job, &mut resource);
+        assert_eq!(resource["metadata"]["generateName"], "job-x7k2p-");
+        assert_eq!(resource["metadata"].get("name"), None);
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::BTreeMap;
+
+use clap::Args;
+use time::Duration;
+
+use super::{list::MaxAge, ClusterContext};
+use crate::{
+    executor::latency::Latencies,
+    kubectl::{self, annotation},
+    Result,
+};
+
+#[derive(Debug, Args)]
+pub struct StatsArgs {
+    /// Only include jobs created within this age, such as `7d` or `12h`. Jobs are deleted 7 days after they finish, so
+    /// older jobs are not available.
+    #[arg(long = "since", default_value = "7d")]
+    pub since: MaxAge,
+}
+
+pub fn stats(context: &ClusterContext, args: StatsArgs) -> Result<()> {
+    let StatsArgs { since } = args;
+
+    let kubectl = context.kubectl();
+    let now = time::OffsetDateTime::now_utc();
+
+    let samples = kubectl
+        .jobs(kubectl::NAMESPACE)?
+        .into_iter()
+        .filter(|job| now - job.metadata.creation_timestamp <= since.duration())
+        .filter_map(|job| {
+            let latencies = Latencies::from_annotations(&job.metadata.annotations)?;
+            let gpu_product = job
+                .metadata
+                .annotations
+                .get(annotation::GPU_PRODUCT)
+                .cloned();
+            Some((gpu_product, latencies))
+        });
+    let summaries = aggregate(samples);
+
+    if summaries.is_empty() {
+        println!("No jobs created within {since} have recorded latencies.");
+        return Ok(());
+    }
+
+    let mut table = comfy_table::Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .set_header(
+            [
+                "GPU",
+                "jobs",
+                "scheduling p50",
+                "scheduling p95",
+                "pull p50",
+                "pull p95",
+            ]
+            .into_iter()
+            .map(|name| comfy_table::Cell::new(name).add_attribute(comfy_table::Attribute::Bold)),
+        );
+    for (gpu_product, summary) in &summaries {
+        let format = |value: Option<Duration>| value.map(format_duration).unwrap_or_default();
+        table.add_row([
+            gpu_product.as_deref().unwrap_or("none").to_owned(),
+            summary.count.to_string(),
+            format(summary.scheduling.map(|value| value.p50)),
+            format(summary.scheduling.map(|value| value.p95)),
+            format(summary.pull.map(|value| value.p50)),
+            format(summary.pull.map(|value| value.p95)),
+        ]);
+    }
+    println!("{table}");
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Percentiles {
+    p50: Duration,
+    p95: Duration,
+}
+
+impl Percentiles {
+    /// Returns `None` when there are no values.
+    fn new(mut values: Vec<Duration>) -> Option<Self> {
+        values.sort_unstable();
+        Some(Self {
+            p50: percentile(&values, 50)?,
+            p95: percentile(&values, 95)?,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Summary {
+    /// The number of jobs, including those that lack some of the latencies.
+    count: usize,
+    scheduling: Option<Percentiles>,
+    pull: Option<Percentiles>,
+}
+
+/// Returns the `percent`th percentile of the sorted values using the nearest-rank method, which always returns one of
+/// the values.
+fn percentile(sorted: &[Duration], percent: usize) -> Option<Duration> {
+    let rank = (percent * sorted.len()).div_ceil(100).max(1);
+    sorted.get(rank - 1).copied()
+}
+
+/// Groups the latencies by GPU product. Jobs without GPUs are grouped under `None`.
+fn aggregate(
+    samples: impl IntoIterator<Item = (Option<String>, Latencies)>,
+) -> BTreeMap<Option<String>, Summary> {
+    let mut groups = BTreeMap::<_, Vec<Latencies>>::new();
+    for (gpu_product, latencies) in samples {
+        groups.entry(gpu_product).or_default().push(latencies);
+    }
+    groups
+        .into_iter()
+        .map(|(gpu_product, latencies)| {
+            let summary = Summary {
+                count: latencies.len(),
+                scheduling: Percentiles::new(
+                    latencies
+                        .iter()
+                        .filter_map(|value| value.scheduling)
+                        .collect(),
+                ),
+                pull: Percentiles::new(latencies.iter().filter_map(|value| value.pull).collect()),
+            };
+            (gpu_product, summary)
+        })
+        .collect()
+}
+
+fn format_duration(value: Duration) -> String {
+    let seconds = value.whole_seconds();
+    if seconds >= 60 {
+        format!("{}m {}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seconds(values: &[i64]) -> Vec<Duration> {
+        values.iter().copied().map(Duration::seconds).collect()
+    }
+
+    #[test]
+    fn percentile_works() {
+        assert_eq!(percentile(&[], 50), None);
+        assert_eq!(percentile(&seconds(&[7]), 50), Some(Duration::seconds(7)));
+        assert_eq!(percentile(&seconds(&[7]), 95), Some(Duration::seconds(7)));
+
+        let values = seconds(&(1..=10).collect::<Vec<_>>());
+        assert_eq!(percentile(&values, 0), Some(Duration::seconds(1)));
+        assert_eq!(percentile(&values, 50), Some(Duration::seconds(5)));
+        assert_eq!(percentile(&values, 95), Some(Duration::seconds(10)));
+        assert_eq!(percentile(&values, 100), Some(Duration::seconds(10)));
+
+        let values = seconds(&(1..=100).collect::<Vec<_>>());
+        assert_eq!(percentile(&values, 50), Some(Duration::seconds(50)));
+        assert_eq!(percentile(&values, 95), Some(Duration::seconds(95)));
+    }
+
+    #[test]
+    fn aggregate_works() {
+        let latencies = |scheduling: i64, pull: Option<i64>| Latencies {
+            scheduling: Some(Duration::seconds(scheduling)),
+            pull: pull.map(Duration::seconds),
+            logs: None,
+        };
+        let h100 = Some("NVIDIA-H100-80GB-HBM3".to_owned());
+        let summaries = aggregate([
+            (h100.clone(), latencies(30, Some(100))),
+            (None, latencies(1, Some(5))),
+            (h100.clone(), latencies(10, None)),
+            (h100.clone(), latencies(20, Some(60))),
+        ]);
+
+        assert_eq!(
+            summaries.keys().collect::<Vec<_>>(),
+            [&None, &h100],
+            "jobs without GPUs sort first"
+        );
+        assert_eq!(
+            summaries[&h100],
+            Summary {
+                count: 3,
+                scheduling: Some(Percentiles {
+                    p50: Duration::seconds(20),
+                    p95: Duration::seconds(30),
+                }),
+                pull: Some(Percentiles {
+                    p50: Duration::seconds(60),
+                    p95: Duration::seconds(100),
+                }),
+            }
+        );
+        assert_eq!(summaries[&None].count, 1);
+        assert_eq!(
+            summaries[&None].pull,
+            Some(Percentiles {
+                p50: Duration::seconds(5),
+                p95: Duration::seconds(5),
+            })
+        );
+
+        let summaries = aggregate([(None, Latencies::default())]);
+        assert_eq!(
+            summaries[&None],
+            Summary {
+                count: 1,
+                scheduling: None,
+                pull: None,
+            }
+        );
+    }
+
+    #[test]
+    fn format_duration_works() {
+        assert_eq!(format_duration(Duration::seconds(5)), "5s");
+        assert_eq!(format_duration(Duration::seconds(95)), "1m 35s");
+    }
+}
@@ -1,44 +1,147 @@
-use std::path::PathBuf;
+pub mod environment;
+mod multi_context;
+mod secret_args;
+
+use std::{
+    fmt::Write as _,
+    io::Write as _,
+    path::{Path, PathBuf},
+};
 
 use clap::{Args, ValueEnum};
 use constcat::concat;
-use container_image_name::ImageName;
-use home::home_dir;
-use log::{debug, warn};
+use container_image_name::{
+    image_name, registry_eq_canonical, ImageName, ImageNameRef, Tag, TAG_MAX_LENGTH,
+};
+use environment::SubmitEnvironment;
+use log::{debug, info, warn};
 
-use super::ClusterContext;
+use super::{
+    comment,
+    config::{self, Resolved, StrictGit},
+    history,
+    list::MaxAge,
+    repo_config::{self, SubmitDefaults},
+    summary, ClusterContext,
+};
 use crate::{
-    builder,
+    builder::{self, Builder as _},
+    databricks, docker,
     executor::{self, ExecutionArgs, Executor as _},
     git,
+    http::Http,
     kubectl::{self, is_rfc_1035_label, NAMESPACE},
+    process, ui,
     unit::bytes::{self, Bytes},
+    urls,
     user_host::UserHost,
     Result,
 };
 
+/// Parses a bare number as gibibytes, and otherwise requires a unit like `--mem`.
 fn gibibyte(s: &str) -> Result<Bytes> {
-    Ok(Bytes::new::<bytes::gibibyte>(s.parse()?).ok_or_else(|| "value too large".to_string())?)
+    match s.parse() {
+        Ok(value) => Ok(
+            Bytes::new::<bytes::gibibyte>(value).ok_or_else(|| "value too large".to_string())?
+        ),
+        Err(_) => Ok(s.parse()?),
+    }
 }
 
 #[derive(Debug, Args)]
 pub struct SubmitArgs {
-    /// How to build the image.
-    #[arg(long = "builder", value_enum, default_value_t)]
-    pub builder: BuilderArg,
+    /// How to build the image. Defaults to `docker`.
+    #[arg(long = "builder", value_enum)]
+    pub builder: Option<BuilderArg>,
+
+    /// The Dockerfile to build, relative to the current directory, which is the build context. Defaults to
+    /// `Dockerfile`, or to `Dockerfile.kaniko` with the kaniko builder when it exists.
+    #[arg(long = "dockerfile")]
+    pub dockerfile: Option<PathBuf>,
 
-    /// The minimum number of GPUs per worker.
-    #[arg(long = "gpus", default_value_t)]
-    pub gpus: u32,
+    /// The minimum number of GPUs per worker. Defaults to 0.
+    #[arg(long = "gpus")]
+    pub gpus: Option<u32>,
 
-    /// The minimum GPU RAM memory per worker in gibibyte (GiB, 2^30 bytes).
+    /// The minimum GPU RAM memory per worker, for example `80` or `80GiB`. A bare number is in gibibyte (GiB, 2^30
+    /// bytes).
     #[arg(long = "gpu-mem", value_parser=gibibyte)]
     pub gpu_mem: Option<Bytes>,
 
+    /// The cpus requested per worker, for example `4` or `500m`. Without a cpu limit, the job may use more when its
+    /// node has idle cpus.
+    #[arg(long = "cpus", value_parser = executor::parse_cpu_quantity)]
+    pub cpus: Option<String>,
+
+    /// The cpu limit per worker. Defaults to no limit.
+    #[arg(
+        long = "cpus-limit",
+        value_parser = executor::parse_cpu_quantity,
+        conflicts_with = "limits_from_requests"
+    )]
+    pub cpus_limit: Option<String>,
+
+    /// The memory requested per worker, for example `16GiB`. It is also the memory limit unless `--mem-limit` is
+    /// passed. The container is killed when it uses more memory than its limit.
+    #[arg(long = "mem")]
+    pub mem: Option<Bytes>,
+
+    /// The memory limit per worker. Defaults to `--mem`.
+    #[arg(long = "mem-limit", conflicts_with = "limits_from_requests")]
+    pub mem_limit: Option<Bytes>,
+
+    /// Limit the cpu and memory to their requests, including those of `default_resources`, so that the job never uses
+    /// more than it requested.
+    #[arg(long = "limits-from-requests")]
+    pub limits_from_requests: bool,
+
     /// The number of workers to spawn. If the number of workers is larger than 1, the Ray execution backend will be
-    /// used.
-    #[arg(long = "workers", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
-    pub workers: u32,
+    /// used. Defaults to 1.
+    #[arg(long = "workers", value_parser = clap::value_parser!(u32).range(1..))]
+    pub workers: Option<u32>,
+
+    /// How to run the command. Defaults to `ray` when there is more than 1 worker and `job` otherwise.
+    #[arg(long = "executor", value_enum, conflicts_with = "katib_path")]
+    pub executor: Option<ExecutorArg>,
+
+    /// Create a CronJob that runs the command on this cron schedule instead of running it once, for example
+    /// `"0 3 * * *"` for 03:00 every day in the time zone of the cluster, which is usually UTC. A run is skipped while
+    /// the previous one is still going. Only applies to the job executor.
+    #[arg(
+        long = "schedule",
+        value_name = "CRON",
+        conflicts_with_all = ["katib_path", "notify", "wait_for_capacity"]
+    )]
+    pub schedule: Option<executor::Schedule>,
+
+    /// Print the kaniko build logs as they are instead of summarizing them as Dockerfile steps. The logs are always
+    /// printed as they are when stderr is not a terminal.
+    #[arg(long = "raw-build-logs", default_value_t)]
+    pub raw_build_logs: bool,
+
+    /// Build and push the image even when the registry already has an image built from the current commit. Images of
+    /// dirty working trees are always built.
+    #[arg(long = "force-rebuild", default_value_t)]
+    pub force_rebuild: bool,
+
+    /// What to do when the git working tree is dirty or the commit has not been pushed, when building with docker.
+    /// Overrides the LAUNCH_STRICT_GIT environment variable and the `strict_git` setting in
+    /// `~/.config/launch/<context>.yaml`. Defaults to `warn`.
+    #[arg(long = "strict-git", value_enum)]
+    pub strict_git: Option<StrictGit>,
+
+    /// Check that the first word of the command can be found on `PATH` inside the image by running it with docker after
+    /// the build. Not supported by the kaniko builder.
+    #[arg(long = "verify-command", default_value_t)]
+    pub verify_command: bool,
+
+    /// Refuse to submit when `--verify-command` can not find the command, instead of printing a warning.
+    #[arg(
+        long = "strict-command-check",
+        default_value_t,
+        requires = "verify_command"
+    )]
+    pub strict_command_check: bool,
 
     #[arg(long = "allow-dirty", default_value_t)]
     pub allow_dirty: bool,
@@ -46,11 +149,22 @@ pub struct SubmitArgs {
     #[arg(long = "allow-unpushed", default_value_t)]
     pub allow_unpushed: bool,
 
+    /// Submit even though the command appears to contain secrets, such as API keys or tokens. The command is readable
+    /// by everyone with access to the namespace, so prefer passing secrets through a Kubernetes Secret.
+    #[arg(long = "allow-secret-args", default_value_t)]
+    pub allow_secret_args: bool,
+
     /// Job name prefix of up to 20 characters, starting with an alphabetic character (a-z) and further consisting of
     /// alphanumeric characters (a-z, 0-9) optionally separated by dashes (-).
     #[arg(long = "name-prefix", value_parser = expect_name_prefix)]
     pub name_prefix: Option<String>,
 
+    /// Free text that describes the intent of the submission, such as `"baseline with the new tokenizer"`. Recorded in
+    /// the `launch.astera.org/comment` annotation and the local history, and cut at 1024 bytes. When omitted, launch
+    /// asks for a comment if `prompt_for_comment: true` is set in `defaults.yaml` and the terminal is interactive.
+    #[arg(long = "comment")]
+    pub comment: Option<String>,
+
     /// Path to a Katib experiment spec YAML file.
     /// The valid fields are documented here, but note that trialTemplate is not allowed since
     /// the launch tool constructs that for you:
@@ -58,17 +172,101 @@ pub struct SubmitArgs {
     /// Any parameter listed in the config file will be passed as a command line arg to the given
     /// command. E.g. if ther is a parameter named "foo.bar", then each trial of the experiment
     /// will get "--foo.bar=<param value for that trial>" appended to the command.
+    /// Additionally, `trialEnv` sets environment variables in each trial and `mountDatabricksCfg` overrides
+    /// `--databrickscfg-mode` for the trials.
     #[arg(long = "katib")]
     pub katib_path: Option<PathBuf>,
 
-    #[arg(long = "databrickscfg-mode", value_enum, default_value_t, help = concat!("Control whether a secret should be created from the submitting machine and mounted as a file at \"", executor::DATABRICKSCFG_MOUNT, "\" through a volume in the container of the submitted job."))]
-    pub databrickscfg_mode: DatabricksCfgMode,
+    #[arg(long = "databrickscfg-mode", value_enum, help = concat!("Control whether a secret should be created from the submitting machine and mounted as a file at \"", databricks::MOUNT, "\" through a volume in the container of the submitted job. Defaults to `auto`."))]
+    pub databrickscfg_mode: Option<databricks::Mode>,
+
+    /// Warn when the databricks configuration was modified more than this many days ago, because its token may have
+    /// expired. Defaults to 90. Giving it also prints which configuration is mounted and when it was modified.
+    #[arg(long = "databrickscfg-max-age", value_name = "DAYS")]
+    pub databrickscfg_max_age: Option<u32>,
+
+    /// Deprecated, use `--databrickscfg-mode omit` instead.
+    #[arg(long = "no-databricks-env", conflicts_with = "databrickscfg_mode")]
+    pub no_databricks_env: bool,
+
+    /// Wait for the submitted work to finish after the logs end and post a JSON notification to this webhook URL, such
+    /// as a Slack incoming webhook. See `launch watch --help`.
+    #[arg(long = "notify")]
+    pub notify: Option<String>,
+
+    /// With `json`, the kaniko build of the image and the monitoring of a katib experiment print their events to stdout
+    /// as one JSON object per line, next to the logs on stderr.
+    #[arg(long = "progress", value_enum, default_value_t)]
+    pub progress: executor::Progress,
+
+    /// How to print the summary of the submissions to multiple contexts. Exits with 3 when only some of them failed.
+    /// With `json`, the summary is the only output on stdout, and the logs and manifests are printed to stderr.
+    #[arg(long = "output", value_enum, default_value_t)]
+    pub output: summary::OutputArg,
+
+    /// Print the generated manifest (`client`) or validate it against the cluster without creating anything
+    /// (`server`). The image build is skipped and a placeholder image is used unless `--build` is also passed.
+    #[arg(long = "dry-run", value_enum, num_args = 0..=1, default_missing_value = "client")]
+    pub dry_run: Option<DryRunMode>,
+
+    /// Hold the submission after the image is built until the GPUs it requests are free, so that it does not sit in
+    /// Pending. Takes an optional timeout such as `30m` or `2h`, after which the submission fails; waits indefinitely
+    /// otherwise. A RayJob waits for all of its workers, a Katib experiment for a single trial.
+    #[arg(
+        long = "wait-for-capacity",
+        value_name = "TIMEOUT",
+        num_args = 0..=1,
+        conflicts_with = "dry_run"
+    )]
+    pub wait_for_capacity: Option<Option<MaxAge>>,
+
+    /// Build and push the image even when performing a dry run.
+    #[arg(long = "build", default_value_t, requires = "dry_run")]
+    pub build: bool,
+
+    /// Run this image instead of building one, for example `ghcr.io/org/tool:1.2.3@sha256:...`. An image in the
+    /// registry of another context is copied to the registry of the context it is submitted to, other images are run
+    /// as they are.
+    #[arg(long = "image", conflicts_with_all = ["builder", "dockerfile", "build", "raw_build_logs", "force_rebuild"])]
+    pub image: Option<ImageName>,
+
+    /// Run `--image` from its own registry, even when it is the registry of another context.
+    #[arg(
+        long = "no-registry-rewrite",
+        requires = "image",
+        conflicts_with = "force_registry_rewrite"
+    )]
+    pub no_registry_rewrite: bool,
+
+    /// Copy `--image` to the registry of the context, even when it is not in the registry of a context.
+    #[arg(long = "force-registry-rewrite", requires = "image")]
+    pub force_registry_rewrite: bool,
+
+    /// The Ray version of the image, for example `2.40.0`. Sets `rayVersion` of the RayCluster, which kuberay uses to
+    /// decide which features the RayCluster supports. Only applies to the ray executor.
+    #[arg(long = "ray-version", value_parser = expect_ray_version)]
+    pub ray_version: Option<String>,
+
+    /// Path to a YAML file with a Ray runtime environment, such as `pip` packages and `env_vars`, that is passed to the
+    /// RayJob as `runtimeEnvYAML`. Only applies to the ray executor.
+    #[arg(long = "ray-runtime-env")]
+    pub ray_runtime_env: Option<PathBuf>,
+
+    /// The `restartPolicy` of the Ray worker Pods. With `on-failure`, a worker that is killed for running out of memory
+    /// restarts in its Pod instead of kuberay replacing the Pod. Defaults to `always`. Only applies to the ray executor.
+    #[arg(long = "ray-worker-restart-policy", value_enum)]
+    pub ray_worker_restart_policy: Option<executor::RestartPolicy>,
+
+    /// Collapse the identical lines that the Ray workers print within half a second of each other, such as the progress
+    /// of an epoch, into one line that says how many workers printed it. Only applies to the ray executor.
+    #[arg(long = "dedupe-logs")]
+    pub dedupe_logs: bool,
 
     #[arg(required = true, last = true)]
     pub command: Vec<String>,
 }
 
-fn expect_name_prefix(value: &str) -> Result<String, &'static str> {
+pub(super) fn expect_name_prefix(value: &str) -> Result<String, &'static str> {
     if !is_rfc_1035_label(value) {
         return Err("expected an RFC 1035 label matching regex /^[a-z]([-a-z0-9]*[a-z0-9])?$/");
     }
@@ -78,7 +276,14 @@ fn expect_name_prefix(value: &str) -> Result<String, &'static str> {
     Ok(value.to_string())
 }
 
-#[derive(Debug, Default, Clone, Copy, ValueEnum, PartialEq, Eq)]
+fn expect_ray_version(value: &str) -> Result<String, String> {
+    semver::Version::parse(value)
+        .map(|version| version.to_string())
+        .map_err(|error| format!("expected a version such as `2.40.0`: {error}"))
+}
+
+#[derive(Debug, Default, Clone, Copy, ValueEnum, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum BuilderArg {
     /// Use `docker` to build the image locally.
     #[default]
@@ -87,217 +292,1624 @@ pub enum BuilderArg {
     Kaniko,
 }
 
-#[derive(Debug, Default, Clone, Copy, ValueEnum, PartialEq, Eq)]
-pub enum DatabricksCfgMode {
-    /// The databrickscfg secret will be created and attached to the container if possible.
-    #[default]
-    Auto,
-    /// The databrickscfg secret is required.
-    Require,
-    /// The databrickscfg secret should be omitted.
-    Omit,
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum ExecutorArg {
+    /// Run a Kubernetes Job with a single Pod.
+    Job,
+    /// Run a RayJob with a head Pod and a Pod per worker.
+    Ray,
 }
 
-pub fn submit(context: &ClusterContext, args: SubmitArgs) -> Result<()> {
+/// The executor that [`select_executor`] decided on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExecutorKind {
+    Job,
+    Ray,
+    Katib,
+}
+
+/// Decides the executor from the settings that control parallelism. Combinations in which one of them would be ignored
+/// are rejected with a message that names both.
+fn select_executor(
+    workers: &Resolved<u32>,
+    executor: Option<ExecutorArg>,
+    katib: bool,
+) -> Result<ExecutorKind, String> {
+    let Resolved {
+        value: workers,
+        source,
+    } = workers;
+    match (katib, executor, *workers) {
+        (true, Some(executor), _) => Err(format!(
+            "`--executor {}` can not be combined with `--katib`, which runs every trial as a Job",
+            executor.to_possible_value().unwrap().get_name()
+        )),
+        (true, None, 2..) => Err(format!(
+            "`--katib` can not be combined with {workers} workers set by {source}. Configure `parallelTrialCount` in the experiment specification instead, and pass `--workers 1` if the workers come from a configuration file."
+        )),
+        (true, None, _) => Ok(ExecutorKind::Katib),
+        (false, Some(ExecutorArg::Job), 2..) => Err(format!(
+            "`--executor job` runs a single Pod and can not be combined with {workers} workers set by {source}. Pass `--executor ray` to run the workers with Ray."
+        )),
+        (false, Some(ExecutorArg::Job), _) | (false, None, 0..=1) => Ok(ExecutorKind::Job),
+        (false, Some(ExecutorArg::Ray), _) | (false, None, 2..) => Ok(ExecutorKind::Ray),
+    }
+}
+
+/// Rejects the options of RayJobs when the command does not run as a RayJob, because they would be ignored. `flags`
+/// pairs each option with whether it was passed.
+fn check_ray_options(executor: ExecutorKind, flags: &[(&str, bool)]) -> Result<(), String> {
+    let Some(&(flag, _)) = flags.iter().find(|&&(_, passed)| passed) else {
+        return Ok(());
+    };
+    match executor {
+        ExecutorKind::Ray => Ok(()),
+        ExecutorKind::Job => Err(format!(
+            "`{flag}` only applies to RayJobs. Pass `--executor ray` to run the command with Ray."
+        )),
+        ExecutorKind::Katib => Err(format!(
+            "`{flag}` only applies to RayJobs and can not be combined with `--katib`, which runs every trial as a Job"
+        )),
+    }
+}
+
+/// Rejects `--schedule` when the command does not run as a Job, because only Jobs can be created by a CronJob.
+fn check_schedule(executor: ExecutorKind) -> Result<(), String> {
+    match executor {
+        ExecutorKind::Job => Ok(()),
+        ExecutorKind::Ray => Err(
+            "`--schedule` only applies to Jobs and can not be combined with a RayJob, which `--executor ray` or more than 1 worker selects".to_owned()
+        ),
+        ExecutorKind::Katib => {
+            Err("`--schedule` only applies to Jobs and can not be combined with `--katib`".to_owned())
+        }
+    }
+}
+
+/// Rejects `--progress json` when the submission has no events to report, because neither a katib experiment is
+/// monitored nor the image is built with kaniko.
+fn check_progress(executor: ExecutorKind, builder: BuilderArg, image: bool) -> Result<(), String> {
+    if executor == ExecutorKind::Katib || (builder == BuilderArg::Kaniko && !image) {
+        Ok(())
+    } else if image {
+        Err("`--progress json` only reports katib experiments and kaniko builds, and `--image` skips the build. Pass `--katib` or drop `--progress`.".to_owned())
+    } else {
+        Err("`--progress json` only reports katib experiments and kaniko builds. Pass `--katib` or `--builder kaniko`, or drop `--progress`.".to_owned())
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum DryRunMode {
+    /// Print the generated manifest without contacting the cluster.
+    Client,
+    /// Send the generated manifest through `kubectl create --dry-run=server` to run validation and admission webhooks.
+    Server,
+}
+
+/// Submits to each of the contexts in order. The image is built once and copied to the registries of the other contexts,
+/// except with kaniko which builds it in each cluster. The submission to a context follows the logs like a single
+/// submission before the next context is submitted to. A failure does not prevent or undo the submissions to the
+/// other contexts.
+pub fn submit(contexts: &[ClusterContext], args: SubmitArgs) -> Result<()> {
+    submit_with(contexts, args, &environment::System, true).map(drop)
+}
+
+/// Like [`submit`], but queries the submitting machine through `sources`, which `launch agent` caches. Without
+/// `follow_logs`, a submission returns as soon as its resource is created. Returns the URLs of the created resources.
+pub fn submit_with(
+    contexts: &[ClusterContext],
+    args: SubmitArgs,
+    sources: &impl environment::Sources,
+    follow_logs: bool,
+) -> Result<Vec<String>> {
     let SubmitArgs {
         builder,
+        dockerfile,
+        raw_build_logs,
+        force_rebuild,
         gpus,
         gpu_mem,
+        cpus,
+        cpus_limit,
+        mem,
+        mem_limit,
+        limits_from_requests,
         workers,
+        executor,
+        schedule,
+        strict_git,
+        verify_command,
+        strict_command_check,
         allow_dirty,
         allow_unpushed,
+        allow_secret_args,
         databrickscfg_mode,
+        databrickscfg_max_age,
+        no_databricks_env,
         name_prefix,
+        comment,
         command,
         katib_path,
+        notify,
+        progress,
+        output,
+        dry_run,
+        wait_for_capacity,
+        build,
+        image,
+        no_registry_rewrite,
+        force_registry_rewrite,
+        ray_version,
+        ray_runtime_env: ray_runtime_env_path,
+        ray_worker_restart_policy,
+        dedupe_logs,
     } = args;
+    if output == summary::OutputArg::Json && progress == executor::Progress::Json {
+        return Err(
+            "`--progress json` and `--output json` both print to stdout, pass only one of them"
+                .into(),
+        );
+    }
+    if output == summary::OutputArg::Json || progress == executor::Progress::Json {
+        process::reserve_stdout();
+    }
+
+    let registry_rewrite = match (no_registry_rewrite, force_registry_rewrite) {
+        (true, _) => RegistryRewrite::Never,
+        (_, true) => RegistryRewrite::Force,
+        _ => RegistryRewrite::Auto,
+    };
+
+    // The image is built into the registry of each context.
+    if image.is_none() {
+        for context in contexts {
+            context.require_container_registry_host()?;
+        }
+    }
 
     if command.is_empty() {
         return Err("Please provide the command to run".into());
     }
+    let secrets = secret_args::scan(&command);
+    if !secrets.is_empty() {
+        let described = secret_args::describe(&command, &secrets);
+        if !allow_secret_args {
+            return Err(format!("The command appears to contain secrets, which everyone with access to the namespace would be able to read:\n{described}\nPass them through a Kubernetes Secret instead, or pass `--allow-secret-args` to submit anyway.").into());
+        }
+        warn!("Submitting a command that appears to contain secrets because `--allow-secret-args` is set:\n{described}");
+    }
+    debug!(
+        "Command: {}",
+        crate::bash_escape::quote_join(
+            secret_args::redact(&command, &secrets)
+                .iter()
+                .map(String::as_str)
+        )
+    );
+    executor::check_resource_limits(cpus.as_deref(), cpus_limit.as_deref(), mem, mem_limit)?;
+
+    // Asked before the build, so that the user does not have to wait for the question.
+    let comment = match comment {
+        Some(comment) => comment::normalize(&comment),
+        None if dry_run.is_none() && config::prompt_for_comment()? && ui::is_interactive() => {
+            comment::prompt()?
+        }
+        None => None,
+    };
+    if let Some(comment) = &comment {
+        debug!("Comment: {}", comment::display(comment));
+    }
 
-    let machine_user_host = super::common::machine_user_host();
-    let tailscale_user_host = super::common::tailscale_user_host();
-    let user = kubectl::to_rfc_1035_label_lossy(
-        tailscale_user_host
+    let repo_defaults = repo_config::submit_defaults()?;
+    let settings = SubmitSettings::resolve(
+        SubmitFlags {
+            builder,
+            gpus,
+            gpu_mem,
+            workers,
+            name_prefix,
+            databrickscfg_mode,
+        },
+        repo_defaults
             .as_ref()
-            .and_then(|value| value.host().is_some().then_some(value.user()))
-            .unwrap_or(machine_user_host.user()),
+            .map(|(path, defaults)| (path.as_path(), defaults)),
     );
+    // Only draw attention to the settings when some of them may come from the repository.
+    if repo_defaults.is_some() {
+        info!("{}", settings.summary());
+    } else {
+        debug!("{}", settings.summary());
+    }
+    let executor_kind = select_executor(&settings.workers, executor, katib_path.is_some())?;
+    check_ray_options(
+        executor_kind,
+        &[
+            ("--ray-version", ray_version.is_some()),
+            ("--ray-runtime-env", ray_runtime_env_path.is_some()),
+            (
+                "--ray-worker-restart-policy",
+                ray_worker_restart_policy.is_some(),
+            ),
+            ("--dedupe-logs", dedupe_logs),
+        ],
+    )?;
+    if schedule.is_some() {
+        check_schedule(executor_kind)?;
+    }
+    let ray_runtime_env = ray_runtime_env_path
+        .as_deref()
+        .map(executor::read_runtime_env)
+        .transpose()?;
+    let SubmitSettings {
+        builder: Resolved { value: builder, .. },
+        gpus: Resolved { value: gpus, .. },
+        gpu_mem: Resolved { value: gpu_mem, .. },
+        workers: Resolved { value: workers, .. },
+        name_prefix: Resolved {
+            value: name_prefix, ..
+        },
+        databrickscfg_mode:
+            Resolved {
+                value: databrickscfg_mode,
+                ..
+            },
+    } = settings;
+    if progress == executor::Progress::Json {
+        check_progress(executor_kind, builder, image.is_some())?;
+    }
+    let databrickscfg_mode = if no_databricks_env {
+        warn!("`--no-databricks-env` is deprecated, use `--databrickscfg-mode omit` instead");
+        databricks::Mode::Omit
+    } else {
+        databrickscfg_mode
+    };
+    let SubmitEnvironment {
+        machine_user_host,
+        tailscale_user_host,
+        user,
+        git_info,
+        databrickscfg_path,
+    } = SubmitEnvironment::resolve(sources, databrickscfg_mode)?;
+    if let Some(path) = databrickscfg_path.as_deref() {
+        databricks::report(
+            path,
+            &databricks::secret_name(user.as_deref()),
+            databrickscfg_max_age,
+        );
+    }
 
-    let kubectl = context.kubectl();
-    let git_info = git::info()?;
-
-    if !allow_dirty && !git_info.is_clean {
-        match builder {
-            BuilderArg::Docker => warn!("Please ensure that you commit all changes so we can reproduce the results. This warning may become an error in the future. You can disable this check by passing `--allow-dirty`."),
-            BuilderArg::Kaniko => return Err("There are git changes that have not been committed and pushed. When using the kaniko builder, this means the launched job will not have your latest code. Either commit and push all changes, or disable this check by passing `--allow-dirty`.".into()),
+    // The git checks of the docker builder depend on the `strict_git` setting of each context and are performed per
+    // context.
+    if builder == BuilderArg::Kaniko && image.is_none() {
+        // NOTE: Kaniko builds from the pushed commit, so untracked files can not end up in the image.
+        if !allow_dirty && git_info.status.has_tracked_changes() {
+            return Err(format!("There are git changes that have not been committed and pushed ({}). When using the kaniko builder, this means the launched job will not have your latest code. Either commit and push all changes, or disable this check by passing `--allow-dirty`.", git_info.status).into());
+        }
+        if !allow_unpushed && !git_info.is_pushed {
+            return Err("There are git changes that have not been pushed. When using the kaniko builder, this means the launched job will not have your latest code. Either push all changes, or disable this check by passing `--allow-dirty`.".into());
+        }
+        if !allow_unpushed {
+            check_kaniko_context_remote(&git_info)?;
         }
     }
 
-    if !allow_unpushed && !git_info.is_pushed {
-        match builder {
-            BuilderArg::Docker => warn!("Please ensure that your commit is pushed so we can reproduce the results. This warning may become an error in the future. You can disable this check by passing `--allow-unpushed`."),
-            BuilderArg::Kaniko => return Err("There are git changes that have not been pushed. When using the kaniko builder, this means the launched job will not have your latest code. Either push all changes, or disable this check by passing `--allow-dirty`.".into()),
+    let current_dir = std::env::current_dir()?;
+    let dockerfile = dockerfile.unwrap_or_else(|| {
+        builder::dockerfile::default_path(&current_dir, builder == BuilderArg::Kaniko)
+    });
+    // NOTE: Checked before any context is submitted to, so that submitting from the wrong directory fails before docker
+    // runs or the kaniko Pod is created.
+    if image.is_none() && (dry_run.is_none() || build) {
+        builder::dockerfile::check_exists(&current_dir, &git_info.dir, &dockerfile)?;
+        if builder == BuilderArg::Kaniko {
+            builder::dockerfile::check_tracked(&current_dir, &dockerfile)?;
         }
     }
 
-    let client = reqwest::blocking::Client::new();
-    let build_backend = match builder {
-        BuilderArg::Docker => &builder::DockerBuilder as &dyn builder::Builder,
-        BuilderArg::Kaniko => &builder::KanikoBuilder {
-            working_directory: &std::env::current_dir()?,
-            kubectl: &kubectl,
-            namespace: NAMESPACE,
-            user: user.as_deref(),
-            client: &client,
-        } as &dyn builder::Builder,
-    };
-
+    // The registry differs per context and is added when the image is published.
     let tagged_image = {
-        let current_dir = std::env::current_dir()?;
-
         let image_name = std::path::Path::new(&current_dir)
             .file_name()
             .ok_or("launch")?
             .to_str()
             .ok_or("Current directory name contains invalid UTF-8")?;
 
-        let image_tag = if git_info.is_clean || builder == BuilderArg::Kaniko {
-            git_info.commit_hash.clone()
+        let image_tag = if git_info.is_clean() || builder == BuilderArg::Kaniko {
+            git_info.commit_hash.parse()?
         } else {
-            format!(
-                "{user}-{rand:x}",
-                user = user.as_deref().unwrap_or("unknown-user"),
-                rand = rand::random::<u32>()
-            )
+            dirty_image_tag(user.as_deref(), rand::random())
         };
 
         ImageName::builder(image_name.to_lowercase())
-            .with_registry(context.container_registry_host())
-            .with_tag(image_tag)
+            .with_tag(&image_tag)
             .build()?
     };
 
-    let build_output = build_backend.build(builder::BuildArgs {
+    let mut publisher = RegistryPublisher {
+        builder,
         git_info: &git_info,
         image: tagged_image.as_ref(),
-    })?;
-
-    let built_image = tagged_image
-        .as_builder()
-        .with_digest(&build_output.digest)
-        .build()
-        .map_err(|_| {
-            format!(
-                "failed to combine image {:?} with digest {:?}",
-                tagged_image, build_output.digest
-            )
-        })
-        .unwrap();
+        dockerfile: &dockerfile,
+        user: user.as_deref(),
+        client: config::http()?,
+        raw_build_logs,
+        force_rebuild,
+        progress,
+    };
+    let mut image_distribution =
+        multi_context::ImageDistribution::new(builder == BuilderArg::Docker);
+    let mut command_verified = !verify_command;
 
-    debug!("Using container image: {}", built_image);
-    let home_dir = home_dir().ok_or("failed to determine home directory")?;
+    let executor: executor::AnyExecutor = match (executor_kind, katib_path) {
+        (ExecutorKind::Katib, Some(experiment_spec_path)) => executor::KatibExecutor {
+            experiment_spec_path,
+            progress,
+        }
+        .into(),
+        (ExecutorKind::Ray, _) => executor::RayExecutor.into(),
+        (ExecutorKind::Job, _) | (ExecutorKind::Katib, None) => match schedule {
+            Some(schedule) => executor::CronJobExecutor { schedule }.into(),
+            None => executor::KubernetesExecutor.into(),
+        },
+    };
 
-    let databrickscfg_path = if matches!(
-        databrickscfg_mode,
-        DatabricksCfgMode::Auto | DatabricksCfgMode::Require
-    ) {
-        let path = home_dir.join(".databrickscfg");
-        match std::fs::metadata(&path) {
-            Ok(_) => Some(path),
-            Err(error) => {
-                let error_string =
-                    format!("Databricks configuration not found at {path:?}: {error}.");
-                if databrickscfg_mode == DatabricksCfgMode::Require {
-                    return Err(error_string.into());
+    let generate_name = generate_name(name_prefix.as_deref(), user.as_deref(), &executor);
+    // The same for all contexts so that the resources of one submission can be found together.
+    let run_id = executor::generate_run_id();
+    info!("Run ID: {run_id}");
+
+    // Returns `None` for a dry run.
+    let submit_to = |context: ClusterContext| -> Result<Option<executor::ExecutionOutput>> {
+        let context = &context;
+        let kubectl = context.kubectl();
+
+        let strict_git = config::strict_git(context, strict_git)?;
+        debug!(
+            "Using strict_git {} set by {}",
+            strict_git.value, strict_git.source
+        );
+
+        if builder == BuilderArg::Docker && image.is_none() {
+            if !allow_dirty && git_info.status.has_tracked_changes() {
+                check_git_provenance(
+                    &strict_git,
+                    &format!(
+                        "There are git changes that have not been committed ({})",
+                        git_info.status
+                    ),
+                    "--allow-dirty",
+                )?;
+            } else if !allow_dirty && !git_info.is_clean() {
+                check_untracked_files(&strict_git, git_info.status);
+            }
+            if !allow_unpushed && !git_info.is_pushed {
+                check_git_provenance(
+                    &strict_git,
+                    "The current commit has not been pushed",
+                    "--allow-unpushed",
+                )?;
+            }
+        }
+
+        let built_image = match &image {
+            Some(image) => {
+                external_image(image.as_ref(), context, registry_rewrite, dry_run.is_some())?
+            }
+            None => {
+                let mut registry_image = tagged_image
+                    .as_ref()
+                    .with_registry(context.require_container_registry_host()?)?;
+                let digest = if dry_run.is_some() && !build {
+                    debug!("Skipping the image build for the dry run, pass `--build` to build it anyway");
+                    PLACEHOLDER_IMAGE.digest().unwrap().to_owned()
                 } else {
-                    warn!(
-                        "{error_string} To omit the databricks configuration and avoid this warning, pass `--databrickcfg-mode omit`."
-                    );
-                    None
+                    image_distribution.digest_for(*context, &mut publisher)?
+                };
+
+                registry_image.set_digest(&digest)?;
+                registry_image
+            }
+        };
+
+        debug!("Using container image: {}", built_image.familiar_name(true));
+
+        if !command_verified {
+            match (&image, builder) {
+                // A copy to the registry of the context has the same contents, and does not exist yet in a dry run.
+                (Some(image), _) => {
+                    check_command_in_image(image.as_ref(), &command[0], strict_command_check)?
+                }
+                (None, BuilderArg::Docker) if dry_run.is_some() && !build => {
+                    debug!("Skipping the command check because the image was not built")
+                }
+                (None, BuilderArg::Docker) => {
+                    check_command_in_image(built_image.as_ref(), &command[0], strict_command_check)?
+                }
+                (None, BuilderArg::Kaniko) => {
+                    info!("Skipping the command check because kaniko builds the image remotely")
                 }
             }
+            // The image is the same for all contexts.
+            command_verified = true;
         }
-    } else {
-        None
-    };
 
-    let databrickscfg_name = databrickscfg_path
-        .map(|path| -> Result<_> {
-            let namespace = kubectl::NAMESPACE;
-            let name = match user.as_deref() {
-                Some(user) => format!("databrickscfg-{user}"),
-                None => "databrickscfg".to_string(),
+        let databrickscfg_name = databrickscfg_path
+            .as_deref()
+            .map(|path| -> Result<_> {
+                let namespace = kubectl::NAMESPACE;
+                let name = databricks::secret_name(user.as_deref());
+                if dry_run.is_some() {
+                    debug!("Skipping the creation of Secret {namespace}/{name} for the dry run");
+                    return Ok(name);
+                }
+                kubectl.recreate_secret_from_file(kubectl::NAMESPACE, &name, path)?;
+                debug!(
+                    "Created Secret {}",
+                    urls::headlamp_secret(context, namespace, &name)
+                );
+                Ok(name)
+            })
+            .transpose()?;
+
+        let (config_path, default_resources) = config::default_resources(context)?;
+
+        // Recorded for debugging, so failing to obtain it should not prevent the submission.
+        let server_version = kubectl
+            .server_version()
+            .inspect_err(|error| debug!("Unable to obtain the Kubernetes server version: {error}"))
+            .ok()
+            .flatten();
+
+        let execution_args = ExecutionArgs {
+            context,
+            job_namespace: kubectl::NAMESPACE,
+            generate_name: &generate_name,
+            machine_user_host: machine_user_host.to_ref(),
+            tailscale_user_host: tailscale_user_host.as_ref().map(UserHost::to_ref),
+            image: built_image.as_ref(),
+            databrickscfg_name: databrickscfg_name.as_deref(),
+            container_args: &command,
+            workers,
+            gpus,
+            gpu_mem,
+            cpus: cpus.as_deref(),
+            cpus_limit: cpus_limit.as_deref(),
+            mem,
+            mem_limit,
+            limits_from_requests,
+            default_resources: default_resources.as_ref(),
+            git_provenance: executor::GitProvenance {
+                strict_git: strict_git.value.as_str(),
+                is_dirty: !git_info.is_clean(),
+                is_unpushed: !git_info.is_pushed,
+            },
+            server_version: server_version.as_deref(),
+            ray_version: ray_version.as_deref(),
+            ray_runtime_env: ray_runtime_env.as_deref(),
+            ray_worker_restart_policy,
+            dedupe_logs,
+            follow_logs,
+            run_id: &run_id,
+            comment: comment.as_deref(),
+        };
+
+        if executor::is_best_effort(execution_args.resources().as_ref()) {
+            warn!("{}", best_effort_warning(config_path.as_deref()));
+        }
+
+        if let Some(mode) = dry_run {
+            self::dry_run(&kubectl, mode, &executor.spec(&execution_args)?)?;
+            return Ok(None);
+        }
+
+        if let Some(timeout) = wait_for_capacity {
+            let pod_count = match executor_kind {
+                ExecutorKind::Ray => workers,
+                ExecutorKind::Job | ExecutorKind::Katib => 1,
             };
-            kubectl.recreate_secret_from_file(kubectl::NAMESPACE, &name, &path)?;
+            executor::gpu_capacity::wait_for_capacity(
+                &kubectl,
+                pod_count,
+                gpus,
+                gpu_mem,
+                timeout.map(|timeout| timeout.duration().unsigned_abs()),
+            )?;
+        }
+
+        let output = executor.execute(execution_args)?;
+
+        history::record(&history::Entry::new(
+            context,
+            output.kind,
+            &output.namespace,
+            &output.name,
+            &run_id,
+            comment.clone(),
+        ));
+
+        if let Some(notify) = notify.as_deref() {
+            super::watch::watch_resource(
+                context,
+                &kubectl,
+                output.kind,
+                &output.namespace,
+                &output.name,
+                Some(notify),
+            )?;
+        }
+
+        Ok(Some(output))
+    };
+
+    let mut results = multi_context::submit_each(contexts, submit_to);
+    let urls = results
+        .iter()
+        .filter_map(|(context, result)| match result {
+            Ok(Some(output)) => Some(output.kind.url(context, &output.namespace, &output.name)),
+            _ => None,
+        })
+        .collect();
+    if let [(_, _)] = results.as_slice() {
+        // The error of a single context is returned as is.
+        return results.remove(0).1.map(|_| urls);
+    }
+    let summary = multi_context::summary(&results, |context, output| match output {
+        Some(output) => output.kind.url(context, &output.namespace, &output.name),
+        None => "dry run".to_owned(),
+    });
+    summary.print(output)?;
+    summary.into_result().map(|()| urls)
+}
+
+/// Publishes the image to the registry of each context with the selected builder.
+struct RegistryPublisher<'a> {
+    builder: BuilderArg,
+    git_info: &'a git::GitInfo,
+    /// The image without a registry.
+    image: ImageNameRef<'a>,
+    dockerfile: &'a Path,
+    user: Option<&'a str>,
+    client: Http,
+    raw_build_logs: bool,
+    force_rebuild: bool,
+    progress: executor::Progress,
+}
+
+impl RegistryPublisher<'_> {
+    fn image(&self, context: ClusterContext) -> Result<ImageName> {
+        Ok(self
+            .image
+            .with_registry(context.require_container_registry_host()?)?)
+    }
+}
+
+impl multi_context::ImagePublisher<ClusterContext> for RegistryPublisher<'_> {
+    fn build(&mut self, context: ClusterContext) -> Result<String> {
+        let image = self.image(context)?;
+        let args = builder::BuildArgs {
+            git_info: self.git_info,
+            image: image.as_ref(),
+        };
+        let output = match self.builder {
+            BuilderArg::Docker => builder::DockerBuilder {
+                client: &self.client,
+                force_rebuild: self.force_rebuild,
+                dockerfile: self.dockerfile,
+            }
+            .build(args)?,
+            BuilderArg::Kaniko => builder::KanikoBuilder {
+                working_directory: &std::env::current_dir()?,
+                dockerfile: self.dockerfile,
+                kubectl: &context.kubectl(),
+                namespace: NAMESPACE,
+                user: self.user,
+                client: &self.client,
+                raw_logs: self.raw_build_logs,
+                force_rebuild: self.force_rebuild,
+                progress: self.progress,
+            }
+            .build(args)?,
+        };
+        Ok(output.digest)
+    }
+
+    fn copy(&mut self, source: ClusterContext, digest: &str, target: ClusterContext) -> Result<()> {
+        let source = self.image(source)?;
+        let source = source.as_ref().with_digest(digest)?;
+        docker::copy_image(source.as_ref(), self.image(target)?.as_ref())
+    }
+}
+
+/// The flags of `launch submit` that can be defaulted by the `[submit]` table of the repository configuration.
+struct SubmitFlags {
+    builder: Option<BuilderArg>,
+    gpus: Option<u32>,
+    gpu_mem: Option<Bytes>,
+    workers: Option<u32>,
+    name_prefix: Option<String>,
+    databrickscfg_mode: Option<databricks::Mode>,
+}
+
+/// The effective values of [`SubmitFlags`] along with where they came from.
+#[derive(Debug, PartialEq, Eq)]
+struct SubmitSettings {
+    builder: Resolved<BuilderArg>,
+    gpus: Resolved<u32>,
+    gpu_mem: Resolved<Option<Bytes>>,
+    workers: Resolved<u32>,
+    name_prefix: Resolved<Option<String>>,
+    databrickscfg_mode: Resolved<databricks::Mode>,
+}
+
+impl SubmitSettings {
+    /// Flags override the repository configuration, which overrides the defaults.
+    fn resolve(flags: SubmitFlags, repo: Option<(&Path, &SubmitDefaults)>) -> Self {
+        fn file<'a, T>(
+            repo: Option<(&'a Path, &'a SubmitDefaults)>,
+            key: impl FnOnce(&'a SubmitDefaults) -> Option<T>,
+        ) -> Option<(&'a Path, Option<T>)> {
+            repo.map(|(path, defaults)| (path, key(defaults)))
+        }
+
+        Self {
+            builder: config::resolve(
+                flags.builder,
+                "--builder",
+                file(repo, |defaults| defaults.builder),
+                BuilderArg::default(),
+            ),
+            gpus: config::resolve(
+                flags.gpus,
+                "--gpus",
+                file(repo, |defaults| defaults.gpus),
+                0,
+            ),
+            gpu_mem: config::resolve(
+                flags.gpu_mem.map(Some),
+                "--gpu-mem",
+                file(repo, |defaults| defaults.gpu_mem().map(Some)),
+                None,
+            ),
+            workers: config::resolve(
+                flags.workers,
+                "--workers",
+                file(repo, |defaults| defaults.workers),
+                1,
+            ),
+            name_prefix: config::resolve(
+                flags.name_prefix.map(Some),
+                "--name-prefix",
+                file(repo, |defaults| defaults.name_prefix.clone().map(Some)),
+                None,
+            ),
+            databrickscfg_mode: config::resolve(
+                flags.databrickscfg_mode,
+                "--databrickscfg-mode",
+                file(repo, |defaults| defaults.databrickscfg_mode),
+                databricks::Mode::default(),
+            ),
+        }
+    }
+
+    /// Lists the effective values and where they came from, one per line.
+    fn summary(&self) -> String {
+        fn value_name(value: impl ValueEnum) -> String {
+            value
+                .to_possible_value()
+                .map_or_else(String::new, |value| value.get_name().to_owned())
+        }
+
+        fn line(
+            summary: &mut String,
+            name: &str,
+            value: impl std::fmt::Display,
+            source: &config::Source,
+        ) {
+            write!(summary, "\n  {name}: {value} (set by {source})").unwrap();
+        }
+
+        let mut summary = "Submitting with:".to_owned();
+        line(
+            &mut summary,
+            "builder",
+            value_name(self.builder.value),
+            &self.builder.source,
+        );
+        line(&mut summary, "gpus", self.gpus.value, &self.gpus.source);
+        line(
+            &mut summary,
+            "gpu_mem",
+            self.gpu_mem.value.map_or_else(
+                || "none".to_owned(),
+                |value| value.display::<bytes::gibibyte>().to_string(),
+            ),
+            &self.gpu_mem.source,
+        );
+        line(
+            &mut summary,
+            "workers",
+            self.workers.value,
+            &self.workers.source,
+        );
+        line(
+            &mut summary,
+            "name_prefix",
+            self.name_prefix.value.as_deref().unwrap_or("none"),
+            &self.name_prefix.source,
+        );
+        line(
+            &mut summary,
+            "databrickscfg_mode",
+            value_name(self.databrickscfg_mode.value),
+            &self.databrickscfg_mode.source,
+        );
+        summary
+    }
+}
+
+/// Applies the `strict_git` policy to a git problem that makes it hard to reproduce the results.
+fn check_git_provenance(
+    policy: &config::Resolved<StrictGit>,
+    problem: &str,
+    allow_flag: &str,
+) -> Result<()> {
+    let config::Resolved { value, source } = policy;
+    match value {
+        StrictGit::Off => {
+            debug!("{problem}, ignoring because `strict_git` is set to `off` by {source}");
+            Ok(())
+        }
+        StrictGit::Warn => {
+            warn!("{problem}. Please commit and push all changes so we can reproduce the results. You can disable this check by passing `{allow_flag}` or turn it into an error by setting `strict_git` to `error`.");
+            Ok(())
+        }
+        StrictGit::Error => Err(format!("{problem}. This is an error because `strict_git` is set to `error` by {source}. Commit and push all changes so we can reproduce the results, or disable this check by passing `{allow_flag}`.").into()),
+    }
+}
+
+/// Untracked files are only a hint that the results may not be reproducible, for example when the command reads them,
+/// so they never fail the submission and only show up when `strict_git` is `error`.
+fn check_untracked_files(policy: &config::Resolved<StrictGit>, status: git::Status) {
+    let config::Resolved { value, source } = policy;
+    let problem = format!("There are untracked files ({status})");
+    match value {
+        StrictGit::Off | StrictGit::Warn => {
+            debug!("{problem}, ignoring because `strict_git` is set to `{value}` by {source}")
+        }
+        StrictGit::Error => warn!("{problem}. They are copied into the image unless they are listed in `.dockerignore`. Commit them or add them to `.gitignore` so we can reproduce the results."),
+    }
+}
+
+/// The push remote is not necessarily the remote kaniko clones from, for example when working on a fork. Warns when
+/// the commit does not exist on the remote that points to the kaniko context repository.
+fn check_kaniko_context_remote(git_info: &git::GitInfo) -> Result<()> {
+    let repository = builder::KANIKO_CONTEXT_REPOSITORY;
+    let commit = &git_info.commit_hash;
+    match git::repository_push_status(repository, commit, git_info.push_remote.as_deref())? {
+        git::RepositoryPushStatus::Pushed { remote } => {
             debug!(
-                "Created Secret {headlamp_url}/c/main/secrets/{namespace}/{name}",
-                headlamp_url = context.headlamp_url()
+                "Commit {commit} exists on remote {:?} ({repository})",
+                remote.name
             );
-            Ok(name)
-        })
-        .transpose()?;
+        }
+        git::RepositoryPushStatus::NotPushed { remote } => {
+            warn!("Commit {commit} has been pushed, but not to remote {:?} which points to {repository}. The kaniko builder clones from {repository} and will not be able to find the commit.", remote.name);
+        }
+        git::RepositoryPushStatus::NoRemote => {
+            warn!("None of the git remotes point to {repository}, which the kaniko builder clones from. Unable to verify that commit {commit} exists there.");
+        }
+    }
+    Ok(())
+}
 
-    let executor: executor::AnyExecutor = if let Some(experiment_spec_path) = katib_path {
-        if workers > 1 {
-            // TODO: Consider refactoring the argument parsing to prohibit this.
-            warn!("The katib execution backend ignores the workers argument. Configure `parallelTrialCount` in the experiment specification instead.")
+fn best_effort_warning(config_path: Option<&Path>) -> String {
+    let mut message = "The job requests neither cpu nor memory, so Kubernetes gives it the BestEffort quality of service class. BestEffort Pods are the first to be evicted when a node runs low on resources, which makes the job disappear without an error of its own. Pass `--cpus` or `--mem`".to_owned();
+    match config_path {
+        Some(path) => write!(
+            message,
+            " or set `default_resources` in {}, for example `default_resources: {{ cpu: 4, memory: 16GiB }}`.",
+            path.display()
+        )
+        .unwrap(),
+        None => message.push('.'),
+    }
+    message
+}
+
+/// Warns, or errors when `strict` is set, if `program` can not be found on `PATH` inside the image.
+fn check_command_in_image(image: ImageNameRef, program: &str, strict: bool) -> Result<()> {
+    let message = match docker::check_command(image, docker::Platform::LinuxAmd64, program)? {
+        docker::CommandCheck::Found(path) => {
+            debug!("Command {program:?} resolves to {path:?} in the image");
+            return Ok(());
         }
-        executor::KatibExecutor {
-            experiment_spec_path,
+        docker::CommandCheck::Missing { candidates } => {
+            let mut message = format!("Command {program:?} was not found on PATH in the image");
+            if !candidates.is_empty() {
+                write!(message, ", did you mean one of {}?", candidates.join(", ")).unwrap();
+            }
+            message
+        }
+        docker::CommandCheck::Inconclusive(code) => {
+            warn!(
+                "Unable to check if command {program:?} is available in the image, `docker run` exited with {}",
+                code.map_or_else(|| "a signal".to_owned(), |code| format!("code {code}"))
+            );
+            return Ok(());
         }
-        .into()
-    } else if workers > 1 {
-        executor::RayExecutor.into()
+    };
+
+    if strict {
+        Err(message.into())
     } else {
-        executor::KubernetesExecutor.into()
+        warn!("{message}. Pass `--strict-command-check` to make this an error.");
+        Ok(())
+    }
+}
+
+/// Whether `--image` is copied to the registry of the context it is submitted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RegistryRewrite {
+    /// Only images in the registry of a context, because other registries are not mirrored.
+    Auto,
+    Never,
+    Force,
+}
+
+/// Returns the registry that the image should be copied to, or `None` to run it from where it is. Registries are
+/// compared with [`registry_eq_canonical`], so the case of the domain does not matter.
+fn rewrite_registry<'a>(
+    image_registry: Option<&str>,
+    context_registry: &'a str,
+    context_registries: &[&str],
+    rewrite: RegistryRewrite,
+) -> Option<&'a str> {
+    let rewrite = match rewrite {
+        RegistryRewrite::Never => false,
+        RegistryRewrite::Force => true,
+        RegistryRewrite::Auto => image_registry.is_some_and(|registry| {
+            context_registries
+                .iter()
+                .any(|context_registry| registry_eq_canonical(registry, context_registry))
+        }),
     };
+    let in_context_registry =
+        image_registry.is_some_and(|registry| registry_eq_canonical(registry, context_registry));
+    (rewrite && !in_context_registry).then_some(context_registry)
+}
 
-    let generate_name = generate_name(name_prefix.as_deref(), user.as_deref(), &executor);
+/// Returns the image to run for `--image`, copying it to the registry of the context when [`rewrite_registry`] says so.
+fn external_image(
+    image: ImageNameRef,
+    context: &ClusterContext,
+    rewrite: RegistryRewrite,
+    dry_run: bool,
+) -> Result<ImageName> {
+    let Some(context_registry) = context.container_registry_host() else {
+        if rewrite == RegistryRewrite::Force {
+            context.require_container_registry_host()?;
+        }
+        debug!(
+            "Running image {} from its own registry, the {context} context has none",
+            image.familiar_name(true)
+        );
+        return Ok(image.to_owned());
+    };
+    let context_registries = ClusterContext::value_variants()
+        .iter()
+        .filter_map(ClusterContext::container_registry_host)
+        .collect::<Vec<_>>();
+    let Some(registry) = rewrite_registry(
+        image.registry(),
+        context_registry,
+        &context_registries,
+        rewrite,
+    ) else {
+        debug!(
+            "Running image {} from its own registry",
+            image.familiar_name(true)
+        );
+        return Ok(image.to_owned());
+    };
 
-    executor.execute(ExecutionArgs {
-        context,
-        job_namespace: kubectl::NAMESPACE,
-        generate_name: &generate_name,
-        machine_user_host: machine_user_host.to_ref(),
-        tailscale_user_host: tailscale_user_host.as_ref().map(UserHost::to_ref),
-        image: built_image.as_ref(),
-        databrickscfg_name: databrickscfg_name.as_deref(),
-        container_args: &command,
-        workers,
-        gpus,
-        gpu_mem,
-    })?;
+    let target = image.with_registry(registry)?;
+    if dry_run {
+        debug!(
+            "Skipping the copy of image {} to {} for the dry run",
+            image.familiar_name(true),
+            target.familiar_name(true)
+        );
+        return Ok(target);
+    }
+    // The tag of the copy can not contain the digest, which the copy keeps.
+    let mut tag = ImageName::builder(image.path()).with_registry(registry);
+    if let Some(value) = image.tag() {
+        tag = tag.with_tag(value);
+    }
+    docker::copy_image(image, tag.build()?.as_ref())?;
+    info!(
+        "Copied image {} to the registry of {context}",
+        image.familiar_name(true)
+    );
+    Ok(target)
+}
 
-    Ok(())
+/// Its digest is used in place of the digest of the image when the build is skipped during a dry run.
+const PLACEHOLDER_IMAGE: ImageNameRef =
+    image_name!("launch@sha256:0000000000000000000000000000000000000000000000000000000000000000");
+
+fn dry_run(kubectl: &kubectl::Kubectl, mode: DryRunMode, spec: &serde_json::Value) -> Result<()> {
+    match mode {
+        DryRunMode::Client => {
+            write!(process::output_stream(), "{}", serde_yaml::to_string(spec)?)?;
+            Ok(())
+        }
+        DryRunMode::Server => {
+            let kind = spec["kind"].as_str().unwrap_or("resource");
+            match kubectl.create_dry_run(&spec.to_string())? {
+                kubectl::DryRunVerdict::Accepted(handle) => {
+                    info!(
+                        "{kind} {namespace}/{name} passed server-side validation",
+                        namespace = handle.namespace,
+                        name = handle.name
+                    );
+                    Ok(())
+                }
+                kubectl::DryRunVerdict::Rejected(message) => {
+                    Err(format!("{kind} was rejected by the server: {message}").into())
+                }
+            }
+        }
+    }
+}
+
+/// Returns the tag of an image that is not built from a commit, `<user>-<random hex>`. `launch clean images` recognizes
+/// the tags by this shape.
+fn dirty_image_tag(user: Option<&str>, random: u32) -> Tag {
+    let suffix = format!("-{random:x}");
+    let user = user
+        .and_then(|user| Tag::sanitize_lossy_with_max_length(user, TAG_MAX_LENGTH - suffix.len()));
+    let user = user.as_ref().map_or("unknown-user", Tag::as_str);
+    format!("{user}{suffix}")
+        .parse()
+        .expect("a sanitized user followed by a dash and hex digits is a tag")
 }
 
+/// Returns the `generateName` of the submitted resource. The name is shortened to fit the limit of the executor, first
+/// by shortening the user and then the prefix.
 fn generate_name(
     name_prefix: Option<&str>,
     user: Option<&str>,
     executor: &executor::AnyExecutor,
 ) -> String {
-    let mut name = String::new();
-
-    if let Some(value) = name_prefix {
-        name.push_str(value);
-        name.push('-');
+    let default = match *executor {
+        executor::AnyExecutor::Kubernetes(_) => "job",
+        executor::AnyExecutor::Katib(_) => "katib",
+        executor::AnyExecutor::Ray(_) => "ray-job",
+        executor::AnyExecutor::CronJob(_) => "cron-job",
     };
+    let max_len = executor.max_name_len();
+    let (name, shortened) = fit_generate_name(name_prefix, user, default, max_len);
+    for message in shortened {
+        debug!("{message} so that the generated name fits in {max_len} characters");
+    }
+    name
+}
 
-    if let Some(user) = user {
-        name.push_str(user);
-        name.push('-')
+/// Joins the parts of the name with dashes and shortens them so that the name is at most `max_len` characters long
+/// after kubernetes appends its random suffix. Returns the name along with a description of what was shortened.
+fn fit_generate_name(
+    name_prefix: Option<&str>,
+    user: Option<&str>,
+    default: &str,
+    max_len: usize,
+) -> (String, Vec<String>) {
+    /// Labels can not end with a dash, and a trailing dash would be followed by another.
+    fn shorten(value: &str, max_len: usize) -> &str {
+        value[..value.len().min(max_len)].trim_end_matches('-')
     }
 
+    // Every part is followed by a dash.
+    let budget = max_len - kubectl::GENERATED_SUFFIX_LEN;
+    let mut shortened = Vec::new();
+
+    let name_prefix = name_prefix.map(|value| {
+        let fitted = shorten(value, budget - 1);
+        if fitted != value {
+            shortened.push(format!("Shortened the name prefix {value:?} to {fitted:?}"));
+        }
+        fitted
+    });
+    let user = user.and_then(|value| {
+        let available = budget - name_prefix.map_or(0, |value| value.len() + 1);
+        let fitted = shorten(value, available.saturating_sub(1));
+        if fitted.is_empty() {
+            shortened.push(format!("Omitted the user {value:?}"));
+            None
+        } else {
+            if fitted != value {
+                shortened.push(format!("Shortened the user {value:?} to {fitted:?}"));
+            }
+            Some(fitted)
+        }
+    });
+
+    let mut name = String::new();
+    for part in name_prefix.into_iter().chain(user) {
+        name.push_str(part);
+        name.push('-');
+    }
     if name.is_empty() {
-        name.push_str(match *executor {
-            executor::AnyExecutor::Kubernetes(_) => "job",
-            executor::AnyExecutor::Katib(_) => "katib",
-            executor::AnyExecutor::Ray(_) => "ray-job",
-        });
+        name.push_str(default);
         name.push('-');
     }
 
-    name
+    (name, shortened)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser as _;
+
+    use super::*;
+    use crate::cli::{Cli, Commands};
+
+    #[test]
+    fn gpu_mem_accepts_bare_gibibytes_and_units() {
+        let gib = |value| Bytes::new::<bytes::gibibyte>(value);
+        assert_eq!(gibibyte("80").ok(), gib(80));
+        assert_eq!(gibibyte("80GiB").ok(), gib(80));
+        assert_eq!(
+            gibibyte("80G").unwrap_err().to_string(),
+            r#"invalid unit "G", units are case-sensitive, did you mean "80GiB" or "80GB"?"#
+        );
+    }
+
+    fn policy(value: StrictGit, source: config::Source) -> config::Resolved<StrictGit> {
+        config::Resolved { value, source }
+    }
+
+    /// Returns the length of the final name, including the suffix generated by kubernetes.
+    fn total_len(name: &str) -> usize {
+        name.len() + kubectl::GENERATED_SUFFIX_LEN
+    }
+
+    #[test]
+    fn fit_generate_name_works() {
+        let fit =
+            |prefix, user: &str, max_len| fit_generate_name(prefix, Some(user), "job", max_len);
+
+        // Jobs, 62, 63 and 64 characters.
+        for (user_len, expected_user_len) in [(52, 52), (53, 53), (54, 53)] {
+            let user = "u".repeat(user_len);
+            let (name, shortened) = fit(Some("llm"), &user, kubectl::MAX_NAME_LEN);
+            assert_eq!(name, format!("llm-{}-", "u".repeat(expected_user_len)));
+            assert_eq!(total_len(&name), user_len.min(53) + 10);
+            assert_eq!(shortened.is_empty(), user_len <= 53, "{shortened:?}");
+        }
+
+        // Katib experiments, 39, 40 and 41 characters.
+        for (user_len, expected_user_len) in [(29, 29), (30, 30), (31, 30)] {
+            let user = "u".repeat(user_len);
+            let (name, shortened) = fit(Some("llm"), &user, executor::EXPERIMENT_NAME_MAX_LEN);
+            assert_eq!(name, format!("llm-{}-", "u".repeat(expected_user_len)));
+            assert!(total_len(&name) <= executor::EXPERIMENT_NAME_MAX_LEN);
+            assert_eq!(shortened.is_empty(), user_len <= 30, "{shortened:?}");
+        }
+
+        // The prefix is kept intact and trailing dashes of the shortened user are removed.
+        let (name, shortened) = fit(
+            Some("a-twenty-char-prefix"),
+            "alice-smithy-the-third",
+            executor::EXPERIMENT_NAME_MAX_LEN,
+        );
+        assert_eq!(name, "a-twenty-char-prefix-alice-smithy-");
+        assert_eq!(
+            shortened,
+            ["Shortened the user \"alice-smithy-the-third\" to \"alice-smithy\""]
+        );
+
+        // The user is omitted when there is no room left.
+        let prefix = "p".repeat(34);
+        let (name, shortened) = fit(Some(&prefix), "alice", 40);
+        assert_eq!(name, format!("{}-", "p".repeat(34)));
+        assert_eq!(shortened, ["Omitted the user \"alice\""]);
+
+        // The prefix is only shortened when it does not fit on its own.
+        let prefix = "p".repeat(40);
+        let (name, shortened) = fit(Some(&prefix), "alice", 40);
+        assert_eq!(name, format!("{}-", "p".repeat(34)));
+        assert_eq!(shortened.len(), 2, "{shortened:?}");
+
+        assert_eq!(
+            fit_generate_name(None, None, "ray-job", kubectl::MAX_NAME_LEN),
+            ("ray-job-".to_owned(), vec![])
+        );
+    }
+
+    #[test]
+    fn generate_names_of_awkward_users_are_labels() {
+        for user in crate::user_host::awkward_user_labels() {
+            for max_len in [kubectl::MAX_NAME_LEN, executor::EXPERIMENT_NAME_MAX_LEN] {
+                let (name, _) = fit_generate_name(Some("llm"), user.as_deref(), "job", max_len);
+                let generated = format!("{name}{}", "x".repeat(kubectl::GENERATED_SUFFIX_LEN));
+                assert!(is_rfc_1035_label(&generated), "{user:?} became {name:?}");
+                assert!(generated.len() <= max_len, "{user:?} became {name:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn dirty_image_tag_works() {
+        let tag = |user, random| dirty_image_tag(user, random).to_string();
+        assert_eq!(tag(Some("alice"), 0x8a3f9b22), "alice-8a3f9b22");
+        assert_eq!(tag(None, 0xa3f9b), "unknown-user-a3f9b");
+        assert_eq!(
+            tag(Some("alice@example.com"), 0xa3f9b),
+            "alice-example.com-a3f9b"
+        );
+        assert_eq!(tag(Some("Alice Smith"), 0xa3f9b), "Alice-Smith-a3f9b");
+        assert_eq!(tag(Some("ALICE"), 0xa3f9b), "ALICE-a3f9b");
+        assert_eq!(tag(Some("@ @"), 0xa3f9b), "unknown-user-a3f9b");
+        // A user that would make the tag too long is shortened.
+        let long = tag(Some(&format!("{}-b", "a".repeat(118))), u32::MAX);
+        assert_eq!(long, format!("{}-ffffffff", "a".repeat(118)));
+
+        for user in crate::user_host::awkward_user_labels() {
+            let tag = dirty_image_tag(user.as_deref(), u32::MAX);
+            assert!(
+                ImageName::builder("launch")
+                    .with_tag(tag.as_str())
+                    .build()
+                    .is_ok(),
+                "{user:?} became the invalid tag {tag:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn rewrite_registry_works() {
+        use RegistryRewrite::*;
+
+        let berkeley = ClusterContext::Berkeley.container_registry_host().unwrap();
+        let voltage_park = ClusterContext::VoltagePark
+            .container_registry_host()
+            .unwrap();
+        let registries = [berkeley, voltage_park];
+        // (image, rewrite, expected registry to copy to)
+        let table = [
+            (format!("{berkeley}/launch:abc"), Auto, Some(voltage_park)),
+            (
+                format!("{berkeley}/launch@sha256:{}", "0".repeat(64)),
+                Auto,
+                Some(voltage_park),
+            ),
+            (format!("{berkeley}/launch:abc"), Never, None),
+            (format!("{voltage_park}/launch:abc"), Auto, None),
+            (format!("{voltage_park}/launch:abc"), Force, None),
+            // Registries compare their domains case-insensitively.
+            (
+                format!("{}/launch:abc", berkeley.to_uppercase()),
+                Auto,
+                Some(voltage_park),
+            ),
+            (
+                format!("{}/launch:abc", voltage_park.to_uppercase()),
+                Force,
+                None,
+            ),
+            (
+                format!("{}:443/launch:abc", voltage_park.to_uppercase()),
+                Force,
+                Some(voltage_park),
+            ),
+            ("ghcr.io/org/tool:1.2.3".to_owned(), Auto, None),
+            (
+                "ghcr.io/org/tool:1.2.3".to_owned(),
+                Force,
+                Some(voltage_park),
+            ),
+            (
+                format!("ghcr.io/org/tool@sha256:{}", "0".repeat(64)),
+                Auto,
+                None,
+            ),
+            ("localhost:5000/tool:dev".to_owned(), Auto, None),
+            (
+                "localhost:5000/tool:dev".to_owned(),
+                Force,
+                Some(voltage_park),
+            ),
+            ("ubuntu:24.04".to_owned(), Auto, None),
+            ("ubuntu:24.04".to_owned(), Never, None),
+            ("ubuntu:24.04".to_owned(), Force, Some(voltage_park)),
+        ];
+        for (image, rewrite, expected) in table {
+            let image = ImageNameRef::new(&image).unwrap();
+            assert_eq!(
+                rewrite_registry(image.registry(), voltage_park, &registries, rewrite),
+                expected,
+                "{image} with {rewrite:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn external_image_keeps_its_registry_without_a_context_registry() {
+        let image = ImageNameRef::new("localhost:5000/tool:dev").unwrap();
+        for rewrite in [RegistryRewrite::Auto, RegistryRewrite::Never] {
+            assert_eq!(
+                external_image(image, &ClusterContext::Local, rewrite, false).unwrap(),
+                image.to_owned()
+            );
+        }
+        assert!(
+            external_image(image, &ClusterContext::Local, RegistryRewrite::Force, false).is_err()
+        );
+    }
+
+    #[test]
+    fn select_executor_works() {
+        use ExecutorArg as Arg;
+        use ExecutorKind as Kind;
+
+        let flag = |value| Resolved {
+            value,
+            source: config::Source::Flag("--workers"),
+        };
+        let ok = |kind| Ok::<_, &str>(kind);
+        let err = Err;
+        // (workers, --executor, --katib, expected result or part of the error message)
+        let table = [
+            (1, None, false, ok(Kind::Job)),
+            (4, None, false, ok(Kind::Ray)),
+            (1, Some(Arg::Job), false, ok(Kind::Job)),
+            (4, Some(Arg::Job), false, err("`--executor job` runs a single Pod and can not be combined with 4 workers set by the `--workers` flag")),
+            (1, Some(Arg::Ray), false, ok(Kind::Ray)),
+            (4, Some(Arg::Ray), false, ok(Kind::Ray)),
+            (1, None, true, ok(Kind::Katib)),
+            (4, None, true, err("`--katib` can not be combined with 4 workers set by the `--workers` flag")),
+            (1, Some(Arg::Job), true, err("`--executor job` can not be combined with `--katib`")),
+            (4, Some(Arg::Job), true, err("`--executor job` can not be combined with `--katib`")),
+            (1, Some(Arg::Ray), true, err("`--executor ray` can not be combined with `--katib`")),
+            (4, Some(Arg::Ray), true, err("`--executor ray` can not be combined with `--katib`")),
+        ];
+        for (workers, executor, katib, expected) in table {
+            let actual = select_executor(&flag(workers), executor, katib);
+            let case = format!("workers {workers}, executor {executor:?}, katib {katib}");
+            match (actual, expected) {
+                (Ok(actual), Ok(expected)) => assert_eq!(actual, expected, "{case}"),
+                (Err(actual), Err(expected)) => {
+                    assert!(actual.starts_with(expected), "{case}: {actual}")
+                }
+                (actual, expected) => panic!("{case}: expected {expected:?}, got {actual:?}"),
+            }
+        }
+
+        let file = Resolved {
+            value: 2,
+            source: config::Source::File(PathBuf::from("/repo/.launch.toml")),
+        };
+        assert!(select_executor(&file, None, true)
+            .unwrap_err()
+            .contains("2 workers set by the configuration file /repo/.launch.toml"));
+    }
+
+    #[test]
+    fn check_ray_options_works() {
+        let flags = |ray_version, ray_runtime_env, ray_worker_restart_policy| {
+            [
+                ("--ray-version", ray_version),
+                ("--ray-runtime-env", ray_runtime_env),
+                ("--ray-worker-restart-policy", ray_worker_restart_policy),
+            ]
+        };
+        assert!(check_ray_options(ExecutorKind::Job, &flags(false, false, false)).is_ok());
+        assert!(check_ray_options(ExecutorKind::Katib, &flags(false, false, false)).is_ok());
+        assert!(check_ray_options(ExecutorKind::Ray, &flags(true, true, true)).is_ok());
+        assert_eq!(
+            check_ray_options(ExecutorKind::Job, &flags(false, true, false)),
+            Err("`--ray-runtime-env` only applies to RayJobs. Pass `--executor ray` to run the command with Ray.".to_owned())
+        );
+        assert!(
+            check_ray_options(ExecutorKind::Katib, &flags(true, false, false))
+                .unwrap_err()
+                .starts_with("`--ray-version` only applies to RayJobs")
+        );
+        assert!(
+            check_ray_options(ExecutorKind::Job, &flags(false, false, true))
+                .unwrap_err()
+                .starts_with("`--ray-worker-restart-policy` only applies to RayJobs")
+        );
+    }
+
+    #[test]
+    fn check_schedule_works() {
+        assert!(check_schedule(ExecutorKind::Job).is_ok());
+        assert!(check_schedule(ExecutorKind::Ray)
+            .unwrap_err()
+            .contains("can not be combined with a RayJob"));
+        assert!(check_schedule(ExecutorKind::Katib)
+            .unwrap_err()
+            .contains("`--katib`"));
+    }
+
+    #[test]
+    fn check_progress_works() {
+        assert!(check_progress(ExecutorKind::Katib, BuilderArg::Docker, false).is_ok());
+        assert!(check_progress(ExecutorKind::Katib, BuilderArg::Docker, true).is_ok());
+        assert!(check_progress(ExecutorKind::Job, BuilderArg::Kaniko, false).is_ok());
+        assert!(check_progress(ExecutorKind::Ray, BuilderArg::Docker, false)
+            .unwrap_err()
+            .contains("Pass `--katib` or `--builder kaniko`"));
+        assert!(check_progress(ExecutorKind::Job, BuilderArg::Kaniko, true)
+            .unwrap_err()
+            .contains("`--image` skips the build"));
+    }
+
+    /// Parses the arguments of `launch submit`.
+    fn try_submit_args(args: &[&str]) -> Result<SubmitArgs, clap::Error> {
+        let cli = Cli::try_parse_from(["launch", "submit"].iter().chain(args))?;
+        match cli.command {
+            Commands::Submit(args) => Ok(*args),
+            command => panic!("{command:?}"),
+        }
+    }
+
+    /// See [`try_submit_args`].
+    #[cfg(unix)]
+    pub(super) fn submit_args(args: &[&str]) -> SubmitArgs {
+        try_submit_args(args).unwrap()
+    }
+
+    /// Accepts the `kubectl create --dry-run=server` of a Job, and fails every other invocation of kubectl.
+    #[cfg(unix)]
+    pub(super) const ACCEPT_DRY_RUN: &str = r#"case "$*" in
+    *"create --output=json --dry-run=server -f -")
+        cat > /dev/null
+        echo '{"metadata": {"namespace": "launch", "name": "job-x7k2p"}}' ;;
+    *) exit 1 ;;
+esac"#;
+
+    #[cfg(unix)]
+    #[test]
+    fn dry_run_server_only_validates() {
+        let kubectl = kubectl::fake::FakeKubectl::install(ACCEPT_DRY_RUN);
+        submit_with(
+            &[ClusterContext::Local],
+            submit_args(&[
+                "--image",
+                "docker.io/library/python:3.12",
+                "--dry-run=server",
+                "--databrickscfg-mode",
+                "omit",
+                "--",
+                "python",
+                "train.py",
+            ]),
+            &environment::tests::Counting::default(),
+            true,
+        )
+        .unwrap();
+        // The resource is validated once, and not created for real afterwards.
+        assert_eq!(
+            kubectl.invocations_of("create"),
+            ["create --output=json --dry-run=server -f -"]
+        );
+    }
+
+    #[test]
+    fn submit_args_conflicts_work() {
+        let parse = |args: &[&str]| {
+            try_submit_args(
+                &args
+                    .iter()
+                    .chain(&["--", "echo"])
+                    .copied()
+                    .collect::<Vec<_>>(),
+            )
+        };
+        assert!(parse(&["--executor", "ray"]).is_ok());
+        assert!(parse(&["--allow-secret-args"]).is_ok_and(|args| args.allow_secret_args));
+        assert!(parse(&["--executor", "ray", "--katib", "experiment.yaml"]).is_err());
+        assert!(parse(&["--ray-version", "2.40.0"]).is_ok());
+        assert!(parse(&["--ray-version", "2.40"]).is_err());
+        assert!(parse(&["--wait-for-capacity"]).is_ok());
+        assert!(parse(&["--wait-for-capacity", "2h"]).is_ok());
+        assert!(parse(&["--wait-for-capacity", "2"]).is_err());
+        assert!(parse(&["--wait-for-capacity", "--dry-run"]).is_err());
+        assert!(parse(&["--schedule", "0 3 * * *"]).is_ok_and(|args| args
+            .schedule
+            .unwrap()
+            .as_str()
+            == "0 3 * * *"));
+        assert!(parse(&["--schedule", "0 3 * *"]).is_err());
+        assert!(parse(&["--schedule", "@daily", "--dry-run"]).is_ok());
+        assert!(parse(&["--schedule", "@daily", "--notify", "https://example.com"]).is_err());
+        assert!(parse(&["--schedule", "@daily", "--wait-for-capacity"]).is_err());
+        assert!(parse(&["--schedule", "@daily", "--katib", "experiment.yaml"]).is_err());
+        assert!(parse(&["--comment", "lr sweep\nwith 🧪"])
+            .is_ok_and(|args| args.comment.as_deref() == Some("lr sweep\nwith 🧪")));
+        assert!(parse(&[
+            "--cpus",
+            "4",
+            "--cpus-limit",
+            "500m",
+            "--mem",
+            "16GiB",
+            "--mem-limit",
+            "32GiB"
+        ])
+        .is_ok());
+        assert!(parse(&["--cpus", "four"]).is_err());
+        assert!(parse(&["--cpus", "4", "--mem", "16GiB", "--limits-from-requests"]).is_ok());
+        assert!(parse(&["--cpus-limit", "4", "--limits-from-requests"]).is_err());
+        assert!(parse(&["--mem-limit", "16GiB", "--limits-from-requests"]).is_err());
+        assert!(parse(&[
+            "--image",
+            "ghcr.io/org/tool:1.2.3",
+            "--force-registry-rewrite"
+        ])
+        .is_ok());
+        assert!(parse(&["--image", "ghcr.io/org/tool:1.2.3", "--builder", "kaniko"]).is_err());
+        assert!(parse(&["--image", "ghcr.io/org/tool:1.2.3", "--force-rebuild"]).is_err());
+        assert!(parse(&["--force-registry-rewrite"]).is_err());
+        assert!(parse(&[
+            "--image",
+            "ubuntu:24.04",
+            "--no-registry-rewrite",
+            "--force-registry-rewrite"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn submit_settings_resolve_works() {
+        let path = Path::new("/repo/.launch.toml");
+        let file = config::Source::File(path.to_owned());
+        let defaults = SubmitDefaults {
+            builder: Some(BuilderArg::Kaniko),
+            gpus: Some(8),
+            gpu_mem: Some(80),
+            workers: None,
+            name_prefix: Some("llm".to_owned()),
+            databrickscfg_mode: Some(databricks::Mode::Omit),
+        };
+        let no_flags = || SubmitFlags {
+            builder: None,
+            gpus: None,
+            gpu_mem: None,
+            workers: None,
+            name_prefix: None,
+            databrickscfg_mode: None,
+        };
+
+        // Without a configuration file, the defaults apply.
+        let settings = SubmitSettings::resolve(no_flags(), None);
+        assert_eq!(settings.builder.value, BuilderArg::Docker);
+        assert_eq!(settings.gpus.value, 0);
+        assert_eq!(settings.gpu_mem.value, None);
+        assert_eq!(settings.workers.value, 1);
+        assert_eq!(settings.name_prefix.value, None);
+        assert_eq!(settings.databrickscfg_mode.value, databricks::Mode::Auto);
+        assert!(settings.summary().contains("gpus: 0 (set by the default)"));
+
+        // The configuration file overrides the defaults, except for the keys it does not set.
+        let settings = SubmitSettings::resolve(no_flags(), Some((path, &defaults)));
+        assert_eq!(
+            settings.builder,
+            Resolved {
+                value: BuilderArg::Kaniko,
+                source: file.clone()
+            }
+        );
+        assert_eq!(
+            settings.gpus,
+            Resolved {
+                value: 8,
+                source: file.clone()
+            }
+        );
+        assert_eq!(settings.gpu_mem.value, Bytes::new::<bytes::gibibyte>(80));
+        assert_eq!(
+            settings.workers,
+            Resolved {
+                value: 1,
+                source: config::Source::Default
+            }
+        );
+        assert_eq!(settings.name_prefix.value.as_deref(), Some("llm"));
+        assert_eq!(settings.databrickscfg_mode.value, databricks::Mode::Omit);
+        let summary = settings.summary();
+        assert!(
+            summary.contains("builder: kaniko (set by the configuration file /repo/.launch.toml)"),
+            "{summary}"
+        );
+        assert!(summary.contains("gpu_mem: 80GiB"), "{summary}");
+
+        // Flags override the configuration file, also when they equal the default.
+        let settings = SubmitSettings::resolve(
+            SubmitFlags {
+                builder: Some(BuilderArg::Docker),
+                gpus: Some(0),
+                gpu_mem: Bytes::new::<bytes::gibibyte>(40),
+                workers: Some(2),
+                name_prefix: Some("test".to_owned()),
+                databrickscfg_mode: Some(databricks::Mode::Auto),
+            },
+            Some((path, &defaults)),
+        );
+        assert_eq!(
+            settings.builder,
+            Resolved {
+                value: BuilderArg::Docker,
+                source: config::Source::Flag("--builder")
+            }
+        );
+        assert_eq!(
+            settings.gpus,
+            Resolved {
+                value: 0,
+                source: config::Source::Flag("--gpus")
+            }
+        );
+        assert_eq!(settings.gpu_mem.value, Bytes::new::<bytes::gibibyte>(40));
+        assert_eq!(settings.workers.value, 2);
+        assert_eq!(settings.name_prefix.value.as_deref(), Some("test"));
+        assert_eq!(
+            settings.databrickscfg_mode,
+            Resolved {
+                value: databricks::Mode::Auto,
+                source: config::Source::Flag("--databrickscfg-mode")
+            }
+        );
+    }
+
+    #[test]
+    fn check_git_provenance_works() {
+        let problem = "The current commit has not been pushed";
+
+        assert!(check_git_provenance(
+            &policy(StrictGit::Off, config::Source::Default),
+            problem,
+            "--allow-unpushed"
+        )
+        .is_ok());
+
+        assert!(check_git_provenance(
+            &policy(StrictGit::Warn, config::Source::Default),
+            problem,
+            "--allow-unpushed"
+        )
+        .is_ok());
+
+        let error = check_git_provenance(
+            &policy(
+                StrictGit::Error,
+                config::Source::File(Path::new("/c/launch/berkeley.yaml").to_owned()),
+            ),
+            problem,
+            "--allow-unpushed",
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.starts_with(problem), "{error}");
+        assert!(
+            error.contains("set to `error` by the configuration file /c/launch/berkeley.yaml"),
+            "{error}"
+        );
+        assert!(error.contains("`--allow-unpushed`"), "{error}");
+    }
+
+    #[test]
+    fn best_effort_warning_works() {
+        let warning = best_effort_warning(Some(Path::new("/c/launch/berkeley.yaml")));
+        assert!(warning.contains("BestEffort"), "{warning}");
+        assert!(
+            warning.contains("set `default_resources` in /c/launch/berkeley.yaml"),
+            "{warning}"
+        );
+        let warning = best_effort_warning(None);
+        assert!(warning.ends_with("Pass `--cpus` or `--mem`."), "{warning}");
+    }
 }
@@ -0,0 +1,298 @@
+//! What a submission needs to know about the submitting machine. It is resolved once per invocation and shared by the
+//! submissions to all contexts, because querying tailscale and git takes seconds and the tailscale CLI rate limits.
+//! `launch agent` shares it between submissions as well through [`Cached`].
+
+use std::{
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+};
+
+use home::home_dir;
+use log::debug;
+
+use crate::{
+    databricks, git,
+    user_host::{self, UserHost},
+    Result,
+};
+
+const NO_HOME_DIR: &str = "Failed to determine the home directory, which is looked up through the HOME environment variable (USERPROFILE on Windows) and falls back to the user database. Set HOME to locate the databricks configuration, or pass `--databrickscfg-mode omit`.";
+
+/// Queries the submitting machine. Tests replace it to count the queries.
+pub trait Sources {
+    fn machine_user_host(&self) -> UserHost;
+
+    fn tailscale_user_host(&self) -> Option<UserHost>;
+
+    fn git_info(&self) -> Result<git::GitInfo>;
+
+    /// The commit hash and the status of the working tree, which are cheaper to query than [`Sources::git_info`].
+    fn git_head(&self) -> Result<(String, git::Status)>;
+
+    fn home_dir(&self) -> Option<PathBuf>;
+}
+
+/// The sources of this machine.
+pub struct System;
+
+impl Sources for System {
+    fn machine_user_host(&self) -> UserHost {
+        user_host::machine_user_host()
+    }
+
+    fn tailscale_user_host(&self) -> Option<UserHost> {
+        user_host::tailscale_user_host()
+    }
+
+    fn git_info(&self) -> Result<git::GitInfo> {
+        git::info()
+    }
+
+    fn git_head(&self) -> Result<(String, git::Status)> {
+        git::head()
+    }
+
+    fn home_dir(&self) -> Option<PathBuf> {
+        home_dir()
+    }
+}
+
+/// Queries the identity of the user once, and the git information again only when the commit or the status of the
+/// working tree changed, or the commit was not pushed yet. Images are tagged with the commit, so a stale commit would
+/// reuse an image of older code.
+pub struct Cached<S> {
+    sources: S,
+    machine_user_host: OnceLock<UserHost>,
+    tailscale_user_host: OnceLock<Option<UserHost>>,
+    git_info: Mutex<Option<git::GitInfo>>,
+}
+
+impl<S> Cached<S> {
+    pub fn new(sources: S) -> Self {
+        Self {
+            sources,
+            machine_user_host: OnceLock::new(),
+            tailscale_user_host: OnceLock::new(),
+            git_info: Mutex::new(None),
+        }
+    }
+}
+
+impl<S: Sources> Sources for Cached<S> {
+    fn machine_user_host(&self) -> UserHost {
+        self.machine_user_host
+            .get_or_init(|| self.sources.machine_user_host())
+            .clone()
+    }
+
+    fn tailscale_user_host(&self) -> Option<UserHost> {
+        self.tailscale_user_host
+            .get_or_init(|| self.sources.tailscale_user_host())
+            .clone()
+    }
+
+    fn git_info(&self) -> Result<git::GitInfo> {
+        let mut cached = self.git_info.lock().unwrap();
+        if let Some(git_info) = cached.as_ref() {
+            let (commit_hash, status) = self.sources.git_head()?;
+            if git_info.is_pushed
+                && git_info.commit_hash == commit_hash
+                && git_info.status == status
+            {
+                return Ok(git_info.clone());
+            }
+            debug!("The git repository changed, querying it again");
+        }
+        let git_info = self.sources.git_info()?;
+        *cached = Some(git_info.clone());
+        Ok(git_info)
+    }
+
+    fn git_head(&self) -> Result<(String, git::Status)> {
+        self.sources.git_head()
+    }
+
+    fn home_dir(&self) -> Option<PathBuf> {
+        self.sources.home_dir()
+    }
+}
+
+pub struct SubmitEnvironment {
+    pub machine_user_host: UserHost,
+    pub tailscale_user_host: Option<UserHost>,
+    /// The user label, see [`user_host::user_label`]. It does not depend on the context.
+    pub user: Option<String>,
+    pub git_info: git::GitInfo,
+    /// The databricks configuration to mount, see [`databricks::config_path`].
+    pub databrickscfg_path: Option<PathBuf>,
+}
+
+impl SubmitEnvironment {
+    /// Queries each source once. The home directory is only needed to locate the databricks configuration.
+    pub fn resolve(sources: &impl Sources, databrickscfg_mode: databricks::Mode) -> Result<Self> {
+        let machine_user_host = sources.machine_user_host();
+        let tailscale_user_host = sources.tailscale_user_host();
+        let (user, _) = user_host::user_label(&machine_user_host, tailscale_user_host.as_ref());
+        let git_info = sources.git_info()?;
+        let databrickscfg_path = match databrickscfg_mode {
+            databricks::Mode::Omit => None,
+            mode => databricks::config_path(&sources.home_dir().ok_or(NO_HOME_DIR)?, mode)?,
+        };
+        Ok(Self {
+            machine_user_host,
+            tailscale_user_host,
+            user,
+            git_info,
+            databrickscfg_path,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    /// Counts the queries of each source.
+    #[derive(Default)]
+    pub(in crate::cli::submit) struct Counting {
+        machine_user_host: Cell<usize>,
+        tailscale_user_host: Cell<usize>,
+        git_info: Cell<usize>,
+        home_dir: Cell<usize>,
+        home: Option<PathBuf>,
+        /// The number of modified files that the working tree has.
+        modified: Cell<usize>,
+        unpushed: bool,
+    }
+
+    fn increment(count: &Cell<usize>) {
+        count.set(count.get() + 1);
+    }
+
+    impl Sources for Counting {
+        fn machine_user_host(&self) -> UserHost {
+            increment(&self.machine_user_host);
+            UserHost::new("root".to_owned(), Some("devbox".to_owned()))
+        }
+
+        fn tailscale_user_host(&self) -> Option<UserHost> {
+            increment(&self.tailscale_user_host);
+            Some(UserHost::parse("ada@example.com"))
+        }
+
+        fn git_info(&self) -> Result<git::GitInfo> {
+            increment(&self.git_info);
+            let (commit_hash, status) = self.git_head()?;
+            Ok(git::GitInfo {
+                dir: PathBuf::from("/repo/.git"),
+                commit_hash,
+                push_remote_url: None,
+                push_remote: None,
+                status,
+                is_pushed: !self.unpushed,
+            })
+        }
+
+        fn git_head(&self) -> Result<(String, git::Status)> {
+            Ok((
+                "0123456789abcdef0123456789abcdef01234567".to_owned(),
+                git::Status {
+                    modified: self.modified.get(),
+                    ..Default::default()
+                },
+            ))
+        }
+
+        fn home_dir(&self) -> Option<PathBuf> {
+            increment(&self.home_dir);
+            self.home.clone()
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_queries_each_source_once_for_all_contexts() {
+        use crate::{
+            cli::{
+                submit::{
+                    submit_with,
+                    tests::{submit_args, ACCEPT_DRY_RUN},
+                },
+                ClusterContext,
+            },
+            kubectl::fake::FakeKubectl,
+        };
+
+        let kubectl = FakeKubectl::install(ACCEPT_DRY_RUN);
+        let sources = Counting {
+            home: Some(PathBuf::from("/nonexistent/home")),
+            ..Default::default()
+        };
+        submit_with(
+            &[ClusterContext::Local; 3],
+            submit_args(&[
+                "--image",
+                "docker.io/library/python:3.12",
+                "--dry-run=server",
+                "--",
+                "python",
+                "train.py",
+            ]),
+            &sources,
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(kubectl.invocations_of("create").len(), 3);
+        for (source, count) in [
+            ("machine_user_host", &sources.machine_user_host),
+            ("tailscale_user_host", &sources.tailscale_user_host),
+            ("git_info", &sources.git_info),
+            ("home_dir", &sources.home_dir),
+        ] {
+            assert_eq!(count.get(), 1, "{source}");
+        }
+    }
+
+    #[test]
+    fn resolve_only_needs_the_home_dir_for_databricks() {
+        let sources = Counting::default();
+        assert!(SubmitEnvironment::resolve(&sources, databricks::Mode::Omit).is_ok());
+        assert_eq!(sources.home_dir.get(), 0);
+
+        let error = SubmitEnvironment::resolve(&sources, databricks::Mode::Auto)
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), NO_HOME_DIR);
+    }
+
+    #[test]
+    fn cached_queries_git_again_when_the_repository_changed() {
+        let cached = Cached::new(Counting::default());
+        for _ in 0..3 {
+            SubmitEnvironment::resolve(&cached, databricks::Mode::Omit).unwrap();
+        }
+        assert_eq!(cached.sources.machine_user_host.get(), 1);
+        assert_eq!(cached.sources.tailscale_user_host.get(), 1);
+        assert_eq!(cached.sources.git_info.get(), 1);
+
+        cached.sources.modified.set(2);
+        let environment = SubmitEnvironment::resolve(&cached, databricks::Mode::Omit).unwrap();
+        assert_eq!(environment.git_info.status.modified, 2);
+        assert_eq!(cached.sources.git_info.get(), 2);
+        SubmitEnvironment::resolve(&cached, databricks::Mode::Omit).unwrap();
+        assert_eq!(cached.sources.git_info.get(), 2);
+
+        // The commit may be pushed in the meantime.
+        let cached = Cached::new(Counting {
+            unpushed: true,
+            ..Default::default()
+        });
+        for _ in 0..2 {
+            SubmitEnvironment::resolve(&cached, databricks::Mode::Omit).unwrap();
+        }
+        assert_eq!(cached.sources.git_info.get(), 2);
+    }
+}
@@ -0,0 +1,229 @@
+//! Submitting the same work to multiple cluster contexts in one invocation. The contexts are generic so that the
+//! orchestration can be tested without clusters, registries or docker.
+
+use std::fmt;
+
+use log::{error, info};
+
+use crate::{cli::summary::Summary, Result};
+
+/// Makes the image available in the registry of a context.
+pub trait ImagePublisher<C> {
+    /// Builds the image, pushes it to the registry of `context` and returns its digest.
+    fn build(&mut self, context: C) -> Result<String>;
+
+    /// Copies the image with `digest` from the registry of `source` to the registry of `target`, keeping the digest.
+    fn copy(&mut self, source: C, digest: &str, target: C) -> Result<()>;
+}
+
+/// Decides whether the image for a context is built or copied from the registry of a context it was built for.
+pub struct ImageDistribution<C> {
+    /// Whether the registries can be reached from this machine so that an image can be copied between them. Images
+    /// built by kaniko are pushed to the cluster-local registry and are rebuilt for every context instead.
+    copyable: bool,
+    /// The first context the image was successfully built for, along with its digest.
+    built: Option<(C, String)>,
+}
+
+impl<C: Copy> ImageDistribution<C> {
+    pub fn new(copyable: bool) -> Self {
+        Self {
+            copyable,
+            built: None,
+        }
+    }
+
+    /// Returns the digest of the image in the registry of `context`. The image is built at most once when it can be
+    /// copied. A failed build is retried for the next context, which may be able to reach its own registry.
+    pub fn digest_for(
+        &mut self,
+        context: C,
+        publisher: &mut impl ImagePublisher<C>,
+    ) -> Result<String> {
+        match &self.built {
+            Some((source, digest)) if self.copyable => {
+                publisher.copy(*source, digest, context)?;
+                Ok(digest.clone())
+            }
+            _ => {
+                let digest = publisher.build(context)?;
+                self.built.get_or_insert_with(|| (context, digest.clone()));
+                Ok(digest)
+            }
+        }
+    }
+}
+
+/// Submits to the contexts in order. A failure is logged and does not prevent or undo the submissions to the other
+/// contexts. The output of each context is preceded by a line naming it when there are multiple contexts.
+pub fn submit_each<C: Copy + fmt::Display, T>(
+    contexts: &[C],
+    mut submit: impl FnMut(C) -> Result<T>,
+) -> Vec<(C, Result<T>)> {
+    let multiple = contexts.len() > 1;
+    contexts
+        .iter()
+        .map(|&context| {
+            if multiple {
+                info!("[{context}] Submitting...");
+            }
+            let result = submit(context);
+            if let (true, Err(error)) = (multiple, &result) {
+                error!("[{context}] {error}");
+            }
+            (context, result)
+        })
+        .collect()
+}
+
+/// Collects the outcome of the submission to each context, described by `describe` when it succeeded.
+pub fn summary<C: fmt::Display, T>(
+    results: &[(C, Result<T>)],
+    describe: impl Fn(&C, &T) -> String,
+) -> Summary {
+    let mut summary = Summary::new("submit to", "contexts");
+    for (context, result) in results {
+        summary.record(context.to_string(), "submit", result, |value| {
+            Some(describe(context, value))
+        });
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::summary::Outcome;
+
+    /// Records the calls and fails the builds and copies for the contexts in `unreachable`.
+    #[derive(Default)]
+    struct FakePublisher {
+        unreachable: Vec<&'static str>,
+        calls: Vec<String>,
+    }
+
+    impl FakePublisher {
+        fn check(&self, context: &str) -> Result<()> {
+            if self.unreachable.contains(&context) {
+                return Err(format!("registry of {context} is unreachable").into());
+            }
+            Ok(())
+        }
+    }
+
+    impl ImagePublisher<&'static str> for FakePublisher {
+        fn build(&mut self, context: &'static str) -> Result<String> {
+            self.calls.push(format!("build {context}"));
+            self.check(context)?;
+            Ok(format!("sha256:{context}"))
+        }
+
+        fn copy(&mut self, source: &'static str, digest: &str, target: &'static str) -> Result<()> {
+            self.calls.push(format!("copy {digest} {source} {target}"));
+            self.check(target)
+        }
+    }
+
+    fn distribute(
+        copyable: bool,
+        unreachable: &[&'static str],
+        contexts: &[&'static str],
+    ) -> (Vec<String>, Vec<String>) {
+        let mut publisher = FakePublisher {
+            unreachable: unreachable.to_vec(),
+            ..Default::default()
+        };
+        let mut distribution = ImageDistribution::new(copyable);
+        let digests = contexts
+            .iter()
+            .map(
+                |&context| match distribution.digest_for(context, &mut publisher) {
+                    Ok(digest) => digest,
+                    Err(error) => error.to_string(),
+                },
+            )
+            .collect();
+        (digests, publisher.calls)
+    }
+
+    #[test]
+    fn image_distribution_builds_once_when_copyable() {
+        let (digests, calls) = distribute(true, &[], &["a", "b", "c"]);
+        assert_eq!(digests, ["sha256:a"; 3]);
+        assert_eq!(calls, ["build a", "copy sha256:a a b", "copy sha256:a a c"]);
+    }
+
+    #[test]
+    fn image_distribution_builds_per_context_when_not_copyable() {
+        let (digests, calls) = distribute(false, &[], &["a", "b"]);
+        assert_eq!(digests, ["sha256:a", "sha256:b"]);
+        assert_eq!(calls, ["build a", "build b"]);
+    }
+
+    #[test]
+    fn image_distribution_retries_failed_builds() {
+        let (digests, calls) = distribute(true, &["a", "b"], &["a", "b", "c"]);
+        assert_eq!(
+            digests,
+            [
+                "registry of a is unreachable",
+                "registry of b is unreachable",
+                "sha256:c"
+            ]
+        );
+        assert_eq!(calls, ["build a", "build b", "build c"]);
+
+        // A failed copy does not affect the other contexts.
+        let (digests, calls) = distribute(true, &["b"], &["a", "b", "c"]);
+        assert_eq!(
+            digests,
+            ["sha256:a", "registry of b is unreachable", "sha256:a"]
+        );
+        assert_eq!(calls, ["build a", "copy sha256:a a b", "copy sha256:a a c"]);
+    }
+
+    #[test]
+    fn submit_each_continues_after_failures() {
+        let mut submitted = Vec::new();
+        let results = submit_each(&["a", "b", "c"], |context| {
+            submitted.push(context);
+            if context == "b" {
+                return Err("cluster is down".into());
+            }
+            Ok(format!("job-{context}"))
+        });
+        assert_eq!(submitted, ["a", "b", "c"]);
+        let summary = summary(&results, |_, name| name.clone());
+        assert_eq!(
+            summary
+                .entries()
+                .iter()
+                .map(|entry| (entry.target.as_str(), &entry.outcome))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    "a",
+                    &Outcome::Succeeded {
+                        detail: Some("job-a".to_owned())
+                    }
+                ),
+                (
+                    "b",
+                    &Outcome::Failed {
+                        error: "cluster is down".to_owned()
+                    }
+                ),
+                (
+                    "c",
+                    &Outcome::Succeeded {
+                        detail: Some("job-c".to_owned())
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            summary.into_result().unwrap_err().to_string(),
+            "Failed to submit to 1 of 3 contexts"
+        );
+    }
+}
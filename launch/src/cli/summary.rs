@@ -0,0 +1,300 @@
+//! The accounting printed after a command that acts on several resources, such as deleting images with `launch clean`
+//! or submitting to multiple contexts. Scripts can tell complete success from partial and total failure by the exit
+//! code, see [`Status::exit_code`], or read the outcome of each target from `--output json`.
+
+use std::io;
+
+use clap::ValueEnum;
+
+use crate::Result;
+
+/// The exit code when some but not all targets failed. Distinct from 1, which any other error exits with, and from 2,
+/// which clap exits with for invalid arguments.
+pub const PARTIAL_FAILURE_EXIT_CODE: i32 = 3;
+
+#[derive(Debug, Default, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum OutputArg {
+    /// Print a human readable table.
+    #[default]
+    Table,
+    /// Print the summary to stdout as a single line of JSON.
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum Outcome {
+    Succeeded {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        detail: Option<String>,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Entry {
+    pub target: String,
+    pub action: String,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// All targets succeeded, or there were none.
+    Success,
+    /// Some targets failed and the others succeeded.
+    PartialFailure,
+    /// All targets failed.
+    Failure,
+}
+
+impl Status {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Status::Success => 0,
+            Status::PartialFailure => PARTIAL_FAILURE_EXIT_CODE,
+            Status::Failure => 1,
+        }
+    }
+}
+
+/// The error of a command whose summary has failures. Its [`Status`] decides the exit code, see [`exit_code`].
+#[derive(Debug)]
+pub struct Failed {
+    pub status: Status,
+    message: String,
+}
+
+impl std::fmt::Display for Failed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Failed {}
+
+/// Returns the exit code for the error of a command: the one of its summary for [`Failed`], and 1 otherwise.
+pub fn exit_code(error: &(dyn std::error::Error + 'static)) -> i32 {
+    error
+        .downcast_ref::<Failed>()
+        .map_or(1, |failed| failed.status.exit_code())
+}
+
+/// Collects the outcome of each target of an operation.
+#[derive(Debug)]
+pub struct Summary {
+    /// What was done to the targets, such as `delete` in "Failed to delete 2 of 5 manifests".
+    verb: &'static str,
+    /// What the targets are, such as `manifests`.
+    noun: &'static str,
+    entries: Vec<Entry>,
+}
+
+impl Summary {
+    pub fn new(verb: &'static str, noun: &'static str) -> Self {
+        Self {
+            verb,
+            noun,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn record<T>(
+        &mut self,
+        target: impl Into<String>,
+        action: impl Into<String>,
+        result: &Result<T>,
+        detail: impl FnOnce(&T) -> Option<String>,
+    ) {
+        let outcome = match result {
+            Ok(value) => Outcome::Succeeded {
+                detail: detail(value),
+            },
+            Err(error) => Outcome::Failed {
+                error: error.to_string(),
+            },
+        };
+        self.entries.push(Entry {
+            target: target.into(),
+            action: action.into(),
+            outcome,
+        });
+    }
+
+    #[cfg(test)]
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    fn failed(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| matches!(entry.outcome, Outcome::Failed { .. }))
+            .count()
+    }
+
+    pub fn status(&self) -> Status {
+        match self.failed() {
+            0 => Status::Success,
+            failed if failed == self.entries.len() => Status::Failure,
+            _ => Status::PartialFailure,
+        }
+    }
+
+    /// Renders the table of the targets along with a line that counts the successes.
+    pub fn table(&self) -> String {
+        let mut table = comfy_table::Table::new();
+        table
+            .load_preset(comfy_table::presets::UTF8_FULL)
+            .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+            .set_header(["target", "action", "outcome"].map(|name| {
+                comfy_table::Cell::new(name).add_attribute(comfy_table::Attribute::Bold)
+            }));
+        for entry in &self.entries {
+            let outcome = match &entry.outcome {
+                Outcome::Succeeded { detail: None } => "done".to_owned(),
+                Outcome::Succeeded {
+                    detail: Some(detail),
+                } => format!("done: {detail}"),
+                Outcome::Failed { error } => format!("failed: {error}"),
+            };
+            table.add_row([entry.target.as_str(), entry.action.as_str(), &outcome]);
+        }
+        let total = self.entries.len();
+        format!(
+            "{table}\n{} of {total} {} succeeded.",
+            total - self.failed(),
+            self.noun
+        )
+    }
+
+    pub fn json(&self) -> Result<String> {
+        #[derive(serde::Serialize)]
+        struct Json<'a> {
+            status: Status,
+            succeeded: usize,
+            failed: usize,
+            entries: &'a [Entry],
+        }
+        let failed = self.failed();
+        Ok(serde_json::to_string(&Json {
+            status: self.status(),
+            succeeded: self.entries.len() - failed,
+            failed,
+            entries: &self.entries,
+        })?)
+    }
+
+    /// Prints the summary to stdout. The table is left out when there are no targets.
+    pub fn print(&self, output: OutputArg) -> Result<()> {
+        self.write(output, &mut io::stdout().lock())
+    }
+
+    /// Writes the summary the way [`Summary::print`] prints it.
+    pub fn write(&self, output: OutputArg, out: &mut dyn io::Write) -> Result<()> {
+        match output {
+            OutputArg::Table if self.entries.is_empty() => {}
+            OutputArg::Table => writeln!(out, "{}", self.table())?,
+            OutputArg::Json => writeln!(out, "{}", self.json()?)?,
+        }
+        Ok(())
+    }
+
+    /// Fails with a [`Failed`] error when any target failed.
+    pub fn into_result(self) -> Result<()> {
+        let status = self.status();
+        if status == Status::Success {
+            return Ok(());
+        }
+        Err(Box::new(Failed {
+            status,
+            message: format!(
+                "Failed to {} {} of {} {}",
+                self.verb,
+                self.failed(),
+                self.entries.len(),
+                self.noun
+            ),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(results: &[Result<(), &str>]) -> Summary {
+        let mut summary = Summary::new("delete", "manifests");
+        for (index, result) in results.iter().enumerate() {
+            let result = result.map_err(Into::into);
+            summary.record(format!("launch@sha256:{index}"), "delete", &result, |_| {
+                None
+            });
+        }
+        summary
+    }
+
+    fn exit_code_of(results: &[Result<(), &str>]) -> i32 {
+        match summary(results).into_result() {
+            Ok(()) => 0,
+            Err(error) => exit_code(&*error),
+        }
+    }
+
+    #[test]
+    fn exit_code_reflects_the_outcomes() {
+        assert_eq!(exit_code_of(&[]), 0);
+        assert_eq!(exit_code_of(&[Ok(()), Ok(())]), 0);
+        assert_eq!(exit_code_of(&[Ok(()), Err("not found")]), 3);
+        assert_eq!(exit_code_of(&[Err("not found"), Ok(())]), 3);
+        assert_eq!(exit_code_of(&[Err("not found")]), 1);
+        assert_eq!(exit_code_of(&[Err("not found"), Err("forbidden")]), 1);
+
+        // Other errors keep exiting with 1.
+        let error: Box<dyn std::error::Error + Send + Sync> = "cluster is down".into();
+        assert_eq!(exit_code(&*error), 1);
+    }
+
+    #[test]
+    fn into_result_counts_the_failures() {
+        assert_eq!(
+            summary(&[Ok(()), Err("not found"), Err("forbidden")])
+                .into_result()
+                .unwrap_err()
+                .to_string(),
+            "Failed to delete 2 of 3 manifests"
+        );
+    }
+
+    #[test]
+    fn json_works() {
+        let mut summary = Summary::new("submit to", "contexts");
+        summary.record("berkeley", "submit", &Ok(()), |_| {
+            Some("https://example.com/job-a".to_owned())
+        });
+        summary.record::<()>("staging", "submit", &Err("cluster is down".into()), |_| {
+            None
+        });
+        assert_eq!(summary.status(), Status::PartialFailure);
+        assert_eq!(
+            summary.json().unwrap(),
+            concat!(
+                r#"{"status":"partial_failure","succeeded":1,"failed":1,"entries":["#,
+                r#"{"target":"berkeley","action":"submit","outcome":"succeeded","detail":"https://example.com/job-a"},"#,
+                r#"{"target":"staging","action":"submit","outcome":"failed","error":"cluster is down"}]}"#
+            )
+        );
+    }
+
+    #[test]
+    fn table_lists_every_target() {
+        let table = summary(&[Ok(()), Err("not found")]).table();
+        assert!(table.contains("launch@sha256:0"), "{table}");
+        assert!(table.contains("failed: not found"), "{table}");
+        assert!(table.ends_with("\n1 of 2 manifests succeeded."), "{table}");
+    }
+}
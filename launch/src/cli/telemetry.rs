@@ -0,0 +1,407 @@
+//! Anonymous usage counts, which are only recorded after opting in with `telemetry: local` in `defaults.yaml` in the
+//! [directory of launch](super::config::dir). Each command appends a line to `telemetry.jsonl` next to it with the
+//! subcommand, the names of the flags that were passed, how long it took and how it ended. Flag values are never
+//! recorded and nothing is sent anywhere.
+
+use std::{
+    collections::BTreeMap,
+    io::Write as _,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::{parser::ValueSource, ArgMatches, Args, Command, Subcommand};
+use log::debug;
+
+use super::{
+    config::{self, Defaults},
+    summary,
+};
+use crate::Result;
+
+const FILE_NAME: &str = "telemetry.jsonl";
+
+/// The outcome of a command that succeeded, see [`outcome`] for those of failures.
+const SUCCESS: &str = "success";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Telemetry {
+    /// Do not record anything.
+    #[default]
+    Off,
+    /// Record usage counts in a local file.
+    Local,
+}
+
+#[derive(Debug, Args)]
+pub struct TelemetryArgs {
+    #[command(subcommand)]
+    command: TelemetryCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum TelemetryCommand {
+    /// Summarize the recorded usage
+    Show,
+    /// Delete the recorded usage
+    Clear,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct Event {
+    /// The subcommand, such as `submit`.
+    command: String,
+    /// The long names of the flags that were passed on the command line, such as `--dry-run`.
+    flags: Vec<String>,
+    duration_ms: u64,
+    /// `success` or the category of the error, see [`outcome`].
+    outcome: String,
+}
+
+pub fn path() -> Option<PathBuf> {
+    Some(config::dir()?.join(FILE_NAME))
+}
+
+/// Categorizes how a command ended by the first error in the chain of sources that has a known type. Most errors of
+/// launch are messages, which fall into the `other` category. A command that failed for only some of its targets is
+/// `partial`, see [`summary::Status::PartialFailure`].
+pub fn outcome(result: &Result<()>) -> &'static str {
+    let Err(error) = result else {
+        return SUCCESS;
+    };
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(error.as_ref());
+    while let Some(error) = source {
+        if error
+            .downcast_ref::<summary::Failed>()
+            .is_some_and(|failed| failed.status == summary::Status::PartialFailure)
+        {
+            return "partial";
+        }
+        if error.is::<reqwest::Error>() {
+            return "network";
+        }
+        if error.is::<std::io::Error>() {
+            return "io";
+        }
+        if error.is::<serde_json::Error>() || error.is::<serde_yaml::Error>() {
+            return "parse";
+        }
+        source = error.source();
+    }
+    "other"
+}
+
+/// Returns the names of the flags of `command` that were passed on the command line, leaving out positional arguments
+/// because those are values.
+fn passed_flags(command: &Command, matches: &ArgMatches) -> Vec<String> {
+    command
+        .get_arguments()
+        .filter(|arg| !arg.is_positional())
+        .filter(|arg| matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine))
+        .filter_map(|arg| {
+            arg.get_long()
+                .map(|long| format!("--{long}"))
+                .or_else(|| arg.get_short().map(|short| format!("-{short}")))
+        })
+        .collect()
+}
+
+fn event(command: &Command, matches: &ArgMatches, duration: Duration, outcome: &str) -> Event {
+    let mut names = Vec::new();
+    let mut flags = passed_flags(command, matches);
+    let (mut command, mut matches) = (command, matches);
+    while let Some((name, subcommand_matches)) = matches.subcommand() {
+        let Some(subcommand) = command.find_subcommand(name) else {
+            break;
+        };
+        names.push(name);
+        flags.extend(passed_flags(subcommand, subcommand_matches));
+        (command, matches) = (subcommand, subcommand_matches);
+    }
+    flags.sort();
+    flags.dedup();
+    Event {
+        command: names.join(" "),
+        flags,
+        duration_ms: u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+        outcome: outcome.to_owned(),
+    }
+}
+
+fn append(path: &Path, event: &Event) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut line = serde_json::to_string(event)?;
+    line.push('\n');
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// Records the usage of a command when telemetry is enabled. Telemetry must never affect the command, so failures are
+/// only logged at the debug level.
+pub fn record(command: &Command, matches: &ArgMatches, duration: Duration, result: &Result<()>) {
+    let enabled = Defaults::path()
+        .and_then(|path| Defaults::load(&path).ok().flatten())
+        .and_then(|defaults| defaults.telemetry)
+        .unwrap_or_default();
+    if enabled != Telemetry::Local {
+        return;
+    }
+    let Some(path) = path() else {
+        return;
+    };
+    if let Err(error) = append(&path, &event(command, matches, duration, outcome(result))) {
+        debug!("Unable to record usage in {}: {error}", path.display());
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct CommandSummary {
+    runs: usize,
+    total_duration_ms: u64,
+    /// The number of failures per category.
+    failures: BTreeMap<String, usize>,
+    /// The number of runs per flag.
+    flags: BTreeMap<String, usize>,
+}
+
+/// Summarizes the events per command. Lines that can not be parsed, for example those written by another version of
+/// launch, are skipped.
+fn summarize(contents: &str) -> BTreeMap<String, CommandSummary> {
+    let mut summaries = BTreeMap::<String, CommandSummary>::new();
+    for event in contents
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Event>(line).ok())
+    {
+        let summary = summaries.entry(event.command).or_default();
+        summary.runs += 1;
+        summary.total_duration_ms = summary.total_duration_ms.saturating_add(event.duration_ms);
+        if event.outcome != SUCCESS {
+            *summary.failures.entry(event.outcome).or_default() += 1;
+        }
+        for flag in event.flags {
+            *summary.flags.entry(flag).or_default() += 1;
+        }
+    }
+    summaries
+}
+
+fn show(path: &Path) -> Result<()> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(error) => return Err(format!("Failed to read {}: {error}", path.display()).into()),
+    };
+    let summaries = summarize(&contents);
+    if summaries.is_empty() {
+        println!(
+            "No usage has been recorded. Set `telemetry: local` in {} to record it.",
+            Defaults::path()
+                .map(|path| path.display().to_string())
+                .unwrap_or_else(|| "defaults.yaml".to_owned())
+        );
+        return Ok(());
+    }
+
+    let mut table = comfy_table::Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .set_header(
+            ["command", "runs", "mean duration", "failures", "flags"]
+                .into_iter()
+                .map(|name| {
+                    comfy_table::Cell::new(name).add_attribute(comfy_table::Attribute::Bold)
+                }),
+        );
+    let counts = |counts: &BTreeMap<String, usize>| {
+        counts
+            .iter()
+            .map(|(name, count)| format!("{name} ×{count}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    for (command, summary) in &summaries {
+        let mean = Duration::from_millis(summary.total_duration_ms / summary.runs as u64);
+        table.add_row([
+            command.clone(),
+            summary.runs.to_string(),
+            format!("{:.1}s", mean.as_secs_f64()),
+            counts(&summary.failures),
+            counts(&summary.flags),
+        ]);
+    }
+    println!("{table}");
+    println!("Recorded in {}", path.display());
+    Ok(())
+}
+
+fn clear(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Ok(()) => println!("Deleted the recorded usage in {}", path.display()),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            println!("No usage has been recorded.")
+        }
+        Err(error) => return Err(format!("Failed to delete {}: {error}", path.display()).into()),
+    }
+    Ok(())
+}
+
+pub fn telemetry(args: TelemetryArgs) -> Result<()> {
+    let TelemetryArgs { command } = args;
+    let path = path().ok_or("Failed to determine the configuration directory")?;
+    match command {
+        TelemetryCommand::Show => show(&path),
+        TelemetryCommand::Clear => clear(&path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory as _;
+
+    use super::*;
+    use crate::cli::Cli;
+
+    fn event_of(args: &[&str], result: &Result<()>) -> Event {
+        let command = Cli::command();
+        let matches = command.clone().try_get_matches_from(args).unwrap();
+        event(
+            &command,
+            &matches,
+            Duration::from_millis(1500),
+            outcome(result),
+        )
+    }
+
+    #[test]
+    fn event_never_contains_values() {
+        let event = event_of(
+            &[
+                "launch",
+                "--context",
+                "staging",
+                "submit",
+                "--name-prefix",
+                "secretprefix",
+                "--gpus=3",
+                "--katib",
+                "/home/alice/secret.yaml",
+                "--notify",
+                "https://hooks.example.com/secret-token",
+                "--dry-run",
+                "--",
+                "python",
+                "/home/alice/secret/train.py",
+            ],
+            &Ok(()),
+        );
+        assert_eq!(
+            event,
+            Event {
+                command: "submit".to_owned(),
+                flags: [
+                    "--context",
+                    "--dry-run",
+                    "--gpus",
+                    "--katib",
+                    "--name-prefix",
+                    "--notify"
+                ]
+                .map(str::to_owned)
+                .to_vec(),
+                duration_ms: 1500,
+                outcome: "success".to_owned(),
+            }
+        );
+
+        let line = serde_json::to_string(&event).unwrap();
+        for value in ["secret", "alice", "staging", "python", "/", "3"] {
+            assert!(!line.contains(value), "{value:?} in {line}");
+        }
+    }
+
+    #[test]
+    fn event_of_nested_subcommand() {
+        let event = event_of(&["launch", "telemetry", "show"], &Ok(()));
+        assert_eq!(event.command, "telemetry show");
+        assert!(event.flags.is_empty());
+
+        let event = event_of(&["launch", "logs", "secret-pod"], &Err("failed".into()));
+        assert_eq!(event.command, "logs");
+        assert!(event.flags.is_empty());
+        assert_eq!(event.outcome, "other");
+    }
+
+    #[test]
+    fn outcome_works() {
+        assert_eq!(outcome(&Ok(())), "success");
+        assert_eq!(outcome(&Err("Failed to submit".into())), "other");
+        assert_eq!(
+            outcome(&Err(std::io::Error::other("disk full").into())),
+            "io"
+        );
+        assert_eq!(
+            outcome(&Err(serde_json::from_str::<u32>("x").unwrap_err().into())),
+            "parse"
+        );
+
+        let mut summary = summary::Summary::new("submit to", "contexts");
+        summary.record("staging".to_owned(), "submit", &Ok(()), |()| None);
+        summary.record(
+            "berkeley".to_owned(),
+            "submit",
+            &Err("cluster is down".into()),
+            |()| None,
+        );
+        assert_eq!(outcome(&summary.into_result()), "partial");
+    }
+
+    #[test]
+    fn summarize_works() {
+        let line = |command: &str, flags: &[&str], duration_ms, outcome: &str| {
+            serde_json::to_string(&Event {
+                command: command.to_owned(),
+                flags: flags.iter().map(|&flag| flag.to_owned()).collect(),
+                duration_ms,
+                outcome: outcome.to_owned(),
+            })
+            .unwrap()
+        };
+        let contents = [
+            line("submit", &["--dry-run", "--gpus"], 1000, "success"),
+            line("submit", &["--gpus"], 3000, "network"),
+            "not json".to_owned(),
+            line("list", &[], 200, "success"),
+        ]
+        .join("\n");
+
+        let summaries = summarize(&contents);
+        assert_eq!(summaries.keys().collect::<Vec<_>>(), ["list", "submit"]);
+        assert_eq!(
+            summaries["submit"],
+            CommandSummary {
+                runs: 2,
+                total_duration_ms: 4000,
+                failures: BTreeMap::from([("network".to_owned(), 1)]),
+                flags: BTreeMap::from([("--dry-run".to_owned(), 1), ("--gpus".to_owned(), 2)]),
+            }
+        );
+        assert_eq!(summaries["list"].runs, 1);
+    }
+
+    #[test]
+    fn telemetry_parses() {
+        let defaults: Defaults = serde_yaml::from_str("telemetry: local\n").unwrap();
+        assert_eq!(defaults.telemetry, Some(Telemetry::Local));
+        let defaults: Defaults = serde_yaml::from_str("telemetry: off\n").unwrap();
+        assert_eq!(defaults.telemetry, Some(Telemetry::Off));
+        assert!(serde_yaml::from_str::<Defaults>("telemetry: remote\n").is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use clap::Args;
+use log::{error, info};
+
+use super::{config, history, ClusterContext};
+use crate::{
+    executor::{ResourceKind, TerminalStatus},
+    kubectl::{self, Kubectl},
+    notify, Result,
+};
+
+#[derive(Debug, Args)]
+pub struct WatchArgs {
+    /// The name of the Job, RayJob or Experiment to watch.
+    pub name: String,
+
+    /// Post a JSON notification to this webhook URL, such as a Slack incoming webhook, once the resource has finished.
+    #[arg(long = "notify")]
+    pub notify: Option<String>,
+}
+
+pub fn watch(context: &ClusterContext, args: WatchArgs) -> Result<()> {
+    let WatchArgs { name, notify } = args;
+
+    let kubectl = context.kubectl();
+    let namespace = kubectl::NAMESPACE;
+
+    let kind = resource_kind(context, &kubectl, namespace, &name)?;
+
+    watch_resource(context, &kubectl, kind, namespace, &name, notify.as_deref())
+}
+
+/// Determines whether `name` refers to a Job or an Experiment. RayJobs are watched through their submitter Job which
+/// has the same name.
+fn resource_kind(
+    context: &ClusterContext,
+    kubectl: &Kubectl,
+    namespace: &str,
+    name: &str,
+) -> Result<ResourceKind> {
+    if kubectl.try_get_job(namespace, name)?.is_some() {
+        return Ok(ResourceKind::Job);
+    }
+    if kubectl.try_get_katib_experiment(namespace, name)?.is_some() {
+        return Ok(ResourceKind::Experiment);
+    }
+    Err(history::not_found(
+        context,
+        name,
+        format!("No Job, RayJob or Experiment named {name:?} exists in namespace {namespace:?}"),
+    )
+    .into())
+}
+
+/// Waits for the resource to finish and optionally posts a notification to the provided webhook.
+pub fn watch_resource(
+    context: &ClusterContext,
+    kubectl: &Kubectl,
+    kind: ResourceKind,
+    namespace: &str,
+    name: &str,
+    notify_url: Option<&str>,
+) -> Result<()> {
+    let completion = kind.wait_for_completion(kubectl, namespace, name)?;
+    let url = kind.url(context, namespace, name);
+
+    let kind_str = kind.as_str();
+    match completion.status {
+        TerminalStatus::Succeeded => info!("{kind_str} {url:?} succeeded"),
+        TerminalStatus::Failed => error!(
+            "{kind_str} {url:?} failed{}",
+            completion
+                .message
+                .as_deref()
+                .map(|message| format!(": {message}"))
+                .unwrap_or_default()
+        ),
+    }
+
+    if let Some(notify_url) = notify_url {
+        let payload = notify::Payload::new(kind_str, namespace, name, &completion, &url);
+        notify::notify(&config::http()?, notify_url, &payload)?;
+    }
+
+    Ok(())
+}
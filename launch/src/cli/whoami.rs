@@ -0,0 +1,322 @@
+//! Explains who launch thinks the user is, without talking to the cluster. The identity names the resources of a
+//! submission and ends up in its `launched-by-*` annotations, so a surprising name is easier to debug here than after
+//! submitting. The version of kubectl and the optional flags it supports are reported along with it.
+
+use std::collections::BTreeMap;
+
+use clap::Args;
+
+use crate::{
+    kubectl::{self, annotation, Capabilities, ClientVersion},
+    user_host::{self, HostnameSource, UserHost, UserLabelSource},
+    Result,
+};
+
+#[derive(Debug, Args)]
+pub struct WhoamiArgs {
+    /// Print the identity as JSON instead of text.
+    #[arg(long = "json")]
+    json: bool,
+}
+
+/// The identity of the user as resolved by `launch submit`.
+#[derive(Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Identity {
+    machine_user_host: String,
+    hostname_source: String,
+    tailscale_user_host: Option<String>,
+    /// Why there is no tailscale user, when there is none.
+    tailscale_error: Option<String>,
+    /// The label that names the resources of a submission, when the user name contains any allowed characters.
+    user_label: Option<String>,
+    /// How the user label was chosen.
+    user_label_source: String,
+    /// The `launched-by-*` annotations of a submission.
+    annotations: BTreeMap<&'static str, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kubectl: Option<KubectlInfo>,
+}
+
+/// The kubectl client, as checked before talking to a cluster.
+#[derive(Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct KubectlInfo {
+    version: Option<String>,
+    capabilities: Option<Capabilities>,
+    /// Why launch can not use kubectl, when it can not.
+    error: Option<String>,
+}
+
+impl KubectlInfo {
+    fn new(client_version: std::result::Result<ClientVersion, String>) -> Self {
+        match client_version {
+            Ok(client_version) => Self {
+                error: client_version
+                    .check_supported()
+                    .err()
+                    .map(|error| error.to_string()),
+                capabilities: Some(client_version.capabilities()),
+                version: Some(client_version.git_version),
+            },
+            Err(error) => Self {
+                version: None,
+                capabilities: None,
+                error: Some(error),
+            },
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut output = self.version.as_deref().unwrap_or("unknown").to_owned();
+        if let Some(capabilities) = &self.capabilities {
+            output.push_str(&format!(" ({capabilities})"));
+        }
+        if let Some(error) = &self.error {
+            output.push_str(&format!(", {error}"));
+        }
+        output
+    }
+}
+
+impl Identity {
+    fn new(
+        machine_user_host: UserHost,
+        hostname_source: HostnameSource,
+        tailscale: std::result::Result<UserHost, String>,
+    ) -> Self {
+        let (user_label, source) =
+            user_host::user_label(&machine_user_host, tailscale.as_ref().ok());
+        let user_label_source = match (source, &tailscale) {
+            (UserLabelSource::Tailscale, _) => "the tailscale login name".to_owned(),
+            (UserLabelSource::Machine, Ok(_)) => {
+                "the machine user, because the tailscale login name has no domain".to_owned()
+            }
+            (UserLabelSource::Machine, Err(_)) => {
+                "the machine user, because the tailscale login name is unavailable".to_owned()
+            }
+        };
+        let user_label_source = match &user_label {
+            Some(_) => user_label_source,
+            None => format!("{user_label_source}, which has no characters that a label allows"),
+        };
+
+        let mut annotations = BTreeMap::from([(
+            annotation::LAUNCHED_BY_MACHINE_USER,
+            machine_user_host.to_string(),
+        )]);
+        if let Ok(tailscale) = &tailscale {
+            annotations.insert(
+                annotation::LAUNCHED_BY_TAILSCALE_USER,
+                tailscale.to_string(),
+            );
+        }
+
+        Self {
+            machine_user_host: machine_user_host.to_string(),
+            hostname_source: hostname_source.to_string(),
+            tailscale_user_host: tailscale.as_ref().ok().map(ToString::to_string),
+            tailscale_error: tailscale.err(),
+            user_label,
+            user_label_source,
+            annotations,
+            kubectl: None,
+        }
+    }
+
+    fn render(&self) -> String {
+        let tailscale = match (&self.tailscale_user_host, &self.tailscale_error) {
+            (Some(value), _) => value.clone(),
+            (None, Some(error)) => format!("none ({error})"),
+            (None, None) => "none".to_owned(),
+        };
+        let mut output = format!(
+            "Machine user:   {} (hostname from {})\nTailscale user: {tailscale}\nUser label:     {} (from {})\nAnnotations:\n",
+            self.machine_user_host,
+            self.hostname_source,
+            self.user_label.as_deref().unwrap_or("none"),
+            self.user_label_source,
+        );
+        for (key, value) in &self.annotations {
+            output.push_str(&format!("  {key}={value}\n"));
+        }
+        if let Some(kubectl) = &self.kubectl {
+            output.push_str(&format!("Kubectl:        {}\n", kubectl.render()));
+        }
+        output
+    }
+}
+
+pub fn whoami(args: WhoamiArgs) -> Result<()> {
+    let WhoamiArgs { json } = args;
+
+    let (hostname, hostname_source) = user_host::hostname_with_source();
+    let mut identity = Identity::new(
+        UserHost::new(::whoami::username(), Some(hostname)),
+        hostname_source,
+        user_host::try_tailscale_user_host().map_err(|error| error.to_string()),
+    );
+    identity.kubectl = Some(KubectlInfo::new(
+        kubectl::client_version().map_err(|error| error.to_string()),
+    ));
+    if json {
+        serde_json::to_writer_pretty(std::io::stdout().lock(), &identity)?;
+        println!();
+    } else {
+        print!("{}", identity.render());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn machine() -> UserHost {
+        UserHost::parse("alice@laptop")
+    }
+
+    #[test]
+    fn identity_prefers_tailscale() {
+        let identity = Identity::new(
+            machine(),
+            HostnameSource::System,
+            Ok(UserHost::parse("alice.smith@astera.org")),
+        );
+        assert_eq!(identity.user_label.as_deref(), Some("alice-smith"));
+        assert_eq!(identity.user_label_source, "the tailscale login name");
+        assert_eq!(
+            identity.annotations,
+            BTreeMap::from([
+                (
+                    annotation::LAUNCHED_BY_MACHINE_USER,
+                    "alice@laptop".to_owned()
+                ),
+                (
+                    annotation::LAUNCHED_BY_TAILSCALE_USER,
+                    "alice.smith@astera.org".to_owned()
+                ),
+            ])
+        );
+        assert_eq!(
+            identity.render(),
+            "\
+Machine user:   alice@laptop (hostname from the system)
+Tailscale user: alice.smith@astera.org
+User label:     alice-smith (from the tailscale login name)
+Annotations:
+  launch.astera.org/launched-by-machine-user=alice@laptop
+  launch.astera.org/launched-by-tailscale-user=alice.smith@astera.org
+"
+        );
+    }
+
+    #[test]
+    fn identity_falls_back_to_the_machine_user() {
+        let identity = Identity::new(machine(), HostnameSource::Env, Ok(UserHost::parse("bob")));
+        assert_eq!(identity.user_label.as_deref(), Some("alice"));
+        assert_eq!(
+            identity.user_label_source,
+            "the machine user, because the tailscale login name has no domain"
+        );
+
+        let identity = Identity::new(
+            machine(),
+            HostnameSource::EtcHostname,
+            Err("tailscale is not running".to_owned()),
+        );
+        assert_eq!(identity.user_label.as_deref(), Some("alice"));
+        assert_eq!(
+            identity.user_label_source,
+            "the machine user, because the tailscale login name is unavailable"
+        );
+        assert_eq!(identity.annotations.len(), 1);
+        assert_eq!(
+            identity.render(),
+            "\
+Machine user:   alice@laptop (hostname from /etc/hostname)
+Tailscale user: none (tailscale is not running)
+User label:     alice (from the machine user, because the tailscale login name is unavailable)
+Annotations:
+  launch.astera.org/launched-by-machine-user=alice@laptop
+"
+        );
+    }
+
+    #[test]
+    fn identity_without_label() {
+        let identity = Identity::new(
+            UserHost::parse("123@unknown"),
+            HostnameSource::Unknown,
+            Err("tailscale is not installed".to_owned()),
+        );
+        assert_eq!(identity.user_label, None);
+        assert_eq!(
+            identity.user_label_source,
+            "the machine user, because the tailscale login name is unavailable, which has no characters that a label \
+             allows"
+        );
+    }
+
+    #[test]
+    fn kubectl_info_renders() {
+        let version = |major_minor| ClientVersion {
+            git_version: "v1.x".to_owned(),
+            major_minor,
+        };
+        assert_eq!(
+            KubectlInfo::new(Ok(version((1, 31)))).render(),
+            "v1.x (--chunk-size: yes, --server-side apply: yes)"
+        );
+        assert_eq!(
+            KubectlInfo::new(Ok(version((1, 17)))).render(),
+            "v1.x (--chunk-size: yes, --server-side apply: no), kubectl v1.x is too old, launch requires kubectl 1.20 or \
+             newer. Upgrade kubectl, see https://kubernetes.io/docs/tasks/tools/#kubectl"
+        );
+        assert_eq!(
+            KubectlInfo::new(Err("kubectl is not installed".to_owned())).render(),
+            "unknown, kubectl is not installed"
+        );
+
+        let mut identity = Identity::new(
+            machine(),
+            HostnameSource::System,
+            Err("tailscale is not running".to_owned()),
+        );
+        identity.kubectl = Some(KubectlInfo::new(Ok(version((1, 31)))));
+        assert!(identity
+            .render()
+            .ends_with("Kubectl:        v1.x (--chunk-size: yes, --server-side apply: yes)\n"));
+        assert_eq!(
+            serde_json::to_value(&identity).unwrap()["kubectl"],
+            serde_json::json!({
+                "version": "v1.x",
+                "capabilities": { "chunkSize": true, "serverSideApply": true },
+                "error": null,
+            })
+        );
+    }
+
+    #[test]
+    fn identity_serializes() {
+        let identity = Identity::new(
+            machine(),
+            HostnameSource::System,
+            Err("tailscale is not running".to_owned()),
+        );
+        assert_eq!(
+            serde_json::to_value(&identity).unwrap(),
+            serde_json::json!({
+                "machineUserHost": "alice@laptop",
+                "hostnameSource": "the system",
+                "tailscaleUserHost": null,
+                "tailscaleError": "tailscale is not running",
+                "userLabel": "alice",
+                "userLabelSource": "the machine user, because the tailscale login name is unavailable",
+                "annotations": {
+                    "launch.astera.org/launched-by-machine-user": "alice@laptop",
+                },
+            })
+        );
+    }
+}
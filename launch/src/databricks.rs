@@ -0,0 +1,294 @@
+//! The databricks configuration of the submitting machine, which is copied into a secret and mounted in the container so
+//! that mlflow can log to databricks.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use ::kubernetes::models as km;
+use clap::ValueEnum;
+use log::{debug, info, warn};
+use time::OffsetDateTime;
+
+use crate::{kubectl, Result};
+
+/// Where the configuration is mounted in the container.
+pub const MOUNT: &str = "/root/.databrickscfg";
+
+const FILE_NAME: &str = ".databrickscfg";
+const VOLUME_NAME: &str = "databrickscfg";
+const SECRET_NAME_PREFIX: &str = "databrickscfg";
+/// The default of `--databrickscfg-max-age`.
+pub const DEFAULT_MAX_AGE_DAYS: u32 = 90;
+
+#[derive(Debug, Default, Clone, Copy, ValueEnum, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// The databrickscfg secret will be created and attached to the container if possible.
+    #[default]
+    Auto,
+    /// The databrickscfg secret is required.
+    Require,
+    /// The databrickscfg secret should be omitted.
+    Omit,
+}
+
+/// Returns the path to the configuration in `home_dir` if it should be mounted according to `mode`. A missing
+/// configuration is only an error in [`Mode::Require`], but a configuration that exists and can not be read is always an
+/// error because omitting it would make the job fail remotely in a confusing way.
+pub fn config_path(home_dir: &Path, mode: Mode) -> Result<Option<PathBuf>> {
+    if mode == Mode::Omit {
+        return Ok(None);
+    }
+    let path = home_dir.join(FILE_NAME);
+    // Opening the file rather than reading its metadata also catches files that exist but are not readable.
+    match std::fs::File::open(&path) {
+        Ok(_) => Ok(Some(path)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            let error_string = format!("Databricks configuration not found at {path:?}: {error}.");
+            if mode == Mode::Require {
+                Err(error_string.into())
+            } else {
+                warn!(
+                    "{error_string} To omit the databricks configuration and avoid this warning, pass `--databrickscfg-mode omit`."
+                );
+                Ok(None)
+            }
+        }
+        Err(error) if error.kind() == io::ErrorKind::PermissionDenied => Err(format!(
+            "Databricks configuration at {path:?} is not readable: {error}. Make it readable with `chmod u+r {}`, or pass `--databrickscfg-mode omit` to omit it.",
+            path.display()
+        )
+        .into()),
+        Err(error) => Err(format!(
+            "Failed to read the databricks configuration at {path:?}: {error}. Pass `--databrickscfg-mode omit` to omit it."
+        )
+        .into()),
+    }
+}
+
+/// How long ago the configuration was modified, in whole days.
+fn age_description(modified: OffsetDateTime, now: OffsetDateTime) -> String {
+    match (now - modified).whole_days() {
+        ..=0 => "today".to_owned(),
+        1 => "1 day ago".to_owned(),
+        days => format!("{days} days ago"),
+    }
+}
+
+/// Names the configuration and the secret it is copied to, along with when the configuration was modified, so that a
+/// stale token stands out.
+fn mount_message(
+    path: &Path,
+    secret_name: &str,
+    modified: OffsetDateTime,
+    now: OffsetDateTime,
+) -> String {
+    format!(
+        "Mounting {} as Secret {secret_name}, last modified {} ({})",
+        path.display(),
+        modified.date(),
+        age_description(modified, now)
+    )
+}
+
+/// Returns a warning when the configuration was modified more than `max_age_days` ago, because the token in it may
+/// have expired, which makes mlflow fail to authenticate in the job.
+fn age_warning(
+    path: &Path,
+    modified: OffsetDateTime,
+    now: OffsetDateTime,
+    max_age_days: u32,
+) -> Option<String> {
+    let days = (now - modified).whole_days();
+    (days > i64::from(max_age_days)).then(|| {
+        format!(
+            "The databricks configuration at {} was last modified {days} days ago, more than `--databrickscfg-max-age` {max_age_days} days. If its token has expired, mlflow will fail to authenticate in the job; create a new token and update the configuration.",
+            path.display()
+        )
+    })
+}
+
+/// Logs which configuration is mounted and warns when it is older than `max_age_days`, which defaults to
+/// [`DEFAULT_MAX_AGE_DAYS`]. The mount is only logged at the info level when `max_age_days` was given or the
+/// configuration is too old, so that every submission does not print it. Failing to read the modification time only
+/// loses the information, so it is not an error.
+pub fn report(path: &Path, secret_name: &str, max_age_days: Option<u32>) {
+    let modified = match std::fs::metadata(path).and_then(|metadata| metadata.modified()) {
+        Ok(modified) => OffsetDateTime::from(modified),
+        Err(error) => {
+            debug!("Unable to read the modification time of {path:?}: {error}");
+            return;
+        }
+    };
+    let now = OffsetDateTime::now_utc();
+    let message = mount_message(path, secret_name, modified, now);
+    let warning = age_warning(
+        path,
+        modified,
+        now,
+        max_age_days.unwrap_or(DEFAULT_MAX_AGE_DAYS),
+    );
+    if max_age_days.is_some() || warning.is_some() {
+        info!("{message}");
+    } else {
+        debug!("{message}");
+    }
+    if let Some(warning) = warning {
+        warn!("{warning}");
+    }
+}
+
+/// The name of the secret that holds the configuration of `user`. Every user gets their own secret so that concurrent
+/// submissions do not overwrite each other's configuration. The user name is lowercased so that its uppercase letters
+/// are kept. Falls back to the un-suffixed name that was used for all users before when the user name can not be turned
+/// into a label.
+pub fn secret_name(user: Option<&str>) -> String {
+    let user = user.map(str::to_lowercase);
+    match user.as_deref().and_then(kubectl::to_rfc_1035_label_lossy) {
+        Some(user) => format!("{SECRET_NAME_PREFIX}-{user}"),
+        None => SECRET_NAME_PREFIX.to_owned(),
+    }
+}
+
+pub fn volume(secret_name: &str) -> km::V1Volume {
+    km::V1Volume {
+        name: VOLUME_NAME.to_owned(),
+        secret: Some(Box::new(km::V1SecretVolumeSource {
+            secret_name: Some(secret_name.to_owned()),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+}
+
+pub fn volume_mount() -> km::V1VolumeMount {
+    km::V1VolumeMount {
+        name: VOLUME_NAME.to_owned(),
+        mount_path: MOUNT.to_owned(),
+        sub_path: Some(FILE_NAME.to_owned()),
+        read_only: Some(true),
+        ..Default::default()
+    }
+}
+
+/// Makes mlflow pick up the mounted configuration.
+pub fn env() -> km::V1EnvVar {
+    km::V1EnvVar {
+        name: "MLFLOW_TRACKING_URI".to_owned(),
+        value: Some("databricks".to_owned()),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_path::TempDir;
+
+    #[test]
+    fn secret_name_works() {
+        assert_eq!(secret_name(Some("alice")), "databrickscfg-alice");
+        assert_eq!(
+            secret_name(Some("Alice.Smith")),
+            "databrickscfg-alice-smith"
+        );
+        assert_eq!(secret_name(Some("bob-2")), "databrickscfg-bob-2");
+        assert_eq!(secret_name(Some("123")), "databrickscfg");
+        assert_eq!(secret_name(Some("")), "databrickscfg");
+        assert_eq!(secret_name(None), "databrickscfg");
+        for user in crate::user_host::awkward_user_labels() {
+            let name = secret_name(user.as_deref());
+            assert!(
+                kubectl::is_rfc_1035_label(&name),
+                "{user:?} became {name:?}"
+            );
+            // Secret names are DNS subdomains, which are longer than labels.
+            assert!(name.len() <= 253, "{user:?} became {name:?}");
+        }
+    }
+
+    #[test]
+    fn config_path_works() {
+        let temp_dir = TempDir::new();
+        let dir = temp_dir.path();
+
+        assert_eq!(config_path(dir, Mode::Auto).unwrap(), None);
+        assert!(config_path(dir, Mode::Require).is_err());
+
+        std::fs::write(dir.join(FILE_NAME), "[DEFAULT]\n").unwrap();
+        assert_eq!(
+            config_path(dir, Mode::Auto).unwrap(),
+            Some(dir.join(FILE_NAME))
+        );
+        assert_eq!(
+            config_path(dir, Mode::Require).unwrap(),
+            Some(dir.join(FILE_NAME))
+        );
+        assert_eq!(config_path(dir, Mode::Omit).unwrap(), None);
+    }
+
+    #[test]
+    fn report_messages_use_the_modification_time() {
+        let temp_dir = TempDir::new();
+        let dir = temp_dir.path();
+        let path = dir.join(FILE_NAME);
+        let file = std::fs::File::create(&path).unwrap();
+        let modified = time::macros::datetime!(2025-01-01 09:30 UTC);
+        file.set_modified(modified.into()).unwrap();
+        drop(file);
+
+        let read = OffsetDateTime::from(std::fs::metadata(&path).unwrap().modified().unwrap());
+        assert_eq!(read, modified);
+
+        let now = time::macros::datetime!(2025-04-11 12:00 UTC);
+        assert_eq!(
+            mount_message(&path, "databrickscfg-alice", read, now),
+            format!(
+                "Mounting {} as Secret databrickscfg-alice, last modified 2025-01-01 (100 days ago)",
+                path.display()
+            )
+        );
+        assert_eq!(age_warning(&path, read, now, 100), None);
+        let warning = age_warning(&path, read, now, 99).unwrap();
+        assert!(
+            warning.contains(
+                "was last modified 100 days ago, more than `--databrickscfg-max-age` 99 days"
+            ),
+            "{warning}"
+        );
+    }
+
+    #[test]
+    fn age_description_works() {
+        let modified = time::macros::datetime!(2025-01-01 09:30 UTC);
+        let after = |duration| age_description(modified, modified + duration);
+        assert_eq!(after(time::Duration::hours(23)), "today");
+        assert_eq!(after(time::Duration::hours(-1)), "today");
+        assert_eq!(after(time::Duration::days(1)), "1 day ago");
+        assert_eq!(after(time::Duration::days(45)), "45 days ago");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn config_path_fails_on_unreadable_config() {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let temp_dir = TempDir::new();
+        let dir = temp_dir.path();
+        let path = dir.join(FILE_NAME);
+        std::fs::write(&path, "[DEFAULT]\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o000)).unwrap();
+
+        // Permissions are not enforced for privileged users such as root.
+        if std::fs::File::open(&path).is_err() {
+            for mode in [Mode::Auto, Mode::Require] {
+                let error = config_path(dir, mode).unwrap_err().to_string();
+                assert!(error.contains("is not readable"), "{error}");
+                assert!(error.contains("chmod u+r"), "{error}");
+            }
+            assert_eq!(config_path(dir, Mode::Omit).unwrap(), None);
+        }
+    }
+}
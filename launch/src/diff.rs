@@ -0,0 +1,230 @@
+//! A line-based unified diff. The longest common subsequence is computed with a table of `old.len() * new.len()`
+//! entries, which is fine for manifests of a few hundred lines but not for large files.
+
+use std::fmt::Write as _;
+
+use crate::ansi;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Line<'a> {
+    Context(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+impl Line<'_> {
+    fn is_change(&self) -> bool {
+        !matches!(self, Line::Context(_))
+    }
+
+    fn in_old(&self) -> bool {
+        !matches!(self, Line::Added(_))
+    }
+
+    fn in_new(&self) -> bool {
+        !matches!(self, Line::Removed(_))
+    }
+}
+
+/// A group of changes along with the lines around them. The starts are 1-based line numbers like in the output of
+/// `diff -u`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk<'a> {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub lines: Vec<Line<'a>>,
+}
+
+/// Returns the lines of `old` and `new` in order, with the removals of a change before its additions.
+fn edit_script<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    let (n, m) = (old.len(), new.len());
+    // `lcs[i][j]` is the length of the longest common subsequence of `old[i..]` and `new[j..]`.
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::with_capacity(n.max(m));
+    while i < n || j < m {
+        if i < n && j < m && old[i] == new[j] {
+            lines.push(Line::Context(old[i]));
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(new[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+/// Returns the changes from `old` to `new` with up to `context` unchanged lines around each of them. Changes that are
+/// at most `2 * context` lines apart share a hunk.
+pub fn hunks<'a>(old: &'a str, new: &'a str, context: usize) -> Vec<Hunk<'a>> {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+    let lines = edit_script(&old, &new);
+
+    let changes = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.is_change())
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    let mut ranges = Vec::<(usize, usize)>::new();
+    for index in changes {
+        let start = index.saturating_sub(context);
+        let end = (index + context + 1).min(lines.len());
+        match ranges.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            let before = &lines[..start];
+            let lines = lines[start..end].to_vec();
+            let old_before = before.iter().filter(|line| line.in_old()).count();
+            let new_before = before.iter().filter(|line| line.in_new()).count();
+            let old_len = lines.iter().filter(|line| line.in_old()).count();
+            let new_len = lines.iter().filter(|line| line.in_new()).count();
+            // An empty side refers to the line before it, like `diff -u` does.
+            Hunk {
+                old_start: old_before + usize::from(old_len != 0),
+                old_len,
+                new_start: new_before + usize::from(new_len != 0),
+                new_len,
+                lines,
+            }
+        })
+        .collect()
+}
+
+/// Renders the hunks in the unified format, coloring the removed lines red and the added lines green when `color` is
+/// set. Returns an empty string when there are no hunks.
+pub fn unified(hunks: &[Hunk], old_label: &str, new_label: &str, color: bool) -> String {
+    if hunks.is_empty() {
+        return String::new();
+    }
+    let paint = |code: &'static str| if color { code } else { ansi::EMPTY };
+    let reset = paint(ansi::RESET);
+
+    let mut out = format!(
+        "{bold}--- {old_label}{reset}\n{bold}+++ {new_label}{reset}\n",
+        bold = paint(ansi::BOLD)
+    );
+    for hunk in hunks {
+        writeln!(
+            out,
+            "{}@@ -{},{} +{},{} @@{reset}",
+            paint(ansi::CYAN),
+            hunk.old_start,
+            hunk.old_len,
+            hunk.new_start,
+            hunk.new_len
+        )
+        .unwrap();
+        for line in &hunk.lines {
+            match line {
+                Line::Context(text) => writeln!(out, " {text}"),
+                Line::Removed(text) => writeln!(out, "{}-{text}{reset}", paint(ansi::RED)),
+                Line::Added(text) => writeln!(out, "{}+{text}{reset}", paint(ansi::GREEN)),
+            }
+            .unwrap();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the numbers from 1 to 20 on separate lines, with some of them replaced or removed by `edit`.
+    fn numbered(edit: impl Fn(usize) -> Option<String>) -> String {
+        (1..21)
+            .filter_map(edit)
+            .map(|line| format!("{line}\n"))
+            .collect()
+    }
+
+    #[test]
+    fn hunks_works() {
+        assert_eq!(hunks("a\nb\n", "a\nb\n", 3), []);
+
+        let old = numbered(|line| Some(line.to_string()));
+        let new = numbered(|line| match line {
+            3 => Some("three".to_owned()),
+            18 => None,
+            line => Some(line.to_string()),
+        });
+        assert_eq!(
+            unified(&hunks(&old, &new, 2), "old", "new", false),
+            "\
+--- old
++++ new
+@@ -1,5 +1,5 @@
+ 1
+ 2
+-3
++three
+ 4
+ 5
+@@ -16,5 +16,4 @@
+ 16
+ 17
+-18
+ 19
+ 20
+"
+        );
+
+        // Changes that are close share a hunk.
+        let new = numbered(|line| match line {
+            3 => Some("three".to_owned()),
+            7 => Some("seven".to_owned()),
+            line => Some(line.to_string()),
+        });
+        assert_eq!(hunks(&old, &new, 2).len(), 1);
+        assert_eq!(hunks(&old, &new, 1).len(), 2);
+    }
+
+    #[test]
+    fn hunks_at_the_edges() {
+        assert_eq!(
+            hunks("", "a\n", 3),
+            [Hunk {
+                old_start: 0,
+                old_len: 0,
+                new_start: 1,
+                new_len: 1,
+                lines: vec![Line::Added("a")],
+            }]
+        );
+        assert_eq!(
+            unified(&hunks("a\nb\n", "b\nc\n", 0), "old", "new", false),
+            "--- old\n+++ new\n@@ -1,1 +0,0 @@\n-a\n@@ -2,0 +2,1 @@\n+c\n"
+        );
+    }
+
+    #[test]
+    fn unified_colors() {
+        let rendered = unified(&hunks("a\n", "b\n", 3), "old", "new", true);
+        assert!(rendered.contains(&format!("{}-a{}", ansi::RED, ansi::RESET)));
+        assert!(rendered.contains(&format!("{}+b{}", ansi::GREEN, ansi::RESET)));
+    }
+}
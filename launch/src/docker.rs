@@ -1,4 +1,5 @@
 use core::fmt;
+use std::path::Path;
 
 use container_image_name::ImageNameRef;
 use log::debug;
@@ -35,6 +36,8 @@ impl fmt::Display for Platform {
 pub struct BuildArgs<'a> {
     pub git_commit_hash: &'a str,
     pub image: ImageNameRef<'a>,
+    /// The Dockerfile relative to the current directory, which is the build context.
+    pub dockerfile: &'a Path,
     pub platform: Platform,
 }
 
@@ -46,6 +49,7 @@ pub fn build_and_push(args: BuildArgs) -> Result<BuildOutput> {
     let BuildArgs {
         image,
         git_commit_hash,
+        dockerfile,
         platform,
     } = args;
     debug!("Building image: {:?}", image);
@@ -56,6 +60,7 @@ pub fn build_and_push(args: BuildArgs) -> Result<BuildOutput> {
         "buildx",
         "build",
         ".",
+        format!("--file={}", dockerfile.display()),
         format!("--metadata-file={}", metadata_filepath.display()),
         format!("--tag={}", image),
         format!("--build-arg=COMMIT_HASH={git_commit_hash}"),
@@ -73,3 +78,198 @@ pub fn build_and_push(args: BuildArgs) -> Result<BuildOutput> {
         digest: metadata.containerimage_digest,
     })
 }
+
+/// Copies the image to `target` without pulling it, which keeps its digest. The registries of both images must be
+/// reachable from this machine.
+pub fn copy_image(source: ImageNameRef, target: ImageNameRef) -> Result<()> {
+    debug!("Copying image {source} to {target}...");
+    process::command!(
+        "docker",
+        "buildx",
+        "imagetools",
+        "create",
+        format!("--tag={target}"),
+        source.to_string(),
+    )
+    .status()?;
+    Ok(())
+}
+
+/// Exit code of [`COMMAND_CHECK_SCRIPT`] when the command can not be found, chosen to not collide with the exit codes
+/// of `docker run` itself.
+const COMMAND_MISSING_EXIT_CODE: i32 = 100;
+
+/// Prints the path of the command passed as the first argument if it can be resolved. Otherwise, lists the contents of
+/// the directories on `PATH` and exits with [`COMMAND_MISSING_EXIT_CODE`].
+const COMMAND_CHECK_SCRIPT: &str = r#"command -v "$1" && exit 0
+for dir in $(echo "$PATH" | tr ':' ' '); do ls "$dir" 2>/dev/null; done
+exit 100"#;
+
+/// The number of bytes of the output of [`COMMAND_CHECK_SCRIPT`] to keep.
+const COMMAND_CHECK_OUTPUT_LIMIT: usize = 1024 * 1024;
+
+/// The maximum number of alternatives to suggest when a command can not be found.
+const MAX_CANDIDATES: usize = 5;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CommandCheck {
+    /// The command resolves to the provided path.
+    Found(String),
+    /// The command can not be found. Contains similarly named commands that are available.
+    Missing { candidates: Vec<String> },
+    /// The check itself failed, for example because the image does not contain `sh`.
+    Inconclusive(Option<i32>),
+}
+
+fn check_command_args(image: ImageNameRef, platform: Platform, program: &str) -> Vec<String> {
+    [
+        "run",
+        "--rm",
+        &format!("--platform={platform}"),
+        "--entrypoint=sh",
+        &image.to_string(),
+        "-c",
+        COMMAND_CHECK_SCRIPT,
+        // Becomes `$0` of the script.
+        "sh",
+        program,
+    ]
+    .into_iter()
+    .map(str::to_owned)
+    .collect()
+}
+
+fn interpret_command_check(exit_code: Option<i32>, stdout: &str, program: &str) -> CommandCheck {
+    match exit_code {
+        Some(0) => CommandCheck::Found(stdout.lines().next().unwrap_or(program).trim().to_owned()),
+        Some(COMMAND_MISSING_EXIT_CODE) => CommandCheck::Missing {
+            candidates: command_candidates(program, stdout.lines()),
+        },
+        other => CommandCheck::Inconclusive(other),
+    }
+}
+
+/// Finds commands whose name starts with `program` without its version suffix, so that `python3` suggests `python`
+/// and `python3.11`.
+fn command_candidates<'a>(program: &str, available: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let name = program.rsplit('/').next().unwrap_or(program);
+    let stem = name.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    if stem.is_empty() {
+        return Vec::new();
+    }
+    let mut candidates = available
+        .map(str::trim)
+        .filter(|candidate| candidate.starts_with(stem) && *candidate != name)
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    candidates.sort();
+    candidates.dedup();
+    candidates.truncate(MAX_CANDIDATES);
+    candidates
+}
+
+/// Checks whether `program` can be resolved on `PATH` inside the image by running `sh` in a container.
+pub fn check_command(
+    image: ImageNameRef,
+    platform: Platform,
+    program: &str,
+) -> Result<CommandCheck> {
+    debug!("Checking if {program:?} is available in image {image}...");
+    let output = process::Command::new("docker")
+        .args(
+            check_command_args(image, platform, program)
+                .iter()
+                .map(std::ffi::OsStr::new),
+        )
+        // The listing of `PATH` is only used to suggest alternatives.
+        .output_limit(COMMAND_CHECK_OUTPUT_LIMIT)
+        .try_output()?;
+    Ok(interpret_command_check(
+        output.status.code(),
+        &String::from_utf8_lossy(&output.stdout),
+        program,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use container_image_name::ImageName;
+
+    use super::*;
+
+    #[test]
+    fn check_command_args_works() {
+        let image = ImageName::builder("launch")
+            .with_registry("registry:5000")
+            .with_tag("abc")
+            .build()
+            .unwrap();
+        let args = check_command_args(image.as_ref(), Platform::LinuxAmd64, "python3");
+        assert_eq!(
+            &args[..5],
+            [
+                "run",
+                "--rm",
+                "--platform=linux/amd64",
+                "--entrypoint=sh",
+                "registry:5000/launch:abc"
+            ]
+        );
+        assert_eq!(&args[5..], ["-c", COMMAND_CHECK_SCRIPT, "sh", "python3"]);
+        assert!(COMMAND_CHECK_SCRIPT.ends_with(&format!("exit {COMMAND_MISSING_EXIT_CODE}")));
+    }
+
+    #[test]
+    fn interpret_command_check_works() {
+        assert_eq!(
+            interpret_command_check(Some(0), "/usr/local/bin/python3\n", "python3"),
+            CommandCheck::Found("/usr/local/bin/python3".to_owned())
+        );
+        assert_eq!(
+            interpret_command_check(
+                Some(COMMAND_MISSING_EXIT_CODE),
+                "pip\npython\npython3.11\npython-config\nsh\nuv\npython\n",
+                "python3"
+            ),
+            CommandCheck::Missing {
+                candidates: vec![
+                    "python".to_owned(),
+                    "python-config".to_owned(),
+                    "python3.11".to_owned()
+                ]
+            }
+        );
+        assert_eq!(
+            interpret_command_check(Some(COMMAND_MISSING_EXIT_CODE), "ls\nsh\n", "uv"),
+            CommandCheck::Missing { candidates: vec![] }
+        );
+        assert_eq!(
+            interpret_command_check(Some(125), "", "python3"),
+            CommandCheck::Inconclusive(Some(125))
+        );
+        assert_eq!(
+            interpret_command_check(None, "", "python3"),
+            CommandCheck::Inconclusive(None)
+        );
+    }
+
+    #[test]
+    fn command_candidates_works() {
+        let available = ["python", "python3.12", "pydoc", "uvicorn"];
+        assert_eq!(
+            command_candidates("/usr/bin/python3", available.into_iter()),
+            ["python", "python3.12"]
+        );
+        assert_eq!(command_candidates("uv", available.into_iter()), ["uvicorn"]);
+        assert!(command_candidates("3", available.into_iter()).is_empty());
+        assert_eq!(
+            command_candidates(
+                "p",
+                (0..10)
+                    .map(|_| "p1")
+                    .chain(["p2", "p3", "p4", "p5", "p6", "p7"])
+            ),
+            ["p1", "p2", "p3", "p4", "p5"]
+        );
+    }
+}
@@ -1,25 +1,38 @@
+mod cron_job;
 mod katib;
 mod kubernetes;
 mod ray;
 
 pub(crate) mod common;
-use std::collections::HashMap;
+pub(crate) mod dedupe_logs;
+pub(crate) mod gpu_capacity;
+pub(crate) mod image_digest;
+pub(crate) mod latency;
+pub(crate) mod missing_reference;
+pub(crate) mod monitor;
+pub(crate) mod oom;
+use std::collections::{BTreeMap, HashMap};
 
 use ::kubernetes::models as km;
+use clap::ValueEnum;
 pub use common::*;
 use container_image_name::ImageNameRef;
+pub use cron_job::*;
 pub use katib::*;
 pub use kubernetes::*;
 pub use ray::*;
 
 use crate::{
     cli::ClusterContext,
+    databricks,
     kubectl::{self},
     unit::bytes::{self, Bytes},
+    urls,
     user_host::UserHostRef,
     Result,
 };
 
+#[derive(Clone)]
 pub struct ExecutionArgs<'a> {
     pub context: &'a ClusterContext,
     pub job_namespace: &'a str,
@@ -32,12 +45,208 @@ pub struct ExecutionArgs<'a> {
     pub workers: u32,
     pub gpus: u32,
     pub gpu_mem: Option<Bytes>,
+    /// The cpu request, a quantity such as `4` or `500m`.
+    pub cpus: Option<&'a str>,
+    /// The cpu limit. Without it, the job may use the idle cpus of the node.
+    pub cpus_limit: Option<&'a str>,
+    /// The memory request, which is also the memory limit unless `mem_limit` says otherwise.
+    pub mem: Option<Bytes>,
+    pub mem_limit: Option<Bytes>,
+    /// Whether the cpu and memory without an explicit limit are limited to their requests.
+    pub limits_from_requests: bool,
+    /// Requested when the job would otherwise request neither cpu nor memory.
+    pub default_resources: Option<&'a DefaultResources>,
+    pub git_provenance: GitProvenance,
+    /// The Kubernetes version of the cluster, if it could be determined.
+    pub server_version: Option<&'a str>,
+    /// The Ray version of the image, which kuberay uses to decide which features the RayCluster supports.
+    pub ray_version: Option<&'a str>,
+    /// A Ray runtime environment in YAML, such as pip packages and environment variables for the Ray workers.
+    pub ray_runtime_env: Option<&'a str>,
+    /// The `restartPolicy` of the Ray worker Pods. Defaults to that of Kubernetes, `Always`.
+    pub ray_worker_restart_policy: Option<RestartPolicy>,
+    /// Whether the identical lines that the Ray workers print at about the same time are collapsed into one.
+    pub dedupe_logs: bool,
+    /// Whether [`Executor::execute`] follows the logs until the work finishes, rather than returning once the resource
+    /// is created.
+    pub follow_logs: bool,
+    /// Identifies the submission across the created resources, their Pods and the environment of the job.
+    pub run_id: &'a str,
+    /// Free text that describes the intent of the submission, recorded as an annotation.
+    pub comment: Option<&'a str>,
 }
 
-pub const DATABRICKSCFG_MOUNT: &str = "/root/.databrickscfg";
+/// The [restart policy](https://kubernetes.io/docs/concepts/workloads/pods/pod-lifecycle/#restart-policy) of the
+/// containers of a Pod.
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// Restart the container whenever it exits.
+    Always,
+    /// Restart the container when it fails, for example when it is killed for running out of memory.
+    OnFailure,
+    /// Never restart the container. Kuberay replaces a failed Ray worker Pod instead.
+    Never,
+}
+
+impl RestartPolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RestartPolicy::Always => "Always",
+            RestartPolicy::OnFailure => "OnFailure",
+            RestartPolicy::Never => "Never",
+        }
+    }
+}
+
+/// The environment variable that holds the run ID of the submission in every container that launch creates.
+pub const RUN_ID_ENV: &str = "LAUNCH_RUN_ID";
+
+/// Generates a run ID. A UUIDv7 sorts by creation time and is a valid label value.
+pub fn generate_run_id() -> String {
+    uuid::Uuid::now_v7().to_string()
+}
+
+/// Jobs that request neither cpu nor memory get the BestEffort quality of service class, which makes them the first to
+/// be evicted when a node runs low on resources. These requests avoid that.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DefaultResources {
+    /// A cpu quantity such as `4` or `500m`.
+    #[serde(default, deserialize_with = "deserialize_cpu_quantity")]
+    pub cpu: Option<String>,
+    /// The memory in the same format as `--mem`, for example `16GiB`.
+    #[serde(default, deserialize_with = "deserialize_memory")]
+    pub memory: Option<Bytes>,
+}
+
+/// Returns the number of millicpus of a cpu quantity, rounded up like Kubernetes does.
+fn cpu_millis(value: &str) -> Option<u64> {
+    if !is_cpu_quantity(value) {
+        return None;
+    }
+    match value.strip_suffix('m') {
+        Some(millis) => millis.parse().ok(),
+        None => value
+            .parse::<f64>()
+            .ok()
+            .map(|cpus| (cpus * 1000.0).ceil() as u64),
+    }
+}
+
+/// Parses a cpu quantity such as `4` or `500m` passed on the command line.
+pub fn parse_cpu_quantity(value: &str) -> Result<String, String> {
+    if is_cpu_quantity(value) {
+        Ok(value.to_owned())
+    } else {
+        Err("expected a number of cpus such as `4` or millicpus such as `500m`".to_owned())
+    }
+}
+
+/// Kubernetes rejects a container whose limit is lower than its request.
+pub fn check_resource_limits(
+    cpus: Option<&str>,
+    cpus_limit: Option<&str>,
+    mem: Option<Bytes>,
+    mem_limit: Option<Bytes>,
+) -> Result<()> {
+    if let (Some(request), Some(limit)) = (cpus, cpus_limit) {
+        if cpu_millis(limit) < cpu_millis(request) {
+            return Err(
+                format!("The cpu limit {limit} is lower than the cpu request {request}").into(),
+            );
+        }
+    }
+    if let (Some(request), Some(limit)) = (mem, mem_limit) {
+        if limit < request {
+            return Err(format!(
+                "The memory limit {}MiB is lower than the memory request {}MiB",
+                limit.get::<bytes::mebibyte>(),
+                request.get::<bytes::mebibyte>()
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+fn is_cpu_quantity(value: &str) -> bool {
+    let number = value.strip_suffix('m').unwrap_or(value);
+    let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+    !integer.is_empty()
+        && integer.bytes().all(|byte| byte.is_ascii_digit())
+        && fraction.bytes().all(|byte| byte.is_ascii_digit())
+        && (fraction.is_empty() || number.len() == value.len())
+}
+
+fn deserialize_cpu_quantity<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    use serde::Deserialize;
+
+    // YAML parses `cpu: 4` as a number.
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Quantity {
+        Integer(u64),
+        Float(f64),
+        String(String),
+    }
+
+    let value = match Quantity::deserialize(deserializer)? {
+        Quantity::Integer(value) => value.to_string(),
+        Quantity::Float(value) => value.to_string(),
+        Quantity::String(value) => value,
+    };
+    if !is_cpu_quantity(&value) {
+        return Err(serde::de::Error::custom(format!(
+            "invalid cpu quantity {value:?}, expected a number of cpus such as `4` or millicpus such as `500m`"
+        )));
+    }
+    Ok(Some(value))
+}
+
+fn deserialize_memory<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Bytes>, D::Error> {
+    use serde::Deserialize;
+
+    let value = String::deserialize(deserializer)?;
+    value
+        .parse()
+        .map(Some)
+        .map_err(|error| serde::de::Error::custom(format!("invalid memory {value:?}: {error:?}")))
+}
+
+/// Whether a container with these resources gets the BestEffort quality of service class, which is the case when it
+/// has neither requests nor limits for cpu and memory.
+pub fn is_best_effort(resources: Option<&km::V1ResourceRequirements>) -> bool {
+    let Some(resources) = resources else {
+        return true;
+    };
+    [&resources.limits, &resources.requests]
+        .into_iter()
+        .flatten()
+        .all(|quantities| !quantities.contains_key("cpu") && !quantities.contains_key("memory"))
+}
+
+/// Recorded as annotations so that anyone looking at the results can tell whether the code can be reproduced from git.
+#[derive(Clone)]
+pub struct GitProvenance {
+    /// The effective `strict_git` policy.
+    pub strict_git: &'static str,
+    pub is_dirty: bool,
+    pub is_unpushed: bool,
+}
 
 impl ExecutionArgs<'_> {
-    fn annotations(&self) -> HashMap<String, String> {
+    /// The image of the containers in the spec. The tag of an image that is pinned by digest is dropped, because the
+    /// digest decides what is pulled and the tag would only suggest otherwise.
+    fn pinned_image(&self) -> String {
+        let image = self.image.to_owned();
+        image.to_canonical().unwrap_or(image).to_string()
+    }
+
+    fn annotations(&self) -> BTreeMap<String, String> {
         use std::borrow::Cow;
 
         use kubectl::annotation;
@@ -48,6 +257,19 @@ impl ExecutionArgs<'_> {
                 annotation::LAUNCHED_BY_MACHINE_USER,
                 Cow::Owned(self.machine_user_host.to_string()),
             ),
+            (
+                annotation::STRICT_GIT,
+                Cow::Borrowed(self.git_provenance.strict_git),
+            ),
+            (
+                annotation::GIT_DIRTY,
+                Cow::Owned(self.git_provenance.is_dirty.to_string()),
+            ),
+            (
+                annotation::GIT_UNPUSHED,
+                Cow::Owned(self.git_provenance.is_unpushed.to_string()),
+            ),
+            (annotation::RUN_ID, Cow::Borrowed(self.run_id)),
         ]
         .into_iter()
         .chain(self.tailscale_user_host.as_ref().map(|value| {
@@ -56,46 +278,89 @@ impl ExecutionArgs<'_> {
                 Cow::Owned(value.to_string()),
             )
         }))
+        .chain(
+            self.server_version
+                .map(|value| (annotation::K8S_SERVER_VERSION, Cow::Borrowed(value))),
+        )
+        .chain(
+            self.comment
+                .map(|value| (annotation::COMMENT, Cow::Borrowed(value))),
+        )
         .map(|(a, b)| (a.to_owned(), b.into_owned()))
-        .collect::<std::collections::HashMap<_, _>>()
+        .collect::<BTreeMap<_, _>>()
     }
 
-    fn volume_mounts(&self) -> Option<Vec<km::V1VolumeMount>> {
-        if self.databrickscfg_name.is_some() {
-            Some(vec![km::V1VolumeMount {
-                name: "databrickscfg".to_owned(),
-                mount_path: DATABRICKSCFG_MOUNT.to_owned(),
-                sub_path: Some(".databrickscfg".to_owned()),
-                read_only: Some(true),
-                ..Default::default()
-            }])
-        } else {
-            None
+    fn labels(&self) -> BTreeMap<String, String> {
+        BTreeMap::from([(kubectl::label::RUN_ID.to_owned(), self.run_id.to_owned())])
+    }
+
+    fn run_id_env(&self) -> km::V1EnvVar {
+        km::V1EnvVar {
+            name: RUN_ID_ENV.to_owned(),
+            value: Some(self.run_id.to_owned()),
+            ..Default::default()
         }
     }
 
+    fn volume_mounts(&self) -> Option<Vec<km::V1VolumeMount>> {
+        self.databrickscfg_name
+            .map(|_| vec![databricks::volume_mount()])
+    }
+
     fn volumes(&self) -> Option<Vec<km::V1Volume>> {
-        self.databrickscfg_name.map(|name| {
-            vec![km::V1Volume {
-                name: "databrickscfg".to_owned(),
-                secret: Some(Box::new(km::V1SecretVolumeSource {
-                    secret_name: Some(name.to_owned()),
-                    ..Default::default()
-                })),
-                ..Default::default()
-            }]
-        })
+        self.databrickscfg_name
+            .map(|name| vec![databricks::volume(name)])
     }
 
-    fn resources(&self) -> Option<km::V1ResourceRequirements> {
-        if self.gpus != 0 {
+    /// The requests and limits of every container that runs the command. The cpu is only limited when asked for, so
+    /// that the job can burst into the idle cpus of its node, while the memory is limited to its request by default,
+    /// so that the job is killed for its own usage rather than evicted when the node runs low on memory.
+    pub fn resources(&self) -> Option<km::V1ResourceRequirements> {
+        let memory = |memory: Bytes| memory.get::<bytes::byte>().to_string();
+
+        // A limit also requests that amount, so the defaults are only needed without any of the flags.
+        let explicit = self.cpus.is_some()
+            || self.cpus_limit.is_some()
+            || self.mem.is_some()
+            || self.mem_limit.is_some();
+        let defaults = self.default_resources.filter(|_| !explicit);
+        let cpu_request = self
+            .cpus
+            .map(str::to_owned)
+            .or_else(|| defaults.and_then(|defaults| defaults.cpu.clone()));
+        let memory_request = self
+            .mem
+            .or_else(|| defaults.and_then(|defaults| defaults.memory));
+
+        let cpu_limit = self
+            .cpus_limit
+            .map(str::to_owned)
+            .or_else(|| cpu_request.clone().filter(|_| self.limits_from_requests));
+        let memory_limit = self
+            .mem_limit
+            .or(self.mem)
+            .or_else(|| memory_request.filter(|_| self.limits_from_requests));
+
+        let limits = [
+            (self.gpus != 0).then(|| ("nvidia.com/gpu".to_owned(), self.gpus.to_string())),
+            cpu_limit.map(|cpu| ("cpu".to_owned(), cpu)),
+            memory_limit.map(|limit| ("memory".to_owned(), memory(limit))),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<HashMap<_, _>>();
+        let requests = [
+            cpu_request.map(|cpu| ("cpu".to_owned(), cpu)),
+            memory_request.map(|request| ("memory".to_owned(), memory(request))),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<HashMap<_, _>>();
+
+        if !limits.is_empty() || !requests.is_empty() {
             Some(km::V1ResourceRequirements {
-                limits: Some(
-                    [("nvidia.com/gpu".to_owned(), self.gpus.to_string())]
-                        .into_iter()
-                        .collect(),
-                ),
-                ..Default::default()
+                limits: (!limits.is_empty()).then_some(limits),
+                requests: (!requests.is_empty()).then_some(requests),
             })
         } else {
             None
@@ -142,11 +407,8 @@ impl ExecutionArgs<'_> {
                     value: Some("quiet".to_owned()),
                     ..Default::default()
                 }),
-                self.databrickscfg_name.map(|_| km::V1EnvVar {
-                    name: "MLFLOW_TRACKING_URI".to_owned(),
-                    value: Some("databricks".to_owned()),
-                    ..Default::default()
-                }),
+                Some(self.run_id_env()),
+                self.databrickscfg_name.map(|_| databricks::env()),
             ]
             .into_iter()
             .flatten()
@@ -156,9 +418,73 @@ impl ExecutionArgs<'_> {
     }
 }
 
-pub struct ExecutionOutput {}
+/// Merges `overrides` into `env`. A variable in `overrides` replaces the variable with the same name in `env`, others are
+/// appended.
+pub(crate) fn merge_env(
+    env: &mut Vec<km::V1EnvVar>,
+    overrides: impl IntoIterator<Item = km::V1EnvVar>,
+) {
+    for var in overrides {
+        match env.iter_mut().find(|existing| existing.name == var.name) {
+            Some(existing) => *existing = var,
+            None => env.push(var),
+        }
+    }
+}
+
+/// The kinds of submitted resources. The completion of all but CronJobs can be awaited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    /// Also used for RayJobs, which create a submitter Job with the same name.
+    Job,
+    Experiment,
+    CronJob,
+}
+
+impl ResourceKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResourceKind::Job => "Job",
+            ResourceKind::Experiment => "Experiment",
+            ResourceKind::CronJob => "CronJob",
+        }
+    }
+
+    pub fn url(&self, context: &ClusterContext, namespace: &str, name: &str) -> String {
+        match self {
+            ResourceKind::Job => urls::headlamp_job(context, namespace, name),
+            ResourceKind::Experiment => urls::katib_experiment(context, namespace, name),
+            ResourceKind::CronJob => urls::headlamp_cron_job(context, namespace, name),
+        }
+    }
+
+    pub fn wait_for_completion(
+        &self,
+        kubectl: &kubectl::Kubectl,
+        namespace: &str,
+        name: &str,
+    ) -> Result<Completion> {
+        match self {
+            ResourceKind::Job => wait_for_job_completion(kubectl, namespace, name),
+            ResourceKind::Experiment => wait_for_experiment_completion(kubectl, namespace, name),
+            ResourceKind::CronJob => Err(format!(
+                "CronJob {namespace}/{name} runs on its schedule and never completes"
+            )
+            .into()),
+        }
+    }
+}
+
+pub struct ExecutionOutput {
+    pub kind: ResourceKind,
+    pub namespace: String,
+    pub name: String,
+}
 
 pub trait Executor {
+    /// Renders the resource that [`Executor::execute`] creates without creating it.
+    fn spec(&self, args: &ExecutionArgs) -> Result<serde_json::Value>;
+
     fn execute(&self, args: ExecutionArgs) -> Result<ExecutionOutput>;
 }
 
@@ -179,6 +505,14 @@ macro_rules! impl_any_executor {
         )*
 
         impl Executor for AnyExecutor {
+            fn spec(&self, args: &ExecutionArgs) -> Result<serde_json::Value> {
+                match self {
+                    $(
+                        Self::$v(executor) => executor.spec(args),
+                    )*
+                }
+            }
+
             fn execute(&self, args: ExecutionArgs) -> Result<ExecutionOutput> {
                 match self {
                     $(
@@ -194,4 +528,412 @@ impl_any_executor! {
     Kubernetes(KubernetesExecutor),
     Katib(KatibExecutor),
     Ray(RayExecutor),
+    CronJob(CronJobExecutor),
+}
+
+impl AnyExecutor {
+    /// The maximum length of the name of the submitted resource, including the suffix generated by kubernetes.
+    pub fn max_name_len(&self) -> usize {
+        match self {
+            AnyExecutor::Kubernetes(_) | AnyExecutor::Ray(_) => kubectl::MAX_NAME_LEN,
+            AnyExecutor::Katib(_) => EXPERIMENT_NAME_MAX_LEN,
+            AnyExecutor::CronJob(_) => CRON_JOB_NAME_MAX_LEN,
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use container_image_name::ImageName;
+
+    use super::*;
+
+    /// The run ID of the submissions in the tests of the executors.
+    pub(crate) const RUN_ID: &str = "0192f0c4-5d3e-7a4b-8c2d-9e1f2a3b4c5d";
+
+    /// The arguments of a submission of `image` that sets none of the options, which the tests of the executors
+    /// adjust with struct update syntax.
+    pub(crate) fn execution_args(image: ImageNameRef<'_>) -> ExecutionArgs<'_> {
+        ExecutionArgs {
+            context: &ClusterContext::Berkeley,
+            job_namespace: "launch",
+            generate_name: "job-",
+            machine_user_host: UserHostRef::from_user("user"),
+            tailscale_user_host: None,
+            image,
+            databrickscfg_name: None,
+            container_args: &[],
+            workers: 1,
+            gpus: 0,
+            gpu_mem: None,
+            cpus: None,
+            cpus_limit: None,
+            mem: None,
+            mem_limit: None,
+            limits_from_requests: false,
+            default_resources: None,
+            git_provenance: GitProvenance {
+                strict_git: "warn",
+                is_dirty: false,
+                is_unpushed: false,
+            },
+            server_version: None,
+            ray_version: None,
+            ray_runtime_env: None,
+            ray_worker_restart_policy: None,
+            dedupe_logs: false,
+            follow_logs: true,
+            run_id: RUN_ID,
+            comment: None,
+        }
+    }
+
+    pub(super) fn args<'a>(
+        image: ImageNameRef<'a>,
+        gpus: u32,
+        mem: Option<Bytes>,
+        default_resources: Option<&'a DefaultResources>,
+    ) -> ExecutionArgs<'a> {
+        ExecutionArgs {
+            gpus,
+            mem,
+            default_resources,
+            ..execution_args(image)
+        }
+    }
+
+    fn image() -> ImageName {
+        ImageName::builder("launch")
+            .with_registry("registry:5000")
+            .with_tag("latest")
+            .build()
+            .unwrap()
+    }
+
+    fn resources(
+        gpus: u32,
+        mem: Option<Bytes>,
+        default_resources: Option<&DefaultResources>,
+    ) -> Option<km::V1ResourceRequirements> {
+        args(image().as_ref(), gpus, mem, default_resources).resources()
+    }
+
+    fn gib(value: u64) -> Bytes {
+        Bytes::new::<bytes::gibibyte>(value).unwrap()
+    }
+
+    fn quantities(entries: &[(&str, &str)]) -> Option<HashMap<String, String>> {
+        Some(
+            entries
+                .iter()
+                .map(|&(key, value)| (key.to_owned(), value.to_owned()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn resources_without_defaults_are_best_effort() {
+        assert_eq!(resources(0, None, None), None);
+        assert!(is_best_effort(resources(0, None, None).as_ref()));
+
+        let gpu_only = resources(1, None, None);
+        assert_eq!(
+            gpu_only.as_ref().unwrap().limits,
+            quantities(&[("nvidia.com/gpu", "1")])
+        );
+        assert!(is_best_effort(gpu_only.as_ref()));
+
+        assert!(!is_best_effort(resources(0, Some(gib(16)), None).as_ref()));
+    }
+
+    #[test]
+    fn resources_apply_defaults() {
+        let defaults = DefaultResources {
+            cpu: Some("4".to_owned()),
+            memory: Some(gib(16)),
+        };
+
+        let applied = resources(1, None, Some(&defaults)).unwrap();
+        assert_eq!(applied.limits, quantities(&[("nvidia.com/gpu", "1")]));
+        assert_eq!(
+            applied.requests,
+            quantities(&[("cpu", "4"), ("memory", "17179869184")])
+        );
+        assert!(!is_best_effort(Some(&applied)));
+
+        // An explicit memory request takes precedence over the defaults.
+        let explicit = resources(0, Some(gib(8)), Some(&defaults)).unwrap();
+        assert_eq!(explicit.limits, quantities(&[("memory", "8589934592")]));
+        assert_eq!(explicit.requests, quantities(&[("memory", "8589934592")]));
+    }
+
+    #[test]
+    fn resources_truth_table() {
+        let defaults = DefaultResources {
+            cpu: Some("2".to_owned()),
+            memory: Some(gib(4)),
+        };
+        let image = image();
+        type Quantities = &'static [(&'static str, &'static str)];
+        type Case = (
+            Option<&'static str>,
+            Option<&'static str>,
+            Option<Bytes>,
+            Option<Bytes>,
+            bool,
+            Quantities,
+            Quantities,
+        );
+        let cases: [Case; 12] = [
+            // cpus, cpus_limit, mem, mem_limit, limits_from_requests, requests, limits
+            (
+                None,
+                None,
+                None,
+                None,
+                false,
+                &[("cpu", "2"), ("memory", "4294967296")],
+                &[],
+            ),
+            (
+                None,
+                None,
+                None,
+                None,
+                true,
+                &[("cpu", "2"), ("memory", "4294967296")],
+                &[("cpu", "2"), ("memory", "4294967296")],
+            ),
+            (Some("4"), None, None, None, false, &[("cpu", "4")], &[]),
+            (
+                Some("4"),
+                None,
+                None,
+                None,
+                true,
+                &[("cpu", "4")],
+                &[("cpu", "4")],
+            ),
+            (
+                Some("4"),
+                Some("8"),
+                None,
+                None,
+                false,
+                &[("cpu", "4")],
+                &[("cpu", "8")],
+            ),
+            (None, Some("8"), None, None, false, &[], &[("cpu", "8")]),
+            (
+                None,
+                None,
+                Some(gib(16)),
+                None,
+                false,
+                &[("memory", "17179869184")],
+                &[("memory", "17179869184")],
+            ),
+            (
+                None,
+                None,
+                Some(gib(16)),
+                Some(gib(32)),
+                false,
+                &[("memory", "17179869184")],
+                &[("memory", "34359738368")],
+            ),
+            (
+                None,
+                None,
+                None,
+                Some(gib(32)),
+                false,
+                &[],
+                &[("memory", "34359738368")],
+            ),
+            (
+                Some("500m"),
+                None,
+                Some(gib(16)),
+                None,
+                false,
+                &[("cpu", "500m"), ("memory", "17179869184")],
+                &[("memory", "17179869184")],
+            ),
+            (
+                Some("500m"),
+                None,
+                Some(gib(16)),
+                None,
+                true,
+                &[("cpu", "500m"), ("memory", "17179869184")],
+                &[("cpu", "500m"), ("memory", "17179869184")],
+            ),
+            (
+                Some("4"),
+                Some("6"),
+                Some(gib(16)),
+                Some(gib(32)),
+                false,
+                &[("cpu", "4"), ("memory", "17179869184")],
+                &[("cpu", "6"), ("memory", "34359738368")],
+            ),
+        ];
+        for (cpus, cpus_limit, mem, mem_limit, limits_from_requests, requests, limits) in cases {
+            let mut args = args(image.as_ref(), 0, mem, Some(&defaults));
+            args.cpus = cpus;
+            args.cpus_limit = cpus_limit;
+            args.mem_limit = mem_limit;
+            args.limits_from_requests = limits_from_requests;
+            let resources = args.resources().unwrap();
+            let expected =
+                |entries: Quantities| (!entries.is_empty()).then(|| quantities(entries).unwrap());
+            let case = (cpus, cpus_limit, mem, mem_limit, limits_from_requests);
+            assert_eq!(resources.requests, expected(requests), "{case:?}");
+            assert_eq!(resources.limits, expected(limits), "{case:?}");
+        }
+    }
+
+    #[test]
+    fn check_resource_limits_works() {
+        assert!(
+            check_resource_limits(Some("4"), Some("4000m"), Some(gib(8)), Some(gib(8))).is_ok()
+        );
+        assert!(check_resource_limits(Some("500m"), Some("0.5"), None, None).is_ok());
+        assert!(check_resource_limits(None, Some("1"), None, Some(gib(1))).is_ok());
+        assert_eq!(
+            check_resource_limits(Some("4"), Some("2"), None, None)
+                .unwrap_err()
+                .to_string(),
+            "The cpu limit 2 is lower than the cpu request 4"
+        );
+        assert_eq!(
+            check_resource_limits(None, None, Some(gib(16)), Some(gib(8)))
+                .unwrap_err()
+                .to_string(),
+            "The memory limit 8192MiB is lower than the memory request 16384MiB"
+        );
+        assert!(parse_cpu_quantity("1.5").is_ok());
+        assert!(parse_cpu_quantity("1.5m").is_err());
+        assert!(parse_cpu_quantity("four").is_err());
+    }
+
+    #[test]
+    fn job_spec_pins_the_image_by_digest() {
+        let hex = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let container_image = |image: &str| {
+            let image = ImageNameRef::new(image).unwrap();
+            let job = common::job_spec(&args(image, 0, None, None), None, None);
+            job.spec.unwrap().template.spec.unwrap().containers[0]
+                .image
+                .clone()
+                .unwrap()
+        };
+        assert_eq!(
+            container_image(&format!("registry:5000/launch:abc@sha256:{hex}")),
+            format!("registry:5000/launch@sha256:{hex}")
+        );
+        assert_eq!(
+            container_image(&format!("registry:5000/launch@sha256:{hex}")),
+            format!("registry:5000/launch@sha256:{hex}")
+        );
+        // Without a digest, the tag is all there is.
+        assert_eq!(
+            container_image("registry:5000/launch:abc"),
+            "registry:5000/launch:abc"
+        );
+    }
+
+    #[test]
+    fn job_spec_sets_run_id() {
+        let image = image();
+        let job = common::job_spec(&args(image.as_ref(), 0, None, None), None, None);
+
+        let metadata = job.metadata.unwrap();
+        assert_eq!(
+            metadata.annotations.unwrap()[kubectl::annotation::RUN_ID],
+            RUN_ID
+        );
+        assert_eq!(metadata.labels.unwrap()[kubectl::label::RUN_ID], RUN_ID);
+
+        let template = job.spec.unwrap().template;
+        let template_metadata = template.metadata.unwrap();
+        assert_eq!(
+            template_metadata.annotations.unwrap()[kubectl::annotation::RUN_ID],
+            RUN_ID
+        );
+        assert_eq!(
+            template_metadata.labels.unwrap()[kubectl::label::RUN_ID],
+            RUN_ID
+        );
+        let env = template.spec.unwrap().containers[0].env.clone().unwrap();
+        assert!(env
+            .iter()
+            .any(|var| var.name == RUN_ID_ENV && var.value.as_deref() == Some(RUN_ID)));
+    }
+
+    #[test]
+    fn spec_records_comment() {
+        let image = image();
+        let comment = "sweep \"lr\" with\nwarmup, été 🧪";
+        let mut args = args(image.as_ref(), 0, None, None);
+        assert!(!args
+            .annotations()
+            .contains_key(kubectl::annotation::COMMENT));
+        args.comment = Some(comment);
+
+        let executors: [AnyExecutor; 2] = [KubernetesExecutor.into(), RayExecutor.into()];
+        for executor in executors {
+            // The manifest is passed to kubectl as JSON.
+            let json = serde_json::to_string(&executor.spec(&args).unwrap()).unwrap();
+            let spec: serde_json::Value = serde_json::from_str(&json).unwrap();
+            assert_eq!(
+                spec["metadata"]["annotations"][kubectl::annotation::COMMENT],
+                comment
+            );
+        }
+    }
+
+    #[test]
+    fn generate_run_id_is_a_label_value() {
+        let run_id = generate_run_id();
+        assert_eq!(run_id.len(), 36);
+        assert!(run_id
+            .bytes()
+            .all(|byte| byte.is_ascii_digit() || byte.is_ascii_lowercase() || byte == b'-'));
+        assert_ne!(run_id, generate_run_id());
+    }
+
+    #[test]
+    fn default_resources_parse() {
+        let defaults: DefaultResources = serde_yaml::from_str("cpu: 4\nmemory: 16GiB\n").unwrap();
+        assert_eq!(
+            defaults,
+            DefaultResources {
+                cpu: Some("4".to_owned()),
+                memory: Some(gib(16)),
+            }
+        );
+        let defaults: DefaultResources = serde_yaml::from_str("cpu: 0.5\n").unwrap();
+        assert_eq!(defaults.cpu.as_deref(), Some("0.5"));
+        let defaults: DefaultResources = serde_yaml::from_str("cpu: 500m\n").unwrap();
+        assert_eq!(defaults.cpu.as_deref(), Some("500m"));
+        assert_eq!(defaults.memory, None);
+
+        assert!(serde_yaml::from_str::<DefaultResources>("cpu: four\n").is_err());
+        assert!(serde_yaml::from_str::<DefaultResources>("memory: 16\n").is_err());
+        assert!(serde_yaml::from_str::<DefaultResources>("gpu: 1\n").is_err());
+    }
+
+    #[test]
+    fn is_cpu_quantity_works() {
+        assert!(is_cpu_quantity("4"));
+        assert!(is_cpu_quantity("0.5"));
+        assert!(is_cpu_quantity("500m"));
+        assert!(!is_cpu_quantity(""));
+        assert!(!is_cpu_quantity("m"));
+        assert!(!is_cpu_quantity("1.5m"));
+        assert!(!is_cpu_quantity(".5"));
+        assert!(!is_cpu_quantity("4 cpus"));
+    }
 }
@@ -1,5 +1,6 @@
-use std::{error::Error, fmt, thread, time};
+use std::{error::Error, fmt, io, thread, time};
 
+use ::time::OffsetDateTime;
 use kubernetes::models as k8s;
 use log::{debug, info, warn};
 
@@ -8,8 +9,14 @@ use crate::kubectl::{self, PodStatus};
 
 pub const KANIKO_POST_BUILD_TIMEOUT: time::Duration = time::Duration::from_secs(30);
 pub const RAY_JOB_CREATION_TIMEOUT: time::Duration = time::Duration::from_secs(600);
+/// How long the head Pod of a RayCluster may be unschedulable or unable to pull its image before we give up on it.
+pub const RAY_HEAD_POD_STUCK_TIMEOUT: time::Duration = time::Duration::from_secs(120);
 pub const LOG_AVAILABILITY_TIMEOUT: time::Duration = time::Duration::from_secs(600);
 pub const POLLING_INTERVAL: time::Duration = time::Duration::from_secs(2);
+/// How long to wait for the pod status to reflect that the containers terminated after the logs end.
+pub const POD_STATUS_LAG_TIMEOUT: time::Duration = time::Duration::from_secs(10);
+/// Jobs can run for days, so we poll for their completion less aggressively than we poll for short-lived transitions.
+pub const COMPLETION_POLLING_INTERVAL: time::Duration = time::Duration::from_secs(10);
 
 pub struct Deadline(time::Instant);
 
@@ -19,10 +26,15 @@ impl Deadline {
         Self(time::Instant::now() + timeout)
     }
 
+    /// Whether there is enough time to sleep for `duration` before the deadline.
+    pub fn allows(&self, duration: time::Duration) -> bool {
+        time::Instant::now() + duration < self.0
+    }
+
     /// If there is enough time to sleep before the deadline, sleeps and returns
     /// Ok. Otherwise, returns Err.
     pub fn sleep(&self, duration: time::Duration) -> Result<(), ()> {
-        if time::Instant::now() + duration < self.0 {
+        if self.allows(duration) {
             thread::sleep(duration);
             Ok(())
         } else {
@@ -34,6 +46,7 @@ impl Deadline {
 #[derive(Debug)]
 pub enum PodLogPollError {
     BadStatus(Box<PodStatus>),
+    MissingReference(Box<super::missing_reference::MissingReferenceError>),
     Timeout,
     Other(Box<dyn std::error::Error + Send + Sync>),
 }
@@ -46,6 +59,7 @@ impl fmt::Display for PodLogPollError {
                 "Pod logs will not become available because it reached status {}",
                 status.display_multi_line(0),
             ),
+            PodLogPollError::MissingReference(error) => error.fmt(f),
             PodLogPollError::Timeout => write!(
                 f,
                 "Deadline exceeded while waiting for pod logs to become available!"
@@ -70,11 +84,25 @@ impl From<Box<dyn Error + Send + Sync>> for PodLogPollError {
     }
 }
 
+/// Returns when the logs became available, or `None` if the Pod is queued because it is unschedulable.
 pub fn wait_for_and_follow_pod_logs(
     kubectl: &kubectl::Kubectl,
     namespace: &str,
     name: &str,
-) -> Result<(), PodLogPollError> {
+) -> Result<Option<OffsetDateTime>, PodLogPollError> {
+    wait_for_and_follow_pod_log_lines(kubectl, namespace, name, None)
+}
+
+/// Receives the log lines of a Pod. Following the logs stops with its error.
+pub type OnLine<'a> = dyn FnMut(&str) -> io::Result<()> + 'a;
+
+/// Like [`wait_for_and_follow_pod_logs`], but passes each log line to `on_line` instead of printing it, if provided.
+pub fn wait_for_and_follow_pod_log_lines(
+    kubectl: &kubectl::Kubectl,
+    namespace: &str,
+    name: &str,
+    on_line: Option<&mut OnLine>,
+) -> Result<Option<OffsetDateTime>, PodLogPollError> {
     fn log_status(status: &kubectl::PodStatus) {
         debug!("Pod status: {status}");
     }
@@ -82,17 +110,27 @@ pub fn wait_for_and_follow_pod_logs(
     info!("Waiting for logs of Pod {namespace}/{name} to become available...");
 
     let deadline = Deadline::after(LOG_AVAILABILITY_TIMEOUT);
-    let mut status = kubectl.pod(namespace, name)?.status;
-    log_status(&status);
+    let mut pod = kubectl.pod(namespace, name)?;
+    log_status(&pod.status);
     loop {
+        let status = &pod.status;
+        match super::missing_reference::diagnose(kubectl, namespace, name, status) {
+            Ok(Some(error)) => return Err(PodLogPollError::MissingReference(error.into())),
+            Ok(None) => {}
+            Err(error) => {
+                debug!("Unable to check Pod {namespace}/{name} for missing references: {error}")
+            }
+        }
+
         if let Some(logs_available) = status.are_logs_available() {
             if logs_available {
+                super::image_digest::warn_about_mismatches(&pod);
                 break;
             } else if status.is_unschedulable() {
                 warn!("The Pod is unschedulable which means that the Pod is queued. The Pod will start once the cluster has sufficient capacity. Please ensure that your Pod does not request more resources than the cluster can possibly offer.");
-                return Ok(());
+                return Ok(None);
             } else {
-                return Err(PodLogPollError::BadStatus(status.into()));
+                return Err(PodLogPollError::BadStatus(pod.status.into()));
             }
         }
 
@@ -100,18 +138,133 @@ pub fn wait_for_and_follow_pod_logs(
             .sleep(POLLING_INTERVAL)
             .map_err(|_| PodLogPollError::Timeout)?;
 
-        status = {
-            let new_status = kubectl.pod(namespace, name)?.status;
-            if new_status != status {
-                log_status(&new_status);
+        pod = {
+            let new_pod = kubectl.pod(namespace, name)?;
+            if new_pod.status != pod.status {
+                log_status(&new_pod.status);
             }
-            new_status
+            new_pod
         }
     }
 
-    kubectl.follow_pod_logs(namespace, name)?;
+    let logs_available = OffsetDateTime::now_utc();
+
+    match on_line {
+        Some(on_line) => kubectl.follow_pod_log_lines(namespace, name, on_line)?,
+        None => kubectl.follow_pod_logs(namespace, name)?,
+    }
 
-    Ok(())
+    // The pod status has a lag to update after the logs end.
+    let deadline = Deadline::after(POD_STATUS_LAG_TIMEOUT);
+    loop {
+        let Ok(pod) = kubectl.pod(namespace, name).inspect_err(|error| {
+            debug!("Unable to obtain the final status of Pod {namespace}/{name}: {error}")
+        }) else {
+            break;
+        };
+        let running = pod
+            .status
+            .container_statuses
+            .iter()
+            .any(|status| matches!(status.state, kubectl::ContainerState::Running(_)));
+        if !running {
+            super::oom::warn_about_oom_kills(kubectl, &pod);
+            break;
+        }
+        if deadline.sleep(POLLING_INTERVAL).is_err() {
+            break;
+        }
+    }
+
+    Ok(Some(logs_available))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TerminalStatus {
+    Succeeded,
+    Failed,
+}
+
+impl TerminalStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TerminalStatus::Succeeded => "succeeded",
+            TerminalStatus::Failed => "failed",
+        }
+    }
+}
+
+impl fmt::Display for TerminalStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The outcome of a resource that reached a terminal state.
+#[derive(Debug)]
+pub struct Completion {
+    pub status: TerminalStatus,
+    pub message: Option<String>,
+    pub duration: Option<time::Duration>,
+}
+
+/// Returns `Some` if the job has reached a terminal state, and `None` otherwise.
+pub fn job_completion(job: &kubectl::Job) -> Option<Completion> {
+    let condition = job.status.conditions.iter().find(|condition| {
+        condition.status
+            && matches!(
+                condition.r#type,
+                kubectl::JobConditionType::Complete | kubectl::JobConditionType::Failed
+            )
+    })?;
+
+    let status = match condition.r#type {
+        kubectl::JobConditionType::Complete => TerminalStatus::Succeeded,
+        _ => TerminalStatus::Failed,
+    };
+
+    let finished_at = job
+        .status
+        .completion_time
+        .or(condition.last_transition_time);
+
+    let duration = job
+        .status
+        .start_time
+        .zip(finished_at)
+        .and_then(|(start, end)| time::Duration::try_from(end - start).ok());
+
+    Some(Completion {
+        status,
+        message: condition
+            .message
+            .clone()
+            .or_else(|| condition.reason.clone()),
+        duration,
+    })
+}
+
+/// Polls the job until it reaches a terminal state. There is no deadline because jobs may legitimately run for a long
+/// time.
+pub fn wait_for_job_completion(
+    kubectl: &kubectl::Kubectl,
+    namespace: &str,
+    name: &str,
+) -> Result<Completion> {
+    info!("Waiting for Job {namespace}/{name} to complete...");
+
+    loop {
+        let job = kubectl
+            .try_get_job(namespace, name)?
+            .ok_or_else(|| format!("Job {namespace}/{name} no longer exists"))?;
+
+        if let Some(completion) = job_completion(&job) {
+            return Ok(completion);
+        }
+
+        thread::sleep(COMPLETION_POLLING_INTERVAL);
+    }
 }
 
 pub(super) const PRIMARY_CONTAINER_NAME: &str = "main";
@@ -122,13 +275,15 @@ pub(super) fn job_spec(
     container_args: Option<Vec<String>>,
 ) -> k8s::V1Job {
     let annotations = args.annotations();
+    let labels = args.labels();
 
     k8s::V1Job {
         api_version: Some("batch/v1".to_owned()),
         kind: Some("Job".to_owned()),
         metadata: Some(Box::new(k8s::V1ObjectMeta {
-            annotations: Some(annotations.clone()),
+            annotations: Some(annotations.clone().into_iter().collect()),
             generate_name: Some(args.generate_name.to_owned()),
+            labels: Some(labels.clone().into_iter().collect()),
             namespace: Some(args.job_namespace.to_owned()),
             ..Default::default()
         })),
@@ -138,7 +293,8 @@ pub(super) fn job_spec(
             backoff_limit: Some(0),
             template: Box::new(k8s::V1PodTemplateSpec {
                 metadata: Some(Box::new(k8s::V1ObjectMeta {
-                    annotations: Some(annotations.clone()),
+                    annotations: Some(annotations.into_iter().collect()),
+                    labels: Some(labels.into_iter().collect()),
                     ..Default::default()
                 })),
                 spec: Some(Box::new(k8s::V1PodSpec {
@@ -148,7 +304,7 @@ pub(super) fn job_spec(
                         command: container_command,
                         args: container_args,
                         env: args.env(),
-                        image: Some(args.image.to_string()),
+                        image: Some(args.pinned_image()),
                         volume_mounts: args.volume_mounts(),
                         resources: args.resources().map(Box::new),
                         ..Default::default()
@@ -0,0 +1,394 @@
+//! Submits the Job as a CronJob that creates it on a schedule, for recurring work such as nightly evaluations.
+
+use kubernetes::models as k8s;
+use log::info;
+
+use super::{ExecutionArgs, ExecutionOutput, Executor, ResourceKind, Result};
+use crate::{executor::common::job_spec, kubectl::ResourceHandle, urls};
+
+/// The controller names the Jobs of a CronJob after it with an 11 character suffix, which must fit in the 63 characters
+/// of a label value.
+pub const CRON_JOB_NAME_MAX_LEN: usize = 52;
+
+/// How many finished Jobs the CronJob keeps, so that the logs of the last few runs can be looked at.
+pub const SUCCESSFUL_JOBS_HISTORY_LIMIT: i32 = 3;
+pub const FAILED_JOBS_HISTORY_LIMIT: i32 = 3;
+
+/// The fields of a schedule in order, with their range and the names they accept.
+const FIELDS: [Field; 5] = [
+    Field {
+        name: "minute",
+        min: 0,
+        max: 59,
+        names: &[],
+        question_mark: false,
+    },
+    Field {
+        name: "hour",
+        min: 0,
+        max: 23,
+        names: &[],
+        question_mark: false,
+    },
+    Field {
+        name: "day of the month",
+        min: 1,
+        max: 31,
+        names: &[],
+        question_mark: true,
+    },
+    Field {
+        name: "month",
+        min: 1,
+        max: 12,
+        names: &[
+            "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+        ],
+        question_mark: false,
+    },
+    Field {
+        name: "day of the week",
+        min: 0,
+        max: 6,
+        names: &["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"],
+        question_mark: true,
+    },
+];
+
+/// The shorthands that the CronJob controller accepts in place of the five fields.
+const MACROS: [&str; 7] = [
+    "@yearly",
+    "@annually",
+    "@monthly",
+    "@weekly",
+    "@daily",
+    "@midnight",
+    "@hourly",
+];
+
+struct Field {
+    name: &'static str,
+    min: u8,
+    max: u8,
+    /// The names of the values from `min` on, such as `JAN` for 1.
+    names: &'static [&'static str],
+    /// Whether `?` is accepted as a synonym of `*`.
+    question_mark: bool,
+}
+
+impl Field {
+    fn value(&self, value: &str) -> Result<u8, String> {
+        let numeric = !value.is_empty() && value.bytes().all(|byte| byte.is_ascii_digit());
+        let number = if numeric {
+            value.parse::<u8>().ok()
+        } else {
+            self.names
+                .iter()
+                .position(|name| name.eq_ignore_ascii_case(value))
+                .map(|index| self.min + index as u8)
+        };
+        match number {
+            Some(number) if (self.min..=self.max).contains(&number) => Ok(number),
+            _ if numeric => Err(format!(
+                "the {} {value} is out of range, expected {} to {}",
+                self.name, self.min, self.max
+            )),
+            _ => Err(format!(
+                "{value:?} is not a valid {}, {}",
+                self.name,
+                self.expected()
+            )),
+        }
+    }
+
+    fn expected(&self) -> String {
+        match (self.names.first(), self.names.last()) {
+            (Some(first), Some(last)) => format!(
+                "expected a number from {} to {} or a name from {first} to {last}",
+                self.min, self.max
+            ),
+            _ => format!("expected a number from {} to {}", self.min, self.max),
+        }
+    }
+
+    /// Checks one item of the comma separated list of a field, such as `*`, `5`, `1-5` or `*/15`.
+    fn check_item(&self, item: &str) -> Result<(), String> {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, Some(step)),
+            None => (item, None),
+        };
+        if let Some(step) = step {
+            if !matches!(step.parse::<u8>(), Ok(1..)) {
+                return Err(format!(
+                    "the step {step:?} of the {} is not a positive number",
+                    self.name
+                ));
+            }
+        }
+        if range == "*" || (range == "?" && self.question_mark) {
+            return Ok(());
+        }
+        match range.split_once('-') {
+            Some((start, end)) => {
+                let (start_value, end_value) = (self.value(start)?, self.value(end)?);
+                if start_value > end_value {
+                    return Err(format!(
+                        "the range {start}-{end} of the {} ends before it starts",
+                        self.name
+                    ));
+                }
+            }
+            None => {
+                self.value(range)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A cron schedule in the format of the CronJob controller: five fields for the minute, hour, day of the month, month
+/// and day of the week, such as `0 3 * * *` for 03:00 every day, or a shorthand such as `@daily`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule(String);
+
+impl Schedule {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::str::FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let fields = value.split_whitespace().collect::<Vec<_>>();
+        if let [field] = fields[..] {
+            if field.starts_with('@') {
+                return match MACROS.contains(&field) {
+                    true => Ok(Self(field.to_owned())),
+                    false => Err(format!(
+                        "unknown shorthand {field:?}, expected one of {}",
+                        MACROS.join(", ")
+                    )),
+                };
+            }
+        }
+        if fields
+            .first()
+            .is_some_and(|field| field.starts_with("TZ=") || field.starts_with("CRON_TZ="))
+        {
+            return Err(
+                "the schedule can not set a time zone, it runs in the time zone of the cluster, which is usually UTC"
+                    .to_owned(),
+            );
+        }
+        if fields.len() != FIELDS.len() {
+            let seconds = match fields.len() {
+                6 => ", there is no field for seconds",
+                _ => "",
+            };
+            return Err(format!(
+                "expected 5 fields separated by spaces (minute, hour, day of the month, month and day of the week) \
+                 such as \"0 3 * * *\" for 03:00 every day, got {}{seconds}",
+                fields.len()
+            ));
+        }
+        for (field, value) in FIELDS.iter().zip(&fields) {
+            for item in value.split(',') {
+                field.check_item(item)?;
+            }
+        }
+        Ok(Self(fields.join(" ")))
+    }
+}
+
+pub struct CronJobExecutor {
+    pub schedule: Schedule,
+}
+
+impl CronJobExecutor {
+    fn cron_job_spec(&self, args: &ExecutionArgs) -> k8s::V1CronJob {
+        let job = job_spec(args, None, Some(args.container_args.to_vec()));
+        k8s::V1CronJob {
+            api_version: Some("batch/v1".to_owned()),
+            kind: Some("CronJob".to_owned()),
+            metadata: job.metadata,
+            spec: Some(Box::new(k8s::V1CronJobSpec {
+                schedule: self.schedule.as_str().to_owned(),
+                // A run that is still going when the next one is due makes the next one skip, rather than two runs
+                // competing for the same GPUs.
+                concurrency_policy: Some("Forbid".to_owned()),
+                successful_jobs_history_limit: Some(SUCCESSFUL_JOBS_HISTORY_LIMIT),
+                failed_jobs_history_limit: Some(FAILED_JOBS_HISTORY_LIMIT),
+                job_template: Box::new(k8s::V1JobTemplateSpec {
+                    metadata: Some(Box::new(k8s::V1ObjectMeta {
+                        annotations: Some(args.annotations().into_iter().collect()),
+                        labels: Some(args.labels().into_iter().collect()),
+                        ..Default::default()
+                    })),
+                    spec: job.spec,
+                }),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+}
+
+impl Executor for CronJobExecutor {
+    fn spec(&self, args: &ExecutionArgs) -> Result<serde_json::Value> {
+        Ok(serde_json::to_value(self.cron_job_spec(args))?)
+    }
+
+    fn execute(&self, args: ExecutionArgs) -> Result<ExecutionOutput> {
+        let kubectl = args.context.kubectl();
+
+        let handle = kubectl.create_with_run_id(&self.spec(&args)?.to_string(), args.run_id)?;
+        assert_eq!(args.job_namespace, handle.namespace);
+
+        info!(
+            "Created CronJob {:?} with schedule {:?}, its Jobs will show up in `launch list`",
+            urls::headlamp_cron_job(args.context, &handle.namespace, &handle.name),
+            self.schedule.as_str()
+        );
+
+        let ResourceHandle { namespace, name } = handle;
+        Ok(ExecutionOutput {
+            kind: ResourceKind::CronJob,
+            namespace,
+            name,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use container_image_name::ImageName;
+
+    use super::*;
+    use crate::{
+        executor::{tests::args, KubernetesExecutor},
+        kubectl,
+    };
+
+    fn parse(value: &str) -> Result<Schedule, String> {
+        value.parse()
+    }
+
+    #[test]
+    fn schedule_accepts_valid_expressions() {
+        for value in [
+            "0 3 * * *",
+            "*/15 * * * *",
+            "0 9-17 * * MON-FRI",
+            "30 2 1,15 * ?",
+            "0 0 1 jan,jul *",
+            "5-55/10 0 ? * 0",
+            "59 23 31 12 6",
+            "@daily",
+            "@hourly",
+        ] {
+            assert_eq!(parse(value).unwrap().as_str(), value);
+        }
+        assert_eq!(parse("  0  3 * *\t* ").unwrap().as_str(), "0 3 * * *");
+    }
+
+    #[test]
+    fn schedule_explains_invalid_expressions() {
+        let error = |value| parse(value).unwrap_err();
+        assert!(error("0 3 * *").ends_with("for 03:00 every day, got 4"));
+        assert!(error("").ends_with("got 0"));
+        assert!(error("0 0 3 * * *").ends_with("got 6, there is no field for seconds"));
+        assert_eq!(
+            error("60 * * * *"),
+            "the minute 60 is out of range, expected 0 to 59"
+        );
+        assert_eq!(
+            error("0 0 0 * *"),
+            "the day of the month 0 is out of range, expected 1 to 31"
+        );
+        assert_eq!(
+            error("0 0 * * 7"),
+            "the day of the week 7 is out of range, expected 0 to 6"
+        );
+        assert_eq!(
+            error("0 0 * * 300"),
+            "the day of the week 300 is out of range, expected 0 to 6"
+        );
+        assert_eq!(
+            error("0 3am * * *"),
+            "\"3am\" is not a valid hour, expected a number from 0 to 23"
+        );
+        assert_eq!(
+            error("0 0 * foo *"),
+            "\"foo\" is not a valid month, expected a number from 1 to 12 or a name from JAN to DEC"
+        );
+        assert_eq!(
+            error("? * * * *"),
+            "\"?\" is not a valid minute, expected a number from 0 to 59"
+        );
+        assert_eq!(
+            error("0 0 * * FRI-MON"),
+            "the range FRI-MON of the day of the week ends before it starts"
+        );
+        assert_eq!(
+            error("*/0 * * * *"),
+            "the step \"0\" of the minute is not a positive number"
+        );
+        assert_eq!(
+            error("0 0 1, * *"),
+            "\"\" is not a valid day of the month, expected a number from 1 to 31"
+        );
+        assert!(error("@fortnightly").starts_with("unknown shorthand \"@fortnightly\""));
+        assert!(error("CRON_TZ=Europe/Amsterdam 0 3 * * *").contains("can not set a time zone"));
+    }
+
+    #[test]
+    fn spec_wraps_the_job() {
+        let image = ImageName::builder("launch")
+            .with_registry("registry:5000")
+            .with_tag("latest")
+            .build()
+            .unwrap();
+        let args = args(image.as_ref(), 1, None, None);
+        let executor = CronJobExecutor {
+            schedule: parse("0 3 * * *").unwrap(),
+        };
+        let spec = executor.spec(&args).unwrap();
+
+        assert_eq!(spec["apiVersion"], "batch/v1");
+        assert_eq!(spec["kind"], "CronJob");
+        assert_eq!(spec["metadata"]["generateName"], "job-");
+        assert_eq!(spec["metadata"]["namespace"], "launch");
+        assert_eq!(spec["spec"]["schedule"], "0 3 * * *");
+        assert_eq!(spec["spec"]["concurrencyPolicy"], "Forbid");
+        assert_eq!(spec["spec"]["successfulJobsHistoryLimit"], 3);
+        assert_eq!(spec["spec"]["failedJobsHistoryLimit"], 3);
+
+        // The launch annotations are on the CronJob, the Jobs it creates and their Pods.
+        let job_template = &spec["spec"]["jobTemplate"];
+        for metadata in [
+            &spec["metadata"],
+            &job_template["metadata"],
+            &job_template["spec"]["template"]["metadata"],
+        ] {
+            assert_eq!(
+                metadata["annotations"][kubectl::annotation::RUN_ID],
+                args.run_id
+            );
+            assert!(metadata["annotations"][kubectl::annotation::VERSION].is_string());
+            assert_eq!(metadata["labels"][kubectl::label::RUN_ID], args.run_id);
+        }
+
+        // The template is the Job that `launch submit` creates without a schedule.
+        let job = KubernetesExecutor.spec(&args).unwrap();
+        assert_eq!(job_template["spec"], job["spec"]);
+        assert!(job_template["metadata"].get("generateName").is_none());
+    }
+}
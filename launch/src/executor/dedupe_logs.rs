@@ -0,0 +1,347 @@
+//! Collapses the identical lines that Ray workers print at about the same time, such as the progress of every epoch,
+//! for `--dedupe-logs`. Ray forwards the output of each worker to the driver log with a prefix such as
+//! `(RayTrainWorker pid=1234, ip=10.0.0.5)` that identifies the worker, and the logs of the submitter Pod follow the
+//! driver log.
+//!
+//! [`Deduper`] is a pure transformer over the lines and the time at which they arrived. A line of a worker is held for
+//! [`WINDOW`] to collect the same line from other workers. Lines without a worker prefix, which the entrypoint prints
+//! itself, pass through immediately. The lines of a single worker keep their order, and every line is printed exactly
+//! once, either on its own or as part of a collapsed line.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How long the line of a worker waits for the same line from other workers.
+pub const WINDOW: Duration = Duration::from_millis(500);
+
+/// A line to print, along with the prefixes of the workers that printed it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    /// Empty for a line without a worker prefix.
+    pub sources: Vec<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.sources.as_slice() {
+            [] => f.write_str(&self.message),
+            [source] => write!(f, "{source}{}", self.message),
+            [source, ..] => write!(
+                f,
+                "{source}{} (×{} workers)",
+                self.message,
+                self.sources.len()
+            ),
+        }
+    }
+}
+
+struct Pending {
+    first_at: Duration,
+    line: Line,
+}
+
+pub struct Deduper {
+    window: Duration,
+    /// In the order in which they were first seen, which is the order in which they are printed.
+    pending: VecDeque<Pending>,
+}
+
+impl Deduper {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Takes a line of `source` that arrived at `at`, which does not decrease between calls, and returns the lines that
+    /// are ready to be printed.
+    pub fn push(&mut self, source: Option<&str>, message: &str, at: Duration) -> Vec<Line> {
+        let mut ready = self.expire(at);
+        let Some(source) = source else {
+            ready.push(Line {
+                sources: Vec::new(),
+                message: message.to_owned(),
+            });
+            return ready;
+        };
+        // Joining a line that is printed before a pending line of the same source would reorder the lines of the
+        // source, so only the lines after its last pending line are candidates.
+        let candidates = self
+            .pending
+            .iter()
+            .rposition(|pending| pending.line.sources.iter().any(|other| other == source))
+            .map_or(0, |index| index + 1);
+        match self
+            .pending
+            .range_mut(candidates..)
+            .find(|pending| pending.line.message == message)
+        {
+            Some(pending) => pending.line.sources.push(source.to_owned()),
+            None => self.pending.push_back(Pending {
+                first_at: at,
+                line: Line {
+                    sources: vec![source.to_owned()],
+                    message: message.to_owned(),
+                },
+            }),
+        }
+        ready
+    }
+
+    /// Returns the lines that have waited for the window at `now`.
+    pub fn expire(&mut self, now: Duration) -> Vec<Line> {
+        let expired = self
+            .pending
+            .iter()
+            .take_while(|pending| now.saturating_sub(pending.first_at) >= self.window)
+            .count();
+        self.pending
+            .drain(..expired)
+            .map(|pending| pending.line)
+            .collect()
+    }
+
+    /// When the next pending line is ready to be printed.
+    pub fn next_expiry(&self) -> Option<Duration> {
+        self.pending
+            .front()
+            .map(|pending| pending.first_at + self.window)
+    }
+
+    /// Returns all pending lines, at the end of the logs.
+    pub fn flush(&mut self) -> Vec<Line> {
+        self.pending.drain(..).map(|pending| pending.line).collect()
+    }
+}
+
+/// Splits the prefix that Ray adds to the output of a worker, such as `(RayTrainWorker pid=1234, ip=10.0.0.5) `, from
+/// the message. The prefix keeps the space that separates it from the message and the color codes around it.
+pub fn split_worker_prefix(line: &str) -> (Option<&str>, &str) {
+    let split = || {
+        let rest = skip_ansi(line);
+        let end = rest.strip_prefix('(')?.find(") ")? + 3;
+        if !rest[..end].contains("pid=") {
+            return None;
+        }
+        let end = line.len() - skip_ansi(&rest[end..]).len();
+        Some(line.split_at(end))
+    };
+    match split() {
+        Some((prefix, message)) => (Some(prefix), message),
+        None => (None, line),
+    }
+}
+
+/// Skips the ANSI escape sequences at the start of `value`, such as the colors of Ray's prefixes.
+fn skip_ansi(mut value: &str) -> &str {
+    while let Some(rest) = value.strip_prefix("\x1b[") {
+        match rest.find(|c: char| ('\x40'..='\x7e').contains(&c)) {
+            Some(index) => value = &rest[index + 1..],
+            None => break,
+        }
+    }
+    value
+}
+
+/// Runs `follow` with a callback for every log line and passes the lines to `output` as they become ready, with the
+/// identical lines of different workers collapsed. The lines are deduplicated on another thread, so that a line that
+/// waits for the window is printed even when no further lines arrive.
+pub fn follow_deduped<T>(
+    window: Duration,
+    follow: impl FnOnce(&mut dyn FnMut(&str)) -> T,
+    mut output: impl FnMut(&Line) + Send,
+) -> T {
+    let (sender, receiver) = mpsc::channel::<String>();
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            let start = Instant::now();
+            let mut deduper = Deduper::new(window);
+            loop {
+                let received = match deduper.next_expiry() {
+                    Some(expiry) => receiver.recv_timeout(expiry.saturating_sub(start.elapsed())),
+                    None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                let ready = match received {
+                    Ok(line) => {
+                        let (source, message) = split_worker_prefix(&line);
+                        deduper.push(source, message, start.elapsed())
+                    }
+                    Err(RecvTimeoutError::Timeout) => deduper.expire(start.elapsed()),
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
+                ready.iter().for_each(&mut output);
+            }
+            deduper.flush().iter().for_each(&mut output);
+        });
+        let result = follow(&mut |line| {
+            // The receiver only goes away when the printing thread panicked, which the scope propagates.
+            let _ = sender.send(line.to_owned());
+        });
+        drop(sender);
+        result
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    const PREFIX_A: &str = "(RayTrainWorker pid=101, ip=10.0.0.5) ";
+    const PREFIX_B: &str = "(RayTrainWorker pid=102, ip=10.0.0.6) ";
+
+    fn ms(value: u64) -> Duration {
+        Duration::from_millis(value)
+    }
+
+    fn display(lines: &[Line]) -> Vec<String> {
+        lines.iter().map(Line::to_string).collect()
+    }
+
+    #[test]
+    fn split_worker_prefix_works() {
+        assert_eq!(
+            split_worker_prefix("(RayTrainWorker pid=101, ip=10.0.0.5) epoch 1"),
+            (Some(PREFIX_A), "epoch 1")
+        );
+        assert_eq!(
+            split_worker_prefix("(pid=101) loss (0.5) "),
+            (Some("(pid=101) "), "loss (0.5) ")
+        );
+        let colored = "\x1b[2m\x1b[36m(pid=101, ip=10.0.0.5) \x1b[0mepoch 1";
+        assert_eq!(
+            split_worker_prefix(colored),
+            (
+                Some("\x1b[2m\x1b[36m(pid=101, ip=10.0.0.5) \x1b[0m"),
+                "epoch 1"
+            )
+        );
+        for line in ["epoch 1", "(note) epoch 1", "(pid=101)", "", "\x1b[36m"] {
+            assert_eq!(split_worker_prefix(line), (None, line));
+        }
+    }
+
+    #[test]
+    fn deduper_collapses_identical_lines() {
+        let mut deduper = Deduper::new(ms(500));
+        assert!(deduper.push(Some(PREFIX_A), "epoch 1", ms(0)).is_empty());
+        assert!(deduper.push(Some(PREFIX_B), "epoch 1", ms(100)).is_empty());
+        assert!(deduper.push(Some(PREFIX_B), "loss 0.5", ms(200)).is_empty());
+        assert_eq!(deduper.next_expiry(), Some(ms(500)));
+        assert_eq!(
+            display(&deduper.push(None, "saving checkpoint", ms(300))),
+            ["saving checkpoint"]
+        );
+        assert_eq!(
+            display(&deduper.expire(ms(600))),
+            [format!("{PREFIX_A}epoch 1 (×2 workers)")]
+        );
+        assert_eq!(display(&deduper.flush()), [format!("{PREFIX_B}loss 0.5")]);
+        assert_eq!(deduper.next_expiry(), None);
+    }
+
+    #[test]
+    fn deduper_keeps_the_order_of_a_worker() {
+        let mut deduper = Deduper::new(ms(500));
+        deduper.push(Some(PREFIX_B), "b", ms(0));
+        deduper.push(Some(PREFIX_A), "a", ms(1));
+        // Joining the pending "b" of worker B would print "b" of worker A before its "a".
+        deduper.push(Some(PREFIX_A), "b", ms(2));
+        // A worker that repeats a line starts a new one.
+        deduper.push(Some(PREFIX_B), "b", ms(3));
+        assert_eq!(
+            display(&deduper.flush()),
+            [
+                format!("{PREFIX_B}b"),
+                format!("{PREFIX_A}a"),
+                format!("{PREFIX_A}b (×2 workers)"),
+            ]
+        );
+    }
+
+    /// For every seed, a random interleaving of the lines of some workers and the entrypoint loses no line and keeps
+    /// the order of the lines of every source.
+    #[test]
+    fn deduper_loses_no_lines_and_keeps_the_order_of_each_source() {
+        for seed in 0..200 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let window = ms(rng.gen_range(1..50));
+            let mut deduper = Deduper::new(window);
+            let mut expected: HashMap<Option<String>, Vec<String>> = HashMap::new();
+            let mut output = Vec::new();
+            let mut now = Duration::ZERO;
+            for _ in 0..rng.gen_range(0..300) {
+                now += ms(rng.gen_range(0..10));
+                let source = match rng.gen_range(0..6) {
+                    0 => None,
+                    worker => Some(format!("(pid={worker}) ")),
+                };
+                let message = format!("step {}", rng.gen_range(0..4));
+                expected
+                    .entry(source.clone())
+                    .or_default()
+                    .push(message.clone());
+                if rng.gen_bool(0.1) {
+                    output.extend(deduper.expire(now));
+                }
+                output.extend(deduper.push(source.as_deref(), &message, now));
+            }
+            output.extend(deduper.flush());
+
+            let mut actual: HashMap<Option<String>, Vec<String>> = HashMap::new();
+            for line in &output {
+                if line.sources.is_empty() {
+                    actual.entry(None).or_default().push(line.message.clone());
+                }
+                for (index, source) in line.sources.iter().enumerate() {
+                    assert!(
+                        !line.sources[..index].contains(source),
+                        "seed {seed}: {line:?} has a source twice"
+                    );
+                    actual
+                        .entry(Some(source.clone()))
+                        .or_default()
+                        .push(line.message.clone());
+                }
+            }
+            assert_eq!(actual, expected, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn follow_deduped_prints_lines_after_the_window() {
+        let (sender, printed) = mpsc::channel();
+        let result = follow_deduped(
+            WINDOW,
+            |on_line| {
+                on_line(&format!("{PREFIX_A}epoch 1"));
+                on_line(&format!("{PREFIX_B}epoch 1"));
+                // No further lines arrive, like between two epochs, so the line is printed once its window expires.
+                assert_eq!(
+                    printed.recv_timeout(Duration::from_secs(30)),
+                    Ok(format!("{PREFIX_A}epoch 1 (×2 workers)"))
+                );
+                on_line("done");
+                on_line(&format!("{PREFIX_A}epoch 2"));
+                "followed"
+            },
+            move |line| sender.send(line.to_string()).unwrap(),
+        );
+        assert_eq!(result, "followed");
+        assert_eq!(
+            printed.try_iter().collect::<Vec<_>>(),
+            ["done".to_owned(), format!("{PREFIX_A}epoch 2")]
+        );
+    }
+}
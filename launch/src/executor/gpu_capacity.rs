@@ -0,0 +1,517 @@
+//! Compares the GPUs that a submission requests with the free GPUs of the cluster. Katib creates `parallelTrialCount`
+//! trials at once and the trials that do not fit wait in Pending, which is easy to miss. `--wait-for-capacity` holds a
+//! submission until its Pods fit.
+
+use std::{collections::HashMap, time::Duration};
+
+use log::{debug, info, warn};
+
+use crate::{
+    kubectl::{Kubectl, Node, Pod, PodPhase},
+    unit::bytes::{self, Bytes},
+    Result,
+};
+
+const GPU_RESOURCE: &str = "nvidia.com/gpu";
+const GPU_MEMORY_LABEL: &str = "nvidia.com/gpu.memory";
+
+/// How often the capacity is evaluated while waiting for it.
+const CAPACITY_POLLING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The GPUs of a node that the Pods can be scheduled on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeGpus {
+    pub allocatable: u32,
+    /// The allocatable GPUs that no Pod has requested.
+    pub free: u32,
+}
+
+/// The GPUs of the nodes that the Pods can be scheduled on.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GpuInventory {
+    pub nodes: Vec<NodeGpus>,
+}
+
+impl GpuInventory {
+    /// The allocatable GPUs of all nodes.
+    pub fn total(&self) -> u32 {
+        self.nodes.iter().map(|node| node.allocatable).sum()
+    }
+
+    pub fn free(&self) -> u32 {
+        self.nodes.iter().map(|node| node.free).sum()
+    }
+
+    /// The most allocatable GPUs of a single node, which bounds the GPUs of a single Pod.
+    pub fn max_per_node(&self) -> u32 {
+        self.nodes
+            .iter()
+            .map(|node| node.allocatable)
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn largest_free(&self) -> u32 {
+        self.nodes.iter().map(|node| node.free).max().unwrap_or(0)
+    }
+
+    /// How many Pods that each request `gpus` fit on the free GPUs, given that a Pod can not span nodes.
+    pub fn free_slots(&self, gpus: u32) -> u32 {
+        match gpus {
+            0 => u32::MAX,
+            _ => self.nodes.iter().map(|node| node.free / gpus).sum(),
+        }
+    }
+}
+
+fn parse_gpus(value: Option<&String>) -> u32 {
+    value.and_then(|value| value.parse().ok()).unwrap_or(0)
+}
+
+/// Whether the node can run a Pod with the `--gpu-mem` node affinity, see [`super::ExecutionArgs`].
+fn matches_gpu_mem(node: &Node, gpu_mem: Option<Bytes>) -> bool {
+    let Some(gpu_mem) = gpu_mem else {
+        return true;
+    };
+    node.metadata
+        .labels
+        .get(GPU_MEMORY_LABEL)
+        .and_then(|value| value.parse::<u64>().ok())
+        .is_some_and(|mib| mib >= gpu_mem.get::<bytes::mebibyte>())
+}
+
+/// The GPUs that a Pod holds on its node. Extended resources such as GPUs default their request to their limit.
+fn requested_gpus(pod: &Pod) -> u32 {
+    pod.spec
+        .containers
+        .iter()
+        .map(|container| {
+            let resources = &container.resources;
+            parse_gpus(
+                resources
+                    .requests
+                    .get(GPU_RESOURCE)
+                    .or(resources.limits.get(GPU_RESOURCE)),
+            )
+        })
+        .sum()
+}
+
+/// Counts the GPUs of the schedulable nodes that satisfy the `--gpu-mem` filter, and subtracts the GPUs requested by
+/// the Pods that are bound to them and have not terminated. Pods that are being deleted keep their GPUs until they are
+/// gone, like they do for the scheduler.
+pub fn gpu_inventory(nodes: &[Node], pods: &[Pod], gpu_mem: Option<Bytes>) -> GpuInventory {
+    let mut gpus: HashMap<&str, NodeGpus> = nodes
+        .iter()
+        .filter(|node| node.spec.unschedulable != Some(true) && matches_gpu_mem(node, gpu_mem))
+        .map(|node| {
+            let allocatable = parse_gpus(node.status.allocatable.get(GPU_RESOURCE));
+            (
+                node.metadata.name.as_str(),
+                NodeGpus {
+                    allocatable,
+                    free: allocatable,
+                },
+            )
+        })
+        .collect();
+
+    for pod in pods {
+        if matches!(pod.status.phase, PodPhase::Succeeded | PodPhase::Failed) {
+            continue;
+        }
+        if let Some(node) = pod
+            .spec
+            .node_name
+            .as_deref()
+            .and_then(|node_name| gpus.get_mut(node_name))
+        {
+            node.free = node.free.saturating_sub(requested_gpus(pod));
+        }
+    }
+
+    let mut nodes = gpus.into_values().collect::<Vec<_>>();
+    // Largest first, which makes the inventory independent of the order of the nodes.
+    nodes.sort_by_key(|node| std::cmp::Reverse((node.allocatable, node.free)));
+    GpuInventory { nodes }
+}
+
+/// Describes the `--gpu-mem` filter as a suffix of "node" or "nodes".
+fn describe_filter(gpu_mem: Option<Bytes>) -> String {
+    match gpu_mem {
+        Some(gpu_mem) => format!(
+            " with at least {}MiB of GPU memory",
+            gpu_mem.get::<bytes::mebibyte>()
+        ),
+        None => String::new(),
+    }
+}
+
+/// Returns a warning when the parallel trials request more GPUs than are free, and an error when they request more
+/// GPUs than the matching nodes have in total, which can never be satisfied.
+pub fn check_trial_gpus(
+    parallel_trial_count: u32,
+    gpus_per_trial: u32,
+    gpu_mem: Option<Bytes>,
+    inventory: &GpuInventory,
+) -> Result<Option<String>, String> {
+    if gpus_per_trial == 0 {
+        return Ok(None);
+    }
+    let filter = describe_filter(gpu_mem);
+    if gpus_per_trial > inventory.max_per_node() {
+        return Err(format!(
+            "Each trial requests {gpus_per_trial} GPUs, but no node{filter} has more than {} allocatable GPUs, so the trials can never be scheduled",
+            inventory.max_per_node()
+        ));
+    }
+    let requested = parallel_trial_count.saturating_mul(gpus_per_trial);
+    let total = inventory.total();
+    if requested > total {
+        return Err(format!(
+            "parallelTrialCount {parallel_trial_count} with {gpus_per_trial} GPUs per trial requests {requested} GPUs, but the nodes{filter} have {} allocatable GPUs in total. Lower parallelTrialCount to at most {} or pass a smaller `--gpus`.",
+            total,
+            total / gpus_per_trial
+        ));
+    }
+    if requested > inventory.free() {
+        return Ok(Some(format!(
+            "parallelTrialCount {parallel_trial_count} with {gpus_per_trial} GPUs per trial requests {requested} GPUs, but only {} of the {} GPUs of the nodes{filter} are free. About {} of the trials start now and the others wait in Pending until GPUs become free.",
+            inventory.free(),
+            total,
+            inventory.free_slots(gpus_per_trial)
+        )));
+    }
+    Ok(None)
+}
+
+/// Checks the GPUs of the parallel trials against the cluster. The check is skipped when the nodes or Pods can not be
+/// listed, because the user may not be allowed to.
+pub fn check(
+    kubectl: &Kubectl,
+    parallel_trial_count: u32,
+    gpus_per_trial: u32,
+    gpu_mem: Option<Bytes>,
+) -> Result<()> {
+    if gpus_per_trial == 0 {
+        return Ok(());
+    }
+    let inventory = match kubectl
+        .nodes()
+        .and_then(|nodes| Ok((nodes, kubectl.pods_in_all_namespaces()?)))
+    {
+        Ok((nodes, pods)) => gpu_inventory(&nodes, &pods, gpu_mem),
+        Err(error) => {
+            debug!("Skipping the GPU capacity check because the cluster could not be inspected: {error}");
+            return Ok(());
+        }
+    };
+    if let Some(warning) =
+        check_trial_gpus(parallel_trial_count, gpus_per_trial, gpu_mem, &inventory)?
+    {
+        warn!("{warning}");
+    }
+    Ok(())
+}
+
+/// Describes why `pod_count` Pods that each request `gpus` can not all be scheduled right now, or returns `None` when
+/// they fit on the free GPUs.
+pub fn blocking_reason(
+    inventory: &GpuInventory,
+    pod_count: u32,
+    gpus: u32,
+    gpu_mem: Option<Bytes>,
+) -> Option<String> {
+    if inventory.free_slots(gpus) >= pod_count {
+        return None;
+    }
+    let gpu_type = match gpu_mem {
+        Some(gpu_mem) => format!(" GPUs with {}MiB", gpu_mem.get::<bytes::mebibyte>()),
+        None => " GPUs".to_owned(),
+    };
+    Some(match pod_count {
+        1 => format!(
+            "waiting for {gpus}×{gpu_type}: largest free node has {}",
+            inventory.largest_free()
+        ),
+        _ => format!(
+            "waiting for {pod_count} Pods of {gpus}×{gpu_type}: {} fit on the free nodes",
+            inventory.free_slots(gpus)
+        ),
+    })
+}
+
+/// Waits until `pod_count` Pods that each request `gpus` fit on the free GPUs of the cluster, logging what is missing
+/// whenever it changes. Fails when the Pods can never fit or when `timeout` elapses first.
+pub fn wait_for_capacity(
+    kubectl: &Kubectl,
+    pod_count: u32,
+    gpus: u32,
+    gpu_mem: Option<Bytes>,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    if gpus == 0 {
+        return Ok(());
+    }
+    let start = std::time::Instant::now();
+    let mut last_reason = None;
+    loop {
+        let inventory = gpu_inventory(
+            &kubectl.nodes()?,
+            &kubectl.pods_in_all_namespaces()?,
+            gpu_mem,
+        );
+        if gpus > inventory.max_per_node() {
+            return Err(format!(
+                "Each Pod requests {gpus} GPUs, but no node{} has more than {} allocatable GPUs, so the submission can never be scheduled",
+                describe_filter(gpu_mem),
+                inventory.max_per_node()
+            )
+            .into());
+        }
+        let Some(reason) = blocking_reason(&inventory, pod_count, gpus, gpu_mem) else {
+            info!("Capacity is available after {:.0?}", start.elapsed());
+            return Ok(());
+        };
+        if last_reason.as_ref() != Some(&reason) {
+            info!("{reason}");
+            last_reason = Some(reason);
+        }
+        if let Some(timeout) = timeout {
+            if start.elapsed() + CAPACITY_POLLING_INTERVAL > timeout {
+                return Err(format!(
+                    "Gave up waiting for capacity after {:.0?}, last {}",
+                    start.elapsed(),
+                    last_reason.unwrap_or_default()
+                )
+                .into());
+            }
+        }
+        std::thread::sleep(CAPACITY_POLLING_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, gpus: u32, gpu_memory_mib: Option<u64>, unschedulable: bool) -> Node {
+        let labels = gpu_memory_mib
+            .map(|mib| serde_json::json!({ GPU_MEMORY_LABEL: mib.to_string() }))
+            .unwrap_or_else(|| serde_json::json!({}));
+        serde_json::from_value(serde_json::json!({
+            "metadata": {
+                "name": name,
+                "creationTimestamp": "2025-01-13T12:00:00Z",
+                "labels": labels,
+            },
+            "spec": { "unschedulable": unschedulable },
+            "status": {
+                "addresses": [],
+                "allocatable": { GPU_RESOURCE: gpus.to_string() },
+                "capacity": { GPU_RESOURCE: gpus.to_string() },
+                "conditions": [],
+                "nodeInfo": {
+                    "architecture": "amd64",
+                    "containerRuntimeVersion": "containerd://1.7.0",
+                    "kernelVersion": "6.1.0",
+                    "kubeProxyVersion": "v1.31.0",
+                    "kubeletVersion": "v1.31.0",
+                    "operatingSystem": "linux",
+                    "osImage": "Ubuntu 22.04",
+                },
+            },
+        }))
+        .unwrap()
+    }
+
+    fn pod(node_name: &str, gpus: u32, phase: &str) -> Pod {
+        pod_with_metadata(
+            node_name,
+            gpus,
+            phase,
+            serde_json::json!({
+                "name": format!("pod-on-{node_name}"),
+                "namespace": "launch",
+                "creationTimestamp": "2025-01-13T12:00:00Z",
+            }),
+        )
+    }
+
+    fn terminating_pod(node_name: &str, gpus: u32) -> Pod {
+        pod_with_metadata(
+            node_name,
+            gpus,
+            "Running",
+            serde_json::json!({
+                "name": format!("terminating-pod-on-{node_name}"),
+                "namespace": "launch",
+                "creationTimestamp": "2025-01-13T12:00:00Z",
+                "deletionTimestamp": "2025-01-13T13:00:00Z",
+            }),
+        )
+    }
+
+    fn pod_with_metadata(
+        node_name: &str,
+        gpus: u32,
+        phase: &str,
+        metadata: serde_json::Value,
+    ) -> Pod {
+        serde_json::from_value(serde_json::json!({
+            "metadata": metadata,
+            "spec": {
+                "nodeName": node_name,
+                "containers": [
+                    { "name": "main", "resources": { "limits": { GPU_RESOURCE: gpus.to_string() } } },
+                ],
+            },
+            "status": { "phase": phase },
+        }))
+        .unwrap()
+    }
+
+    fn mib(value: u64) -> Bytes {
+        Bytes::new::<bytes::mebibyte>(value).unwrap()
+    }
+
+    fn inventory(nodes: &[(u32, u32)]) -> GpuInventory {
+        GpuInventory {
+            nodes: nodes
+                .iter()
+                .map(|&(allocatable, free)| NodeGpus { allocatable, free })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn gpu_inventory_works() {
+        let nodes = [
+            node("a100", 8, Some(81920), false),
+            node("a10", 4, Some(24576), false),
+            node("cordoned", 8, Some(81920), true),
+            node("cpu", 0, None, false),
+        ];
+        let pods = [
+            pod("a100", 2, "Running"),
+            pod("a10", 4, "Pending"),
+            pod("a100", 8, "Succeeded"),
+        ];
+
+        let inventory = gpu_inventory(&nodes, &pods, None);
+        assert_eq!(
+            inventory.nodes,
+            [
+                NodeGpus {
+                    allocatable: 8,
+                    free: 6
+                },
+                NodeGpus {
+                    allocatable: 4,
+                    free: 0
+                },
+                NodeGpus {
+                    allocatable: 0,
+                    free: 0
+                },
+            ]
+        );
+        assert_eq!(
+            (
+                inventory.total(),
+                inventory.free(),
+                inventory.max_per_node(),
+                inventory.largest_free()
+            ),
+            (12, 6, 8, 6)
+        );
+        assert_eq!(
+            gpu_inventory(&nodes, &pods, Some(mib(40960))).nodes,
+            [NodeGpus {
+                allocatable: 8,
+                free: 6
+            }]
+        );
+        assert_eq!(
+            gpu_inventory(&nodes, &[], Some(mib(102400))),
+            GpuInventory::default()
+        );
+    }
+
+    #[test]
+    fn check_trial_gpus_works() {
+        let inventory = inventory(&[(8, 8), (8, 4), (8, 0), (8, 0)]);
+
+        assert_eq!(check_trial_gpus(16, 0, None, &inventory), Ok(None));
+        assert_eq!(check_trial_gpus(1, 8, None, &inventory), Ok(None));
+
+        let warning = check_trial_gpus(4, 8, None, &inventory).unwrap().unwrap();
+        assert!(warning.contains("requests 32 GPUs"), "{warning}");
+        assert!(warning.contains("only 12 of the 32 GPUs"), "{warning}");
+        assert!(
+            warning.contains("About 1 of the trials start now"),
+            "{warning}"
+        );
+
+        let error = check_trial_gpus(16, 8, None, &inventory).unwrap_err();
+        assert!(error.contains("requests 128 GPUs"), "{error}");
+        assert!(
+            error.contains("the nodes have 32 allocatable GPUs"),
+            "{error}"
+        );
+        assert!(error.contains("at most 4"), "{error}");
+
+        let error = check_trial_gpus(1, 16, Some(mib(40960)), &inventory).unwrap_err();
+        assert!(
+            error.contains("no node with at least 40960MiB of GPU memory has more than 8"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn gpu_inventory_counts_terminating_pods() {
+        let nodes = [
+            node("h100-a", 8, None, false),
+            node("h100-b", 8, None, false),
+        ];
+        let pods = [
+            terminating_pod("h100-a", 8),
+            pod("h100-b", 6, "Running"),
+            pod("h100-b", 2, "Failed"),
+        ];
+
+        let inventory = gpu_inventory(&nodes, &pods, None);
+        assert_eq!(inventory.free(), 2);
+        assert_eq!(inventory.largest_free(), 2);
+    }
+
+    #[test]
+    fn free_slots_works() {
+        let inventory = inventory(&[(8, 6), (8, 3), (4, 4)]);
+        assert_eq!(inventory.free_slots(0), u32::MAX);
+        assert_eq!(inventory.free_slots(1), 13);
+        assert_eq!(inventory.free_slots(3), 4);
+        assert_eq!(inventory.free_slots(4), 2);
+        assert_eq!(inventory.free_slots(8), 0);
+        assert_eq!(GpuInventory::default().free_slots(1), 0);
+    }
+
+    #[test]
+    fn blocking_reason_works() {
+        let inventory = inventory(&[(8, 2), (8, 1), (8, 0)]);
+        assert_eq!(blocking_reason(&inventory, 1, 2, None), None);
+        assert_eq!(
+            blocking_reason(&inventory, 1, 4, None).as_deref(),
+            Some("waiting for 4× GPUs: largest free node has 2")
+        );
+        assert_eq!(
+            blocking_reason(&inventory, 1, 4, Some(mib(81920))).as_deref(),
+            Some("waiting for 4× GPUs with 81920MiB: largest free node has 2")
+        );
+        assert_eq!(blocking_reason(&inventory, 3, 1, None), None);
+        assert_eq!(
+            blocking_reason(&inventory, 2, 2, None).as_deref(),
+            Some("waiting for 2 Pods of 2× GPUs: 1 fit on the free nodes")
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! Detects containers that run another image than launch submitted. A registry mirror that serves a stale manifest can
+//! cause this, in which case the job does not run the code that was just built.
+
+use container_image_name::ImageNameRef;
+use log::{debug, warn};
+
+use crate::kubectl::Pod;
+
+const TROUBLESHOOTING_URL: &str =
+    "https://github.com/Astera-org/launch#the-container-runs-another-image-than-was-submitted";
+
+/// Container runtimes may prefix the `imageID` of a container status with a scheme.
+const IMAGE_ID_SCHEMES: &[&str] = &["docker-pullable://", "docker://"];
+
+/// Returns the digest of the manifest that a container runs, given the `imageID` of its status. Returns `None` when the
+/// `imageID` is empty or only identifies the image locally, like `docker://sha256:<hex>`, because that can not be
+/// compared with a manifest digest.
+pub fn image_id_digest(image_id: &str) -> Option<&str> {
+    let reference = IMAGE_ID_SCHEMES
+        .iter()
+        .find_map(|scheme| image_id.strip_prefix(scheme))
+        .unwrap_or(image_id);
+    ImageNameRef::try_from(reference).ok()?.digest()
+}
+
+/// Returns the digest of the image that the container runs, if it is known.
+pub fn observed_digest<'a>(pod: &'a Pod, container_name: &str) -> Option<&'a str> {
+    pod.status
+        .container_statuses
+        .iter()
+        .find(|status| status.name == container_name)
+        .and_then(|status| image_id_digest(&status.image_id))
+}
+
+/// A container that runs another image than the one pinned by digest in its spec.
+#[derive(Debug, PartialEq, Eq)]
+struct Mismatch<'a> {
+    container_name: &'a str,
+    submitted: &'a str,
+    observed: &'a str,
+}
+
+fn mismatches(pod: &Pod) -> Vec<Mismatch<'_>> {
+    pod.spec
+        .containers
+        .iter()
+        .filter_map(|container| {
+            let submitted = ImageNameRef::try_from(container.image.as_deref()?)
+                .ok()?
+                .digest()?;
+            let observed = observed_digest(pod, &container.name)?;
+            (submitted != observed).then_some(Mismatch {
+                container_name: &container.name,
+                submitted,
+                observed,
+            })
+        })
+        .collect()
+}
+
+/// Warns about the containers of the Pod that run another image than launch submitted. Containers whose image is not
+/// pinned by digest, or whose runtime does not report the digest it runs, are not checked.
+pub fn warn_about_mismatches(pod: &Pod) {
+    let namespace = &pod.metadata.namespace;
+    let pod_name = &pod.metadata.name;
+    let mismatches = mismatches(pod);
+    if mismatches.is_empty() {
+        debug!("The containers of Pod {namespace}/{pod_name} run the submitted images");
+    }
+    for Mismatch {
+        container_name,
+        submitted,
+        observed,
+    } in mismatches
+    {
+        warn!(
+            "Container {container_name} of Pod {namespace}/{pod_name} runs image {observed} instead of the submitted image {submitted}. A registry mirror may have served a stale manifest, so the container may not run the code that was just built. See {TROUBLESHOOTING_URL}"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUBMITTED: &str =
+        "sha256:89b7200c2632bdf418a6bc10f8a26495ab929947c6d962833a9114310df15532";
+    const STALE: &str = "sha256:0f3a6bd1c8e6ea1bf4b95b0b4e8ae2d0a8e0d3c0f6c9d1b8a4e7f2c5d9b3a1e6";
+
+    #[test]
+    fn image_id_digest_works() {
+        assert_eq!(
+            image_id_digest(&format!(
+                "berkeley-docker.taila1eba.ts.net/launch@{SUBMITTED}"
+            )),
+            Some(SUBMITTED)
+        );
+        assert_eq!(
+            image_id_digest(&format!(
+                "docker-pullable://berkeley-docker.taila1eba.ts.net/launch@{SUBMITTED}"
+            )),
+            Some(SUBMITTED)
+        );
+        assert_eq!(
+            image_id_digest(&format!("docker.io/library/ubuntu@{SUBMITTED}")),
+            Some(SUBMITTED)
+        );
+        assert_eq!(image_id_digest(&format!("docker://{SUBMITTED}")), None);
+        assert_eq!(image_id_digest(SUBMITTED), None);
+        assert_eq!(image_id_digest(""), None);
+    }
+
+    fn running_pod(spec_image: &str, image_id: &str) -> Pod {
+        serde_json::from_value(serde_json::json!({
+            "metadata": {
+                "name": "launch-abc",
+                "namespace": "launch",
+                "creationTimestamp": "2025-01-13T12:00:00Z",
+            },
+            "spec": {
+                "containers": [{ "name": "main", "image": spec_image }],
+            },
+            "status": {
+                "phase": "Running",
+                "containerStatuses": [{
+                    "name": "main",
+                    "image": spec_image,
+                    "imageID": image_id,
+                    "state": { "running": { "startedAt": "2025-01-13T12:01:00Z" } },
+                }],
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn mismatches_works() {
+        let image = format!("berkeley-docker.taila1eba.ts.net/launch:abc@{SUBMITTED}");
+
+        let pod = running_pod(
+            &image,
+            &format!("docker-pullable://berkeley-docker.taila1eba.ts.net/launch@{SUBMITTED}"),
+        );
+        assert_eq!(observed_digest(&pod, "main"), Some(SUBMITTED));
+        assert_eq!(mismatches(&pod), []);
+
+        let pod = running_pod(
+            &image,
+            &format!("berkeley-docker.taila1eba.ts.net/launch@{STALE}"),
+        );
+        assert_eq!(
+            mismatches(&pod),
+            [Mismatch {
+                container_name: "main",
+                submitted: SUBMITTED,
+                observed: STALE,
+            }]
+        );
+
+        let pod = running_pod(
+            "berkeley-docker.taila1eba.ts.net/launch:abc",
+            &format!("berkeley-docker.taila1eba.ts.net/launch@{STALE}"),
+        );
+        assert_eq!(mismatches(&pod), []);
+
+        let pod = running_pod(&image, "");
+        assert_eq!(observed_digest(&pod, "main"), None);
+        assert_eq!(mismatches(&pod), []);
+    }
+}
@@ -1,9 +1,13 @@
 //! The katib experiment backend implementation.
 
+mod algorithms;
+mod events;
+
 use std::collections::HashMap;
 
 use ::katib::models as km;
 use ::kubernetes::models as k8s;
+pub use events::{Event, Progress, Reporter};
 use katib::models::{
     V1beta1AlgorithmSetting, V1beta1AlgorithmSpec, V1beta1CollectorSpec, V1beta1FeasibleSpace,
     V1beta1FileSystemPath, V1beta1MetricStrategy, V1beta1MetricsCollectorSpec,
@@ -11,8 +15,8 @@ use katib::models::{
 };
 use log::{error, info, warn};
 
-use super::{ExecutionArgs, ExecutionOutput, Executor, Result};
-use crate::{cli::ClusterContext, executor::common, kubectl::ResourceHandle};
+use super::{monitor::Cluster, ExecutionArgs, ExecutionOutput, Executor, ResourceKind, Result};
+use crate::{cli::ClusterContext, executor::common, kubectl::ResourceHandle, urls};
 
 fn sanitize_param_name(param_name: &str) -> String {
     // '.' is special because it's used in the template substitution that katib does on
@@ -94,7 +98,42 @@ const TENSORBOARD_DIR_FLAG: &str = "--tensorboard_dir";
 const LAUNCH_KATIB_TRIAL_NAME: &str = "__launchKatibTrialName";
 const LAUNCH_KATIB_NAMESPACE: &str = "__launchKatibNamespace";
 
-fn trial_spec(input_exp_spec: &crate::katib::ExperimentSpec, args: &ExecutionArgs) -> k8s::V1Job {
+/// Environment variables that launch sets in each trial and that can therefore not be set through `trialEnv`.
+const RESERVED_TRIAL_ENV: &[&str] = &[
+    "KATIB_BASE_URL",
+    "KATIB_TRIAL_NAME",
+    "KATIB_NAMESPACE",
+    super::RUN_ID_ENV,
+];
+
+fn trial_spec(
+    input_exp_spec: &crate::katib::ExperimentSpec,
+    args: &ExecutionArgs,
+) -> Result<k8s::V1Job> {
+    if let Some(var) = input_exp_spec
+        .trial_env
+        .iter()
+        .find(|var| RESERVED_TRIAL_ENV.contains(&var.name.as_str()))
+    {
+        return Err(format!(
+            "trialEnv can not set {:?} because launch sets it for each trial",
+            var.name
+        )
+        .into());
+    }
+
+    let databrickscfg_name = match input_exp_spec.mount_databricks_cfg {
+        None => args.databrickscfg_name,
+        Some(false) => None,
+        Some(true) => Some(args.databrickscfg_name.ok_or(
+            "mountDatabricksCfg is true but no databricks configuration is available, see `--databrickscfg-mode`",
+        )?),
+    };
+    let args = &ExecutionArgs {
+        databrickscfg_name,
+        ..args.clone()
+    };
+
     let container_args = {
         let param_args = input_exp_spec.parameters.iter().map(|p| {
             let name = p.name.as_str();
@@ -117,8 +156,7 @@ fn trial_spec(input_exp_spec: &crate::katib::ExperimentSpec, args: &ExecutionArg
     // Katib doesn't allow metadata in the trial spec
     trial_spec.metadata = None;
 
-    // https://www.kubeflow.org/docs/components/katib/user-guides/trial-template/#use-metadata-in-trial-template
-    trial_spec
+    let env = trial_spec
         .spec
         .as_mut()
         .unwrap()
@@ -128,11 +166,26 @@ fn trial_spec(input_exp_spec: &crate::katib::ExperimentSpec, args: &ExecutionArg
         .unwrap()
         .containers[0]
         .env
-        .as_mut()
-        .unwrap()
-        .extend(
-            [
-                ("KATIB_BASE_URL", args.context.katib_url().to_owned()),
+        .get_or_insert_with(Vec::new);
+
+    super::merge_env(
+        env,
+        input_exp_spec.trial_env.iter().map(|var| k8s::V1EnvVar {
+            name: var.name.clone(),
+            value: Some(var.value.clone()),
+            value_from: None,
+        }),
+    );
+
+    // https://www.kubeflow.org/docs/components/katib/user-guides/trial-template/#use-metadata-in-trial-template
+    // A context without the katib UI has no base URL to pass.
+    super::merge_env(
+        env,
+        args.context
+            .katib_url()
+            .map(|katib_url| ("KATIB_BASE_URL", katib_url.to_owned()))
+            .into_iter()
+            .chain([
                 (
                     "KATIB_TRIAL_NAME",
                     format!("${{trialParameters.{LAUNCH_KATIB_TRIAL_NAME}}}"),
@@ -141,23 +194,22 @@ fn trial_spec(input_exp_spec: &crate::katib::ExperimentSpec, args: &ExecutionArg
                     "KATIB_NAMESPACE",
                     format!("${{trialParameters.{LAUNCH_KATIB_NAMESPACE}}}"),
                 ),
-            ]
-            .into_iter()
+            ])
             .map(|(k, v)| k8s::V1EnvVar {
                 name: k.to_owned(),
                 value: Some(v),
                 value_from: None,
             }),
-        );
+    );
 
-    trial_spec
+    Ok(trial_spec)
 }
 
 fn experiment(
     input_exp_spec: crate::katib::ExperimentSpec,
-    args: &mut ExecutionArgs,
+    args: &ExecutionArgs,
 ) -> Result<km::V1beta1Experiment> {
-    let trial_spec = trial_spec(&input_exp_spec, args);
+    let trial_spec = trial_spec(&input_exp_spec, args)?;
 
     let exp_spec = km::V1beta1ExperimentSpec {
         objective: Some(Box::new(V1beta1ObjectiveSpec {
@@ -247,23 +299,13 @@ fn experiment(
         ..Default::default()
     };
 
-    // Ensure the experiment name is at most [40
-    // characters](https://github.com/kubeflow/katib/issues/2454#issuecomment-2508754891) to avoid
-    // [an issue with katib](https://github.com/kubeflow/katib/issues/2454).
-    const EXPERIMENT_NAME_MAX_LEN: usize = 40;
-    let generate_name = if args.generate_name.len() <= EXPERIMENT_NAME_MAX_LEN {
-        args.generate_name
-    } else {
-        warn!("Truncating experiment name to {EXPERIMENT_NAME_MAX_LEN} characters");
-        &args.generate_name[..EXPERIMENT_NAME_MAX_LEN]
-    };
-
     Ok(km::V1beta1Experiment {
         api_version: Some("kubeflow.org/v1beta1".to_owned()), // https://github.com/kubeflow/katib/blob/2b41ae62ab3905984e02123218351a703c03bf56/sdk/python/v1beta1/kubeflow/katib/constants/constants.py#L28
         kind: Some("Experiment".to_owned()), // https://github.com/kubeflow/katib/blob/2b41ae62ab3905984e02123218351a703c03bf56/sdk/python/v1beta1/kubeflow/katib/constants/constants.py#L29
         metadata: Some(k8s::V1ObjectMeta {
-            annotations: Some(args.annotations().clone()),
-            generate_name: Some(generate_name.to_owned()),
+            annotations: Some(args.annotations().into_iter().collect()),
+            generate_name: Some(args.generate_name.to_owned()),
+            labels: Some(args.labels().into_iter().collect()),
             namespace: Some(args.job_namespace.to_owned()),
             ..Default::default()
         }),
@@ -272,61 +314,123 @@ fn experiment(
     })
 }
 
+/// Experiment names are limited to [40 characters](https://github.com/kubeflow/katib/issues/2454#issuecomment-2508754891)
+/// to avoid [an issue with katib](https://github.com/kubeflow/katib/issues/2454).
+pub const EXPERIMENT_NAME_MAX_LEN: usize = 40;
+
 pub struct KatibExecutor {
     pub experiment_spec_path: std::path::PathBuf,
+    pub progress: Progress,
 }
 
 fn read_experiment_spec(path: &std::path::Path) -> Result<crate::katib::ExperimentSpec> {
-    Ok(serde_yaml::from_slice(
-        &std::fs::read(path).map_err(|err| format!("Failed to read Katib experiment spec file {}: {err}", path.display()))?,
+    Ok(serde_yaml::from_str(
+        &crate::text_config::read_text_config(path).map_err(|err| format!("Failed to read Katib experiment spec file {}: {err}", path.display()))?,
     )
     .map_err(|err| format!("Failed to parse Katib experiment spec file {}: {err}\nSee `launch submit --help` for format.", path.display()))?)
 }
 
 impl Executor for KatibExecutor {
-    fn execute(&self, mut args: ExecutionArgs) -> Result<ExecutionOutput> {
-        let kubectl = args.context.kubectl();
-
+    fn spec(&self, args: &ExecutionArgs) -> Result<serde_json::Value> {
         let experiment_spec = read_experiment_spec(&self.experiment_spec_path)?;
+        Ok(serde_json::to_value(experiment(experiment_spec, args)?)?)
+    }
 
-        let ResourceHandle { namespace, name } = kubectl.create(&serde_json::to_string(
-            &experiment(experiment_spec, &mut args)?,
-        )?)?;
+    fn execute(&self, args: ExecutionArgs) -> Result<ExecutionOutput> {
+        let kubectl = args.context.kubectl();
 
-        let experiment_url = experiment_url(args.context.katib_url(), &namespace, &name);
-        info!("Created experiment {experiment_url}",);
+        let experiment_spec = read_experiment_spec(&self.experiment_spec_path)?;
+        algorithms::check(&kubectl, &experiment_spec.algorithm.algorithm_name)?;
+        super::gpu_capacity::check(
+            &kubectl,
+            u32::try_from(experiment_spec.parallel_trial_count).unwrap_or(0),
+            args.gpus,
+            args.gpu_mem,
+        )?;
+
+        let handle = kubectl.create_with_run_id(&self.spec(&args)?.to_string(), args.run_id)?;
+
+        info!(
+            "Created experiment {}",
+            urls::katib_experiment(args.context, &handle.namespace, &handle.name)
+        );
+        let mut reporter = Reporter::new(self.progress);
+        reporter.emit(Event::ExperimentCreated {
+            namespace: handle.namespace.clone(),
+            experiment: handle.name.clone(),
+        })?;
+
+        if args.follow_logs {
+            monitor_experiment(args.context, &kubectl, &handle, &mut reporter)?;
+        }
 
-        let mut trial_to_state: HashMap<String, TrialState> = Default::default();
+        let ResourceHandle { namespace, name } = handle;
+        Ok(ExecutionOutput {
+            kind: ResourceKind::Experiment,
+            namespace,
+            name,
+        })
+    }
+}
 
-        loop {
-            let experiment = kubectl.katib_experiment(&namespace, &name)?;
+/// Logs the state changes of the trials of the Experiment until it reaches a terminal state, and reports them as
+/// [`Event`]s.
+pub fn monitor_experiment(
+    context: &ClusterContext,
+    cluster: &impl Cluster,
+    handle: &ResourceHandle,
+    reporter: &mut Reporter,
+) -> Result<()> {
+    let ResourceHandle { namespace, name } = handle;
+    let experiment_url = urls::katib_experiment(context, namespace, name);
+
+    let mut trial_to_state: HashMap<String, TrialState> = Default::default();
+    let mut optimal_trial = None;
+
+    loop {
+        if let Some(status) = cluster.experiment_status(namespace, name)? {
+            log_trial_state_changes(
+                context,
+                namespace,
+                name,
+                &mut trial_to_state,
+                &status,
+                reporter,
+            )?;
+
+            let current_optimal_trial =
+                events::optimal_trial(status.current_optimal_trial.as_ref());
+            if current_optimal_trial.is_some() && current_optimal_trial != optimal_trial {
+                optimal_trial = current_optimal_trial;
+                let (trial, metrics) = optimal_trial.clone().unwrap();
+                reporter.emit(Event::OptimalTrialUpdated {
+                    experiment: name.clone(),
+                    trial,
+                    metrics,
+                })?;
+            }
 
-            if let Some(status) = experiment.status.as_deref() {
-                log_trial_state_changes(
-                    args.context,
-                    &namespace,
-                    &name,
-                    &mut trial_to_state,
-                    status,
-                );
-
-                if let Some(status) = terminal_experiment_status(status) {
-                    match status {
-                        TerminalExperimentStatus::Succeeded => {
-                            info!("Succesfully completed experiment {experiment_url}")
-                        }
-                        TerminalExperimentStatus::Failed(message) => {
-                            error!("Failed to complete experiment {experiment_url}: {message}",)
-                        }
+            if let Some(status) = terminal_experiment_status(&status) {
+                let (status, message) = match status {
+                    TerminalExperimentStatus::Succeeded => {
+                        info!("Succesfully completed experiment {experiment_url}");
+                        (common::TerminalStatus::Succeeded, None)
+                    }
+                    TerminalExperimentStatus::Failed(message) => {
+                        error!("Failed to complete experiment {experiment_url}: {message}",);
+                        (common::TerminalStatus::Failed, Some(message.to_owned()))
                     }
-                    break;
-                }
+                };
+                reporter.emit(Event::ExperimentFinished {
+                    experiment: name.clone(),
+                    status,
+                    message,
+                })?;
+                return Ok(());
             }
-
-            std::thread::sleep(super::POLLING_INTERVAL);
         }
 
-        Ok(ExecutionOutput {})
+        cluster.sleep(super::POLLING_INTERVAL);
     }
 }
 
@@ -336,16 +440,23 @@ fn log_trial_state_changes(
     experiment_name: &str,
     trial_to_state: &mut HashMap<String, TrialState>,
     status: &km::V1beta1ExperimentStatus,
-) {
+    reporter: &mut Reporter,
+) -> Result<()> {
     for (trial_name, state) in trial_state_iter(status) {
         let prev_state = trial_to_state.insert(trial_name.to_owned(), state);
 
         if prev_state == Some(state) {
             continue;
         }
-
-        let trial_url = trial_url(context.katib_url(), namespace, experiment_name, trial_name);
-        let trial_job_url = trial_job_url(context.headlamp_url(), namespace, trial_name);
+        reporter.emit(Event::TrialStateChanged {
+            experiment: experiment_name.to_owned(),
+            trial: trial_name.to_owned(),
+            old: prev_state,
+            new: state,
+        })?;
+
+        let trial_url = urls::katib_trial(context, namespace, experiment_name, trial_name);
+        let trial_job_url = urls::headlamp_job(context, namespace, trial_name);
         match state {
             TrialState::Pending => info!("Awaiting pending trial {trial_url}"),
             TrialState::Running => info!("Running trial {trial_url}"),
@@ -356,10 +467,12 @@ fn log_trial_state_changes(
             TrialState::MetricsUnavailable => error!("Metrics unavailable for trial {trial_url}. View logs for details: {trial_job_url}."),
         }
     }
+    Ok(())
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
-enum TrialState {
+#[derive(Debug, Copy, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrialState {
     Pending,
     Running,
     Failed,
@@ -457,14 +570,427 @@ fn terminal_experiment_status(
     }
 }
 
-fn experiment_url(katib_url: &str, namespace: &str, experiment_name: &str) -> String {
-    format!("{katib_url}/katib/experiment/{namespace}/{experiment_name}",)
-}
+/// Polls the experiment until it reaches a terminal state.
+pub fn wait_for_experiment_completion(
+    kubectl: &crate::kubectl::Kubectl,
+    namespace: &str,
+    name: &str,
+) -> Result<common::Completion> {
+    info!("Waiting for Experiment {namespace}/{name} to complete...");
+
+    loop {
+        let experiment = kubectl
+            .try_get_katib_experiment(namespace, name)?
+            .ok_or_else(|| format!("Experiment {namespace}/{name} no longer exists"))?;
+
+        if let Some(status) = experiment
+            .status
+            .as_deref()
+            .and_then(terminal_experiment_status)
+        {
+            return Ok(match status {
+                TerminalExperimentStatus::Succeeded => common::Completion {
+                    status: common::TerminalStatus::Succeeded,
+                    message: None,
+                    duration: None,
+                },
+                TerminalExperimentStatus::Failed(message) => common::Completion {
+                    status: common::TerminalStatus::Failed,
+                    message: Some(message.to_owned()),
+                    duration: None,
+                },
+            });
+        }
 
-fn trial_url(katib_url: &str, namespace: &str, experiment_name: &str, trial_name: &str) -> String {
-    format!("{katib_url}/katib/experiment/{namespace}/{experiment_name}/trial/{trial_name}",)
+        std::thread::sleep(common::COMPLETION_POLLING_INTERVAL);
+    }
 }
 
-fn trial_job_url(headlamp_url: &str, namespace: &str, trial_name: &str) -> String {
-    format!("{headlamp_url}/c/main/jobs/{namespace}/{trial_name}")
+#[cfg(test)]
+mod tests {
+    use container_image_name::ImageName;
+
+    use super::*;
+    use crate::{
+        executor::{
+            monitor::tests::Fixture,
+            tests::{execution_args, RUN_ID},
+        },
+        kubectl,
+    };
+
+    fn experiment_spec(extra: &str) -> crate::katib::ExperimentSpec {
+        serde_yaml::from_str(&format!(
+            r#"
+objective:
+  type: maximize
+  objectiveMetricName: metric
+algorithm:
+  algorithmName: random
+parallelTrialCount: 1
+maxTrialCount: 1
+parameters:
+  - name: lr
+    parameterType: double
+    feasibleSpace:
+      min: 0.01
+      max: 1.0
+{extra}"#
+        ))
+        .unwrap()
+    }
+
+    fn image() -> ImageName {
+        ImageName::builder("launch")
+            .with_registry("registry:5000")
+            .with_tag("latest")
+            .build()
+            .unwrap()
+    }
+
+    fn args<'a>(image: &'a ImageName, databrickscfg_name: Option<&'a str>) -> ExecutionArgs<'a> {
+        ExecutionArgs {
+            generate_name: "katib-",
+            databrickscfg_name,
+            ..execution_args(image.as_ref())
+        }
+    }
+
+    /// The environment variables and the volume names of the trial container.
+    type EnvAndVolumes = (Vec<(String, String)>, Vec<String>);
+
+    fn trial_env_and_volumes(
+        spec: &crate::katib::ExperimentSpec,
+        databrickscfg_name: Option<&str>,
+    ) -> Result<EnvAndVolumes> {
+        let image = image();
+        let job = trial_spec(spec, &args(&image, databrickscfg_name))?;
+        let pod_spec = job.spec.unwrap().template.spec.unwrap();
+        let env = pod_spec.containers[0]
+            .env
+            .iter()
+            .flatten()
+            .map(|var| (var.name.clone(), var.value.clone().unwrap_or_default()))
+            .collect();
+        let volumes = pod_spec
+            .volumes
+            .iter()
+            .flatten()
+            .map(|volume| volume.name.clone())
+            .collect();
+        Ok((env, volumes))
+    }
+
+    fn get<'a>(env: &'a [(String, String)], name: &str) -> Option<&'a str> {
+        env.iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[test]
+    fn trial_spec_inherits_databrickscfg_by_default() {
+        let (env, volumes) =
+            trial_env_and_volumes(&experiment_spec(""), Some("databrickscfg-user")).unwrap();
+        assert_eq!(get(&env, "MLFLOW_TRACKING_URI"), Some("databricks"));
+        assert_eq!(volumes, ["databrickscfg"]);
+        assert!(get(&env, "KATIB_TRIAL_NAME").is_some());
+        assert_eq!(get(&env, super::super::RUN_ID_ENV), Some(RUN_ID));
+    }
+
+    #[test]
+    fn trial_spec_omits_katib_base_url_without_katib_ui() {
+        let image = image();
+        let base_url = |context| {
+            let args = ExecutionArgs {
+                context,
+                ..args(&image, None)
+            };
+            let job = trial_spec(&experiment_spec(""), &args).unwrap();
+            let pod_spec = job.spec.unwrap().template.spec.unwrap();
+            let env = pod_spec.containers[0].env.clone().unwrap_or_default();
+            assert!(env.iter().any(|var| var.name == "KATIB_TRIAL_NAME"));
+            env.into_iter()
+                .find(|var| var.name == "KATIB_BASE_URL")
+                .and_then(|var| var.value)
+        };
+        assert_eq!(
+            base_url(&ClusterContext::Berkeley).as_deref(),
+            Some("http://berkeley-katib.taila1eba.ts.net")
+        );
+        assert_eq!(base_url(&ClusterContext::Local), None);
+    }
+
+    #[test]
+    fn experiment_sets_run_id() {
+        let image = image();
+        let experiment = experiment(experiment_spec(""), &args(&image, None)).unwrap();
+
+        let metadata = experiment.metadata.unwrap();
+        assert_eq!(
+            metadata.annotations.unwrap()[kubectl::annotation::RUN_ID],
+            RUN_ID
+        );
+        assert_eq!(metadata.labels.unwrap()[kubectl::label::RUN_ID], RUN_ID);
+
+        let trial_spec = experiment
+            .spec
+            .unwrap()
+            .trial_template
+            .unwrap()
+            .trial_spec
+            .unwrap();
+        let template_metadata = &trial_spec["spec"]["template"]["metadata"];
+        assert_eq!(
+            template_metadata["annotations"][kubectl::annotation::RUN_ID],
+            RUN_ID
+        );
+        assert_eq!(template_metadata["labels"][kubectl::label::RUN_ID], RUN_ID);
+    }
+
+    #[test]
+    fn trial_spec_applies_trial_env_and_mount_opt_out() {
+        let spec = experiment_spec(
+            r#"
+trialEnv:
+  - name: MLFLOW_TRACKING_URI
+    value: http://mlflow
+  - name: SWEEP
+    value: "1"
+mountDatabricksCfg: false
+"#,
+        );
+        let (env, volumes) = trial_env_and_volumes(&spec, Some("databrickscfg-user")).unwrap();
+        assert_eq!(get(&env, "MLFLOW_TRACKING_URI"), Some("http://mlflow"));
+        assert_eq!(
+            env.iter()
+                .filter(|(key, _)| key == "MLFLOW_TRACKING_URI")
+                .count(),
+            1
+        );
+        assert_eq!(get(&env, "SWEEP"), Some("1"));
+        assert!(volumes.is_empty());
+    }
+
+    #[test]
+    fn trial_spec_validates_mount_and_reserved_env() {
+        let spec = experiment_spec("mountDatabricksCfg: true\n");
+        assert!(trial_env_and_volumes(&spec, None)
+            .unwrap_err()
+            .to_string()
+            .contains("mountDatabricksCfg"));
+        let (_, volumes) = trial_env_and_volumes(&spec, Some("databrickscfg-user")).unwrap();
+        assert_eq!(volumes, ["databrickscfg"]);
+
+        let spec = experiment_spec("trialEnv: [{name: KATIB_TRIAL_NAME, value: x}]\n");
+        assert!(trial_env_and_volumes(&spec, None)
+            .unwrap_err()
+            .to_string()
+            .contains("KATIB_TRIAL_NAME"));
+    }
+
+    /// The status of an Experiment with the trials in `trials` per state, and the last condition of type `condition`.
+    fn experiment_status(
+        condition: &str,
+        trials: serde_json::Value,
+    ) -> km::V1beta1ExperimentStatus {
+        let mut status = serde_json::json!({
+            "conditions": [{
+                "type": condition,
+                "status": "True",
+                "message": format!("Experiment is {condition}"),
+            }],
+        });
+        status
+            .as_object_mut()
+            .unwrap()
+            .extend(trials.as_object().unwrap().clone());
+        serde_json::from_value(status).unwrap()
+    }
+
+    fn handle() -> ResourceHandle {
+        ResourceHandle {
+            namespace: "launch".to_owned(),
+            name: "experiment-abc".to_owned(),
+        }
+    }
+
+    #[test]
+    fn monitor_experiment_polls_until_terminal() {
+        let cluster = Fixture {
+            experiment_statuses: Fixture::sequence([
+                // Katib has not reconciled the Experiment yet.
+                None,
+                Some(experiment_status(
+                    "Created",
+                    serde_json::json!({ "pendingTrialList": ["trial-a", "trial-b"] }),
+                )),
+                Some(experiment_status(
+                    "Running",
+                    serde_json::json!({ "runningTrialList": ["trial-a"], "pendingTrialList": ["trial-b"] }),
+                )),
+                Some(experiment_status(
+                    "Succeeded",
+                    serde_json::json!({ "succeededTrialList": ["trial-a"], "failedTrialList": ["trial-b"] }),
+                )),
+            ]),
+            ..Default::default()
+        };
+        monitor_experiment(
+            &ClusterContext::Berkeley,
+            &cluster,
+            &handle(),
+            &mut Reporter::new(Progress::Human),
+        )
+        .unwrap();
+        assert_eq!(cluster.sleeps.get(), 3);
+        assert_eq!(cluster.experiment_statuses.borrow().len(), 1);
+    }
+
+    #[test]
+    fn monitor_experiment_returns_for_failed_experiments() {
+        let cluster = Fixture {
+            experiment_statuses: Fixture::sequence([Some(experiment_status(
+                "Failed",
+                serde_json::json!({ "failedTrialList": ["trial-a"] }),
+            ))]),
+            ..Default::default()
+        };
+        monitor_experiment(
+            &ClusterContext::Berkeley,
+            &cluster,
+            &handle(),
+            &mut Reporter::new(Progress::Human),
+        )
+        .unwrap();
+        assert_eq!(cluster.sleeps.get(), 0);
+    }
+
+    #[test]
+    fn trial_state_changes_are_logged_once() {
+        let status = experiment_status(
+            "Running",
+            serde_json::json!({ "runningTrialList": ["trial-a"], "succeededTrialList": ["trial-b"] }),
+        );
+        let mut trial_to_state = HashMap::new();
+        log_trial_state_changes(
+            &ClusterContext::Berkeley,
+            "launch",
+            "experiment-abc",
+            &mut trial_to_state,
+            &status,
+            &mut Reporter::new(Progress::Human),
+        )
+        .unwrap();
+        assert_eq!(
+            trial_to_state,
+            HashMap::from([
+                ("trial-a".to_owned(), TrialState::Running),
+                ("trial-b".to_owned(), TrialState::Succeeded),
+            ])
+        );
+    }
+
+    #[test]
+    fn monitor_experiment_reports_events() {
+        let optimal_trial = |trial: &str, loss: &str| {
+            serde_json::json!({
+                "bestTrialName": trial,
+                "observation": { "metrics": [{ "name": "loss", "latest": loss, "min": loss, "max": "0.9" }] },
+            })
+        };
+        let cluster = Fixture {
+            experiment_statuses: Fixture::sequence([
+                None,
+                Some(experiment_status(
+                    "Running",
+                    serde_json::json!({
+                        "runningTrialList": ["trial-a"],
+                        "pendingTrialList": ["trial-b"],
+                        "currentOptimalTrial": { "bestTrialName": "", "observation": { "metrics": null } },
+                    }),
+                )),
+                Some(experiment_status(
+                    "Running",
+                    serde_json::json!({
+                        "succeededTrialList": ["trial-a"],
+                        "runningTrialList": ["trial-b"],
+                        "currentOptimalTrial": optimal_trial("trial-a", "0.5"),
+                    }),
+                )),
+                // Nothing changed.
+                Some(experiment_status(
+                    "Running",
+                    serde_json::json!({
+                        "succeededTrialList": ["trial-a"],
+                        "runningTrialList": ["trial-b"],
+                        "currentOptimalTrial": optimal_trial("trial-a", "0.5"),
+                    }),
+                )),
+                Some(experiment_status(
+                    "Succeeded",
+                    serde_json::json!({
+                        "succeededTrialList": ["trial-a", "trial-b"],
+                        "currentOptimalTrial": optimal_trial("trial-b", "0.25"),
+                    }),
+                )),
+            ]),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        monitor_experiment(
+            &ClusterContext::Berkeley,
+            &cluster,
+            &handle(),
+            &mut Reporter::json(&mut out),
+        )
+        .unwrap();
+
+        let events: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let trial_state_changed = |trial: &str, old: Option<&str>, new: &str| {
+            serde_json::json!({
+                "schema_version": events::SCHEMA_VERSION,
+                "event": "trial_state_changed",
+                "experiment": "experiment-abc",
+                "trial": trial,
+                "old": old,
+                "new": new,
+            })
+        };
+        let optimal_trial_updated = |trial: &str, loss: &str| {
+            serde_json::json!({
+                "schema_version": events::SCHEMA_VERSION,
+                "event": "optimal_trial_updated",
+                "experiment": "experiment-abc",
+                "trial": trial,
+                "metrics": [{ "name": "loss", "latest": loss, "min": loss, "max": "0.9" }],
+            })
+        };
+        assert_eq!(
+            events,
+            [
+                trial_state_changed("trial-b", None, "pending"),
+                trial_state_changed("trial-a", None, "running"),
+                trial_state_changed("trial-a", Some("running"), "succeeded"),
+                trial_state_changed("trial-b", Some("pending"), "running"),
+                optimal_trial_updated("trial-a", "0.5"),
+                trial_state_changed("trial-b", Some("running"), "succeeded"),
+                optimal_trial_updated("trial-b", "0.25"),
+                serde_json::json!({
+                    "schema_version": events::SCHEMA_VERSION,
+                    "event": "experiment_finished",
+                    "experiment": "experiment-abc",
+                    "status": "succeeded",
+                    "message": null,
+                }),
+            ]
+        );
+        // Every event parses back into the schema.
+        for event in events {
+            serde_json::from_value::<events::Record>(event).unwrap();
+        }
+    }
 }
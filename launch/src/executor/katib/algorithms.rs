@@ -0,0 +1,187 @@
+//! Checks that katib has a suggestion service for the algorithm of an experiment. Katib accepts an experiment with any
+//! `algorithmName`, but without a suggestion service for it the experiment stays `Created` without ever starting a
+//! trial.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use log::{debug, warn};
+
+use super::Result;
+use crate::kubectl::Kubectl;
+
+/// The namespace that katib is deployed to.
+const KATIB_NAMESPACE: &str = "kubeflow";
+
+/// The ConfigMap of the katib controller, which lists the image of the suggestion service of each algorithm.
+const KATIB_CONFIG: &str = "katib-config";
+
+/// The key of the configuration since katib v0.16.
+const KATIB_CONFIG_KEY: &str = "katib-config.yaml";
+
+/// The key of the suggestion services before katib v0.16.
+const LEGACY_SUGGESTION_KEY: &str = "suggestion";
+
+#[derive(Debug, serde::Deserialize)]
+struct KatibConfig {
+    #[serde(default)]
+    runtime: Runtime,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+struct Runtime {
+    #[serde(default)]
+    suggestions: Vec<Suggestion>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Suggestion {
+    algorithm_name: String,
+}
+
+/// Returns the algorithms with a suggestion service from the data of the `katib-config` ConfigMap.
+fn suggestion_algorithms(data: &BTreeMap<String, String>) -> Result<BTreeSet<String>> {
+    if let Some(config) = data.get(KATIB_CONFIG_KEY) {
+        let config: KatibConfig = serde_yaml::from_str(config)
+            .map_err(|error| format!("Failed to parse {KATIB_CONFIG_KEY}: {error}"))?;
+        return Ok(config
+            .runtime
+            .suggestions
+            .into_iter()
+            .map(|suggestion| suggestion.algorithm_name)
+            .collect());
+    }
+    if let Some(suggestions) = data.get(LEGACY_SUGGESTION_KEY) {
+        // A JSON object with the settings of each algorithm by name.
+        let suggestions: BTreeMap<String, serde_json::Value> = serde_json::from_str(suggestions)
+            .map_err(|error| format!("Failed to parse {LEGACY_SUGGESTION_KEY}: {error}"))?;
+        return Ok(suggestions.into_keys().collect());
+    }
+    Err(format!("Neither {KATIB_CONFIG_KEY} nor {LEGACY_SUGGESTION_KEY} is set").into())
+}
+
+/// Fails when none of the deployed suggestion services implements the algorithm.
+fn check_algorithm(algorithm_name: &str, deployed: &BTreeSet<String>) -> Result<(), String> {
+    if deployed.contains(algorithm_name) {
+        return Ok(());
+    }
+    let supported = if deployed.is_empty() {
+        "none".to_owned()
+    } else {
+        deployed.iter().cloned().collect::<Vec<_>>().join(", ")
+    };
+    Err(format!(
+        "Katib has no suggestion service for algorithmName {algorithm_name:?} on this cluster, so the experiment would never start a trial. Supported algorithms: {supported}"
+    ))
+}
+
+/// Checks the algorithm against the `katib-config` ConfigMap of the cluster. The check is skipped with a warning when
+/// the ConfigMap can not be read, for example for lack of permission.
+pub fn check(kubectl: &Kubectl, algorithm_name: &str) -> Result<()> {
+    let deployed = match kubectl.try_get_config_map(KATIB_NAMESPACE, KATIB_CONFIG) {
+        Ok(Some(config_map)) => suggestion_algorithms(&config_map.data),
+        Ok(None) => {
+            Err(format!("ConfigMap {KATIB_NAMESPACE}/{KATIB_CONFIG} does not exist").into())
+        }
+        Err(error) => Err(error),
+    };
+    match deployed {
+        Ok(deployed) => {
+            debug!("Katib suggestion algorithms: {deployed:?}");
+            Ok(check_algorithm(algorithm_name, &deployed)?)
+        }
+        Err(error) => {
+            warn!("Unable to verify that katib supports algorithmName {algorithm_name:?}, reading ConfigMap {KATIB_NAMESPACE}/{KATIB_CONFIG} failed: {error}");
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(key: &str, value: &str) -> BTreeMap<String, String> {
+        BTreeMap::from([(key.to_owned(), value.to_owned())])
+    }
+
+    fn algorithms(data: &BTreeMap<String, String>) -> Vec<String> {
+        suggestion_algorithms(data).unwrap().into_iter().collect()
+    }
+
+    #[test]
+    fn suggestion_algorithms_parses_katib_config() {
+        // As deployed by katib v0.17.
+        let config = r#"
+apiVersion: config.kubeflow.org/v1beta1
+kind: KatibConfig
+init:
+  certGenerator:
+    enable: true
+runtime:
+  metricsCollectors:
+    - kind: StdOut
+      image: docker.io/kubeflowkatib/file-metrics-collector:v0.17.0
+  suggestions:
+    - algorithmName: random
+      image: docker.io/kubeflowkatib/suggestion-hyperopt:v0.17.0
+    - algorithmName: tpe
+      image: docker.io/kubeflowkatib/suggestion-hyperopt:v0.17.0
+    - algorithmName: grid
+      image: docker.io/kubeflowkatib/suggestion-optuna:v0.17.0
+  earlyStoppings:
+    - algorithmName: medianstop
+      image: docker.io/kubeflowkatib/earlystopping-medianstop:v0.17.0
+"#;
+        assert_eq!(
+            algorithms(&data(KATIB_CONFIG_KEY, config)),
+            ["grid", "random", "tpe"]
+        );
+        assert_eq!(
+            algorithms(&data(KATIB_CONFIG_KEY, "runtime: {}\n")),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn suggestion_algorithms_parses_legacy_config() {
+        let suggestion = r#"{
+  "random": {"image": "docker.io/kubeflowkatib/suggestion-hyperopt:v0.15.0"},
+  "bayesianoptimization": {"image": "docker.io/kubeflowkatib/suggestion-skopt:v0.15.0"}
+}"#;
+        let mut data = data(LEGACY_SUGGESTION_KEY, suggestion);
+        data.insert(
+            "early-stopping".to_owned(),
+            r#"{"medianstop": {"image": "docker.io/kubeflowkatib/earlystopping-medianstop:v0.15.0"}}"#
+                .to_owned(),
+        );
+        assert_eq!(algorithms(&data), ["bayesianoptimization", "random"]);
+    }
+
+    #[test]
+    fn suggestion_algorithms_rejects_unknown_configs() {
+        assert_eq!(
+            suggestion_algorithms(&BTreeMap::new())
+                .unwrap_err()
+                .to_string(),
+            "Neither katib-config.yaml nor suggestion is set"
+        );
+        assert!(suggestion_algorithms(&data(KATIB_CONFIG_KEY, "runtime: [")).is_err());
+        assert!(suggestion_algorithms(&data(LEGACY_SUGGESTION_KEY, "[\"random\"]")).is_err());
+    }
+
+    #[test]
+    fn check_algorithm_lists_the_supported_algorithms() {
+        let deployed = BTreeSet::from(["random".to_owned(), "tpe".to_owned()]);
+        assert_eq!(check_algorithm("random", &deployed), Ok(()));
+        assert_eq!(
+            check_algorithm("bayesianoptimization", &deployed),
+            Err("Katib has no suggestion service for algorithmName \"bayesianoptimization\" on this cluster, so the experiment would never start a trial. Supported algorithms: random, tpe".to_owned())
+        );
+        // The names are case-sensitive, like those of katib.
+        assert!(check_algorithm("Random", &deployed).is_err());
+        assert!(check_algorithm("random", &BTreeSet::new())
+            .unwrap_err()
+            .ends_with("Supported algorithms: none"));
+    }
+}
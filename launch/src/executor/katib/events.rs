@@ -0,0 +1,224 @@
+//! The machine-readable events of the monitoring of an Experiment and of the kaniko build of the image. With
+//! `--progress json`, each event is printed to stdout as a JSON object on its own line, next to the unchanged log
+//! messages on stderr. Scripts rely on the fields
+//! rather than on the wording of the logs, so changes to them go along with a bump of [`SCHEMA_VERSION`].
+
+use std::io::Write;
+
+use clap::ValueEnum;
+
+use super::{Result, TrialState};
+use crate::executor::common::TerminalStatus;
+
+/// The version of the event schema, recorded in every event. Bumped when a field is renamed or removed, or its meaning
+/// changes. Adding events or fields does not bump it, so consumers should ignore what they do not know.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Default, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum Progress {
+    /// Only log the progress for humans.
+    #[default]
+    Human,
+    /// Also print the progress of kaniko builds and katib experiments to stdout as one JSON object per line.
+    Json,
+}
+
+/// A metric of the optimal trial, with the values as katib reports them.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Metric {
+    pub name: String,
+    #[serde(default)]
+    pub latest: Option<String>,
+    #[serde(default)]
+    pub min: Option<String>,
+    #[serde(default)]
+    pub max: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    ExperimentCreated {
+        namespace: String,
+        experiment: String,
+    },
+    /// `old` is `None` for a trial that was not seen before.
+    TrialStateChanged {
+        experiment: String,
+        trial: String,
+        old: Option<TrialState>,
+        new: TrialState,
+    },
+    OptimalTrialUpdated {
+        experiment: String,
+        trial: String,
+        metrics: Vec<Metric>,
+    },
+    ExperimentFinished {
+        experiment: String,
+        status: TerminalStatus,
+        message: Option<String>,
+    },
+    /// Kaniko finished executing the Dockerfile instruction of a step, or failed to when `succeeded` is false.
+    BuildStepFinished {
+        step: usize,
+        instruction: String,
+        seconds: u64,
+        cached: bool,
+        succeeded: bool,
+    },
+}
+
+/// An event as printed, along with the version of its schema.
+#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Record {
+    pub schema_version: u32,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// Prints the events for `--progress json`, and drops them otherwise.
+pub struct Reporter<'a> {
+    out: Option<Box<dyn Write + 'a>>,
+}
+
+impl Reporter<'_> {
+    pub fn new(progress: Progress) -> Reporter<'static> {
+        match progress {
+            Progress::Human => Reporter { out: None },
+            Progress::Json => Reporter::json(std::io::stdout()),
+        }
+    }
+
+    /// Prints the events to `out`.
+    pub fn json<'a>(out: impl Write + 'a) -> Reporter<'a> {
+        Reporter {
+            out: Some(Box::new(out)),
+        }
+    }
+
+    pub fn emit(&mut self, event: Event) -> Result<()> {
+        let Some(out) = &mut self.out else {
+            return Ok(());
+        };
+        let record = Record {
+            schema_version: SCHEMA_VERSION,
+            event,
+        };
+        serde_json::to_writer(&mut *out, &record)?;
+        // Flush every line, so that a script sees the event while the experiment is still running.
+        writeln!(out)?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// The `currentOptimalTrial` of the status of an Experiment.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OptimalTrial {
+    #[serde(default)]
+    best_trial_name: String,
+    #[serde(default)]
+    observation: Option<Observation>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+struct Observation {
+    #[serde(default)]
+    metrics: Option<Vec<Metric>>,
+}
+
+/// Returns the name and metrics of the optimal trial, or `None` before katib determined one.
+pub fn optimal_trial(
+    current_optimal_trial: Option<&serde_json::Value>,
+) -> Option<(String, Vec<Metric>)> {
+    let optimal_trial: OptimalTrial =
+        serde_json::from_value(current_optimal_trial?.clone()).ok()?;
+    if optimal_trial.best_trial_name.is_empty() {
+        return None;
+    }
+    let metrics = optimal_trial
+        .observation
+        .and_then(|observation| observation.metrics)
+        .unwrap_or_default();
+    Some((optimal_trial.best_trial_name, metrics))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emit_writes_json_lines() {
+        let mut out = Vec::new();
+        let mut reporter = Reporter::json(&mut out);
+        reporter
+            .emit(Event::TrialStateChanged {
+                experiment: "experiment-abc".to_owned(),
+                trial: "trial-a".to_owned(),
+                old: None,
+                new: TrialState::MetricsUnavailable,
+            })
+            .unwrap();
+        reporter
+            .emit(Event::ExperimentFinished {
+                experiment: "experiment-abc".to_owned(),
+                status: TerminalStatus::Failed,
+                message: Some("Experiment has failed".to_owned()),
+            })
+            .unwrap();
+        reporter
+            .emit(Event::BuildStepFinished {
+                step: 2,
+                instruction: "RUN pip install .".to_owned(),
+                seconds: 21,
+                cached: true,
+                succeeded: true,
+            })
+            .unwrap();
+        drop(reporter);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                r#"{"schema_version":1,"event":"trial_state_changed","experiment":"experiment-abc","trial":"trial-a","old":null,"new":"metrics_unavailable"}"#,
+                "\n",
+                r#"{"schema_version":1,"event":"experiment_finished","experiment":"experiment-abc","status":"failed","message":"Experiment has failed"}"#,
+                "\n",
+                r#"{"schema_version":1,"event":"build_step_finished","step":2,"instruction":"RUN pip install .","seconds":21,"cached":true,"succeeded":true}"#,
+                "\n",
+            )
+        );
+    }
+
+    #[test]
+    fn optimal_trial_works() {
+        assert_eq!(optimal_trial(None), None);
+        // Katib reports an empty optimal trial until the first trial has metrics.
+        assert_eq!(
+            optimal_trial(Some(&serde_json::json!({
+                "bestTrialName": "",
+                "observation": { "metrics": null },
+            }))),
+            None
+        );
+        assert_eq!(
+            optimal_trial(Some(&serde_json::json!({
+                "bestTrialName": "trial-a",
+                "observation": {
+                    "metrics": [{ "name": "loss", "latest": "0.25", "min": "0.25", "max": "0.9" }],
+                },
+                "parameterAssignments": [{ "name": "lr", "value": "0.01" }],
+            }))),
+            Some((
+                "trial-a".to_owned(),
+                vec![Metric {
+                    name: "loss".to_owned(),
+                    latest: Some("0.25".to_owned()),
+                    min: Some("0.25".to_owned()),
+                    max: Some("0.9".to_owned()),
+                }]
+            ))
+        );
+    }
+}
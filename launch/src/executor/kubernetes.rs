@@ -2,52 +2,166 @@
 
 use log::info;
 
-use super::{ExecutionArgs, ExecutionOutput, Executor, Result};
-use crate::{
-    executor::common::{self, job_spec},
-    kubectl::ResourceHandle,
-};
+use super::{monitor::Cluster, ExecutionArgs, ExecutionOutput, Executor, ResourceKind, Result};
+use crate::{cli::ClusterContext, executor::common::job_spec, kubectl::ResourceHandle, urls};
 
 pub struct KubernetesExecutor;
 
 impl Executor for KubernetesExecutor {
+    fn spec(&self, args: &ExecutionArgs) -> Result<serde_json::Value> {
+        Ok(serde_json::to_value(job_spec(
+            args,
+            None,
+            Some(args.container_args.to_vec()),
+        ))?)
+    }
+
     fn execute(&self, args: ExecutionArgs) -> Result<ExecutionOutput> {
         let kubectl = args.context.kubectl();
-        let headlamp_url = args.context.headlamp_url();
-
-        let (job_namespace, job_name) = {
-            let job_spec = job_spec(&args, None, Some(args.container_args.to_vec()));
-            let ResourceHandle { namespace, name } =
-                kubectl.create(&serde_json::to_string(&job_spec)?)?;
-            assert_eq!(args.job_namespace, namespace);
-            (namespace, name)
-        };
+
+        let handle = kubectl.create_with_run_id(&self.spec(&args)?.to_string(), args.run_id)?;
+        assert_eq!(args.job_namespace, handle.namespace);
 
         info!(
             "Created Job {:?}",
-            format!("{headlamp_url}/c/main/jobs/{job_namespace}/{job_name}")
+            urls::headlamp_job(args.context, &handle.namespace, &handle.name)
         );
 
-        let pod_name = {
-            let mut pod_names = kubectl.get_pods_for_job(&job_namespace, &job_name)?;
-            for pod_name in &pod_names {
-                info!(
-                    "Created Pod {:?}",
-                    format!("{headlamp_url}/c/main/pods/{job_namespace}/{pod_name}")
-                );
-            }
-            let pod_name = pod_names.pop().ok_or("No pods created for job")?;
-            if !pod_names.is_empty() {
-                return Err(format!(
-                    "Expected only a single Pod for Job {job_name:?} but there are multiple. Not sure for which one to follow the logs."
-                )
-                .into());
-            }
-            pod_name
+        if args.follow_logs {
+            monitor_job(args.context, &kubectl, &handle, true)?;
+        }
+
+        let ResourceHandle { namespace, name } = handle;
+        Ok(ExecutionOutput {
+            kind: ResourceKind::Job,
+            namespace,
+            name,
+        })
+    }
+}
+
+/// Follows the logs of the Pod of the Job until it exits. With `record_latency`, the latencies of the Pod are recorded on
+/// the Job, which is only meaningful while following a Job that was just created.
+pub fn monitor_job(
+    context: &ClusterContext,
+    cluster: &impl Cluster,
+    handle: &ResourceHandle,
+    record_latency: bool,
+) -> Result<()> {
+    let ResourceHandle {
+        namespace: job_namespace,
+        name: job_name,
+    } = handle;
+
+    let pod_name = {
+        let mut pod_names = cluster.job_pod_names(job_namespace, job_name)?;
+        for pod_name in &pod_names {
+            info!(
+                "Created Pod {:?}",
+                urls::headlamp_pod(context, job_namespace, pod_name)
+            );
+        }
+        let pod_name = pod_names.pop().ok_or("No pods created for job")?;
+        if !pod_names.is_empty() {
+            return Err(format!(
+                "Expected only a single Pod for Job {job_name:?} but there are multiple. Not sure for which one to follow the logs."
+            )
+            .into());
+        }
+        pod_name
+    };
+
+    let logs_available = cluster.follow_pod_logs(job_namespace, &pod_name)?;
+    if let (true, Some(logs_available)) = (record_latency, logs_available) {
+        cluster.record_latency(job_namespace, job_name, &pod_name, logs_available);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::monitor::tests::Fixture;
+
+    fn handle() -> ResourceHandle {
+        ResourceHandle {
+            namespace: "launch".to_owned(),
+            name: "job-abc".to_owned(),
+        }
+    }
+
+    #[test]
+    fn monitor_job_follows_the_only_pod() {
+        let cluster = Fixture {
+            pod_names: vec!["job-abc-x7k2p".to_owned()],
+            ..Default::default()
+        };
+        monitor_job(&ClusterContext::Berkeley, &cluster, &handle(), true).unwrap();
+        assert_eq!(*cluster.followed.borrow(), ["job-abc-x7k2p"]);
+        assert_eq!(*cluster.latencies.borrow(), ["job-abc-x7k2p"]);
+    }
+
+    #[test]
+    fn monitor_job_records_no_latency_when_attaching() {
+        let cluster = Fixture {
+            pod_names: vec!["job-abc-x7k2p".to_owned()],
+            ..Default::default()
         };
+        monitor_job(&ClusterContext::Berkeley, &cluster, &handle(), false).unwrap();
+        assert_eq!(*cluster.followed.borrow(), ["job-abc-x7k2p"]);
+        assert!(cluster.latencies.borrow().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn execute_without_following_logs_only_creates_the_job() {
+        use container_image_name::ImageName;
+
+        use crate::{executor::tests::execution_args, kubectl::fake::FakeKubectl};
 
-        common::wait_for_and_follow_pod_logs(&kubectl, &job_namespace, &pod_name)?;
+        let kubectl = FakeKubectl::install(
+            r#"case "$*" in
+    *"create --output=json -f -")
+        cat > /dev/null
+        echo '{"metadata": {"namespace": "launch", "name": "job-x7k2p"}}' ;;
+    *) exit 1 ;;
+esac"#,
+        );
+        let image: ImageName = "docker.io/library/python:3.12".parse().unwrap();
+        let output = KubernetesExecutor
+            .execute(ExecutionArgs {
+                context: &ClusterContext::Local,
+                follow_logs: false,
+                ..execution_args(image.as_ref())
+            })
+            .unwrap();
+        assert_eq!(output.name, "job-x7k2p");
+        assert_eq!(kubectl.invocations().len(), 1);
+    }
 
-        Ok(ExecutionOutput {})
+    #[test]
+    fn monitor_job_requires_a_single_pod() {
+        let cluster = Fixture::default();
+        let error = monitor_job(&ClusterContext::Berkeley, &cluster, &handle(), true).unwrap_err();
+        assert_eq!(error.to_string(), "No pods created for job");
+
+        let cluster = Fixture {
+            pod_names: vec!["job-abc-x7k2p".to_owned(), "job-abc-q9w4z".to_owned()],
+            ..Default::default()
+        };
+        let error = monitor_job(&ClusterContext::Berkeley, &cluster, &handle(), true).unwrap_err();
+        assert!(error.to_string().contains("there are multiple"), "{error}");
+        assert!(cluster.followed.borrow().is_empty());
+    }
+
+    #[test]
+    fn monitor_job_reports_log_failures() {
+        let cluster = Fixture {
+            pod_names: vec!["job-abc-x7k2p".to_owned()],
+            logs_fail: true,
+            ..Default::default()
+        };
+        assert!(monitor_job(&ClusterContext::Berkeley, &cluster, &handle(), true).is_err());
+        assert!(cluster.latencies.borrow().is_empty());
     }
 }
@@ -0,0 +1,313 @@
+//! How long a submission waits before its container runs. The latencies are recorded as annotations on the Job so that
+//! `launch stats` can aggregate them across submissions.
+
+use std::{collections::HashMap, fmt};
+
+use log::{debug, info};
+use time::{Duration, OffsetDateTime};
+
+use crate::kubectl::{self, annotation, ContainerState, Pod};
+
+/// The node label that holds the product name of the GPUs of a node.
+pub const GPU_PRODUCT_LABEL: &str = "nvidia.com/gpu.product";
+
+/// When a Pod went through the stages that precede its logs becoming available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamps {
+    /// When the Pod was created, which immediately follows the creation of its Job.
+    pub created: OffsetDateTime,
+    /// When the Pod was bound to a node.
+    pub scheduled: Option<OffsetDateTime>,
+    /// When the container started, which is after its image has been pulled.
+    pub container_started: Option<OffsetDateTime>,
+    /// When launch observed that the logs became available.
+    pub logs_available: Option<OffsetDateTime>,
+}
+
+impl Timestamps {
+    pub fn from_pod(
+        pod: &Pod,
+        container_name: &str,
+        logs_available: Option<OffsetDateTime>,
+    ) -> Self {
+        let scheduled = pod
+            .status
+            .conditions
+            .iter()
+            .find(|condition| condition.r#type == "PodScheduled" && condition.status == "True")
+            .and_then(|condition| condition.last_transition_time);
+        let container_started = pod
+            .status
+            .container_statuses
+            .iter()
+            .find(|status| status.name == container_name)
+            .and_then(|status| match &status.state {
+                ContainerState::Running(state) => Some(state.started_at),
+                ContainerState::Terminated(state) => Some(state.started_at),
+                ContainerState::Waiting(_) => None,
+            });
+        Self {
+            created: pod.metadata.creation_timestamp,
+            scheduled,
+            container_started,
+            logs_available,
+        }
+    }
+}
+
+/// The time spent in each stage. A latency is missing when either end of its stage is unknown.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Latencies {
+    /// From the creation of the Pod until it was scheduled.
+    pub scheduling: Option<Duration>,
+    /// From scheduling until the container started, which is dominated by pulling the image.
+    pub pull: Option<Duration>,
+    /// From the start of the container until launch observed its logs.
+    pub logs: Option<Duration>,
+}
+
+impl From<Timestamps> for Latencies {
+    fn from(value: Timestamps) -> Self {
+        fn between(start: Option<OffsetDateTime>, end: Option<OffsetDateTime>) -> Option<Duration> {
+            // The timestamps of the API server have a resolution of a second and are not necessarily ordered with the
+            // local clock, so small negative differences are clamped.
+            start
+                .zip(end)
+                .map(|(start, end)| (end - start).max(Duration::ZERO))
+        }
+        Self {
+            scheduling: between(Some(value.created), value.scheduled),
+            pull: between(value.scheduled, value.container_started),
+            logs: between(value.container_started, value.logs_available),
+        }
+    }
+}
+
+impl Latencies {
+    const ANNOTATIONS: [&'static str; 3] = [
+        annotation::SCHEDULING_LATENCY_SECONDS,
+        annotation::PULL_LATENCY_SECONDS,
+        annotation::LOG_LATENCY_SECONDS,
+    ];
+
+    fn values(&self) -> [Option<Duration>; 3] {
+        [self.scheduling, self.pull, self.logs]
+    }
+
+    /// Returns the annotations that record the known latencies in whole seconds.
+    pub fn annotations(&self) -> Vec<(&'static str, String)> {
+        Self::ANNOTATIONS
+            .into_iter()
+            .zip(self.values())
+            .filter_map(|(key, value)| Some((key, value?.whole_seconds().to_string())))
+            .collect()
+    }
+
+    /// Returns `None` when none of the latencies have been recorded.
+    pub fn from_annotations(annotations: &HashMap<String, String>) -> Option<Self> {
+        let [scheduling, pull, logs] = Self::ANNOTATIONS.map(|key| {
+            annotations
+                .get(key)
+                .and_then(|value| value.parse::<i64>().ok())
+                .map(Duration::seconds)
+        });
+        let latencies = Self {
+            scheduling,
+            pull,
+            logs,
+        };
+        latencies
+            .values()
+            .iter()
+            .any(Option::is_some)
+            .then_some(latencies)
+    }
+}
+
+impl fmt::Display for Latencies {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts = ["scheduling", "pulling and starting", "logs"]
+            .into_iter()
+            .zip(self.values())
+            .filter_map(|(name, value)| Some(format!("{name} {}s", value?.whole_seconds())))
+            .collect::<Vec<_>>();
+        if parts.is_empty() {
+            f.write_str("unknown")
+        } else {
+            f.write_str(&parts.join(", "))
+        }
+    }
+}
+
+/// Whether the container requests GPUs, in which case the GPU product of its node is recorded.
+fn requests_gpus(pod: &Pod, container_name: &str) -> bool {
+    pod.spec.container(container_name).is_some_and(|container| {
+        let resources = &container.resources;
+        resources
+            .limits
+            .get("nvidia.com/gpu")
+            .or(resources.requests.get("nvidia.com/gpu"))
+            .is_some_and(|value| value != "0")
+    })
+}
+
+/// Logs the latencies of the Pod of a Job and records them as annotations on the Job, together with the GPU product and
+/// the digest of the image that the container runs. The latencies are informative, so failures are logged instead of
+/// returned.
+pub fn record(
+    kubectl: &kubectl::Kubectl,
+    namespace: &str,
+    job_name: &str,
+    pod_name: &str,
+    container_name: &str,
+    logs_available: OffsetDateTime,
+) {
+    let pod = match kubectl.pod(namespace, pod_name) {
+        Ok(pod) => pod,
+        Err(error) => {
+            debug!("Unable to obtain Pod {namespace}/{pod_name} to record its latencies: {error}");
+            return;
+        }
+    };
+    let latencies = Latencies::from(Timestamps::from_pod(
+        &pod,
+        container_name,
+        Some(logs_available),
+    ));
+    info!("Latencies of Pod {namespace}/{pod_name}: {latencies}");
+
+    let gpu_product = pod
+        .spec
+        .node_name
+        .as_deref()
+        .filter(|_| requests_gpus(&pod, container_name))
+        .and_then(|node_name| {
+            kubectl
+                .node(node_name)
+                .inspect_err(|error| debug!("Unable to obtain Node {node_name}: {error}"))
+                .ok()
+        })
+        .and_then(|mut node| node.metadata.labels.remove(GPU_PRODUCT_LABEL));
+
+    let mut annotations = latencies.annotations();
+    annotations.extend(gpu_product.map(|value| (annotation::GPU_PRODUCT, value)));
+    annotations.extend(
+        super::image_digest::observed_digest(&pod, container_name)
+            .map(|digest| (annotation::OBSERVED_IMAGE_DIGEST, digest.to_owned())),
+    );
+    if let Err(error) = kubectl.annotate("job", namespace, job_name, &annotations) {
+        debug!("Unable to record the latencies on Job {namespace}/{job_name}: {error}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn pod(json: serde_json::Value) -> Pod {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn latencies_from_pod_works() {
+        let pod = pod(serde_json::json!({
+            "metadata": {
+                "name": "job-abc-xyz",
+                "namespace": "launch",
+                "creationTimestamp": "2024-06-01T12:00:00Z",
+            },
+            "spec": {
+                "nodeName": "node-1",
+                "containers": [{
+                    "name": "main",
+                    "resources": { "limits": { "nvidia.com/gpu": "1" } },
+                }],
+            },
+            "status": {
+                "phase": "Running",
+                "conditions": [
+                    { "type": "Initialized", "status": "True", "lastTransitionTime": "2024-06-01T12:00:01Z" },
+                    { "type": "PodScheduled", "status": "True", "lastTransitionTime": "2024-06-01T12:00:05Z" },
+                ],
+                "containerStatuses": [{
+                    "name": "main",
+                    "image": "image",
+                    "imageID": "",
+                    "state": { "running": { "startedAt": "2024-06-01T12:01:35Z" } },
+                }],
+            },
+        }));
+
+        let timestamps =
+            Timestamps::from_pod(&pod, "main", Some(datetime!(2024-06-01 12:01:37 UTC)));
+        assert_eq!(
+            timestamps.scheduled,
+            Some(datetime!(2024-06-01 12:00:05 UTC))
+        );
+        assert_eq!(
+            timestamps.container_started,
+            Some(datetime!(2024-06-01 12:01:35 UTC))
+        );
+        let latencies = Latencies::from(timestamps);
+        assert_eq!(
+            latencies,
+            Latencies {
+                scheduling: Some(Duration::seconds(5)),
+                pull: Some(Duration::seconds(90)),
+                logs: Some(Duration::seconds(2)),
+            }
+        );
+        assert_eq!(
+            latencies.to_string(),
+            "scheduling 5s, pulling and starting 90s, logs 2s"
+        );
+        assert!(requests_gpus(&pod, "main"));
+
+        // Unknown stages have no latency, and the local clock may lag behind the API server.
+        let latencies = Latencies::from(Timestamps {
+            created: datetime!(2024-06-01 12:00:00 UTC),
+            scheduled: Some(datetime!(2024-06-01 12:00:05 UTC)),
+            container_started: None,
+            logs_available: Some(datetime!(2024-06-01 12:00:04 UTC)),
+        });
+        assert_eq!(
+            latencies,
+            Latencies {
+                scheduling: Some(Duration::seconds(5)),
+                pull: None,
+                logs: None,
+            }
+        );
+    }
+
+    #[test]
+    fn latencies_annotations_round_trip() {
+        let latencies = Latencies {
+            scheduling: Some(Duration::seconds(5)),
+            pull: None,
+            logs: Some(Duration::milliseconds(2500)),
+        };
+        let annotations = latencies.annotations();
+        assert_eq!(
+            annotations,
+            [
+                (annotation::SCHEDULING_LATENCY_SECONDS, "5".to_owned()),
+                (annotation::LOG_LATENCY_SECONDS, "2".to_owned()),
+            ]
+        );
+        let annotations = annotations
+            .into_iter()
+            .map(|(key, value)| (key.to_owned(), value))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(
+            Latencies::from_annotations(&annotations),
+            Some(Latencies {
+                logs: Some(Duration::seconds(2)),
+                ..latencies
+            })
+        );
+        assert_eq!(Latencies::from_annotations(&HashMap::new()), None);
+    }
+}
@@ -0,0 +1,461 @@
+//! Explains Pods that can not start because they reference a Secret, ConfigMap or PersistentVolumeClaim that does not
+//! exist. Kubernetes keeps retrying such Pods, which would otherwise make us wait until the log availability deadline.
+
+use std::fmt;
+
+use log::debug;
+
+use crate::{
+    kubectl::{self, Kubectl, PodStatus},
+    Result,
+};
+
+/// The maximum number of existing names to suggest for a missing reference.
+const MAX_SUGGESTIONS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceKind {
+    Secret,
+    ConfigMap,
+    PersistentVolumeClaim,
+}
+
+impl ReferenceKind {
+    const ALL: [Self; 3] = [
+        ReferenceKind::Secret,
+        ReferenceKind::ConfigMap,
+        ReferenceKind::PersistentVolumeClaim,
+    ];
+
+    /// The name that kubernetes uses in its messages, which kubectl accepts as resource type.
+    pub fn resource(self) -> &'static str {
+        match self {
+            ReferenceKind::Secret => "secret",
+            ReferenceKind::ConfigMap => "configmap",
+            ReferenceKind::PersistentVolumeClaim => "persistentvolumeclaim",
+        }
+    }
+}
+
+impl fmt::Display for ReferenceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ReferenceKind::Secret => "Secret",
+            ReferenceKind::ConfigMap => "ConfigMap",
+            ReferenceKind::PersistentVolumeClaim => "PersistentVolumeClaim",
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct MissingReference {
+    pub kind: ReferenceKind,
+    pub name: String,
+}
+
+/// Finds the first `<kind> "<name>" not found` in a message of the kubelet or the scheduler.
+pub fn parse_missing_reference(message: &str) -> Option<MissingReference> {
+    ReferenceKind::ALL
+        .into_iter()
+        .filter_map(|kind| {
+            let prefix = format!("{} \"", kind.resource());
+            message.match_indices(&prefix).find_map(|(index, _)| {
+                let (name, rest) = message[index + prefix.len()..].split_once('"')?;
+                rest.starts_with(" not found").then(|| {
+                    (
+                        index,
+                        MissingReference {
+                            kind,
+                            name: name.to_owned(),
+                        },
+                    )
+                })
+            })
+        })
+        .min_by_key(|(index, _)| *index)
+        .map(|(_, reference)| reference)
+}
+
+/// Whether the events of the Pod may explain why it is stuck. Volumes are mounted while the containers are being
+/// created and failures to mount them are only reported through events.
+pub fn needs_events(status: &PodStatus) -> bool {
+    status
+        .container_statuses
+        .iter()
+        .any(|status| status.state.reason() == Some("ContainerCreating"))
+}
+
+/// Returns the reference that keeps the Pod from starting, if its status or events report one. A missing Secret or
+/// ConfigMap used in the environment fails the creation of the container, a missing one used as volume fails the
+/// mount, and a missing PersistentVolumeClaim makes the Pod unschedulable.
+pub fn find_missing_reference(
+    status: &PodStatus,
+    events: &[kubectl::Event],
+) -> Option<MissingReference> {
+    let container_messages = status
+        .container_statuses
+        .iter()
+        .filter(|status| status.state.reason() == Some("CreateContainerConfigError"))
+        .filter_map(|status| status.state.message());
+    let scheduling_messages = status
+        .conditions
+        .iter()
+        .filter(|condition| {
+            condition.r#type == "PodScheduled"
+                && condition.reason.as_deref() == Some("Unschedulable")
+        })
+        .filter_map(|condition| condition.message.as_deref());
+    // The events are sorted oldest first and the latest attempt is the most relevant.
+    let event_messages = events
+        .iter()
+        .rev()
+        .filter(|event| {
+            matches!(
+                event.reason.as_deref(),
+                Some("FailedMount" | "FailedScheduling")
+            )
+        })
+        .filter_map(|event| event.message.as_deref());
+
+    container_messages
+        .chain(scheduling_messages)
+        .chain(event_messages)
+        .find_map(parse_missing_reference)
+}
+
+/// The number of single character insertions, deletions and substitutions needed to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, &b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// Returns the candidates that are close enough to `name` to be what was meant, closest first.
+pub fn suggestions<'a>(name: &str, candidates: &'a [String]) -> Vec<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    let mut close = candidates
+        .iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate.as_str()))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .collect::<Vec<_>>();
+    close.sort();
+    close
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+#[derive(Debug)]
+pub struct MissingReferenceError {
+    pub namespace: String,
+    pub pod_name: String,
+    pub reference: MissingReference,
+    /// Existing names in the namespace that are similar to the missing one.
+    pub suggestions: Vec<String>,
+}
+
+impl fmt::Display for MissingReferenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            namespace,
+            pod_name,
+            reference: MissingReference { kind, name },
+            suggestions,
+        } = self;
+        write!(
+            f,
+            "Pod {namespace}/{pod_name} can not start because {kind} {name:?} does not exist in namespace {namespace}"
+        )?;
+        if !suggestions.is_empty() {
+            let suggestions = suggestions
+                .iter()
+                .map(|suggestion| format!("{suggestion:?}"))
+                .collect::<Vec<_>>();
+            write!(f, ", did you mean {}?", suggestions.join(" or "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MissingReferenceError {}
+
+/// Checks whether the Pod is stuck on a missing reference. The reference is looked up because the status and events
+/// may predate its creation, in which case the Pod starts on its next attempt.
+pub fn diagnose(
+    kubectl: &Kubectl,
+    namespace: &str,
+    pod_name: &str,
+    status: &PodStatus,
+) -> Result<Option<MissingReferenceError>> {
+    let events = if needs_events(status) {
+        kubectl.pod_events(namespace, pod_name)?
+    } else {
+        Vec::new()
+    };
+    let Some(reference) = find_missing_reference(status, &events) else {
+        return Ok(None);
+    };
+    let resource = reference.kind.resource();
+    if kubectl.exists(resource, namespace, &reference.name)? {
+        debug!(
+            "{} {namespace}/{} exists now, Pod {namespace}/{pod_name} should start on its next attempt",
+            reference.kind, reference.name
+        );
+        return Ok(None);
+    }
+    // The suggestions are a bonus, the error explains the Pod without them.
+    let candidates = kubectl
+        .resource_names(resource, namespace)
+        .unwrap_or_else(|error| {
+            debug!(
+                "Unable to list the {resource} in namespace {namespace} to suggest names: {error}"
+            );
+            Vec::new()
+        });
+    let suggestions = suggestions(&reference.name, &candidates)
+        .into_iter()
+        .map(ToOwned::to_owned)
+        .collect();
+    Ok(Some(MissingReferenceError {
+        namespace: namespace.to_owned(),
+        pod_name: pod_name.to_owned(),
+        reference,
+        suggestions,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reference(kind: ReferenceKind, name: &str) -> Option<MissingReference> {
+        Some(MissingReference {
+            kind,
+            name: name.to_owned(),
+        })
+    }
+
+    #[test]
+    fn parse_missing_reference_works() {
+        assert_eq!(
+            parse_missing_reference(r#"secret "db-creds" not found"#),
+            reference(ReferenceKind::Secret, "db-creds")
+        );
+        assert_eq!(
+            parse_missing_reference(
+                r#"MountVolume.SetUp failed for volume "config" : configmap "app-config" not found"#
+            ),
+            reference(ReferenceKind::ConfigMap, "app-config")
+        );
+        assert_eq!(
+            parse_missing_reference(
+                r#"0/4 nodes are available: persistentvolumeclaim "datasets" not found. preemption: 0/4 nodes are available: 4 Preemption is not helpful for scheduling."#
+            ),
+            reference(ReferenceKind::PersistentVolumeClaim, "datasets")
+        );
+        // The first of multiple missing references is reported.
+        assert_eq!(
+            parse_missing_reference(
+                r#"MountVolume.SetUp failed for volume "kube-api-access-x2b9q" : [configmap "ca-bundle" not found, secret "db-creds" not found]"#
+            ),
+            reference(ReferenceKind::ConfigMap, "ca-bundle")
+        );
+        // A missing key in an existing Secret is not a missing reference.
+        assert_eq!(
+            parse_missing_reference("couldn't find key password in Secret launch/db-creds"),
+            None
+        );
+        assert_eq!(
+            parse_missing_reference(r#"secret "db-creds" is forbidden"#),
+            None
+        );
+    }
+
+    fn status(fixture: serde_json::Value) -> PodStatus {
+        serde_json::from_value(fixture).unwrap()
+    }
+
+    fn event(reason: &str, message: &str) -> kubectl::Event {
+        kubectl::Event {
+            reason: Some(reason.to_owned()),
+            message: Some(message.to_owned()),
+            r#type: Some("Warning".to_owned()),
+        }
+    }
+
+    #[test]
+    fn find_missing_reference_works() {
+        let create_container_config_error = status(serde_json::json!({
+            "phase": "Pending",
+            "containerStatuses": [{
+                "name": "main",
+                "image": "berkeley-docker.taila1eba.ts.net/launch:abc",
+                "imageID": "",
+                "state": {
+                    "waiting": {
+                        "reason": "CreateContainerConfigError",
+                        "message": "secret \"db-cred\" not found"
+                    }
+                }
+            }]
+        }));
+        assert!(!needs_events(&create_container_config_error));
+        assert_eq!(
+            find_missing_reference(&create_container_config_error, &[]),
+            reference(ReferenceKind::Secret, "db-cred")
+        );
+
+        let unschedulable = status(serde_json::json!({
+            "phase": "Pending",
+            "conditions": [{
+                "type": "PodScheduled",
+                "status": "False",
+                "reason": "Unschedulable",
+                "message": "0/1 nodes are available: persistentvolumeclaim \"dataset\" not found. preemption: 0/1 nodes are available: 1 Preemption is not helpful for scheduling."
+            }]
+        }));
+        assert_eq!(
+            find_missing_reference(&unschedulable, &[]),
+            reference(ReferenceKind::PersistentVolumeClaim, "dataset")
+        );
+
+        let container_creating = status(serde_json::json!({
+            "phase": "Pending",
+            "containerStatuses": [{
+                "name": "main",
+                "image": "berkeley-docker.taila1eba.ts.net/launch:abc",
+                "imageID": "",
+                "state": { "waiting": { "reason": "ContainerCreating" } }
+            }]
+        }));
+        assert!(needs_events(&container_creating));
+        assert_eq!(find_missing_reference(&container_creating, &[]), None);
+        let events = [
+            event(
+                "Scheduled",
+                "Successfully assigned launch/job-abc-x7k2p to gpu-node-3",
+            ),
+            event(
+                "FailedMount",
+                r#"MountVolume.SetUp failed for volume "creds" : secret "db-creds-old" not found"#,
+            ),
+            event(
+                "FailedMount",
+                r#"MountVolume.SetUp failed for volume "creds" : secret "db-creds" not found"#,
+            ),
+        ];
+        assert_eq!(
+            find_missing_reference(&container_creating, &events),
+            reference(ReferenceKind::Secret, "db-creds")
+        );
+        assert_eq!(
+            find_missing_reference(&container_creating, &events[..1]),
+            None
+        );
+    }
+
+    #[test]
+    fn edit_distance_works() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("db-creds", "db-creds"), 0);
+        assert_eq!(edit_distance("db-cred", "db-creds"), 1);
+        assert_eq!(edit_distance("db-crdes", "db-creds"), 2);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn suggestions_work() {
+        let candidates = [
+            "databrickscfg-alice",
+            "db-creds",
+            "db-creds-staging",
+            "wandb-api-key",
+        ]
+        .map(str::to_owned);
+        assert_eq!(suggestions("db-cred", &candidates), ["db-creds"]);
+        assert_eq!(
+            suggestions("databrickscfg-alcie", &candidates),
+            ["databrickscfg-alice"]
+        );
+        assert_eq!(suggestions("wandb-key", &candidates), Vec::<&str>::new());
+        assert_eq!(
+            suggestions("x", &["y", "z", "xy", "ab"].map(str::to_owned)),
+            ["xy", "y", "z"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn diagnose_works_without_suggestions() {
+        use crate::{cli::ClusterContext, kubectl::fake::FakeKubectl};
+
+        let _kubectl = FakeKubectl::install(
+            r#"case "$*" in
+    *"--output=json") echo 'Error from server (NotFound): secrets "db-cred" not found' >&2; exit 1 ;;
+    *) echo 'Error from server (Forbidden): secrets is forbidden' >&2; exit 1 ;;
+esac"#,
+        );
+        let status = status(serde_json::json!({
+            "phase": "Pending",
+            "containerStatuses": [{
+                "name": "main",
+                "image": "berkeley-docker.taila1eba.ts.net/launch:abc",
+                "imageID": "",
+                "state": {
+                    "waiting": {
+                        "reason": "CreateContainerConfigError",
+                        "message": "secret \"db-cred\" not found"
+                    }
+                }
+            }]
+        }));
+        let error = diagnose(
+            &ClusterContext::Local.kubectl(),
+            "launch",
+            "job-abc-x7k2p",
+            &status,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            error.to_string(),
+            r#"Pod launch/job-abc-x7k2p can not start because Secret "db-cred" does not exist in namespace launch"#
+        );
+    }
+
+    #[test]
+    fn missing_reference_error_displays() {
+        let error = MissingReferenceError {
+            namespace: "launch".to_owned(),
+            pod_name: "job-abc-x7k2p".to_owned(),
+            reference: MissingReference {
+                kind: ReferenceKind::Secret,
+                name: "db-cred".to_owned(),
+            },
+            suggestions: vec!["db-creds".to_owned(), "db-creds-2".to_owned()],
+        };
+        assert_eq!(
+            error.to_string(),
+            r#"Pod launch/job-abc-x7k2p can not start because Secret "db-cred" does not exist in namespace launch, did you mean "db-creds" or "db-creds-2"?"#
+        );
+        let error = MissingReferenceError {
+            suggestions: Vec::new(),
+            ..error
+        };
+        assert_eq!(
+            error.to_string(),
+            r#"Pod launch/job-abc-x7k2p can not start because Secret "db-cred" does not exist in namespace launch"#
+        );
+    }
+}
@@ -0,0 +1,305 @@
+//! What the executors observe of the cluster while they monitor a resource after it has been created. The monitoring
+//! does not depend on having created the resource in this process, so `launch attach` can resume it.
+
+use std::time::Duration;
+
+use ::katib::models as km;
+use time::OffsetDateTime;
+
+use super::{common, latency, Result};
+use crate::kubectl::{self, Kubectl, PodOwner};
+
+pub trait Cluster {
+    fn job_exists(&self, namespace: &str, name: &str) -> Result<bool>;
+
+    fn pod_exists(&self, namespace: &str, name: &str) -> Result<bool>;
+
+    fn job_pod_names(&self, namespace: &str, job_name: &str) -> Result<Vec<String>>;
+
+    fn head_pod(&self, namespace: &str, ray_cluster_name: &str) -> Result<Option<kubectl::Pod>>;
+
+    /// See [`Kubectl::try_get_ray_job`].
+    fn ray_job(&self, namespace: &str, name: &str) -> Result<Option<serde_json::Value>>;
+
+    /// Returns the status of the Experiment, which is `None` until katib has reconciled it.
+    fn experiment_status(
+        &self,
+        namespace: &str,
+        name: &str,
+    ) -> Result<Option<km::V1beta1ExperimentStatus>>;
+
+    /// See [`common::wait_for_and_follow_pod_logs`].
+    fn follow_pod_logs(
+        &self,
+        namespace: &str,
+        pod_name: &str,
+    ) -> Result<Option<OffsetDateTime>, common::PodLogPollError>;
+
+    /// Like [`Cluster::follow_pod_logs`], but passes each log line to `on_line` instead of printing it.
+    fn follow_pod_log_lines(
+        &self,
+        namespace: &str,
+        pod_name: &str,
+        on_line: &mut dyn FnMut(&str),
+    ) -> Result<Option<OffsetDateTime>, common::PodLogPollError>;
+
+    /// See [`latency::record`].
+    fn record_latency(
+        &self,
+        namespace: &str,
+        job_name: &str,
+        pod_name: &str,
+        logs_available: OffsetDateTime,
+    );
+
+    fn read_file(
+        &self,
+        namespace: &str,
+        pod_name: &str,
+        container: &str,
+        path: &str,
+    ) -> Result<String>;
+
+    /// Waits between two polls.
+    fn sleep(&self, duration: Duration);
+}
+
+impl Cluster for Kubectl<'_> {
+    fn job_exists(&self, namespace: &str, name: &str) -> Result<bool> {
+        Ok(self.try_get_job(namespace, name)?.is_some())
+    }
+
+    fn pod_exists(&self, namespace: &str, name: &str) -> Result<bool> {
+        self.exists("pod", namespace, name)
+    }
+
+    fn job_pod_names(&self, namespace: &str, job_name: &str) -> Result<Vec<String>> {
+        Ok(self
+            .pods_for_owner(namespace, PodOwner::Job(job_name))?
+            .into_iter()
+            .map(|pod| pod.metadata.name)
+            .collect())
+    }
+
+    fn head_pod(&self, namespace: &str, ray_cluster_name: &str) -> Result<Option<kubectl::Pod>> {
+        Ok(self
+            .pods_with_selector(namespace, &kubectl::head_pod_selector(ray_cluster_name))?
+            .pop())
+    }
+
+    fn ray_job(&self, namespace: &str, name: &str) -> Result<Option<serde_json::Value>> {
+        self.try_get_ray_job(namespace, name)
+    }
+
+    fn experiment_status(
+        &self,
+        namespace: &str,
+        name: &str,
+    ) -> Result<Option<km::V1beta1ExperimentStatus>> {
+        Ok(self
+            .katib_experiment(namespace, name)?
+            .status
+            .map(|status| *status))
+    }
+
+    fn follow_pod_logs(
+        &self,
+        namespace: &str,
+        pod_name: &str,
+    ) -> Result<Option<OffsetDateTime>, common::PodLogPollError> {
+        common::wait_for_and_follow_pod_logs(self, namespace, pod_name)
+    }
+
+    fn follow_pod_log_lines(
+        &self,
+        namespace: &str,
+        pod_name: &str,
+        on_line: &mut dyn FnMut(&str),
+    ) -> Result<Option<OffsetDateTime>, common::PodLogPollError> {
+        common::wait_for_and_follow_pod_log_lines(
+            self,
+            namespace,
+            pod_name,
+            Some(&mut |line| {
+                on_line(line);
+                Ok(())
+            }),
+        )
+    }
+
+    fn record_latency(
+        &self,
+        namespace: &str,
+        job_name: &str,
+        pod_name: &str,
+        logs_available: OffsetDateTime,
+    ) {
+        latency::record(
+            self,
+            namespace,
+            job_name,
+            pod_name,
+            common::PRIMARY_CONTAINER_NAME,
+            logs_available,
+        );
+    }
+
+    fn read_file(
+        &self,
+        namespace: &str,
+        pod_name: &str,
+        container: &str,
+        path: &str,
+    ) -> Result<String> {
+        Kubectl::read_file(self, namespace, pod_name, container, path)
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        cell::{Cell, RefCell},
+        collections::VecDeque,
+    };
+
+    use super::*;
+
+    /// Returns the next observation of a sequence. The last observation repeats, like a resource that no longer
+    /// changes.
+    fn next<T: Clone>(sequence: &RefCell<VecDeque<T>>) -> T {
+        let mut sequence = sequence.borrow_mut();
+        if sequence.len() > 1 {
+            sequence.pop_front().unwrap()
+        } else {
+            sequence
+                .front()
+                .cloned()
+                .expect("the fixture should have an observation")
+        }
+    }
+
+    pub(crate) type BuildPod = fn() -> kubectl::Pod;
+
+    /// A cluster that plays back sequences of observations, one per poll.
+    #[derive(Default)]
+    pub(crate) struct Fixture {
+        pub jobs_exist: RefCell<VecDeque<bool>>,
+        pub pods_exist: bool,
+        pub pod_names: Vec<String>,
+        /// Pods are not `Clone`, so the head Pods are built for every poll.
+        pub head_pods: RefCell<VecDeque<Option<BuildPod>>>,
+        pub ray_jobs: RefCell<VecDeque<Option<serde_json::Value>>>,
+        pub experiment_statuses: RefCell<VecDeque<Option<km::V1beta1ExperimentStatus>>>,
+        /// Whether following the logs fails, like it does when the Pod has been deleted.
+        pub logs_fail: bool,
+        /// The lines that following the logs passes to the callback of [`Cluster::follow_pod_log_lines`].
+        pub log_lines: Vec<String>,
+        /// The Pods whose logs were followed with [`Cluster::follow_pod_log_lines`], which are also in `followed`.
+        pub followed_lines: RefCell<Vec<String>>,
+        pub files: Vec<(String, String)>,
+        pub followed: RefCell<Vec<String>>,
+        pub latencies: RefCell<Vec<String>>,
+        pub sleeps: Cell<usize>,
+    }
+
+    impl Fixture {
+        pub(crate) fn sequence<T>(
+            observations: impl IntoIterator<Item = T>,
+        ) -> RefCell<VecDeque<T>> {
+            RefCell::new(observations.into_iter().collect())
+        }
+    }
+
+    impl Cluster for Fixture {
+        fn job_exists(&self, _namespace: &str, _name: &str) -> Result<bool> {
+            Ok(next(&self.jobs_exist))
+        }
+
+        fn pod_exists(&self, _namespace: &str, _name: &str) -> Result<bool> {
+            Ok(self.pods_exist)
+        }
+
+        fn job_pod_names(&self, _namespace: &str, _job_name: &str) -> Result<Vec<String>> {
+            Ok(self.pod_names.clone())
+        }
+
+        fn head_pod(
+            &self,
+            _namespace: &str,
+            _ray_cluster_name: &str,
+        ) -> Result<Option<kubectl::Pod>> {
+            Ok(next(&self.head_pods).map(|pod| pod()))
+        }
+
+        fn ray_job(&self, _namespace: &str, _name: &str) -> Result<Option<serde_json::Value>> {
+            Ok(next(&self.ray_jobs))
+        }
+
+        fn experiment_status(
+            &self,
+            _namespace: &str,
+            _name: &str,
+        ) -> Result<Option<km::V1beta1ExperimentStatus>> {
+            Ok(next(&self.experiment_statuses))
+        }
+
+        fn follow_pod_logs(
+            &self,
+            _namespace: &str,
+            pod_name: &str,
+        ) -> Result<Option<OffsetDateTime>, common::PodLogPollError> {
+            self.followed.borrow_mut().push(pod_name.to_owned());
+            if self.logs_fail {
+                Err(common::PodLogPollError::Other(
+                    format!("pods \"{pod_name}\" not found").into(),
+                ))
+            } else {
+                Ok(Some(OffsetDateTime::UNIX_EPOCH))
+            }
+        }
+
+        fn follow_pod_log_lines(
+            &self,
+            namespace: &str,
+            pod_name: &str,
+            on_line: &mut dyn FnMut(&str),
+        ) -> Result<Option<OffsetDateTime>, common::PodLogPollError> {
+            self.followed_lines.borrow_mut().push(pod_name.to_owned());
+            let followed = self.follow_pod_logs(namespace, pod_name)?;
+            self.log_lines.iter().for_each(|line| on_line(line));
+            Ok(followed)
+        }
+
+        fn record_latency(
+            &self,
+            _namespace: &str,
+            _job_name: &str,
+            pod_name: &str,
+            _logs_available: OffsetDateTime,
+        ) {
+            self.latencies.borrow_mut().push(pod_name.to_owned());
+        }
+
+        fn read_file(
+            &self,
+            _namespace: &str,
+            _pod_name: &str,
+            _container: &str,
+            path: &str,
+        ) -> Result<String> {
+            self.files
+                .iter()
+                .find(|(file, _)| file == path)
+                .map(|(_, contents)| contents.clone())
+                .ok_or_else(|| format!("{path}: No such file or directory").into())
+        }
+
+        fn sleep(&self, _duration: Duration) {
+            self.sleeps.set(self.sleeps.get() + 1);
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! Explains out-of-memory kills, which kubernetes only reports as exit code 137 with reason `OOMKilled`.
+
+use log::{debug, warn};
+
+use crate::{
+    kubectl::{self, Kubectl},
+    unit::bytes::{self, Bytes},
+};
+
+/// Formats memory in GiB when it is an exact multiple of a GiB, and in MiB otherwise. Rounds down so that statements
+/// like "using >= X" remain true.
+fn format_memory(value: Bytes) -> String {
+    let gib = value.get_floor::<bytes::gibibyte>();
+    if gib > 0 && Bytes::new::<bytes::gibibyte>(gib) == Some(value) {
+        format!("{gib}GiB")
+    } else {
+        format!("{}MiB", value.get_floor::<bytes::mebibyte>())
+    }
+}
+
+/// Suggests a memory limit of 1.5 times the current limit, rounded up to a whole number of GiB.
+pub fn suggest_memory_limit(limit: Bytes) -> Bytes {
+    let scaled = Bytes::new::<bytes::byte>(limit.get::<bytes::byte>().saturating_mul(3) / 2)
+        .expect("bytes unit should not overflow");
+    Bytes::new::<bytes::gibibyte>(scaled.get_ceil::<bytes::gibibyte>()).unwrap_or(scaled)
+}
+
+/// Produces a human readable explanation of an out-of-memory kill. The limit and usage are optional because the
+/// container may not have a memory limit and the metrics server may not be available.
+pub fn oom_summary(container: &str, limit: Option<Bytes>, usage: Option<Bytes>) -> String {
+    match (limit, usage) {
+        (Some(limit), usage) => {
+            let suggestion = format_memory(suggest_memory_limit(limit));
+            let limit = format_memory(limit);
+            match usage {
+                Some(usage) => format!(
+                    "container {container:?} was killed using >= {usage} of its {limit} memory limit; consider `--mem {suggestion}`",
+                    usage = format_memory(usage),
+                ),
+                None => format!(
+                    "container {container:?} was killed for exceeding its {limit} memory limit; consider `--mem {suggestion}`"
+                ),
+            }
+        }
+        (None, Some(usage)) => format!(
+            "container {container:?} was killed after using >= {usage} of memory because the node ran out of memory; consider setting a limit with `--mem`",
+            usage = format_memory(usage),
+        ),
+        (None, None) => format!(
+            "container {container:?} was killed because the node ran out of memory; consider setting a limit with `--mem`"
+        ),
+    }
+}
+
+/// Emits a warning for each container of the pod that was killed because it ran out of memory. Failures to obtain the
+/// information are logged and otherwise ignored because this is purely informational.
+pub fn warn_about_oom_kills(kubectl: &Kubectl, pod: &kubectl::Pod) {
+    let namespace = &pod.metadata.namespace;
+    let name = &pod.metadata.name;
+
+    let oom_killed = pod
+        .status
+        .container_statuses
+        .iter()
+        .filter(|status| status.is_oom_killed())
+        .collect::<Vec<_>>();
+
+    if oom_killed.is_empty() {
+        return;
+    }
+
+    let usages = kubectl
+        .top_pod(namespace, name)
+        .inspect_err(|error| {
+            debug!("Unable to obtain memory usage for Pod {namespace}/{name}: {error}")
+        })
+        .unwrap_or_default();
+
+    for status in oom_killed {
+        let limit = pod
+            .spec
+            .container(&status.name)
+            .and_then(kubectl::Container::memory_limit);
+        let usage = usages
+            .iter()
+            .find(|usage| usage.container == status.name)
+            .and_then(|usage| usage.memory);
+        warn!(
+            "Pod {namespace}/{name} ran out of memory: {}",
+            oom_summary(&status.name, limit, usage)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gib(value: u64) -> Bytes {
+        Bytes::new::<bytes::gibibyte>(value).unwrap()
+    }
+
+    fn mib(value: u64) -> Bytes {
+        Bytes::new::<bytes::mebibyte>(value).unwrap()
+    }
+
+    #[test]
+    fn suggest_memory_limit_works() {
+        assert_eq!(suggest_memory_limit(gib(16)), gib(24));
+        assert_eq!(suggest_memory_limit(gib(1)), gib(2));
+        assert_eq!(suggest_memory_limit(mib(500)), gib(1));
+        assert_eq!(suggest_memory_limit(gib(3)), gib(5));
+    }
+
+    #[test]
+    fn format_memory_works() {
+        assert_eq!(format_memory(gib(16)), "16GiB");
+        assert_eq!(format_memory(mib(1536)), "1536MiB");
+        assert_eq!(format_memory(mib(500)), "500MiB");
+    }
+
+    #[test]
+    fn oom_summary_works() {
+        assert_eq!(
+            oom_summary("main", Some(gib(16)), Some(mib(16 * 1024 + 10))),
+            "container \"main\" was killed using >= 16394MiB of its 16GiB memory limit; consider `--mem 24GiB`"
+        );
+        assert_eq!(
+            oom_summary("main", Some(gib(16)), Some(gib(16))),
+            "container \"main\" was killed using >= 16GiB of its 16GiB memory limit; consider `--mem 24GiB`"
+        );
+        assert_eq!(
+            oom_summary("main", Some(gib(16)), None),
+            "container \"main\" was killed for exceeding its 16GiB memory limit; consider `--mem 24GiB`"
+        );
+        assert_eq!(
+            oom_summary("main", None, None),
+            "container \"main\" was killed because the node ran out of memory; consider setting a limit with `--mem`"
+        );
+    }
+}
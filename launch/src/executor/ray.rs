@@ -1,23 +1,37 @@
 //! The ray on kubernetes ray_job backend implementation.
 
+use std::{
+    io::Write as _,
+    path::Path,
+    time::{Duration, Instant},
+};
+
 use log::{debug, info, warn};
 
-use super::{ExecutionArgs, ExecutionOutput, Executor, Result};
-use crate::{bash_escape, executor::common, kubectl::ResourceHandle};
+use super::{monitor::Cluster, ExecutionArgs, ExecutionOutput, Executor, ResourceKind, Result};
+use crate::{
+    bash_escape,
+    cli::ClusterContext,
+    executor::{common, dedupe_logs},
+    kubectl::{self, ResourceHandle},
+    process, urls,
+};
 
 fn ray_job_spec(args: &ExecutionArgs) -> serde_json::Value {
     let annotations = args.annotations();
+    let labels = args.labels();
 
     // Ray parses this string with `shlex`. See https://github.com/Astera-org/obelisk/issues/329.
     let entrypoint = bash_escape::quote_join(args.container_args.iter().map(String::as_str));
 
-    serde_json::json!({
+    let mut spec = serde_json::json!({
         "apiVersion": "ray.io/v1",
         "kind": "RayJob",
         "metadata": {
             "namespace": args.job_namespace,
             "generateName": args.generate_name,
             "annotations": annotations,
+            "labels": labels,
         },
         "spec": {
             "entrypoint": entrypoint,
@@ -34,12 +48,13 @@ fn ray_job_spec(args: &ExecutionArgs) -> serde_json::Value {
                     "template": {
                         "metadata": {
                             "annotations": annotations,
+                            "labels": labels,
                         },
                         "spec": {
                             "containers": [
                                 {
                                     "name": "ray-head",
-                                    "image": args.image,
+                                    "image": args.pinned_image(),
                                     // Default ports, see https://github.com/ray-project/kuberay/blob/master/ray-operator/config/samples/ray-job.sample.yaml.
                                     "ports": [
                                         {
@@ -71,13 +86,14 @@ fn ray_job_spec(args: &ExecutionArgs) -> serde_json::Value {
                         "template": {
                             "metadata": {
                                 "annotations": annotations,
+                                "labels": labels,
                             },
                             "spec": {
                                 "affinity": args.affinity(),
                                 "containers": [
                                     {
                                         "name": "ray-worker",
-                                        "image": args.image,
+                                        "image": args.pinned_image(),
                                         "lifecycle": {
                                             "preStop": {
                                                 "exec": {
@@ -101,103 +117,857 @@ fn ray_job_spec(args: &ExecutionArgs) -> serde_json::Value {
             "submitterPodTemplate": {
                 "metadata": {
                     "annotations": annotations,
+                    "labels": labels,
                 },
                 "spec": {
                     "restartPolicy": "Never",
                     "containers": [
                         {
                             "name": "ray-job-submitter",
-                            "image": args.image,
+                            "image": args.pinned_image(),
                             // We have to specify the command because otherwise kuberay overwrites it. Ideally, we would
                             // omit this and use `args` instead. See https://github.com/ray-project/kuberay/pull/2208.
                             "command": ["/bin/bash", "-lc", "--"],
                             // We should not quote this script. The script contains the quoted entrypoint. See https://github.com/Astera-org/obelisk/issues/329.
                             "args": [format!("ray job submit --address=http://$RAY_DASHBOARD_ADDRESS --submission-id=$RAY_JOB_SUBMISSION_ID -- {entrypoint}")],
+                            "env": [args.run_id_env()],
                         }
                     ]
                 }
             }
         }
+    });
+
+    if let Some(ray_version) = args.ray_version {
+        spec["spec"]["rayClusterSpec"]["rayVersion"] = ray_version.into();
+    }
+    if let Some(runtime_env) = args.ray_runtime_env {
+        spec["spec"]["runtimeEnvYAML"] = runtime_env.into();
+    }
+    if let Some(restart_policy) = args.ray_worker_restart_policy {
+        spec["spec"]["rayClusterSpec"]["workerGroupSpecs"][0]["template"]["spec"]
+            ["restartPolicy"] = restart_policy.as_str().into();
+    }
+
+    spec
+}
+
+/// Reads a Ray [runtime environment](https://docs.ray.io/en/latest/ray-core/handling-dependencies.html#runtime-environments)
+/// for `spec.runtimeEnvYAML`. The file is embedded as it is, so it is validated here rather than by kuberay once the
+/// RayJob has been created.
+pub fn read_runtime_env(path: &Path) -> Result<String> {
+    let contents = crate::text_config::read_text_config(path)
+        .map_err(|error| format!("Failed to read the Ray runtime environment {path:?}: {error}"))?;
+    validate_runtime_env(&contents)
+        .map_err(|error| format!("Invalid Ray runtime environment {path:?}: {error}"))?;
+    Ok(contents)
+}
+
+fn validate_runtime_env(contents: &str) -> Result<(), String> {
+    match serde_yaml::from_str(contents).map_err(|error| error.to_string())? {
+        serde_yaml::Value::Mapping(_) => Ok(()),
+        _ => Err("expected a mapping with fields such as `pip` and `env_vars`".to_owned()),
+    }
+}
+
+/// Describes why the head Pod can not start, if it is stuck. An unschedulable Pod includes the scheduler's message,
+/// which summarizes the resources that are insufficient.
+fn head_pod_problem(status: &kubectl::PodStatus) -> Option<String> {
+    if let Some(condition) = status.conditions.iter().find(|condition| {
+        condition.r#type == "PodScheduled" && condition.reason.as_deref() == Some("Unschedulable")
+    }) {
+        return Some(match condition.message.as_deref() {
+            Some(message) => format!("is unschedulable: {message}"),
+            None => "is unschedulable".to_owned(),
+        });
+    }
+    status.container_statuses.iter().find_map(|container| {
+        if !container.cannot_pull_image() {
+            return None;
+        }
+        let kubectl::ContainerState::Waiting(state) = &container.state else {
+            return None;
+        };
+        Some(match state.message.as_deref() {
+            Some(message) => format!(
+                "can not pull the image of container {:?}: {message}",
+                container.name
+            ),
+            None => format!("can not pull the image of container {:?}", container.name),
+        })
+    })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum CreationProgress {
+    SubmitterCreated,
+    Waiting {
+        /// Why the head Pod can not start, if it is stuck.
+        head_pod_problem: Option<String>,
+    },
+    HeadPodStuck {
+        pod_name: String,
+        problem: String,
+    },
+}
+
+/// Tracks the wait for kuberay to create the submitter Job. Kuberay only creates the submitter Job once the head Pod is
+/// running, so a head Pod that can not start would otherwise make us wait until the creation deadline.
+struct CreationWait {
+    stuck_timeout: Duration,
+    stuck_since: Option<Instant>,
+}
+
+impl CreationWait {
+    fn new(stuck_timeout: Duration) -> Self {
+        Self {
+            stuck_timeout,
+            stuck_since: None,
+        }
+    }
+
+    fn observe(
+        &mut self,
+        now: Instant,
+        submitter_job_exists: bool,
+        head_pod: Option<&kubectl::Pod>,
+    ) -> CreationProgress {
+        if submitter_job_exists {
+            return CreationProgress::SubmitterCreated;
+        }
+        let Some((pod_name, problem)) =
+            head_pod.and_then(|pod| Some((&pod.metadata.name, head_pod_problem(&pod.status)?)))
+        else {
+            self.stuck_since = None;
+            return CreationProgress::Waiting {
+                head_pod_problem: None,
+            };
+        };
+        let stuck_since = *self.stuck_since.get_or_insert(now);
+        if now.duration_since(stuck_since) >= self.stuck_timeout {
+            CreationProgress::HeadPodStuck {
+                pod_name: pod_name.clone(),
+                problem,
+            }
+        } else {
+            CreationProgress::Waiting {
+                head_pod_problem: Some(problem),
+            }
+        }
+    }
+}
+
+/// The outcome of a RayJob whose submitter Pod was deleted before its logs could be followed.
+#[derive(Debug, PartialEq, Eq)]
+struct FinishedRayJob<'a> {
+    /// One of `SUCCEEDED`, `FAILED` or `STOPPED`.
+    job_status: &'a str,
+    message: Option<&'a str>,
+    ray_cluster_name: Option<&'a str>,
+    submission_id: Option<&'a str>,
+}
+
+/// Returns the outcome of the RayJob if its submitter Pod is gone because the RayJob already finished. A RayJob with
+/// `shutdownAfterJobFinishes` that finishes within seconds can be cleaned up before we follow the logs. Returns `None`
+/// when the Pod still exists or the RayJob has not finished, in which case the failure to follow the logs has a
+/// different cause.
+fn finished_before_logs(
+    submitter_pod_exists: bool,
+    ray_job: Option<&serde_json::Value>,
+) -> Option<FinishedRayJob<'_>> {
+    if submitter_pod_exists {
+        return None;
+    }
+    let ray_job = ray_job?;
+    let status = |field: &str| {
+        ray_job
+            .pointer(&format!("/status/{field}"))
+            .and_then(serde_json::Value::as_str)
+            .filter(|value| !value.is_empty())
+    };
+    let job_status = status("jobStatus")
+        .filter(|status| matches!(*status, "SUCCEEDED" | "FAILED" | "STOPPED"))?;
+    Some(FinishedRayJob {
+        job_status,
+        message: status("message"),
+        ray_cluster_name: kubectl::ray_cluster_name(ray_job),
+        submission_id: kubectl::ray_job_submission_id(ray_job),
     })
 }
 
+/// The log file of the Ray driver on the head Pod, which contains the output of the entrypoint.
+fn driver_log_path(submission_id: &str) -> String {
+    format!("/tmp/ray/session_latest/logs/job-driver-{submission_id}.log")
+}
+
+/// Reads the output of the entrypoint from the head Pod, if the RayCluster still exists.
+fn driver_log(
+    cluster: &impl Cluster,
+    namespace: &str,
+    ray_cluster_name: &str,
+    submission_id: &str,
+) -> Result<Option<String>> {
+    let Some(head_pod) = cluster.head_pod(namespace, ray_cluster_name)? else {
+        return Ok(None);
+    };
+    cluster
+        .read_file(
+            namespace,
+            &head_pod.metadata.name,
+            "ray-head",
+            &driver_log_path(submission_id),
+        )
+        .map(Some)
+}
+
+/// Reports what can be recovered of a RayJob that finished before the logs of its submitter Pod could be followed.
+/// Returns the error that occurred while following the logs when the RayJob did not finish, and an error with the
+/// status of the RayJob when it did not succeed.
+fn recover_output(
+    cluster: &impl Cluster,
+    namespace: &str,
+    job_name: &str,
+    pod_name: Option<&str>,
+    error: Box<dyn std::error::Error + Send + Sync>,
+) -> Result<()> {
+    let submitter_pod_exists = match pod_name {
+        Some(pod_name) => cluster.pod_exists(namespace, pod_name)?,
+        None => false,
+    };
+    let ray_job = cluster.ray_job(namespace, job_name)?;
+    let Some(finished) = finished_before_logs(submitter_pod_exists, ray_job.as_ref()) else {
+        return Err(error);
+    };
+    debug!("Unable to follow the logs of the submitter Pod: {error}");
+    warn!("RayJob {job_name:?} finished before the logs of its submitter Pod could be followed.");
+
+    let recovered = match (finished.ray_cluster_name, finished.submission_id) {
+        (Some(ray_cluster_name), Some(submission_id)) => {
+            driver_log(cluster, namespace, ray_cluster_name, submission_id)
+                .inspect_err(|error| debug!("Unable to read the Ray driver log: {error}"))
+                .ok()
+                .flatten()
+                .map(|output| (ray_cluster_name, output))
+        }
+        _ => None,
+    };
+    match recovered {
+        Some((ray_cluster_name, output)) => {
+            info!("Recovered the output from the head Pod of RayCluster {namespace}/{ray_cluster_name}:");
+            print!("{output}");
+        }
+        None => warn!(
+            "The output could not be recovered because the RayCluster has been deleted. RayJobs that finish within seconds can lose their output this way, until the planned `--ray-log-persistence` keeps the logs after the RayCluster is deleted."
+        ),
+    }
+
+    match (finished.job_status, finished.message) {
+        ("SUCCEEDED", _) => {
+            info!("RayJob {job_name:?} succeeded");
+            Ok(())
+        }
+        (status, Some(message)) => {
+            Err(format!("RayJob {job_name:?} finished with status {status}: {message}").into())
+        }
+        (status, None) => Err(format!("RayJob {job_name:?} finished with status {status}").into()),
+    }
+}
+
 pub struct RayExecutor;
 
 impl Executor for RayExecutor {
+    fn spec(&self, args: &ExecutionArgs) -> Result<serde_json::Value> {
+        Ok(ray_job_spec(args))
+    }
+
     fn execute(&self, args: ExecutionArgs) -> Result<ExecutionOutput> {
         let kubectl = args.context.kubectl();
-        let headlamp_url = args.context.headlamp_url();
 
-        let (job_namespace, job_name) = {
-            let job_spec = ray_job_spec(&args);
-            let ResourceHandle { namespace, name } = kubectl.create(&job_spec.to_string())?;
-            assert_eq!(args.job_namespace, namespace);
-            (namespace, name)
-        };
+        let handle = kubectl.create_with_run_id(&self.spec(&args)?.to_string(), args.run_id)?;
+        assert_eq!(args.job_namespace, handle.namespace);
         debug!(
             "Created RayJob {:?}.",
-            format!(
-                "{headlamp_url}/c/main/customresources/rayjobs.ray.io/{job_namespace}/{job_name}"
-            )
+            urls::headlamp_rayjob_customresource(args.context, &handle.namespace, &handle.name)
         );
 
-        let deadline = common::Deadline::after(common::RAY_JOB_CREATION_TIMEOUT);
+        if args.follow_logs {
+            monitor_ray_job(args.context, &kubectl, &handle, args.dedupe_logs)?;
+        }
 
-        info!(
-            "Waiting for submitter Job {:?} to become available...",
-            job_name
-        );
+        let ResourceHandle { namespace, name } = handle;
+        Ok(ExecutionOutput {
+            kind: ResourceKind::Job,
+            namespace,
+            name,
+        })
+    }
+}
 
-        loop {
-            match kubectl.try_get_job(&job_namespace, &job_name) {
-                Ok(Some(_)) => {
-                    break;
-                }
-                Ok(None) => {
-                    // Keep polling.
-                }
-                Err(error) => return Err(error),
+/// Waits for kuberay to create the submitter Job, giving up on a head Pod that can not start, and follows the logs of
+/// the submitter Pod until it exits. A RayJob that already finished reports what can be recovered of its output. With
+/// `dedupe_logs`, the identical lines of the workers are collapsed, see [`dedupe_logs`].
+pub fn monitor_ray_job(
+    context: &ClusterContext,
+    cluster: &impl Cluster,
+    handle: &ResourceHandle,
+    dedupe_logs: bool,
+) -> Result<()> {
+    monitor_ray_job_with_output(context, cluster, handle, dedupe_logs, |line| {
+        let _ = writeln!(process::output_stream(), "{line}");
+    })
+}
+
+/// Like [`monitor_ray_job`], but passes the deduplicated lines to `output` instead of printing them.
+fn monitor_ray_job_with_output(
+    context: &ClusterContext,
+    cluster: &impl Cluster,
+    handle: &ResourceHandle,
+    dedupe_logs: bool,
+    output: impl FnMut(&dedupe_logs::Line) + Send,
+) -> Result<()> {
+    let ResourceHandle {
+        namespace: job_namespace,
+        name: job_name,
+    } = handle;
+
+    let deadline = common::Deadline::after(common::RAY_JOB_CREATION_TIMEOUT);
+
+    info!(
+        "Waiting for submitter Job {:?} to become available...",
+        job_name
+    );
+
+    let mut creation_wait = CreationWait::new(common::RAY_HEAD_POD_STUCK_TIMEOUT);
+    let mut ray_cluster_name = None;
+    let mut reported_problem = None;
+
+    loop {
+        let submitter_job_exists = cluster.job_exists(job_namespace, job_name)?;
+
+        if !submitter_job_exists && ray_cluster_name.is_none() {
+            let ray_job = cluster.ray_job(job_namespace, job_name)?;
+            // NOTE: kuberay deletes the submitter Job of a finished RayJob with `shutdownAfterJobFinishes`, which is
+            // only observed here when monitoring a RayJob that was created before.
+            if finished_before_logs(false, ray_job.as_ref()).is_some() {
+                return recover_output(
+                    cluster,
+                    job_namespace,
+                    job_name,
+                    None,
+                    format!("Submitter Job {job_name:?} no longer exists").into(),
+                );
             }
+            ray_cluster_name = ray_job
+                .as_ref()
+                .and_then(kubectl::ray_cluster_name)
+                .map(str::to_owned);
+            if let Some(name) = &ray_cluster_name {
+                debug!("RayJob {job_name:?} created RayCluster {name:?}");
+            }
+        }
+        let head_pod = match &ray_cluster_name {
+            Some(name) if !submitter_job_exists => cluster.head_pod(job_namespace, name)?,
+            _ => None,
+        };
 
-            if deadline.sleep(common::POLLING_INTERVAL).is_err() {
+        match creation_wait.observe(Instant::now(), submitter_job_exists, head_pod.as_ref()) {
+            CreationProgress::SubmitterCreated => break,
+            CreationProgress::Waiting { head_pod_problem } => {
+                if let (Some(problem), Some(pod)) = (&head_pod_problem, &head_pod) {
+                    if reported_problem.as_ref() != Some(problem) {
+                        warn!("Head Pod {job_namespace}/{} {problem}", pod.metadata.name);
+                    }
+                }
+                reported_problem = head_pod_problem;
+            }
+            CreationProgress::HeadPodStuck { pod_name, problem } => {
                 return Err(format!(
-                    "Deadline exceeded while waiting for job {:?} to come into existance",
-                    job_name
+                    "Head Pod {job_namespace}/{pod_name} of RayJob {job_name:?} {problem}. Gave up after {}s. Please ensure that the job does not request more resources than the cluster can possibly offer and that the image exists.",
+                    common::RAY_HEAD_POD_STUCK_TIMEOUT.as_secs()
                 )
                 .into());
             }
+        }
 
-            debug!(
-                "Waiting for submitter Job {:?} to become available...",
+        if !deadline.allows(common::POLLING_INTERVAL) {
+            return Err(format!(
+                "Deadline exceeded while waiting for job {:?} to come into existance",
                 job_name
+            )
+            .into());
+        }
+        cluster.sleep(common::POLLING_INTERVAL);
+
+        debug!(
+            "Waiting for submitter Job {:?} to become available...",
+            job_name
+        );
+    }
+
+    info!(
+        "Created submitter Job {:?}.",
+        urls::headlamp_job(context, job_namespace, job_name)
+    );
+    // Kuberay sets the submission ID before it creates the submitter Job.
+    match cluster
+        .ray_job(job_namespace, job_name)?
+        .as_ref()
+        .and_then(kubectl::ray_job_submission_id)
+    {
+        Some(submission_id) => info!(
+            "Ray submission ID {submission_id:?}, run `launch ray-status {job_name}` for its status in the Ray dashboard."
+        ),
+        None => debug!("RayJob {job_name:?} has no submission ID yet"),
+    }
+
+    let pod_name = {
+        let mut pod_names = cluster.job_pod_names(job_namespace, job_name)?;
+        for pod_name in &pod_names {
+            info!(
+                "Created submitter Pod {:?}.",
+                urls::headlamp_pod(context, job_namespace, pod_name)
+            );
+        }
+        let pod_name = pod_names.pop();
+        if let (Some(pod_name), false) = (&pod_name, pod_names.is_empty()) {
+            warn!(
+                "Following logs only for Pod {:?} and ignoring the others.",
+                urls::headlamp_pod(context, job_namespace, pod_name)
             );
         }
+        pod_name
+    };
 
-        info!(
-            "Created submitter Job {:?}.",
-            format!("{headlamp_url}/c/main/jobs/{job_namespace}/{job_name}")
+    let followed = match &pod_name {
+        Some(pod_name) if dedupe_logs => dedupe_logs::follow_deduped(
+            dedupe_logs::WINDOW,
+            |on_line| cluster.follow_pod_log_lines(job_namespace, pod_name, on_line),
+            output,
+        )
+        .map(drop)
+        .map_err(Into::into),
+        Some(pod_name) => cluster
+            .follow_pod_logs(job_namespace, pod_name)
+            .map(drop)
+            .map_err(Into::into),
+        None => Err("No pods created for job".into()),
+    };
+    if let Err(error) = followed {
+        recover_output(cluster, job_namespace, job_name, pod_name.as_deref(), error)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use container_image_name::ImageName;
+
+    use super::*;
+    use crate::{
+        cli::ClusterContext,
+        executor::{
+            monitor::tests::Fixture,
+            tests::{execution_args, RUN_ID},
+            RestartPolicy, RUN_ID_ENV,
+        },
+    };
+
+    fn spec(
+        ray_version: Option<&str>,
+        ray_runtime_env: Option<&str>,
+        ray_worker_restart_policy: Option<RestartPolicy>,
+    ) -> serde_json::Value {
+        let image = ImageName::builder("launch")
+            .with_registry("registry:5000")
+            .with_tag("latest")
+            .build()
+            .unwrap();
+        ray_job_spec(&ExecutionArgs {
+            generate_name: "ray-job-",
+            container_args: &["python".to_owned(), "train.py".to_owned()],
+            workers: 2,
+            ray_version,
+            ray_runtime_env,
+            ray_worker_restart_policy,
+            ..execution_args(image.as_ref())
+        })
+    }
+
+    /// The RayJob as it is between the deletion of its submitter Pod and the deletion of its RayCluster.
+    fn finished_ray_job(job_status: &str) -> serde_json::Value {
+        serde_json::json!({
+            "metadata": { "name": "ray-job-abc", "namespace": "launch" },
+            "status": {
+                "jobId": "ray-job-abc-x7k2p",
+                "jobStatus": job_status,
+                "jobDeploymentStatus": "Complete",
+                "message": "Job finished successfully.",
+                "rayClusterName": "ray-job-abc-raycluster-lq9vd",
+                "rayClusterStatus": { "state": "ready" },
+            }
+        })
+    }
+
+    #[test]
+    fn finished_before_logs_detects_deleted_submitter() {
+        // The logs could not be followed for another reason while the Pod exists.
+        let succeeded = finished_ray_job("SUCCEEDED");
+        assert_eq!(finished_before_logs(true, Some(&succeeded)), None);
+
+        // The Pod is gone because the RayJob finished.
+        assert_eq!(
+            finished_before_logs(false, Some(&succeeded)),
+            Some(FinishedRayJob {
+                job_status: "SUCCEEDED",
+                message: Some("Job finished successfully."),
+                ray_cluster_name: Some("ray-job-abc-raycluster-lq9vd"),
+                submission_id: Some("ray-job-abc-x7k2p"),
+            })
         );
 
-        let pod_name = {
-            let mut pod_names = kubectl.get_pods_for_job(&job_namespace, &job_name)?;
-            for pod_name in &pod_names {
-                info!(
-                    "Created submitter Pod {:?}.",
-                    format!("{headlamp_url}/c/main/pods/{job_namespace}/{pod_name}")
-                );
+        // kuberay has also deleted the RayCluster.
+        let mut deleted_cluster = finished_ray_job("FAILED");
+        deleted_cluster["status"]["rayClusterName"] = "".into();
+        assert_eq!(
+            finished_before_logs(false, Some(&deleted_cluster)),
+            Some(FinishedRayJob {
+                job_status: "FAILED",
+                message: Some("Job finished successfully."),
+                ray_cluster_name: None,
+                submission_id: Some("ray-job-abc-x7k2p"),
+            })
+        );
+
+        // A running RayJob or a deleted RayJob do not explain the missing Pod.
+        assert_eq!(
+            finished_before_logs(false, Some(&finished_ray_job("RUNNING"))),
+            None
+        );
+        assert_eq!(finished_before_logs(false, None), None);
+    }
+
+    #[test]
+    fn driver_log_path_works() {
+        assert_eq!(
+            driver_log_path("ray-job-abc-x7k2p"),
+            "/tmp/ray/session_latest/logs/job-driver-ray-job-abc-x7k2p.log"
+        );
+    }
+
+    #[test]
+    fn ray_job_spec_sets_ray_options() {
+        let worker_pod_spec = |spec: &serde_json::Value| {
+            spec["spec"]["rayClusterSpec"]["workerGroupSpecs"][0]["template"]["spec"].clone()
+        };
+        let default = spec(None, None, None);
+        assert_eq!(default["spec"]["rayClusterSpec"].get("rayVersion"), None);
+        assert_eq!(default["spec"].get("runtimeEnvYAML"), None);
+        assert_eq!(worker_pod_spec(&default).get("restartPolicy"), None);
+
+        let runtime_env = "pip:\n  - torch==2.5.1\nenv_vars:\n  WANDB_MODE: offline\n";
+        let configured = spec(
+            Some("2.40.0"),
+            Some(runtime_env),
+            Some(RestartPolicy::OnFailure),
+        );
+        assert_eq!(configured["spec"]["rayClusterSpec"]["rayVersion"], "2.40.0");
+        assert_eq!(configured["spec"]["runtimeEnvYAML"], runtime_env);
+        assert_eq!(worker_pod_spec(&configured)["restartPolicy"], "OnFailure");
+        // The head and the submitter keep their restart policies.
+        assert_eq!(
+            configured["spec"]["rayClusterSpec"]["headGroupSpec"]["template"]["spec"]
+                .get("restartPolicy"),
+            None
+        );
+        assert_eq!(
+            configured["spec"]["submitterPodTemplate"]["spec"]["restartPolicy"],
+            "Never"
+        );
+    }
+
+    #[test]
+    fn ray_job_spec_sets_run_id() {
+        let spec = spec(None, None, None);
+        assert_eq!(
+            spec["metadata"]["annotations"][kubectl::annotation::RUN_ID],
+            RUN_ID
+        );
+        assert_eq!(spec["metadata"]["labels"][kubectl::label::RUN_ID], RUN_ID);
+
+        let templates = [
+            &spec["spec"]["rayClusterSpec"]["headGroupSpec"]["template"],
+            &spec["spec"]["rayClusterSpec"]["workerGroupSpecs"][0]["template"],
+            &spec["spec"]["submitterPodTemplate"],
+        ];
+        for template in templates {
+            assert_eq!(
+                template["metadata"]["annotations"][kubectl::annotation::RUN_ID],
+                RUN_ID
+            );
+            assert_eq!(
+                template["metadata"]["labels"][kubectl::label::RUN_ID],
+                RUN_ID
+            );
+            let env = template["spec"]["containers"][0]["env"].as_array().unwrap();
+            assert!(env
+                .iter()
+                .any(|var| var["name"] == RUN_ID_ENV && var["value"] == RUN_ID));
+        }
+    }
+
+    #[test]
+    fn validate_runtime_env_works() {
+        assert_eq!(
+            validate_runtime_env("pip:\n  - torch==2.5.1\nenv_vars:\n  WANDB_MODE: offline\n"),
+            Ok(())
+        );
+        assert_eq!(
+            validate_runtime_env("- torch==2.5.1\n"),
+            Err("expected a mapping with fields such as `pip` and `env_vars`".to_owned())
+        );
+        assert!(validate_runtime_env("").is_err());
+        assert!(validate_runtime_env("pip: [torch\n").is_err());
+    }
+
+    fn pod(status: serde_json::Value) -> kubectl::Pod {
+        serde_json::from_value(serde_json::json!({
+            "metadata": {
+                "name": "ray-job-abc-raycluster-lq9vd-head-x2x4z",
+                "namespace": "launch",
+                "creationTimestamp": "2025-01-13T10:20:31Z",
+                "labels": {
+                    "ray.io/cluster": "ray-job-abc-raycluster-lq9vd",
+                    "ray.io/node-type": "head",
+                },
+            },
+            "status": status,
+        }))
+        .unwrap()
+    }
+
+    fn unschedulable_pod() -> kubectl::Pod {
+        pod(serde_json::json!({
+            "conditions": [
+                {
+                    "message": "0/4 nodes are available: 4 Insufficient nvidia.com/gpu.",
+                    "reason": "Unschedulable",
+                    "status": "False",
+                    "type": "PodScheduled"
+                }
+            ],
+            "phase": "Pending"
+        }))
+    }
+
+    fn image_pull_back_off_pod() -> kubectl::Pod {
+        pod(serde_json::json!({
+            "containerStatuses": [
+                {
+                    "image": "berkeley-docker.taila1eba.ts.net/launch:abc",
+                    "imageID": "",
+                    "name": "ray-head",
+                    "state": {
+                        "waiting": {
+                            "message": "Back-off pulling image",
+                            "reason": "ImagePullBackOff"
+                        }
+                    }
+                }
+            ],
+            "phase": "Pending"
+        }))
+    }
+
+    fn pending_pod() -> kubectl::Pod {
+        pod(serde_json::json!({ "phase": "Pending" }))
+    }
+
+    #[test]
+    fn head_pod_problem_works() {
+        assert_eq!(
+            head_pod_problem(&unschedulable_pod().status).as_deref(),
+            Some("is unschedulable: 0/4 nodes are available: 4 Insufficient nvidia.com/gpu.")
+        );
+        assert_eq!(
+            head_pod_problem(&image_pull_back_off_pod().status).as_deref(),
+            Some("can not pull the image of container \"ray-head\": Back-off pulling image")
+        );
+        assert_eq!(head_pod_problem(&pending_pod().status), None);
+    }
+
+    #[test]
+    fn creation_wait_gives_up_on_stuck_head_pod() {
+        let timeout = Duration::from_secs(120);
+        let mut wait = CreationWait::new(timeout);
+        let start = Instant::now();
+        let head_pod = unschedulable_pod();
+        let problem = head_pod_problem(&head_pod.status);
+
+        // The RayCluster has not been created yet.
+        assert_eq!(
+            wait.observe(start, false, None),
+            CreationProgress::Waiting {
+                head_pod_problem: None
             }
-            let pod_name = pod_names.pop().ok_or("No pods created for job")?;
-            if pod_names.len() > 1 {
-                warn!(
-                    "Following logs only for Pod {:?} and ignoring the others.",
-                    format!("{headlamp_url}/c/main/pods/{job_namespace}/{pod_name}")
-                );
+        );
+        assert_eq!(
+            wait.observe(start, false, Some(&head_pod)),
+            CreationProgress::Waiting {
+                head_pod_problem: problem.clone()
+            }
+        );
+        assert_eq!(
+            wait.observe(start + timeout / 2, false, Some(&head_pod)),
+            CreationProgress::Waiting {
+                head_pod_problem: problem.clone()
+            }
+        );
+        assert_eq!(
+            wait.observe(start + timeout, false, Some(&head_pod)),
+            CreationProgress::HeadPodStuck {
+                pod_name: head_pod.metadata.name.clone(),
+                problem: problem.unwrap(),
+            }
+        );
+    }
+
+    #[test]
+    fn creation_wait_resets_when_head_pod_recovers() {
+        let timeout = Duration::from_secs(120);
+        let mut wait = CreationWait::new(timeout);
+        let start = Instant::now();
+
+        assert!(matches!(
+            wait.observe(start, false, Some(&image_pull_back_off_pod())),
+            CreationProgress::Waiting {
+                head_pod_problem: Some(_)
             }
-            pod_name
+        ));
+        assert_eq!(
+            wait.observe(start + timeout / 2, false, Some(&pending_pod())),
+            CreationProgress::Waiting {
+                head_pod_problem: None
+            }
+        );
+        // The stuck time starts over, so this is not yet beyond the timeout.
+        assert!(matches!(
+            wait.observe(start + timeout, false, Some(&image_pull_back_off_pod())),
+            CreationProgress::Waiting {
+                head_pod_problem: Some(_)
+            }
+        ));
+        assert_eq!(
+            wait.observe(start + timeout * 2, true, Some(&image_pull_back_off_pod())),
+            CreationProgress::SubmitterCreated
+        );
+    }
+
+    fn handle() -> ResourceHandle {
+        ResourceHandle {
+            namespace: "launch".to_owned(),
+            name: "ray-job-abc".to_owned(),
+        }
+    }
+
+    fn running_ray_job() -> serde_json::Value {
+        serde_json::json!({
+            "metadata": { "name": "ray-job-abc", "namespace": "launch" },
+            "status": {
+                "jobId": "ray-job-abc-x7k2p",
+                "jobStatus": "RUNNING",
+                "jobDeploymentStatus": "Running",
+                "rayClusterName": "ray-job-abc-raycluster-lq9vd",
+            }
+        })
+    }
+
+    #[test]
+    fn monitor_ray_job_waits_for_the_submitter() {
+        let cluster = Fixture {
+            jobs_exist: Fixture::sequence([false, false, true]),
+            ray_jobs: Fixture::sequence([Some(running_ray_job())]),
+            head_pods: Fixture::sequence([Some(unschedulable_pod as fn() -> _), Some(pending_pod)]),
+            pod_names: vec!["ray-job-abc-q9w4z".to_owned()],
+            ..Default::default()
         };
+        monitor_ray_job(&ClusterContext::Berkeley, &cluster, &handle(), false).unwrap();
+        assert_eq!(cluster.sleeps.get(), 2);
+        assert_eq!(*cluster.followed.borrow(), ["ray-job-abc-q9w4z"]);
+        assert!(cluster.followed_lines.borrow().is_empty());
+        // The latencies are only recorded for Jobs.
+        assert!(cluster.latencies.borrow().is_empty());
+    }
+
+    #[test]
+    fn monitor_ray_job_dedupes_the_logs_when_asked() {
+        let cluster = Fixture {
+            jobs_exist: Fixture::sequence([true]),
+            ray_jobs: Fixture::sequence([Some(running_ray_job())]),
+            pod_names: vec!["ray-job-abc-q9w4z".to_owned()],
+            log_lines: vec![
+                "(RayTrainWorker pid=101, ip=10.0.0.5) epoch 1".to_owned(),
+                "(RayTrainWorker pid=102, ip=10.0.0.6) epoch 1".to_owned(),
+            ],
+            ..Default::default()
+        };
+        let mut printed = Vec::new();
+        monitor_ray_job_with_output(
+            &ClusterContext::Berkeley,
+            &cluster,
+            &handle(),
+            true,
+            |line| printed.push(line.to_string()),
+        )
+        .unwrap();
+        assert_eq!(*cluster.followed_lines.borrow(), ["ray-job-abc-q9w4z"]);
+        assert_eq!(
+            printed,
+            ["(RayTrainWorker pid=101, ip=10.0.0.5) epoch 1 (×2 workers)"]
+        );
+    }
 
-        common::wait_for_and_follow_pod_logs(&kubectl, &job_namespace, &pod_name)?;
+    #[test]
+    fn monitor_ray_job_recovers_a_finished_ray_job() {
+        // The submitter Job of a RayJob that finished before attaching has been deleted.
+        let cluster = Fixture {
+            jobs_exist: Fixture::sequence([false]),
+            ray_jobs: Fixture::sequence([Some(finished_ray_job("SUCCEEDED"))]),
+            head_pods: Fixture::sequence([Some(pending_pod as fn() -> _)]),
+            files: vec![(
+                driver_log_path("ray-job-abc-x7k2p"),
+                "training done\n".to_owned(),
+            )],
+            ..Default::default()
+        };
+        monitor_ray_job(&ClusterContext::Berkeley, &cluster, &handle(), false).unwrap();
+        assert_eq!(cluster.sleeps.get(), 0);
+        assert!(cluster.followed.borrow().is_empty());
+    }
+
+    #[test]
+    fn monitor_ray_job_recovers_when_the_submitter_pod_disappears() {
+        let cluster = |ray_job: serde_json::Value| Fixture {
+            jobs_exist: Fixture::sequence([true]),
+            ray_jobs: Fixture::sequence([Some(ray_job)]),
+            head_pods: Fixture::sequence([None]),
+            pod_names: vec!["ray-job-abc-q9w4z".to_owned()],
+            logs_fail: true,
+            ..Default::default()
+        };
+
+        let finished = cluster(finished_ray_job("FAILED"));
+        let error =
+            monitor_ray_job(&ClusterContext::Berkeley, &finished, &handle(), false).unwrap_err();
+        assert!(
+            error.to_string().contains("finished with status FAILED"),
+            "{error}"
+        );
+        assert_eq!(*finished.followed.borrow(), ["ray-job-abc-q9w4z"]);
 
-        Ok(ExecutionOutput {})
+        // The error of following the logs remains when the RayJob did not finish.
+        let running = cluster(running_ray_job());
+        let error =
+            monitor_ray_job(&ClusterContext::Berkeley, &running, &handle(), false).unwrap_err();
+        assert!(error.to_string().contains("not found"), "{error}");
     }
 }
@@ -94,6 +94,31 @@ pub struct ExperimentSpec {
     pub max_failed_trial_count: u16,
     #[serde(deserialize_with = "deserialize_parameters")]
     pub parameters: Vec<Parameter>,
+    /// Environment variables to set in each trial. They take precedence over the environment variables that launch
+    /// sets, such as `MLFLOW_TRACKING_URI`.
+    #[serde(default, deserialize_with = "deserialize_trial_env")]
+    pub trial_env: Vec<EnvVar>,
+    /// Whether to mount the databricks configuration in each trial. Overrides `--databrickscfg-mode` for the trials
+    /// when set.
+    #[serde(default)]
+    pub mount_databricks_cfg: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnvVar {
+    pub name: String,
+    pub value: String,
+}
+
+/// Kubernetes accepts environment variable names consisting of alphanumeric characters, '-', '_' and '.' that do not
+/// start with a digit.
+pub fn is_env_var_name(value: &str) -> bool {
+    let mut chars = value.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || matches!(c, '-' | '_' | '.'))
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
 }
 
 fn default_max_failed_trial_count() -> u16 {
@@ -113,6 +138,29 @@ where
     Ok(vec)
 }
 
+fn deserialize_trial_env<'de, D>(deserializer: D) -> Result<Vec<EnvVar>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::{de::Error, Deserialize};
+    let vec = Vec::<EnvVar>::deserialize(deserializer)?;
+    for (index, var) in vec.iter().enumerate() {
+        if !is_env_var_name(&var.name) {
+            return Err(Error::custom(format!(
+                "trialEnv name {:?} is not a valid environment variable name",
+                var.name
+            )));
+        }
+        if vec[..index].iter().any(|other| other.name == var.name) {
+            return Err(Error::custom(format!(
+                "trialEnv name {:?} is specified more than once",
+                var.name
+            )));
+        }
+    }
+    Ok(vec)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -182,4 +230,93 @@ parameters: []
             .to_string()
             .contains("parameters must not be empty"));
     }
+
+    const MINIMAL_SPEC: &str = r#"
+objective:
+  type: maximize
+  objectiveMetricName: metric
+algorithm:
+  algorithmName: random
+parallelTrialCount: 1
+maxTrialCount: 1
+parameters:
+  - name: lr
+    parameterType: double
+    feasibleSpace:
+      min: 0.01
+      max: 1.0
+"#;
+
+    #[test]
+    fn test_trial_env_and_mount_databricks_cfg() {
+        let spec = serde_yaml::from_str::<ExperimentSpec>(MINIMAL_SPEC).unwrap();
+        assert!(spec.trial_env.is_empty());
+        assert_eq!(spec.mount_databricks_cfg, None);
+
+        let yaml = format!(
+            r#"{MINIMAL_SPEC}
+trialEnv:
+  - name: MLFLOW_TRACKING_URI
+    value: http://mlflow.mlflow.svc.cluster.local
+  - name: SWEEP
+    value: "1"
+mountDatabricksCfg: false
+"#
+        );
+        let spec = serde_yaml::from_str::<ExperimentSpec>(&yaml).unwrap();
+        assert_eq!(
+            spec.trial_env,
+            [
+                EnvVar {
+                    name: "MLFLOW_TRACKING_URI".to_owned(),
+                    value: "http://mlflow.mlflow.svc.cluster.local".to_owned(),
+                },
+                EnvVar {
+                    name: "SWEEP".to_owned(),
+                    value: "1".to_owned(),
+                },
+            ]
+        );
+        assert_eq!(spec.mount_databricks_cfg, Some(false));
+
+        let round_tripped =
+            serde_yaml::from_str::<Vec<EnvVar>>(&serde_yaml::to_string(&spec.trial_env).unwrap())
+                .unwrap();
+        assert_eq!(round_tripped, spec.trial_env);
+    }
+
+    #[test]
+    fn test_invalid_trial_env() {
+        for (trial_env, error) in [
+            (
+                "[{name: 1FOO, value: x}]",
+                "\"1FOO\" is not a valid environment variable name",
+            ),
+            (
+                "[{name: 'FOO=BAR', value: x}]",
+                "\"FOO=BAR\" is not a valid environment variable name",
+            ),
+            ("[{name: '', value: x}]", "\"\" is not a valid"),
+            (
+                "[{name: FOO, value: x}, {name: FOO, value: y}]",
+                "\"FOO\" is specified more than once",
+            ),
+            ("[{name: FOO}]", "missing field `value`"),
+        ] {
+            let yaml = format!("{MINIMAL_SPEC}\ntrialEnv: {trial_env}\n");
+            let result = serde_yaml::from_str::<ExperimentSpec>(&yaml);
+            let message = result.unwrap_err().to_string();
+            assert!(message.contains(error), "{message}");
+        }
+    }
+
+    #[test]
+    fn test_is_env_var_name() {
+        assert!(is_env_var_name("MLFLOW_TRACKING_URI"));
+        assert!(is_env_var_name("_private"));
+        assert!(is_env_var_name("my.var-1"));
+        assert!(!is_env_var_name(""));
+        assert!(!is_env_var_name("1VAR"));
+        assert!(!is_env_var_name("A B"));
+    }
 }
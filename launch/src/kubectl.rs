@@ -1,4 +1,7 @@
-use std::path::Path;
+use std::{ffi::OsStr, io, path::Path, sync::OnceLock};
+
+use constcat::concat;
+use log::debug;
 
 use crate::{process, Result};
 
@@ -17,38 +20,264 @@ pub use ray_job::*;
 mod job;
 pub use job::*;
 
+mod cron_job;
+pub use cron_job::*;
+
 mod common;
 pub use common::*;
 
+mod quantity;
+pub use quantity::*;
+
+mod top;
+pub use top::*;
+
+mod version;
+pub use version::*;
+
+mod port_forward;
+pub use port_forward::*;
+
+mod event;
+pub use event::*;
+
+mod config_map;
+pub use config_map::*;
+
+mod create;
+
+#[cfg(all(test, unix))]
+pub mod fake;
+
+/// The tailscale operator authenticates requests through the tailnet identity of the caller and ignores the bearer token.
+/// kubectl still needs some credentials to not prompt for them.
+const TOKEN: &str = "unused";
+
+/// Points kubectl at an empty kubeconfig, see [`Kubectl::kubectl`]. Windows has no `/dev/null` and calls its null device
+/// `NUL` instead.
+const EMPTY_KUBECONFIG: &str = if cfg!(windows) {
+    "--kubeconfig=NUL"
+} else {
+    "--kubeconfig=/dev/null"
+};
+
+/// The environment variable with extra arguments for kubectl, separated by whitespace, see [`set_extra_args`].
+pub const EXTRA_ARGS_ENV: &str = "LAUNCH_KUBECTL_ARGS";
+
+/// The flags that select the cluster, the credentials and the namespace. Launch sets them itself, and passing them again
+/// would talk to another cluster or namespace than launch thinks or bypass the tailscale operator.
+const DENIED_EXTRA_ARGS: &[&str] = &[
+    "--kubeconfig",
+    "--server",
+    "-s",
+    "--token",
+    "--context",
+    "--cluster",
+    "--user",
+    "--namespace",
+    "-n",
+];
+
+static EXTRA_ARGS: OnceLock<Vec<String>> = OnceLock::new();
+
+#[cfg(all(test, unix))]
+use fake::command as program;
+
+/// Returns the command that runs kubectl, which tests replace with [`fake::FakeKubectl`].
+#[cfg(not(all(test, unix)))]
+fn program() -> process::Command {
+    process::Command::new("kubectl")
+}
+
+/// Returns the denied flag that `arg` sets, either as `--flag`, `--flag=value` or, for short flags, `-fvalue`.
+fn denied_flag(arg: &str) -> Option<&'static str> {
+    let flag = arg.split_once('=').map_or(arg, |(flag, _)| flag);
+    DENIED_EXTRA_ARGS
+        .iter()
+        .copied()
+        .find(|denied| *denied == flag || (!denied.starts_with("--") && arg.starts_with(denied)))
+}
+
+/// Fails when one of the arguments sets a flag that launch relies on, see [`DENIED_EXTRA_ARGS`].
+fn check_extra_args(args: &[String]) -> Result<()> {
+    match args.iter().find_map(|arg| denied_flag(arg)) {
+        Some(flag) => Err(format!(
+            "`--kubectl-arg {flag}` is not allowed, launch sets it to select the cluster, its credentials and the namespace"
+        )
+        .into()),
+        None => Ok(()),
+    }
+}
+
+/// Appends `args` to every kubectl invocation of the process, such as `--v=8` to see the API traffic. Must be called at
+/// most once, before the first invocation.
+pub fn set_extra_args(args: Vec<String>) -> Result<()> {
+    check_extra_args(&args)?;
+    if !args.is_empty() {
+        debug!("Passing extra arguments to kubectl: {args:?}");
+    }
+    EXTRA_ARGS
+        .set(args)
+        .map_err(|_| "The extra arguments for kubectl were already set".into())
+}
+
+/// How kubectl reaches the cluster.
+enum Target<'a> {
+    /// The API server behind the tailscale operator, ignoring the kubeconfig of the user.
+    Server(&'a str),
+    /// The kubeconfig of the user, with its current context or the given one.
+    Kubeconfig { context: Option<String> },
+}
+
 pub struct Kubectl<'a> {
-    server: &'a str,
+    target: Target<'a>,
 }
 
 impl<'a> Kubectl<'a> {
     pub fn new(server: &'a str) -> Self {
-        Self { server }
+        Self {
+            target: Target::Server(server),
+        }
     }
 
-    /// Returns the kubectl command where authentication arguments have already been set.
-    fn kubectl(&self) -> process::Command {
-        process::command!(
-            "kubectl",
+    /// Talks to the cluster of the kubeconfig of the user (`KUBECONFIG` or `~/.kube/config`) with its own credentials,
+    /// such as a kind or minikube cluster. Uses the current context of the kubeconfig unless `context` is given.
+    pub fn from_kubeconfig(context: Option<String>) -> Self {
+        Self {
+            target: Target::Kubeconfig { context },
+        }
+    }
+
+    /// Returns the arguments that select the cluster and authenticate with it.
+    fn connection_args(&self) -> Vec<&str> {
+        match &self.target {
             // Despite passing `--server` and `--token`, kubectl will still load the kubeconfig if
             // present. By setting `--kubeconfig` to an empty file, we can make sure no other
             // options apply.
-            "--kubeconfig=/dev/null", // Does not work on Windows but Windows users develop inside WSL.
-            "--server",
-            self.server,
-            "--token=unused",
-        )
+            Target::Server(server) => vec![
+                EMPTY_KUBECONFIG,
+                "--server",
+                server,
+                concat!("--token=", TOKEN),
+            ],
+            Target::Kubeconfig { context } => match context {
+                Some(context) => vec!["--context", context],
+                None => Vec::new(),
+            },
+        }
+    }
+
+    /// Returns the kubectl command where authentication arguments and the arguments of [`set_extra_args`] have already
+    /// been set.
+    fn kubectl(&self) -> process::Command {
+        program()
+            .args(self.connection_args().into_iter().map(OsStr::new))
+            .args(EXTRA_ARGS.get().into_iter().flatten().map(OsStr::new))
     }
 
+    /// Fails with a clear message when the kubeconfig does not have the context that [`Kubectl::from_kubeconfig`] was
+    /// given, or has no current context. Does nothing for the other clusters.
+    pub fn check_kubeconfig_context(&self) -> Result<()> {
+        let Target::Kubeconfig { context } = &self.target else {
+            return Ok(());
+        };
+        let output = match context {
+            Some(context) => process::command!("kubectl", "config", "get-contexts", context),
+            None => process::command!("kubectl", "config", "current-context"),
+        }
+        .try_output()?;
+        if output.status.success() {
+            return Ok(());
+        }
+        Err(match context {
+            Some(context) => format!(
+                "Your kubeconfig has no context {context:?}, see `kubectl config get-contexts`"
+            ),
+            None => "Your kubeconfig has no current context, set one with `kubectl config use-context` or set LAUNCH_KUBE_CONTEXT".to_owned(),
+        }
+        .into())
+    }
+
+    /// Returns a kubeconfig that authenticates the same way as [`Self::kubectl`], with a single context named `name`
+    /// that defaults to `namespace`. Fails for [`Kubectl::from_kubeconfig`], which uses a kubeconfig already.
+    pub fn kubeconfig(&self, name: &str, namespace: &str) -> Result<serde_json::Value> {
+        let Target::Server(server) = self.target else {
+            return Err("This context uses your kubeconfig already".into());
+        };
+        Ok(serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "Config",
+            "clusters": [{
+                "name": name,
+                "cluster": {
+                    "server": server,
+                },
+            }],
+            "users": [{
+                "name": name,
+                "user": {
+                    "token": TOKEN,
+                },
+            }],
+            "contexts": [{
+                "name": name,
+                "context": {
+                    "cluster": name,
+                    "user": name,
+                    "namespace": namespace,
+                },
+            }],
+            "current-context": name,
+        }))
+    }
+
+    /// Replaces the secret with one that holds the file. When kubectl supports `--server-side` apply, the secret is
+    /// replaced in one request, otherwise it is deleted and created again.
     pub fn recreate_secret_from_file(
         &self,
         namespace: &str,
         name: &str,
         path: &Path,
     ) -> Result<()> {
+        self.recreate_secret_with(capabilities(), namespace, name, path)
+    }
+
+    fn recreate_secret_with(
+        &self,
+        capabilities: Capabilities,
+        namespace: &str,
+        name: &str,
+        path: &Path,
+    ) -> Result<()> {
+        if capabilities.server_side_apply {
+            let secret = process::args!(
+                self.kubectl(),
+                "create",
+                "secret",
+                "generic",
+                "--from-file",
+                path,
+                "--namespace",
+                namespace,
+                name,
+                "--dry-run=client",
+                "--output=json",
+            )
+            .output()?;
+            process::args!(
+                self.kubectl(),
+                "apply",
+                "--server-side",
+                "--force-conflicts",
+                "--field-manager=launch",
+                "-f",
+                "-",
+            )
+            .output_with_input(secret.output.stdout)?
+            .require_success()?;
+            return Ok(());
+        }
+
         process::args!(
             self.kubectl(),
             "delete",
@@ -84,11 +313,64 @@ impl<'a> Kubectl<'a> {
         Ok(serde_json::from_slice::<GetResource<_>>(&output.stdout)?.items)
     }
 
+    pub fn node(&self, name: &str) -> Result<Node> {
+        let output =
+            process::args!(self.kubectl(), "get", "node", name, "--output=json").output()?;
+
+        Ok(serde_json::from_slice(&output.stdout)?)
+    }
+
+    /// Sets the annotations on the resource, overwriting existing values.
+    pub fn annotate(
+        &self,
+        resource: &str,
+        namespace: &str,
+        name: &str,
+        annotations: &[(&str, String)],
+    ) -> Result<()> {
+        let annotations = annotations
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>();
+        process::args!(
+            self.kubectl(),
+            "annotate",
+            "--overwrite",
+            "--namespace",
+            namespace,
+            resource,
+            name,
+        )
+        .args(annotations.iter().map(OsStr::new))
+        .output()?;
+        Ok(())
+    }
+
     /// The input is written to stdin and should be a [YAML or JSON formatted kubernetes
     /// configuration](https://kubernetes.io/docs/tasks/manage-kubernetes-objects/imperative-config/).
     pub fn create(&self, input: &str) -> Result<ResourceHandle> {
-        let output = process::args!(self.kubectl(), "create", "--output=json", "-f", "-")
-            .output_with_input(input.as_bytes().to_owned())?;
+        self.try_create(input).map_err(|failure| failure.error)
+    }
+
+    /// Creates the resource like [`Kubectl::create`], but at most once. The input should be a single resource formatted as
+    /// JSON. The run ID is stamped on it as [`label::RUN_ID`], so that the resource can be looked up and adopted instead
+    /// of being created again when kubectl fails after the request may have reached the server.
+    pub fn create_with_run_id(&self, input: &str, run_id: &str) -> Result<ResourceHandle> {
+        let mut resource: serde_json::Value = serde_json::from_str(input)?;
+        create::stamp_run_id(&mut resource, run_id);
+        let input = resource.to_string();
+        create::create_idempotently(
+            || self.try_create(&input),
+            || self.find_by_run_id(&resource, run_id),
+        )
+    }
+
+    fn try_create(&self, input: &str) -> Result<ResourceHandle, create::Failure> {
+        let output = self
+            .kubectl()
+            .args(create_args(None).into_iter().map(OsStr::new))
+            .output_with_input(input.as_bytes().to_owned())
+            .map_err(create::Failure::certain)?;
 
         // The following should probably be integrated with a custom error type, but useful and good enough for now.
         if log::log_enabled!(log::Level::Error) && !output.status.success() {
@@ -100,24 +382,55 @@ impl<'a> Kubectl<'a> {
             }
         }
 
-        let output = output.require_success()?;
+        let ambiguous = !output.status.success()
+            && create::is_ambiguous_failure(&String::from_utf8_lossy(&output.stderr));
+        let output = output.require_success().map_err(|error| create::Failure {
+            error: error.into(),
+            ambiguous,
+        })?;
 
-        let root: CreateJobRoot = serde_json::from_slice(&output.stdout)?;
+        let root: CreateJobRoot =
+            serde_json::from_slice(&output.stdout).map_err(create::Failure::certain)?;
 
-        Ok(ResourceHandle {
-            namespace: root.metadata.namespace,
-            name: root.metadata.name,
-        })
+        Ok(root.into())
     }
 
-    pub fn try_get_job(&self, namespace: &str, job_name: &str) -> Result<Option<Job>> {
+    /// Returns the resources of the same type and namespace as `resource` that carry the run ID.
+    fn find_by_run_id(
+        &self,
+        resource: &serde_json::Value,
+        run_id: &str,
+    ) -> Result<Vec<ResourceHandle>> {
+        let resource_type = create::resource_type(resource).ok_or("The resource has no type")?;
+        let namespace = resource["metadata"]["namespace"]
+            .as_str()
+            .ok_or("The resource has no namespace")?;
+        let roots: Vec<CreateJobRoot> = collect_items(process::args!(
+            self.kubectl(),
+            "get",
+            resource_type,
+            "--namespace",
+            namespace,
+            format!("--selector={}={run_id}", label::RUN_ID),
+            "--output=json"
+        ))?;
+        Ok(roots.into_iter().map(Into::into).collect())
+    }
+
+    /// Returns `Ok(None)` when the resource does not exist.
+    fn try_get<T: serde::de::DeserializeOwned>(
+        &self,
+        resource: &str,
+        namespace: &str,
+        name: &str,
+    ) -> Result<Option<T>> {
         let output = process::args!(
             self.kubectl(),
             "get",
-            "job",
+            resource,
             "--namespace",
             namespace,
-            job_name,
+            name,
             "--output=json"
         )
         .try_output()?;
@@ -131,21 +444,118 @@ impl<'a> Kubectl<'a> {
         } else {
             Err(process::Error {
                 command,
-                kind: process::ErrorKind::NonZeroExitStatus(
-                    output.status.code().and_then(std::num::NonZeroI32::new),
-                ),
+                kind: process::ErrorKind::Failed {
+                    code: output.status.code().and_then(std::num::NonZeroI32::new),
+                    stderr: output.stderr,
+                },
             }
             .into())
         }
     }
 
+    /// Submits the input like [`Kubectl::create`] with `--dry-run=server`, which runs it through validation and admission
+    /// control without persisting anything.
+    pub fn create_dry_run(&self, input: &str) -> Result<DryRunVerdict> {
+        let output = self
+            .kubectl()
+            .args(
+                create_args(Some(DryRun::Server))
+                    .into_iter()
+                    .map(OsStr::new),
+            )
+            .output_with_input(input.as_bytes().to_owned())?;
+
+        if output.status.success() {
+            let root: CreateJobRoot = serde_json::from_slice(&output.stdout)?;
+            Ok(DryRunVerdict::Accepted(root.into()))
+        } else {
+            Ok(DryRunVerdict::Rejected(
+                String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+            ))
+        }
+    }
+
+    /// Returns the input as the server would create it, with the defaults of the server and of its admission webhooks
+    /// filled in, by submitting it with `--dry-run=server`.
+    pub fn with_server_defaults(&self, input: &str) -> Result<serde_json::Value> {
+        let process::Output { command, output } = self
+            .kubectl()
+            .args(
+                create_args(Some(DryRun::Server))
+                    .into_iter()
+                    .map(OsStr::new),
+            )
+            .output_with_input(input.as_bytes().to_owned())?;
+
+        if output.status.success() {
+            Ok(serde_json::from_slice(&output.stdout)?)
+        } else {
+            Err(process::Error {
+                command,
+                kind: process::ErrorKind::Failed {
+                    code: output.status.code().and_then(std::num::NonZeroI32::new),
+                    stderr: output.stderr,
+                },
+            }
+            .into())
+        }
+    }
+
+    /// Returns the resource of type `resource`, such as `job`, as JSON or `Ok(None)` when it does not exist.
+    pub fn try_get_json(
+        &self,
+        resource: &str,
+        namespace: &str,
+        name: &str,
+    ) -> Result<Option<serde_json::Value>> {
+        self.try_get(resource, namespace, name)
+    }
+
+    pub fn try_get_config_map(&self, namespace: &str, name: &str) -> Result<Option<ConfigMap>> {
+        self.try_get("configmap", namespace, name)
+    }
+
+    pub fn try_get_job(&self, namespace: &str, job_name: &str) -> Result<Option<Job>> {
+        self.try_get("job", namespace, job_name)
+    }
+
+    /// Returns all Pods in the namespace. Use [`Self::pods_for_owner`] when only the Pods of a single resource are
+    /// needed.
     pub fn pods(&self, namespace: &str) -> Result<Vec<Pod>> {
+        let mut pods = Vec::new();
+        self.for_each_pod(namespace, |pod| pods.push(pod))?;
+        Ok(pods)
+    }
+
+    /// Passes the Pods in the namespace to `on_pod` as they are parsed, so that callers that only keep some of the
+    /// Pods do not hold all of them in memory.
+    pub fn for_each_pod(&self, namespace: &str, on_pod: impl FnMut(Pod)) -> Result<()> {
+        stream_items(
+            process::args!(
+                self.kubectl(),
+                "get",
+                "pods",
+                "--namespace",
+                namespace,
+                "--output=json"
+            ),
+            on_pod,
+        )
+    }
+
+    /// Returns the Pods of the owner, selected by label so that the server only returns those Pods.
+    pub fn pods_for_owner(&self, namespace: &str, owner: PodOwner) -> Result<Vec<Pod>> {
+        self.pods_with_selector(namespace, &owner.selector())
+    }
+
+    pub fn pods_with_selector(&self, namespace: &str, selector: &str) -> Result<Vec<Pod>> {
         let output = process::args!(
             self.kubectl(),
             "get",
             "pods",
             "--namespace",
             namespace,
+            format!("--selector={selector}"),
             "--output=json"
         )
         .output()?;
@@ -153,22 +563,71 @@ impl<'a> Kubectl<'a> {
         Ok(serde_json::from_slice::<GetResource<_>>(&output.stdout)?.items)
     }
 
-    pub fn get_pods_for_job(&self, namespace: &str, job_name: &str) -> Result<Vec<String>> {
+    /// Returns the events that are still retained for the Pod, oldest first.
+    pub fn pod_events(&self, namespace: &str, pod_name: &str) -> Result<Vec<Event>> {
         let output = process::args!(
+            self.kubectl(),
+            "get",
+            "events",
+            "--namespace",
+            namespace,
+            format!("--field-selector=involvedObject.kind=Pod,involvedObject.name={pod_name}"),
+            "--sort-by=.lastTimestamp",
+            "--output=json"
+        )
+        .output()?;
+
+        Ok(serde_json::from_slice::<GetResource<_>>(&output.stdout)?.items)
+    }
+
+    /// Returns whether a resource of a type like `secret` exists.
+    pub fn exists(&self, resource: &str, namespace: &str, name: &str) -> Result<bool> {
+        Ok(self
+            .try_get::<serde_json::Value>(resource, namespace, name)?
+            .is_some())
+    }
+
+    pub fn pods_in_all_namespaces(&self) -> Result<Vec<Pod>> {
+        collect_items(process::args!(
             self.kubectl(),
             "get",
             "pods",
+            "--all-namespaces",
+            "--output=json"
+        ))
+    }
+
+    /// Returns the resources of one or more comma separated types, such as `jobs,rayjobs`, in all namespaces as a JSON
+    /// list.
+    pub fn list_all_namespaces(&self, resources: &str) -> Result<serde_json::Value> {
+        let output = process::args!(
+            self.kubectl(),
+            "get",
+            resources,
+            "--all-namespaces",
+            "--output=json"
+        )
+        .args(list_args(capabilities()).iter().map(OsStr::new))
+        .output()?;
+
+        Ok(serde_json::from_slice(&output.stdout)?)
+    }
+
+    /// Returns the names of all resources of a type like `secrets` in the namespace.
+    pub fn resource_names(&self, resource: &str, namespace: &str) -> Result<Vec<String>> {
+        let output = process::args!(
+            self.kubectl(),
+            "get",
+            resource,
             "--namespace",
             namespace,
-            format!("--selector=job-name={job_name}"),
-            "--output=jsonpath={.items[*].metadata.name}"
+            "--output=name"
         )
         .output()?;
 
-        Ok(std::str::from_utf8(&output.stdout)?
-            .split_whitespace()
-            .map(str::to_string)
-            .collect())
+        Ok(parse_resource_names(&String::from_utf8_lossy(
+            &output.stdout,
+        )))
     }
 
     pub fn follow_pod_logs(&self, namespace: &str, pod_name: &str) -> Result<()> {
@@ -184,6 +643,65 @@ impl<'a> Kubectl<'a> {
         Ok(())
     }
 
+    /// Like [`Kubectl::follow_pod_logs`], but passes each log line to `on_line` instead of printing it.
+    pub fn follow_pod_log_lines(
+        &self,
+        namespace: &str,
+        pod_name: &str,
+        on_line: impl FnMut(&str) -> io::Result<()>,
+    ) -> Result<()> {
+        process::args!(
+            self.kubectl(),
+            "logs",
+            "--namespace",
+            namespace,
+            "-f",
+            pod_name
+        )
+        .status_with_stdout_lines(on_line)?;
+        Ok(())
+    }
+
+    /// Returns the logs of a Pod that has terminated.
+    pub fn pod_logs(&self, namespace: &str, pod_name: &str) -> Result<String> {
+        let output =
+            process::args!(self.kubectl(), "logs", "--namespace", namespace, pod_name).output()?;
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Returns the contents of a file in a container of a running Pod.
+    pub fn read_file(
+        &self,
+        namespace: &str,
+        pod_name: &str,
+        container: &str,
+        path: &str,
+    ) -> Result<String> {
+        let output = process::args!(
+            self.kubectl(),
+            "exec",
+            "--namespace",
+            namespace,
+            pod_name,
+            "--container",
+            container,
+            "--",
+            "cat",
+            path
+        )
+        .output()?;
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Returns the version of the server, or `None` if the server can not be reached. kubectl exits with an error in
+    /// that case but still prints its own version.
+    pub fn server_version(&self) -> Result<Option<String>> {
+        let output = process::args!(self.kubectl(), "version", "--output=json").try_output()?;
+        let versions = parse_versions(&output.output.stdout)?;
+        Ok(versions.server_version.map(|version| version.git_version))
+    }
+
     pub fn pod(&self, namespace: &str, pod_name: &str) -> Result<Pod> {
         let output = process::args!(
             self.kubectl(),
@@ -199,18 +717,45 @@ impl<'a> Kubectl<'a> {
         Ok(serde_json::from_slice(&output.stdout)?)
     }
 
-    pub fn jobs(&self, namespace: &str) -> Result<Vec<Job>> {
+    /// Requires the [metrics server](https://github.com/kubernetes-sigs/metrics-server) to be installed in the cluster.
+    pub fn top_pod(&self, namespace: &str, pod_name: &str) -> Result<Vec<ContainerUsage>> {
         let output = process::args!(
+            self.kubectl(),
+            "top",
+            "pod",
+            "--namespace",
+            namespace,
+            pod_name,
+            "--containers",
+            "--no-headers",
+        )
+        .output()?;
+
+        Ok(parse_top_pod_containers(std::str::from_utf8(
+            &output.stdout,
+        )?))
+    }
+
+    pub fn jobs(&self, namespace: &str) -> Result<Vec<Job>> {
+        collect_items(process::args!(
             self.kubectl(),
             "get",
             "jobs",
             "--namespace",
             namespace,
             "--output=json"
-        )
-        .output()?;
+        ))
+    }
 
-        Ok(serde_json::from_slice::<GetResource<_>>(&output.stdout)?.items)
+    pub fn cron_jobs(&self, namespace: &str) -> Result<Vec<CronJob>> {
+        collect_items(process::args!(
+            self.kubectl(),
+            "get",
+            "cronjobs",
+            "--namespace",
+            namespace,
+            "--output=json"
+        ))
     }
 
     pub fn katib_experiment(
@@ -232,18 +777,65 @@ impl<'a> Kubectl<'a> {
         Ok(serde_json::from_slice(&output.stdout)?)
     }
 
+    pub fn try_get_katib_experiment(
+        &self,
+        namespace: &str,
+        name: &str,
+    ) -> Result<Option<::katib::models::V1beta1Experiment>> {
+        self.try_get("experiment", namespace, name)
+    }
+
+    /// Returns the RayJob as JSON because [`RayJob`] requires fields that a new RayJob does not have yet.
+    pub fn try_get_ray_job(
+        &self,
+        namespace: &str,
+        ray_job_name: &str,
+    ) -> Result<Option<serde_json::Value>> {
+        self.try_get("rayjob", namespace, ray_job_name)
+    }
+
+    /// Forwards a port on 127.0.0.1 to `remote_port` of the Pod until the returned value is dropped.
+    pub fn port_forward(
+        &self,
+        namespace: &str,
+        pod_name: &str,
+        remote_port: u16,
+    ) -> Result<PortForward> {
+        let child = process::args!(
+            self.kubectl(),
+            "port-forward",
+            "--namespace",
+            namespace,
+            format!("pod/{pod_name}"),
+            format!(":{remote_port}"),
+        )
+        .spawn_with_piped_stdout()?;
+        Ok(PortForward::new(child)?)
+    }
+
     pub fn ray_jobs(&self, namespace: &str) -> Result<Vec<RayJob>> {
-        let output = process::args!(
+        collect_items(process::args!(
             self.kubectl(),
             "get",
             "rayjobs",
             "--namespace",
             namespace,
             "--output=json"
+        ))
+    }
+
+    pub fn delete_pod(&self, namespace: &str, pod_name: &str) -> Result<()> {
+        process::args!(
+            self.kubectl(),
+            "--namespace",
+            namespace,
+            "delete",
+            "pod",
+            pod_name,
+            "--wait=false"
         )
         .output()?;
-
-        Ok(serde_json::from_slice::<GetResource<_>>(&output.stdout)?.items)
+        Ok(())
     }
 
     pub fn delete_job(&self, job_name: &str, namespace: &str) -> Result<()> {
@@ -260,6 +852,85 @@ impl<'a> Kubectl<'a> {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DryRun {
+    Server,
+}
+
+impl DryRun {
+    pub fn as_arg(&self) -> &'static str {
+        match self {
+            DryRun::Server => "--dry-run=server",
+        }
+    }
+}
+
+static CLIENT_VERSION: OnceLock<std::result::Result<ClientVersion, String>> = OnceLock::new();
+
+/// Returns the version of kubectl itself, which does not need a cluster. kubectl is only asked once per process.
+pub fn client_version() -> Result<ClientVersion> {
+    CLIENT_VERSION
+        .get_or_init(|| {
+            let query = || -> Result<ClientVersion> {
+                let output = process::command!("kubectl", "version", "--client", "--output=json")
+                    .output()?;
+                ClientVersion::from_versions(parse_versions(&output.stdout)?)
+            };
+            query().map_err(|error| error.to_string())
+        })
+        .clone()
+        .map_err(Into::into)
+}
+
+/// Returns what the kubectl client supports, or that it supports none of the optional flags when its version is
+/// unknown.
+fn capabilities() -> Capabilities {
+    client_version().map_or(Capabilities::of((0, 0)), |version| version.capabilities())
+}
+
+/// The arguments of `kubectl get` for lists that can be large, such as the Pods in all namespaces. Pages keep the
+/// responses of the server small. kubectl versions without `--chunk-size` get the whole list at once.
+fn list_args(capabilities: Capabilities) -> &'static [&'static str] {
+    if capabilities.chunk_size {
+        &["--chunk-size=250"]
+    } else {
+        &[]
+    }
+}
+
+fn create_args(dry_run: Option<DryRun>) -> Vec<&'static str> {
+    let mut args = vec!["create", "--output=json"];
+    if let Some(dry_run) = dry_run {
+        args.push(dry_run.as_arg());
+    }
+    args.extend(["-f", "-"]);
+    args
+}
+
+#[derive(Debug)]
+pub enum DryRunVerdict {
+    Accepted(ResourceHandle),
+    /// Contains the error message emitted by the server.
+    Rejected(String),
+}
+
+/// Passes the items of the JSON list that `command` prints to `on_item` while the output is produced, see
+/// [`for_each_item`].
+fn stream_items<T: serde::de::DeserializeOwned>(
+    command: process::Command,
+    on_item: impl FnMut(T),
+) -> Result<()> {
+    let command = command.args(list_args(capabilities()).iter().map(OsStr::new));
+    command.status_with_stdout(|stdout| for_each_item(stdout, on_item))??;
+    Ok(())
+}
+
+fn collect_items<T: serde::de::DeserializeOwned>(command: process::Command) -> Result<Vec<T>> {
+    let mut items = Vec::new();
+    stream_items(command, |item| items.push(item))?;
+    Ok(items)
+}
+
 #[derive(Debug)]
 pub struct ResourceHandle {
     pub namespace: String,
@@ -286,7 +957,405 @@ struct CreateOutputMetadata {
 pub const NAMESPACE: &str = "launch";
 
 pub mod annotation {
+    /// The prefix of all annotations that launch sets.
+    pub const PREFIX: &str = "launch.astera.org/";
     pub const LAUNCHED_BY_MACHINE_USER: &str = "launch.astera.org/launched-by-machine-user";
     pub const LAUNCHED_BY_TAILSCALE_USER: &str = "launch.astera.org/launched-by-tailscale-user";
     pub const VERSION: &str = "launch.astera.org/version";
+    pub const STRICT_GIT: &str = "launch.astera.org/strict-git";
+    pub const GIT_DIRTY: &str = "launch.astera.org/git-dirty";
+    pub const GIT_UNPUSHED: &str = "launch.astera.org/git-unpushed";
+    pub const K8S_SERVER_VERSION: &str = "launch.astera.org/k8s-server-version";
+    pub const SCHEDULING_LATENCY_SECONDS: &str = "launch.astera.org/scheduling-latency-seconds";
+    pub const PULL_LATENCY_SECONDS: &str = "launch.astera.org/pull-latency-seconds";
+    pub const LOG_LATENCY_SECONDS: &str = "launch.astera.org/log-latency-seconds";
+    pub const GPU_PRODUCT: &str = "launch.astera.org/gpu-product";
+    pub const OBSERVED_IMAGE_DIGEST: &str = "launch.astera.org/observed-image-digest";
+    pub const RUN_ID: &str = "launch.astera.org/run-id";
+    /// The name of the resource that `launch resubmit` copied.
+    pub const RESUBMITTED_FROM: &str = "launch.astera.org/resubmitted-from";
+    /// Free text that describes the intent of the submission.
+    pub const COMMENT: &str = "launch.astera.org/comment";
+    /// Recorded the machine user before launch prefixed its annotations.
+    pub const LEGACY_LAUNCHED_BY_USER: &str = "launched_by_user";
+}
+
+pub mod label {
+    /// Also set as an annotation, the label lets the server select the resources of a submission.
+    pub const RUN_ID: &str = super::annotation::RUN_ID;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(windows)]
+    #[test]
+    fn empty_kubeconfig_uses_the_null_device_on_windows() {
+        assert_eq!(EMPTY_KUBECONFIG, "--kubeconfig=NUL");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn empty_kubeconfig_uses_the_null_device() {
+        assert_eq!(EMPTY_KUBECONFIG, "--kubeconfig=/dev/null");
+    }
+
+    #[test]
+    fn kubeconfig_works() {
+        let kubeconfig = Kubectl::new("https://example-tailscale-operator.ts.net")
+            .kubeconfig("launch-example", NAMESPACE)
+            .unwrap();
+        let yaml = serde_yaml::to_string(&kubeconfig).unwrap();
+        let parsed: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+
+        assert_eq!(parsed["apiVersion"], "v1");
+        assert_eq!(parsed["kind"], "Config");
+        assert_eq!(parsed["current-context"], "launch-example");
+        assert_eq!(parsed["clusters"][0]["name"], "launch-example");
+        assert_eq!(
+            parsed["clusters"][0]["cluster"]["server"],
+            "https://example-tailscale-operator.ts.net"
+        );
+        assert_eq!(parsed["users"][0]["name"], "launch-example");
+        assert_eq!(parsed["users"][0]["user"]["token"], "unused");
+        let context = &parsed["contexts"][0];
+        assert_eq!(context["name"], "launch-example");
+        assert_eq!(context["context"]["cluster"], "launch-example");
+        assert_eq!(context["context"]["user"], "launch-example");
+        assert_eq!(context["context"]["namespace"], "launch");
+    }
+
+    #[test]
+    fn denied_flag_works() {
+        assert_eq!(denied_flag("--kubeconfig"), Some("--kubeconfig"));
+        assert_eq!(
+            denied_flag("--kubeconfig=/tmp/config"),
+            Some("--kubeconfig")
+        );
+        assert_eq!(
+            denied_flag("--server=https://example.com"),
+            Some("--server")
+        );
+        assert_eq!(denied_flag("-s"), Some("-s"));
+        assert_eq!(denied_flag("--token=secret"), Some("--token"));
+        assert_eq!(denied_flag("--context"), Some("--context"));
+        assert_eq!(denied_flag("--v=8"), None);
+        assert_eq!(denied_flag("--as=alice"), None);
+        assert_eq!(denied_flag("--as-group"), None);
+        assert_eq!(denied_flag("--tokenfile"), None);
+        // A value that happens to look like a denied flag is not a flag.
+        assert_eq!(denied_flag("--as=--token"), None);
+    }
+
+    #[test]
+    fn check_extra_args_works() {
+        let check = |args: &[&str]| {
+            check_extra_args(&args.iter().map(|&arg| arg.to_owned()).collect::<Vec<_>>())
+        };
+        assert!(check(&[]).is_ok());
+        assert!(check(&["--v=8", "--as", "alice"]).is_ok());
+        let error = check(&["--v=8", "--server=https://example.com"]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "`--kubectl-arg --server` is not allowed, launch sets it to select the cluster, its credentials and the namespace"
+        );
+        for args in [
+            &["--cluster", "other"][..],
+            &["--user=admin"],
+            &["--namespace", "default"],
+            &["-n", "default"],
+            &["-ndefault"],
+            &["-shttps://example.com"],
+        ] {
+            assert!(check(args).is_err(), "{args:?}");
+        }
+        assert!(check(&["--v=8", "--as-group=admins"]).is_ok());
+    }
+
+    #[test]
+    fn connection_args_work() {
+        assert_eq!(
+            Kubectl::new("https://example-tailscale-operator.ts.net").connection_args(),
+            [
+                EMPTY_KUBECONFIG,
+                "--server",
+                "https://example-tailscale-operator.ts.net",
+                "--token=unused"
+            ]
+        );
+        // The kubeconfig of the user applies, with its own credentials.
+        assert!(Kubectl::from_kubeconfig(None).connection_args().is_empty());
+        assert_eq!(
+            Kubectl::from_kubeconfig(Some("kind-launch".to_owned())).connection_args(),
+            ["--context", "kind-launch"]
+        );
+        assert!(Kubectl::from_kubeconfig(None)
+            .kubeconfig("launch-local", NAMESPACE)
+            .is_err());
+    }
+
+    #[test]
+    fn create_args_works() {
+        assert_eq!(create_args(None), ["create", "--output=json", "-f", "-"]);
+        assert_eq!(
+            create_args(Some(DryRun::Server)),
+            ["create", "--output=json", "--dry-run=server", "-f", "-"]
+        );
+    }
+
+    #[test]
+    fn list_args_works() {
+        assert_eq!(list_args(Capabilities::of((1, 31))), ["--chunk-size=250"]);
+        assert!(list_args(Capabilities::of((1, 8))).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn recreate_secret_falls_back_without_server_side_apply() {
+        let kubectl = fake::FakeKubectl::install(
+            r#"case "$1" in create) echo '{"kind":"Secret"}';; apply) cat >/dev/null;; esac"#,
+        );
+        Kubectl::from_kubeconfig(None)
+            .recreate_secret_with(
+                Capabilities::of((1, 31)),
+                NAMESPACE,
+                "config",
+                Path::new("a"),
+            )
+            .unwrap();
+        assert_eq!(
+            kubectl.invocations(),
+            [
+                "create secret generic --from-file a --namespace launch config --dry-run=client --output=json",
+                "apply --server-side --force-conflicts --field-manager=launch -f -",
+            ]
+        );
+        drop(kubectl);
+
+        let kubectl = fake::FakeKubectl::install("");
+        Kubectl::from_kubeconfig(None)
+            .recreate_secret_with(
+                Capabilities::of((1, 21)),
+                NAMESPACE,
+                "config",
+                Path::new("a"),
+            )
+            .unwrap();
+        assert_eq!(
+            kubectl.invocations(),
+            [
+                "delete secret --ignore-not-found --namespace launch config",
+                "create secret generic --from-file a --namespace launch config",
+            ]
+        );
+    }
+
+    /// Asserts that every value in `subset` is also present in `superset`.
+    fn assert_json_subset(subset: &serde_json::Value, superset: &serde_json::Value, path: &str) {
+        match (subset, superset) {
+            (serde_json::Value::Object(subset), serde_json::Value::Object(superset)) => {
+                for (key, value) in subset {
+                    let path = format!("{path}.{key}");
+                    let other = superset
+                        .get(key)
+                        .unwrap_or_else(|| panic!("{path} is not present in the original"));
+                    assert_json_subset(value, other, &path);
+                }
+            }
+            (serde_json::Value::Array(subset), serde_json::Value::Array(superset)) => {
+                assert_eq!(
+                    subset.len(),
+                    superset.len(),
+                    "{path} has a different length"
+                );
+                for (index, (value, other)) in subset.iter().zip(superset).enumerate() {
+                    assert_json_subset(value, other, &format!("{path}[{index}]"));
+                }
+            }
+            (subset, superset) => assert_eq!(subset, superset, "{path} differs"),
+        }
+    }
+
+    /// Deserializes the fixture, serializes it back out and checks that the output only contains values from the
+    /// fixture and deserializes to the same value.
+    fn assert_round_trip<T>(fixture: &str)
+    where
+        T: serde::de::DeserializeOwned + serde::Serialize + PartialEq + std::fmt::Debug,
+    {
+        let original: serde_json::Value = serde_json::from_str(fixture).unwrap();
+        let value: T = serde_json::from_value(original.clone()).unwrap();
+        let serialized = serde_json::to_value(&value).unwrap();
+        assert_json_subset(&serialized, &original, "$");
+        assert_eq!(serde_json::from_value::<T>(serialized).unwrap(), value);
+    }
+
+    #[test]
+    fn job_status_round_trips() {
+        assert_round_trip::<JobStatus>(
+            r#"{
+                "active": 1,
+                "ready": 1,
+                "startTime": "2025-01-13T10:20:30Z",
+                "uncountedTerminatedPods": {}
+            }"#,
+        );
+        assert_round_trip::<JobStatus>(
+            r#"{
+                "completionTime": "2025-01-13T11:00:00Z",
+                "conditions": [
+                    {
+                        "lastProbeTime": "2025-01-13T11:00:00Z",
+                        "lastTransitionTime": "2025-01-13T11:00:00Z",
+                        "message": "Reached expected number of succeeded pods",
+                        "reason": "CompletionsReached",
+                        "status": "True",
+                        "type": "Complete"
+                    }
+                ],
+                "ready": 0,
+                "startTime": "2025-01-13T10:20:30Z",
+                "succeeded": 1,
+                "terminating": 0,
+                "uncountedTerminatedPods": {}
+            }"#,
+        );
+        assert_round_trip::<JobStatus>(
+            r#"{
+                "conditions": [
+                    {
+                        "lastProbeTime": "2025-01-13T10:25:00Z",
+                        "lastTransitionTime": "2025-01-13T10:25:00Z",
+                        "message": "Job has reached the specified backoff limit",
+                        "reason": "BackoffLimitExceeded",
+                        "status": "True",
+                        "type": "Failed"
+                    }
+                ],
+                "failed": 1,
+                "ready": 0,
+                "startTime": "2025-01-13T10:20:30Z"
+            }"#,
+        );
+    }
+
+    #[test]
+    fn ray_job_status_round_trips() {
+        assert_round_trip::<RayJobStatus>(
+            r#"{
+                "jobDeploymentStatus": "Initializing",
+                "jobId": "ray-job-abc-x7k2p",
+                "rayClusterName": "ray-job-abc-raycluster-lq9vd",
+                "rayClusterStatus": {
+                    "desiredCPU": "2",
+                    "head": {}
+                },
+                "startTime": "2025-01-13T10:20:30Z"
+            }"#,
+        );
+        assert_round_trip::<RayJobStatus>(
+            r#"{
+                "dashboardURL": "ray-job-abc-raycluster-lq9vd-head-svc.launch.svc.cluster.local:8265",
+                "endTime": "2025-01-13T10:40:00Z",
+                "jobDeploymentStatus": "Complete",
+                "jobId": "ray-job-abc-x7k2p",
+                "jobStatus": "SUCCEEDED",
+                "rayClusterName": "ray-job-abc-raycluster-lq9vd",
+                "rayClusterStatus": {
+                    "lastUpdateTime": "2025-01-13T10:39:58Z",
+                    "observedGeneration": 1,
+                    "state": "ready"
+                },
+                "startTime": "2025-01-13T10:20:30Z"
+            }"#,
+        );
+    }
+
+    #[test]
+    fn pod_status_round_trips() {
+        assert_round_trip::<PodStatus>(
+            r#"{
+                "conditions": [
+                    {
+                        "lastProbeTime": null,
+                        "lastTransitionTime": "2025-01-13T10:20:31Z",
+                        "message": "0/4 nodes are available: 4 Insufficient nvidia.com/gpu.",
+                        "reason": "Unschedulable",
+                        "status": "False",
+                        "type": "PodScheduled"
+                    }
+                ],
+                "phase": "Pending",
+                "qosClass": "BestEffort"
+            }"#,
+        );
+        assert_round_trip::<PodStatus>(
+            r#"{
+                "conditions": [
+                    {
+                        "lastTransitionTime": "2025-01-13T10:20:35Z",
+                        "status": "True",
+                        "type": "Ready"
+                    }
+                ],
+                "containerStatuses": [
+                    {
+                        "containerID": "containerd://1a2b",
+                        "image": "berkeley-docker.taila1eba.ts.net/launch:abc",
+                        "imageID": "berkeley-docker.taila1eba.ts.net/launch@sha256:1234",
+                        "lastState": {},
+                        "name": "main",
+                        "ready": true,
+                        "restartCount": 0,
+                        "started": true,
+                        "state": {
+                            "running": {
+                                "startedAt": "2025-01-13T10:20:34Z"
+                            }
+                        }
+                    }
+                ],
+                "hostIP": "10.0.0.4",
+                "phase": "Running",
+                "startTime": "2025-01-13T10:20:31Z"
+            }"#,
+        );
+        assert_round_trip::<PodStatus>(
+            r#"{
+                "containerStatuses": [
+                    {
+                        "containerID": "containerd://1a2b",
+                        "image": "berkeley-docker.taila1eba.ts.net/launch:abc",
+                        "imageID": "berkeley-docker.taila1eba.ts.net/launch@sha256:1234",
+                        "name": "main",
+                        "restartCount": 2,
+                        "state": {
+                            "terminated": {
+                                "containerID": "containerd://1a2b",
+                                "exitCode": 137,
+                                "finishedAt": "2025-01-13T10:30:00Z",
+                                "reason": "OOMKilled",
+                                "startedAt": "2025-01-13T10:20:34Z"
+                            }
+                        }
+                    },
+                    {
+                        "image": "berkeley-docker.taila1eba.ts.net/launch:abc",
+                        "imageID": "",
+                        "name": "sidecar",
+                        "restartCount": 0,
+                        "state": {
+                            "waiting": {
+                                "message": "Back-off pulling image",
+                                "reason": "ImagePullBackOff"
+                            }
+                        }
+                    }
+                ],
+                "message": "The node was low on resource: memory.",
+                "phase": "Failed",
+                "reason": "Evicted"
+            }"#,
+        );
+    }
 }
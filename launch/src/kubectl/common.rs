@@ -1,6 +1,13 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt, io, marker::PhantomData};
 
-use serde::Deserialize;
+use log::debug;
+use serde::{
+    de::{self, DeserializeOwned, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer as _,
+};
+
+use super::{annotation, label};
+use crate::user_host::UserHostRef;
 
 #[derive(Debug, Deserialize)]
 pub struct GetResource<T> {
@@ -8,6 +15,75 @@ pub struct GetResource<T> {
     pub items: Vec<T>,
 }
 
+/// Deserializes the `items` of a list like [`GetResource`] one at a time and passes each to `on_item`, so that neither
+/// the raw JSON nor all items have to be held in memory.
+pub fn for_each_item<T: DeserializeOwned>(
+    reader: impl io::Read,
+    on_item: impl FnMut(T),
+) -> serde_json::Result<()> {
+    let mut deserializer = serde_json::Deserializer::from_reader(io::BufReader::new(reader));
+    deserializer.deserialize_map(ListVisitor {
+        on_item,
+        item: PhantomData,
+    })?;
+    deserializer.end()
+}
+
+struct ListVisitor<F, T> {
+    on_item: F,
+    item: PhantomData<fn(T)>,
+}
+
+impl<'de, F: FnMut(T), T: DeserializeOwned> Visitor<'de> for ListVisitor<F, T> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a list with items")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+        let mut has_items = false;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "items" {
+                map.next_value_seed(ItemsSeed(&mut self))?;
+                has_items = true;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        if !has_items {
+            return Err(de::Error::missing_field("items"));
+        }
+        Ok(())
+    }
+}
+
+/// Deserializes the `items` array through the callback of the [`ListVisitor`].
+struct ItemsSeed<'a, F, T>(&'a mut ListVisitor<F, T>);
+
+impl<'de, F: FnMut(T), T: DeserializeOwned> DeserializeSeed<'de> for ItemsSeed<'_, F, T> {
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, F: FnMut(T), T: DeserializeOwned> Visitor<'de> for ItemsSeed<'_, F, T> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of items")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(item) = seq.next_element::<T>()? {
+            (self.0.on_item)(item);
+        }
+        Ok(())
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -39,6 +115,57 @@ pub struct ResourceMetadata {
     pub generation: Option<i32>,
 }
 
+impl ResourceMetadata {
+    /// Returns the non-empty value of the annotation.
+    fn annotation(&self, key: &str) -> Option<&str> {
+        self.annotations
+            .get(key)
+            .map(String::as_str)
+            .filter(|value| !value.is_empty())
+    }
+
+    /// The user and host of the machine that submitted the resource, also read from the annotation of older versions
+    /// of launch.
+    pub fn launched_by_machine_user(&self) -> Option<UserHostRef<'_>> {
+        self.annotation(annotation::LAUNCHED_BY_MACHINE_USER)
+            .or_else(|| self.annotation(annotation::LEGACY_LAUNCHED_BY_USER))
+            .map(UserHostRef::parse)
+    }
+
+    pub fn launched_by_tailscale_user(&self) -> Option<UserHostRef<'_>> {
+        self.annotation(annotation::LAUNCHED_BY_TAILSCALE_USER)
+            .map(UserHostRef::parse)
+    }
+
+    /// The version of launch that submitted the resource. Versions that can not be parsed are ignored.
+    pub fn launch_version(&self) -> Option<semver::Version> {
+        let value = self.annotation(annotation::VERSION)?;
+        semver::Version::parse(value)
+            .inspect_err(|error| {
+                debug!(
+                    "Ignoring the version {value:?} of {}/{}: {error}",
+                    self.namespace, self.name
+                )
+            })
+            .ok()
+    }
+
+    /// The comment of the submission, see [`annotation::COMMENT`].
+    pub fn comment(&self) -> Option<&str> {
+        self.annotation(annotation::COMMENT)
+    }
+
+    /// The run ID of the submission, see [`label::RUN_ID`].
+    pub fn run_id(&self) -> Option<&str> {
+        self.annotation(annotation::RUN_ID).or_else(|| {
+            self.labels
+                .get(label::RUN_ID)
+                .map(String::as_str)
+                .filter(|value| !value.is_empty())
+        })
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -78,3 +205,127 @@ pub struct OwnerReference {
     #[serde(rename = "name")]
     pub name: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(annotations: &[(&str, &str)], labels: &[(&str, &str)]) -> ResourceMetadata {
+        let map = |entries: &[(&str, &str)]| {
+            entries
+                .iter()
+                .map(|&(key, value)| (key.to_owned(), value.to_owned()))
+                .collect::<HashMap<_, _>>()
+        };
+        serde_json::from_value(serde_json::json!({
+            "name": "job-abc",
+            "namespace": "launch",
+            "creationTimestamp": "2025-01-13T12:00:00Z",
+            "annotations": map(annotations),
+            "labels": map(labels),
+        }))
+        .unwrap()
+    }
+
+    fn user_host(value: Option<UserHostRef>) -> Option<String> {
+        value.map(|value| value.to_string())
+    }
+
+    #[test]
+    fn launched_by_machine_user_works() {
+        let get = |annotations| user_host(metadata(annotations, &[]).launched_by_machine_user());
+        assert_eq!(get(&[]), None);
+        assert_eq!(
+            get(&[(annotation::LAUNCHED_BY_MACHINE_USER, "mick@laptop")]),
+            Some("mick@laptop".to_owned())
+        );
+        assert_eq!(get(&[(annotation::LAUNCHED_BY_MACHINE_USER, "")]), None);
+        assert_eq!(
+            get(&[(annotation::LEGACY_LAUNCHED_BY_USER, "mick")]),
+            Some("mick".to_owned())
+        );
+        assert_eq!(
+            get(&[
+                (annotation::LAUNCHED_BY_MACHINE_USER, "mick@laptop"),
+                (annotation::LEGACY_LAUNCHED_BY_USER, "old")
+            ]),
+            Some("mick@laptop".to_owned())
+        );
+    }
+
+    #[test]
+    fn launched_by_tailscale_user_works() {
+        let get = |annotations| user_host(metadata(annotations, &[]).launched_by_tailscale_user());
+        assert_eq!(get(&[]), None);
+        assert_eq!(
+            get(&[(annotation::LAUNCHED_BY_TAILSCALE_USER, "mick@astera.org")]),
+            Some("mick@astera.org".to_owned())
+        );
+        assert_eq!(get(&[(annotation::LAUNCHED_BY_TAILSCALE_USER, "")]), None);
+    }
+
+    #[test]
+    fn launch_version_works() {
+        let get = |annotations| metadata(annotations, &[]).launch_version();
+        assert_eq!(get(&[]), None);
+        assert_eq!(
+            get(&[(annotation::VERSION, "0.1.10")]),
+            Some(semver::Version::new(0, 1, 10))
+        );
+        assert_eq!(get(&[(annotation::VERSION, "0.1")]), None);
+        assert_eq!(get(&[(annotation::VERSION, "")]), None);
+    }
+
+    #[test]
+    fn comment_works() {
+        // As printed by `kubectl get --output=json`, which escapes line breaks and may escape non-ASCII characters.
+        let printed: ResourceMetadata = serde_json::from_str(
+            r#"{
+                "name": "job-abc",
+                "namespace": "launch",
+                "creationTimestamp": "2025-01-13T12:00:00Z",
+                "annotations": {
+                    "launch.astera.org/comment": "baseline for the\nnew tokenizer, \u00e9t\u00e9 \ud83e\uddea"
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            printed.comment(),
+            Some("baseline for the\nnew tokenizer, été 🧪")
+        );
+        assert_eq!(metadata(&[], &[]).comment(), None);
+        assert_eq!(metadata(&[(annotation::COMMENT, "")], &[]).comment(), None);
+    }
+
+    #[test]
+    fn run_id_works() {
+        let run_id = "0192f0c4-5d3e-7a4b-8c2d-9e1f2a3b4c5d";
+        assert_eq!(metadata(&[], &[]).run_id(), None);
+        assert_eq!(
+            metadata(&[(annotation::RUN_ID, run_id)], &[]).run_id(),
+            Some(run_id)
+        );
+        assert_eq!(
+            metadata(&[], &[(label::RUN_ID, run_id)]).run_id(),
+            Some(run_id)
+        );
+        assert_eq!(metadata(&[(annotation::RUN_ID, "")], &[]).run_id(), None);
+    }
+
+    #[test]
+    fn for_each_item_works() {
+        let json = r#"{"apiVersion": "v1", "items": [{"name": "a"}, {"name": "b"}], "kind": "List", "metadata": {}}"#;
+        let mut names = Vec::new();
+        for_each_item(json.as_bytes(), |item: serde_json::Value| {
+            names.push(item["name"].as_str().unwrap().to_owned())
+        })
+        .unwrap();
+        assert_eq!(names, ["a", "b"]);
+
+        let ignore = |_: serde_json::Value| {};
+        assert!(for_each_item(r#"{"kind": "List"}"#.as_bytes(), ignore).is_err());
+        assert!(for_each_item(r#"{"items": [{}]"#.as_bytes(), ignore).is_err());
+        assert!(for_each_item(r#"{"items": []} trailing"#.as_bytes(), ignore).is_err());
+    }
+}
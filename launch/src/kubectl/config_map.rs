@@ -0,0 +1,12 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+/// Partially implements [ConfigMap](https://kubernetes.io/docs/reference/kubernetes-api/config-and-storage-resources/config-map-v1/)
+/// as returned by `kubectl get configmap`.
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+pub struct ConfigMap {
+    /// The UTF-8 entries of the ConfigMap.
+    #[serde(default)]
+    pub data: BTreeMap<String, String>,
+}
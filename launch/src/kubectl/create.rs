@@ -0,0 +1,302 @@
+//! Creates resources with `generateName` at most once. When kubectl fails after the request may have reached the API
+//! server, such as when the response timed out under load, creating the resource again would create a duplicate under
+//! another generated name. The resource carries the run ID as [`label::RUN_ID`] instead, so that it can be looked up
+//! and adopted when the server did create it.
+
+use log::{info, warn};
+use serde_json::Value;
+
+use super::{label, ResourceHandle};
+use crate::Result;
+
+/// A failed attempt to create a resource.
+pub(super) struct Failure {
+    pub error: Box<dyn std::error::Error + Send + Sync + 'static>,
+    /// Whether the server may have created the resource anyway.
+    pub ambiguous: bool,
+}
+
+impl Failure {
+    pub fn certain(error: impl Into<Box<dyn std::error::Error + Send + Sync + 'static>>) -> Self {
+        Self {
+            error: error.into(),
+            ambiguous: false,
+        }
+    }
+}
+
+/// The messages of kubectl that mean that the request was sent, but the response was lost. Failures to connect, such as
+/// `dial tcp ...: i/o timeout`, happen before the request is sent and are not ambiguous.
+const AMBIGUOUS_MESSAGES: &[&str] = &[
+    "Client.Timeout exceeded while awaiting headers",
+    "context deadline exceeded",
+    "connection reset by peer",
+    "http2: client connection lost",
+    "unexpected EOF",
+    "the server was unable to return a response in the time allotted",
+    "Timeout: request did not complete within requested timeout",
+];
+
+/// Returns whether the `stderr` of a failed `kubectl create` leaves open whether the server created the resource.
+pub(super) fn is_ambiguous_failure(stderr: &str) -> bool {
+    !stderr.contains("dial tcp")
+        && AMBIGUOUS_MESSAGES
+            .iter()
+            .any(|message| stderr.contains(message))
+}
+
+/// Sets [`label::RUN_ID`] on the resource.
+pub(super) fn stamp_run_id(resource: &mut Value, run_id: &str) {
+    let Some(resource) = resource.as_object_mut() else {
+        return;
+    };
+    let metadata = resource
+        .entry("metadata")
+        .or_insert_with(|| Value::Object(Default::default()));
+    if let Some(metadata) = metadata.as_object_mut() {
+        let labels = metadata
+            .entry("labels")
+            .or_insert_with(|| Value::Object(Default::default()));
+        if let Some(labels) = labels.as_object_mut() {
+            labels.insert(label::RUN_ID.to_owned(), run_id.into());
+        }
+    }
+}
+
+/// Returns the fully qualified type of the resource for `kubectl get`, such as `Job.v1.batch`, so that it is not
+/// mistaken for a resource of the same kind in another API group.
+pub(super) fn resource_type(resource: &Value) -> Option<String> {
+    let kind = resource.get("kind")?.as_str()?;
+    Some(
+        match resource.get("apiVersion")?.as_str()?.split_once('/') {
+            Some((group, version)) => format!("{kind}.{version}.{group}"),
+            None => kind.to_owned(),
+        },
+    )
+}
+
+/// Decides what to do after an ambiguous failure from the resources that carry the run ID: adopt the one the server
+/// created, or create it when there is none.
+pub(super) fn adopt_or_create(mut existing: Vec<ResourceHandle>) -> Result<Option<ResourceHandle>> {
+    match existing.len() {
+        0 | 1 => Ok(existing.pop()),
+        _ => Err(format!(
+            "Found {} resources with the run ID of the submission, {}, while expecting at most one",
+            existing.len(),
+            existing
+                .iter()
+                .map(|handle| handle.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+        .into()),
+    }
+}
+
+/// Calls `create` and, when it fails ambiguously, looks the resource up with `find` before calling `create` again.
+pub(super) fn create_idempotently(
+    mut create: impl FnMut() -> Result<ResourceHandle, Failure>,
+    find: impl FnOnce() -> Result<Vec<ResourceHandle>>,
+) -> Result<ResourceHandle> {
+    let failure = match create() {
+        Ok(handle) => return Ok(handle),
+        Err(failure) => failure,
+    };
+    if !failure.ambiguous {
+        return Err(failure.error);
+    }
+    warn!(
+        "Unable to tell whether the resource was created: {}. Looking it up by its run ID.",
+        failure.error
+    );
+    let existing = find().map_err(|error| {
+        format!(
+            "{}. Looking up the resource by its run ID failed as well: {error}",
+            failure.error
+        )
+    })?;
+    match adopt_or_create(existing)? {
+        Some(handle) => {
+            info!(
+                "Adopting {}/{}, which the server created despite the failure",
+                handle.namespace, handle.name
+            );
+            Ok(handle)
+        }
+        None => create().map_err(|failure| failure.error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn handle(name: &str) -> ResourceHandle {
+        ResourceHandle {
+            namespace: "launch".to_owned(),
+            name: name.to_owned(),
+        }
+    }
+
+    #[test]
+    fn is_ambiguous_failure_works() {
+        for stderr in [
+            "Unable to connect to the server: net/http: request canceled (Client.Timeout exceeded while awaiting headers)",
+            "error: Post \"https://berkeley-tailscale-operator.taila1eba.ts.net/apis/batch/v1/namespaces/launch/jobs\": context deadline exceeded",
+            "Unable to connect to the server: read tcp 100.64.0.2:51234->100.64.0.1:443: read: connection reset by peer",
+            "Unable to connect to the server: http2: client connection lost",
+            "Unable to connect to the server: unexpected EOF",
+            "Error from server (Timeout): the server was unable to return a response in the time allotted, but may still be processing the request",
+        ] {
+            assert!(is_ambiguous_failure(stderr), "{stderr}");
+        }
+        for stderr in [
+            "Unable to connect to the server: dial tcp 100.64.0.1:443: i/o timeout",
+            "Unable to connect to the server: dial tcp: lookup berkeley-tailscale-operator: no such host",
+            "Unable to connect to the server: dial tcp 100.64.0.1:443: connect: connection refused",
+            "Error from server (Forbidden): jobs.batch is forbidden: exceeded quota",
+            "The Job \"job-\" is invalid: spec.template.spec.containers[0].image: Required value",
+        ] {
+            assert!(!is_ambiguous_failure(stderr), "{stderr}");
+        }
+    }
+
+    #[test]
+    fn stamp_run_id_works() {
+        let mut resource =
+            serde_json::json!({ "kind": "Job", "metadata": { "generateName": "job-" } });
+        stamp_run_id(&mut resource, "abc");
+        assert_eq!(resource["metadata"]["labels"][label::RUN_ID], "abc");
+
+        let mut resource =
+            serde_json::json!({ "metadata": { "labels": { "app": "x", label::RUN_ID: "old" } } });
+        stamp_run_id(&mut resource, "abc");
+        assert_eq!(
+            resource["metadata"]["labels"],
+            serde_json::json!({ "app": "x", label::RUN_ID: "abc" })
+        );
+    }
+
+    #[test]
+    fn resource_type_works() {
+        let resource_type = |value| resource_type(&value);
+        assert_eq!(
+            resource_type(serde_json::json!({ "apiVersion": "batch/v1", "kind": "Job" }))
+                .as_deref(),
+            Some("Job.v1.batch")
+        );
+        assert_eq!(
+            resource_type(
+                serde_json::json!({ "apiVersion": "kubeflow.org/v1beta1", "kind": "Experiment" })
+            )
+            .as_deref(),
+            Some("Experiment.v1beta1.kubeflow.org")
+        );
+        assert_eq!(
+            resource_type(serde_json::json!({ "apiVersion": "v1", "kind": "Pod" })).as_deref(),
+            Some("Pod")
+        );
+        assert_eq!(resource_type(serde_json::json!({ "kind": "Job" })), None);
+    }
+
+    #[test]
+    fn adopt_or_create_works() {
+        assert!(adopt_or_create(vec![]).unwrap().is_none());
+        assert_eq!(
+            adopt_or_create(vec![handle("job-abcde")])
+                .unwrap()
+                .unwrap()
+                .name,
+            "job-abcde"
+        );
+        let error = adopt_or_create(vec![handle("job-abcde"), handle("job-fghij")]).unwrap_err();
+        assert!(
+            error.to_string().contains("job-abcde, job-fghij"),
+            "{error}"
+        );
+    }
+
+    /// Runs [`create_idempotently`] with fake attempts that return the `outcomes` in order, and a fake lookup that
+    /// returns `existing`. Returns the result along with the number of attempts and lookups.
+    fn run(
+        outcomes: Vec<Result<&str, bool>>,
+        existing: Result<Vec<&str>, &str>,
+    ) -> (Result<String, String>, usize, usize) {
+        let mut outcomes = outcomes.into_iter();
+        let attempts = Cell::new(0);
+        let lookups = Cell::new(0);
+        let result = create_idempotently(
+            || {
+                attempts.set(attempts.get() + 1);
+                match outcomes.next().expect("unexpected attempt") {
+                    Ok(name) => Ok(handle(name)),
+                    Err(ambiguous) => Err(Failure {
+                        error: format!("attempt {} failed", attempts.get()).into(),
+                        ambiguous,
+                    }),
+                }
+            },
+            || {
+                lookups.set(lookups.get() + 1);
+                existing
+                    .map(|names| names.into_iter().map(handle).collect())
+                    .map_err(Into::into)
+            },
+        );
+        (
+            result
+                .map(|handle| handle.name)
+                .map_err(|error| error.to_string()),
+            attempts.get(),
+            lookups.get(),
+        )
+    }
+
+    #[test]
+    fn create_idempotently_works() {
+        // Success does not look anything up.
+        assert_eq!(
+            run(vec![Ok("job-a")], Ok(vec![])),
+            (Ok("job-a".to_owned()), 1, 0)
+        );
+
+        // A certain failure is returned as is, because the server did not create anything.
+        assert_eq!(
+            run(vec![Err(false)], Ok(vec![])),
+            (Err("attempt 1 failed".to_owned()), 1, 0)
+        );
+
+        // An ambiguous failure adopts the resource that the server created.
+        assert_eq!(
+            run(vec![Err(true)], Ok(vec!["job-a"])),
+            (Ok("job-a".to_owned()), 1, 1)
+        );
+
+        // An ambiguous failure creates the resource when the server did not create it.
+        assert_eq!(
+            run(vec![Err(true), Ok("job-b")], Ok(vec![])),
+            (Ok("job-b".to_owned()), 2, 1)
+        );
+
+        // The second attempt is not retried again.
+        assert_eq!(
+            run(vec![Err(true), Err(true)], Ok(vec![])),
+            (Err("attempt 2 failed".to_owned()), 2, 1)
+        );
+
+        // Without a working lookup, creating again could create a duplicate.
+        let (result, attempts, lookups) = run(vec![Err(true)], Err("forbidden"));
+        assert_eq!(
+            result.unwrap_err(),
+            "attempt 1 failed. Looking up the resource by its run ID failed as well: forbidden"
+        );
+        assert_eq!((attempts, lookups), (1, 1));
+
+        // Duplicates that already exist are not made worse.
+        let (result, attempts, _) = run(vec![Err(true)], Ok(vec!["job-a", "job-b"]));
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+}
@@ -0,0 +1,74 @@
+use serde::Deserialize;
+
+use super::ResourceMetadata;
+
+/// https://kubernetes.io/docs/reference/kubernetes-api/workload-resources/cron-job-v1/
+#[derive(Debug, Deserialize)]
+pub struct CronJob {
+    pub metadata: ResourceMetadata,
+    pub spec: CronJobSpec,
+    /// A new CronJob has no status until the controller first reconciles it.
+    #[serde(default)]
+    pub status: CronJobStatus,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CronJobSpec {
+    pub schedule: String,
+    #[serde(default)]
+    pub suspend: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CronJobStatus {
+    /// When a Job was last created, which the controller also sets when the Job is skipped because the previous one is
+    /// still running.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub last_schedule_time: Option<time::OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub last_successful_time: Option<time::OffsetDateTime>,
+    /// The Jobs that are running.
+    #[serde(default)]
+    pub active: Vec<serde_json::Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cron_job_deserializes() {
+        let metadata = serde_json::json!({
+            "name": "cron-job-alice-x7k2p",
+            "namespace": "launch",
+            "creationTimestamp": "2025-01-13T12:00:00Z",
+        });
+        let cron_job: CronJob = serde_json::from_value(serde_json::json!({
+            "metadata": metadata,
+            "spec": { "schedule": "0 3 * * *", "jobTemplate": {} },
+        }))
+        .unwrap();
+        assert_eq!(cron_job.spec.schedule, "0 3 * * *");
+        assert!(!cron_job.spec.suspend);
+        assert_eq!(cron_job.status.last_schedule_time, None);
+        assert!(cron_job.status.active.is_empty());
+
+        let cron_job: CronJob = serde_json::from_value(serde_json::json!({
+            "metadata": metadata,
+            "spec": { "schedule": "@daily", "suspend": true, "jobTemplate": {} },
+            "status": {
+                "lastScheduleTime": "2025-01-14T03:00:00Z",
+                "active": [{ "kind": "Job", "name": "cron-job-alice-x7k2p-28945620" }],
+            },
+        }))
+        .unwrap();
+        assert!(cron_job.spec.suspend);
+        assert_eq!(
+            cron_job.status.last_schedule_time,
+            Some(time::macros::datetime!(2025-01-14 03:00 UTC))
+        );
+        assert_eq!(cron_job.status.active.len(), 1);
+    }
+}
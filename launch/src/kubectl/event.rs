@@ -0,0 +1,41 @@
+use serde::Deserialize;
+
+/// Partially implements [Event](https://kubernetes.io/docs/reference/kubernetes-api/cluster-resources/event-v1/) as
+/// returned by `kubectl get events`.
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Event {
+    /// A short, machine understandable string that gives the reason for the event, such as `FailedMount`.
+    #[serde(default)]
+    pub reason: Option<String>,
+
+    /// A human-readable description of the status of this operation.
+    #[serde(default)]
+    pub message: Option<String>,
+
+    /// Type of this event (Normal, Warning), new types could be added in the future.
+    #[serde(default)]
+    pub r#type: Option<String>,
+}
+
+/// Parses the output of `kubectl get <resource> --output=name`, which has one `<resource>/<name>` per line.
+pub fn parse_resource_names(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| Some(line.trim().split_once('/')?.1.to_owned()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_resource_names_works() {
+        assert_eq!(
+            parse_resource_names("secret/db-creds\nsecret/databrickscfg-alice\n"),
+            ["db-creds", "databrickscfg-alice"]
+        );
+        assert_eq!(parse_resource_names(""), Vec::<String>::new());
+    }
+}
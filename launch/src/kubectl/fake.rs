@@ -0,0 +1,66 @@
+//! Runs a shell script in place of kubectl for the tests of the current thread and records the arguments of every
+//! invocation. Every test runs on its own thread, so tests that fake kubectl do not see each other's invocations.
+
+use std::{cell::RefCell, fs, path::PathBuf};
+
+use crate::{process, temp_path::TempDir};
+
+thread_local! {
+    static SCRIPT: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// Returns the command that runs kubectl, or the script of the [`FakeKubectl`] of the current thread.
+pub(super) fn command() -> process::Command {
+    match SCRIPT.with_borrow(Clone::clone) {
+        // The script is passed to `sh` rather than executed, because executing a file that was just written fails when
+        // another thread forks in the meantime.
+        Some(script) => process::Command::new("sh").args([script.as_os_str()]),
+        None => process::Command::new("kubectl"),
+    }
+}
+
+/// Replaces kubectl on the current thread until it is dropped.
+pub struct FakeKubectl {
+    dir: TempDir,
+}
+
+impl FakeKubectl {
+    /// `respond` is the shell code that answers an invocation, with the arguments of kubectl in `$@` and its input on
+    /// stdin.
+    pub fn install(respond: &str) -> Self {
+        let dir = TempDir::new();
+        let script = dir.path().join("kubectl.sh");
+        fs::write(
+            &script,
+            format!(
+                "printf '%s\\n' \"$*\" >> '{}'\n{respond}\n",
+                dir.path().join("invocations").display()
+            ),
+        )
+        .unwrap();
+        SCRIPT.with_borrow_mut(|value| *value = Some(script));
+        Self { dir }
+    }
+
+    /// The arguments of each invocation so far, joined by spaces.
+    pub fn invocations(&self) -> Vec<String> {
+        match fs::read_to_string(self.dir.path().join("invocations")) {
+            Ok(invocations) => invocations.lines().map(str::to_owned).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// The invocations that run the kubectl command `name`, such as `create`.
+    pub fn invocations_of(&self, name: &str) -> Vec<String> {
+        self.invocations()
+            .into_iter()
+            .filter(|invocation| invocation.split(' ').any(|arg| arg == name))
+            .collect()
+    }
+}
+
+impl Drop for FakeKubectl {
+    fn drop(&mut self) {
+        SCRIPT.with_borrow_mut(|value| *value = None);
+    }
+}
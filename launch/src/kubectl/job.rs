@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use super::ResourceMetadata;
 
@@ -11,7 +11,7 @@ pub struct Job {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 /// https://kubernetes.io/docs/reference/kubernetes-api/workload-resources/job-v1/#JobStatus
 ///
@@ -21,36 +21,52 @@ pub struct Job {
 /// conditions will have type "Complete" and status true. More info:
 /// https://kubernetes.io/docs/concepts/workloads/controllers/jobs-run-to-completion/
 pub struct JobStatus {
-    #[serde(default, with = "time::serde::rfc3339::option")]
+    #[serde(
+        default,
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub start_time: Option<time::OffsetDateTime>,
-    #[serde(default, with = "time::serde::rfc3339::option")]
+    #[serde(
+        default,
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub completion_time: Option<time::OffsetDateTime>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub ready: Option<u64>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub active: Option<u64>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub failed: Option<u64>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub succeeded: Option<u64>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub conditions: Vec<JobCondition>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub uncounted_terminated_pods: HashMap<String, String>,
 }
 
 /// [JobCondition](https://kubernetes.io/docs/reference/generated/kubernetes-api/v1.30/#jobcondition-v1-batch)
 #[allow(dead_code)]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct JobCondition {
-    #[serde(default, with = "time::serde::rfc3339::option")]
+    #[serde(
+        default,
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub last_probe_time: Option<time::OffsetDateTime>,
-    #[serde(default, with = "time::serde::rfc3339::option")]
+    #[serde(
+        default,
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub last_transition_time: Option<time::OffsetDateTime>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub reason: Option<String>,
     #[serde(with = "job_condition_status")]
     pub status: bool,
@@ -59,11 +75,21 @@ pub struct JobCondition {
 
 pub mod job_condition_status {
     // Learn more at https://serde.rs/custom-date-format.html.
+    pub fn serialize<S>(value: &bool, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(if *value { "True" } else { "False" })
+    }
+
     pub fn deserialize<'de, D>(deserializer: D) -> Result<bool, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        Ok(match serde::Deserialize::deserialize(deserializer)? {
+        // Deserializing into a `String` rather than a `&str` also supports deserializers that can not borrow, such as
+        // `serde_json::Value`.
+        let value: String = serde::Deserialize::deserialize(deserializer)?;
+        Ok(match value.as_str() {
             "True" => true,
             "False" => false,
             invalid => {
@@ -76,7 +102,7 @@ pub mod job_condition_status {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
 pub enum JobConditionType {
     Failed,
     Suspended,
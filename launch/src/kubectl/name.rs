@@ -1,5 +1,11 @@
 use std::borrow::Cow;
 
+/// The maximum length of the names of resources such as Jobs, whose name is used as a label value.
+pub const MAX_NAME_LEN: usize = 63;
+
+/// The length of the random suffix that kubernetes appends to `generateName`.
+pub const GENERATED_SUFFIX_LEN: usize = 5;
+
 fn is_ascii_lowercase_numeric(byte: u8) -> bool {
     matches!(byte, b'a'..=b'z' | b'0'..=b'9')
 }
@@ -1,37 +1,130 @@
-use std::fmt::{self, Write};
+use std::{
+    collections::HashMap,
+    fmt::{self, Write},
+};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use super::common;
+use crate::unit::bytes::Bytes;
 
 /// [Pod](https://kubernetes.io/docs/reference/kubernetes-api/workload-resources/pod-v1/)
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Pod {
     pub metadata: common::ResourceMetadata,
+    #[serde(default)]
+    pub spec: PodSpec,
     pub status: PodStatus,
 }
 
+/// A resource whose Pods can be selected through the labels that its controller puts on them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PodOwner<'a> {
+    /// The Job controller labels Pods with the name of their Job.
+    Job(&'a str),
+    /// Kuberay labels the head and worker Pods with the name of their RayCluster.
+    RayCluster(&'a str),
+}
+
+impl PodOwner<'_> {
+    pub const JOB_NAME_LABEL: &'static str = "job-name";
+    pub const RAY_CLUSTER_LABEL: &'static str = "ray.io/cluster";
+
+    /// Returns the label selector that matches the Pods of the owner.
+    pub fn selector(&self) -> String {
+        match self {
+            PodOwner::Job(name) => format!("{}={name}", Self::JOB_NAME_LABEL),
+            PodOwner::RayCluster(name) => format!("{}={name}", Self::RAY_CLUSTER_LABEL),
+        }
+    }
+}
+
+/// Partially implements [PodSpec](https://kubernetes.io/docs/reference/generated/kubernetes-api/v1.30/#podspec-v1-core)
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PodSpec {
+    /// List of containers belonging to the pod.
+    #[serde(default)]
+    pub containers: Vec<Container>,
+
+    /// The node that the pod has been scheduled onto.
+    #[serde(default)]
+    pub node_name: Option<String>,
+}
+
+impl PodSpec {
+    pub fn container(&self, name: &str) -> Option<&Container> {
+        self.containers
+            .iter()
+            .find(|container| container.name == name)
+    }
+}
+
+/// Partially implements [Container](https://kubernetes.io/docs/reference/generated/kubernetes-api/v1.30/#container-v1-core)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Container {
+    /// Name of the container specified as a DNS_LABEL.
+    pub name: String,
+
+    /// Container image name. More info: https://kubernetes.io/docs/concepts/containers/images
+    #[serde(default)]
+    pub image: Option<String>,
+
+    /// Compute Resources required by this container. More info: https://kubernetes.io/docs/concepts/configuration/manage-resources-containers/
+    #[serde(default)]
+    pub resources: ResourceRequirements,
+}
+
+impl Container {
+    /// Returns the memory limit if it is set and can be parsed.
+    pub fn memory_limit(&self) -> Option<Bytes> {
+        self.resources
+            .limits
+            .get("memory")
+            .and_then(|value| super::parse_memory_quantity(value))
+    }
+}
+
+/// Partially implements [ResourceRequirements](https://kubernetes.io/docs/reference/generated/kubernetes-api/v1.30/#resourcerequirements-v1-core)
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceRequirements {
+    /// Limits describes the maximum amount of compute resources allowed.
+    #[serde(default)]
+    pub limits: HashMap<String, String>,
+
+    /// Requests describes the minimum amount of compute resources required.
+    #[serde(default)]
+    pub requests: HashMap<String, String>,
+}
+
 /// Partially implements [PodStatus](https://kubernetes.io/docs/reference/generated/kubernetes-api/v1.30/#podstatus-v1-core)
-#[derive(Debug, Deserialize, Eq, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct PodStatus {
     /// Current service state of pod. More info: https://kubernetes.io/docs/concepts/workloads/pods/pod-lifecycle#pod-conditions
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub conditions: Vec<PodCondition>,
 
     /// The list has one entry per container in the manifest. More info: https://kubernetes.io/docs/concepts/workloads/pods/pod-lifecycle#pod-and-container-status
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub container_statuses: Vec<ContainerStatus>,
 
     /// A human readable message indicating details about why the pod is in this condition.
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
 
     /// A brief CamelCase message indicating details about why the pod is in this state. e.g. 'Evicted'.
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub reason: Option<String>,
 
+    /// The Quality of Service (QOS) classification assigned to the pod based on resource requirements: Guaranteed,
+    /// Burstable or BestEffort. More info: https://kubernetes.io/docs/concepts/workloads/pods/pod-qos/
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub qos_class: Option<String>,
+
     /// The phase of a Pod is a simple, high-level summary of where the Pod is in its lifecycle. The conditions array,
     /// the reason and message fields, and the individual container status arrays contain more detail about the pod's
     /// status. There are five possible phase values: Pending: The pod has been accepted by the Kubernetes system, but
@@ -162,23 +255,31 @@ impl fmt::Display for PodStatusDisplayMultiLine<'_> {
 }
 
 /// Partially implements [PodCondition](https://kubernetes.io/docs/reference/generated/kubernetes-api/v1.30/#podcondition-v1-core)
-#[derive(Debug, Deserialize, Eq, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct PodCondition {
     /// Last time we probed the condition.
-    #[serde(default, with = "time::serde::rfc3339::option")]
+    #[serde(
+        default,
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub last_probe_time: Option<time::OffsetDateTime>,
 
     /// Last time the condition transitioned from one status to another.
-    #[serde(default, with = "time::serde::rfc3339::option")]
+    #[serde(
+        default,
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub last_transition_time: Option<time::OffsetDateTime>,
 
     /// Human-readable message indicating details about last transition.
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
 
     /// Unique, one-word, CamelCase reason for the condition's last transition.
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub reason: Option<String>,
 
     /// Status is the status of the condition. Can be True, False, Unknown. More info: https://kubernetes.io/docs/concepts/workloads/pods/pod-lifecycle#pod-conditions
@@ -189,7 +290,7 @@ pub struct PodCondition {
 }
 
 // https://kubernetes.io/docs/reference/generated/kubernetes-api/v1.30/#podstatus-v1-core
-#[derive(Debug, Deserialize, Eq, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct ContainerStatus {
     /// Name is a DNS_LABEL representing the unique name of the container. Each container in a pod must have a unique name across all container types. Cannot be updated.
@@ -203,9 +304,17 @@ pub struct ContainerStatus {
     pub image_id: String,
 
     pub state: ContainerState,
+
+    /// The number of times the container has been restarted.
+    #[serde(default)]
+    pub restart_count: u32,
 }
 
 impl ContainerStatus {
+    pub fn is_oom_killed(&self) -> bool {
+        matches!(&self.state, ContainerState::Terminated(state) if state.reason.as_deref() == Some("OOMKilled"))
+    }
+
     pub fn cannot_pull_image(&self) -> bool {
         let ContainerState::Waiting(state) = &self.state else {
             return false;
@@ -217,7 +326,7 @@ impl ContainerStatus {
     }
 }
 
-#[derive(Debug, Deserialize, Eq, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
 pub enum ContainerState {
     #[serde(rename = "waiting")]
     Waiting(ContainerStateWaiting),
@@ -267,20 +376,20 @@ impl fmt::Display for ContainerState {
 }
 
 /// https://kubernetes.io/docs/reference/generated/kubernetes-api/v1.30/#containerstatewaiting-v1-core
-#[derive(Debug, Deserialize, Eq, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct ContainerStateWaiting {
     /// Message regarding why the container is not yet running.
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
 
     /// (brief) reason the container is not yet running.
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub reason: Option<String>,
 }
 
 /// https://kubernetes.io/docs/reference/generated/kubernetes-api/v1.30/#containerstaterunning-v1-core
-#[derive(Debug, Deserialize, Eq, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct ContainerStateRunning {
     /// Time at which the container was last (re-)started
@@ -289,7 +398,7 @@ pub struct ContainerStateRunning {
 }
 
 /// https://kubernetes.io/docs/reference/generated/kubernetes-api/v1.30/#containerstateterminated-v1-core
-#[derive(Debug, Deserialize, Eq, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct ContainerStateTerminated {
     /// Container's ID in the format '<type>://<container_id>'
@@ -297,7 +406,7 @@ pub struct ContainerStateTerminated {
     pub container_id: String,
 
     /// Exit status from the last termination of the container
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub exit_code: Option<i32>,
 
     /// Time at which the container last terminated
@@ -305,15 +414,15 @@ pub struct ContainerStateTerminated {
     pub finished_at: time::OffsetDateTime,
 
     /// Message regarding the last termination of the container
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
 
     /// (brief) reason from the last termination of the container
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub reason: Option<String>,
 
     /// Signal from the last termination of the container
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub signal: Option<i32>,
 
     /// Time at which previous execution of the container started
@@ -322,7 +431,7 @@ pub struct ContainerStateTerminated {
 }
 
 /// Field `phase` of [PodStatus](https://kubernetes.io/docs/reference/generated/kubernetes-api/v1.30/#podstatus-v1-core).
-#[derive(Debug, Deserialize, Eq, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
 pub enum PodPhase {
     Pending,
     Running,
@@ -342,3 +451,96 @@ impl fmt::Display for PodPhase {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(pod: serde_json::Value) -> HashMap<String, String> {
+        serde_json::from_value::<Pod>(pod).unwrap().metadata.labels
+    }
+
+    /// Whether the pod has all the `key=value` pairs of the selector.
+    fn matches(selector: &str, labels: &HashMap<String, String>) -> bool {
+        selector.split(',').all(|requirement| {
+            let (key, value) = requirement.split_once('=').unwrap();
+            labels.get(key).map(String::as_str) == Some(value)
+        })
+    }
+
+    #[test]
+    fn pod_owner_selector_works() {
+        assert_eq!(PodOwner::Job("job-abc").selector(), "job-name=job-abc");
+        assert_eq!(
+            PodOwner::RayCluster("ray-job-abc-raycluster-lq9vd").selector(),
+            "ray.io/cluster=ray-job-abc-raycluster-lq9vd"
+        );
+    }
+
+    #[test]
+    fn pod_owner_selector_matches_controller_labels() {
+        // Labels of a Pod created by the Job controller of Kubernetes 1.31.
+        let job_pod = labels(serde_json::json!({
+            "metadata": {
+                "name": "job-abc-x2x4z",
+                "namespace": "launch",
+                "creationTimestamp": "2025-01-13T10:20:31Z",
+                "labels": {
+                    "batch.kubernetes.io/controller-uid": "5a1f4c9e-8b2d-4e6a-9c3f-7d1e0b2a4c6e",
+                    "batch.kubernetes.io/job-name": "job-abc",
+                    "controller-uid": "5a1f4c9e-8b2d-4e6a-9c3f-7d1e0b2a4c6e",
+                    "job-name": "job-abc",
+                },
+            },
+            "status": { "phase": "Running" },
+        }));
+        assert!(matches(&PodOwner::Job("job-abc").selector(), &job_pod));
+        assert!(!matches(&PodOwner::Job("job-xyz").selector(), &job_pod));
+
+        // Labels of a head Pod created by kuberay 1.2.
+        let head_pod = labels(serde_json::json!({
+            "metadata": {
+                "name": "ray-job-abc-raycluster-lq9vd-head-x2x4z",
+                "namespace": "launch",
+                "creationTimestamp": "2025-01-13T10:20:31Z",
+                "labels": {
+                    "app.kubernetes.io/created-by": "kuberay-operator",
+                    "app.kubernetes.io/name": "kuberay",
+                    "ray.io/cluster": "ray-job-abc-raycluster-lq9vd",
+                    "ray.io/group": "headgroup",
+                    "ray.io/identifier": "ray-job-abc-raycluster-lq9vd-head",
+                    "ray.io/is-ray-node": "yes",
+                    "ray.io/node-type": "head",
+                },
+            },
+            "status": { "phase": "Running" },
+        }));
+        let cluster = PodOwner::RayCluster("ray-job-abc-raycluster-lq9vd");
+        assert!(matches(&cluster.selector(), &head_pod));
+        assert!(matches(
+            &super::super::head_pod_selector("ray-job-abc-raycluster-lq9vd"),
+            &head_pod
+        ));
+
+        let worker_pod = labels(serde_json::json!({
+            "metadata": {
+                "name": "ray-job-abc-raycluster-lq9vd-small-group-worker-7kq2n",
+                "namespace": "launch",
+                "creationTimestamp": "2025-01-13T10:20:31Z",
+                "labels": {
+                    "app.kubernetes.io/created-by": "kuberay-operator",
+                    "app.kubernetes.io/name": "kuberay",
+                    "ray.io/cluster": "ray-job-abc-raycluster-lq9vd",
+                    "ray.io/group": "small-group",
+                    "ray.io/identifier": "ray-job-abc-raycluster-lq9vd-worker",
+                    "ray.io/is-ray-node": "yes",
+                    "ray.io/node-type": "worker",
+                },
+            },
+            "status": { "phase": "Running" },
+        }));
+        let worker_selector = super::super::worker_pod_selector("ray-job-abc-raycluster-lq9vd");
+        assert!(matches(&worker_selector, &worker_pod));
+        assert!(!matches(&worker_selector, &head_pod));
+    }
+}
@@ -0,0 +1,86 @@
+use std::{
+    io::{self, BufRead as _},
+    process,
+};
+
+/// A running `kubectl port-forward`, which is stopped when this is dropped.
+pub struct PortForward {
+    child: process::Child,
+    /// Kept open because kubectl writes a line for every connection and fails once stdout is closed.
+    _stdout: io::BufReader<process::ChildStdout>,
+    /// The port on 127.0.0.1 that is forwarded.
+    pub local_port: u16,
+}
+
+impl PortForward {
+    /// Waits until kubectl reports the local port it listens on.
+    pub(super) fn new(mut child: process::Child) -> Result<Self, String> {
+        let mut stdout = io::BufReader::new(child.stdout.take().expect("stdout should be piped"));
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match stdout.read_line(&mut line) {
+                Ok(0) => {
+                    let status = child.wait().map_err(|error| error.to_string())?;
+                    return Err(format!(
+                        "kubectl port-forward exited with {status} before forwarding a port"
+                    ));
+                }
+                Ok(_) => {
+                    if let Some(local_port) = parse_forwarding_line(&line) {
+                        return Ok(Self {
+                            child,
+                            _stdout: stdout,
+                            local_port,
+                        });
+                    }
+                }
+                Err(error) => {
+                    let _ = child.kill();
+                    return Err(format!(
+                        "Failed to read the output of kubectl port-forward: {error}"
+                    ));
+                }
+            }
+        }
+    }
+}
+
+impl Drop for PortForward {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Parses the local port from a line like `Forwarding from 127.0.0.1:41234 -> 8265`. kubectl also forwards from
+/// `[::1]`, which is ignored so that the port is always reachable through 127.0.0.1.
+fn parse_forwarding_line(line: &str) -> Option<u16> {
+    let (local, _remote) = line
+        .trim()
+        .strip_prefix("Forwarding from 127.0.0.1:")?
+        .split_once(" -> ")?;
+    local.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_forwarding_line_works() {
+        assert_eq!(
+            parse_forwarding_line("Forwarding from 127.0.0.1:41234 -> 8265\n"),
+            Some(41234)
+        );
+        assert_eq!(
+            parse_forwarding_line("Forwarding from [::1]:41234 -> 8265"),
+            None
+        );
+        assert_eq!(parse_forwarding_line("Handling connection for 41234"), None);
+        assert_eq!(
+            parse_forwarding_line("Forwarding from 127.0.0.1:port -> 8265"),
+            None
+        );
+    }
+}
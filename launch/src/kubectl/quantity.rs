@@ -0,0 +1,75 @@
+use crate::unit::bytes::{self, Bytes};
+
+/// Parses a memory [quantity](https://kubernetes.io/docs/reference/kubernetes-api/common-definitions/quantity/) such
+/// as `16Gi`, `500M` or `1.5Gi`. Returns `None` if the value can not be represented as a whole number of bytes.
+pub fn parse_memory_quantity(value: &str) -> Option<Bytes> {
+    let split_at = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, suffix) = value.split_at(split_at);
+
+    let multiplier: u128 = match suffix {
+        "" => 1,
+        "k" => 1000,
+        "M" => 1000u128.pow(2),
+        "G" => 1000u128.pow(3),
+        "T" => 1000u128.pow(4),
+        "P" => 1000u128.pow(5),
+        "E" => 1000u128.pow(6),
+        "Ki" => 1 << 10,
+        "Mi" => 1 << 20,
+        "Gi" => 1 << 30,
+        "Ti" => 1 << 40,
+        "Pi" => 1 << 50,
+        "Ei" => 1 << 60,
+        _ => return None,
+    };
+
+    let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+    if integer.is_empty() && fraction.is_empty() {
+        return None;
+    }
+
+    let scale = 10u128.checked_pow(fraction.len().try_into().ok()?)?;
+    let integer: u128 = if integer.is_empty() {
+        0
+    } else {
+        integer.parse().ok()?
+    };
+    let fraction: u128 = if fraction.is_empty() {
+        0
+    } else {
+        fraction.parse().ok()?
+    };
+
+    let value = integer
+        .checked_mul(scale)?
+        .checked_add(fraction)?
+        .checked_mul(multiplier)?
+        .div_ceil(scale);
+
+    Bytes::new::<bytes::byte>(value.try_into().ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_memory_quantity_works() {
+        fn b(value: u64) -> Option<Bytes> {
+            Bytes::new::<bytes::byte>(value)
+        }
+
+        assert_eq!(parse_memory_quantity("123"), b(123));
+        assert_eq!(parse_memory_quantity("1k"), b(1000));
+        assert_eq!(parse_memory_quantity("16Gi"), b(16 << 30));
+        assert_eq!(parse_memory_quantity("500M"), b(500_000_000));
+        assert_eq!(parse_memory_quantity("1.5Gi"), b(3 << 29));
+        assert_eq!(parse_memory_quantity(".5Ki"), b(512));
+        assert_eq!(parse_memory_quantity(""), None);
+        assert_eq!(parse_memory_quantity("Gi"), None);
+        assert_eq!(parse_memory_quantity("1Xi"), None);
+        assert_eq!(parse_memory_quantity("1.2.3"), None);
+    }
+}
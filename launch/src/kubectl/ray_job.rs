@@ -1,6 +1,6 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use super::ResourceMetadata;
+use super::{PodOwner, ResourceMetadata};
 
 #[derive(Debug, Deserialize)]
 /// https://github.com/ray-project/kuberay/blob/master/docs/reference/api.md#rayjob
@@ -10,24 +10,38 @@ pub struct RayJob {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
 pub struct RayJobStatus {
     #[serde(rename = "jobId")]
     pub job_id: String,
 
-    #[serde(rename = "jobStatus", default)]
+    #[serde(rename = "jobStatus", default, skip_serializing_if = "Option::is_none")]
     pub job_status: Option<String>,
 
     #[serde(rename = "jobDeploymentStatus")]
     pub job_deployment_status: String,
 
-    #[serde(rename = "startTime", default, with = "time::serde::rfc3339::option")]
+    #[serde(
+        rename = "startTime",
+        default,
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub start_time: Option<time::OffsetDateTime>,
 
-    #[serde(rename = "endTime", default, with = "time::serde::rfc3339::option")]
+    #[serde(
+        rename = "endTime",
+        default,
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub end_time: Option<time::OffsetDateTime>,
 
-    #[serde(rename = "rayClusterName", default)]
+    #[serde(
+        rename = "rayClusterName",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
     pub ray_cluster_name: Option<String>,
 
     #[serde(rename = "rayClusterStatus")]
@@ -35,15 +49,106 @@ pub struct RayJobStatus {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
 pub struct RayJobStatusRayClusterStatus {
-    #[serde(rename = "state", default)]
+    #[serde(rename = "state", default, skip_serializing_if = "Option::is_none")]
     pub state: Option<String>,
 
     #[serde(
         rename = "lastUpdateTime",
         default,
-        with = "time::serde::rfc3339::option"
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
     )]
     pub last_update_time: Option<time::OffsetDateTime>,
 }
+
+/// Returns the name of the RayCluster that kuberay created for the RayJob, which is only known once kuberay has
+/// reconciled the RayJob. Takes the RayJob as JSON because [`RayJob`] requires fields that a new RayJob does not have
+/// yet.
+pub fn ray_cluster_name(ray_job: &serde_json::Value) -> Option<&str> {
+    ray_job
+        .pointer("/status/rayClusterName")
+        .and_then(serde_json::Value::as_str)
+        .filter(|name| !name.is_empty())
+}
+
+/// Returns the submission ID that the submitter passes to `ray job submit`, which kuberay sets once it has reconciled
+/// the RayJob. The Ray dashboard identifies jobs by this ID.
+pub fn ray_job_submission_id(ray_job: &serde_json::Value) -> Option<&str> {
+    ray_job
+        .pointer("/status/jobId")
+        .and_then(serde_json::Value::as_str)
+        .filter(|id| !id.is_empty())
+}
+
+/// The label selector of the head Pod of a RayCluster.
+pub fn head_pod_selector(ray_cluster_name: &str) -> String {
+    format!(
+        "{},ray.io/node-type=head",
+        PodOwner::RayCluster(ray_cluster_name).selector()
+    )
+}
+
+/// The label selector of the worker Pods of a RayCluster.
+pub fn worker_pod_selector(ray_cluster_name: &str) -> String {
+    format!(
+        "{},ray.io/node-type=worker",
+        PodOwner::RayCluster(ray_cluster_name).selector()
+    )
+}
+
+/// The label that kuberay puts on the worker Pods with the name of their worker group.
+pub const WORKER_GROUP_LABEL: &str = "ray.io/group";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ray_cluster_name_works() {
+        let ray_job = serde_json::json!({
+            "metadata": { "name": "ray-job-abc" },
+            "status": {
+                "jobDeploymentStatus": "Initializing",
+                "rayClusterName": "ray-job-abc-raycluster-lq9vd",
+            }
+        });
+        assert_eq!(
+            ray_cluster_name(&ray_job),
+            Some("ray-job-abc-raycluster-lq9vd")
+        );
+
+        let ray_job = serde_json::json!({ "metadata": { "name": "ray-job-abc" } });
+        assert_eq!(ray_cluster_name(&ray_job), None);
+
+        let ray_job = serde_json::json!({
+            "metadata": { "name": "ray-job-abc" },
+            "status": { "rayClusterName": "" }
+        });
+        assert_eq!(ray_cluster_name(&ray_job), None);
+    }
+
+    #[test]
+    fn ray_job_submission_id_works() {
+        let ray_job = serde_json::json!({
+            "metadata": { "name": "ray-job-abc" },
+            "status": {
+                "jobDeploymentStatus": "Running",
+                "jobId": "ray-job-abc-x7k2p",
+            }
+        });
+        assert_eq!(ray_job_submission_id(&ray_job), Some("ray-job-abc-x7k2p"));
+
+        let ray_job = serde_json::json!({ "metadata": { "name": "ray-job-abc" } });
+        assert_eq!(ray_job_submission_id(&ray_job), None);
+    }
+
+    #[test]
+    fn head_pod_selector_works() {
+        assert_eq!(
+            head_pod_selector("ray-job-abc-raycluster-lq9vd"),
+            "ray.io/cluster=ray-job-abc-raycluster-lq9vd,ray.io/node-type=head"
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use crate::unit::bytes::Bytes;
+
+/// Resource usage of a single container as reported by `kubectl top pod --containers`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ContainerUsage {
+    pub container: String,
+    pub memory: Option<Bytes>,
+}
+
+/// Parses the output of `kubectl top pod --containers --no-headers` which has the columns `POD NAME CPU(cores)
+/// MEMORY(bytes)`.
+pub fn parse_top_pod_containers(output: &str) -> Vec<ContainerUsage> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let _pod = columns.next()?;
+            let container = columns.next()?;
+            let _cpu = columns.next()?;
+            let memory = columns.next()?;
+            Some(ContainerUsage {
+                container: container.to_owned(),
+                memory: super::parse_memory_quantity(memory),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unit::bytes;
+
+    #[test]
+    fn parse_top_pod_containers_works() {
+        assert_eq!(
+            parse_top_pod_containers("job-abc-xyz   main   950m   15871Mi\n"),
+            vec![ContainerUsage {
+                container: "main".to_owned(),
+                memory: Bytes::new::<bytes::mebibyte>(15871),
+            }]
+        );
+        assert_eq!(parse_top_pod_containers(""), vec![]);
+    }
+}
@@ -0,0 +1,291 @@
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::Result;
+
+/// The oldest kubectl that launch supports, as `(major, minor)`. `--dry-run=server`, which `launch submit --dry-run`
+/// relies on, replaced `--server-dry-run` in 1.18, and kubectl 1.19 fails on the output and flags launch uses in
+/// ways that are hard to tell apart from other errors.
+pub const MIN_CLIENT_VERSION: (u32, u32) = (1, 20);
+
+/// The output of `kubectl version --output=json`. The server version is missing when the server is unreachable.
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Versions {
+    #[serde(default)]
+    pub client_version: Option<VersionInfo>,
+    #[serde(default)]
+    pub server_version: Option<VersionInfo>,
+}
+
+/// Partially implements [Info](https://pkg.go.dev/k8s.io/apimachinery/pkg/version#Info).
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionInfo {
+    #[serde(default)]
+    pub major: String,
+    #[serde(default)]
+    pub minor: String,
+    /// The version including the `v` prefix and build metadata, for example `v1.31.1+k3s1`.
+    pub git_version: String,
+}
+
+impl VersionInfo {
+    /// Returns the major and minor version. Some distributions append a `+` to the minor version, as in `"32+"`, and
+    /// some leave the fields empty, in which case they are taken from the git version.
+    pub fn major_minor(&self) -> Option<(u32, u32)> {
+        let parse = |value: &str| value.trim_end_matches('+').parse().ok();
+        if let (Some(major), Some(minor)) = (parse(&self.major), parse(&self.minor)) {
+            return Some((major, minor));
+        }
+        let mut parts = self.git_version.strip_prefix('v')?.split(['.', '-', '+']);
+        Some((parse(parts.next()?)?, parse(parts.next()?)?))
+    }
+}
+
+/// The optional flags of kubectl that launch can use, depending on the version of the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    /// `kubectl get --chunk-size`, which lists large collections in pages, from 1.9 on.
+    pub chunk_size: bool,
+    /// `kubectl apply --server-side`, which is generally available from 1.22 on.
+    pub server_side_apply: bool,
+}
+
+impl Capabilities {
+    pub fn of(version: (u32, u32)) -> Self {
+        Self {
+            chunk_size: version >= (1, 9),
+            server_side_apply: version >= (1, 22),
+        }
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            chunk_size,
+            server_side_apply,
+        } = *self;
+        let yes_no = |value| if value { "yes" } else { "no" };
+        write!(
+            f,
+            "--chunk-size: {}, --server-side apply: {}",
+            yes_no(chunk_size),
+            yes_no(server_side_apply)
+        )
+    }
+}
+
+/// The version of the kubectl client and what it supports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientVersion {
+    /// The version as kubectl reports it, for example `v1.31.1`.
+    pub git_version: String,
+    pub major_minor: (u32, u32),
+}
+
+impl ClientVersion {
+    pub fn from_versions(versions: Versions) -> Result<Self> {
+        let info = versions
+            .client_version
+            .ok_or("kubectl did not report its version")?;
+        let major_minor = info
+            .major_minor()
+            .ok_or_else(|| format!("Unable to parse the kubectl version {:?}", info.git_version))?;
+        Ok(Self {
+            git_version: info.git_version,
+            major_minor,
+        })
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities::of(self.major_minor)
+    }
+
+    /// Fails with a message that names the found and the required version when kubectl is older than
+    /// [`MIN_CLIENT_VERSION`].
+    pub fn check_supported(&self) -> Result<()> {
+        if self.major_minor >= MIN_CLIENT_VERSION {
+            return Ok(());
+        }
+        let (major, minor) = MIN_CLIENT_VERSION;
+        Err(format!(
+            "kubectl {} is too old, launch requires kubectl {major}.{minor} or newer. Upgrade kubectl, see https://kubernetes.io/docs/tasks/tools/#kubectl",
+            self.git_version
+        )
+        .into())
+    }
+}
+
+/// Parses the output of `kubectl version --output=json`. Older versions of kubectl also print a `kustomizeVersion`,
+/// and all versions omit `serverVersion` when the server can not be reached.
+pub fn parse_versions(output: &[u8]) -> Result<Versions> {
+    Ok(serde_json::from_slice(output)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_versions_works() {
+        // kubectl 1.27
+        let versions = parse_versions(
+            br#"{
+                "clientVersion": {
+                    "major": "1",
+                    "minor": "27",
+                    "gitVersion": "v1.27.4",
+                    "gitCommit": "fa3d7990104d7c1f16943a67f11b154b71f6a132",
+                    "gitTreeState": "clean",
+                    "buildDate": "2023-07-19T12:20:54Z",
+                    "goVersion": "go1.20.6",
+                    "compiler": "gc",
+                    "platform": "linux/amd64"
+                },
+                "kustomizeVersion": "v5.0.1",
+                "serverVersion": {
+                    "major": "1",
+                    "minor": "29",
+                    "gitVersion": "v1.29.3+k3s1",
+                    "gitCommit": "e7f2f9a8b3b5e6c1c9d8d5f4c3b2a1908f7e6d5c",
+                    "gitTreeState": "clean",
+                    "buildDate": "2024-03-25T20:30:17Z",
+                    "goVersion": "go1.21.8",
+                    "compiler": "gc",
+                    "platform": "linux/amd64"
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(versions.client_version.unwrap().git_version, "v1.27.4");
+        let server_version = versions.server_version.unwrap();
+        assert_eq!(server_version.git_version, "v1.29.3+k3s1");
+        assert_eq!(
+            (server_version.major.as_str(), server_version.minor.as_str()),
+            ("1", "29")
+        );
+
+        // kubectl 1.31
+        let versions = parse_versions(
+            br#"{
+                "clientVersion": {
+                    "major": "1",
+                    "minor": "31",
+                    "gitVersion": "v1.31.1",
+                    "gitCommit": "948afe5ca072329a73c8e79ed5938717a5cb3d21",
+                    "gitTreeState": "clean",
+                    "buildDate": "2024-09-11T21:28:49Z",
+                    "goVersion": "go1.22.6",
+                    "compiler": "gc",
+                    "platform": "darwin/arm64"
+                },
+                "serverVersion": {
+                    "major": "1",
+                    "minor": "31",
+                    "gitVersion": "v1.31.0",
+                    "gitCommit": "9edcffcde5595e8a5b1a35f88c421764e575afce",
+                    "gitTreeState": "clean",
+                    "buildDate": "2024-08-13T07:28:49Z",
+                    "goVersion": "go1.22.5",
+                    "compiler": "gc",
+                    "platform": "linux/amd64"
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(versions.client_version.unwrap().git_version, "v1.31.1");
+        assert_eq!(versions.server_version.unwrap().git_version, "v1.31.0");
+
+        // The server is unreachable.
+        let versions = parse_versions(
+            br#"{
+                "clientVersion": {
+                    "major": "1",
+                    "minor": "31",
+                    "gitVersion": "v1.31.1"
+                }
+            }"#,
+        )
+        .unwrap();
+        assert!(versions.client_version.is_some());
+        assert_eq!(versions.server_version, None);
+
+        assert!(parse_versions(b"").is_err());
+    }
+
+    fn client_version(major: &str, minor: &str, git_version: &str) -> Result<ClientVersion> {
+        ClientVersion::from_versions(Versions {
+            client_version: Some(VersionInfo {
+                major: major.to_owned(),
+                minor: minor.to_owned(),
+                git_version: git_version.to_owned(),
+            }),
+            server_version: None,
+        })
+    }
+
+    #[test]
+    fn client_version_works() {
+        let major_minor = |major, minor, git_version| {
+            client_version(major, minor, git_version)
+                .ok()
+                .map(|version| version.major_minor)
+        };
+        assert_eq!(major_minor("1", "31", "v1.31.1"), Some((1, 31)));
+        assert_eq!(major_minor("1", "32+", "v1.32.4-dispatcher"), Some((1, 32)));
+        assert_eq!(major_minor("", "", "v1.29.3+k3s1"), Some((1, 29)));
+        assert_eq!(major_minor("", "", "v1.30.0-eks-036c24b"), Some((1, 30)));
+        assert_eq!(major_minor("", "", "unknown"), None);
+        assert!(ClientVersion::from_versions(Versions::default()).is_err());
+    }
+
+    #[test]
+    fn check_supported_works() {
+        assert!(client_version("1", "20", "v1.20.0")
+            .unwrap()
+            .check_supported()
+            .is_ok());
+        assert!(client_version("1", "31", "v1.31.1")
+            .unwrap()
+            .check_supported()
+            .is_ok());
+        assert_eq!(
+            client_version("1", "19", "v1.19.16")
+                .unwrap()
+                .check_supported()
+                .unwrap_err()
+                .to_string(),
+            "kubectl v1.19.16 is too old, launch requires kubectl 1.20 or newer. Upgrade kubectl, see https://kubernetes.io/docs/tasks/tools/#kubectl"
+        );
+    }
+
+    #[test]
+    fn capabilities_work() {
+        for (version, chunk_size, server_side_apply) in [
+            ((1, 8), false, false),
+            ((1, 9), true, false),
+            ((1, 20), true, false),
+            ((1, 21), true, false),
+            ((1, 22), true, true),
+            ((1, 31), true, true),
+            ((2, 0), true, true),
+        ] {
+            assert_eq!(
+                Capabilities::of(version),
+                Capabilities {
+                    chunk_size,
+                    server_side_apply
+                },
+                "{version:?}"
+            );
+        }
+        assert_eq!(
+            Capabilities::of((1, 21)).to_string(),
+            "--chunk-size: yes, --server-side apply: no"
+        );
+    }
+}
@@ -1,19 +1,34 @@
 pub(crate) mod ansi;
 pub(crate) mod bash_escape;
 pub(crate) mod builder;
+pub(crate) mod databricks;
+pub(crate) mod diff;
 pub(crate) mod docker;
 pub(crate) mod executor;
 pub(crate) mod git;
+pub(crate) mod http;
 pub(crate) mod katib;
 pub(crate) mod kubectl;
+pub(crate) mod notify;
 pub(crate) mod process;
+pub(crate) mod ray_dashboard;
+pub(crate) mod registry;
 pub(crate) mod tailscale;
 pub(crate) mod temp_path;
+pub(crate) mod text_config;
+pub(crate) mod ui;
 pub(crate) mod unit;
+pub(crate) mod urls;
 pub(crate) mod user_host;
 pub(crate) mod version;
 
 pub mod cli;
 
+/// The internals that the integration tests in `tests/` use, which can only reach the public API.
+#[doc(hidden)]
+pub mod test_support {
+    pub use crate::kubectl::for_each_item;
+}
+
 pub(crate) type Result<T, E = Box<dyn std::error::Error + Send + Sync + 'static>> =
     std::result::Result<T, E>;
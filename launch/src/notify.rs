@@ -0,0 +1,271 @@
+//! Completion notifications through webhooks, such as [Slack incoming
+//! webhooks](https://api.slack.com/messaging/webhooks).
+
+use std::{fmt::Write as _, thread, time::Duration};
+
+use log::{debug, info, warn};
+
+use crate::{executor::Completion, http::Http, Result};
+
+const ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// The JSON body that is posted to the webhook. The `text` field is what Slack displays, the other fields are there for
+/// consumers that want to process the notification.
+#[derive(Debug, serde::Serialize)]
+pub struct Payload<'a> {
+    pub text: String,
+    pub kind: &'a str,
+    pub namespace: &'a str,
+    pub name: &'a str,
+    pub status: &'a str,
+    pub duration_seconds: Option<u64>,
+    pub url: &'a str,
+}
+
+impl<'a> Payload<'a> {
+    pub fn new(
+        kind: &'a str,
+        namespace: &'a str,
+        name: &'a str,
+        completion: &'a Completion,
+        url: &'a str,
+    ) -> Self {
+        let status = completion.status.as_str();
+
+        let mut text = format!("{kind} {namespace}/{name} {status}");
+        if let Some(duration) = completion.duration {
+            write!(text, " after {}", format_duration(duration)).unwrap();
+        }
+        if let Some(message) = completion.message.as_deref() {
+            write!(text, ": {message}").unwrap();
+        }
+        write!(text, " ({url})").unwrap();
+
+        Self {
+            text,
+            kind,
+            namespace,
+            name,
+            status,
+            duration_seconds: completion.duration.map(|duration| duration.as_secs()),
+            url,
+        }
+    }
+}
+
+/// Formats a duration as hours, minutes and seconds, omitting leading zero components.
+fn format_duration(duration: Duration) -> String {
+    let total = duration.as_secs();
+    let (hours, minutes, seconds) = (total / 3600, total / 60 % 60, total % 60);
+    if hours > 0 {
+        format!("{hours}h {minutes}m {seconds}s")
+    } else if minutes > 0 {
+        format!("{minutes}m {seconds}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+/// Webhook URLs usually embed a secret in their path, so only the scheme and authority can be logged safely.
+pub fn redact_url(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(url) => {
+            let mut out = format!("{}://{}", url.scheme(), url.host_str().unwrap_or_default());
+            if let Some(port) = url.port() {
+                write!(out, ":{port}").unwrap();
+            }
+            out.push_str("/<redacted>");
+            out
+        }
+        Err(_) => "<redacted>".to_owned(),
+    }
+}
+
+/// Posts the payload to the webhook, retrying transient failures.
+pub fn notify(client: &Http, url: &str, payload: &Payload) -> Result<()> {
+    post_with_retries(client, url, payload, ATTEMPTS, RETRY_DELAY)
+}
+
+fn post_with_retries(
+    client: &Http,
+    url: &str,
+    payload: &Payload,
+    attempts: u32,
+    retry_delay: Duration,
+) -> Result<()> {
+    let redacted_url = redact_url(url);
+    let body = serde_json::to_vec(payload)?;
+
+    for attempt in 1..=attempts {
+        debug!("Posting notification to {redacted_url} (attempt {attempt}/{attempts})...");
+
+        let error = match client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .body(body.clone())
+            .send()
+        {
+            Ok(response) => {
+                let status = response.status();
+                if status.is_success() {
+                    info!("Sent notification to {redacted_url}");
+                    return Ok(());
+                }
+                let error = format!("webhook responded with status {status}");
+                if !(status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS) {
+                    return Err(format!("Failed to notify {redacted_url}: {error}").into());
+                }
+                error
+            }
+            Err(error) => client.describe_error(error),
+        };
+
+        if attempt < attempts {
+            warn!("Failed to notify {redacted_url}, retrying: {error}");
+            thread::sleep(retry_delay);
+        } else {
+            return Err(format!(
+                "Failed to notify {redacted_url} after {attempts} attempts: {error}"
+            )
+            .into());
+        }
+    }
+
+    unreachable!("the loop returns on the last attempt")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+    };
+
+    use super::*;
+    use crate::executor::TerminalStatus;
+
+    /// Serves one connection per status code and returns the url and the received request bodies.
+    fn serve(statuses: &'static [u16]) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/services/secret", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            statuses
+                .iter()
+                .map(|status| {
+                    let (stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream);
+                    let mut content_length = 0;
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        let line = line.trim_end();
+                        if line.is_empty() {
+                            break;
+                        }
+                        if let Some((key, value)) = line.split_once(':') {
+                            if key.eq_ignore_ascii_case("content-length") {
+                                content_length = value.trim().parse().unwrap();
+                            }
+                        }
+                    }
+                    let mut body = vec![0; content_length];
+                    reader.read_exact(&mut body).unwrap();
+                    write!(
+                        reader.get_mut(),
+                        "HTTP/1.1 {status} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    )
+                    .unwrap();
+                    String::from_utf8(body).unwrap()
+                })
+                .collect()
+        });
+        (url, handle)
+    }
+
+    fn completion() -> Completion {
+        Completion {
+            status: TerminalStatus::Succeeded,
+            message: None,
+            duration: Some(Duration::from_secs(3723)),
+        }
+    }
+
+    #[test]
+    fn payload_works() {
+        let completion = completion();
+        let payload = Payload::new("Job", "launch", "job-abc", &completion, "https://h/jobs/x");
+        assert_eq!(
+            payload.text,
+            "Job launch/job-abc succeeded after 1h 2m 3s (https://h/jobs/x)"
+        );
+        assert_eq!(
+            serde_json::to_value(&payload).unwrap(),
+            serde_json::json!({
+                "text": "Job launch/job-abc succeeded after 1h 2m 3s (https://h/jobs/x)",
+                "kind": "Job",
+                "namespace": "launch",
+                "name": "job-abc",
+                "status": "succeeded",
+                "duration_seconds": 3723,
+                "url": "https://h/jobs/x",
+            })
+        );
+    }
+
+    #[test]
+    fn format_duration_works() {
+        assert_eq!(format_duration(Duration::from_secs(0)), "0s");
+        assert_eq!(format_duration(Duration::from_secs(61)), "1m 1s");
+        assert_eq!(format_duration(Duration::from_secs(3600)), "1h 0m 0s");
+    }
+
+    #[test]
+    fn redact_url_works() {
+        assert_eq!(
+            redact_url("https://hooks.slack.com/services/T000/B000/XXXX"),
+            "https://hooks.slack.com/<redacted>"
+        );
+        assert_eq!(
+            redact_url("http://localhost:8080/hook?token=secret"),
+            "http://localhost:8080/<redacted>"
+        );
+        assert_eq!(redact_url("not a url"), "<redacted>");
+    }
+
+    #[test]
+    fn retries_transient_failures() {
+        let (url, handle) = serve(&[503, 200]);
+        let completion = completion();
+        let payload = Payload::new("Job", "launch", "job-abc", &completion, "u");
+        let client = Http::new(&Default::default(), Default::default()).unwrap();
+        post_with_retries(&client, &url, &payload, 3, Duration::ZERO).unwrap();
+        let bodies = handle.join().unwrap();
+        assert_eq!(bodies.len(), 2);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&bodies[1]).unwrap()["name"],
+            "job-abc"
+        );
+    }
+
+    #[test]
+    fn gives_up_after_attempts() {
+        let (url, handle) = serve(&[500, 502, 503]);
+        let completion = completion();
+        let payload = Payload::new("Job", "launch", "job-abc", &completion, "u");
+        let client = Http::new(&Default::default(), Default::default()).unwrap();
+        let error = post_with_retries(&client, &url, &payload, 3, Duration::ZERO).unwrap_err();
+        assert_eq!(handle.join().unwrap().len(), 3);
+        assert!(!error.to_string().contains("secret"));
+    }
+
+    #[test]
+    fn does_not_retry_client_errors() {
+        let (url, handle) = serve(&[404]);
+        let completion = completion();
+        let payload = Payload::new("Job", "launch", "job-abc", &completion, "u");
+        let client = Http::new(&Default::default(), Default::default()).unwrap();
+        assert!(post_with_retries(&client, &url, &payload, 3, Duration::ZERO).is_err());
+        assert_eq!(handle.join().unwrap().len(), 1);
+    }
+}
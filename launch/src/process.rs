@@ -1,34 +1,94 @@
-use std::{ffi::OsStr, fmt, io, num::NonZeroI32, process};
+use std::{
+    ffi::OsStr,
+    fmt, io,
+    num::NonZeroI32,
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
 use log::debug;
 
-pub struct Command(process::Command);
+/// How often to check whether a command with a timeout has exited.
+const TIMEOUT_POLLING_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How much of stderr is kept by the commands whose stdout is streamed, which only need it for the error.
+const STDERR_LIMIT: usize = 64 * 1024;
+
+static STDOUT_RESERVED: AtomicBool = AtomicBool::new(false);
+
+/// Keeps stdout for the output that is meant for programs, such as `launch submit --output json`, for the rest of the
+/// process. The commands run with [`Command::status`] print to stderr instead, and so does [`output_stream`].
+pub fn reserve_stdout() {
+    STDOUT_RESERVED.store(true, Ordering::Relaxed);
+}
+
+/// Where the output of the submitted work, such as its logs, and the generated manifests are printed: stdout, or stderr
+/// once [`reserve_stdout`] was called.
+pub fn output_stream() -> Box<dyn io::Write> {
+    if STDOUT_RESERVED.load(Ordering::Relaxed) {
+        Box::new(io::stderr())
+    } else {
+        Box::new(io::stdout())
+    }
+}
+
+pub struct Command {
+    inner: process::Command,
+    timeout: Option<Duration>,
+    output_limit: Option<usize>,
+}
 
 impl fmt::Debug for Command {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.0.fmt(f)
+        self.inner.fmt(f)
     }
 }
 
 impl Command {
     pub fn new<S: AsRef<OsStr>>(program: S) -> Self {
-        Self(process::Command::new(program))
+        Self {
+            inner: process::Command::new(program),
+            timeout: None,
+            output_limit: None,
+        }
+    }
+
+    /// Kills the command when it runs longer than `timeout`, which fails [`Command::try_output`] and
+    /// [`Command::output`] with [`ErrorKind::TimedOut`].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Keeps at most `limit` bytes of stdout and of stderr in [`Command::try_output`] and [`Command::output`]. The rest
+    /// is read and discarded, and a line that says how much was discarded is appended.
+    pub fn output_limit(mut self, limit: usize) -> Self {
+        self.output_limit = Some(limit);
+        self
     }
 
     pub fn args<'a, I>(mut self, args: I) -> Self
     where
         I: IntoIterator<Item = &'a OsStr>,
     {
-        self.0.args(args);
+        self.inner.args(args);
         self
     }
 
     pub fn try_status(mut self) -> Result<ExitStatus, Error> {
         if log::log_enabled!(log::Level::Debug) {
-            debug!("running `{command:?}`...", command = &self.0);
+            debug!("running `{command:?}`...", command = &self.inner);
         }
 
-        match self.0.status() {
+        if STDOUT_RESERVED.load(Ordering::Relaxed) {
+            self.inner.stdout(io::stderr());
+        }
+        match self.inner.status() {
             Ok(status) => Ok(ExitStatus {
                 command: self,
                 status,
@@ -44,12 +104,137 @@ impl Command {
         self.try_status().and_then(ExitStatus::require_success)
     }
 
+    /// Like [`Command::status`], but stdout is captured and passed to `on_line` line by line as it is produced. The
+    /// command is killed when reading stdout or `on_line` fails, and the error is returned.
+    pub fn status_with_stdout_lines(
+        mut self,
+        mut on_line: impl FnMut(&str) -> io::Result<()>,
+    ) -> Result<(), Error> {
+        use io::BufRead;
+
+        if log::log_enabled!(log::Level::Debug) {
+            debug!("streaming `{command:?}`...", command = &self.inner);
+        }
+
+        let mut child = match self.inner.stdout(process::Stdio::piped()).spawn() {
+            Ok(child) => child,
+            Err(error) => {
+                return Err(Error {
+                    command: self,
+                    kind: error.into(),
+                })
+            }
+        };
+
+        let mut stdout = io::BufReader::new(child.stdout.take().expect("Failed to open stdout"));
+        let mut line = Vec::new();
+        let read = loop {
+            line.clear();
+            match stdout.read_until(b'\n', &mut line) {
+                Ok(0) => break Ok(()),
+                Ok(_) => {
+                    let line = String::from_utf8_lossy(&line);
+                    if let Err(error) = on_line(line.trim_end_matches(['\n', '\r'])) {
+                        break Err(error);
+                    }
+                }
+                Err(error) => break Err(error),
+            }
+        };
+        if let Err(error) = read {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(Error {
+                command: self,
+                kind: ErrorKind::Output(error),
+            });
+        }
+
+        let status = child.wait().expect("Failed to wait for child");
+        ExitStatus {
+            command: self,
+            status,
+        }
+        .require_success()
+    }
+
+    /// Like [`Command::status`], but stdout is passed to `read` as it is produced instead of being buffered, for output
+    /// that is too large to hold in memory. The result of `read` is returned when the command succeeds. stderr is
+    /// captured like [`Command::output`] does, and is part of the error when the command fails.
+    pub fn status_with_stdout<T>(
+        mut self,
+        read: impl FnOnce(&mut dyn io::Read) -> T,
+    ) -> Result<T, Error> {
+        if log::log_enabled!(log::Level::Debug) {
+            debug!("streaming `{command:?}`...", command = &self.inner);
+        }
+
+        let mut child = match self
+            .inner
+            .stdout(process::Stdio::piped())
+            .stderr(process::Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(error) => {
+                return Err(Error {
+                    command: self,
+                    kind: error.into(),
+                })
+            }
+        };
+
+        // Read on another thread, so that the command can not block on writing to a full stderr pipe.
+        let stderr = Capture::spawn(
+            child.stderr.take().expect("Failed to open stderr"),
+            Some(STDERR_LIMIT),
+        );
+        let value = {
+            let mut stdout = child.stdout.take().expect("Failed to open stdout");
+            read(&mut stdout)
+            // Dropping stdout closes the pipe so that the command can not block on writing output that is not read.
+        };
+
+        let status = child.wait().expect("Failed to wait for child");
+        let stderr = stderr.join();
+        if !status.success() {
+            return Err(Error {
+                command: self,
+                kind: ErrorKind::Failed {
+                    code: status.code().and_then(NonZeroI32::new),
+                    stderr,
+                },
+            });
+        }
+        Ok(value)
+    }
+
+    /// Starts the command without waiting for it with stdout captured, for commands that keep running in the
+    /// background.
+    pub fn spawn_with_piped_stdout(mut self) -> Result<process::Child, Error> {
+        if log::log_enabled!(log::Level::Debug) {
+            debug!("spawning `{command:?}`...", command = &self.inner);
+        }
+
+        self.inner
+            .stdout(process::Stdio::piped())
+            .spawn()
+            .map_err(|error| Error {
+                command: self,
+                kind: error.into(),
+            })
+    }
+
     pub fn try_output(mut self) -> Result<Output, Error> {
         if log::log_enabled!(log::Level::Debug) {
-            debug!("capturing `{command:?}`...", command = &self.0);
+            debug!("capturing `{command:?}`...", command = &self.inner);
+        }
+
+        if self.timeout.is_some() || self.output_limit.is_some() {
+            return self.try_output_with_limits();
         }
 
-        match self.0.output() {
+        match self.inner.output() {
             Ok(output) => Ok(Output {
                 command: self,
                 output,
@@ -61,17 +246,81 @@ impl Command {
         }
     }
 
+    /// Implements [`Command::try_output`] for a command with a timeout or an output limit.
+    fn try_output_with_limits(mut self) -> Result<Output, Error> {
+        let mut child = match self
+            .inner
+            .stdin(process::Stdio::null())
+            .stdout(process::Stdio::piped())
+            .stderr(process::Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(error) => {
+                return Err(Error {
+                    command: self,
+                    kind: error.into(),
+                })
+            }
+        };
+
+        let stdout = Capture::spawn(
+            child.stdout.take().expect("Failed to open stdout"),
+            self.output_limit,
+        );
+        let stderr = Capture::spawn(
+            child.stderr.take().expect("Failed to open stderr"),
+            self.output_limit,
+        );
+
+        let status = match self.timeout {
+            Some(timeout) => {
+                let deadline = Instant::now() + timeout;
+                loop {
+                    if let Some(status) = child.try_wait().expect("Failed to wait for child") {
+                        break status;
+                    }
+                    let now = Instant::now();
+                    if now >= deadline {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        // Processes spawned by the command may keep the pipes open, so the output is taken without
+                        // waiting for the end of the streams.
+                        return Err(Error {
+                            command: self,
+                            kind: ErrorKind::TimedOut {
+                                timeout,
+                                stderr: stderr.snapshot(),
+                            },
+                        });
+                    }
+                    thread::sleep(TIMEOUT_POLLING_INTERVAL.min(deadline - now));
+                }
+            }
+            None => child.wait().expect("Failed to wait for child"),
+        };
+
+        Ok(Output {
+            command: self,
+            output: process::Output {
+                status,
+                stdout: stdout.join(),
+                stderr: stderr.join(),
+            },
+        })
+    }
+
     pub fn output(self) -> Result<Output, Error> {
         self.try_output().and_then(Output::require_success)
     }
 
     pub fn output_with_input(mut self, input: Vec<u8>) -> Result<Output, Error> {
         if log::log_enabled!(log::Level::Debug) {
-            debug!("capturing `{command:?}`...", command = &self.0);
+            debug!("capturing `{command:?}`...", command = &self.inner);
         }
 
         let mut child = match self
-            .0
+            .inner
             .stdin(process::Stdio::piped())
             .stdout(process::Stdio::piped())
             .stderr(process::Stdio::piped())
@@ -106,6 +355,68 @@ impl Command {
     }
 }
 
+/// Reads a stream on a separate thread and keeps at most `limit` bytes of it.
+struct Capture {
+    thread: thread::JoinHandle<()>,
+    captured: Arc<Mutex<Captured>>,
+}
+
+#[derive(Default)]
+struct Captured {
+    bytes: Vec<u8>,
+    discarded: usize,
+}
+
+impl Captured {
+    fn to_output(&self) -> Vec<u8> {
+        let mut output = self.bytes.clone();
+        if self.discarded > 0 {
+            output.extend_from_slice(
+                format!("\n[{} more bytes were discarded]\n", self.discarded).as_bytes(),
+            );
+        }
+        output
+    }
+}
+
+impl Capture {
+    fn spawn(mut stream: impl io::Read + Send + 'static, limit: Option<usize>) -> Self {
+        let captured = Arc::new(Mutex::new(Captured::default()));
+        let thread = thread::spawn({
+            let captured = Arc::clone(&captured);
+            move || {
+                let mut buffer = [0; 8192];
+                loop {
+                    let len = match stream.read(&mut buffer) {
+                        Ok(0) => break,
+                        Ok(len) => len,
+                        Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(_) => break,
+                    };
+                    let mut captured = captured.lock().unwrap();
+                    let kept = limit.map_or(len, |limit| {
+                        limit.saturating_sub(captured.bytes.len()).min(len)
+                    });
+                    captured.bytes.extend_from_slice(&buffer[..kept]);
+                    captured.discarded += len - kept;
+                }
+            }
+        });
+        Self { thread, captured }
+    }
+
+    /// Returns what has been read so far.
+    fn snapshot(&self) -> Vec<u8> {
+        self.captured.lock().unwrap().to_output()
+    }
+
+    /// Waits for the end of the stream and returns what has been read.
+    fn join(self) -> Vec<u8> {
+        self.thread.join().expect("Thread reading output panicked");
+        self.captured.lock().unwrap().to_output()
+    }
+}
+
 #[derive(Debug)]
 pub struct ExitStatus {
     command: Command,
@@ -159,6 +470,19 @@ pub enum ErrorKind {
     NotFound,
     PermissionDenied,
     NonZeroExitStatus(Option<NonZeroI32>),
+    /// Like [`ErrorKind::NonZeroExitStatus`], along with what the command printed to stderr.
+    Failed {
+        code: Option<NonZeroI32>,
+        stderr: Vec<u8>,
+    },
+    /// The command was killed because its output could not be read or handled.
+    Output(io::Error),
+    /// The command was killed because it ran longer than the timeout. Contains what it printed to stderr up to that
+    /// point.
+    TimedOut {
+        timeout: Duration,
+        stderr: Vec<u8>,
+    },
 }
 
 impl From<io::Error> for ErrorKind {
@@ -185,28 +509,46 @@ impl fmt::Display for Error {
         write!(
             f,
             "failed to run `{command:?}`: ",
-            command = &self.command.0
+            command = &self.command.inner
         )?;
-        match self.kind {
+        match &self.kind {
             ErrorKind::NotFound => {
-                let program = self.command.0.get_program().to_string_lossy();
+                let program = self.command.inner.get_program().to_string_lossy();
                 write!(f, "the `{program}` command is required but not available on your system, please install it")
             }
             ErrorKind::PermissionDenied => {
-                let program = self.command.0.get_program().to_string_lossy();
+                let program = self.command.inner.get_program().to_string_lossy();
                 write!(f, "the `{program}` command is available but does not have the right permissions, please make sure the binary is executable")
             }
-            ErrorKind::NonZeroExitStatus(code) => {
-                if let Some(code) = code {
-                    write!(f, "exited with non-zero exit code `{code}`")
-                } else {
-                    write!(f, "did not run succesfully")
+            ErrorKind::NonZeroExitStatus(code) => write_exit_code(f, *code),
+            ErrorKind::Failed { code, stderr } => {
+                write_exit_code(f, *code)?;
+                let stderr = String::from_utf8_lossy(stderr);
+                match stderr.trim() {
+                    "" => Ok(()),
+                    stderr => write!(f, ", stderr: {stderr}"),
+                }
+            }
+            ErrorKind::Output(error) => write!(f, "failed to handle its output: {error}"),
+            ErrorKind::TimedOut { timeout, stderr } => {
+                write!(f, "timed out after {timeout:?}")?;
+                let stderr = String::from_utf8_lossy(stderr);
+                match stderr.trim() {
+                    "" => Ok(()),
+                    stderr => write!(f, ", stderr: {stderr}"),
                 }
             }
         }
     }
 }
 
+fn write_exit_code(f: &mut fmt::Formatter<'_>, code: Option<NonZeroI32>) -> fmt::Result {
+    match code {
+        Some(code) => write!(f, "exited with non-zero exit code `{code}`"),
+        None => write!(f, "did not run succesfully"),
+    }
+}
+
 impl std::error::Error for Error {}
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -231,3 +573,91 @@ macro_rules! args {
 
 pub(crate) use args;
 pub(crate) use command;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout_kills_the_command() {
+        let start = Instant::now();
+        let error = command!("sh", "-c", "echo partial; echo oops >&2; sleep 10")
+            .timeout(Duration::from_millis(500))
+            .output()
+            .unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        match &error.kind {
+            ErrorKind::TimedOut { timeout, stderr } => {
+                assert_eq!(*timeout, Duration::from_millis(500));
+                assert_eq!(stderr, b"oops\n");
+            }
+            kind => panic!("expected a timeout, got {kind:?}"),
+        }
+        assert!(error
+            .to_string()
+            .ends_with("timed out after 500ms, stderr: oops"));
+
+        let output = command!("sh", "-c", "echo done")
+            .timeout(Duration::from_secs(10))
+            .output()
+            .unwrap();
+        assert_eq!(output.stdout, b"done\n");
+    }
+
+    #[test]
+    fn status_with_stdout_puts_stderr_in_the_error() {
+        let error = command!("sh", "-c", "echo partial; echo oops >&2; exit 3")
+            .status_with_stdout(|stdout| io::read_to_string(stdout).unwrap())
+            .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .ends_with("exited with non-zero exit code `3`, stderr: oops"),
+            "{error}"
+        );
+
+        let stdout = command!("sh", "-c", "echo done")
+            .status_with_stdout(|stdout| io::read_to_string(stdout).unwrap())
+            .unwrap();
+        assert_eq!(stdout, "done\n");
+    }
+
+    #[test]
+    fn status_with_stdout_lines_returns_the_errors_of_on_line() {
+        let mut lines = Vec::new();
+        let error = command!("sh", "-c", "echo first; echo second; sleep 10")
+            .status_with_stdout_lines(|line| {
+                lines.push(line.to_owned());
+                Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "stderr is closed",
+                ))
+            })
+            .unwrap_err();
+        assert_eq!(lines, ["first"]);
+        assert!(
+            error
+                .to_string()
+                .ends_with("failed to handle its output: stderr is closed"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn output_limit_truncates_the_output() {
+        let output = command!("sh", "-c", "yes | head -c 100000")
+            .output_limit(1000)
+            .output()
+            .unwrap();
+        let (kept, marker) = output.stdout.split_at(1000);
+        assert!(kept.chunks(2).all(|chunk| chunk == b"y\n"));
+        assert_eq!(marker, b"\n[99000 more bytes were discarded]\n");
+        assert!(output.stderr.is_empty());
+
+        let output = command!("sh", "-c", "echo short")
+            .output_limit(1000)
+            .output()
+            .unwrap();
+        assert_eq!(output.stdout, b"short\n");
+    }
+}
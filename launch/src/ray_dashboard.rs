@@ -0,0 +1,250 @@
+//! A client for the HTTP API of the [Ray dashboard](https://docs.ray.io/en/latest/cluster/running-applications/job-submission/rest.html),
+//! which runs on the head Pod of a RayCluster.
+
+use std::{collections::BTreeMap, fmt};
+
+use serde::de::DeserializeOwned;
+
+use crate::{http::Http, Result};
+
+/// The port of the dashboard on the head Pod.
+pub const PORT: u16 = 8265;
+
+/// The number of actors or tasks to request. The state API truncates lists to this many entries.
+const LIST_LIMIT: usize = 10_000;
+
+/// Partial implementation of the `JobDetails` returned by `/api/jobs/<submission_id>`.
+#[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+pub struct JobDetails {
+    /// The ID of the job within the Ray cluster, which actors and tasks refer to. Unset until the driver has started.
+    pub job_id: Option<String>,
+    pub submission_id: Option<String>,
+    /// One of `PENDING`, `RUNNING`, `STOPPED`, `SUCCEEDED` or `FAILED`.
+    pub status: String,
+    pub message: Option<String>,
+    pub error_type: Option<String>,
+    /// In milliseconds since the Unix epoch.
+    pub start_time: Option<u64>,
+    /// In milliseconds since the Unix epoch.
+    pub end_time: Option<u64>,
+}
+
+/// The envelope of the responses of the state API under `/api/v0`.
+#[derive(Debug, serde::Deserialize)]
+struct StateResponse<T> {
+    result: bool,
+    msg: String,
+    data: Option<StateResponseData<T>>,
+}
+
+/// Failed requests have empty data.
+#[derive(Debug, serde::Deserialize)]
+struct StateResponseData<T> {
+    result: Option<T>,
+}
+
+/// Partial implementation of the `ListApiResponse` of the state API.
+#[derive(Debug, serde::Deserialize)]
+struct ListResponse {
+    /// The number of entries in the cluster before filtering.
+    total: usize,
+    num_after_truncation: usize,
+    result: Vec<StateEntry>,
+}
+
+/// The fields that actors and tasks have in common.
+#[derive(Debug, serde::Deserialize)]
+struct StateEntry {
+    state: String,
+}
+
+/// The number of actors or tasks per state.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct StateCounts {
+    pub by_state: BTreeMap<String, usize>,
+    /// Whether the state API returned only part of the entries.
+    pub truncated: bool,
+}
+
+impl StateCounts {
+    fn new(response: ListResponse) -> Self {
+        let mut by_state = BTreeMap::new();
+        for entry in response.result {
+            *by_state.entry(entry.state).or_default() += 1;
+        }
+        Self {
+            by_state,
+            truncated: response.num_after_truncation < response.total,
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.by_state.values().sum()
+    }
+}
+
+impl fmt::Display for StateCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.truncated {
+            f.write_str("at least ")?;
+        }
+        write!(f, "{}", self.total())?;
+        if !self.by_state.is_empty() {
+            let states = self
+                .by_state
+                .iter()
+                .map(|(state, count)| format!("{state} {count}"))
+                .collect::<Vec<_>>();
+            write!(f, " ({})", states.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Client<'a> {
+    http: &'a Http,
+    /// Such as `http://127.0.0.1:8265`.
+    base_url: String,
+}
+
+impl<'a> Client<'a> {
+    pub fn new(http: &'a Http, base_url: String) -> Self {
+        Self { http, base_url }
+    }
+
+    fn get<T: DeserializeOwned>(&self, path_and_query: &str) -> Result<T> {
+        let url = format!("{}{path_and_query}", self.base_url);
+        let response = self
+            .http
+            .get(&url)
+            .send()
+            .map_err(|error| self.http.describe_error(error))?
+            .error_for_status()?;
+        Ok(serde_json::from_slice(&response.bytes()?)?)
+    }
+
+    pub fn job(&self, submission_id: &str) -> Result<JobDetails> {
+        self.get(&format!("/api/jobs/{submission_id}"))
+    }
+
+    pub fn actors(&self, job_id: &str) -> Result<StateCounts> {
+        self.list("actors", job_id)
+    }
+
+    pub fn tasks(&self, job_id: &str) -> Result<StateCounts> {
+        self.list("tasks", job_id)
+    }
+
+    fn list(&self, resource: &str, job_id: &str) -> Result<StateCounts> {
+        let response: StateResponse<ListResponse> = self.get(&format!(
+            "/api/v0/{resource}?limit={LIST_LIMIT}&detail=false&filter_keys=job_id&filter_predicates=%3D&filter_values={job_id}"
+        ))?;
+        Ok(StateCounts::new(into_result(response)?))
+    }
+}
+
+fn into_result<T>(response: StateResponse<T>) -> Result<T> {
+    match response {
+        StateResponse {
+            result: true,
+            data: Some(StateResponseData {
+                result: Some(result),
+            }),
+            ..
+        } => Ok(result),
+        StateResponse { msg, .. } => Err(format!("The Ray state API failed: {msg}").into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn job_details_deserialize() {
+        let details: JobDetails = serde_json::from_str(
+            r#"{
+                "type": "SUBMISSION",
+                "job_id": "02000000",
+                "submission_id": "ray-job-abc-x7k2p",
+                "driver_info": {
+                    "id": "02000000",
+                    "node_ip_address": "10.244.3.17",
+                    "pid": "1287"
+                },
+                "status": "RUNNING",
+                "entrypoint": "python train.py --epochs=10",
+                "message": "Job is currently running.",
+                "error_type": null,
+                "start_time": 1736763631000,
+                "end_time": null,
+                "metadata": {},
+                "runtime_env": {},
+                "driver_agent_http_address": "http://10.244.3.17:52365",
+                "driver_node_id": "b8f1c1d4e0a34b6f9d1e2a7c5b3f8e6d9a0c4b2e1f7d3a5c6b8e9f0a",
+                "driver_exit_code": null
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            details,
+            JobDetails {
+                job_id: Some("02000000".to_owned()),
+                submission_id: Some("ray-job-abc-x7k2p".to_owned()),
+                status: "RUNNING".to_owned(),
+                message: Some("Job is currently running.".to_owned()),
+                error_type: None,
+                start_time: Some(1736763631000),
+                end_time: None,
+            }
+        );
+    }
+
+    #[test]
+    fn state_counts_work() {
+        let response: StateResponse<ListResponse> = serde_json::from_str(
+            r#"{
+                "result": true,
+                "msg": "",
+                "data": {
+                    "result": {
+                        "total": 5,
+                        "num_after_truncation": 5,
+                        "num_filtered": 3,
+                        "result": [
+                            { "actor_id": "a1", "class_name": "Trainer", "state": "ALIVE", "job_id": "02000000", "name": "", "node_id": "n1", "pid": 1301, "ray_namespace": "r1" },
+                            { "actor_id": "a2", "class_name": "Trainer", "state": "ALIVE", "job_id": "02000000", "name": "", "node_id": "n2", "pid": 977, "ray_namespace": "r1" },
+                            { "actor_id": "a3", "class_name": "DataLoader", "state": "DEAD", "job_id": "02000000", "name": "", "node_id": "n1", "pid": 1302, "ray_namespace": "r1" }
+                        ],
+                        "partial_failure_warning": "",
+                        "warnings": null
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        let counts = StateCounts::new(into_result(response).unwrap());
+        assert_eq!(counts.total(), 3);
+        assert!(!counts.truncated);
+        assert_eq!(counts.to_string(), "3 (ALIVE 2, DEAD 1)");
+
+        let counts = StateCounts {
+            truncated: true,
+            ..counts
+        };
+        assert_eq!(counts.to_string(), "at least 3 (ALIVE 2, DEAD 1)");
+        assert_eq!(StateCounts::default().to_string(), "0");
+    }
+
+    #[test]
+    fn state_api_errors_are_reported() {
+        let response: StateResponse<ListResponse> = serde_json::from_str(
+            r#"{ "result": false, "msg": "Failed to query the GCS", "data": {} }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            into_result(response).unwrap_err().to_string(),
+            "The Ray state API failed: Failed to query the GCS"
+        );
+    }
+}
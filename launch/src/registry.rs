@@ -0,0 +1,435 @@
+//! A client for the [HTTP API](https://distribution.github.io/distribution/spec/api/) of the container registries of
+//! the clusters.
+
+use reqwest::{blocking::Response, header, Method, StatusCode};
+use serde::de::DeserializeOwned;
+
+use crate::{http::Http, Result};
+
+// Account for different image types in the Registry API
+// Authoritive list: https://github.com/google/go-containerregistry/blob/6bce25ecf0297c1aa9072bc665b5cf58d53e1c54/pkg/v1/types/types.go#L22
+pub const ACCEPTABLE_MANIFEST_TYPES: &[&str] = &[
+    "application/vnd.oci.image.manifest.v1+json", // kaniko builder
+    "application/vnd.oci.image.index.v1+json",    // docker builder
+];
+
+/// The number of repositories or tags to request per page.
+const PAGE_SIZE: usize = 100;
+
+#[derive(Debug, serde::Deserialize)]
+struct Catalog {
+    #[serde(default)]
+    repositories: Vec<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct TagList {
+    /// Null when the repository has no tags left.
+    #[serde(default)]
+    tags: Option<Vec<String>>,
+}
+
+/// Partially implements an [image manifest](https://github.com/opencontainers/image-spec/blob/main/manifest.md) or an
+/// [image index](https://github.com/opencontainers/image-spec/blob/main/image-index.md).
+#[derive(Debug, serde::Deserialize)]
+struct Manifest {
+    /// Set for an image manifest.
+    #[serde(default)]
+    config: Option<Descriptor>,
+    /// Set for an image index.
+    #[serde(default)]
+    manifests: Vec<Descriptor>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct Descriptor {
+    digest: String,
+    #[serde(default)]
+    platform: Option<Platform>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct Platform {
+    os: String,
+}
+
+/// Partially implements the [image configuration](https://github.com/opencontainers/image-spec/blob/main/config.md).
+#[derive(Debug, serde::Deserialize)]
+struct ImageConfig {
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    created: Option<time::OffsetDateTime>,
+}
+
+pub struct Client<'a> {
+    http: &'a Http,
+    /// Such as `https://berkeley-docker.taila1eba.ts.net`.
+    base_url: String,
+}
+
+impl<'a> Client<'a> {
+    pub fn new(http: &'a Http, base_url: String) -> Self {
+        Self { http, base_url }
+    }
+
+    /// Creates a client for a registry host such as `berkeley-docker.taila1eba.ts.net`, which is served over https.
+    pub fn for_registry(http: &'a Http, registry: &str) -> Self {
+        Self::new(http, format!("https://{registry}"))
+    }
+
+    fn request(&self, method: Method, path: &str) -> reqwest::blocking::RequestBuilder {
+        self.http
+            .request(method, format!("{}{path}", self.base_url))
+            .header(header::ACCEPT, ACCEPTABLE_MANIFEST_TYPES.join(","))
+    }
+
+    fn send(&self, request: reqwest::blocking::RequestBuilder) -> Result<Response> {
+        Ok(request
+            .send()
+            .map_err(|error| self.http.describe_error(error))?)
+    }
+
+    fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let response = self
+            .send(self.request(Method::GET, path))?
+            .error_for_status()?;
+        Ok(serde_json::from_slice(&response.bytes()?)?)
+    }
+
+    /// Follows the `Link` headers of a paginated listing and returns the items of all pages.
+    fn get_paginated<T: DeserializeOwned>(
+        &self,
+        first_page: String,
+        items: impl Fn(T) -> Vec<String>,
+    ) -> Result<Vec<String>> {
+        let mut all = Vec::new();
+        let mut page = Some(first_page);
+        while let Some(path) = page.take() {
+            let response = self
+                .send(self.request(Method::GET, &path))?
+                .error_for_status()?;
+            page = response
+                .headers()
+                .get(header::LINK)
+                .and_then(|value| value.to_str().ok())
+                .and_then(next_page)
+                .map(ToOwned::to_owned);
+            all.extend(items(serde_json::from_slice(&response.bytes()?)?));
+        }
+        Ok(all)
+    }
+
+    /// Lists the repositories in the registry.
+    pub fn repositories(&self) -> Result<Vec<String>> {
+        self.get_paginated(format!("/v2/_catalog?n={PAGE_SIZE}"), |catalog: Catalog| {
+            catalog.repositories
+        })
+    }
+
+    /// Lists the tags of a repository.
+    pub fn list_tags(&self, repository: &str) -> Result<Vec<String>> {
+        self.get_paginated(
+            format!("/v2/{repository}/tags/list?n={PAGE_SIZE}"),
+            |list: TagList| list.tags.unwrap_or_default(),
+        )
+    }
+
+    /// Returns the digest of the manifest that a tag refers to, or `None` if the tag does not exist.
+    pub fn manifest_digest(&self, repository: &str, reference: &str) -> Result<Option<String>> {
+        let response = self.send(self.request(
+            Method::HEAD,
+            &format!("/v2/{repository}/manifests/{reference}"),
+        ))?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = response.error_for_status()?;
+        // Registry API should always return a digest
+        // https://distribution.github.io/distribution/spec/api/#digest-header
+        let digest = response
+            .headers()
+            .get("Docker-Content-Digest")
+            .ok_or("Expected image digest not found")?
+            .to_str()?;
+        Ok(Some(digest.to_owned()))
+    }
+
+    /// Returns when the image was created according to its configuration. For an image index, which docker pushes
+    /// along with attestations, the first image for an actual platform is used.
+    pub fn image_created(
+        &self,
+        repository: &str,
+        digest: &str,
+    ) -> Result<Option<time::OffsetDateTime>> {
+        let mut manifest: Manifest = self.get(&format!("/v2/{repository}/manifests/{digest}"))?;
+        if manifest.config.is_none() {
+            let Some(image) = image_manifest_digest(&manifest) else {
+                return Ok(None);
+            };
+            manifest = self.get(&format!("/v2/{repository}/manifests/{image}"))?;
+        }
+        let Some(config) = manifest.config else {
+            return Ok(None);
+        };
+        let config: ImageConfig = self.get(&format!("/v2/{repository}/blobs/{}", config.digest))?;
+        Ok(config.created)
+    }
+
+    /// Deletes a manifest and thereby all tags that refer to it. The storage is reclaimed by the garbage collection of
+    /// the registry.
+    pub fn delete_manifest(&self, repository: &str, digest: &str) -> Result<()> {
+        let response = self.send(self.request(
+            Method::DELETE,
+            &format!("/v2/{repository}/manifests/{digest}"),
+        ))?;
+        if response.status() == StatusCode::METHOD_NOT_ALLOWED {
+            return Err(format!(
+                "Unable to delete {repository}@{digest} because the registry does not allow deletes"
+            )
+            .into());
+        }
+        response.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Returns the first manifest of an image index that is not an attestation, which buildx stores with platform
+/// `unknown/unknown`.
+fn image_manifest_digest(index: &Manifest) -> Option<&str> {
+    index
+        .manifests
+        .iter()
+        .find(|descriptor| {
+            descriptor
+                .platform
+                .as_ref()
+                .is_none_or(|platform| platform.os != "unknown")
+        })
+        .map(|descriptor| descriptor.digest.as_str())
+}
+
+/// Parses the next page from a [`Link` header](https://distribution.github.io/distribution/spec/api/#pagination) like
+/// `</v2/_catalog?last=b&n=100>; rel="next"`.
+fn next_page(link: &str) -> Option<&str> {
+    link.split(',').find_map(|link| {
+        let (target, params) = link.trim().split_once(';')?;
+        params
+            .split(';')
+            .any(|param| matches!(param.trim(), r#"rel="next""# | "rel=next"))
+            .then_some(())?;
+        target.trim().strip_prefix('<')?.strip_suffix('>')
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod mock {
+    use std::{
+        io::{BufRead as _, BufReader, Write as _},
+        net::TcpListener,
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    pub struct Response {
+        pub status: u16,
+        pub headers: Vec<(&'static str, String)>,
+        pub body: String,
+    }
+
+    impl Response {
+        pub fn status(status: u16) -> Self {
+            Self {
+                status,
+                headers: Vec::new(),
+                body: String::new(),
+            }
+        }
+
+        pub fn json(body: serde_json::Value) -> Self {
+            Self {
+                body: body.to_string(),
+                ..Self::status(200)
+            }
+        }
+
+        pub fn header(mut self, key: &'static str, value: impl Into<String>) -> Self {
+            self.headers.push((key, value.into()));
+            self
+        }
+    }
+
+    /// Serves requests on a background thread until the test ends. `respond` is called with the method and the path
+    /// and query of each request. Returns the base URL and the received requests as `<method> <path>`.
+    pub fn serve(
+        respond: impl Fn(&str, &str) -> Response + Send + 'static,
+    ) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        thread::spawn({
+            let requests = Arc::clone(&requests);
+            move || {
+                for stream in listener.incoming() {
+                    let mut reader = BufReader::new(stream.unwrap());
+                    let mut request_line = String::new();
+                    reader.read_line(&mut request_line).unwrap();
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if line.trim_end().is_empty() {
+                            break;
+                        }
+                    }
+                    let mut parts = request_line.split_whitespace();
+                    let (method, path) = (parts.next().unwrap(), parts.next().unwrap());
+                    requests.lock().unwrap().push(format!("{method} {path}"));
+                    let response = respond(method, path);
+                    let stream = reader.get_mut();
+                    write!(stream, "HTTP/1.1 {} Status\r\n", response.status).unwrap();
+                    for (key, value) in &response.headers {
+                        write!(stream, "{key}: {value}\r\n").unwrap();
+                    }
+                    write!(
+                        stream,
+                        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+                        response.body.len()
+                    )
+                    .unwrap();
+                    if method != "HEAD" {
+                        stream.write_all(response.body.as_bytes()).unwrap();
+                    }
+                }
+            }
+        });
+        (url, requests)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mock::Response, *};
+
+    #[test]
+    fn next_page_works() {
+        assert_eq!(
+            next_page(r#"</v2/_catalog?last=b&n=100>; rel="next""#),
+            Some("/v2/_catalog?last=b&n=100")
+        );
+        assert_eq!(
+            next_page(
+                r#"</v2/launch/tags/list?n=2&last=abc>; rel="prev", </v2/launch/tags/list?n=2&last=def>; rel="next""#
+            ),
+            Some("/v2/launch/tags/list?n=2&last=def")
+        );
+        assert_eq!(next_page(r#"</v2/_catalog?last=b>; rel="prev""#), None);
+        assert_eq!(next_page(""), None);
+    }
+
+    #[test]
+    fn list_tags_follows_pagination() {
+        let (url, requests) = mock::serve(|_, path| match path {
+            "/v2/launch/tags/list?n=100" => Response::json(serde_json::json!({
+                "name": "launch",
+                "tags": ["alice-8a3f9b22", "bob-1c2d3e4f"],
+            }))
+            .header(
+                "Link",
+                r#"</v2/launch/tags/list?last=bob-1c2d3e4f&n=100>; rel="next""#,
+            ),
+            "/v2/launch/tags/list?last=bob-1c2d3e4f&n=100" => Response::json(serde_json::json!({
+                "name": "launch",
+                "tags": ["c0ffee0000000000000000000000000000000000"],
+            })),
+            _ => Response::status(404),
+        });
+        let http = Http::new(&Default::default(), Default::default()).unwrap();
+        let client = Client::new(&http, url);
+        assert_eq!(
+            client.list_tags("launch").unwrap(),
+            [
+                "alice-8a3f9b22",
+                "bob-1c2d3e4f",
+                "c0ffee0000000000000000000000000000000000"
+            ]
+        );
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn repositories_work_without_tags() {
+        let (url, _) = mock::serve(|_, path| match path {
+            "/v2/_catalog?n=100" => {
+                Response::json(serde_json::json!({ "repositories": ["launch", "obelisk"] }))
+            }
+            "/v2/obelisk/tags/list?n=100" => {
+                Response::json(serde_json::json!({ "name": "obelisk", "tags": null }))
+            }
+            _ => Response::status(404),
+        });
+        let http = Http::new(&Default::default(), Default::default()).unwrap();
+        let client = Client::new(&http, url);
+        assert_eq!(client.repositories().unwrap(), ["launch", "obelisk"]);
+        assert_eq!(client.list_tags("obelisk").unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn manifests_work() {
+        let (url, requests) = mock::serve(|method, path| match (method, path) {
+            ("HEAD", "/v2/launch/manifests/alice-8a3f9b22") => {
+                Response::status(200).header("Docker-Content-Digest", "sha256:index")
+            }
+            ("GET", "/v2/launch/manifests/sha256:index") => Response::json(serde_json::json!({
+                "mediaType": "application/vnd.oci.image.index.v1+json",
+                "manifests": [
+                    {
+                        "digest": "sha256:attestation",
+                        "platform": { "architecture": "unknown", "os": "unknown" }
+                    },
+                    {
+                        "digest": "sha256:image",
+                        "platform": { "architecture": "amd64", "os": "linux" }
+                    }
+                ]
+            })),
+            ("GET", "/v2/launch/manifests/sha256:image") => Response::json(serde_json::json!({
+                "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                "config": { "digest": "sha256:config" },
+                "layers": []
+            })),
+            ("GET", "/v2/launch/blobs/sha256:config") => Response::json(serde_json::json!({
+                "architecture": "amd64",
+                "created": "2025-01-13T10:20:31.123456789Z",
+                "os": "linux"
+            })),
+            ("DELETE", "/v2/launch/manifests/sha256:index") => Response::status(202),
+            ("DELETE", _) => Response::status(405),
+            _ => Response::status(404),
+        });
+        let http = Http::new(&Default::default(), Default::default()).unwrap();
+        let client = Client::new(&http, url);
+
+        assert_eq!(
+            client
+                .manifest_digest("launch", "alice-8a3f9b22")
+                .unwrap()
+                .as_deref(),
+            Some("sha256:index")
+        );
+        assert_eq!(client.manifest_digest("launch", "missing").unwrap(), None);
+        assert_eq!(
+            client.image_created("launch", "sha256:index").unwrap(),
+            Some(time::macros::datetime!(2025-01-13 10:20:31.123456789 UTC))
+        );
+        client.delete_manifest("launch", "sha256:index").unwrap();
+        assert_eq!(
+            client
+                .delete_manifest("launch", "sha256:other")
+                .unwrap_err()
+                .to_string(),
+            "Unable to delete launch@sha256:other because the registry does not allow deletes"
+        );
+        assert!(requests
+            .lock()
+            .unwrap()
+            .contains(&"DELETE /v2/launch/manifests/sha256:index".to_owned()));
+    }
+}
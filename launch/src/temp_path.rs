@@ -1,13 +1,70 @@
+/// Returns a path for a new JSON file in the temporary directory of the platform, such as `/tmp` or `%TEMP%`.
 pub fn tmp_json_path() -> std::path::PathBuf {
+    tmp_path(".json")
+}
+
+/// Returns a path for a new file or directory with a random name that ends in `ext` in the temporary directory.
+fn tmp_path(ext: &str) -> std::path::PathBuf {
     use rand::distributions::{Alphanumeric, DistString};
 
-    const DIR: &str = "/tmp/";
-    const EXT: &str = ".json";
     const LEN: usize = 16;
 
-    let mut path = String::with_capacity(DIR.len() + LEN + EXT.len());
-    path.push_str(DIR);
-    Alphanumeric.append_string(&mut rand::thread_rng(), &mut path, LEN);
-    path.push_str(EXT);
-    path.into()
+    let mut name = String::with_capacity(LEN + ext.len());
+    Alphanumeric.append_string(&mut rand::thread_rng(), &mut name, LEN);
+    name.push_str(ext);
+    std::env::temp_dir().join(name)
+}
+
+/// A new directory in the temporary directory that is removed with its contents when dropped.
+#[cfg(test)]
+pub struct TempDir(std::path::PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    pub fn new() -> Self {
+        let path = tmp_path("");
+        std::fs::create_dir(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tmp_json_path_is_in_the_temporary_directory() {
+        let path = tmp_json_path();
+        assert_eq!(path.parent(), Some(std::env::temp_dir().as_path()));
+        assert_eq!(path.extension().and_then(|ext| ext.to_str()), Some("json"));
+        assert_ne!(path, tmp_json_path());
+    }
+
+    #[test]
+    fn temp_dir_is_removed_when_dropped() {
+        let dir = TempDir::new();
+        let path = dir.path().to_owned();
+        std::fs::write(path.join("file"), "contents").unwrap();
+        drop(dir);
+        assert!(!path.exists());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn tmp_json_path_uses_windows_separators() {
+        let path = tmp_json_path();
+        assert!(!path.to_string_lossy().starts_with("/tmp/"), "{path:?}");
+        assert!(path.to_string_lossy().contains('\\'), "{path:?}");
+    }
 }
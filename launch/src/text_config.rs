@@ -0,0 +1,170 @@
+//! Reads the YAML files that users write, such as Katib experiment specs and Ray runtime environments. Files edited on
+//! Windows or exported from web tools can start with a byte order mark, use CRLF line endings or be encoded as UTF-16,
+//! which serde_yaml reports as unhelpful errors at line 1.
+
+use std::{io, path::Path};
+
+/// The byte order marks of the encodings that are not UTF-8, longest first because the UTF-32 LE mark starts with the
+/// UTF-16 LE mark.
+const OTHER_BOMS: [(&[u8], &str); 4] = [
+    (b"\x00\x00\xFE\xFF", "UTF-32"),
+    (b"\xFF\xFE\x00\x00", "UTF-32"),
+    (b"\xFE\xFF", "UTF-16"),
+    (b"\xFF\xFE", "UTF-16"),
+];
+
+/// Reads a text file, removing a leading UTF-8 byte order mark and converting CRLF and CR line endings to LF. A lone CR
+/// is only a line ending in a file without LF, so that one inside a quoted scalar of any other file is kept. Fails with
+/// [`io::ErrorKind::InvalidData`] and a message that tells the user to re-save the file when it is not UTF-8, and with
+/// [`io::ErrorKind::UnexpectedEof`] when it only contains whitespace, which settings files treat as no settings. Other
+/// errors, such as [`io::ErrorKind::NotFound`], are those of reading the file.
+pub fn read_text_config(path: &Path) -> io::Result<String> {
+    normalize(std::fs::read(path)?)
+}
+
+fn normalize(bytes: Vec<u8>) -> io::Result<String> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    if let Some((_, encoding)) = OTHER_BOMS.iter().find(|(bom, _)| bytes.starts_with(bom)) {
+        return Err(invalid(format!(
+            "the file is encoded as {encoding}, re-save it as UTF-8"
+        )));
+    }
+    // NOTE: Text in UTF-16 without a byte order mark has a NUL byte in every ASCII character, which YAML never contains.
+    if bytes.iter().take(64).any(|&byte| byte == 0) {
+        return Err(invalid(
+            "the file contains NUL bytes, which suggests that it is encoded as UTF-16, re-save it as UTF-8".to_owned(),
+        ));
+    }
+    let text = String::from_utf8(bytes).map_err(|error| {
+        invalid(format!(
+            "the file is not valid UTF-8 at byte {}, re-save it as UTF-8",
+            error.utf8_error().valid_up_to()
+        ))
+    })?;
+    let text = text.strip_prefix('\u{FEFF}').unwrap_or(&text);
+    if text.trim().is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "the file is empty",
+        ));
+    }
+    let text = text.replace("\r\n", "\n");
+    if text.contains('\n') {
+        Ok(text)
+    } else {
+        Ok(text.replace('\r', "\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+    fn utf16(text: &str, bom: bool, big_endian: bool) -> Vec<u8> {
+        let units = bom.then_some(0xFEFF).into_iter().chain(text.encode_utf16());
+        units
+            .flat_map(|unit| match big_endian {
+                true => unit.to_be_bytes(),
+                false => unit.to_le_bytes(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn normalize_keeps_plain_text() {
+        assert_eq!(normalize(b"a: 1\nb: 2\n".to_vec()).unwrap(), "a: 1\nb: 2\n");
+        assert_eq!(normalize("name: émile\n".into()).unwrap(), "name: émile\n");
+    }
+
+    #[test]
+    fn normalize_strips_the_bom() {
+        let mut bytes = UTF8_BOM.to_vec();
+        bytes.extend(b"a: 1\n");
+        assert_eq!(normalize(bytes).unwrap(), "a: 1\n");
+    }
+
+    #[test]
+    fn normalize_converts_line_endings() {
+        assert_eq!(
+            normalize(b"a: 1\r\nb: |\r\n  x\r\n  y\r\n".to_vec()).unwrap(),
+            "a: 1\nb: |\n  x\n  y\n"
+        );
+        assert_eq!(normalize(b"a: 1\rb: 2\r".to_vec()).unwrap(), "a: 1\nb: 2\n");
+        // A CR in a quoted scalar is no line ending when the file has others.
+        for ending in ["\n", "\r\n"] {
+            assert_eq!(
+                normalize(format!("a: \"x\ry\"{ending}b: 2{ending}").into_bytes()).unwrap(),
+                "a: \"x\ry\"\nb: 2\n"
+            );
+        }
+    }
+
+    #[test]
+    fn normalize_makes_windows_files_parse() {
+        let mut bytes = UTF8_BOM.to_vec();
+        bytes.extend(b"name: test\r\nvalues:\r\n  - 1\r\n  - 2\r\n");
+        let value: serde_yaml::Value = serde_yaml::from_str(&normalize(bytes).unwrap()).unwrap();
+        assert_eq!(value["name"], "test");
+        assert_eq!(value["values"][1], 2);
+    }
+
+    #[test]
+    fn normalize_rejects_utf16() {
+        for (bom, big_endian) in [(true, false), (true, true)] {
+            let error = normalize(utf16("a: 1\n", bom, big_endian))
+                .unwrap_err()
+                .to_string();
+            assert_eq!(error, "the file is encoded as UTF-16, re-save it as UTF-8");
+        }
+        for big_endian in [false, true] {
+            let error = normalize(utf16("a: 1\n", false, big_endian))
+                .unwrap_err()
+                .to_string();
+            assert!(error.contains("UTF-16"), "{error}");
+        }
+        let error = normalize(b"\xFF\xFE\x00\x00a\x00\x00\x00".to_vec())
+            .unwrap_err()
+            .to_string();
+        assert_eq!(error, "the file is encoded as UTF-32, re-save it as UTF-8");
+    }
+
+    #[test]
+    fn normalize_rejects_other_encodings() {
+        // "name: émile" in Latin-1.
+        let error = normalize(b"name: \xE9mile\n".to_vec()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            error.to_string(),
+            "the file is not valid UTF-8 at byte 6, re-save it as UTF-8"
+        );
+    }
+
+    #[test]
+    fn normalize_rejects_empty_files() {
+        for bytes in [&b""[..], b"\n\r\n  ", UTF8_BOM] {
+            let error = normalize(bytes.to_vec()).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+            assert_eq!(error.to_string(), "the file is empty");
+        }
+    }
+
+    #[test]
+    fn read_text_config_keeps_the_kind_of_io_errors() {
+        let path = crate::temp_path::tmp_json_path();
+        assert_eq!(
+            read_text_config(&path).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        std::fs::write(&path, b"\xEF\xBB\xBFa: 1\r\n").unwrap();
+        let result = read_text_config(&path);
+        std::fs::write(&path, b"").unwrap();
+        let empty = read_text_config(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(result.unwrap(), "a: 1\n");
+        let error = empty.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,245 @@
+//! A minimal menu for choosing one of a few items on a terminal. The terminal stays in its normal line-buffered mode, so
+//! the user types the number of an item or moves the highlight with the arrow keys, and confirms with Enter.
+
+use std::{
+    io::{self, BufRead as _, IsTerminal as _, Write as _},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{ansi, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Up,
+    Down,
+    Enter,
+    Escape,
+    Digit(u32),
+    Other,
+}
+
+/// Decodes a line of input into keys. A line-buffered terminal echoes the arrow keys as the escape sequences
+/// `ESC [ A` and `ESC [ B`. The line ends with [`Key::Enter`].
+pub fn decode_keys(line: &str) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut chars = line.trim_end_matches(['\r', '\n']).chars().peekable();
+    while let Some(c) = chars.next() {
+        keys.push(match c {
+            '\x1b' if chars.peek() == Some(&'[') => {
+                chars.next();
+                match chars.next() {
+                    Some('A') => Key::Up,
+                    Some('B') => Key::Down,
+                    _ => Key::Other,
+                }
+            }
+            '\x1b' | 'q' => Key::Escape,
+            'k' => Key::Up,
+            'j' => Key::Down,
+            ' ' | '\t' => continue,
+            c => c.to_digit(10).map_or(Key::Other, Key::Digit),
+        });
+    }
+    keys.push(Key::Enter);
+    keys
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    Item(usize),
+    Cancelled,
+}
+
+/// The state of a menu of `len` items, of which one is highlighted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Menu {
+    len: usize,
+    highlighted: usize,
+    /// Whether a key that does not apply to the menu was pressed since the last Enter, in which case Enter is ignored
+    /// rather than selecting an item that the user may not have meant.
+    invalid: bool,
+}
+
+impl Menu {
+    pub fn new(len: usize) -> Self {
+        assert!(len > 0, "a menu needs at least one item");
+        Self {
+            len,
+            highlighted: 0,
+            invalid: false,
+        }
+    }
+
+    /// Applies the key and returns the selection once the menu is done.
+    pub fn handle(&mut self, key: Key) -> Option<Selection> {
+        match key {
+            Key::Up => self.highlighted = (self.highlighted + self.len - 1) % self.len,
+            Key::Down => self.highlighted = (self.highlighted + 1) % self.len,
+            Key::Digit(digit) => match usize::try_from(digit) {
+                Ok(number @ 1..) if number <= self.len => self.highlighted = number - 1,
+                _ => self.invalid = true,
+            },
+            Key::Other => self.invalid = true,
+            Key::Escape => return Some(Selection::Cancelled),
+            Key::Enter if self.invalid => self.invalid = false,
+            Key::Enter => return Some(Selection::Item(self.highlighted)),
+        }
+        None
+    }
+
+    /// Renders the items with their numbers, marking the highlighted one.
+    pub fn render(&self, items: &[impl AsRef<str>]) -> String {
+        let mut output = String::new();
+        for (index, item) in items.iter().enumerate() {
+            let item = item.as_ref();
+            let number = index + 1;
+            if index == self.highlighted {
+                output.push_str(&format!(
+                    "{}> {number}) {item}{}\n",
+                    ansi::CYAN,
+                    ansi::RESET
+                ));
+            } else {
+                output.push_str(&format!("  {number}) {item}\n"));
+            }
+        }
+        output
+    }
+}
+
+static PROMPTS_DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Makes [`is_interactive`] return `false` for the rest of the process, for `launch agent`, which runs unattended even
+/// when it was started from a terminal.
+pub fn disable_prompts() {
+    PROMPTS_DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// Whether the user can be asked questions, which requires both stdin and stderr to be a terminal.
+pub fn is_interactive() -> bool {
+    !PROMPTS_DISABLED.load(Ordering::Relaxed)
+        && io::stdin().is_terminal()
+        && io::stderr().is_terminal()
+}
+
+/// Asks the user to choose one of `items` on stderr and returns its index, or `None` when the user cancels.
+pub fn select(question: &str, items: &[impl AsRef<str>]) -> Result<Option<usize>> {
+    let mut menu = Menu::new(items.len());
+    let mut stdin = io::stdin().lock();
+    loop {
+        eprint!(
+            "{question}\n{}Type a number or use the arrow keys, then press Enter (q to cancel): ",
+            menu.render(items)
+        );
+        io::stderr().flush()?;
+        let mut line = String::new();
+        if stdin.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        for key in decode_keys(&line) {
+            match menu.handle(key) {
+                Some(Selection::Item(index)) => return Ok(Some(index)),
+                Some(Selection::Cancelled) => return Ok(None),
+                None => {}
+            }
+        }
+        eprintln!();
+    }
+}
+
+/// Asks a yes or no question on stderr, which defaults to no.
+pub fn confirm(question: &str) -> Result<bool> {
+    eprint!("{question} [y/N] ");
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Asks a question on stderr and returns the answer without the line break, which is empty when stdin is closed.
+pub fn prompt(question: &str) -> Result<String> {
+    eprint!("{question} ");
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(answer.trim_end_matches(['\n', '\r']).to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(menu: &mut Menu, input: &str) -> Option<Selection> {
+        decode_keys(input)
+            .into_iter()
+            .find_map(|key| menu.handle(key))
+    }
+
+    #[test]
+    fn decode_keys_works() {
+        assert_eq!(decode_keys("\n"), [Key::Enter]);
+        assert_eq!(decode_keys("2\n"), [Key::Digit(2), Key::Enter]);
+        assert_eq!(
+            decode_keys("\x1b[B\x1b[B\x1b[A\r\n"),
+            [Key::Down, Key::Down, Key::Up, Key::Enter]
+        );
+        assert_eq!(decode_keys("jk"), [Key::Down, Key::Up, Key::Enter]);
+        assert_eq!(decode_keys(" q\n"), [Key::Escape, Key::Enter]);
+        assert_eq!(decode_keys("\x1b\n"), [Key::Escape, Key::Enter]);
+        assert_eq!(decode_keys("\x1b[C\n"), [Key::Other, Key::Enter]);
+        assert_eq!(decode_keys("x\n"), [Key::Other, Key::Enter]);
+    }
+
+    #[test]
+    fn menu_selects_the_highlighted_item() {
+        let mut menu = Menu::new(3);
+        assert_eq!(run(&mut menu, "\n"), Some(Selection::Item(0)));
+
+        let mut menu = Menu::new(3);
+        assert_eq!(run(&mut menu, "\x1b[B\x1b[B\n"), Some(Selection::Item(2)));
+
+        let mut menu = Menu::new(3);
+        assert_eq!(run(&mut menu, "\x1b[A\n"), Some(Selection::Item(2)));
+
+        let mut menu = Menu::new(3);
+        assert_eq!(menu.handle(Key::Down), None);
+        assert_eq!(menu.handle(Key::Down), None);
+        assert_eq!(menu.handle(Key::Down), None);
+        assert_eq!(menu.highlighted, 0);
+    }
+
+    #[test]
+    fn menu_selects_by_number() {
+        let mut menu = Menu::new(3);
+        assert_eq!(run(&mut menu, "3\n"), Some(Selection::Item(2)));
+
+        let mut menu = Menu::new(3);
+        assert_eq!(run(&mut menu, "\x1b[B 1\n"), Some(Selection::Item(0)));
+    }
+
+    #[test]
+    fn menu_ignores_lines_with_invalid_keys() {
+        let mut menu = Menu::new(3);
+        assert_eq!(run(&mut menu, "4\n"), None);
+        assert_eq!(run(&mut menu, "0\n"), None);
+        assert_eq!(run(&mut menu, "\x1b[Bx\n"), None);
+        assert_eq!(menu.highlighted, 1);
+        assert_eq!(run(&mut menu, "\n"), Some(Selection::Item(1)));
+    }
+
+    #[test]
+    fn menu_can_be_cancelled() {
+        let mut menu = Menu::new(3);
+        assert_eq!(run(&mut menu, "2q\n"), Some(Selection::Cancelled));
+    }
+
+    #[test]
+    fn render_works() {
+        let mut menu = Menu::new(2);
+        menu.handle(Key::Down);
+        assert_eq!(
+            menu.render(&["berkeley", "staging"]),
+            format!("  1) berkeley\n{}> 2) staging{}\n", ansi::CYAN, ansi::RESET)
+        );
+    }
+}
@@ -10,6 +10,17 @@ super::unit!(kibibyte, "KiB", 1024);
 super::unit!(mebibyte, "MiB", 1024 * 1024);
 super::unit!(gibibyte, "GiB", 1024 * 1024 * 1024);
 
+/// The symbols that [`Bytes::from_str`] accepts.
+const SYMBOLS: [&str; 7] = [
+    byte::SYMBOL,
+    kilobyte::SYMBOL,
+    megabyte::SYMBOL,
+    gigabyte::SYMBOL,
+    kibibyte::SYMBOL,
+    mebibyte::SYMBOL,
+    gibibyte::SYMBOL,
+];
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
 pub struct Bytes(u64);
 
@@ -42,6 +53,16 @@ impl Bytes {
         div_round(self.0, U::BASE)
     }
 
+    /// Obtain the value in the provided unit. Rounds down.
+    pub const fn get_floor<U: Unit>(self) -> u64 {
+        self.0 / U::BASE.get()
+    }
+
+    /// Obtain the value in the provided unit. Rounds up.
+    pub const fn get_ceil<U: Unit>(self) -> u64 {
+        self.0.div_ceil(U::BASE.get())
+    }
+
     /// Returns an object that implements `std::fmt::Display` and formats the value in the provided unit.
     pub fn display<U: Unit>(self) -> impl fmt::Display {
         super::Display {
@@ -57,6 +78,9 @@ impl fmt::Display for Bytes {
     }
 }
 
+/// Parses a number directly followed by one of the [`SYMBOLS`], such as `16GiB`. The symbols are case-sensitive
+/// because a guess between decimal and binary units, such as for `16G`, would be off by 7%. Near misses get a
+/// suggestion instead, see [`ParseBytesError`].
 impl FromStr for Bytes {
     type Err = ParseBytesError;
 
@@ -86,7 +110,7 @@ impl FromStr for Bytes {
         };
 
         loop {
-            let (head, tail) = head_tail(input).ok_or(ParseBytesError::NoUnit)?;
+            let (head, tail) = head_tail(input).ok_or(ParseBytesError::NoUnit { value: output })?;
             match to_digit(head) {
                 Some(digit) => {
                     output = output.checked_mul(10).ok_or(ParseBytesError::PosOverflow)?;
@@ -109,7 +133,12 @@ impl FromStr for Bytes {
             kibibyte::SYMBOL_BYTES => Self::new::<kibibyte>(output),
             mebibyte::SYMBOL_BYTES => Self::new::<mebibyte>(output),
             gibibyte::SYMBOL_BYTES => Self::new::<gibibyte>(output),
-            _ => return Err(ParseBytesError::InvalidUnit),
+            _ => {
+                return Err(ParseBytesError::InvalidUnit {
+                    value: output,
+                    unit: String::from_utf8_lossy(input).into_owned(),
+                })
+            }
         }
         .ok_or(ParseBytesError::PosOverflow)
     }
@@ -127,24 +156,66 @@ pub enum ParseBytesError {
     /// Integer is too large to store in target integer type.
     PosOverflow,
 
-    /// No unit was provided.
-    NoUnit,
+    /// No unit was provided after the number `value`.
+    NoUnit { value: u64 },
 
-    /// Unit is invalid.
-    InvalidUnit,
+    /// The unit after the number `value` is not one of the [`SYMBOLS`].
+    InvalidUnit { value: u64, unit: String },
+}
+
+/// Returns the symbols that an invalid unit was likely meant to be, such as `GiB` for `gib` or ` GiB`, and both `GiB`
+/// and `GB` for `G`.
+fn suggest_symbols(unit: &str) -> Vec<&'static str> {
+    let unit = unit.trim_start().to_ascii_lowercase();
+    if let Some(symbol) = SYMBOLS
+        .into_iter()
+        .find(|symbol| symbol.to_ascii_lowercase() == unit)
+    {
+        return vec![symbol];
+    }
+    match unit.as_str() {
+        "k" | "ki" => vec![kibibyte::SYMBOL, kilobyte::SYMBOL],
+        "m" | "mi" => vec![mebibyte::SYMBOL, megabyte::SYMBOL],
+        "g" | "gi" => vec![gibibyte::SYMBOL, gigabyte::SYMBOL],
+        _ => Vec::new(),
+    }
 }
 
 impl std::error::Error for ParseBytesError {}
 
 impl fmt::Display for ParseBytesError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_str(match self {
-            ParseBytesError::Empty => "empty",
-            ParseBytesError::InvalidDigit => "invalid digit",
-            ParseBytesError::PosOverflow => "positive overflow",
-            ParseBytesError::NoUnit => "no unit",
-            ParseBytesError::InvalidUnit => "invalid unit",
-        })
+        match self {
+            ParseBytesError::Empty => f.write_str("empty"),
+            ParseBytesError::InvalidDigit => f.write_str("invalid digit"),
+            ParseBytesError::PosOverflow => f.write_str("positive overflow"),
+            ParseBytesError::NoUnit { value } => write!(
+                f,
+                "a unit is required, one of {}, did you mean \"{value}{}\"?",
+                SYMBOLS.join(", "),
+                gibibyte::SYMBOL
+            ),
+            ParseBytesError::InvalidUnit { value, unit } => {
+                let suggestions = suggest_symbols(unit);
+                if suggestions.is_empty() {
+                    write!(
+                        f,
+                        "invalid unit {unit:?}, expected one of {}",
+                        SYMBOLS.join(", ")
+                    )
+                } else {
+                    let suggestions = suggestions
+                        .iter()
+                        .map(|symbol| format!("\"{value}{symbol}\""))
+                        .collect::<Vec<_>>()
+                        .join(" or ");
+                    write!(
+                        f,
+                        "invalid unit {unit:?}, units are case-sensitive, did you mean {suggestions}?"
+                    )
+                }
+            }
+        }
     }
 }
 
@@ -156,8 +227,17 @@ mod tests {
     fn parse_bytes_works() {
         assert_eq!("".parse::<Bytes>(), Err(ParseBytesError::Empty));
         assert_eq!("B".parse::<Bytes>(), Err(ParseBytesError::InvalidDigit));
-        assert_eq!("1".parse::<Bytes>(), Err(ParseBytesError::NoUnit));
-        assert_eq!("12K".parse::<Bytes>(), Err(ParseBytesError::InvalidUnit));
+        assert_eq!(
+            "1".parse::<Bytes>(),
+            Err(ParseBytesError::NoUnit { value: 1 })
+        );
+        assert_eq!(
+            "12K".parse::<Bytes>(),
+            Err(ParseBytesError::InvalidUnit {
+                value: 12,
+                unit: "K".to_string()
+            })
+        );
         assert_eq!(
             "99999999999999999999B".parse::<Bytes>(),
             Err(ParseBytesError::PosOverflow)
@@ -176,6 +256,60 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_bytes_errors_suggest_units() {
+        let error = |input: &str| input.parse::<Bytes>().unwrap_err().to_string();
+
+        assert_eq!(
+            error("80"),
+            r#"a unit is required, one of B, KB, MB, GB, KiB, MiB, GiB, did you mean "80GiB"?"#
+        );
+        for symbol in SYMBOLS {
+            let base = match symbol {
+                "B" => 1,
+                "KB" => 1000,
+                "MB" => 1000 * 1000,
+                "GB" => 1000 * 1000 * 1000,
+                "KiB" => 1024,
+                "MiB" => 1024 * 1024,
+                "GiB" => 1024 * 1024 * 1024,
+                _ => unreachable!(),
+            };
+            assert_eq!(format!("80{symbol}").parse::<Bytes>(), Ok(Bytes(80 * base)));
+            let expected = format!(r#"units are case-sensitive, did you mean "80{symbol}"?"#);
+            for spelling in [
+                symbol.to_lowercase(),
+                symbol.to_uppercase(),
+                format!(" {symbol}"),
+            ] {
+                if spelling != symbol {
+                    assert!(
+                        error(&format!("80{spelling}")).ends_with(&expected),
+                        "{spelling}"
+                    );
+                }
+            }
+        }
+        for (unit, expected) in [
+            ("K", r#""80KiB" or "80KB""#),
+            ("Mi", r#""80MiB" or "80MB""#),
+            ("G", r#""80GiB" or "80GB""#),
+            ("g", r#""80GiB" or "80GB""#),
+            ("Gi", r#""80GiB" or "80GB""#),
+        ] {
+            assert_eq!(
+                error(&format!("80{unit}")),
+                format!(
+                    "invalid unit {unit:?}, units are case-sensitive, did you mean {expected}?"
+                )
+            );
+        }
+        assert_eq!(
+            error("80TiB"),
+            r#"invalid unit "TiB", expected one of B, KB, MB, GB, KiB, MiB, GiB"#
+        );
+    }
+
     #[test]
     fn display_works() {
         assert_eq!(
@@ -204,6 +338,9 @@ mod tests {
     #[test]
     fn round_on_conversion() {
         assert_eq!(Bytes::new::<byte>(700).unwrap().get::<kilobyte>(), 1);
+        assert_eq!(Bytes::new::<byte>(700).unwrap().get_floor::<kilobyte>(), 0);
+        assert_eq!(Bytes::new::<byte>(300).unwrap().get_ceil::<kilobyte>(), 1);
+        assert_eq!(Bytes::new::<byte>(1000).unwrap().get_ceil::<kilobyte>(), 1);
     }
 
     #[test]
@@ -0,0 +1,185 @@
+//! Links to the web interfaces of the clusters. The links are only built here, so that a change to the routes of
+//! headlamp or katib is fixed in one place. A context without the web interface, such as
+//! [`ClusterContext::Local`], gets `<namespace>/<name>` instead.
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+use crate::cli::ClusterContext;
+
+/// Everything but the unreserved characters of [RFC 3986](https://www.rfc-editor.org/rfc/rfc3986#section-2.3) is
+/// encoded, so that a name can not add path segments or a query.
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Joins the base URL with the percent-encoded segments, or names the resource when there is no base URL.
+fn link(base: Option<&str>, segments: &[&str], namespace: &str, name: &str) -> String {
+    match base {
+        Some(base) => join(base, segments),
+        None => format!("{namespace}/{name}"),
+    }
+}
+
+/// Joins the base URL with the percent-encoded segments.
+fn join(base: &str, segments: &[&str]) -> String {
+    let mut url = base.to_owned();
+    for segment in segments {
+        url.push('/');
+        url.extend(utf8_percent_encode(segment, PATH_SEGMENT));
+    }
+    url
+}
+
+fn headlamp(context: &ClusterContext, resource: &str, namespace: &str, name: &str) -> String {
+    link(
+        context.headlamp_url(),
+        &["c", "main", resource, namespace, name],
+        namespace,
+        name,
+    )
+}
+
+pub fn headlamp_job(context: &ClusterContext, namespace: &str, name: &str) -> String {
+    headlamp(context, "jobs", namespace, name)
+}
+
+pub fn headlamp_cron_job(context: &ClusterContext, namespace: &str, name: &str) -> String {
+    headlamp(context, "cronjobs", namespace, name)
+}
+
+pub fn headlamp_pod(context: &ClusterContext, namespace: &str, name: &str) -> String {
+    headlamp(context, "pods", namespace, name)
+}
+
+pub fn headlamp_secret(context: &ClusterContext, namespace: &str, name: &str) -> String {
+    headlamp(context, "secrets", namespace, name)
+}
+
+pub fn headlamp_rayjob_customresource(
+    context: &ClusterContext,
+    namespace: &str,
+    name: &str,
+) -> String {
+    link(
+        context.headlamp_url(),
+        &[
+            "c",
+            "main",
+            "customresources",
+            "rayjobs.ray.io",
+            namespace,
+            name,
+        ],
+        namespace,
+        name,
+    )
+}
+
+pub fn katib_experiment(
+    context: &ClusterContext,
+    namespace: &str,
+    experiment_name: &str,
+) -> String {
+    link(
+        context.katib_url(),
+        &["katib", "experiment", namespace, experiment_name],
+        namespace,
+        experiment_name,
+    )
+}
+
+pub fn katib_trial(
+    context: &ClusterContext,
+    namespace: &str,
+    experiment_name: &str,
+    trial_name: &str,
+) -> String {
+    link(
+        context.katib_url(),
+        &[
+            "katib",
+            "experiment",
+            namespace,
+            experiment_name,
+            "trial",
+            trial_name,
+        ],
+        namespace,
+        trial_name,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTEXT: &ClusterContext = &ClusterContext::Berkeley;
+
+    #[test]
+    fn headlamp_urls_work() {
+        assert_eq!(
+            headlamp_job(CONTEXT, "launch", "job-alice-x7k2p"),
+            "https://berkeley-headlamp.taila1eba.ts.net/c/main/jobs/launch/job-alice-x7k2p"
+        );
+        assert_eq!(
+            headlamp_pod(CONTEXT, "launch", "job-alice-x7k2p-5mz8q"),
+            "https://berkeley-headlamp.taila1eba.ts.net/c/main/pods/launch/job-alice-x7k2p-5mz8q"
+        );
+        assert_eq!(
+            headlamp_secret(CONTEXT, "launch", "databrickscfg-alice"),
+            "https://berkeley-headlamp.taila1eba.ts.net/c/main/secrets/launch/databrickscfg-alice"
+        );
+        assert_eq!(
+            headlamp_rayjob_customresource(CONTEXT, "launch", "ray-job-alice-x7k2p"),
+            "https://berkeley-headlamp.taila1eba.ts.net/c/main/customresources/rayjobs.ray.io/launch/ray-job-alice-x7k2p"
+        );
+    }
+
+    #[test]
+    fn katib_urls_work() {
+        assert_eq!(
+            katib_experiment(CONTEXT, "launch", "katib-alice-x7k2p"),
+            "http://berkeley-katib.taila1eba.ts.net/katib/experiment/launch/katib-alice-x7k2p"
+        );
+        assert_eq!(
+            katib_trial(CONTEXT, "launch", "katib-alice-x7k2p", "katib-alice-x7k2p-5mz8q"),
+            "http://berkeley-katib.taila1eba.ts.net/katib/experiment/launch/katib-alice-x7k2p/trial/katib-alice-x7k2p-5mz8q"
+        );
+    }
+
+    #[test]
+    fn names_are_percent_encoded() {
+        assert_eq!(
+            headlamp_job(CONTEXT, "launch", "../secrets/x?y#z ä"),
+            "https://berkeley-headlamp.taila1eba.ts.net/c/main/jobs/launch/..%2Fsecrets%2Fx%3Fy%23z%20%C3%A4"
+        );
+        assert_eq!(
+            join("http://host", &["a-b.c_d~e", "f/g"]),
+            "http://host/a-b.c_d~e/f%2Fg"
+        );
+    }
+
+    #[test]
+    fn contexts_without_web_interfaces_name_the_resource() {
+        let local = &ClusterContext::Local;
+        assert_eq!(
+            headlamp_job(local, "launch", "job-alice-x7k2p"),
+            "launch/job-alice-x7k2p"
+        );
+        assert_eq!(
+            headlamp_rayjob_customresource(local, "launch", "ray-job-alice-x7k2p"),
+            "launch/ray-job-alice-x7k2p"
+        );
+        assert_eq!(
+            katib_trial(
+                local,
+                "launch",
+                "katib-alice-x7k2p",
+                "katib-alice-x7k2p-5mz8q"
+            ),
+            "launch/katib-alice-x7k2p-5mz8q"
+        );
+    }
+}
@@ -1,6 +1,140 @@
 use std::{convert::Infallible, fmt, str::FromStr};
 
-#[derive(Debug)]
+use log::{debug, warn};
+
+use crate::{kubectl, tailscale, Result};
+
+/// The host that is recorded when no source knows the hostname.
+const UNKNOWN_HOST: &str = "unknown";
+
+/// The user and host of this machine. The hostname is only informational, so it falls back to [`hostname`] instead
+/// of failing.
+pub fn machine_user_host() -> UserHost {
+    UserHost::new(whoami::username(), Some(hostname()))
+}
+
+pub fn try_tailscale_user_host() -> Result<UserHost> {
+    Ok(UserHost::parse(&tailscale::get_login_name()?))
+}
+
+pub fn tailscale_user_host() -> Option<UserHost> {
+    try_tailscale_user_host()
+        .inspect_err(|error| {
+            warn!("Unable to determine tailscale user: {error}");
+        })
+        .ok()
+}
+
+/// Where [`hostname`] found the hostname, in the order in which the sources are tried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostnameSource {
+    System,
+    Env,
+    EtcHostname,
+    Unknown,
+}
+
+impl fmt::Display for HostnameSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HostnameSource::System => "the system",
+            HostnameSource::Env => "the HOSTNAME environment variable",
+            HostnameSource::EtcHostname => "/etc/hostname",
+            HostnameSource::Unknown => "no source, none of them knew it",
+        })
+    }
+}
+
+/// Looks up the hostname of this machine. Minimal containers often have no hostname configured, in which case the
+/// `HOSTNAME` environment variable and `/etc/hostname` are tried before falling back to `"unknown"`.
+pub fn hostname() -> String {
+    hostname_with_source().0
+}
+
+pub fn hostname_with_source() -> (String, HostnameSource) {
+    first_hostname([
+        &|| {
+            whoami::fallible::hostname()
+                .inspect_err(|error| debug!("Unable to determine hostname: {error}"))
+                .ok()
+        },
+        &|| std::env::var("HOSTNAME").ok(),
+        &|| std::fs::read_to_string("/etc/hostname").ok(),
+    ])
+}
+
+/// Returns the first usable hostname of the sources, which are only queried until one succeeds.
+fn first_hostname(sources: [&dyn Fn() -> Option<String>; 3]) -> (String, HostnameSource) {
+    const SOURCES: [HostnameSource; 3] = [
+        HostnameSource::System,
+        HostnameSource::Env,
+        HostnameSource::EtcHostname,
+    ];
+    sources
+        .into_iter()
+        .zip(SOURCES)
+        .find_map(|(source, name)| Some((normalize_hostname(source()?)?, name)))
+        .unwrap_or_else(|| {
+            warn!("Unable to determine hostname, using {UNKNOWN_HOST:?}");
+            (UNKNOWN_HOST.to_owned(), HostnameSource::Unknown)
+        })
+}
+
+/// Which identity the user label of a submission is derived from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserLabelSource {
+    /// The tailscale login name, which identifies the user across machines.
+    Tailscale,
+    /// The user of this machine, because the tailscale login name is unknown or has no domain.
+    Machine,
+}
+
+/// Returns the label that names the resources of a submission, such as the databricks Secret and the image tag, and
+/// where it came from. The user name is lowercased first, because a label only allows lowercase letters. The label is
+/// `None` when the user name has no characters that a label allows.
+pub fn user_label(
+    machine_user_host: &UserHost,
+    tailscale_user_host: Option<&UserHost>,
+) -> (Option<String>, UserLabelSource) {
+    let label =
+        |user: &str| kubectl::to_rfc_1035_label_lossy(&user.to_ascii_lowercase()).map(Into::into);
+    match tailscale_user_host.filter(|value| value.host().is_some()) {
+        Some(tailscale) => (label(tailscale.user()), UserLabelSource::Tailscale),
+        None => (label(machine_user_host.user()), UserLabelSource::Machine),
+    }
+}
+
+/// Login names that do not map onto names one to one, for the tests of every place that the user label ends up in.
+#[cfg(test)]
+pub const AWKWARD_LOGIN_NAMES: &[&str] = &[
+    "first.last+bot@company.com",
+    "First.Last@company.com",
+    "o'brien@company.com",
+    "émile.zola@company.com",
+    "_svc-ci_@company.com",
+    "123@company.com",
+    "a.b.c.d.e.f.g.h.i.j.k.l.m.n.o.p.q.r.s.t.u.v.w.x.y.z.a.b.c.d.e.f.g.h.i.j.k.l.m.n.o.p.q.r.s.t.u.v.w.x.y.z@company.com",
+    "user-with-a-name-that-is-longer-than-any-kubernetes-label-and-then-some-more-characters-on-top@company.com",
+];
+
+/// The user labels of [`AWKWARD_LOGIN_NAMES`] when they are the tailscale login names.
+#[cfg(test)]
+pub fn awkward_user_labels() -> impl Iterator<Item = Option<String>> {
+    let machine = UserHost::parse("root@laptop");
+    AWKWARD_LOGIN_NAMES
+        .iter()
+        .map(move |login_name| user_label(&machine, Some(&UserHost::parse(login_name))).0)
+}
+
+/// Trims the trailing newline of `/etc/hostname` and rejects values that would not parse back as the host of a
+/// [`UserHost`].
+fn normalize_hostname(value: String) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty() && !value.contains('@') && !value.contains(char::is_whitespace))
+        .then(|| value.to_owned())
+}
+
+#[derive(Debug, Clone)]
 /// The parsed object representation of `"{user}@{host}"` where `@{host}` is optional. See [`UserHostRef`] for
 /// the borrowed version.
 pub struct UserHost {
@@ -47,7 +181,7 @@ impl fmt::Display for UserHost {
 }
 
 /// See [`UserHost`] for the owned version.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct UserHostRef<'a> {
     user: &'a str,
     host: Option<&'a str>,
@@ -124,4 +258,109 @@ mod tests {
     fn without_host() {
         user_host_test!("mick", "mick", None);
     }
+
+    fn hostname_from(
+        lookup: Option<&str>,
+        env: Option<&str>,
+        etc_hostname: Option<&str>,
+    ) -> String {
+        let source = |value: Option<&str>| value.map(str::to_owned);
+        first_hostname([&|| source(lookup), &|| source(env), &|| {
+            source(etc_hostname)
+        }])
+        .0
+    }
+
+    #[test]
+    fn hostname_falls_back() {
+        assert_eq!(
+            hostname_from(Some("laptop"), Some("env"), Some("etc")),
+            "laptop"
+        );
+        assert_eq!(hostname_from(None, Some("env"), Some("etc")), "env");
+        assert_eq!(hostname_from(None, Some(""), Some("etc")), "etc");
+        assert_eq!(hostname_from(None, None, Some("container\n")), "container");
+        assert_eq!(hostname_from(None, None, Some("\n")), "unknown");
+        assert_eq!(hostname_from(None, None, None), "unknown");
+    }
+
+    #[test]
+    fn hostname_is_only_queried_until_found() {
+        let queried = std::cell::Cell::new(false);
+        let hostname = first_hostname([
+            &|| Some("laptop".to_owned()),
+            &|| {
+                queried.set(true);
+                None
+            },
+            &|| None,
+        ]);
+        assert_eq!(hostname, ("laptop".to_owned(), HostnameSource::System));
+        assert!(!queried.get());
+    }
+
+    #[test]
+    fn first_hostname_reports_its_source() {
+        let source = |value: Option<&'static str>| move || value.map(str::to_owned);
+        let sources = |values: [Option<&'static str>; 3]| {
+            let [a, b, c] = values.map(source);
+            first_hostname([&a, &b, &c]).1
+        };
+        assert_eq!(
+            sources([Some("laptop"), Some("env"), None]),
+            HostnameSource::System
+        );
+        assert_eq!(sources([None, Some("env"), None]), HostnameSource::Env);
+        assert_eq!(
+            sources([None, Some(" "), Some("pod")]),
+            HostnameSource::EtcHostname
+        );
+        assert_eq!(sources([None, None, None]), HostnameSource::Unknown);
+    }
+
+    #[test]
+    fn user_label_works() {
+        let machine = UserHost::parse("alice@laptop");
+        assert_eq!(
+            user_label(&machine, Some(&UserHost::parse("alice.smith@astera.org"))),
+            (Some("alice-smith".to_owned()), UserLabelSource::Tailscale)
+        );
+        assert_eq!(
+            user_label(&machine, Some(&UserHost::parse("bob"))),
+            (Some("alice".to_owned()), UserLabelSource::Machine)
+        );
+        assert_eq!(
+            user_label(&machine, None),
+            (Some("alice".to_owned()), UserLabelSource::Machine)
+        );
+        assert_eq!(
+            user_label(&UserHost::parse("123"), None),
+            (None, UserLabelSource::Machine)
+        );
+    }
+
+    #[test]
+    fn awkward_user_labels_are_labels() {
+        let labels = awkward_user_labels().collect::<Vec<_>>();
+        assert_eq!(labels[0].as_deref(), Some("first-last-bot"));
+        assert_eq!(labels[1].as_deref(), Some("first-last"));
+        assert_eq!(labels[3].as_deref(), Some("mile-zola"));
+        assert_eq!(labels[5], None);
+        for label in labels.into_iter().flatten() {
+            assert!(kubectl::is_rfc_1035_label(&label), "{label:?}");
+        }
+    }
+
+    #[test]
+    fn hostname_parses_back() {
+        for host in [
+            hostname_from(None, Some("user@host"), Some("pod-7c9f")),
+            hostname_from(None, None, None),
+        ] {
+            let value = UserHost::new("mick".to_owned(), Some(host.clone())).to_string();
+            let parsed = UserHostRef::parse(&value);
+            assert_eq!(parsed.user(), "mick");
+            assert_eq!(parsed.host(), Some(host.as_str()));
+        }
+    }
 }
@@ -1 +1,15 @@
 include!(concat!(env!("OUT_DIR"), "/version.rs"));
+
+/// [`VERSION`] parsed once, for comparing against other versions of launch.
+pub static SEMVER: std::sync::LazyLock<semver::Version> =
+    std::sync::LazyLock::new(|| semver::Version::parse(VERSION).unwrap());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn semver_matches_version() {
+        assert_eq!(SEMVER.to_string(), VERSION);
+    }
+}
@@ -0,0 +1,131 @@
+//! Measures the memory that reading a large list of resources allocates. This test lives in its own binary because it
+//! installs a global allocator.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    io::{self, Write as _},
+};
+
+use launch::test_support::for_each_item;
+
+/// Tracks the bytes that are allocated on each thread, so that tests running in parallel do not interfere.
+struct CountingAllocator;
+
+thread_local! {
+    static LIVE_BYTES: Cell<isize> = const { Cell::new(0) };
+    static PEAK_BYTES: Cell<isize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = LIVE_BYTES.try_with(|live| {
+            live.set(live.get() + layout.size() as isize);
+            let _ = PEAK_BYTES.try_with(|peak| peak.set(peak.get().max(live.get())));
+        });
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _ = LIVE_BYTES.try_with(|live| live.set(live.get() - layout.size() as isize));
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns how many bytes more than before `f` were allocated at the same time while `f` ran on this thread.
+fn peak_allocated_bytes<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = LIVE_BYTES.with(Cell::get);
+    PEAK_BYTES.with(|peak| peak.set(before));
+    let value = f();
+    let peak = PEAK_BYTES.with(Cell::get);
+    (value, (peak - before).max(0) as usize)
+}
+
+/// Produces a pod list like `kubectl get pods --output=json` with `count` Pods without holding it in memory.
+struct PodList {
+    count: usize,
+    next: usize,
+    chunk: Vec<u8>,
+    position: usize,
+    len: usize,
+}
+
+impl PodList {
+    fn new(count: usize) -> Self {
+        let mut list = Self {
+            count,
+            next: 0,
+            chunk: Vec::with_capacity(1024),
+            position: 0,
+            len: 0,
+        };
+        list.chunk
+            .extend_from_slice(br#"{"apiVersion": "v1", "items": ["#);
+        list
+    }
+
+    fn fill(&mut self) {
+        self.chunk.clear();
+        self.position = 0;
+        if self.next < self.count {
+            let separator = if self.next == 0 { "" } else { "," };
+            write!(
+                self.chunk,
+                r#"{separator}{{"metadata": {{"name": "job-{index}-abcde", "namespace": "launch", "creationTimestamp": "2025-01-13T12:00:00Z", "labels": {{"job-name": "job-{index}"}}, "ownerReferences": [{{"kind": "Job", "name": "job-{index}"}}]}}, "spec": {{"nodeName": "node-{node}", "containers": [{{"name": "main", "resources": {{"limits": {{"nvidia.com/gpu": "1", "memory": "16Gi"}}}}}}]}}, "status": {{"phase": "Running", "qosClass": "Burstable"}}}}"#,
+                index = self.next,
+                node = self.next % 64,
+            )
+            .unwrap();
+            self.next += 1;
+        } else if self.next == self.count {
+            self.chunk.extend_from_slice(br#"], "kind": "List"}"#);
+            self.next += 1;
+        }
+    }
+}
+
+impl io::Read for PodList {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.chunk.len() {
+            self.fill();
+        }
+        let n = buf.len().min(self.chunk.len() - self.position);
+        buf[..n].copy_from_slice(&self.chunk[self.position..self.position + n]);
+        self.position += n;
+        self.len += n;
+        Ok(n)
+    }
+}
+
+#[test]
+fn for_each_item_bounds_memory() {
+    const COUNT: usize = 100_000;
+    const MAX_PEAK_BYTES: usize = 1 << 20;
+
+    let mut list = PodList::new(COUNT);
+    let ((result, pods, gpus), peak) = peak_allocated_bytes(|| {
+        let mut pods = 0;
+        let mut gpus = 0;
+        let result = for_each_item(&mut list, |pod: serde_json::Value| {
+            pods += 1;
+            gpus += pod["spec"]["containers"][0]["resources"]["limits"]["nvidia.com/gpu"]
+                .as_str()
+                .unwrap()
+                .parse::<usize>()
+                .unwrap();
+        });
+        (result, pods, gpus)
+    });
+
+    result.unwrap();
+    assert_eq!((pods, gpus), (COUNT, COUNT));
+    assert!(list.len > 30 * MAX_PEAK_BYTES, "{} bytes", list.len);
+    assert!(
+        peak < MAX_PEAK_BYTES,
+        "parsing {} bytes allocated up to {peak} bytes at once",
+        list.len
+    );
+}
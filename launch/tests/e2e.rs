@@ -0,0 +1,233 @@
+//! End-to-end tests against a real cluster. They are ignored by default and skip themselves unless
+//! `LAUNCH_E2E_CONTEXT` names the context to run against:
+//!
+//! ```sh
+//! LAUNCH_E2E_CONTEXT=staging cargo test --test e2e -- --ignored
+//! ```
+//!
+//! Every test submits work under a unique name prefix and deletes whatever was created under that prefix when it ends,
+//! also when it fails.
+
+use std::{
+    path::PathBuf,
+    process::{Command, Output},
+    thread,
+    time::{Duration, Instant},
+};
+
+use clap::ValueEnum as _;
+use launch::cli::ClusterContext;
+
+const CONTEXT_ENV: &str = "LAUNCH_E2E_CONTEXT";
+const NAMESPACE: &str = "launch";
+const POLLING_INTERVAL: Duration = Duration::from_secs(5);
+const TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Returns the context to run against, or `None` after printing why the test is skipped.
+fn context() -> Option<ClusterContext> {
+    let Some(value) = std::env::var(CONTEXT_ENV)
+        .ok()
+        .filter(|value| !value.is_empty())
+    else {
+        eprintln!("Skipping because {CONTEXT_ENV} is not set");
+        return None;
+    };
+    Some(
+        ClusterContext::from_str(&value, true)
+            .unwrap_or_else(|error| panic!("Invalid {CONTEXT_ENV} {value:?}: {error}")),
+    )
+}
+
+/// A name prefix that does not collide with other test runs. Name prefixes are limited to 20 characters.
+fn unique_name_prefix() -> String {
+    format!("e2e-{:08x}", rand::random::<u32>())
+}
+
+/// The example that is submitted, which builds a small image from the Dockerfile in its directory.
+fn example_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../examples/kubernetes")
+}
+
+fn launch(context: ClusterContext, args: &[&str]) -> Output {
+    let context = context.to_possible_value().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_launch"))
+        .arg("--context")
+        .arg(context.get_name())
+        .args(args)
+        .current_dir(example_dir())
+        .output()
+        .expect("failed to run launch");
+    assert!(
+        output.status.success(),
+        "launch {args:?} failed with {}\nstdout:\n{}\nstderr:\n{}",
+        output.status,
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr),
+    );
+    output
+}
+
+fn kubectl(context: ClusterContext) -> Command {
+    let mut command = Command::new("kubectl");
+    command.args([
+        "--kubeconfig=/dev/null",
+        "--server",
+        context
+            .cluster_url()
+            .expect("the end-to-end tests run against a cluster of launch"),
+        "--token=unused",
+        "--namespace",
+        NAMESPACE,
+    ]);
+    command
+}
+
+/// Calls `f` until it returns `Some` or the timeout expires.
+fn poll<T>(what: &str, timeout: Duration, mut f: impl FnMut() -> Option<T>) -> T {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(value) = f() {
+            return value;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "timed out after {timeout:?} waiting for {what}"
+        );
+        thread::sleep(POLLING_INTERVAL);
+    }
+}
+
+/// Deletes the Jobs and RayJobs whose name starts with the prefix when dropped.
+struct Cleanup {
+    context: ClusterContext,
+    name_prefix: String,
+}
+
+impl Drop for Cleanup {
+    fn drop(&mut self) {
+        for kind in ["jobs", "rayjobs"] {
+            let Ok(output) = kubectl(self.context)
+                .args(["get", kind, "--output=name"])
+                .output()
+            else {
+                continue;
+            };
+            let names = String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter(|name| {
+                    name.split_once('/')
+                        .is_some_and(|(_, name)| name.starts_with(&self.name_prefix))
+                })
+                .map(str::to_owned)
+                .collect::<Vec<_>>();
+            if names.is_empty() {
+                continue;
+            }
+            eprintln!("Deleting {}", names.join(", "));
+            let _ = kubectl(self.context)
+                .args(["delete", "--wait=false"])
+                .args(&names)
+                .status();
+        }
+    }
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).expect("stdout should be UTF-8")
+}
+
+/// Returns the row of `launch list --output wide-json` for the resource whose name starts with the prefix.
+fn find_wide_json_row(output: &str, name_prefix: &str) -> Option<serde_json::Value> {
+    let rows: Vec<serde_json::Value> =
+        serde_json::from_str(output).expect("list output should be a JSON array");
+    rows.into_iter().find(|row| {
+        row["name"]
+            .as_str()
+            .is_some_and(|name| name.starts_with(name_prefix))
+    })
+}
+
+/// Asserts that a line of the table starts with a cell whose value starts with the prefix.
+fn assert_table_contains(output: &str, name_prefix: &str) {
+    assert!(
+        output.lines().any(|line| line
+            .trim_start_matches(['│', '|', ' '])
+            .starts_with(name_prefix)),
+        "expected a row for {name_prefix:?} in:\n{output}"
+    );
+}
+
+#[test]
+#[ignore = "requires a cluster, see the module documentation"]
+fn submit_list_watch() {
+    let Some(context) = context() else {
+        return;
+    };
+    let name_prefix = unique_name_prefix();
+    let _cleanup = Cleanup {
+        context,
+        name_prefix: name_prefix.clone(),
+    };
+
+    // Submit follows the logs until the container exits.
+    let output = launch(
+        context,
+        &[
+            "submit",
+            "--name-prefix",
+            &name_prefix,
+            "--allow-dirty",
+            "--allow-unpushed",
+            "--databrickscfg-mode",
+            "omit",
+            "--",
+            "echo",
+            "hello",
+        ],
+    );
+    assert!(
+        stdout(&output).lines().any(|line| line == "hello"),
+        "expected the logs to contain hello:\n{}",
+        stdout(&output)
+    );
+
+    let row = poll("the job to succeed", TIMEOUT, || {
+        let output = launch(context, &["list", "--output", "wide-json"]);
+        let row = find_wide_json_row(stdout(&output), &name_prefix)?;
+        (row["jobStatus"]["succeeded"].as_u64() >= Some(1)).then_some(row)
+    });
+    let name = row["name"].as_str().unwrap().to_owned();
+    assert_eq!(row["pods"].as_array().map(Vec::len), Some(1), "{row}");
+
+    let output = launch(context, &["list"]);
+    assert_table_contains(stdout(&output), &name_prefix);
+
+    // The job has already finished so this returns immediately.
+    launch(context, &["watch", &name]);
+}
+
+#[test]
+fn find_wide_json_row_works() {
+    let output = r#"[
+        {"name": "job-abc", "created": "2024-01-01T00:00:00Z", "pods": []},
+        {"name": "e2e-0000002a-xyz", "created": "2024-01-01T00:00:00Z", "pods": []}
+    ]"#;
+    assert_eq!(
+        find_wide_json_row(output, "e2e-0000002a").unwrap()["name"],
+        "e2e-0000002a-xyz"
+    );
+    assert!(find_wide_json_row(output, "e2e-ffffffff").is_none());
+}
+
+#[test]
+fn assert_table_contains_works() {
+    let output = "\
+╭──────────────────┬─────────╮
+│ job name         ┆ created │
+╞══════════════════╪═════════╡
+│ e2e-0000002a-xyz ┆ 1m ago  │
+╰──────────────────┴─────────╯
+";
+    assert_table_contains(output, "e2e-0000002a");
+    assert!(std::panic::catch_unwind(|| assert_table_contains(output, "e2e-ffffffff")).is_err());
+}
@@ -0,0 +1,73 @@
+//! Runs `launch submit --output json` against a fake kubectl, to check that programs can parse what it prints to stdout.
+#![cfg(unix)]
+
+use std::{fs, os::unix::fs::PermissionsExt as _, path::PathBuf, process::Command};
+
+/// Answers the questions that launch asks kubectl before a client side dry run.
+const FAKE_KUBECTL: &str = r#"#!/bin/sh
+case "$*" in
+    *"version --client --output=json") echo '{"clientVersion": {"major": "1", "minor": "31", "gitVersion": "v1.31.1"}}' ;;
+    *"config current-context") echo kind-launch ;;
+    *) exit 1 ;;
+esac
+"#;
+
+struct TempDir(PathBuf);
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn json_output_is_the_only_thing_on_stdout() {
+    let dir =
+        TempDir(std::env::temp_dir().join(format!("launch-output-json-{}", rand::random::<u32>())));
+    let bin = dir.0.join("bin");
+    fs::create_dir_all(&bin).unwrap();
+    let kubectl = bin.join("kubectl");
+    fs::write(&kubectl, FAKE_KUBECTL).unwrap();
+    fs::set_permissions(&kubectl, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let path = std::env::join_paths(std::iter::once(bin).chain(std::env::split_paths(
+        &std::env::var_os("PATH").unwrap_or_default(),
+    )))
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_launch"))
+        .args([
+            "--context",
+            "local,staging",
+            "submit",
+            "--output",
+            "json",
+            "--dry-run=client",
+            "--image",
+            "docker.io/library/python:3.12",
+            "--databrickscfg-mode",
+            "omit",
+            "--",
+            "python",
+            "train.py",
+        ])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env("PATH", path)
+        // Keeps the configuration and the telemetry of the user out of the test.
+        .env("HOME", &dir.0)
+        .env_remove("XDG_CONFIG_HOME")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        output.status.success(),
+        "stdout:\n{stdout}\nstderr:\n{stderr}"
+    );
+
+    let summary: serde_json::Value = serde_json::from_str(&stdout)
+        .unwrap_or_else(|error| panic!("{error} in stdout:\n{stdout}"));
+    assert_eq!(summary["status"], "success");
+    assert_eq!(summary["succeeded"], 2);
+    // The manifests of the dry run go to stderr instead.
+    assert!(stderr.contains("kind: Job"), "{stderr}");
+}